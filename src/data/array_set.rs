@@ -0,0 +1,141 @@
+use ndarray::{ArrayD, Axis};
+use data::DataSet;
+
+/// A `DataSet` created from in-memory arrays.
+///
+/// Each array supplied becomes a component, with the outermost axis of each array indexing the elements.
+/// e.g. inputs with shape [100, 28, 28, 1] and targets with shape [100, 10] produce a dataset of length 100,
+/// with each element containing a [28, 28, 1] component and a [10] component.
+pub struct ArraySet {
+	arrays: Vec<ArrayD<f32>>,
+	names: Vec<String>,
+}
+
+impl ArraySet {
+	/// Panics if `arrays` is empty, if any array has no axes, or if the outermost axes are not all the same size.
+	pub fn new(arrays: Vec<ArrayD<f32>>) -> Self {
+		assert!(arrays.len() > 0, "ArraySet requires at least one array");
+		assert!(arrays.iter().all(|arr| arr.ndim() > 0), "ArraySet arrays must have an outer axis to index elements");
+		let length = arrays[0].shape()[0];
+		assert!(arrays.iter().all(|arr| arr.shape()[0] == length), "ArraySet arrays must all have the same outer axis size");
+
+		let names = (0..arrays.len()).map(|i| format!("Array{}", i)).collect();
+		ArraySet{
+			arrays,
+			names,
+		}
+	}
+
+	/// Set the names returned by `components()`.
+	///
+	/// Panics if the number of names does not match the number of arrays.
+	pub fn names(mut self, names: &[&str]) -> Self {
+		assert_eq!(names.len(), self.arrays.len(), "The number of names must match the number of arrays");
+		self.names = names.iter().map(|s| s.to_string()).collect();
+		self
+	}
+
+	/// Borrows the wrapped arrays.
+	pub fn arrays(&self) -> &[ArrayD<f32>] {
+		&self.arrays
+	}
+}
+
+impl DataSet for ArraySet {
+	fn get(&mut self, i: usize) -> Vec<ArrayD<f32>>{
+		self.arrays.iter().map(|arr| arr.subview(Axis(0), i).to_owned()).collect()
+	}
+
+	fn length(&self) -> usize{
+		self.arrays[0].shape()[0]
+	}
+
+	fn width(&self) -> usize{
+		self.arrays.len()
+	}
+
+	fn components(&self) -> Vec<String>{
+		self.names.clone()
+	}
+}
+
+
+#[test]
+fn array_set_batch_wrap() {
+	_array_set_batch_wrap()
+}
+
+fn _array_set_batch_wrap() {
+	use data::DataStream;
+
+	let inputs = ArrayD::from_shape_fn(&[100, 3][..], |idx| (idx[0] * 3 + idx[1]) as f32);
+	let targets = ArrayD::from_shape_fn(&[100, 1][..], |idx| idx[0] as f32);
+
+	let set = ArraySet::new(vec![inputs, targets]).names(&["inputs", "targets"]);
+	assert_eq!(set.length(), 100);
+	assert_eq!(set.components(), vec!["inputs".to_string(), "targets".to_string()]);
+
+	let mut stream = set.sequential().batch(32);
+
+	let check = |batch: Vec<ArrayD<f32>>, expected_indices: Vec<usize>| {
+		assert_eq!(batch[0].shape(), &[32, 3]);
+		assert_eq!(batch[1].shape(), &[32, 1]);
+		for (b, &i) in expected_indices.iter().enumerate() {
+			assert_eq!(batch[1][[b, 0]], i as f32);
+			assert_eq!(batch[0][[b, 2]], (i * 3 + 2) as f32);
+		}
+	};
+
+	check(stream.next(), (0..32).collect());
+	check(stream.next(), (32..64).collect());
+	check(stream.next(), (64..96).collect());
+	// wraps around to the start of the dataset
+	check(stream.next(), (96..100).chain(0..28).collect());
+}
+
+#[test]
+fn array_set_epoch_size() {
+	use data::DataStream;
+
+	let set = || ArraySet::new(vec![ArrayD::zeros(&[100, 3][..]), ArrayD::zeros(&[100, 1][..])]);
+
+	assert_eq!(set().sequential().epoch_size(), Some(100));
+	assert_eq!(set().random().epoch_size(), Some(100));
+	assert_eq!(set().shuffle_random().epoch_size(), Some(100));
+	assert_eq!(set().sequential().batch(32).epoch_size(), Some(4));
+	assert_eq!(set().sequential().batch(25).epoch_size(), Some(4));
+	assert_eq!(set().shuffle_random().batch(10).buffered(4).epoch_size(), Some(10));
+}
+
+#[test]
+fn array_set_zip() {
+	use data::DataStream;
+
+	let images = ArrayD::from_shape_fn(&[10, 2, 2][..], |idx| idx[0] as f32);
+	let metadata = ArrayD::from_shape_fn(&[10, 3][..], |idx| (idx[0] * 10 + idx[1]) as f32);
+
+	let mut stream = ArraySet::new(vec![images]).sequential().batch(4)
+		.zip(ArraySet::new(vec![metadata]).sequential().batch(4));
+	assert_eq!(stream.epoch_size(), Some(3));
+
+	for batch_start in &[0, 4, 8] {
+		let batch = stream.next();
+		assert_eq!(batch.len(), 2);
+		assert_eq!(batch[0].shape(), &[4, 2, 2]);
+		assert_eq!(batch[1].shape(), &[4, 3]);
+		for b in 0..4 {
+			let i = (batch_start + b) % 10;
+			assert_eq!(batch[0][[b, 1, 1]], i as f32);
+			assert_eq!(batch[1][[b, 0]], (i * 10) as f32);
+		}
+	}
+}
+
+#[test]
+#[should_panic(expected = "Zipped streams must have the same epoch size")]
+fn array_set_zip_size_mismatch() {
+	use data::DataStream;
+
+	let _stream = ArraySet::new(vec![ArrayD::zeros(&[10, 3][..])]).sequential()
+		.zip(ArraySet::new(vec![ArrayD::zeros(&[12, 3][..])]).sequential());
+}
@@ -0,0 +1,188 @@
+use ndarray::ArrayD;
+use data::DataSet;
+
+use std::collections::{HashMap, BTreeMap};
+
+/// Stores elements of the wrapped dataset in memory, so that expensive loading and decoding is only done once.
+///
+/// Up to `capacity` elements are kept, keyed by index, and the least recently used element is evicted when the cache is full.
+/// Hits and misses are counted so that the cache size can be tuned.
+///
+/// Elements are cloned out of the cache, so any random augmentation should be applied after caching rather than before.
+pub struct Cache<S: DataSet> {
+	set: S,
+	capacity: usize,
+	entries: HashMap<usize, (u64, Vec<ArrayD<f32>>)>,
+	recency: BTreeMap<u64, usize>,
+	clock: u64,
+	hits: usize,
+	misses: usize,
+}
+
+impl<S: DataSet> Cache<S> {
+	pub fn new(set: S, capacity: usize) -> Self {
+		Cache {
+			set,
+			capacity,
+			entries: HashMap::new(),
+			recency: BTreeMap::new(),
+			clock: 0,
+			hits: 0,
+			misses: 0,
+		}
+	}
+
+	/// The maximum number of elements stored.
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	/// The number of elements currently stored.
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	/// Returns true if no elements are currently stored.
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// The number of calls to `get()` which were served from the cache.
+	pub fn hits(&self) -> usize {
+		self.hits
+	}
+
+	/// The number of calls to `get()` which were passed to the wrapped dataset.
+	pub fn misses(&self) -> usize {
+		self.misses
+	}
+
+	/// Resets the hit and miss counts to zero, without clearing the cache.
+	pub fn reset_stats(&mut self) {
+		self.hits = 0;
+		self.misses = 0;
+	}
+
+	/// Removes all stored elements.
+	pub fn clear(&mut self) {
+		self.entries.clear();
+		self.recency.clear();
+	}
+
+	/// Borrows the wrapped dataset.
+	pub fn inner(&self) -> &S {
+		&self.set
+	}
+
+	/// Returns the wrapped dataset.
+	pub fn into_inner(self) -> S {
+		let Self{set, ..} = self;
+		set
+	}
+
+	fn touch(&mut self, i: usize) -> u64 {
+		self.clock += 1;
+		let time = self.clock;
+		self.recency.insert(time, i);
+		time
+	}
+}
+
+impl<S: DataSet> DataSet for Cache<S> {
+	fn get(&mut self, i: usize) -> Vec<ArrayD<f32>> {
+		let last_used = self.entries.get(&i).map(|&(last_used, _)| last_used);
+		if let Some(last_used) = last_used {
+			self.hits += 1;
+			self.recency.remove(&last_used);
+			let time = self.touch(i);
+			let entry = self.entries.get_mut(&i).unwrap();
+			entry.0 = time;
+			return entry.1.clone();
+		}
+
+		self.misses += 1;
+		let data = self.set.get(i);
+		if self.capacity == 0 {
+			return data;
+		}
+
+		if self.entries.len() >= self.capacity {
+			let oldest = self.recency.keys().next().cloned();
+			if let Some(oldest) = oldest {
+				let evicted = self.recency.remove(&oldest).unwrap();
+				self.entries.remove(&evicted);
+			}
+		}
+		let time = self.touch(i);
+		self.entries.insert(i, (time, data.clone()));
+		data
+	}
+
+	fn length(&self) -> usize{
+		self.set.length()
+	}
+
+	fn width(&self) -> usize {
+		self.set.width()
+	}
+
+	fn components(&self) -> Vec<String>{
+		self.set.components()
+	}
+}
+
+
+#[test]
+fn test_cache_second_pass() {
+	_cache_second_pass()
+}
+
+fn _cache_second_pass() {
+	use data::array_set::ArraySet;
+	use std::sync::{Arc, Mutex};
+
+	let values = ArrayD::from_shape_fn(&[6, 2][..], |idx| (idx[0] * 2 + idx[1]) as f32);
+	let loads = Arc::new(Mutex::new(0));
+	let counter = loads.clone();
+	let mut set = ArraySet::new(vec![values])
+		.map_all(move |_, data| {*counter.lock().unwrap() += 1; data}, None)
+		.cache(10);
+
+	let first: Vec<_> = set.iter().collect();
+	assert_eq!((set.hits(), set.misses()), (0, 6));
+
+	let second: Vec<_> = set.iter().collect();
+	assert_eq!((set.hits(), set.misses()), (6, 6));
+	assert_eq!(first, second);
+	assert_eq!(*loads.lock().unwrap(), 6);
+	assert_eq!(set.len(), 6);
+}
+
+#[test]
+fn test_cache_lru_eviction() {
+	_cache_lru_eviction()
+}
+
+fn _cache_lru_eviction() {
+	use data::array_set::ArraySet;
+
+	let values = ArrayD::from_shape_fn(&[4, 1][..], |idx| idx[0] as f32);
+	let mut set = ArraySet::new(vec![values]).cache(2);
+
+	set.get(0);
+	set.get(1);
+	set.get(0); // 1 is now the least recently used
+	set.get(2); // evicts 1
+	assert_eq!((set.hits(), set.misses()), (1, 3));
+	assert_eq!(set.len(), 2);
+
+	set.reset_stats();
+	set.get(0);
+	set.get(2);
+	assert_eq!((set.hits(), set.misses()), (2, 0));
+	assert_eq!(set.get(1)[0].iter().cloned().collect::<Vec<_>>(), vec![1.0]);
+	assert_eq!((set.hits(), set.misses()), (2, 1));
+
+	set.clear();
+	assert!(set.is_empty());
+}
@@ -1,8 +1,9 @@
 use indexmap::IndexMap;
-use rand::{thread_rng, Rng};
+use rand::{Rng, RngCore};
 use ndarray::{ArrayD, IxDyn, SliceOrIndex, SliceInfo, Slice};
 use smallvec::SmallVec;
 use data::DataSet;
+use rng::new_rng;
 
 use std::mem;
 use std::convert::AsRef;
@@ -21,6 +22,7 @@ pub struct Crop<S: DataSet> {
 	set: S,
 	fill: IndexMap<usize, f32>,
 	crops: IndexMap<usize, (Vec<usize>, Cropping)>,
+	rng: Box<RngCore + Send>,
 }
 
 impl<S: DataSet> Crop<S> {
@@ -32,6 +34,7 @@ impl<S: DataSet> Crop<S> {
 			set,
 			fill: indexmap![],
 			crops,
+			rng: Box::new(new_rng()),
 		}
 	}
 
@@ -49,6 +52,14 @@ impl<S: DataSet> Crop<S> {
 		self
 	}
 
+	/// Set the rng used to position `Cropping::Random` crops.
+	///
+	/// Default: `rng::new_rng()`
+	pub fn rng<R: RngCore + 'static + Send>(mut self, rng: R) -> Self {
+		self.rng = Box::new(rng);
+		self
+	}
+
 	/// Borrows the wrapped dataset.
 	pub fn inner(&self) -> &S {
 		&self.set
@@ -68,7 +79,7 @@ impl<S: DataSet> DataSet for Crop<S> {
 		for (&component, &(ref shape, ref cropping)) in self.crops.iter() {
 			let arr = mem::replace(&mut data[component], ArrayD::zeros(IxDyn(&[])));
 			let fill = self.fill.get(&component).cloned().unwrap_or(0.0);
-			mem::replace(&mut data[component], crop(arr, shape, cropping, fill)) ;
+			mem::replace(&mut data[component], crop(arr, shape, cropping, fill, &mut self.rng)) ;
 		}
 
 		data
@@ -88,7 +99,7 @@ impl<S: DataSet> DataSet for Crop<S> {
 }
 
 
-fn crop(arr: ArrayD<f32>, crop_shape: &[usize], cropping: &Cropping, fill: f32) -> ArrayD<f32> {
+fn crop<R: Rng>(arr: ArrayD<f32>, crop_shape: &[usize], cropping: &Cropping, fill: f32, rng: &mut R) -> ArrayD<f32> {
 
 	assert_eq!(crop_shape.len(), arr.ndim());
 
@@ -97,7 +108,7 @@ fn crop(arr: ArrayD<f32>, crop_shape: &[usize], cropping: &Cropping, fill: f32)
 	let mut input_slice_arg: SmallVec<[SliceOrIndex; 6]> = SmallVec::new();
 	let mut output_slice_arg: SmallVec<[SliceOrIndex; 6]> = SmallVec::new();
 	for (&input_width, &output_width) in arr.shape().iter().zip(crop_shape) {
-		let (in_si, out_si) = range(cropping, input_width as isize, output_width as isize);
+		let (in_si, out_si) = range(cropping, input_width as isize, output_width as isize, rng);
 		input_slice_arg.push(in_si.into());
 		output_slice_arg.push(out_si.into());
 	}
@@ -118,7 +129,7 @@ fn crop(arr: ArrayD<f32>, crop_shape: &[usize], cropping: &Cropping, fill: f32)
 
 
 // returns Si for input and output
-fn range(cropping: &Cropping, input_width: isize, output_width: isize) -> (Slice, Slice) {
+fn range<R: Rng>(cropping: &Cropping, input_width: isize, output_width: isize, rng: &mut R) -> (Slice, Slice) {
 	match cropping {
 		&Cropping::Centre{..} => {
 			if input_width < output_width {
@@ -137,12 +148,12 @@ fn range(cropping: &Cropping, input_width: isize, output_width: isize) -> (Slice
 		&Cropping::Random{..} => {
 			if input_width < output_width {
 				let width = input_width;
-				let output_start = thread_rng().gen_range(0, output_width - input_width + 1);
+				let output_start = rng.gen_range(0, output_width - input_width + 1);
 				(Slice::new(0, Some(width), 1),
 				Slice::new(output_start, Some(output_start + width), 1))
 			} else {
 				let width = output_width;
-				let input_start = thread_rng().gen_range(0, input_width - output_width + 1);
+				let input_start = rng.gen_range(0, input_width - output_width + 1);
 				(Slice::new(input_start, Some(input_start + width), 1),
 				Slice::new(0, Some(width), 1))
 			}
@@ -0,0 +1,130 @@
+use ndarray::ArrayD;
+use std::fs::File;
+use std::path::Path;
+use std::io::{BufRead, BufReader, Read, Error, ErrorKind, Result};
+use data::DataSet;
+use data::array_set::ArraySet;
+
+/// A `DataSet` created from a file of comma separated values.
+///
+/// Each row of the file is an element, containing two components: a feature vector and a label vector,
+/// made up of the values from the respective column indices.
+///
+/// Fields are split on commas and trimmed; quoted fields are not supported.
+/// Empty lines are ignored.
+pub struct CsvSet {
+	data: ArraySet,
+}
+
+impl CsvSet {
+	/// Read a csv file, parsing the columns at `feature_columns` and `label_columns` as `f32` values.
+	///
+	/// If `header` is true the first line is skipped.
+	/// Returns an error of kind `InvalidData` if a row is missing a column or contains a non-numeric value in a requested column.
+	pub fn new<P: AsRef<Path>>(path: P, feature_columns: &[usize], label_columns: &[usize], header: bool) -> Result<Self> {
+		let file = File::open(path)?;
+		CsvSet::from_reader(file, feature_columns, label_columns, header)
+	}
+
+	/// As for `new()`, but reading from any source implementing `Read`.
+	pub fn from_reader<R: Read>(reader: R, feature_columns: &[usize], label_columns: &[usize], header: bool) -> Result<Self> {
+		let mut features = vec![];
+		let mut labels = vec![];
+		let mut rows = 0;
+
+		for (line_number, line) in BufReader::new(reader).lines().enumerate().skip(if header {1} else {0}) {
+			let line = line?;
+			if line.trim().is_empty() {
+				continue;
+			}
+			let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+
+			parse_columns(&fields, feature_columns, line_number, &mut features)?;
+			parse_columns(&fields, label_columns, line_number, &mut labels)?;
+			rows += 1;
+		}
+
+		let features = ArrayD::from_shape_vec(&[rows, feature_columns.len()][..], features).expect("feature count must match rows * feature columns");
+		let labels = ArrayD::from_shape_vec(&[rows, label_columns.len()][..], labels).expect("label count must match rows * label columns");
+
+		Ok(CsvSet{
+			data: ArraySet::new(vec![features, labels]).names(&["Features", "Labels"]),
+		})
+	}
+}
+
+fn parse_columns(fields: &[&str], columns: &[usize], line_number: usize, values: &mut Vec<f32>) -> Result<()> {
+	for &column in columns {
+		let field = fields.get(column).ok_or_else(|| Error::new(ErrorKind::InvalidData,
+			format!("Line {} has {} columns, but column {} was requested", line_number + 1, fields.len(), column)))?;
+		let value = field.parse::<f32>().map_err(|_| Error::new(ErrorKind::InvalidData,
+			format!("Line {} column {} could not be parsed as a number: '{}'", line_number + 1, column, field)))?;
+		values.push(value);
+	}
+	Ok(())
+}
+
+impl DataSet for CsvSet {
+	fn get(&mut self, i: usize) -> Vec<ArrayD<f32>>{
+		self.data.get(i)
+	}
+
+	fn length(&self) -> usize{
+		self.data.length()
+	}
+
+	fn width(&self) -> usize{
+		self.data.width()
+	}
+
+	fn components(&self) -> Vec<String> {
+		self.data.components()
+	}
+}
+
+
+#[test]
+fn csv_set_test() {
+	_csv_set_test()
+}
+
+fn _csv_set_test() {
+	use data::DataStream;
+	use std::path::PathBuf;
+
+	let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	d.push("res");
+	d.push("test.csv");
+
+	let mut set = CsvSet::new(d, &[0, 2], &[3], true).unwrap();
+	assert_eq!(set.length(), 4);
+
+	let element = set.get(2);
+	assert_eq!(element[0].as_slice().unwrap(), &[2.5, 0.0]);
+	assert_eq!(element[1].as_slice().unwrap(), &[1.0]);
+
+	let batch = set.sequential().batch(3).next();
+	assert_eq!(batch[0].shape(), &[3, 2]);
+	assert_eq!(batch[1].shape(), &[3, 1]);
+}
+
+#[test]
+fn csv_set_errors() {
+	_csv_set_errors()
+}
+
+fn _csv_set_errors() {
+	let non_numeric = "1.0,2.0\n3.0,abc\n";
+	let err = CsvSet::from_reader(non_numeric.as_bytes(), &[0], &[1], false).err().unwrap();
+	assert_eq!(err.kind(), ErrorKind::InvalidData);
+	assert!(err.to_string().contains("abc"));
+
+	let missing_column = "1.0,2.0\n3.0\n";
+	let err = CsvSet::from_reader(missing_column.as_bytes(), &[0], &[1], false).err().unwrap();
+	assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+	// without skipping the header, the column names fail to parse
+	let header = "x,y\n1.0,2.0\n";
+	assert!(CsvSet::from_reader(header.as_bytes(), &[0], &[1], false).is_err());
+	assert_eq!(CsvSet::from_reader(header.as_bytes(), &[0], &[1], true).unwrap().length(), 1);
+}
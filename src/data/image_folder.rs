@@ -1,7 +1,7 @@
 
 use walkdir::WalkDir;
 use image;
-use image::{GenericImage, DynamicImage, Pixel};
+use image::{GenericImage, DynamicImage, Pixel, FilterType};
 use ndarray::{ArrayD, ArrayViewD, IxDyn};
 use std::path::{PathBuf, Path};
 use data::DataSet;
@@ -22,27 +22,8 @@ impl ImageFolder {
 		print!("Loading paths for {} ... ", root_path.to_string_lossy());
 		stdout().flush().ok();
 
-		let walker = WalkDir::new(root_path).max_depth(if subfolders {usize::MAX} else {1}).into_iter();
-		let mut paths = walker.filter_map(|e| e.ok()).filter_map(|e| {
-			let path = e.path();
-			
-			if path.is_file() {
-				if let Some(extension) = path.extension() {
-					let extension = extension.to_string_lossy().to_lowercase();
-					if ["jpg", "jpeg", "png", "bmp", "tiff"].iter().any(|&ext| ext == extension) {
-						Some(path.to_path_buf())
-					} else {
-						None
-					}
-				} else {
-					None
-				}
-			} else {
-				None
-			}
-		}).collect::<Vec<_>>();
+		let paths = image_paths(root_path, if subfolders {usize::MAX} else {1});
 		println!("loaded {} paths.", paths.len()); //TODO change to info!() logging
-		paths.sort();
 		ImageFolder{
 			paths: paths,
 		}
@@ -77,6 +58,126 @@ impl DataSet for ImageFolder {
 }
 
 
+/// A labelled image dataset, where each subdirectory of the root is a class containing image files: `root/<class>/<image>`.
+///
+/// Each element contains two components, an RGB image of shape `[height, width, 3]` with values in [0, 1],
+/// and a label of shape `[1]` containing the class index.
+/// Classes are indexed in order of their sorted subdirectory names, see `classes()`.
+/// Use `one_hot()` to convert the labels for use with cross entropy losses.
+pub struct ClassImageFolder {
+	paths: Vec<PathBuf>,
+	labels: Vec<usize>,
+	classes: Vec<String>,
+	resize: Option<(usize, usize)>,
+}
+
+impl ClassImageFolder {
+
+	pub fn new<P: AsRef<Path>>(root_path: P) -> ClassImageFolder {
+		let root_path = root_path.as_ref();
+
+		print!("Loading classes for {} ... ", root_path.to_string_lossy());
+		stdout().flush().ok();
+
+		let mut class_paths = WalkDir::new(root_path).min_depth(1).max_depth(1).into_iter()
+			.filter_map(|e| e.ok())
+			.filter(|e| e.path().is_dir())
+			.map(|e| e.path().to_path_buf())
+			.collect::<Vec<_>>();
+		class_paths.sort();
+
+		let mut paths = vec![];
+		let mut labels = vec![];
+		let mut classes = vec![];
+		for (label, class_path) in class_paths.iter().enumerate() {
+			let mut class_images = image_paths(class_path, 1);
+			labels.extend(class_images.iter().map(|_| label));
+			paths.append(&mut class_images);
+			classes.push(class_path.file_name().unwrap().to_string_lossy().into_owned());
+		}
+
+		println!("loaded {} paths in {} classes.", paths.len(), classes.len()); //TODO change to info!() logging
+		ClassImageFolder{
+			paths: paths,
+			labels: labels,
+			classes: classes,
+			resize: None,
+		}
+	}
+
+	/// Resize every image to `[height, width]`, ignoring the original aspect ratio, so that images can be batched.
+	///
+	/// Default: None
+	pub fn resize<R: Into<Option<(usize, usize)>>>(mut self, resize: R) -> Self {
+		self.resize = resize.into();
+		self
+	}
+
+	/// The class names, in order of class index.
+	pub fn classes(&self) -> &[String] {
+		&self.classes
+	}
+}
+
+impl DataSet for ClassImageFolder {
+	fn get(&mut self, i: usize) -> Vec<ArrayD<f32>> {
+
+		let image = match image::open(&self.paths[i]) {
+			Ok(dyn_image) => match self.resize {
+				Some((height, width)) => image_to_data(&dyn_image.resize_exact(width as u32, height as u32, FilterType::Triangle)),
+				None => image_to_data(&dyn_image),
+			},
+			Err(err) => {
+					eprintln!("Image load error '{}' {}", self.paths[i].to_string_lossy(), err);
+					let (height, width) = self.resize.unwrap_or((1, 1));
+					ArrayD::zeros(IxDyn(&[height, width, CHANNELS][..]))
+				},
+		};
+
+		let label = ArrayD::from_elem(IxDyn(&[1]), self.labels[i] as f32);
+
+		vec![image, label]
+	}
+
+	fn length(&self) -> usize {
+		self.paths.len()
+	}
+
+	fn width(&self) -> usize {
+		2
+	}
+
+	fn components(&self) -> Vec<String> {
+		vec!["Images".to_string(), "Labels".to_string()]
+	}
+}
+
+/// Returns the sorted paths of all image files within `max_depth` of `dir`.
+fn image_paths(dir: &Path, max_depth: usize) -> Vec<PathBuf> {
+	let walker = WalkDir::new(dir).max_depth(max_depth).into_iter();
+	let mut paths = walker.filter_map(|e| e.ok()).filter_map(|e| {
+		let path = e.path();
+		
+		if path.is_file() {
+			if let Some(extension) = path.extension() {
+				let extension = extension.to_string_lossy().to_lowercase();
+				if ["jpg", "jpeg", "png", "bmp", "tiff"].iter().any(|&ext| ext == extension) {
+					Some(path.to_path_buf())
+				} else {
+					None
+				}
+			} else {
+				None
+			}
+		} else {
+			None
+		}
+	}).collect::<Vec<_>>();
+	paths.sort();
+	paths
+}
+
+
 
 
 pub fn data_to_image(image_data: ArrayViewD<f32>) -> DynamicImage {
@@ -142,4 +243,39 @@ fn _image_crop_test() {
 		.sequential();
 
 	assert_eq!(&[25, 25, 3], images.next()[0].shape());
-}
\ No newline at end of file
+}
+
+#[test]
+fn class_image_folder_test() {
+	_class_image_folder_test()
+}
+
+fn _class_image_folder_test() {
+	use data::DataStream;
+
+	let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	d.push("res");
+	d.push("image_folder");
+
+	let mut images = ClassImageFolder::new(&d);
+	assert_eq!(images.classes(), &["circle".to_string(), "square".to_string()]);
+	assert_eq!(images.length(), 3);
+
+	let labels: Vec<f32> = (0..3).map(|i| images.get(i)[1][0]).collect();
+	assert_eq!(labels, vec![0.0, 0.0, 1.0]);
+
+	let element = images.get(0);
+	assert_eq!(element[0].shape(), &[6, 4, 3]);
+	assert_eq!(element[1].shape(), &[1]);
+	assert_eq!(images.get(2)[0].shape(), &[3, 5, 3]);
+	assert!(element[0].outer_iter().all(|row| row.outer_iter().all(|pixel| pixel.to_vec() == vec![1.0, 0.0, 0.0])));
+
+	let mut batches = ClassImageFolder::new(&d)
+		.resize((2, 2))
+		.sequential()
+		.batch(3);
+	let batch = batches.next();
+	assert_eq!(batch[0].shape(), &[3, 2, 2, 3]);
+	assert_eq!(batch[1].shape(), &[3, 1]);
+	assert!(batch[0].iter().all(|&x| x >= 0.0 && x <= 1.0));
+}
@@ -0,0 +1,162 @@
+use ndarray::{ArrayD, Axis};
+use data::DataStream;
+use rng::new_rng;
+use rand::{Rng, RngCore};
+use rand::distributions::{Distribution, Gamma};
+
+/// Mixup augmentation for a batched `DataStream`.
+///
+/// For each batch a single `λ ~ Beta(α, α)` is drawn, and element `i` of every component is replaced by `λ x_i + (1 - λ) x_j`,
+/// where `j` is a random permutation of the batch. As all components are blended equally, the targets are blended to match the inputs.
+/// `λ` is replaced with `max(λ, 1 - λ)` so that each element remains dominated by its original, and as `α` approaches 0 the batch is left unmixed.
+///
+/// Every component must have the batch as its outermost axis, as produced by `batch()`.
+pub struct Mixup<S: DataStream> {
+	stream: S,
+	alpha: f32,
+	rng: Box<RngCore + Send>,
+	lambda: Option<f32>,
+}
+
+impl<S: DataStream> Mixup<S> {
+	/// Panics if `alpha` is negative.
+	pub fn new(stream: S, alpha: f32) -> Self {
+		assert!(alpha >= 0.0, "Mixup alpha must not be negative, found: {}", alpha);
+		Mixup {
+			stream,
+			alpha,
+			rng: Box::new(new_rng()),
+			lambda: None,
+		}
+	}
+
+	/// Supply the rng used to draw `λ` and the permutation, e.g. a seeded rng for reproducibility.
+	///
+	/// Default: `rng::new_rng()`
+	pub fn rng<R: RngCore + 'static + Send>(mut self, rng: R) -> Self {
+		self.rng = Box::new(rng);
+		self
+	}
+
+	/// The `λ` used for the most recent batch, or `None` if `next()` has not been called.
+	pub fn lambda(&self) -> Option<f32> {
+		self.lambda
+	}
+
+	/// Borrows the wrapped datastream.
+	pub fn inner(&self) -> &S {
+		&self.stream
+	}
+
+	/// Returns the wrapped datastream.
+	pub fn into_inner(self) -> S {
+		let Self{stream, ..} = self;
+		stream
+	}
+
+	fn sample_lambda(&mut self) -> f32 {
+		if self.alpha == 0.0 {
+			return 1.0;
+		}
+		// Beta(α, α) = X/(X + Y) for X, Y ~ Gamma(α, 1)
+		let gamma = Gamma::new(self.alpha as f64, 1.0);
+		let x = gamma.sample(&mut self.rng);
+		let y = gamma.sample(&mut self.rng);
+		let lambda = if x + y > 0.0 {(x / (x + y)) as f32} else {1.0};
+		lambda.max(1.0 - lambda)
+	}
+}
+
+impl<S: DataStream> DataStream for Mixup<S> {
+	fn epoch_size(&self) -> Option<usize> {
+		self.stream.epoch_size()
+	}
+
+	fn next(&mut self) -> Vec<ArrayD<f32>>{
+		let data = self.stream.next();
+		let batch_size = data.first().map(|arr| arr.shape()[0]).unwrap_or(0);
+		assert!(data.iter().all(|arr| arr.ndim() > 0 && arr.shape()[0] == batch_size), "Mixup requires all components to have the same outer batch axis");
+
+		let lambda = self.sample_lambda();
+		let mut order: Vec<usize> = (0..batch_size).collect();
+		self.rng.shuffle(&mut order);
+		self.lambda = Some(lambda);
+
+		data.into_iter().map(|arr| {
+			let mut mixed = &arr * lambda;
+			for (i, &j) in order.iter().enumerate() {
+				mixed.subview_mut(Axis(0), i).scaled_add(1.0 - lambda, &arr.subview(Axis(0), j));
+			}
+			mixed
+		}).collect()
+	}
+}
+
+
+#[test]
+fn test_mixup_unmixed() {
+	_mixup_unmixed()
+}
+
+fn _mixup_unmixed() {
+	use data::DataSet;
+	use data::array_set::ArraySet;
+	use rand::{Isaac64Rng, SeedableRng};
+
+	let inputs = ArrayD::from_shape_fn(&[16, 3][..], |idx| (idx[0] * 3 + idx[1]) as f32);
+	let targets = ArrayD::from_shape_fn(&[16, 1][..], |idx| idx[0] as f32);
+
+	for &alpha in [0.0, 1e-4].iter() {
+		let mut plain = ArraySet::new(vec![inputs.clone(), targets.clone()]).sequential().batch(8);
+		let mut mixed = ArraySet::new(vec![inputs.clone(), targets.clone()]).sequential().batch(8)
+			.mixup(alpha)
+			.rng(Isaac64Rng::from_seed([13u8; 32]));
+
+		for _ in 0..4 {
+			let expected = plain.next();
+			let actual = mixed.next();
+			assert!(mixed.lambda().unwrap() > 0.999, "alpha: {} lambda: {:?}", alpha, mixed.lambda());
+			for (e, a) in expected.iter().zip(&actual) {
+				assert!(e.all_close(a, 0.05), "alpha: {} expected: {:?} actual: {:?}", alpha, e, a);
+			}
+		}
+	}
+}
+
+#[test]
+fn test_mixup_blend() {
+	_mixup_blend()
+}
+
+fn _mixup_blend() {
+	use data::DataSet;
+	use data::array_set::ArraySet;
+	use rand::{Isaac64Rng, SeedableRng};
+
+	// each input is the class index, so a blended input is the expectation of the blended target
+	let classes = ArrayD::from_shape_fn(&[32, 1][..], |idx| (idx[0] % 5) as f32);
+	let mut stream = ArraySet::new(vec![classes.clone(), classes]).one_hot(1, 5).sequential().batch(16)
+		.mixup(1.0)
+		.rng(Isaac64Rng::from_seed([17u8; 32]));
+
+	let mut mixed_batches = 0;
+	for _ in 0..10 {
+		let batch = stream.next();
+		let lambda = stream.lambda().unwrap();
+		assert!(lambda >= 0.5 && lambda <= 1.0, "{}", lambda);
+		if lambda < 0.99 {
+			mixed_batches += 1;
+		}
+
+		for b in 0..16 {
+			let target = batch[1].subview(Axis(0), b);
+			let sum: f32 = target.iter().sum();
+			assert!((sum - 1.0).abs() < 1e-5, "{:?}", target);
+			assert!(target.iter().all(|&t| t >= 0.0));
+
+			let expected: f32 = target.iter().enumerate().map(|(k, &t)| k as f32 * t).sum();
+			assert!((batch[0][[b, 0]] - expected).abs() < 1e-4, "{} {}", batch[0][[b, 0]], expected);
+		}
+	}
+	assert!(mixed_batches > 0);
+}
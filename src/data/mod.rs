@@ -2,10 +2,25 @@ pub mod mnist;
 pub mod cifar;
 pub mod image_folder;
 pub mod crop;
+pub mod array_set;
+pub mod csv;
+pub mod one_hot;
+pub mod normalize;
+pub mod synthetic;
+pub mod replay_buffer;
+pub mod mixup;
+pub mod cache;
 
 pub use data::crop::{Crop, Cropping};
-
-use rand::{thread_rng, Isaac64Rng, Rng, RngCore, SeedableRng};
+pub use data::one_hot::OneHot;
+pub use data::normalize::Normalize;
+pub use data::replay_buffer::ReplayBuffer;
+pub use data::mixup::Mixup;
+pub use data::cache::Cache;
+
+use rand::{Rng, RngCore};
+use rng::new_rng;
+use graph::Result;
 use ndarray::{ArrayD, IxDyn, Axis};
 use smallvec::SmallVec;
 
@@ -70,6 +85,22 @@ pub trait DataSet {
 		Crop::new(self, component, shape, cropping)
 	}
 
+	fn one_hot(self, component: usize, num_classes: usize) -> OneHot<Self> where Self: Sized {
+		OneHot::new(self, component, num_classes)
+	}
+
+	fn normalize(self, component: usize, mean: ArrayD<f32>, std: ArrayD<f32>) -> Normalize<Self> where Self: Sized {
+		Normalize::new(self, component, mean, std)
+	}
+
+	fn normalize_fit(self, component: usize) -> Normalize<Self> where Self: Sized {
+		Normalize::fit(self, component)
+	}
+
+	fn cache(self, capacity: usize) -> Cache<Self> where Self: Sized {
+		Cache::new(self, capacity)
+	}
+
 	fn sequential(self) -> Sequential<Self> where Self: Sized {
 		Sequential::new(self)
 	}
@@ -499,6 +530,10 @@ impl<S: DataSet> Sequential<S> {
 }
 
 impl<S: DataSet> DataStream for Sequential<S> {
+	fn epoch_size(&self) -> Option<usize> {
+		Some(self.set.length())
+	}
+
 	fn next(&mut self) -> Vec<ArrayD<f32>>{
 		let out = self.set.get(self.next_i);
 		self.next_i = (self.next_i + 1)%self.set.length();
@@ -515,7 +550,7 @@ impl<S: DataSet> Random<S> {
 	pub fn new(set: S) -> Self {
 		Random{
 			set: set,
-			rng: Box::new(Isaac64Rng::from_rng(thread_rng()).unwrap()),
+			rng: Box::new(new_rng()),
 		}
 	}
 
@@ -537,9 +572,14 @@ impl<S: DataSet> Random<S> {
 }
 
 impl<S: DataSet> DataStream for Random<S> {
+	fn epoch_size(&self) -> Option<usize> {
+		Some(self.set.length())
+	}
+
 	fn next(&mut self) -> Vec<ArrayD<f32>>{
 		let set_len = self.set.length();
-		self.set.get(thread_rng().gen_range(0, set_len))
+		let i = self.rng.gen_range(0, set_len);
+		self.set.get(i)
 	}
 }
 
@@ -555,7 +595,7 @@ impl<S: DataSet> ShuffleRandom<S> {
 		let set_len = set.length();
 		ShuffleRandom{
 			set: set,
-			rng: Box::new(Isaac64Rng::from_rng(thread_rng()).unwrap()),
+			rng: Box::new(new_rng()),
 			order: (0..set_len).collect(),
 			next_i: set_len,
 		}
@@ -579,6 +619,10 @@ impl<S: DataSet> ShuffleRandom<S> {
 }
 
 impl<S: DataSet> DataStream for ShuffleRandom<S> {
+	fn epoch_size(&self) -> Option<usize> {
+		Some(self.set.length())
+	}
+
 	fn next(&mut self) -> Vec<ArrayD<f32>>{
 		if self.next_i >= self.order.len() {
 			self.rng.shuffle(&mut self.order);
@@ -596,6 +640,13 @@ impl<S: DataSet> DataStream for ShuffleRandom<S> {
 pub trait DataStream {
 	fn next(&mut self) -> Vec<ArrayD<f32>>;
 
+	/// Returns the number of calls to `next()` which make up one pass over the underlying data, if known.
+	///
+	/// Streams which draw from a `DataSet` return its length, adjusted for batching. Default: `None`
+	fn epoch_size(&self) -> Option<usize> {
+		None
+	}
+
 	fn boxed(self) -> Box<Self> where Self: Sized {
 		Box::new(self)
 	}
@@ -619,18 +670,24 @@ pub trait DataStream {
 	fn count<S: DataStream>(self) -> Count<Self> where Self: Sized {
 		Count::new(self)
 	}
+
+	fn mixup(self, alpha: f32) -> Mixup<Self> where Self: Sized {
+		Mixup::new(self, alpha)
+	}
 }
 
 /// Augment a stream with a fixed sized buffer fed by a new thread.
 pub struct Buffered<S: DataStream + Send + 'static> {
 	stream: Arc<Mutex<S>>,
 	rx: Receiver<Vec<ArrayD<f32>>>,
+	epoch_size: Option<usize>,
 }
 
 impl<S: DataStream + Send + 'static> Buffered<S> {
 	pub fn new(stream: S, buffer_size: usize) -> Self {
 		let (tx, rx) = sync_channel(buffer_size);
 
+		let epoch_size = stream.epoch_size();
 		let stream = Arc::new(Mutex::new(stream));
 		let stream_clone = stream.clone();
 
@@ -658,6 +715,7 @@ impl<S: DataStream + Send + 'static> Buffered<S> {
 		Buffered{
 			stream: stream,
 			rx: rx,
+			epoch_size: epoch_size,
 		}
 	}
 
@@ -684,6 +742,10 @@ impl<S: DataStream + Send + 'static> Buffered<S> {
 }
 
 impl<S: DataStream + Send + 'static> DataStream for Buffered<S> {
+	fn epoch_size(&self) -> Option<usize> {
+		self.epoch_size
+	}
+
 	fn next(&mut self) -> Vec<ArrayD<f32>>{
 		self.rx.recv().expect("Buffer internal thread has died")
 	}
@@ -691,17 +753,31 @@ impl<S: DataStream + Send + 'static> DataStream for Buffered<S> {
 
 
 /// Concatenates the components of two `DataStream`s
+///
+/// Each call to `next()` draws one element (or batch) from each stream, so that they remain aligned.
 pub struct Zip<S1: DataStream, S2: DataStream> {
 	stream1: S1,
 	stream2: S2,
 }
 
 impl<S1: DataStream, S2: DataStream> Zip<S1, S2> {
+	/// Panics if both streams report an `epoch_size()` and they differ, see `try_new()`.
 	pub fn new(stream1: S1, stream2: S2) -> Self {
-		Zip{
+		match Zip::try_new(stream1, stream2) {
+			Ok(zip) => zip,
+			Err(err) => panic!("{}", err),
+		}
+	}
+
+	/// Returns an error if both streams report an `epoch_size()` and they differ.
+	pub fn try_new(stream1: S1, stream2: S2) -> Result<Self> {
+		if let (Some(size1), Some(size2)) = (stream1.epoch_size(), stream2.epoch_size()) {
+			ensure!(size1 == size2, "Zipped streams must have the same epoch size, found {} and {}", size1, size2);
+		}
+		Ok(Zip{
 			stream1,
 			stream2,
-		}
+		})
 	}
 
 	/// Borrows the first wrapped datastream
@@ -722,6 +798,11 @@ impl<S1: DataStream, S2: DataStream> Zip<S1, S2> {
 }
 
 impl<S1: DataStream, S2: DataStream> DataStream for Zip<S1, S2> {
+	/// The epoch size of either stream, if known.
+	fn epoch_size(&self) -> Option<usize> {
+		self.stream1.epoch_size().or(self.stream2.epoch_size())
+	}
+
 	fn next(&mut self) -> Vec<ArrayD<f32>>{
 		let mut data = self.stream1.next();
 		data.append(&mut self.stream2.next());
@@ -762,6 +843,11 @@ impl Interleave {
 }
 
 impl DataStream for Interleave {
+	/// The sum of the epoch sizes of the streams, if all are known.
+	fn epoch_size(&self) -> Option<usize> {
+		self.streams.iter().fold(Some(0), |total, stream| total.and_then(|total| stream.epoch_size().map(|size| total + size)))
+	}
+
 	fn next(&mut self) -> Vec<ArrayD<f32>>{
 		let data = self.streams[self.next].next();
 		self.next = (self.next + 1) % self.streams.len();
@@ -802,6 +888,10 @@ impl<S: DataStream> Count<S> {
 }
 
 impl<S: DataStream> DataStream for Count<S> {
+	fn epoch_size(&self) -> Option<usize> {
+		self.stream.epoch_size()
+	}
+
 	fn next(&mut self) -> Vec<ArrayD<f32>>{
 		self.count += 1;
 		self.stream.next()
@@ -839,6 +929,11 @@ impl<S: DataStream> Batch<S> {
 }
 
 impl<S: DataStream> DataStream for Batch<S> {
+	/// The number of batches required to draw every element of the wrapped stream's epoch at least once.
+	fn epoch_size(&self) -> Option<usize> {
+		self.stream.epoch_size().map(|size| (size + self.batch_size - 1) / self.batch_size)
+	}
+
 	fn next(&mut self) -> Vec<ArrayD<f32>>{
 
 		let mut batch_data: Vec<_> = self.stream.next().into_iter().map(|arr|{
@@ -862,4 +957,35 @@ impl<S: DataStream> DataStream for Batch<S> {
 
 		batch_data
 	}
-}
\ No newline at end of file
+}
+
+
+#[test]
+fn test_zip_epoch_size(){
+	_test_zip_epoch_size().unwrap();
+}
+
+fn _test_zip_epoch_size() -> Result<()>{
+	struct SizedStream(Option<usize>);
+	impl DataStream for SizedStream {
+		fn next(&mut self) -> Vec<ArrayD<f32>>{
+			vec![ArrayD::zeros(&[1][..])]
+		}
+
+		fn epoch_size(&self) -> Option<usize> {
+			self.0
+		}
+	}
+
+	let mut zip = Zip::try_new(SizedStream(Some(3)), SizedStream(Some(3)))?;
+	assert_eq!(zip.epoch_size(), Some(3));
+	assert_eq!(zip.next().len(), 2);
+
+	// an unknown epoch size is taken from the other stream
+	let zip = Zip::try_new(SizedStream(None), SizedStream(Some(4)))?;
+	assert_eq!(zip.epoch_size(), Some(4));
+
+	assert!(Zip::try_new(SizedStream(Some(3)), SizedStream(Some(4))).is_err());
+
+	Ok(())
+}
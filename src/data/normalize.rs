@@ -0,0 +1,161 @@
+use ndarray::ArrayD;
+use data::DataSet;
+
+/// For one component in each element of the dataset: standardise values by applying `(x - mean)/std`.
+///
+/// The statistics can either be supplied, or fitted with a pass over the whole dataset using `fit()`.
+/// Supplied statistics are broadcast to the component shape, so a `[C]` mean can be used for `[H, W, C]` images.
+/// Fitted statistics have the full component shape, and are calculated separately for each position.
+///
+/// The statistics are available from `mean()` and `std()` so that the same transform can be applied at inference.
+pub struct Normalize<S: DataSet> {
+	set: S,
+	component: usize,
+	mean: ArrayD<f32>,
+	std: ArrayD<f32>,
+}
+
+impl<S: DataSet> Normalize<S> {
+	/// Panics if any element of `std` is not greater than zero.
+	pub fn new(set: S, component: usize, mean: ArrayD<f32>, std: ArrayD<f32>) -> Self {
+		assert!(std.iter().all(|&s| s > 0.0), "Normalize std must be greater than zero, found: {:?}", std);
+		Normalize {
+			set,
+			component,
+			mean,
+			std,
+		}
+	}
+
+	/// Calculates the mean and population standard deviation of the component at each position, over all elements of the dataset.
+	///
+	/// Positions with zero variance get a standard deviation of 1, so that they are only shifted.
+	/// Panics if the dataset is empty or the component shape varies between elements.
+	pub fn fit(mut set: S, component: usize) -> Self {
+		let n = set.length();
+		assert!(n > 0, "Normalize can not fit statistics to an empty dataset");
+
+		let mut sums: Option<(ArrayD<f64>, ArrayD<f64>)> = None;
+		for i in 0..n {
+			let x = set.get(i).swap_remove(component).mapv(|x| x as f64);
+			let x_sqr = &x * &x;
+			sums = Some(match sums {
+				Some((sum, sum_sqr)) => {
+					assert_eq!(sum.shape(), x.shape(), "Normalize component shape of element {} did not match previous elements", i);
+					(sum + &x, sum_sqr + &x_sqr)
+				},
+				None => (x, x_sqr),
+			});
+		}
+		let (sum, sum_sqr) = sums.unwrap();
+
+		let mean = sum / n as f64;
+		let var = sum_sqr / n as f64 - &(&mean * &mean);
+		let std = var.mapv(|v| if v > 0.0 {v.sqrt() as f32} else {1.0});
+
+		Normalize::new(set, component, mean.mapv(|m| m as f32), std)
+	}
+
+	/// The mean subtracted from the component.
+	pub fn mean(&self) -> &ArrayD<f32> {
+		&self.mean
+	}
+
+	/// The standard deviation which the component is divided by, after the mean is subtracted.
+	pub fn std(&self) -> &ArrayD<f32> {
+		&self.std
+	}
+
+	/// Borrows the wrapped dataset.
+	pub fn inner(&self) -> &S {
+		&self.set
+	}
+
+	/// Returns the wrapped dataset.
+	pub fn into_inner(self) -> S {
+		let Self{set, ..} = self;
+		set
+	}
+}
+
+impl<S: DataSet> DataSet for Normalize<S> {
+	fn get(&mut self, i: usize) -> Vec<ArrayD<f32>> {
+		let mut data = self.set.get(i);
+		data[self.component] -= &self.mean;
+		data[self.component] /= &self.std;
+		data
+	}
+
+	fn length(&self) -> usize{
+		self.set.length()
+	}
+
+	fn width(&self) -> usize {
+		self.set.width()
+	}
+
+	fn components(&self) -> Vec<String>{
+		self.set.components()
+	}
+}
+
+
+#[test]
+fn test_normalize_fit() {
+	_normalize_fit()
+}
+
+fn _normalize_fit() {
+	use data::DataStream;
+	use data::array_set::ArraySet;
+	use ndarray::{Axis, IxDyn};
+
+	let features = ArrayD::from_shape_vec(IxDyn(&[4, 3]), vec![
+		1.0, 10.0, -3.0,
+		2.0, 30.0, -3.0,
+		3.0, 50.0, -3.0,
+		6.0, 70.0, -3.0,
+	]).unwrap();
+	let set = Normalize::fit(ArraySet::new(vec![features]), 0);
+
+	assert!((set.mean()[0] - 3.0).abs() < 1e-5);
+	assert!((set.mean()[1] - 40.0).abs() < 1e-5);
+	assert!((set.std()[1] - 500.0f32.sqrt()).abs() < 1e-4);
+	assert_eq!(set.std()[2], 1.0);
+
+	let mut stream = set.sequential().batch(4);
+	let batch = stream.next().swap_remove(0);
+
+	let mean = batch.mean_axis(Axis(0));
+	let var = (&batch * &batch).mean_axis(Axis(0)) - &(&mean * &mean);
+	for i in 0..2 {
+		assert!(mean[i].abs() < 1e-5, "{:?}", mean);
+		assert!((var[i] - 1.0).abs() < 1e-4, "{:?}", var);
+	}
+	// a constant feature is only shifted
+	assert!(batch.subview(Axis(1), 2).iter().all(|&x| x == 0.0));
+}
+
+#[test]
+fn test_normalize_broadcast() {
+	_normalize_broadcast()
+}
+
+fn _normalize_broadcast() {
+	use data::array_set::ArraySet;
+	use ndarray::IxDyn;
+
+	let images = ArrayD::from_shape_vec(IxDyn(&[1, 2, 2]), vec![
+		1.0, 4.0,
+		3.0, 8.0,
+	]).unwrap();
+	let mean = ArrayD::from_shape_vec(IxDyn(&[2]), vec![1.0, 2.0]).unwrap();
+	let std = ArrayD::from_shape_vec(IxDyn(&[2]), vec![2.0, 3.0]).unwrap();
+	let mut set = ArraySet::new(vec![images]).normalize(0, mean, std);
+
+	let expected = ArrayD::from_shape_vec(IxDyn(&[2, 2]), vec![
+		0.0, 2.0/3.0,
+		1.0, 2.0,
+	]).unwrap();
+	assert_eq!(set.get(0)[0], expected);
+}
@@ -0,0 +1,101 @@
+use ndarray::{ArrayD, IxDyn};
+use data::DataSet;
+
+use std::mem;
+
+/// For one component in each element of the dataset: replace an integer class label with a one-hot encoding.
+///
+/// The label component must contain a single value, e.g. shape `[]` or `[1]`, which is replaced by an array of shape `[num_classes]`.
+/// When batched this produces `[n, num_classes]` targets suitable for use with cross entropy losses.
+///
+/// Panics if a label is not an integer in the range `[0, num_classes)`.
+pub struct OneHot<S: DataSet> {
+	set: S,
+	component: usize,
+	num_classes: usize,
+}
+
+impl<S: DataSet> OneHot<S> {
+	pub fn new(set: S, component: usize, num_classes: usize) -> Self {
+		OneHot {
+			set,
+			component,
+			num_classes,
+		}
+	}
+
+	/// Borrows the wrapped dataset.
+	pub fn inner(&self) -> &S {
+		&self.set
+	}
+
+	/// Returns the wrapped dataset.
+	pub fn into_inner(self) -> S {
+		let Self{set, ..} = self;
+		set
+	}
+}
+
+impl<S: DataSet> DataSet for OneHot<S> {
+	fn get(&mut self, i: usize) -> Vec<ArrayD<f32>> {
+		let mut data = self.set.get(i);
+		let encoded = one_hot(&data[self.component], self.num_classes, i);
+		mem::replace(&mut data[self.component], encoded);
+		data
+	}
+
+	fn length(&self) -> usize{
+		self.set.length()
+	}
+
+	fn width(&self) -> usize {
+		self.set.width()
+	}
+
+	fn components(&self) -> Vec<String>{
+		self.set.components()
+	}
+}
+
+fn one_hot(label: &ArrayD<f32>, num_classes: usize, element: usize) -> ArrayD<f32> {
+	assert_eq!(label.len(), 1, "OneHot label component of element {} must contain a single value, found shape: {:?}", element, label.shape());
+	let value = *label.iter().next().unwrap();
+	assert!(value >= 0.0 && value < num_classes as f32 && value.fract() == 0.0,
+		"OneHot label {} of element {} is not an integer in the range [0, {})", value, element, num_classes);
+
+	let mut arr = ArrayD::zeros(IxDyn(&[num_classes]));
+	arr[value as usize] = 1.0;
+	arr
+}
+
+
+#[test]
+fn test_one_hot() {
+	_one_hot()
+}
+
+fn _one_hot() {
+	use data::DataStream;
+	use data::array_set::ArraySet;
+
+	let labels = ArrayD::from_shape_vec(IxDyn(&[3]), vec![0.0, 2.0, 1.0]).unwrap();
+	let mut stream = ArraySet::new(vec![labels]).one_hot(0, 3).sequential().batch(3);
+
+	let batch = stream.next();
+	let expected = ArrayD::from_shape_vec(IxDyn(&[3, 3]), vec![
+		1.0, 0.0, 0.0,
+		0.0, 0.0, 1.0,
+		0.0, 1.0, 0.0,
+	]).unwrap();
+	assert_eq!(batch[0], expected);
+}
+
+#[test]
+#[should_panic]
+fn test_one_hot_out_of_range() {
+	use data::array_set::ArraySet;
+
+	let labels = ArrayD::from_shape_vec(IxDyn(&[2]), vec![0.0, 3.0]).unwrap();
+	let mut set = ArraySet::new(vec![labels]).one_hot(0, 3);
+	set.get(1);
+}
@@ -0,0 +1,167 @@
+use ndarray::{ArrayD, Axis, IxDyn};
+use data::{DataSet, DataStream};
+use rng::new_rng;
+use rand::{Rng, RngCore};
+use smallvec::SmallVec;
+use std::iter;
+
+/// A fixed capacity experience replay buffer, e.g. for value-function approximation in reinforcement learning.
+///
+/// Elements (e.g. `vec![input, target]`) are added with `push()`, and once the buffer is full each new element overwrites the oldest.
+/// As a `DataStream` each call to `next()` samples a stored element uniformly at random, with replacement,
+/// and `sample(n)` returns a batch of `n` such samples.
+/// As a `DataSet` the stored elements are indexed from oldest to newest.
+pub struct ReplayBuffer {
+	capacity: usize,
+	elements: Vec<Vec<ArrayD<f32>>>,
+	oldest: usize,
+	names: Vec<String>,
+	rng: Box<RngCore + Send>,
+}
+
+impl ReplayBuffer {
+	/// Creates an empty buffer which holds at most `capacity` elements, each with `width` components.
+	///
+	/// Panics if `capacity` is zero.
+	pub fn new(capacity: usize, width: usize) -> Self {
+		assert!(capacity > 0, "ReplayBuffer capacity must be greater than zero");
+		ReplayBuffer {
+			capacity,
+			elements: Vec::with_capacity(capacity),
+			oldest: 0,
+			names: (0..width).map(|i| format!("Component{}", i)).collect(),
+			rng: Box::new(new_rng()),
+		}
+	}
+
+	/// Set the names returned by `components()`.
+	///
+	/// Panics if the number of names does not match the width of the buffer.
+	pub fn names(mut self, names: &[&str]) -> Self {
+		assert_eq!(names.len(), self.names.len(), "The number of names must match the width of the ReplayBuffer");
+		self.names = names.iter().map(|s| s.to_string()).collect();
+		self
+	}
+
+	/// Supply the rng used for sampling, e.g. a seeded rng for reproducibility.
+	///
+	/// Default: `rng::new_rng()`
+	pub fn rng<R: RngCore + 'static + Send>(mut self, rng: R) -> Self {
+		self.rng = Box::new(rng);
+		self
+	}
+
+	/// The maximum number of elements stored.
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	/// Adds an element to the buffer, overwriting the oldest element if the buffer is full.
+	///
+	/// Panics if the number of components does not match the width of the buffer.
+	pub fn push(&mut self, element: Vec<ArrayD<f32>>) {
+		assert_eq!(element.len(), self.names.len(), "ReplayBuffer element has {} components, but the buffer has width {}", element.len(), self.names.len());
+		if self.elements.len() < self.capacity {
+			self.elements.push(element);
+		} else {
+			self.elements[self.oldest] = element;
+			self.oldest = (self.oldest + 1) % self.capacity;
+		}
+	}
+
+	/// Removes all stored elements.
+	pub fn clear(&mut self) {
+		self.elements.clear();
+		self.oldest = 0;
+	}
+
+	/// Returns a batch of `n` elements sampled uniformly at random, with replacement, with each component stacked along a new outer axis.
+	///
+	/// Panics if the buffer is empty, or if the shapes of a component differ between the sampled elements.
+	pub fn sample(&mut self, n: usize) -> Vec<ArrayD<f32>> {
+		assert!(n > 0, "ReplayBuffer can not sample a batch of zero elements");
+		let indices: Vec<usize> = (0..n).map(|_| self.sample_index()).collect();
+
+		(0..self.names.len()).map(|c| {
+			let first = &self.elements[indices[0]][c];
+			let batch_shape: SmallVec<[usize; 6]> = iter::once(n).chain(first.shape().iter().cloned()).collect();
+			let mut batch_arr = ArrayD::zeros(IxDyn(&batch_shape));
+			for (b, &i) in indices.iter().enumerate() {
+				let arr = &self.elements[i][c];
+				assert_eq!(arr.shape(), first.shape(), "Cannot batch arrays of different shapes.");
+				batch_arr.subview_mut(Axis(0), b).assign(arr);
+			}
+			batch_arr
+		}).collect()
+	}
+
+	fn sample_index(&mut self) -> usize {
+		assert!(self.elements.len() > 0, "ReplayBuffer can not be sampled before any elements are pushed");
+		self.rng.gen_range(0, self.elements.len())
+	}
+}
+
+impl DataSet for ReplayBuffer {
+	fn get(&mut self, i: usize) -> Vec<ArrayD<f32>> {
+		assert!(i < self.elements.len(), "Index {} is out of range for ReplayBuffer holding {} elements", i, self.elements.len());
+		self.elements[(self.oldest + i) % self.elements.len()].clone()
+	}
+
+	fn length(&self) -> usize {
+		self.elements.len()
+	}
+
+	fn width(&self) -> usize {
+		self.names.len()
+	}
+
+	fn components(&self) -> Vec<String> {
+		self.names.clone()
+	}
+}
+
+impl DataStream for ReplayBuffer {
+	fn next(&mut self) -> Vec<ArrayD<f32>> {
+		let i = self.sample_index();
+		self.elements[i].clone()
+	}
+}
+
+
+#[test]
+fn test_replay_buffer() {
+	_replay_buffer()
+}
+
+fn _replay_buffer() {
+	use rand::{Isaac64Rng, SeedableRng};
+
+	let element = |i: usize| vec![ArrayD::from_elem(IxDyn(&[2]), i as f32), ArrayD::from_elem(IxDyn(&[1]), -(i as f32))];
+
+	let mut buffer = ReplayBuffer::new(5, 2).names(&["input", "target"]).rng(Isaac64Rng::from_seed([11u8; 32]));
+	for i in 0..12 {
+		buffer.push(element(i));
+	}
+	assert_eq!(buffer.length(), 5);
+
+	// oldest to newest
+	let stored: Vec<f32> = (0..5).map(|i| buffer.get(i)[1][0]).collect();
+	assert_eq!(stored, vec![-7.0, -8.0, -9.0, -10.0, -11.0]);
+
+	// only the most recent `capacity` elements can be sampled
+	let batch = buffer.sample(200);
+	assert_eq!(batch[0].shape(), &[200, 2]);
+	assert_eq!(batch[1].shape(), &[200, 1]);
+	for b in 0..200 {
+		assert!(batch[0][[b, 0]] >= 7.0 && batch[0][[b, 0]] < 12.0);
+		assert_eq!(batch[0][[b, 1]], -batch[1][[b, 0]]);
+	}
+
+	// sampling is reproducible under a seed
+	let mut buffer2 = ReplayBuffer::new(5, 2).rng(Isaac64Rng::from_seed([11u8; 32]));
+	for i in 0..12 {
+		buffer2.push(element(i));
+	}
+	assert_eq!(buffer2.sample(200), batch);
+	assert_eq!(buffer.next(), buffer2.next());
+}
@@ -0,0 +1,125 @@
+use ndarray::{ArrayD, Ix1, Ix2};
+use data::DataStream;
+use rng::new_rng;
+use rand::RngCore;
+use rand::distributions::{Distribution, Normal};
+
+/// A `DataStream` of synthetic regression data, for tests and tutorials.
+///
+/// Each element has two components, `x` drawn from N(0, 1) with shape [k], and `y = x·W + b + noise` with shape [n],
+/// where `W` has shape [k, n], `b` has shape [n], and the noise is drawn from N(0, σ²).
+/// The stream is infinite, use `batch()` to produce batches.
+pub struct SyntheticLinear {
+	weights: ArrayD<f32>,
+	bias: ArrayD<f32>,
+	noise_std: f32,
+	rng: Box<RngCore + Send>,
+}
+
+impl SyntheticLinear {
+	/// Panics if `weights` is not two dimensional, or if `bias` does not match the number of columns of `weights`.
+	pub fn new(weights: ArrayD<f32>, bias: ArrayD<f32>) -> Self {
+		assert_eq!(weights.ndim(), 2, "SyntheticLinear weights must have shape [k, n]");
+		assert_eq!(bias.shape(), &weights.shape()[1..], "SyntheticLinear bias must have shape [n] to match weights of shape [k, n]");
+		SyntheticLinear {
+			weights,
+			bias,
+			noise_std: 0.0,
+			rng: Box::new(new_rng()),
+		}
+	}
+
+	/// The standard deviation, σ, of the gaussian noise added to `y`.
+	///
+	/// Default: 0.0
+	pub fn noise_std(mut self, noise_std: f32) -> Self {
+		self.noise_std = noise_std;
+		self
+	}
+
+	/// Supply the rng used to generate `x` and the noise, e.g. a seeded rng for reproducibility.
+	///
+	/// Default: `rng::new_rng()`
+	pub fn rng<R: RngCore + 'static + Send>(mut self, rng: R) -> Self {
+		self.rng = Box::new(rng);
+		self
+	}
+
+	/// The weights, `W`, used to generate the data.
+	pub fn weights(&self) -> &ArrayD<f32> {
+		&self.weights
+	}
+
+	/// The bias, `b`, used to generate the data.
+	pub fn bias(&self) -> &ArrayD<f32> {
+		&self.bias
+	}
+}
+
+impl DataStream for SyntheticLinear {
+	fn next(&mut self) -> Vec<ArrayD<f32>>{
+		let k = self.weights.shape()[0];
+		let normal = Normal::new(0.0, 1.0);
+		let noise = Normal::new(0.0, self.noise_std as f64);
+		let rng = &mut self.rng;
+
+		let x = ArrayD::from_shape_fn(&[k][..], |_| normal.sample(&mut *rng) as f32);
+
+		let mut y = x.view().into_dimensionality::<Ix1>().unwrap()
+			.dot(&self.weights.view().into_dimensionality::<Ix2>().unwrap())
+			.into_dyn();
+		y += &self.bias;
+		if self.noise_std != 0.0 {
+			y.mapv_inplace(|y| y + noise.sample(&mut *rng) as f32);
+		}
+
+		vec![x, y]
+	}
+}
+
+
+#[test]
+fn test_synthetic_linear_recovery(){
+	_test_synthetic_linear_recovery().unwrap();
+}
+
+fn _test_synthetic_linear_recovery() -> ::graph::Result<()>{
+	use graph::GraphDef;
+	use ops::nn::linear::Linear;
+	use ops::nn::bias::Bias;
+	use ops::loss::mse::Mse;
+	use opt::Opt;
+	use opt::sgd::Sgd;
+	use rand::{Isaac64Rng, SeedableRng};
+
+	let weights = ArrayD::from_shape_vec(&[3, 2][..], vec![0.5, -1.0, 2.0, 0.25, -1.5, 1.0]).unwrap();
+	let bias = ArrayD::from_shape_vec(&[2][..], vec![0.3, -0.7]).unwrap();
+	let mut stream = SyntheticLinear::new(weights.clone(), bias.clone())
+		.noise_std(0.01)
+		.rng(Isaac64Rng::from_seed([3u8; 32]))
+		.batch(16);
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![Unknown, 3], "input", tag![])?;
+	let output = g.new_node(shape![Unknown, 2], "output", tag![])?;
+	let target = g.new_node(shape![Unknown, 2], "target", tag![])?;
+	let o1 = g.new_op(Linear::new(&input, &output), tag![])?;
+	let o2 = g.new_op(Bias::new(&output), tag![])?;
+	let _o3 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+	let mut opt = Sgd::new(&g)?.rate(0.01);
+	let weights_index = opt.parameters().iter().position(|p| o1.instance().inner_nodes().contains(p)).unwrap();
+	let bias_index = opt.parameters().iter().position(|p| o2.instance().inner_nodes().contains(p)).unwrap();
+
+	let mut params = g.initialise_nodes(opt.parameters())?;
+	for _ in 0..500 {
+		let (_err, _step, _change_norm, new_params) = opt.step(stream.next(), params)?;
+		params = new_params;
+	}
+
+	assert!(params[weights_index].iter().zip(&weights).all(|(a, b)| (a - b).abs() < 0.05), "{:?}", params[weights_index]);
+	assert!(params[bias_index].iter().zip(&bias).all(|(a, b)| (a - b).abs() < 0.05), "{:?}", params[bias_index]);
+
+	Ok(())
+}
@@ -9,9 +9,13 @@ use init::Initialiser;
 use std::collections::VecDeque;
 use indexmap::{IndexMap, IndexSet};
 use ops::*;
+use std::sync::{Arc, RwLock};
 use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
 use id::*;
 use storage::Storage;
+use json::Json;
+use opt::op_parameters;
+use std::time::{Duration, Instant};
 
 error_chain!{
 	errors {
@@ -107,6 +111,11 @@ error_chain!{
 		PassError(pass_name: String, message: String){
 			display("Pass: '{}' returned error message: {}", pass_name,	message)
 		}
+
+		/// Returned by `Initialiser::try_call()` when the initialiser can not be applied, e.g. to an array of the wrong shape
+		InitialiserError(initialiser_name: String, message: String){
+			display("Initialiser: '{}' returned error message: {}", initialiser_name, message)
+		}
 	}
 
 	links {
@@ -132,6 +141,8 @@ pub struct GraphDef {
 	// Extra information pertaining to nodes
 	static_inputs: IndexMap<DataID, ArrayD<f32>>,
 	initialisers: IndexMap<NodeID, Initialiser>,
+	recompute: IndexSet<NodeID>,
+	op_param_values: OpParamValues,
 
 	// These are used to quickly look op names and tags
 	// Just duplicates data from node_ids/op_ids
@@ -157,6 +168,8 @@ impl GraphDef {
 
 			static_inputs: indexmap![],
 			initialisers: indexmap![],
+			recompute: indexset![],
+			op_param_values: OpParamValues::default(),
 
 			node_names: indexmap![],
 			node_tags: indexmap![],
@@ -192,6 +205,19 @@ impl GraphDef {
 		Subgraph::new(&self, inputs, outputs)
 	}
 
+	/// Extracts a subgraph suitable for inference, which computes only the values of the requested `outputs`.
+	///
+	/// As only node values are requested, only forward passes are included, and no gradients are allocated or calculated.
+	/// The order of `inputs` is the order in which values must be supplied to `Subgraph::execute()`.
+	///
+	/// See `subgraph()`.
+	pub fn forward_subgraph(&self, inputs: &[NodeID], outputs: &[NodeID]) -> Result<Subgraph> {
+		self.subgraph(
+			&inputs.iter().map(|node_id| node_id.value_id()).collect::<Vec<_>>(),
+			&outputs.iter().map(|node_id| node_id.value_id()).collect::<Vec<_>>()
+		)
+	}
+
 	/// The default subgraph is typicaly suitable for training.
 	///
 	/// All nodes with no input ops are taken to be subgraph inputs,
@@ -211,6 +237,67 @@ impl GraphDef {
 		)
 	}
 
+	/// Returns the gradient of the loss with respect to all parameters, flattened and concatenated in the order of `parameter_ids()`.
+	///
+	/// `node_ids` and `values` supply the value of each input node, and must include every parameter node.
+	///
+	/// See `backprop_subset()`.
+	pub fn backprop(&self, node_ids: &[NodeID], values: Vec<ArrayD<f32>>) -> Result<Vec<f32>> {
+		let parameter_ids = self.parameter_ids();
+		let len = node_ids.iter().zip(&values).filter(|&(node_id, _)| parameter_ids.contains(node_id)).map(|(_, value)| value.len()).sum();
+		self.backprop_subset(node_ids, values, &(0..len).collect::<Vec<_>>())
+	}
+
+	/// Returns the elements of the flattened parameter gradient (see `backprop()`) at each of `param_indices`, in the same order.
+	///
+	/// The backward passes leading to each parameter containing a requested index must still be run in full,
+	/// however gradients are not calculated for parameters which contain none of the requested indices.
+	pub fn backprop_subset(&self, node_ids: &[NodeID], values: Vec<ArrayD<f32>>, param_indices: &[usize]) -> Result<Vec<f32>> {
+		ensure!(node_ids.len() == values.len(), format!("backprop_subset() requires one value per node, but {} nodes and {} values were supplied", node_ids.len(), values.len()));
+
+		let parameter_ids = self.parameter_ids();
+
+		// offsets[i] is the index of the first element of parameter i in the flattened parameter gradient
+		let mut offsets = vec![0];
+		for param_id in &parameter_ids {
+			let len = match node_ids.iter().position(|node_id| node_id == param_id) {
+				Some(i) => values[i].len(),
+				None => bail!(format!("backprop_subset() requires a value for parameter node '{}'", param_id.name())),
+			};
+			let next = offsets[offsets.len() - 1] + len;
+			offsets.push(next);
+		}
+		let total = offsets[offsets.len() - 1];
+
+		// (parameter, element) for each requested index
+		let mut locations = Vec::with_capacity(param_indices.len());
+		for &index in param_indices {
+			ensure!(index < total, format!("Parameter index {} is out of bounds for {} parameter elements", index, total));
+			let param = offsets.iter().rposition(|&offset| offset <= index).unwrap();
+			locations.push((param, index - offsets[param]));
+		}
+
+		let required_ids: Vec<NodeID> = parameter_ids.iter().enumerate()
+			.filter(|&(i, _)| locations.iter().any(|&(param, _)| param == i))
+			.map(|(_, param_id)| param_id.clone())
+			.collect();
+		if required_ids.is_empty() {
+			return Ok(vec![]);
+		}
+
+		let mut subgraph = self.subgraph(
+			&node_ids.iter().map(|node_id| node_id.value_id()).collect::<Vec<_>>(),
+			&required_ids.iter().map(|node_id| node_id.gradient_id()).collect::<Vec<_>>()
+		)?;
+		let mut map = subgraph.execute(values)?.into_map();
+
+		let grads: Vec<Option<Vec<f32>>> = parameter_ids.iter()
+			.map(|param_id| map.remove(&param_id.gradient_id()).map(|grad| grad.iter().cloned().collect()))
+			.collect();
+
+		Ok(locations.iter().map(|&(param, i)| grads[param].as_ref().expect("Subgraph must have requested parameter gradients as outputs.")[i]).collect())
+	}
+
 	/// Node values are initialised to be zero filled by default.
 	/// The ArrayD value supplied to this method will be used to set the initial value of the node, and this data must be able to broadcast to this node.
 	/// This can be used to supply fixed inputs to Ops in place of parameters
@@ -234,6 +321,15 @@ impl GraphDef {
 		self.initialisers.remove(node_id);
 	}
 
+	/// Marks node values which should be discarded after the forward passes which use them, and recomputed when required by backward passes.
+	///
+	/// This trades compute for memory, and replaces any previously marked nodes. It affects subgraphs created after this call.
+	/// Subgraph inputs are never recomputed.
+	/// Subgraph creation will fail if a marked node is written by a forward pass which also writes other data.
+	pub fn set_recompute(&mut self, node_ids: &[NodeID]) {
+		self.recompute = node_ids.iter().cloned().collect();
+	}
+
 	/// Creates values for the requested nodes according to the initialisers of each node.
 	///
 	/// This should only be called on nodes with a fully known shape.
@@ -245,7 +341,7 @@ impl GraphDef {
 			if let Some(initialiser) = self.initialisers.get(node) {
 				let op_id = initialiser.op_id();
 				let op = op_id.as_ref().map(|id| id.instance());
-				initialiser.call(arr.view_mut(), op);
+				initialiser.try_call(arr.view_mut(), op)?;
 			}
 			vec.push(arr);
 		}
@@ -330,6 +426,7 @@ impl GraphDef {
 	fn new_op_impl<O: Op>(&mut self, op: O, tags: Vec<OpTag>, next_id: usize) -> Result<OpID> {
 		
 		
+		let type_name = op.type_name();
 		let op = op.build(self)?;
 		
 		let name = op.name().to_string();
@@ -350,7 +447,7 @@ impl GraphDef {
 
 
 		// all good, so add op
-		let op_id = OpID::new(next_id, op, tags.iter().cloned().collect());
+		let op_id = OpID::new(next_id, type_name, op, tags.iter().cloned().collect());
 		self.op_ids.push(op_id.clone());
 
 		// update lookup maps
@@ -376,6 +473,201 @@ impl GraphDef {
 		pass_id
 	}
 
+	/// Replaces chains of single input, single output elementwise ops with a single `FusedElementwise` op, removing the intermediate nodes.
+	///
+	/// Only untagged top level ops are fused, and an intermediate node is only removed if it is untagged,
+	/// is written only by the previous op in the chain and read only by the next, and has no static input, initialiser or recompute marking.
+	/// This saves the memory and time spent on the intermediate values and gradients, at the cost of recomputing the intermediate values during backprop.
+	/// Returns the number of nodes removed.
+	///
+	/// `FusedElementwise` ops can't be recreated by `from_json()`, so serialise the graph before fusing.
+	pub fn fuse_elementwise(&mut self) -> Result<usize> {
+		let chains = {
+			let dependencies = Dependencies::new(self);
+
+			let mut inner_ops = IndexSet::new();
+			let mut inner_nodes = IndexSet::new();
+			for op_id in &self.op_ids {
+				let instance = op_id.instance();
+				inner_ops.extend(instance.inner_ops());
+				inner_nodes.extend(instance.inner_nodes());
+			}
+
+			let fusable = |op_id: &OpID| {
+				op_id.tags().is_empty()
+					&& !inner_ops.contains(op_id)
+					&& op_id.instance().elementwise_func().is_some()
+			};
+
+			// If the node can be removed, returns the op which consumes it
+			let next_in_chain = |node_id: &NodeID| -> Option<OpID> {
+				if !node_id.tags().is_empty()
+					|| inner_nodes.contains(node_id)
+					|| self.static_inputs.contains_key(&node_id.value_id())
+					|| self.static_inputs.contains_key(&node_id.gradient_id())
+					|| self.initialisers.contains_key(node_id)
+					|| self.recompute.contains(node_id) {
+					return None;
+				}
+
+				let producers = dependencies.node_inputs(node_id);
+				let consumers = dependencies.node_outputs(node_id);
+				if producers.len() != 1 || consumers.len() != 1 || dependencies.node_shape_inputs(node_id) != producers {
+					return None;
+				}
+
+				let producer = producers.get_index(0).unwrap();
+				let consumer = consumers.get_index(0).unwrap();
+				if producer != consumer && fusable(producer) && fusable(consumer) {
+					Some(consumer.clone())
+				} else {
+					None
+				}
+			};
+
+			let mut chains: Vec<(Vec<OpID>, Vec<NodeID>)> = vec![];
+			for op_id in self.op_ids.iter().filter(|op_id| fusable(op_id)) {
+				// only start chains at ops whose input can't be removed
+				let (inputs, _) = op_id.instance().dependencies();
+				if next_in_chain(&inputs[0]).is_some() {
+					continue;
+				}
+
+				let mut ops = vec![op_id.clone()];
+				let mut nodes = vec![];
+				loop {
+					let (_, outputs) = ops[ops.len() - 1].instance().dependencies();
+					match next_in_chain(&outputs[0]) {
+						Some(next_op) => {
+							nodes.push(outputs[0].clone());
+							ops.push(next_op);
+						},
+						None => break,
+					}
+				}
+
+				if ops.len() > 1 {
+					chains.push((ops, nodes));
+				}
+			}
+			chains
+		};
+
+		let mut removed = 0;
+		for (ops, nodes) in chains {
+			let (inputs, _) = ops[0].instance().dependencies();
+			let (_, outputs) = ops[ops.len() - 1].instance().dependencies();
+			let funcs = ops.iter().map(|op_id| op_id.instance().elementwise_func().unwrap()).collect();
+
+			self.remove_ops(&ops);
+			self.remove_nodes(&nodes);
+			removed += nodes.len();
+
+			self.new_op(::ops::activ::fused::FusedElementwise::new(&inputs[0], &outputs[0], funcs), tag![])?;
+		}
+
+		Ok(removed)
+	}
+
+	/// Removes nodes and ops which can't affect the loss or any tagged node, returning the number of nodes and ops removed.
+	///
+	/// Ops without output nodes, such as losses, and nodes with a tag other than `Parameter` are kept, along with everything upstream of them.
+	/// Ops which write to a kept node are always kept, so the values computed for kept nodes are unaltered.
+	/// Ops created inside the build of another op are kept or removed along with the outer op.
+	///
+	/// Untagged nodes which are only used for inference, e.g. the output of a network trained on a different node, will be removed, so tag nodes to keep them.
+	/// Returns an error without removing anything if the graph has no loss and no tagged nodes, as everything would be removed.
+	pub fn prune(&mut self) -> Result<usize> {
+		let (dead_ops, dead_nodes) = {
+			let dependencies = Dependencies::new(self);
+
+			// map each inner op to the outermost op which created it, relying on inner ops being added first
+			let mut owners: IndexMap<OpID, OpID> = IndexMap::new();
+			for op_id in &self.op_ids {
+				let mut stack = op_id.instance().inner_ops();
+				while let Some(inner_op) = stack.pop() {
+					stack.extend(inner_op.instance().inner_ops());
+					owners.insert(inner_op, op_id.clone());
+				}
+			}
+			let owner = |op_id: &OpID| owners.get(op_id).unwrap_or(op_id).clone();
+
+			let mut live_ops = IndexSet::new();
+			let mut live_nodes = IndexSet::new();
+
+			let mut stack: Vec<OpID> = self.op_ids.iter().filter(|op_id| op_id.instance().dependencies().1.is_empty()).map(&owner).collect();
+			for node_id in &self.node_ids {
+				if node_id.tags().iter().any(|tag| tag != &NodeTag::Parameter) {
+					live_nodes.insert(node_id.clone());
+					stack.extend(dependencies.node_inputs(node_id).iter().map(&owner));
+				}
+			}
+			ensure!(!stack.is_empty() || !live_nodes.is_empty(), "Cannot prune a graph without any ops lacking outputs, such as losses, or tagged nodes, as everything would be removed. Tag the output nodes to keep them.");
+
+			while let Some(op_id) = stack.pop() {
+				if live_ops.contains(&op_id) {
+					continue;
+				}
+
+				let mut ops = vec![op_id];
+				let mut i = 0;
+				while i < ops.len() {
+					let inner_ops = ops[i].instance().inner_ops();
+					ops.extend(inner_ops);
+					i += 1;
+				}
+
+				for op_id in ops {
+					live_ops.insert(op_id.clone());
+					let instance = op_id.instance();
+					let (inputs, outputs) = instance.dependencies();
+					for node_id in inputs.into_iter().chain(outputs).chain(instance.inner_nodes()) {
+						if live_nodes.insert(node_id.clone()) {
+							stack.extend(dependencies.node_inputs(&node_id).iter().map(&owner));
+							stack.extend(dependencies.node_shape_inputs(&node_id).iter().map(&owner));
+						}
+					}
+				}
+			}
+
+			let dead_ops: Vec<OpID> = self.op_ids.iter().filter(|op_id| !live_ops.contains(*op_id)).cloned().collect();
+			let dead_nodes: Vec<NodeID> = self.node_ids.iter().filter(|node_id| !live_nodes.contains(*node_id)).cloned().collect();
+			(dead_ops, dead_nodes)
+		};
+
+		self.remove_ops(&dead_ops);
+		self.remove_nodes(&dead_nodes);
+
+		Ok(dead_ops.len() + dead_nodes.len())
+	}
+
+	/// Removes ops and their passes, without checking whether anything else depends on them.
+	fn remove_ops(&mut self, op_ids: &[OpID]) {
+		let pass_ids: IndexSet<PassID> = op_ids.iter().flat_map(|op_id| op_id.instance().inner_passes()).collect();
+		self.pass_ids.retain(|pass_id| !pass_ids.contains(pass_id));
+
+		self.op_ids.retain(|op_id| !op_ids.contains(op_id));
+		self.op_names.retain(|_, op_id| !op_ids.contains(op_id));
+		for set in self.op_tags.values_mut() {
+			set.retain(|op_id| !op_ids.contains(op_id));
+		}
+		self.op_tags.retain(|_, set| !set.is_empty());
+	}
+
+	/// Removes nodes and any information pertaining to them, without checking whether any ops depend on them.
+	fn remove_nodes(&mut self, node_ids: &[NodeID]) {
+		self.node_ids.retain(|node_id| !node_ids.contains(node_id));
+		self.node_names.retain(|_, node_id| !node_ids.contains(node_id));
+		for set in self.node_tags.values_mut() {
+			set.retain(|node_id| !node_ids.contains(node_id));
+		}
+		self.node_tags.retain(|_, set| !set.is_empty());
+
+		self.static_inputs.retain(|data_id, _| !node_ids.contains(&data_id.node_id()));
+		self.initialisers.retain(|node_id, _| !node_ids.contains(node_id));
+		self.recompute.retain(|node_id| !node_ids.contains(node_id));
+	}
+
 	/// Create a node which acts as a subview of another node.
 	/// Contiguous views will be free from time and memory overhead, recording just a view.
 	/// Non-contigues views will incurr a memory and time overhead during runtime.
@@ -400,6 +692,169 @@ impl GraphDef {
 		&self.pass_ids
 	}
 
+	/// Returns the nodes which are not the output of any op, in order of creation.
+	///
+	/// Nodes tagged `Parameter` and nodes with a static input value are excluded.
+	/// These are the nodes which typically must be fed data, and match the non-parameter inputs of `default_subgraph()`.
+	pub fn input_nodes(&self) -> Vec<NodeID> {
+		let dependencies = Dependencies::new(self);
+		self.get_nodes().iter()
+			.filter(|node_id| dependencies.node_inputs(node_id).len() == 0
+				&& !node_id.tags().contains(&NodeTag::Parameter)
+				&& !self.static_inputs.contains_key(&node_id.value_id()))
+			.cloned().collect()
+	}
+
+	/// Returns the nodes which are not the input of any op, in order of creation.
+	pub fn output_nodes(&self) -> Vec<NodeID> {
+		let dependencies = Dependencies::new(self);
+		self.get_nodes().iter()
+			.filter(|node_id| dependencies.node_outputs(node_id).len() == 0)
+			.cloned().collect()
+	}
+
+	/// Returns the node with the given name, if one exists.
+	pub fn node_by_name(&self, name: &str) -> Option<NodeID> {
+		self.node_names.get(name).cloned()
+	}
+
+	/// Serialises the architecture of the graph to JSON: the nodes with their shapes and tags, and the ops connecting them.
+	///
+	/// Only top level ops are recorded, by `type_name()` and the nodes they depend on.
+	/// Nodes and ops created inside the build of another op are left to be recreated when that op is rebuilt.
+	/// Parameter values, initialisers, static inputs and op tags are not included; use the parameter save/load functionality for weights.
+	///
+	/// As ops are rebuilt with their default settings, an error is returned if any op was built with other settings, or is not registered (see `ops::registry`).
+	/// Settings are compared against a default op built from the registry, using `OpInstance::op_params()` and `OpInstance::settings()`.
+	pub fn to_json(&self) -> Result<String> {
+		let mut inner_nodes = IndexSet::new();
+		let mut inner_ops = IndexSet::new();
+		for op_id in &self.op_ids {
+			inner_nodes.extend(op_id.instance().inner_nodes());
+			inner_ops.extend(op_id.instance().inner_ops());
+		}
+
+		let nodes = self.node_ids.iter()
+			.filter(|node_id| !inner_nodes.contains(*node_id))
+			.map(|node_id| Json::Object(vec![
+				("name".to_string(), Json::Str(node_id.name().to_string())),
+				("shape".to_string(), Json::Array(node_id.shape().dimensions().iter().map(|dim| match dim {
+					&NodeDim::Unknown => Json::Null,
+					&NodeDim::Known(x) => Json::Number(x as f64),
+					&NodeDim::Interval{lower, upper} => Json::Array(vec![Json::Number(lower as f64), Json::Number(upper as f64)]),
+				}).collect())),
+				("tags".to_string(), Json::Array(node_id.tags().iter().filter_map(|tag| match tag {
+					&NodeTag::Parameter => Some(Json::Str("Parameter".to_string())),
+					&NodeTag::Int(x) => Some(Json::Number(x as f64)),
+					&NodeTag::Str(ref string) => Some(Json::Object(vec![("Str".to_string(), Json::Str(string.clone()))])),
+					&NodeTag::Id(_) => None,
+				}).collect())),
+			]))
+			.collect();
+
+		let mut ops = vec![];
+		let mut default_graph = self.clone();
+		for op_id in self.op_ids.iter().filter(|op_id| !inner_ops.contains(*op_id)) {
+			let (inputs, outputs) = op_id.instance().dependencies();
+			for node_id in inputs.iter().chain(&outputs) {
+				ensure!(!inner_nodes.contains(node_id), format!("Op '{}' depends on node '{}' which was created inside another op, and cannot be serialised", op_id.name(), node_id.name()));
+			}
+			let default_op_id = registry::build_op_by_name(&mut default_graph, op_id.type_name(), &inputs, &outputs)?;
+			let params_changed = op_id.instance().op_params().iter().any(|key| self.op_param(op_id, key) != default_graph.op_param(&default_op_id, key));
+			ensure!(!params_changed && op_id.instance().settings() == default_op_id.instance().settings(), format!("Op '{}' was built with settings other than the defaults for '{}', which cannot be serialised", op_id.name(), op_id.type_name()));
+			ops.push(Json::Object(vec![
+				("type".to_string(), Json::Str(op_id.type_name().to_string())),
+				("inputs".to_string(), Json::Array(inputs.iter().map(|node_id| Json::Str(node_id.name().to_string())).collect())),
+				("outputs".to_string(), Json::Array(outputs.iter().map(|node_id| Json::Str(node_id.name().to_string())).collect())),
+			]));
+		}
+
+		Ok(Json::Object(vec![
+			("nodes".to_string(), Json::Array(nodes)),
+			("ops".to_string(), Json::Array(ops)),
+		]).to_string())
+	}
+
+	/// Reconstructs a graph from the JSON produced by `to_json()`.
+	///
+	/// Ops are rebuilt with their default settings using the op registry (see `ops::registry`), so ops must be registered under their `type_name()`.
+	pub fn from_json(json: &str) -> Result<GraphDef> {
+		let json = Json::parse(json)?;
+		let mut graph = GraphDef::new();
+
+		let nodes = json.get("nodes").and_then(Json::as_array).ok_or_else(|| "Graph JSON must contain a 'nodes' array".to_string())?;
+		for node in nodes {
+			let name = node.get("name").and_then(Json::as_str).ok_or_else(|| "Each node must have a 'name' string".to_string())?;
+			let dims = node.get("shape").and_then(Json::as_array).ok_or_else(|| format!("Node '{}' must have a 'shape' array", name))?;
+			let tags = node.get("tags").and_then(Json::as_array).ok_or_else(|| format!("Node '{}' must have a 'tags' array", name))?;
+
+			let mut shape = vec![];
+			for dim in dims {
+				shape.push(match dim {
+					&Json::Null => NodeDim::Unknown,
+					&Json::Array(ref bounds) if bounds.len() == 2 && bounds[0].as_usize().is_some() && bounds[1].as_usize().is_some() => {
+						NodeDim::Interval{lower: bounds[0].as_usize().unwrap(), upper: bounds[1].as_usize().unwrap()}
+					},
+					_ => NodeDim::Known(dim.as_usize().ok_or_else(|| format!("Node '{}' has an invalid dimension: {}", name, dim))?),
+				});
+			}
+
+			let mut node_tags = vec![];
+			for tag in tags {
+				node_tags.push(match tag {
+					&Json::Str(ref string) if string == "Parameter" => NodeTag::Parameter,
+					&Json::Number(_) => NodeTag::Int(tag.as_usize().ok_or_else(|| format!("Node '{}' has an invalid tag: {}", name, tag))?),
+					_ => NodeTag::Str(tag.get("Str").and_then(Json::as_str).ok_or_else(|| format!("Node '{}' has an invalid tag: {}", name, tag))?.to_string()),
+				});
+			}
+
+			graph.new_node(shape.into(), name, node_tags)?;
+		}
+
+		let ops = json.get("ops").and_then(Json::as_array).ok_or_else(|| "Graph JSON must contain an 'ops' array".to_string())?;
+		for op in ops {
+			let type_name = op.get("type").and_then(Json::as_str).ok_or_else(|| "Each op must have a 'type' string".to_string())?;
+			let mut dependencies = vec![];
+			for key in &["inputs", "outputs"] {
+				let names = op.get(key).and_then(Json::as_array).ok_or_else(|| format!("Op '{}' must have an '{}' array", type_name, key))?;
+				let mut node_ids = vec![];
+				for name in names {
+					let name = name.as_str().ok_or_else(|| format!("Op '{}' has an invalid node name: {}", type_name, name))?;
+					node_ids.push(graph.node_by_name(name).ok_or_else(|| format!("Op '{}' refers to an unknown node: {}", type_name, name))?);
+				}
+				dependencies.push(node_ids);
+			}
+			let op_id = registry::build_op_by_name(&mut graph, type_name, &dependencies[0], &dependencies[1])?;
+			ensure!(op_id.type_name() == type_name, format!("Op registered as '{}' built an op of type '{}'", type_name, op_id.type_name()));
+		}
+
+		Ok(graph)
+	}
+
+	/// Returns the current value of a named scalar hyperparameter of an op, such as the `alpha` of a `LeakyReLU`.
+	///
+	/// Returns `None` if the op does not expose a hyperparameter with that name, see `OpInstance::op_params()`.
+	pub fn op_param(&self, op_id: &OpID, key: &str) -> Option<f32> {
+		op_id.instance().op_param(key).map(|param| self.op_param_values.get(&param))
+	}
+
+	/// Changes a named scalar hyperparameter of an op without rebuilding the graph, e.g. between phases of training.
+	///
+	/// The change also applies to existing `Subgraph`s built from this `GraphDef`, but not to clones of it, which hold their own values.
+	/// Returns an error if `value` is outside the range accepted by the op, see `OpParam::range()`.
+	pub fn set_op_param(&mut self, op_id: &OpID, key: &str, value: f32) -> Result<()> {
+		ensure!(self.op_ids.contains(op_id), format!("Op '{}' is not part of this graph", op_id.name()));
+		match op_id.instance().op_param(key) {
+			Some(param) => {
+				let (min, max) = param.range();
+				ensure!(param.in_range(value), format!("Op '{}' parameter '{}' must be in the range [{}, {}], found: {}", op_id.name(), key, min, max, value));
+				self.op_param_values.set(&param, value)
+			},
+			None => bail!(format!("Op '{}' has no parameter '{}', available parameters are: {:?}", op_id.name(), key, op_id.instance().op_params())),
+		}
+		Ok(())
+	}
+
 	pub fn parameter_ids<'a>(&'a self) -> Vec<NodeID> {
 		self.node_ids(NodeTag::Parameter)
 	}
@@ -483,6 +938,26 @@ impl GraphDef {
 		}
 	}
 
+	/// Returns all nodes which were created with the string tag, e.g. `tag!["weight"]`, in the order they were created.
+	///
+	/// Unlike `node_ids()`, node names are not matched, so only tagged nodes are returned.
+	pub fn nodes_with_tag(&self, tag: &str) -> Vec<NodeID> {
+		match self.node_tags.get(&NodeTag::from(tag)){
+			Some(set) => set.iter().cloned().collect(),
+			None => Vec::new(),
+		}
+	}
+
+	/// Returns all ops which were created with the string tag, e.g. `tag!["encoder"]`, in the order they were created.
+	///
+	/// Unlike `op_ids()`, op names are not matched, so only tagged ops are returned.
+	pub fn ops_with_tag(&self, tag: &str) -> Vec<OpID> {
+		match self.op_tags.get(&OpTag::from(tag)){
+			Some(set) => set.iter().cloned().collect(),
+			None => Vec::new(),
+		}
+	}
+
 
 	/// Returns the number of nodes in the graph.
 	pub fn num_nodes(&self) -> usize{
@@ -507,6 +982,102 @@ impl GraphDef {
 	pub fn num_passes(&self) -> usize{
 		self.pass_ids.len()
 	}
+
+	/// Returns the total number of elements in all `Parameter` nodes.
+	///
+	/// Returns an error if any `Parameter` node does not have a Known size.
+	pub fn num_params(&self) -> Result<usize>{
+		self.parameter_ids().iter().map(param_size).sum()
+	}
+
+	/// Returns a summary of the size of the model, which can be printed as a table.
+	///
+	/// Each top level op is listed with the shapes of its outputs and the number of parameter elements it uses,
+	/// including parameters created by or supplied to its inner ops.
+	/// Parameters used by more than one op are counted against the first.
+	/// Returns an error if any `Parameter` node does not have a Known size.
+	pub fn summary(&self) -> Result<ModelSummary> {
+		let mut inner_ops = IndexSet::new();
+		for op_id in &self.op_ids {
+			inner_ops.extend(op_id.instance().inner_ops());
+		}
+
+		let parameter_ids = self.parameter_ids();
+		let mut counted = IndexSet::new();
+		let mut ops = vec![];
+		for op_id in self.op_ids.iter().filter(|op_id| !inner_ops.contains(*op_id)) {
+			let num_params = op_parameters(&parameter_ids, &[op_id.clone()]).iter()
+				.filter(|node_id| counted.insert((*node_id).clone()))
+				.map(param_size)
+				.sum::<Result<usize>>()?;
+
+			ops.push(OpSummary {
+				name: op_id.name().to_string(),
+				type_name: op_id.type_name().to_string(),
+				output_shapes: op_id.instance().dependencies().1.iter().map(|node_id| node_id.shape().clone()).collect(),
+				num_params: num_params,
+			});
+		}
+
+		let num_params = self.num_params()?;
+		Ok(ModelSummary {
+			ops: ops,
+			num_params: num_params,
+			param_bytes: num_params * ::std::mem::size_of::<f32>(),
+		})
+	}
+}
+
+/// The size of a model, as returned by `GraphDef::summary()`
+///
+/// Printing with `Display` produces a table with one row per top level op.
+#[derive(Clone, Debug)]
+pub struct ModelSummary {
+	pub ops: Vec<OpSummary>,
+	/// The total number of elements in all `Parameter` nodes
+	pub num_params: usize,
+	/// The memory required to store the parameters
+	pub param_bytes: usize,
+}
+
+/// A single row of a `ModelSummary`
+#[derive(Clone, Debug)]
+pub struct OpSummary {
+	pub name: String,
+	pub type_name: String,
+	pub output_shapes: Vec<NodeShape>,
+	/// The number of parameter elements used by the op which weren't already counted against an earlier op
+	pub num_params: usize,
+}
+
+impl ::std::fmt::Display for ModelSummary {
+	fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+		let shape_string = |shape: &NodeShape| {
+			let dims: Vec<String> = shape.dimensions().iter().map(|dim| match dim {
+				&NodeDim::Unknown => "?".to_string(),
+				&NodeDim::Known(x) => x.to_string(),
+				&NodeDim::Interval{lower, upper} => format!("{}-{}", lower, upper),
+			}).collect();
+			format!("[{}]", dims.join(", "))
+		};
+
+		let rows: Vec<(String, String, String, String)> = self.ops.iter().map(|op| (
+			op.name.clone(),
+			op.type_name.clone(),
+			op.output_shapes.iter().map(|shape| shape_string(shape)).collect::<Vec<_>>().join(" "),
+			op.num_params.to_string(),
+		)).collect();
+
+		let header = ("Op".to_string(), "Type".to_string(), "Output Shapes".to_string(), "Params".to_string());
+		let widths = rows.iter().chain(Some(&header)).fold((0, 0, 0, 0), |(w0, w1, w2, w3), row| {
+			(w0.max(row.0.len()), w1.max(row.1.len()), w2.max(row.2.len()), w3.max(row.3.len()))
+		});
+
+		for row in Some(&header).into_iter().chain(&rows) {
+			writeln!(f, "{:w0$}  {:w1$}  {:w2$}  {:>w3$}", row.0, row.1, row.2, row.3, w0=widths.0, w1=widths.1, w2=widths.2, w3=widths.3)?;
+		}
+		write!(f, "Total params: {} ({} bytes)", self.num_params, self.param_bytes)
+	}
 }
 
 /// Denormalised data about dependencies
@@ -698,10 +1269,21 @@ pub struct Subgraph {
 	included_data: IndexMap<DataID, DataStatus>,
 	included_passes: IndexSet<PassID>,
 	pass_order: Vec<PassID>,
+	execution_plan: Vec<PlanStep>,
 	passes_before_dealloc: IndexMap<DataID, usize>,
 
 	// To what degree should ops drag in upstream ops
 	strict_op_inclusion: bool,
+
+	// The op which created each pass, and accumulated (forward, backward) timings if profiling is enabled
+	pass_ops: IndexMap<PassID, OpID>,
+	profile: Option<IndexMap<OpID, (Duration, Duration)>>,
+
+	// Multiplies the gradients produced by ops which add to the loss
+	loss_scale: f32,
+
+	// Shared with the GraphDef, so that changes made by set_op_param() apply to existing subgraphs
+	op_param_values: Arc<RwLock<IndexMap<usize, f32>>>,
 }
 
 impl Subgraph {
@@ -732,12 +1314,30 @@ impl Subgraph {
 			.filter(|&(k, _v)| !inputs.contains(k))
 			.map(|(k, v)| (k.clone(), v.clone())).collect();
 
-		// for each data_id count the number of passes deemed required which depend on it, then add 1 if it is a requested output
-		let mut passes_before_dealloc: IndexMap<DataID, usize> = dependencies.data_outputs.iter().map(|(id, passes)| (id.clone(), passes.iter().filter(|&pass| included_passes.contains(pass)).count())).collect();
+		let recompute: IndexSet<DataID> = graph.recompute.iter()
+			.map(|node_id| node_id.value_id())
+			.filter(|data_id| included_data.contains_key(data_id) && !input_set.contains(data_id) && !graph.static_inputs.contains_key(data_id))
+			.collect();
+		let execution_plan = find_execution_plan(&pass_order, &recompute, &dependencies)?;
+
+		// for each data_id count the number of times it is read by passes in the execution plan, then add 1 if it is a requested output
+		let mut passes_before_dealloc: IndexMap<DataID, usize> = dependencies.data_outputs.keys().map(|id| (id.clone(), 0)).collect();
+		for step in &execution_plan {
+			if let PlanStep::Run(ref pass_id) = *step {
+				for data_id in dependencies.pass_inputs(pass_id) {
+					*passes_before_dealloc.get_mut(data_id).unwrap() += 1;
+				}
+			}
+		}
 		for data_id in outputs {
 			*passes_before_dealloc.get_mut(data_id).unwrap() += 1;
 		}
 
+		let pass_ops = graph.get_ops().iter()
+			.flat_map(|op_id| op_id.instance().inner_passes().into_iter().map(move |pass_id| (pass_id, op_id.clone())))
+			.filter(|&(ref pass_id, _)| included_passes.contains(pass_id))
+			.collect();
+
 		let graph = Subgraph{
 			dependencies: dependencies,
 
@@ -751,12 +1351,20 @@ impl Subgraph {
 			included_data: included_data,
 			included_passes: included_passes,
 			pass_order: pass_order,
+			execution_plan: execution_plan,
 			passes_before_dealloc: passes_before_dealloc,
 
 			subgraph_inputs: inputs.to_vec(),
 			subgraph_outputs: outputs.to_vec(),
 
 			strict_op_inclusion: strict_op_inclusion,
+
+			pass_ops: pass_ops,
+			profile: None,
+
+			loss_scale: 1.0,
+
+			op_param_values: graph.op_param_values.share(),
 		};
 
 		Ok(graph)
@@ -777,12 +1385,36 @@ impl Subgraph {
 		}
 
 		let mut storage = Storage::new(&self.included_data, &self.dependencies, &self.filtered_static_inputs, input_data, &self.shapes);
+		storage.set_loss_scale(self.loss_scale);
+		storage.set_op_params(self.op_param_values.read().expect("Could not acquire lock on OpParam values").clone());
 
 		let mut passes_before_dealloc = self.passes_before_dealloc.clone();
 
-		for pass_id in &self.pass_order {
+		for step in &self.execution_plan {
+			let pass_id = match *step {
+				PlanStep::Run(ref pass_id) => pass_id,
+				PlanStep::Discard(ref data_id) => {
+					storage.discard(data_id);
+					continue;
+				},
+			};
 			storage.set_current_pass(Some(pass_id.clone()));
-			let pass_data = pass_id.instance().run(&mut storage)?;
+			let pass_data = if let Some(ref mut profile) = self.profile {
+				let start = Instant::now();
+				let pass_data = pass_id.instance().run(&mut storage)?;
+				let elapsed = start.elapsed();
+				if let Some(op_id) = self.pass_ops.get(pass_id) {
+					let times = profile.entry(op_id.clone()).or_insert((Duration::new(0, 0), Duration::new(0, 0)));
+					if self.dependencies.pass_is_forward(pass_id) {
+						times.0 += elapsed;
+					} else {
+						times.1 += elapsed;
+					}
+				}
+				pass_data
+			} else {
+				pass_id.instance().run(&mut storage)?
+			};
 			storage.set_pass_data(pass_id, pass_data);
 
 			for data_id in self.dependencies.pass_inputs.get(pass_id).unwrap() {
@@ -814,6 +1446,42 @@ impl Subgraph {
 		Ok(())
 	}
 
+	/// Enables or disables recording of the time spent in the passes of each op during `execute()`.
+	///
+	/// Enabling profiling clears any previously recorded timings, which then accumulate over subsequent calls to `execute()`.
+	/// When disabled, no timing is performed.
+	///
+	/// Default: false
+	pub fn enable_profiling(&mut self, enable: bool) {
+		self.profile = if enable {Some(indexmap![])} else {None};
+	}
+
+	/// Returns the accumulated forward and backward pass durations for each op which has run since profiling was enabled.
+	///
+	/// Each entry is `(op_id, op_name, forward_duration, backward_duration)`, in order of first execution.
+	/// Time spent in the passes of an inner op is attributed to the inner op, not its parent.
+	/// Returns an empty `Vec` if profiling is not enabled.
+	pub fn profile_report(&self) -> Vec<(OpID, String, Duration, Duration)> {
+		match self.profile {
+			Some(ref profile) => profile.iter().map(|(op_id, &(forward, backward))| (op_id.clone(), op_id.name().to_string(), forward, backward)).collect(),
+			None => vec![],
+		}
+	}
+
+	/// Sets the factor by which ops which add to the loss multiply the gradients they produce during `execute()`, without altering the loss itself.
+	///
+	/// All gradients computed are scaled by the same factor, so small gradients which would otherwise underflow can be represented, and must be divided by the scale before use.
+	///
+	/// Default: 1.0
+	pub fn set_loss_scale(&mut self, scale: f32) {
+		self.loss_scale = scale;
+	}
+
+	/// Returns the current loss scale, see `set_loss_scale()`.
+	pub fn loss_scale(&self) -> f32 {
+		self.loss_scale
+	}
+
 	/// Returns a slice containings all the inputs required to execute this subgraph.
 	pub fn inputs(&self) -> &[DataID]{
 		&self.subgraph_inputs
@@ -826,6 +1494,11 @@ impl Subgraph {
 }
 
 
+/// Returns the number of elements in a `Parameter` node, or an error if it does not have a Known size.
+fn param_size(node_id: &NodeID) -> Result<usize> {
+	node_id.shape().force_flat_size().map_err(|_| ErrorKind::ParameterNodesMustHaveKnownSize(node_id.name().to_string(), node_id.shape().clone()).into())
+}
+
 /// Work backwards from the requested output data marking data, passes, nodes, and ops as required.
 fn find_included(graph: &GraphDef, inputs: &[DataID], static_inputs: &IndexMap<DataID, ArrayD<f32>>, outputs: &[DataID], dependencies: &Dependencies, strict_op_inclusion: bool) -> (IndexMap<DataID, DataStatus>, IndexSet<PassID>, IndexMap<NodeID, NodeStatus>, IndexSet<OpID>){
 		
@@ -947,6 +1620,86 @@ fn find_included(graph: &GraphDef, inputs: &[DataID], static_inputs: &IndexMap<D
 	(included_data, included_passes, included_nodes, included_ops)
 }
 
+/// A step in the execution of a subgraph
+#[derive(Clone, Debug)]
+enum PlanStep {
+	Run(PassID),
+	/// Return data to an unallocated state, so that it is recomputed by rerunning the passes that write it
+	Discard(DataID),
+}
+
+/// Extends the pass order with the discarding and recomputation of the `recompute` data.
+///
+/// Each recompute data is discarded after the last forward pass that reads it, if any passes read it later.
+/// When a later pass requires discarded data, the forward passes which write it are rerun first,
+/// recursively recomputing any of their inputs that were also discarded, which are then discarded again.
+fn find_execution_plan(pass_order: &[PassID], recompute: &IndexSet<DataID>, dependencies: &Dependencies) -> Result<Vec<PlanStep>> {
+
+	if recompute.len() == 0 {
+		return Ok(pass_order.iter().map(|pass_id| PlanStep::Run(pass_id.clone())).collect());
+	}
+
+	let mut producers: IndexMap<DataID, Vec<PassID>> = indexmap![];
+	let mut discard_after: IndexMap<usize, Vec<DataID>> = indexmap![];
+	for data_id in recompute {
+		let data_producers: Vec<PassID> = pass_order.iter().filter(|pass_id| dependencies.pass_outputs(pass_id).contains(data_id)).cloned().collect();
+		for pass_id in &data_producers {
+			ensure!(dependencies.pass_is_forward(pass_id) && dependencies.pass_outputs(pass_id).len() == 1,
+				"Cannot recompute '{}' as it is written by pass '{}', which is not a forward pass with a single output", data_id.name(), pass_id.name());
+		}
+		producers.insert(data_id.clone(), data_producers);
+
+		let reads: Vec<usize> = pass_order.iter().enumerate().filter(|&(_, pass_id)| dependencies.pass_inputs(pass_id).contains(data_id)).map(|(i, _)| i).collect();
+		let last_forward_read = reads.iter().cloned().filter(|&i| dependencies.pass_is_forward(&pass_order[i])).last();
+		if let Some(last_forward_read) = last_forward_read {
+			if reads.iter().any(|&i| i > last_forward_read) {
+				discard_after.entry(last_forward_read).or_insert_with(Vec::new).push(data_id.clone());
+			}
+		}
+	}
+
+	fn recompute_data(data_id: &DataID, plan: &mut Vec<PlanStep>, discarded: &mut IndexSet<DataID>, extras: &mut Vec<DataID>, producers: &IndexMap<DataID, Vec<PassID>>, dependencies: &Dependencies) {
+		discarded.remove(data_id);
+		for pass_id in producers.get(data_id).unwrap() {
+			for input_id in dependencies.pass_inputs(pass_id) {
+				if discarded.contains(input_id) {
+					recompute_data(input_id, plan, discarded, extras, producers, dependencies);
+					extras.push(input_id.clone());
+				}
+			}
+		}
+		for pass_id in producers.get(data_id).unwrap() {
+			plan.push(PlanStep::Run(pass_id.clone()));
+		}
+	}
+
+	let mut plan = vec![];
+	let mut discarded: IndexSet<DataID> = indexset![];
+	for (i, pass_id) in pass_order.iter().enumerate() {
+		for data_id in dependencies.pass_inputs(pass_id) {
+			if discarded.contains(data_id) {
+				let mut extras = vec![];
+				recompute_data(data_id, &mut plan, &mut discarded, &mut extras, &producers, dependencies);
+				for extra_id in extras {
+					plan.push(PlanStep::Discard(extra_id.clone()));
+					discarded.insert(extra_id);
+				}
+			}
+		}
+
+		plan.push(PlanStep::Run(pass_id.clone()));
+
+		if let Some(data_ids) = discard_after.get(&i) {
+			for data_id in data_ids {
+				plan.push(PlanStep::Discard(data_id.clone()));
+				discarded.insert(data_id.clone());
+			}
+		}
+	}
+
+	Ok(plan)
+}
+
 /// Returns the order in which passes should be called such that dependencies are respected.
 /// By default this will order passes in the order that they were added to the graph, and only perform the minimal rearrangement required to ensure dependencies are met.
 /// out of order dependeancies can cause quadratic slow down (this can probably be removed using priority queues)
@@ -1589,10 +2342,491 @@ fn _test_insufficient_input_detection() -> Result<()>{
 
 
 
+#[test]
+fn test_forward_subgraph(){
+	_test_forward_subgraph().unwrap();
+}
+
+fn _test_forward_subgraph() -> Result<()>{
+	use ops::activ::srgb::LinearToSrgb;
+	use ops::loss::mse::Mse;
+	use graph::GraphDef;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![Unknown, 5, 16], "input", tag![])?;
+	let output = g.new_node(shape![Unknown, 5, 16], "output", tag![])?;
+	let target = g.new_node(shape![Unknown, 5, 16], "target", tag![])?;
+
+	let _o1 = g.new_op(LinearToSrgb::new(&input, &output), tag![])?;
+	let _o2 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+	let input_data = ArrayD::from_shape_fn(&[4, 5, 16][..], |idx| (idx[0] * 80 + idx[1] * 16 + idx[2]) as f32 / 320.0);
+
+	let mut sg_forward = g.forward_subgraph(&[input.clone()], &[output.clone()])?;
+	assert!(sg_forward.pass_order.iter().all(|pass_id| sg_forward.dependencies.pass_is_forward(pass_id)));
+	assert!(sg_forward.included_data.keys().all(|data_id| data_id.is_value()));
+
+	let mut sg_backward = g.subgraph(&[input.value_id(), target.value_id()], &[output.value_id(), input.gradient_id()])?;
+	assert!(sg_backward.pass_order.iter().any(|pass_id| !sg_backward.dependencies.pass_is_forward(pass_id)));
+
+	let forward_storage = sg_forward.execute(vec![input_data.clone()])?;
+	let backward_storage = sg_backward.execute(vec![input_data.clone(), ArrayD::zeros(&[4, 5, 16][..])])?;
+
+	assert_eq!(forward_storage.get(&output.value_id())?, backward_storage.get(&output.value_id())?);
+
+	Ok(())
+}
+
+#[test]
+fn test_profiling(){
+	_test_profiling().unwrap();
+}
+
+fn _test_profiling() -> Result<()>{
+	use ops::math::exp::Exp;
+	use ops::activ::tanh::Tanh;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![8, 64], "input", tag![])?;
+	let hidden = g.new_node(shape![8, 64], "hidden", tag![])?;
+	let output = g.new_node(shape![8, 64], "output", tag![])?;
+	let target = g.new_node(shape![8, 64], "target", tag![])?;
+
+	let o1 = g.new_op(Exp::new(&input, &hidden), tag![])?;
+	let o2 = g.new_op(Tanh::new(&hidden, &output), tag![])?;
+	let o3 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+	let mut subgraph = g.subgraph(&[input.value_id(), target.value_id()], &[input.gradient_id()])?;
+	let inputs = || vec![ArrayD::zeros(&[8, 64][..]), ArrayD::zeros(&[8, 64][..])];
+
+	// disabled by default
+	subgraph.execute(inputs())?;
+	assert!(subgraph.profile_report().is_empty());
+
+	subgraph.enable_profiling(true);
+	subgraph.execute(inputs())?;
+	let report = subgraph.profile_report();
+
+	assert_eq!(report.len(), 3);
+	for &(ref op_id, ref name, forward, backward) in &report {
+		assert_eq!(op_id.name(), name);
+		if op_id == &o3 {
+			assert!(forward + backward > Duration::new(0, 0), "{} {:?} {:?}", name, forward, backward);
+		} else {
+			assert!(op_id == &o1 || op_id == &o2);
+			assert!(forward > Duration::new(0, 0), "{} {:?}", name, forward);
+			assert!(backward > Duration::new(0, 0), "{} {:?}", name, backward);
+		}
+	}
+
+	subgraph.enable_profiling(false);
+	assert!(subgraph.profile_report().is_empty());
+
+	Ok(())
+}
+
+#[test]
+fn test_nodes_with_tag(){
+	_test_nodes_with_tag().unwrap();
+}
+
+fn _test_nodes_with_tag() -> Result<()>{
+	use ops::nn::linear::Linear;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let w1 = g.new_node(shape![3, 5], "w1", tag![Parameter, "weight"])?;
+	let hidden = g.new_node(shape![4, 5], "hidden", tag![])?;
+	let w2 = g.new_node(shape![5, 2], "w2", tag!["weight", 7])?;
+	let bias = g.new_node(shape![2], "bias", tag![Parameter])?;
+	let output = g.new_node(shape![4, 2], "output", tag![])?;
+
+	let o1 = g.new_op(Linear::new(&input, &hidden).weights(Some(&w1)), tag!["encoder"])?;
+	let o2 = g.new_op(Linear::new(&hidden, &output).weights(Some(&w2)), tag!["encoder", 3])?;
+
+	assert_eq!(g.nodes_with_tag("weight"), vec![w1.clone(), w2.clone()]);
+	assert!(!g.nodes_with_tag("weight").contains(&bias));
+	assert_eq!(g.ops_with_tag("encoder"), vec![o1, o2]);
+
+	// names are not tags
+	assert!(g.nodes_with_tag("bias").is_empty());
+	assert!(g.ops_with_tag("missing").is_empty());
+
+	Ok(())
+}
+
+#[test]
+fn test_input_output_nodes(){
+	_test_input_output_nodes().unwrap();
+}
+
+fn _test_input_output_nodes() -> Result<()>{
+	use ops::activ::srgb::{LinearToSrgb, SrgbToLinear};
+	use ops::nn::bias::Bias;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![Unknown, 5, 16], "input", tag![])?;
+	let srgb = g.new_node(shape![Unknown, 5, 16], "srgb", tag![])?;
+	let output = g.new_node(shape![Unknown, 5, 16], "output", tag![])?;
+	let target = g.new_node(shape![Unknown, 5, 16], "target", tag![])?;
+	let bias = g.new_node(shape![5, 16], "bias", tag![Parameter])?;
+
+	let _o1 = g.new_op(LinearToSrgb::new(&input, &srgb), tag![])?;
+	let _o2 = g.new_op(Bias::new(&srgb).weights(Some(&bias)), tag![])?;
+	let _o3 = g.new_op(SrgbToLinear::new(&srgb, &output), tag![])?;
+	let _o4 = g.new_op(Mse::new(&srgb, &target), tag![])?;
+
+	assert_eq!(g.input_nodes(), vec![input.clone(), target.clone()]);
+	assert_eq!(g.output_nodes(), vec![output.clone()]);
+
+	assert_eq!(g.node_by_name("srgb"), Some(srgb.clone()));
+	assert_eq!(g.node_by_name("bias"), Some(bias.clone()));
+	assert_eq!(g.node_by_name("missing"), None);
+
+	Ok(())
+}
+
+#[test]
+fn test_summary(){
+	_test_summary().unwrap();
+}
+
+fn _test_summary() -> Result<()>{
+	use ops::nn::linear::Linear;
+	use ops::nn::bias::Bias;
+	use ops::activ::tanh::Tanh;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![Unknown, 3], "input", tag![])?;
+	let hidden = g.new_node(shape![Unknown, 4], "hidden", tag![])?;
+	let activ = g.new_node(shape![Unknown, 4], "activ", tag![])?;
+	let output = g.new_node(shape![Unknown, 2], "output", tag![])?;
+	let target = g.new_node(shape![Unknown, 2], "target", tag![])?;
+
+	let _o1 = g.new_op(Linear::new(&input, &hidden), tag![])?;
+	let _o2 = g.new_op(Bias::new(&hidden), tag![])?;
+	let _o3 = g.new_op(Tanh::new(&hidden, &activ), tag![])?;
+	let _o4 = g.new_op(Linear::new(&activ, &output), tag![])?;
+	let _o5 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+	// weights of 3x4 and 4x2, and a bias of 1x4
+	assert_eq!(g.num_params()?, 24);
+
+	let summary = g.summary()?;
+	assert_eq!(summary.num_params, g.num_params()?);
+	assert_eq!(summary.param_bytes, 24 * 4);
+
+	// inner ops, such as the MatMul inside Linear, are not listed separately
+	let type_names: Vec<&str> = summary.ops.iter().map(|op| op.type_name.as_str()).collect();
+	assert_eq!(type_names, vec!["Linear", "Bias", "Tanh", "Linear", "Mse"]);
+	let counts: Vec<usize> = summary.ops.iter().map(|op| op.num_params).collect();
+	assert_eq!(counts, vec![12, 4, 0, 8, 0]);
+	assert_eq!(counts.iter().sum::<usize>(), summary.num_params);
+	assert_eq!(summary.ops[2].output_shapes, vec![activ.shape().clone()]);
+
+	let table = format!("{}", summary);
+	assert!(table.lines().count() == 7, "{}", table);
+	assert!(table.ends_with("Total params: 24 (96 bytes)"), "{}", table);
+
+	Ok(())
+}
+
+#[test]
+fn test_json_round_trip(){
+	_test_json_round_trip().unwrap();
+}
+
+fn _test_json_round_trip() -> Result<()>{
+	use ops::activ::srgb::{LinearToSrgb, SrgbToLinear};
+	use ops::activ::clip::Clip;
+	use ops::nn::bias::Bias;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![Unknown, 5, 16], "input", tag![])?;
+	let srgb = g.new_node(shape![Unknown, 5, (8, 16)], "srgb", tag!["hidden"])?;
+	let output = g.new_node(shape![Unknown, 5, 16], "output", tag![3])?;
+	let target = g.new_node(shape![Unknown, 5, 16], "target", tag![])?;
+
+	let _o1 = g.new_op(LinearToSrgb::new(&input, &srgb), tag![])?;
+	let _o2 = g.new_op(Bias::new(&srgb), tag![])?;
+	let _o3 = g.new_op(SrgbToLinear::new(&srgb, &output), tag![])?;
+	let _o4 = g.new_op(Mse::new(&srgb, &target), tag![])?;
+
+	let json = g.to_json()?;
+	let g2 = GraphDef::from_json(&json)?;
+
+	// inner nodes and ops, such as the bias parameter, are recreated by rebuilding the op
+	assert_eq!(g.get_nodes().len(), g2.get_nodes().len());
+	for (n1, n2) in g.get_nodes().iter().zip(g2.get_nodes()) {
+		assert_eq!(n1.name(), n2.name());
+		assert_eq!(n1.shape(), n2.shape());
+		assert_eq!(n1.tags(), n2.tags());
+	}
+
+	assert_eq!(g.get_ops().len(), g2.get_ops().len());
+	for (o1, o2) in g.get_ops().iter().zip(g2.get_ops()) {
+		assert_eq!(o1.type_name(), o2.type_name());
+	}
+
+	assert_eq!(g2.to_json()?, json);
+
+	// settings would be lost, so can't be serialised
+	let mut g3 = g.clone();
+	let _o5 = g3.new_op(Mse::new(&output, &target).multiplier(2.0), tag![])?;
+	assert!(g3.to_json().is_err());
+
+	// including settings of composite ops and of elementwise functions
+	let mut g4 = g.clone();
+	let _o5 = g4.new_op(Bias::new(&output).shared_axes(&[1]), tag![])?;
+	assert!(g4.to_json().is_err());
+
+	let mut g5 = g.clone();
+	let _o5 = g5.new_op(Clip::new(&input, &output).max(1.0), tag![])?;
+	assert!(g5.to_json().is_err());
+	let mut g6 = g.clone();
+	let _o5 = g6.new_op(Clip::new(&input, &output), tag![])?;
+	assert!(g6.to_json().is_ok());
+
+	Ok(())
+}
+
+#[test]
+fn test_recompute(){
+	_test_recompute().unwrap();
+}
+
+fn _test_recompute() -> Result<()>{
+	use ops::activ::srgb::{LinearToSrgb, SrgbToLinear};
+	use ops::activ::tanh::Tanh;
+	use ops::activ::logistic::Logistic;
+	use ops::loss::mse::Mse;
+	use ops::numeric_check::generate_input_data;
+
+	let build = |recompute: bool| -> Result<(GraphDef, Vec<NodeID>, Vec<NodeID>)> {
+		let mut g = GraphDef::new();
+
+		let input = g.new_node(shape![7, 5, 16], "input", tag![])?;
+		let srgb = g.new_node(shape![7, 5, 16], "srgb", tag![])?;
+		let tanh = g.new_node(shape![7, 5, 16], "tanh", tag![])?;
+		let logistic = g.new_node(shape![7, 5, 16], "logistic", tag![])?;
+		let output = g.new_node(shape![7, 5, 16], "output", tag![])?;
+		let target = g.new_node(shape![7, 5, 16], "target", tag![])?;
+
+		let _o1 = g.new_op(LinearToSrgb::new(&input, &srgb), tag![])?;
+		let _o2 = g.new_op(Tanh::new(&srgb, &tanh), tag![])?;
+		let _o3 = g.new_op(Logistic::new(&tanh, &logistic), tag![])?;
+		let _o4 = g.new_op(SrgbToLinear::new(&logistic, &output), tag![])?;
+		let _o5 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+		let recomputed = vec![srgb.clone(), tanh.clone(), logistic.clone()];
+		if recompute {
+			g.set_recompute(&recomputed);
+		}
+
+		Ok((g, vec![input, target], recomputed))
+	};
+
+	let (g1, inputs1, _) = build(false)?;
+	let (g2, inputs2, recomputed) = build(true)?;
+
+	let input_data = generate_input_data(&inputs1, 1.0, &mut indexmap![])?;
+
+	let mut sg1 = g1.subgraph(&[inputs1[0].value_id(), inputs1[1].value_id()], &[inputs1[0].gradient_id()])?;
+	let mut sg2 = g2.subgraph(&[inputs2[0].value_id(), inputs2[1].value_id()], &[inputs2[0].gradient_id()])?;
+
+	// recomputed data is discarded, and its forward passes run again
+	assert_eq!(sg1.execution_plan.len(), sg1.pass_order.len());
+	assert!(sg2.execution_plan.iter().filter(|step| matches!(step, &&PlanStep::Discard(_))).count() >= recomputed.len());
+	assert!(sg2.execution_plan.iter().filter(|step| matches!(step, &&PlanStep::Run(_))).count() > sg2.pass_order.len());
+
+	let storage1 = sg1.execute(input_data.clone())?;
+	let storage2 = sg2.execute(input_data.clone())?;
+
+	assert_eq!(storage1.loss(), storage2.loss());
+	assert_eq!(storage1.get(&inputs1[0].gradient_id())?, storage2.get(&inputs2[0].gradient_id())?);
+
+	Ok(())
+}
+
 // TODO detect required ops which want to write to input data
 
 // TODO detect that name conflict detection works
 
 // TODO detect problems with shape propagation
 
-// TODO detect problems with static_input broadcasting
\ No newline at end of file
+// TODO detect problems with static_input broadcasting
+#[test]
+fn test_fuse_elementwise(){
+	_test_fuse_elementwise().unwrap();
+}
+
+fn _test_fuse_elementwise() -> Result<()>{
+	use ops::activ::srgb::{LinearToSrgb, SrgbToLinear};
+	use ops::activ::tanh::Tanh;
+	use ops::activ::logistic::Logistic;
+	use ops::loss::mse::Mse;
+	use ops::numeric_check::generate_input_data;
+
+	let mut g1 = GraphDef::new();
+
+	let input = g1.new_node(shape![7, 5, 16], "input", tag![])?;
+	let srgb = g1.new_node(shape![7, 5, 16], "srgb", tag![])?;
+	let tanh = g1.new_node(shape![7, 5, 16], "tanh", tag![])?;
+	let logistic = g1.new_node(shape![7, 5, 16], "logistic", tag!["kept"])?;
+	let output = g1.new_node(shape![7, 5, 16], "output", tag![])?;
+	let target = g1.new_node(shape![7, 5, 16], "target", tag![])?;
+
+	let _o1 = g1.new_op(LinearToSrgb::new(&input, &srgb), tag![])?;
+	let _o2 = g1.new_op(Tanh::new(&srgb, &tanh), tag![])?;
+	let _o3 = g1.new_op(Logistic::new(&tanh, &logistic), tag![])?;
+	let _o4 = g1.new_op(SrgbToLinear::new(&logistic, &output), tag![])?;
+	let _o5 = g1.new_op(Mse::new(&output, &target), tag![])?;
+
+	// the tagged node splits the chain, leaving one fusable chain of three ops, and a lone op
+	let mut g2 = g1.clone();
+	let removed = g2.fuse_elementwise()?;
+	assert_eq!(removed, 2);
+	assert_eq!(g2.num_nodes(), g1.num_nodes() - 2);
+	assert_eq!(g2.num_ops(), g1.num_ops() - 2);
+	assert!(g2.node_by_name("srgb").is_none());
+	assert!(g2.node_by_name("logistic").is_some());
+	assert_eq!(g2.fuse_elementwise()?, 0);
+
+	let input_data = generate_input_data(&[input.clone(), target.clone()], 1.0, &mut indexmap![])?;
+
+	let mut sg1 = g1.subgraph(&[input.value_id(), target.value_id()], &[output.value_id(), input.gradient_id()])?;
+	let mut sg2 = g2.subgraph(&[input.value_id(), target.value_id()], &[output.value_id(), input.gradient_id()])?;
+
+	let storage1 = sg1.execute(input_data.clone())?;
+	let storage2 = sg2.execute(input_data)?;
+
+	assert_eq!(storage1.loss(), storage2.loss());
+	assert!(storage1.get(&output.value_id())?.all_close(&storage2.get(&output.value_id())?, 1e-6));
+	assert!(storage1.get(&input.gradient_id())?.all_close(&storage2.get(&input.gradient_id())?, 1e-6));
+
+	Ok(())
+}
+
+#[test]
+fn test_prune(){
+	_test_prune().unwrap();
+}
+
+fn _test_prune() -> Result<()>{
+	use ops::activ::tanh::Tanh;
+	use ops::activ::logistic::Logistic;
+	use ops::nn::bias::Bias;
+	use ops::loss::mse::Mse;
+	use ops::numeric_check::generate_input_data;
+
+	let mut g1 = GraphDef::new();
+
+	let input = g1.new_node(shape![7, 5, 16], "input", tag![])?;
+	let hidden = g1.new_node(shape![7, 5, 16], "hidden", tag![])?;
+	let output = g1.new_node(shape![7, 5, 16], "output", tag![])?;
+	let target = g1.new_node(shape![7, 5, 16], "target", tag![])?;
+	let kept = g1.new_node(shape![7, 5, 16], "kept", tag!["probe"])?;
+	let orphan = g1.new_node(shape![7, 5, 16], "orphan", tag![])?;
+	let _unused = g1.new_node(shape![7, 5, 16], "unused", tag![])?;
+
+	let _o1 = g1.new_op(Tanh::new(&input, &hidden), tag![])?;
+	let _o2 = g1.new_op(Bias::new(&hidden), tag![])?;
+	let _o3 = g1.new_op(Logistic::new(&hidden, &output), tag![])?;
+	let _o4 = g1.new_op(Mse::new(&output, &target), tag![])?;
+	let _o5 = g1.new_op(Tanh::new(&hidden, &kept), tag![])?;
+	let o6 = g1.new_op(Logistic::new(&hidden, &orphan), tag![])?;
+
+	let mut g2 = g1.clone();
+	assert_eq!(g2.prune()?, 3);
+	assert_eq!(g2.num_nodes(), g1.num_nodes() - 2);
+	assert_eq!(g2.num_ops(), g1.num_ops() - 1);
+	assert_eq!(g2.num_passes(), g1.num_passes() - 2);
+	assert!(!g2.get_ops().contains(&o6));
+	assert!(g2.node_by_name("orphan").is_none());
+	assert!(g2.node_by_name("unused").is_none());
+	assert!(g2.node_by_name("kept").is_some());
+	assert_eq!(g2.num_params()?, g1.num_params()?);
+	assert_eq!(g2.prune()?, 0);
+
+	let input_data = generate_input_data(&[input.clone(), target.clone()], 1.0, &mut indexmap![])?;
+	let params = g1.initialise_nodes(&g1.parameter_ids())?;
+	let inputs: Vec<_> = input_data.into_iter().chain(params).collect();
+	let input_ids: Vec<_> = [input.clone(), target.clone()].iter().chain(&g1.parameter_ids()).map(|node_id| node_id.value_id()).collect();
+
+	let mut sg1 = g1.subgraph(&input_ids, &[kept.value_id(), input.gradient_id()])?;
+	let mut sg2 = g2.subgraph(&input_ids, &[kept.value_id(), input.gradient_id()])?;
+
+	let storage1 = sg1.execute(inputs.clone())?;
+	let storage2 = sg2.execute(inputs)?;
+
+	assert_eq!(storage1.loss(), storage2.loss());
+	assert!(storage1.get(&kept.value_id())?.all_close(&storage2.get(&kept.value_id())?, 1e-6));
+	assert!(storage1.get(&input.gradient_id())?.all_close(&storage2.get(&input.gradient_id())?, 1e-6));
+
+	// an inference graph without a loss or tagged nodes has nothing to keep
+	let mut g3 = GraphDef::new();
+	let input = g3.new_node(shape![7, 16], "input", tag![])?;
+	let output = g3.new_node(shape![7, 16], "output", tag![])?;
+	let _o1 = g3.new_op(Tanh::new(&input, &output), tag![])?;
+	assert!(g3.prune().is_err());
+	assert_eq!(g3.num_nodes(), 2);
+	assert_eq!(g3.num_ops(), 1);
+
+	Ok(())
+}
+
+#[test]
+fn test_backprop_subset(){
+	_test_backprop_subset().unwrap();
+}
+
+fn _test_backprop_subset() -> Result<()>{
+	use ops::nn::linear::Linear;
+	use ops::nn::bias::Bias;
+	use ops::loss::mse::Mse;
+	use ops::numeric_check::generate_input_data;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![3, 5], "input", tag![])?;
+	let output = g.new_node(shape![3, 4], "output", tag![])?;
+	let target = g.new_node(shape![3, 4], "target", tag![])?;
+
+	let _o1 = g.new_op(Linear::new(&input, &output), tag![])?;
+	let _o2 = g.new_op(Bias::new(&output), tag![])?;
+	let _o3 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+	let node_ids: Vec<_> = [input.clone(), target.clone()].iter().chain(&g.parameter_ids()).cloned().collect();
+	let values: Vec<_> = generate_input_data(&[input.clone(), target.clone()], 1.0, &mut indexmap![])?.into_iter()
+		.chain(generate_input_data(&g.parameter_ids(), 1.0, &mut indexmap![])?)
+		.collect();
+
+	let full = g.backprop(&node_ids, values.clone())?;
+	assert_eq!(full.len(), g.num_params()?);
+
+	// weights only, bias only, and a mix of both out of order and with repeats
+	let last = full.len() - 1;
+	for indices in &[vec![0, 7, 19], vec![last - 3, last], vec![last, 2, 20, 2, 0]] {
+		let subset = g.backprop_subset(&node_ids, values.clone(), indices)?;
+		assert_eq!(subset.len(), indices.len());
+		for (&x, &i) in subset.iter().zip(indices) {
+			assert!((x - full[i]).abs() <= 1e-6 * full[i].abs().max(1.0), "index {}: {} != {}", i, x, full[i]);
+		}
+	}
+
+	assert!(g.backprop_subset(&node_ids, values.clone(), &[])?.is_empty());
+	assert!(g.backprop_subset(&node_ids, values.clone(), &[full.len()]).is_err());
+	assert!(g.backprop_subset(&node_ids[..2], values[..2].to_vec(), &[0]).is_err());
+
+	Ok(())
+}
@@ -16,11 +16,17 @@ struct NodeDesc {
 
 
 trait OpDescTrait: fmt::Debug + Send + Sync{
+	fn type_name(&self) -> &'static str;
+
 	fn instance(&self) -> &OpInstance;
 
 	fn tags(&self) -> &IndexSet<OpTag>;
 }
 impl<O: OpInstance> OpDescTrait for OpDesc<O> {
+	fn type_name(&self) -> &'static str {
+		self.type_name
+	}
+
 	fn instance(&self) -> &OpInstance {
 		&self.instance
 	}
@@ -32,6 +38,7 @@ impl<O: OpInstance> OpDescTrait for OpDesc<O> {
 
 #[derive(Clone, Debug)]
 struct OpDesc<O: OpInstance> {
+	type_name: &'static str,
 	instance: O,
 	tags: IndexSet<OpTag>,
 }
@@ -166,10 +173,11 @@ pub struct OpID {
 }
 
 impl OpID {
-	pub fn new<O: OpInstance>(id: usize, op: O, tags: IndexSet<OpTag>) -> Self {
+	pub fn new<O: OpInstance>(id: usize, type_name: &'static str, op: O, tags: IndexSet<OpTag>) -> Self {
 		OpID{
 			id: id,
 			desc: Arc::new(OpDesc{
+				type_name: type_name,
 				instance: op,
 				tags: tags,
 			}),
@@ -180,6 +188,11 @@ impl OpID {
 		self.desc.instance().name()
 	}
 
+	/// The `type_name()` of the `Op` which was built to produce this instance.
+	pub fn type_name(&self) -> &'static str {
+		self.desc.type_name()
+	}
+
 	pub fn tags(&self) -> &IndexSet<OpTag> {
 		self.desc.tags()
 	}
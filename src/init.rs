@@ -3,20 +3,30 @@ use std::fmt;
 use std::ops::DerefMut;
 use ops::{OpInstance};
 use id::OpID;
+use graph::{ErrorKind, Result};
 use ndarray::ArrayViewMutD;
-use rand::{thread_rng, Isaac64Rng, SeedableRng};
+use rng::new_rng;
 use rand::distributions::{Distribution, Normal, Range};
 
 /// Wrapper for initialiser closures that implements `Clone` and `Debug`
 #[derive(Clone)]
 pub struct Initialiser {
 	name: String,
-	func: Arc<Mutex<FnMut(ArrayViewMutD<f32>, Option<&OpInstance>)>>,
+	func: Arc<Mutex<FnMut(ArrayViewMutD<f32>, Option<&OpInstance>) -> Result<()>>>,
 	op_id: Option<OpID>,
 }
 
 impl Initialiser {
-	pub fn new<F: 'static + FnMut(ArrayViewMutD<f32>, Option<&OpInstance>)>(name: String, func: F) -> Self {
+	/// Wraps an initialiser closure which can not fail.
+	pub fn new<F: 'static + FnMut(ArrayViewMutD<f32>, Option<&OpInstance>)>(name: String, mut func: F) -> Self {
+		Initialiser::try_new(name, move |arr: ArrayViewMutD<f32>, instance: Option<&OpInstance>|{
+			func(arr, instance);
+			Ok(())
+		})
+	}
+
+	/// Wraps an initialiser closure which can return an error, e.g. if the array is not of a shape it supports.
+	pub fn try_new<F: 'static + FnMut(ArrayViewMutD<f32>, Option<&OpInstance>) -> Result<()>>(name: String, func: F) -> Self {
 		Initialiser {
 			name: name,
 			func: Arc::new(Mutex::new(func)),
@@ -25,23 +35,24 @@ impl Initialiser {
 	}
 
 	pub fn wrap(name: String, func: Arc<Mutex<FnMut(ArrayViewMutD<f32>, Option<&OpInstance>)>>) -> Self {
-		Initialiser {
-			name: name,
-			func: func,
-			op_id: None,
-		}
+		Initialiser::try_new(name, move |arr: ArrayViewMutD<f32>, instance: Option<&OpInstance>|{
+			let mut guard = func.lock().expect("Could not acquire lock on wrapped initialiser");
+			guard.deref_mut()(arr, instance);
+			Ok(())
+		})
 	}
 
 	/// Gaussian initialisation
 	///
 	/// This initialises with gaussian values drawn from N(mean, std_dev^2).
 	pub fn gaussian(mean: f32, std_dev: f32) -> Initialiser {
-		Initialiser::new("Gaussian Initialiser".to_string(), move |mut arr: ArrayViewMutD<f32>, _instance: Option<&OpInstance>|{
-			let mut rng = Isaac64Rng::from_rng(thread_rng()).unwrap();
+		Initialiser::try_new("Gaussian Initialiser".to_string(), move |mut arr: ArrayViewMutD<f32>, _instance: Option<&OpInstance>|{
+			let mut rng = new_rng();
 			let norm = Normal::new(mean as f64, std_dev as f64);
 			for e in arr.iter_mut() {
 				*e = norm.sample(&mut rng) as f32;
 			}
+			Ok(())
 		})
 	}
 
@@ -49,12 +60,13 @@ impl Initialiser {
 	///
 	/// This initialises uniform values drawn from [low, high).
 	pub fn uniform(low: f32, high: f32) -> Initialiser {
-		Initialiser::new("Uniform Initialiser".to_string(), move |mut arr: ArrayViewMutD<f32>, _instance: Option<&OpInstance>|{
-			let mut rng = Isaac64Rng::from_rng(thread_rng()).unwrap();
+		Initialiser::try_new("Uniform Initialiser".to_string(), move |mut arr: ArrayViewMutD<f32>, _instance: Option<&OpInstance>|{
+			let mut rng = new_rng();
 			let rang = Range::new(low, high);
 			for e in arr.iter_mut() {
 				*e = rang.sample(&mut rng) as f32;
 			}
+			Ok(())
 		})
 	}
 
@@ -62,16 +74,42 @@ impl Initialiser {
 	///
 	/// Sets all elements to the supplied value
 	pub fn fill(val: f32) -> Initialiser {
-		Initialiser::new("Fill Initialiser".to_string(), move |mut arr: ArrayViewMutD<f32>, _instance: Option<&OpInstance>|{
+		Initialiser::try_new("Fill Initialiser".to_string(), move |mut arr: ArrayViewMutD<f32>, _instance: Option<&OpInstance>|{
 			for e in arr.iter_mut() {
 				*e = val;
 			}
+			Ok(())
 		})
 	}
 
+	/// Identity initialisation
+	///
+	/// Sets a square matrix to the identity.
+	/// Returns an error if the array is not two dimensional with equal dimensions.
+	pub fn identity() -> Initialiser {
+		Initialiser::try_new("Identity Initialiser".to_string(), move |mut arr: ArrayViewMutD<f32>, _instance: Option<&OpInstance>|{
+			ensure!(arr.ndim() == 2 && arr.shape()[0] == arr.shape()[1], "Identity initialiser requires a square matrix, found shape: {:?}", arr.shape());
+			for (idx, e) in arr.indexed_iter_mut() {
+				*e = if idx[0] == idx[1] {1.0} else {0.0};
+			}
+			Ok(())
+		})
+	}
+
+	/// Runs the initialiser, panicking if it returns an error.
 	pub fn call(&self, arr: ArrayViewMutD<f32>, op: Option<&OpInstance>) {
+		if let Err(err) = self.try_call(arr, op) {
+			panic!("{}", err);
+		}
+	}
+
+	/// Runs the initialiser, returning any error as an `InitialiserError`.
+	pub fn try_call(&self, arr: ArrayViewMutD<f32>, op: Option<&OpInstance>) -> Result<()> {
 		let mut guard = self.func.lock().expect(&format!("Could not acquire lock on initialiser: {:?}", self));
-		guard.deref_mut()(arr, op);
+		match guard.deref_mut()(arr, op) {
+			Ok(()) => Ok(()),
+			Err(err) => bail!(ErrorKind::InitialiserError(self.name.clone(), err.to_string())),
+		}
 	}
 
 	pub fn set_op_id(mut self, op_id: OpID) -> Self {
@@ -96,4 +134,22 @@ impl fmt::Debug for Initialiser {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		write!(f, "Initialiser {{ name: {}, .. }}", self.name)
 	}
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_identity_initialiser(){
+	use graph::Error;
+	use ndarray::{ArrayD, IxDyn};
+
+	let init = Initialiser::identity();
+
+	let mut arr = ArrayD::from_elem(IxDyn(&[3, 3]), 5.0);
+	init.try_call(arr.view_mut(), None).unwrap();
+	assert_eq!(arr, ArrayD::from_shape_fn(IxDyn(&[3, 3]), |idx| if idx[0] == idx[1] {1.0} else {0.0}));
+
+	let mut arr = ArrayD::zeros(IxDyn(&[3, 4]));
+	let result = init.try_call(arr.view_mut(), None);
+	assert!(matches!(result, Err(Error(ErrorKind::InitialiserError(..), _))), "{:?}", result);
+	assert!(init.try_call(ArrayD::zeros(IxDyn(&[3])).view_mut(), None).is_err());
+	assert!(Initialiser::fill(1.0).try_call(arr.view_mut(), None).is_ok());
+}
@@ -0,0 +1,267 @@
+//! A minimal JSON value type, with a writer and parser, used for serialising graph architectures.
+
+use std::fmt;
+use std::result;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+	Null,
+	Bool(bool),
+	Number(f64),
+	Str(String),
+	Array(Vec<Json>),
+	/// Key value pairs, in the order they are written
+	Object(Vec<(String, Json)>),
+}
+
+impl Json {
+	/// Parses a single JSON value, which may be surrounded by whitespace.
+	pub fn parse(string: &str) -> result::Result<Json, String> {
+		let mut parser = Parser{chars: string.chars().collect(), pos: 0};
+		let value = parser.parse_value()?;
+		parser.skip_whitespace();
+		if parser.pos < parser.chars.len() {
+			return Err(format!("Unexpected trailing characters at position {}", parser.pos));
+		}
+		Ok(value)
+	}
+
+	/// Returns the value associated with `key` if this is an object containing it.
+	pub fn get(&self, key: &str) -> Option<&Json> {
+		match self {
+			&Json::Object(ref pairs) => pairs.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref v)| v),
+			_ => None,
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			&Json::Str(ref string) => Some(string.as_str()),
+			_ => None,
+		}
+	}
+
+	pub fn as_array(&self) -> Option<&[Json]> {
+		match self {
+			&Json::Array(ref values) => Some(&values[..]),
+			_ => None,
+		}
+	}
+
+	/// Returns the number as a `usize` if it is a non-negative integer.
+	pub fn as_usize(&self) -> Option<usize> {
+		match self {
+			&Json::Number(x) if x >= 0.0 && x.fract() == 0.0 => Some(x as usize),
+			_ => None,
+		}
+	}
+}
+
+impl fmt::Display for Json {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			&Json::Null => write!(f, "null"),
+			&Json::Bool(b) => write!(f, "{}", b),
+			&Json::Number(x) => write!(f, "{}", x),
+			&Json::Str(ref string) => write_string(f, string),
+			&Json::Array(ref values) => {
+				write!(f, "[")?;
+				for (i, value) in values.iter().enumerate() {
+					if i > 0 {
+						write!(f, ",")?;
+					}
+					write!(f, "{}", value)?;
+				}
+				write!(f, "]")
+			},
+			&Json::Object(ref pairs) => {
+				write!(f, "{{")?;
+				for (i, &(ref key, ref value)) in pairs.iter().enumerate() {
+					if i > 0 {
+						write!(f, ",")?;
+					}
+					write_string(f, key)?;
+					write!(f, ":{}", value)?;
+				}
+				write!(f, "}}")
+			},
+		}
+	}
+}
+
+fn write_string(f: &mut fmt::Formatter, string: &str) -> fmt::Result {
+	write!(f, "\"")?;
+	for c in string.chars() {
+		match c {
+			'"' => write!(f, "\\\"")?,
+			'\\' => write!(f, "\\\\")?,
+			'\n' => write!(f, "\\n")?,
+			'\r' => write!(f, "\\r")?,
+			'\t' => write!(f, "\\t")?,
+			c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+			c => write!(f, "{}", c)?,
+		}
+	}
+	write!(f, "\"")
+}
+
+struct Parser {
+	chars: Vec<char>,
+	pos: usize,
+}
+
+impl Parser {
+	fn peek(&self) -> Option<char> {
+		self.chars.get(self.pos).cloned()
+	}
+
+	fn next(&mut self) -> result::Result<char, String> {
+		let c = self.peek().ok_or_else(|| "Unexpected end of input".to_string())?;
+		self.pos += 1;
+		Ok(c)
+	}
+
+	fn expect(&mut self, expected: char) -> result::Result<(), String> {
+		let c = self.next()?;
+		if c != expected {
+			return Err(format!("Expected '{}' but found '{}' at position {}", expected, c, self.pos - 1));
+		}
+		Ok(())
+	}
+
+	fn expect_literal(&mut self, literal: &str, value: Json) -> result::Result<Json, String> {
+		for expected in literal.chars() {
+			self.expect(expected)?;
+		}
+		Ok(value)
+	}
+
+	fn skip_whitespace(&mut self) {
+		while let Some(c) = self.peek() {
+			if !c.is_whitespace() {
+				break;
+			}
+			self.pos += 1;
+		}
+	}
+
+	fn parse_value(&mut self) -> result::Result<Json, String> {
+		self.skip_whitespace();
+		match self.peek() {
+			Some('n') => self.expect_literal("null", Json::Null),
+			Some('t') => self.expect_literal("true", Json::Bool(true)),
+			Some('f') => self.expect_literal("false", Json::Bool(false)),
+			Some('"') => self.parse_string().map(Json::Str),
+			Some('[') => self.parse_array(),
+			Some('{') => self.parse_object(),
+			Some(c) if c == '-' || c.is_digit(10) => self.parse_number(),
+			Some(c) => Err(format!("Unexpected character '{}' at position {}", c, self.pos)),
+			None => Err("Unexpected end of input".to_string()),
+		}
+	}
+
+	fn parse_number(&mut self) -> result::Result<Json, String> {
+		let start = self.pos;
+		while let Some(c) = self.peek() {
+			if !(c.is_digit(10) || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E') {
+				break;
+			}
+			self.pos += 1;
+		}
+		let string: String = self.chars[start..self.pos].iter().collect();
+		string.parse::<f64>().map(Json::Number).map_err(|_| format!("Invalid number '{}' at position {}", string, start))
+	}
+
+	fn parse_string(&mut self) -> result::Result<String, String> {
+		self.expect('"')?;
+		let mut string = String::new();
+		loop {
+			match self.next()? {
+				'"' => return Ok(string),
+				'\\' => {
+					let escaped = match self.next()? {
+						'"' => '"',
+						'\\' => '\\',
+						'/' => '/',
+						'b' => '\u{8}',
+						'f' => '\u{c}',
+						'n' => '\n',
+						'r' => '\r',
+						't' => '\t',
+						'u' => {
+							let mut code = 0;
+							for _ in 0..4 {
+								let digit = self.next()?.to_digit(16).ok_or_else(|| format!("Invalid unicode escape at position {}", self.pos - 1))?;
+								code = code * 16 + digit;
+							}
+							::std::char::from_u32(code).unwrap_or('\u{fffd}')
+						},
+						c => return Err(format!("Invalid escape '\\{}' at position {}", c, self.pos - 1)),
+					};
+					string.push(escaped);
+				},
+				c => string.push(c),
+			}
+		}
+	}
+
+	fn parse_array(&mut self) -> result::Result<Json, String> {
+		self.expect('[')?;
+		let mut values = vec![];
+		self.skip_whitespace();
+		if self.peek() == Some(']') {
+			self.pos += 1;
+			return Ok(Json::Array(values));
+		}
+		loop {
+			values.push(self.parse_value()?);
+			self.skip_whitespace();
+			match self.next()? {
+				',' => {},
+				']' => return Ok(Json::Array(values)),
+				c => return Err(format!("Expected ',' or ']' but found '{}' at position {}", c, self.pos - 1)),
+			}
+		}
+	}
+
+	fn parse_object(&mut self) -> result::Result<Json, String> {
+		self.expect('{')?;
+		let mut pairs = vec![];
+		self.skip_whitespace();
+		if self.peek() == Some('}') {
+			self.pos += 1;
+			return Ok(Json::Object(pairs));
+		}
+		loop {
+			self.skip_whitespace();
+			let key = self.parse_string()?;
+			self.skip_whitespace();
+			self.expect(':')?;
+			let value = self.parse_value()?;
+			pairs.push((key, value));
+			self.skip_whitespace();
+			match self.next()? {
+				',' => {},
+				'}' => return Ok(Json::Object(pairs)),
+				c => return Err(format!("Expected ',' or '}}' but found '{}' at position {}", c, self.pos - 1)),
+			}
+		}
+	}
+}
+
+
+#[test]
+fn test_json_round_trip(){
+	let value = Json::Object(vec![
+		("name".to_string(), Json::Str("a \"quoted\"\n name".to_string())),
+		("shape".to_string(), Json::Array(vec![Json::Null, Json::Number(5.0), Json::Array(vec![Json::Number(3.0), Json::Number(9.0)])])),
+		("flag".to_string(), Json::Bool(false)),
+		("empty".to_string(), Json::Object(vec![])),
+	]);
+
+	let string = value.to_string();
+	assert_eq!(Json::parse(&string).unwrap(), value);
+	assert_eq!(Json::parse(" { \"a\" : [ 1 , -2.5e1 ] } ").unwrap(), Json::Object(vec![("a".to_string(), Json::Array(vec![Json::Number(1.0), Json::Number(-25.0)]))]));
+	assert!(Json::parse("[1, 2").is_err());
+	assert!(Json::parse("{} x").is_err());
+}
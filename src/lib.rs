@@ -133,4 +133,8 @@ pub mod opt;
 pub mod data;
 pub mod init;
 pub mod id;
-pub mod storage;
\ No newline at end of file
+pub mod storage;
+pub mod rng;
+mod json;
+
+pub use rng::set_global_seed;
\ No newline at end of file
@@ -0,0 +1,148 @@
+use graph::{GraphDef, Result};
+use id::NodeID;
+use ops::Op;
+use ops::activ::elementwise::{ActivationFunc, ElementwiseInstance, elementwise_build};
+use std::f32;
+
+#[derive(Clone, Debug)] 
+pub struct ClipFunc{
+	min: f32,
+	max: f32,
+}
+
+impl ActivationFunc for ClipFunc {
+	fn value(&self, input: f32) -> f32{
+		input.max(self.min).min(self.max)
+	}
+
+	fn gradient(&self, input: f32, output_grad: f32) -> f32{
+		if input > self.min && input < self.max {
+			output_grad
+		} else {
+			0.0
+		}
+	}
+
+	fn backprop_requires_input_value() -> bool {true}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("min", format!("{:?}", self.min)), ("max", format!("{:?}", self.max))]
+	}
+}
+
+/// Clip Op, the input is clamped to the range [min, max].
+///
+/// Gradients are only passed through where the input lies strictly inside the range.
+#[must_use]
+#[derive(Clone, Debug)] 
+pub struct Clip {
+	output: NodeID,
+	input: NodeID,
+	name: Option<String>,
+	min: f32,
+	max: f32,
+}
+
+impl Clip {
+	pub fn new(input: &NodeID, output: &NodeID) -> Self {
+		Clip {
+			input: input.clone(),
+			output: output.clone(),
+			name: None,
+			min: f32::NEG_INFINITY,
+			max: f32::INFINITY,
+		}
+	}
+
+	/// The lower bound of the output.
+	///
+	/// Default: -inf
+	pub fn min(mut self, min: f32) -> Self{
+		self.min = min;
+		self
+	}
+
+	/// The upper bound of the output.
+	///
+	/// Default: inf
+	pub fn max(mut self, max: f32) -> Self{
+		self.max = max;
+		self
+	}
+}
+
+impl Op for Clip {
+	type InstanceType = ElementwiseInstance<ClipFunc>;
+
+	fn type_name(&self) -> &'static str {
+		"Clip"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.min <= self.max, "Clip op min ({}) must not be greater than max ({})", self.min, self.max);
+		elementwise_build(graph, &self, &self.name, &self.input, &self.output, ClipFunc{min: self.min, max: self.max})
+	}
+}
+
+
+#[test]
+fn test_clip_backprop(){
+	_clip_backprop().unwrap();
+}
+
+fn _clip_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+	use rand::thread_rng;
+	use rand::distributions::{Distribution, Range};
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5, 16], "input", tag![])?;
+	let node2 = g.new_node(shape![7, 5, 16], "output", tag![])?;
+	let node3 = g.new_node(shape![7, 5, 16], "target", tag![])?;
+
+
+	let _o1 = g.new_op(Clip::new(&node1, &node2).min(-2.0).max(2.0), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+
+	// keep inputs inside the active region
+	let sample: Box<::std::ops::FnMut() -> f64 + 'static> = Box::new(|| {
+		let rng = &mut thread_rng();
+		let range = Range::new(-1.5, 1.5);
+		range.sample(rng)
+	});
+	let mut override_dist = indexmap![];
+	override_dist.insert(node1.clone(), sample);
+
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut override_dist)?;
+
+	Ok(())
+}
+
+#[test]
+fn test_clip_out_of_range(){
+	let func = ClipFunc{min: -1.0, max: 2.0};
+
+	assert_eq!(func.value(-3.0), -1.0);
+	assert_eq!(func.value(0.5), 0.5);
+	assert_eq!(func.value(5.0), 2.0);
+
+	assert_eq!(func.gradient(-3.0, 1.0), 0.0);
+	assert_eq!(func.gradient(-1.0, 1.0), 0.0);
+	assert_eq!(func.gradient(0.5, 1.0), 1.0);
+	assert_eq!(func.gradient(2.0, 1.0), 0.0);
+	assert_eq!(func.gradient(5.0, 1.0), 0.0);
+}
@@ -0,0 +1,87 @@
+use graph::{GraphDef, Result};
+use id::NodeID;
+use ops::Op;
+use ops::activ::elementwise::{ActivationFunc, ElementwiseInstance, elementwise_build};
+use std::sync::Arc;
+use std::fmt;
+
+/// Wrapper for user supplied value and gradient closures that implements `ActivationFunc`
+#[derive(Clone)]
+pub struct CustomElementwiseFunc {
+	value: Arc<Fn(f32) -> f32 + Send + Sync>,
+	gradient: Arc<Fn(f32, f32) -> f32 + Send + Sync>,
+}
+
+impl fmt::Debug for CustomElementwiseFunc {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "CustomElementwiseFunc {{ .. }}")
+	}
+}
+
+impl ActivationFunc for CustomElementwiseFunc {
+	fn value(&self, input: f32) -> f32{
+		(self.value)(input)
+	}
+
+	fn gradient(&self, input: f32, output_grad: f32) -> f32{
+		(self.gradient)(input, output_grad)
+	}
+
+	fn backprop_requires_input_value() -> bool {true}
+}
+
+/// An elementwise activation defined by closures, for experimenting without writing an `ActivationFunc`.
+///
+/// `value` maps each input to an output, and `gradient` maps the input and the output gradient to the input gradient.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct CustomElementwise {
+	output: NodeID,
+	input: NodeID,
+	func: CustomElementwiseFunc,
+	name: Option<String>,
+}
+
+impl CustomElementwise {
+	pub fn new<V, G>(input: &NodeID, output: &NodeID, value: V, gradient: G) -> Self
+		where V: Fn(f32) -> f32 + Send + Sync + 'static, G: Fn(f32, f32) -> f32 + Send + Sync + 'static {
+		CustomElementwise {
+			input: input.clone(),
+			output: output.clone(),
+			func: CustomElementwiseFunc {
+				value: Arc::new(value),
+				gradient: Arc::new(gradient),
+			},
+			name: None,
+		}
+	}
+}
+
+impl Op for CustomElementwise {
+	type InstanceType = ElementwiseInstance<CustomElementwiseFunc>;
+
+	fn type_name(&self) -> &'static str {
+		"CustomElementwise"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		elementwise_build(graph, &self, &self.name, &self.input, &self.output, self.func.clone())
+	}
+}
+
+
+#[test]
+fn test_custom_elementwise_backprop(){
+	_custom_elementwise_backprop().unwrap();
+}
+
+fn _custom_elementwise_backprop() -> Result<()>{
+	use ops::numeric_check::check_elementwise_op;
+
+	check_elementwise_op(|input, output| CustomElementwise::new(input, output, |x| x * x, |x, grad| 2.0 * x * grad), shape![7, 5, 16], 0.002)
+}
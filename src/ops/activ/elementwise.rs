@@ -1,9 +1,11 @@
 use graph::{GraphDef, GraphShapes, ErrorKind, Result};
 use storage::Storage;
 use id::{NodeID, DataID, OpID, PassID};
-use ops::{standard_op_name, Op, OpInstance, Pass};
+use ops::{standard_op_name, Op, OpInstance, OpParam, Pass};
 use std::any::Any;
 use std::fmt::Debug;
+use std::sync::Arc;
+use std::ops::{Add, Sub, Mul, Div};
 use rayon::prelude::*;
 
 
@@ -36,6 +38,111 @@ pub trait ActivationFunc: Send + Sync + Clone + Debug + 'static {
 	fn gradient(&self, input: f32, output_grad: f32) -> f32;
 
 	fn backprop_requires_input_value() -> bool;
+
+	/// The names of hyperparameters exposed through `OpInstance::op_params()` by ops using this function.
+	///
+	/// Default: empty
+	fn param_names(&self) -> Vec<&'static str> {
+		vec![]
+	}
+
+	/// Returns the named hyperparameter, which is stored as an `OpParam` so that its current value can be found by `with_params()`.
+	///
+	/// Default: None
+	fn param(&self, _key: &str) -> Option<OpParam> {
+		None
+	}
+
+	/// Returns the function using the current values of its hyperparameters, which passes evaluate in place of `self`.
+	///
+	/// Default: a clone
+	fn with_params(&self, _data: &Storage) -> Self {
+		self.clone()
+	}
+
+	/// The settings of this function other than its hyperparameters, reported by `OpInstance::settings()` for ops using this function.
+	///
+	/// Default: empty
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![]
+	}
+}
+
+/// Double precision versions of `value()` and `gradient()`, used for gradient checks which aren't limited by single precision rounding,
+/// see `numeric_check::check_activation_func_f64()`.
+///
+/// Implement only for functions written generically over `Real`, so that no part of the evaluation is rounded to f32.
+pub trait ActivationFuncF64: ActivationFunc {
+	fn value_f64(&self, input: f64) -> f64;
+
+	fn gradient_f64(&self, input: f64, output_grad: f64) -> f64;
+}
+
+/// The floating point operations needed to write an activation function once for both `f32` and `f64`.
+pub trait Real: Copy + PartialOrd + Add<Output=Self> + Sub<Output=Self> + Mul<Output=Self> + Div<Output=Self> {
+	/// Converts a constant, rounding if `Self` is `f32`.
+	fn from_f64(x: f64) -> Self;
+
+	fn to_f64(self) -> f64;
+
+	fn sqrt(self) -> Self;
+
+	fn powf(self, n: Self) -> Self;
+}
+
+impl Real for f32 {
+	fn from_f64(x: f64) -> Self {x as f32}
+
+	fn to_f64(self) -> f64 {self as f64}
+
+	fn sqrt(self) -> Self {f32::sqrt(self)}
+
+	fn powf(self, n: Self) -> Self {f32::powf(self, n)}
+}
+
+impl Real for f64 {
+	fn from_f64(x: f64) -> Self {x}
+
+	fn to_f64(self) -> f64 {self}
+
+	fn sqrt(self) -> Self {f64::sqrt(self)}
+
+	fn powf(self, n: Self) -> Self {f64::powf(self, n)}
+}
+
+/// An object safe version of `ActivationFunc`, used to compose the functions of chained elementwise ops.
+///
+/// The method names differ from `ActivationFunc` so that both traits can be in scope without ambiguity.
+pub trait ElementwiseFunc: Send + Sync + Debug {
+	/// Equivalent to `ActivationFunc::value()`
+	fn eval(&self, input: f32) -> f32;
+
+	/// Equivalent to `ActivationFunc::gradient()`
+	fn eval_gradient(&self, input: f32, output_grad: f32) -> f32;
+
+	/// Equivalent to `ActivationFunc::backprop_requires_input_value()`
+	fn requires_input_value(&self) -> bool;
+
+	/// Equivalent to `ActivationFunc::with_params()`
+	fn bind_params(&self, data: &Storage) -> Arc<ElementwiseFunc>;
+}
+
+impl<F: ActivationFunc> ElementwiseFunc for F {
+	fn eval(&self, input: f32) -> f32 {
+		self.value(input)
+	}
+
+	fn eval_gradient(&self, input: f32, output_grad: f32) -> f32 {
+		self.gradient(input, output_grad)
+	}
+
+	fn requires_input_value(&self) -> bool {
+		F::backprop_requires_input_value()
+	}
+
+	fn bind_params(&self, data: &Storage) -> Arc<ElementwiseFunc> {
+		Arc::new(self.with_params(data))
+	}
 }
 
 #[derive(Clone, Debug)]
@@ -64,6 +171,22 @@ impl<F: ActivationFunc> OpInstance for ElementwiseInstance<F> where F: Sync + Se
 		let input_shape = shapes.get_shape(&self.input_id).clone();
 		shapes.merge_with(&self.output_id, &input_shape)
 	}
+
+	fn elementwise_func(&self) -> Option<Arc<ElementwiseFunc>> {
+		Some(Arc::new(self.func.clone()))
+	}
+
+	fn op_params(&self) -> Vec<&'static str> {
+		self.func.param_names()
+	}
+
+	fn op_param(&self, key: &str) -> Option<OpParam> {
+		self.func.param(key)
+	}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		self.func.settings()
+	}
 }
 
 
@@ -103,6 +226,7 @@ impl<F: ActivationFunc> Pass for ElementwiseForward<F> where F: Send {
 			ErrorKind::PassError(self.name(), format!("input shape: {:?} did not match output shape: {:?}", input.shape(), output.shape()))
 		);
 
+		let func = self.func.with_params(data);
 		let input = input.as_slice().unwrap();
 		let output = output.as_slice_mut().unwrap();
 
@@ -112,7 +236,7 @@ impl<F: ActivationFunc> Pass for ElementwiseForward<F> where F: Send {
 		let out = &mut output[..len];
 
 		inp.par_iter().zip(out.par_iter_mut()).for_each(|(inp, out)|{
-			*out += self.func.value(*inp);
+			*out += func.value(*inp);
 		});
 
 		// for i in 0..len{
@@ -168,6 +292,7 @@ impl<F: ActivationFunc> Pass for ElementwiseBackward<F> {
 			ErrorKind::PassError(self.name(), format!("input shape: {:?} did not match output shape: {:?}", input_grad.shape(), output_grad.shape()))
 		);
 
+		let func = self.func.with_params(data);
 		let output_grad = output_grad.as_slice().unwrap();
 		let input_grad = input_grad.as_slice_mut().unwrap();
 
@@ -186,7 +311,7 @@ impl<F: ActivationFunc> Pass for ElementwiseBackward<F> {
 			// }
 
 			inpd.par_iter_mut().zip(outd.par_iter()).zip(inp.par_iter()).for_each(|((inpd, outd), inp)|{
-				*inpd += self.func.gradient(*inp, *outd);
+				*inpd += func.gradient(*inp, *outd);
 			});
 		} else {
 
@@ -198,7 +323,7 @@ impl<F: ActivationFunc> Pass for ElementwiseBackward<F> {
 			// }
 
 			inpd.par_iter_mut().zip(outd.par_iter()).for_each(|(inpd, outd)|{
-				*inpd += self.func.gradient(0.0, *outd);
+				*inpd += func.gradient(0.0, *outd);
 			});
 		}
 
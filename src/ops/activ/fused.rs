@@ -0,0 +1,112 @@
+use graph::{GraphDef, Result};
+use id::NodeID;
+use ops::Op;
+use storage::Storage;
+use ops::activ::elementwise::{ActivationFunc, ElementwiseFunc, ElementwiseInstance, elementwise_build};
+use smallvec::SmallVec;
+use std::sync::Arc;
+
+/// The composition of a chain of elementwise functions, applied first to last.
+#[derive(Clone, Debug)]
+pub struct ComposedFunc {
+	funcs: Vec<Arc<ElementwiseFunc>>,
+}
+
+impl ActivationFunc for ComposedFunc {
+	fn value(&self, input: f32) -> f32{
+		self.funcs.iter().fold(input, |x, func| func.eval(x))
+	}
+
+	fn gradient(&self, input: f32, output_grad: f32) -> f32{
+		// recompute the input to each function in the chain, then apply the chain rule in reverse
+		let mut inputs: SmallVec<[f32; 8]> = SmallVec::new();
+		let mut x = input;
+		for func in &self.funcs {
+			inputs.push(x);
+			x = func.eval(x);
+		}
+
+		self.funcs.iter().zip(inputs.iter()).rev().fold(output_grad, |grad, (func, &x)| func.eval_gradient(x, grad))
+	}
+
+	fn backprop_requires_input_value() -> bool {true}
+
+	fn with_params(&self, data: &Storage) -> Self {
+		ComposedFunc {
+			funcs: self.funcs.iter().map(|func| func.bind_params(data)).collect(),
+		}
+	}
+}
+
+/// An elementwise Op which applies a chain of elementwise functions in a single pass.
+///
+/// Usually created by `GraphDef::fuse_elementwise()` rather than directly.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct FusedElementwise {
+	output: NodeID,
+	input: NodeID,
+	func: ComposedFunc,
+	name: Option<String>,
+}
+
+impl FusedElementwise {
+	pub fn new(input: &NodeID, output: &NodeID, funcs: Vec<Arc<ElementwiseFunc>>) -> Self {
+		FusedElementwise {
+			input: input.clone(),
+			output: output.clone(),
+			func: ComposedFunc {funcs},
+			name: None,
+		}
+	}
+}
+
+impl Op for FusedElementwise {
+	type InstanceType = ElementwiseInstance<ComposedFunc>;
+
+	fn type_name(&self) -> &'static str {
+		"FusedElementwise"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(!self.func.funcs.is_empty(), "FusedElementwise requires at least one function");
+		elementwise_build(graph, &self, &self.name, &self.input, &self.output, self.func.clone())
+	}
+}
+
+
+#[test]
+fn test_fused_elementwise_backprop(){
+	_fused_elementwise_backprop().unwrap();
+}
+
+fn _fused_elementwise_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+	use ops::activ::tanh::TanhFunc;
+	use ops::activ::logistic::LogisticFunc;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5, 16], "input", tag![])?;
+	let node2 = g.new_node(shape![7, 5, 16], "output", tag![])?;
+	let node3 = g.new_node(shape![7, 5, 16], "target", tag![])?;
+
+	let _o1 = g.new_op(FusedElementwise::new(&node1, &node2, vec![Arc::new(TanhFunc{}), Arc::new(LogisticFunc{})]), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
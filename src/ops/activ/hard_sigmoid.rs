@@ -0,0 +1,116 @@
+use graph::{GraphDef, Result};
+use id::NodeID;
+use ops::Op;
+use ops::activ::elementwise::{ActivationFunc, ElementwiseInstance, elementwise_build};
+
+#[derive(Clone, Debug)] 
+pub struct HardSigmoidFunc{}
+
+impl ActivationFunc for HardSigmoidFunc {
+	fn value(&self, input: f32) -> f32{
+		(input/6.0 + 0.5).max(0.0).min(1.0)
+	}
+
+	fn gradient(&self, input: f32, output_grad: f32) -> f32{
+		if input > -3.0 && input < 3.0 {
+			output_grad/6.0
+		} else {
+			0.0
+		}
+	}
+
+	fn backprop_requires_input_value() -> bool {true}
+}
+
+/// HardSigmoid Activation Op
+///
+/// A piecewise linear approximation of the logistic function: clamp(x/6 + 0.5, 0, 1)
+///
+/// The derivative has three linear regions:
+/// * x <= -3: 0
+/// * -3 < x < 3: 1/6
+/// * x >= 3: 0
+///
+/// At the kinks (x = ±3) the gradient is taken to be 0.
+#[must_use]
+#[derive(Clone, Debug)] 
+pub struct HardSigmoid {
+	output: NodeID,
+	input: NodeID,
+	name: Option<String>,
+}
+
+impl HardSigmoid {
+	pub fn new(input: &NodeID, output: &NodeID) -> Self {
+		HardSigmoid {
+			input: input.clone(),
+			output: output.clone(),
+			name: None,
+		}
+	}
+}
+
+impl Op for HardSigmoid {
+	type InstanceType = ElementwiseInstance<HardSigmoidFunc>;
+
+	fn type_name(&self) -> &'static str {
+		"HardSigmoid"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		elementwise_build(graph, &self, &self.name, &self.input, &self.output, HardSigmoidFunc{})
+	}
+}
+
+
+#[test]
+fn test_hard_sigmoid_backprop(){
+	_hard_sigmoid_backprop().unwrap();
+}
+
+fn _hard_sigmoid_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+	use rand::thread_rng;
+	use rand::distributions::{Distribution, Range};
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5, 16], "input", tag![])?;
+	let node2 = g.new_node(shape![7, 5, 16], "output", tag![])?;
+	let node3 = g.new_node(shape![7, 5, 16], "target", tag![])?;
+
+
+	let _o1 = g.new_op(HardSigmoid::new(&node1, &node2), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+
+	// avoid sampling near the kinks at ±3
+	let sample: Box<::std::ops::FnMut() -> f64 + 'static> = Box::new(|| {
+		let rng = &mut thread_rng();
+		let range = Range::new(-5.0, 5.0);
+		loop {
+			let x: f64 = range.sample(rng);
+			if (x.abs() - 3.0).abs() > 0.1 {
+				return x;
+			}
+		}
+	});
+	let mut override_dist = indexmap![];
+	override_dist.insert(node1.clone(), sample);
+
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut override_dist)?;
+
+	Ok(())
+}
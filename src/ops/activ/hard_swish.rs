@@ -0,0 +1,118 @@
+use graph::{GraphDef, Result};
+use id::NodeID;
+use ops::Op;
+use ops::activ::elementwise::{ActivationFunc, ElementwiseInstance, elementwise_build};
+
+#[derive(Clone, Debug)] 
+pub struct HardSwishFunc{}
+
+impl ActivationFunc for HardSwishFunc {
+	fn value(&self, input: f32) -> f32{
+		input * (input/6.0 + 0.5).max(0.0).min(1.0)
+	}
+
+	fn gradient(&self, input: f32, output_grad: f32) -> f32{
+		if input <= -3.0 {
+			0.0
+		} else if input < 3.0 {
+			output_grad * (input/3.0 + 0.5)
+		} else {
+			output_grad
+		}
+	}
+
+	fn backprop_requires_input_value() -> bool {true}
+}
+
+/// HardSwish Activation Op
+///
+/// x * HardSigmoid(x) = x * clamp(x/6 + 0.5, 0, 1)
+///
+/// The derivative has three linear regions:
+/// * x <= -3: 0
+/// * -3 < x < 3: x/3 + 0.5
+/// * x >= 3: 1
+///
+/// At the kinks the gradient of the lower region is used at x = -3 and the gradient of the upper region at x = 3.
+#[must_use]
+#[derive(Clone, Debug)] 
+pub struct HardSwish {
+	output: NodeID,
+	input: NodeID,
+	name: Option<String>,
+}
+
+impl HardSwish {
+	pub fn new(input: &NodeID, output: &NodeID) -> Self {
+		HardSwish {
+			input: input.clone(),
+			output: output.clone(),
+			name: None,
+		}
+	}
+}
+
+impl Op for HardSwish {
+	type InstanceType = ElementwiseInstance<HardSwishFunc>;
+
+	fn type_name(&self) -> &'static str {
+		"HardSwish"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		elementwise_build(graph, &self, &self.name, &self.input, &self.output, HardSwishFunc{})
+	}
+}
+
+
+#[test]
+fn test_hard_swish_backprop(){
+	_hard_swish_backprop().unwrap();
+}
+
+fn _hard_swish_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+	use rand::thread_rng;
+	use rand::distributions::{Distribution, Range};
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5, 16], "input", tag![])?;
+	let node2 = g.new_node(shape![7, 5, 16], "output", tag![])?;
+	let node3 = g.new_node(shape![7, 5, 16], "target", tag![])?;
+
+
+	let _o1 = g.new_op(HardSwish::new(&node1, &node2), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+
+	// avoid sampling near the kinks at ±3
+	let sample: Box<::std::ops::FnMut() -> f64 + 'static> = Box::new(|| {
+		let rng = &mut thread_rng();
+		let range = Range::new(-5.0, 5.0);
+		loop {
+			let x: f64 = range.sample(rng);
+			if (x.abs() - 3.0).abs() > 0.1 {
+				return x;
+			}
+		}
+	});
+	let mut override_dist = indexmap![];
+	override_dist.insert(node1.clone(), sample);
+
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut override_dist)?;
+
+	Ok(())
+}
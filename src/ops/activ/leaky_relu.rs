@@ -1,25 +1,46 @@
 use graph::{GraphDef, Result};
 use id::NodeID;
-use ops::Op;
+use ops::{Op, OpParam};
+use storage::Storage;
 use ops::activ::elementwise::{ActivationFunc, ElementwiseInstance, elementwise_build};
 
+/// The negative slope, `alpha`, can be changed after the graph is built using `GraphDef::set_op_param()`.
 #[derive(Clone, Debug)] 
 pub struct LeakyReLUFunc{
 	alpha: f32,
+	alpha_param: OpParam,
 }
 
 impl ActivationFunc for LeakyReLUFunc {
 	fn value(&self, input: f32) -> f32{
-		 (input + input.abs())*0.5 + (input - input.abs())*(0.5*self.alpha)
+		(input + input.abs())*0.5 + (input - input.abs())*(0.5*self.alpha)
 	}
 
 	fn gradient(&self, input: f32, output_grad: f32) -> f32{
 		let sign = input.signum();
-		//output_grad*0.5*((sign + 1.0) - (sign - 1.0)*self.alpha) // 3 mults and 3 adds
+		//output_grad*0.5*((sign + 1.0) - (sign - 1.0)*alpha) // 3 mults and 3 adds
 		output_grad* (sign*(0.5 - 0.5*self.alpha) + (0.5 + 0.5*self.alpha)) // after optimisation this should have 2 mults and 1 add
 	}
 
 	fn backprop_requires_input_value() -> bool {true}
+
+	fn param_names(&self) -> Vec<&'static str> {
+		vec!["alpha"]
+	}
+
+	fn param(&self, key: &str) -> Option<OpParam> {
+		match key {
+			"alpha" => Some(self.alpha_param.clone()),
+			_ => None,
+		}
+	}
+
+	fn with_params(&self, data: &Storage) -> Self {
+		LeakyReLUFunc {
+			alpha: self.alpha_param.get(data),
+			alpha_param: self.alpha_param.clone(),
+		}
+	}
 }
 
 #[must_use]
@@ -60,7 +81,7 @@ impl Op for LeakyReLU {
 	}
 
 	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
-		elementwise_build(graph, &self, &self.name, &self.input, &self.output, LeakyReLUFunc{alpha: self.alpha})
+		elementwise_build(graph, &self, &self.name, &self.input, &self.output, LeakyReLUFunc{alpha: self.alpha, alpha_param: OpParam::new(self.alpha)})
 	}
 }
 
@@ -93,4 +114,49 @@ fn _leaky_relu_backprop() -> Result<()>{
 	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
 
 	Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_leaky_relu_set_alpha(){
+	_leaky_relu_set_alpha().unwrap();
+}
+
+fn _leaky_relu_set_alpha() -> Result<()>{
+	use graph::GraphDef;
+	use ndarray::ArrayD;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![2, 3], "input", tag![])?;
+	let output = g.new_node(shape![2, 3], "output", tag![])?;
+
+	let o1 = g.new_op(LeakyReLU::new(&input, &output).alpha(0.3), tag![])?;
+	assert_eq!(o1.instance().op_params(), vec!["alpha"]);
+	assert_eq!(g.op_param(&o1, "alpha"), Some(0.3));
+	assert_eq!(g.op_param(&o1, "beta"), None);
+	assert!(g.set_op_param(&o1, "beta", 0.1).is_err());
+
+	let input_val = ArrayD::from_elem(&[2, 3][..], -2.0);
+	let mut subgraph = g.forward_subgraph(&[input.clone()], &[output.clone()])?;
+	let storage = subgraph.execute(vec![input_val.clone()])?;
+	assert!(storage.get(&output.value_id())?.iter().all(|&x| (x + 0.6).abs() < 1e-6));
+
+	// the existing subgraph uses the new slope without being rebuilt
+	g.set_op_param(&o1, "alpha", 0.05)?;
+	assert_eq!(g.op_param(&o1, "alpha"), Some(0.05));
+	let storage = subgraph.execute(vec![input_val.clone()])?;
+	assert!(storage.get(&output.value_id())?.iter().all(|&x| (x + 0.1).abs() < 1e-6));
+
+	// clones hold their own values, so changing a clone doesn't affect the original or its subgraphs
+	let mut g2 = g.clone();
+	g2.set_op_param(&o1, "alpha", 0.5)?;
+	assert_eq!(g2.op_param(&o1, "alpha"), Some(0.5));
+	assert_eq!(g.op_param(&o1, "alpha"), Some(0.05));
+	let storage = subgraph.execute(vec![input_val.clone()])?;
+	assert!(storage.get(&output.value_id())?.iter().all(|&x| (x + 0.1).abs() < 1e-6));
+	let mut subgraph2 = g2.forward_subgraph(&[input.clone()], &[output.clone()])?;
+	let storage = subgraph2.execute(vec![input_val])?;
+	assert!(storage.get(&output.value_id())?.iter().all(|&x| (x + 1.0).abs() < 1e-6));
+
+	Ok(())
+}
@@ -0,0 +1,111 @@
+use graph::{GraphDef, Result};
+use id::NodeID;
+use ops::Op;
+use ops::activ::elementwise::{ActivationFunc, ElementwiseInstance, elementwise_build};
+
+/// Numerically stable ln(1 + exp(x))
+fn softplus(input: f32) -> f32 {
+	input.max(0.0) + (-input.abs()).exp().ln_1p()
+}
+
+#[derive(Clone, Debug)] 
+pub struct MishFunc{}
+
+impl ActivationFunc for MishFunc {
+	fn value(&self, input: f32) -> f32{
+		input * softplus(input).tanh()
+	}
+
+	fn gradient(&self, input: f32, output_grad: f32) -> f32{
+		let tanh_sp = softplus(input).tanh();
+		let sigmoid = if input >= 0.0 {
+			1.0/(1.0 + (-input).exp())
+		} else {
+			let exp = input.exp();
+			exp/(1.0 + exp)
+		};
+		output_grad * (tanh_sp + input * (1.0 - tanh_sp * tanh_sp) * sigmoid)
+	}
+
+	fn backprop_requires_input_value() -> bool {true}
+}
+
+/// Mish Activation Op
+///
+/// x * tanh(ln(1 + exp(x)))
+#[must_use]
+#[derive(Clone, Debug)] 
+pub struct Mish {
+	output: NodeID,
+	input: NodeID,
+	name: Option<String>,
+}
+
+impl Mish {
+	pub fn new(input: &NodeID, output: &NodeID) -> Self {
+		Mish {
+			input: input.clone(),
+			output: output.clone(),
+			name: None,
+		}
+	}
+}
+
+impl Op for Mish {
+	type InstanceType = ElementwiseInstance<MishFunc>;
+
+	fn type_name(&self) -> &'static str {
+		"Mish"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		elementwise_build(graph, &self, &self.name, &self.input, &self.output, MishFunc{})
+	}
+}
+
+
+#[test]
+fn test_mish_backprop(){
+	_mish_backprop().unwrap();
+}
+
+fn _mish_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+	use rand::thread_rng;
+	use rand::distributions::{Distribution, Range};
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5, 16], "input", tag![])?;
+	let node2 = g.new_node(shape![7, 5, 16], "output", tag![])?;
+	let node3 = g.new_node(shape![7, 5, 16], "target", tag![])?;
+
+
+	let _o1 = g.new_op(Mish::new(&node1, &node2), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+
+	let sample: Box<::std::ops::FnMut() -> f64 + 'static> = Box::new(|| {
+		let rng = &mut thread_rng();
+		let range = Range::new(-5.0, 5.0);
+		range.sample(rng)
+	});
+	let mut override_dist = indexmap![];
+	override_dist.insert(node1.clone(), sample);
+
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut override_dist)?;
+
+	Ok(())
+}
@@ -6,4 +6,11 @@ pub mod elu;
 pub mod tanh;
 pub mod srgb;
 pub mod softmax;
-pub mod spline;
\ No newline at end of file
+pub mod spline;
+pub mod clip;
+pub mod mish;
+pub mod hard_sigmoid;
+pub mod hard_swish;
+pub mod custom;
+
+pub mod fused;
\ No newline at end of file
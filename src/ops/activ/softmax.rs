@@ -14,12 +14,15 @@ use std::f32;
 /// By default all dimensions from the inner most outward until the first non-`Known` dimension will be grouped, and the Softmax operation repeated over the remaining dimensions.
 /// 
 /// This can be overridden using `inner()`, `outer()`, or most generally `mask()`.
+///
+/// A `temperature()` can be set to soften (T > 1) or sharpen (T < 1) the output distribution, e.g. for knowledge distillation.
 #[must_use]
 #[derive(Clone, Debug)]
 pub struct Softmax {
 	input_id: NodeID,
 	output_id: NodeID,
 	axes: SmallVec<[isize; 6]>,
+	temperature: f32,
 	name: Option<String>,
 }
 
@@ -29,6 +32,7 @@ impl Softmax {
 			input_id: input.clone(),
 			output_id: output.clone(),
 			axes: SmallVec::new(),
+			temperature: 1.0,
 			name: None,
 		}
 	}
@@ -42,6 +46,16 @@ impl Softmax {
 		self.axes = axes.iter().cloned().collect();
 		self
 	}
+
+	/// The inputs are divided by the temperature, T, before the Softmax operation is applied.
+	///
+	/// softmax(x/T)
+	///
+	/// Default: 1.0
+	pub fn temperature(mut self, temperature: f32) -> Self {
+		self.temperature = temperature;
+		self
+	}
 }
 
 impl Op for Softmax {
@@ -76,15 +90,18 @@ impl Op for Softmax {
 			input_id: self.input_id.clone(),
 			output_id: self.output_id.clone(),
 			axes: self.axes.clone(),
+			temperature: self.temperature,
 			forward_id: graph.add_pass(SoftmaxForward::new(
 					self.input_id.clone(),
 					self.output_id.clone(),
-					mask.clone()
+					mask.clone(),
+					self.temperature,
 				)),
 			backward_id: graph.add_pass(SoftmaxBackward::new(
 					self.input_id.clone(),
 					self.output_id.clone(),
-					mask.clone()
+					mask.clone(),
+					self.temperature,
 				)),
 		})
 	}
@@ -97,6 +114,7 @@ pub struct SoftmaxInstance {
 	input_id: NodeID,
 	output_id: NodeID,
 	axes: SmallVec<[isize; 6]>,
+	temperature: f32,
 	forward_id: PassID,
 	backward_id: PassID,
 }
@@ -117,6 +135,10 @@ impl OpInstance for SoftmaxInstance {
 		let input_shape = shapes.get_shape(&self.input_id).clone();
 		shapes.merge_with(&self.output_id, &input_shape)
 	}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("axes", format!("{:?}", &self.axes[..])), ("temperature", format!("{:?}", self.temperature))]
+	}
 }
 
 
@@ -138,14 +160,16 @@ struct SoftmaxForward {
 	input_id: NodeID,
 	output_id: NodeID,
 	mask: SmallVec<[bool; 6]>,
+	temperature: f32,
 }
 
 impl SoftmaxForward {
-	pub fn new(input_id: NodeID, output_id: NodeID, mask: SmallVec<[bool; 6]>) -> Self {
+	pub fn new(input_id: NodeID, output_id: NodeID, mask: SmallVec<[bool; 6]>, temperature: f32) -> Self {
 		SoftmaxForward {
 			input_id,
 			output_id,
 			mask,
+			temperature,
 		}
 	}
 }
@@ -171,13 +195,15 @@ impl Pass for SoftmaxForward {
 			ErrorKind::PassError(self.name(), format!("input shape: {:?} did not match output shape: {:?}", input.shape(), output.shape()))
 		);
 
+		let inv_t = 1.0/self.temperature;
+
 		let iter = input.exact_chunks(group_shape.as_slice()).into_iter()
 			.zip(output.exact_chunks_mut(group_shape.as_slice()));
 		for (in_chunk, mut out_chunk) in iter {
 			let max = in_chunk.iter().fold(f32::NEG_INFINITY, |max, &v| v.max(max));
-			let sum = in_chunk.iter().fold(0., |sum, &v| sum + (v-max).exp());	
+			let sum = in_chunk.iter().fold(0., |sum, &v| sum + ((v-max)*inv_t).exp());	
 			
-			out_chunk.zip_mut_with(&in_chunk, |o, i| *o += (*i-max).exp()/sum);
+			out_chunk.zip_mut_with(&in_chunk, |o, i| *o += ((*i-max)*inv_t).exp()/sum);
 		}
 
 		Ok(Box::new(()))
@@ -190,14 +216,16 @@ struct SoftmaxBackward {
 	input_id: NodeID,
 	output_id: NodeID,
 	mask: SmallVec<[bool; 6]>,
+	temperature: f32,
 }
 
 impl SoftmaxBackward {
-	pub fn new(input_id: NodeID, output_id: NodeID, mask: SmallVec<[bool; 6]>) -> Self {
+	pub fn new(input_id: NodeID, output_id: NodeID, mask: SmallVec<[bool; 6]>, temperature: f32) -> Self {
 		SoftmaxBackward {
 			input_id,
 			output_id,
 			mask,
+			temperature,
 		}
 	}
 }
@@ -225,21 +253,23 @@ impl Pass for SoftmaxBackward {
 		);
 
 
+		let inv_t = 1.0/self.temperature;
+
 		let iter = input.exact_chunks(group_shape.as_slice()).into_iter()
 			.zip(input_grad.exact_chunks_mut(group_shape.as_slice()))
 			.zip(output_grad.exact_chunks(group_shape.as_slice()));
 		for ((in_chunk, mut in_grad_chunk), out_grad_chunk) in iter {
 
 			let max = in_chunk.iter().fold(f32::NEG_INFINITY, |max, &v| v.max(max));
-			let sum = in_chunk.iter().fold(0., |sum, &v| sum + (v-max).exp());
+			let sum = in_chunk.iter().fold(0., |sum, &v| sum + ((v-max)*inv_t).exp());
 
 			for (dim, og) in out_grad_chunk.indexed_iter() {
 				if og.abs() > 0. {// hopefully output gradients are sparse, eg from cross entropy loss
-					let a = in_chunk[&dim] - max;
-					let denom = sum*sum;
+					let a = (in_chunk[&dim] - max)*inv_t;
+					let denom = sum*sum*self.temperature;
 
 					in_grad_chunk.zip_mut_with(&in_chunk, |ig, i|{
-						let b = i - max;
+						let b = (i - max)*inv_t;
 						*ig += -(a + b).exp()*og/denom;
 					});
 
@@ -312,4 +342,82 @@ fn _softmax_mask_backprop() -> Result<()>{
 	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
 
 	Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_softmax_temperature_backprop(){
+	_softmax_temperature_backprop().unwrap();
+}
+
+fn _softmax_temperature_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![5, 16], "input", tag![])?;
+	let node2 = g.new_node(shape![5, 16], "output", tag![])?;
+	let node3 = g.new_node(shape![5, 16], "target", tag![])?;
+
+
+	let _o1 = g.new_op(Softmax::new(&node1, &node2).temperature(4.0), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_softmax_temperature(){
+	_softmax_temperature().unwrap();
+}
+
+fn _softmax_temperature() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::mse::Mse;
+	use ndarray::ArrayD;
+
+	let logits = ArrayD::from_shape_vec(&[1, 4][..], vec![2.0, -1.0, 0.5, 3.0]).unwrap();
+
+	// Returns the softmax output and the gradient w.r.t. the logits
+	let run = |temperature: f32, logits: ArrayD<f32>| -> Result<(ArrayD<f32>, ArrayD<f32>)> {
+		let mut g = GraphDef::new();
+
+		let node1 = g.new_node(shape![1, 4], "input", tag![])?;
+		let node2 = g.new_node(shape![1, 4], "output", tag![])?;
+		let node3 = g.new_node(shape![1, 4], "target", tag![])?;
+
+		let _o1 = g.new_op(Softmax::new(&node1, &node2).temperature(temperature), tag![])?;
+		let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+		let mut subgraph = g.subgraph(&[node1.value_id(), node3.value_id()], &[node2.value_id(), node1.gradient_id()])?;
+		let storage = subgraph.execute(vec![logits, ArrayD::from_elem(&[1, 4][..], 0.25)])?;
+		let output = storage.get(&node2.value_id())?.to_owned();
+		let grad = storage.get(&node1.gradient_id())?.to_owned();
+		Ok((output, grad))
+	};
+
+	let (out1, _) = run(1.0, logits.clone())?;
+	let (out4, grad4) = run(4.0, logits.clone())?;
+	let (out_scaled, grad_scaled) = run(1.0, logits.mapv(|x| x/4.0))?;
+
+	// higher temperature gives a flatter distribution
+	let range = |arr: &ArrayD<f32>| arr.iter().fold(f32::NEG_INFINITY, |m, &v| v.max(m)) - arr.iter().fold(f32::INFINITY, |m, &v| v.min(m));
+	assert!(range(&out4) < range(&out1));
+	assert!((out4.scalar_sum() - 1.0).abs() < 1e-5);
+
+	// softmax(x, T) == softmax(x/T, 1), with the gradient scaled by 1/T
+	for ((&o4, &os), (&g4, &gs)) in out4.iter().zip(&out_scaled).zip(grad4.iter().zip(&grad_scaled)) {
+		assert!((o4 - os).abs() < 1e-6);
+		assert!((g4 - gs/4.0).abs() < 1e-6);
+	}
+
+	Ok(())
+}
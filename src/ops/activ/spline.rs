@@ -214,6 +214,10 @@ impl OpInstance for SplineInstance {
 		let input_shape = shapes.get_shape(&self.input_id).clone();
 		shapes.merge_with(&self.output_id, &input_shape)
 	}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("shared_axes", format!("{:?}", &self.shared_axes[..]))]
+	}
 }
 
 
@@ -1,31 +1,51 @@
 use graph::{GraphDef, Result};
 use id::NodeID;
 use ops::Op;
-use ops::activ::elementwise::{ActivationFunc, ElementwiseInstance, elementwise_build};
+use ops::activ::elementwise::{ActivationFunc, ActivationFuncF64, ElementwiseInstance, Real, elementwise_build};
 
 #[derive(Clone, Debug)] 
 pub struct SrgbToLinearFunc{}
 
 impl ActivationFunc for SrgbToLinearFunc {
 	fn value(&self, input: f32) -> f32{
-		if input <= 0.0404482362771082{
-			input/12.92
-		} else {
-			0.001522305 + 0.012475774*input + 0.662456816212772*input*input + 0.32679397543773*input*input*input
-		}
+		srgb_to_linear(input)
 	}
 
 	fn gradient(&self, input: f32, output_grad: f32) -> f32{
-		if input <= 0.0404482362771082{
-			 output_grad/12.92
-		} else {
-			 output_grad*(0.012475774 + 2.0*0.662456816212772*input + 3.0*0.32679397543773*input*input)
-		}
+		srgb_to_linear_gradient(input, output_grad)
 	}
 
 	fn backprop_requires_input_value() -> bool {true}
 }
 
+impl ActivationFuncF64 for SrgbToLinearFunc {
+	fn value_f64(&self, input: f64) -> f64{
+		srgb_to_linear(input)
+	}
+
+	fn gradient_f64(&self, input: f64, output_grad: f64) -> f64{
+		srgb_to_linear_gradient(input, output_grad)
+	}
+}
+
+fn srgb_to_linear<T: Real>(input: T) -> T {
+	let c = T::from_f64;
+	if input <= c(0.0404482362771082){
+		input/c(12.92)
+	} else {
+		c(0.001522305) + c(0.012475774)*input + c(0.662456816212772)*input*input + c(0.32679397543773)*input*input*input
+	}
+}
+
+fn srgb_to_linear_gradient<T: Real>(input: T, output_grad: T) -> T {
+	let c = T::from_f64;
+	if input <= c(0.0404482362771082){
+		output_grad/c(12.92)
+	} else {
+		output_grad*(c(0.012475774) + c(2.0*0.662456816212772)*input + c(3.0*0.32679397543773)*input*input)
+	}
+}
+
 #[must_use]
 #[derive(Clone, Debug)] 
 pub struct SrgbToLinear {
@@ -67,28 +87,49 @@ pub struct LinearToSrgbFunc{}
 
 impl ActivationFunc for LinearToSrgbFunc {
 	fn value(&self, input: f32) -> f32{
-		if input <= 0.00313066844250063{
-			input*12.92
-		} else {
-			let s1 = input.sqrt();
-			let s2 = s1.sqrt();
-			-0.074312538 + 0.852548197*s1 + 0.284336309*s2 - 0.063628643*input
-		}
+		linear_to_srgb(input)
 	}
 
 	fn gradient(&self, input: f32, output_grad: f32) -> f32{
-		if input <= 0.00313066844250063{
-			output_grad*12.92
-		} else {
-			let s1 = input.sqrt();
-			let s2 = s1.sqrt();
-			output_grad*(0.5*0.852548197/s1+ 0.25*0.284336309/(s1*s2) - 0.063628643)
-		}
+		linear_to_srgb_gradient(input, output_grad)
 	}
 
 	fn backprop_requires_input_value() -> bool {true}
 }
 
+impl ActivationFuncF64 for LinearToSrgbFunc {
+	fn value_f64(&self, input: f64) -> f64{
+		linear_to_srgb(input)
+	}
+
+	fn gradient_f64(&self, input: f64, output_grad: f64) -> f64{
+		linear_to_srgb_gradient(input, output_grad)
+	}
+}
+
+fn linear_to_srgb<T: Real>(input: T) -> T {
+	let c = T::from_f64;
+	if input <= c(0.00313066844250063){
+		input*c(12.92)
+	} else {
+		let s1 = input.sqrt();
+		let s2 = s1.sqrt();
+		c(-0.074312538) + c(0.852548197)*s1 + c(0.284336309)*s2 - c(0.063628643)*input
+	}
+}
+
+fn linear_to_srgb_gradient<T: Real>(input: T, output_grad: T) -> T {
+	let c = T::from_f64;
+	if input <= c(0.00313066844250063){
+		output_grad*c(12.92)
+	} else {
+		// the derivative with respect to s2, divided by d(input)/d(s2) = 4*s2^3, avoids summing separately rounded reciprocal terms
+		let s1 = input.sqrt();
+		let s2 = s1.sqrt();
+		output_grad*(c(0.284336309) + s2*(c(1.705096394) - c(0.254514572)*s1))/(c(4.0)*s1*s2)
+	}
+}
+
 
 #[must_use]
 #[derive(Clone, Debug)] 
@@ -251,28 +292,8 @@ fn test_srgb_to_linear_backprop(){
 }
 
 fn _srgb_to_linear_backprop() -> Result<()>{
-	use graph::GraphDef;
-	use ops::numeric_check::numeric_test;
-	use ops::loss::mse::Mse;
-
-	let mut g = GraphDef::new();
-
-	let node1 = g.new_node(shape![7, 5, 16], "input", tag![])?;
-	let node2 = g.new_node(shape![7, 5, 16], "output", tag![])?;
-	let node3 = g.new_node(shape![7, 5, 16], "target", tag![])?;
-
-
-	let _o1 = g.new_op(SrgbToLinear::new(&node1, &node2), tag![])?;
-	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
-
-	let iters = 100;
-	let failures = 1;
-	let tolerance = 0.002;
-	let step_size = 1E-2;
-	let default_variance = 1.0;
-	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
-
-	Ok(())
+	use ops::numeric_check::check_elementwise_op;
+	check_elementwise_op(|i, o| SrgbToLinear::new(i, o), shape![7, 5, 16], 0.002)
 }
 
 #[test]
@@ -281,28 +302,8 @@ fn test_linear_to_srgb_backprop(){
 }
 
 fn _linear_to_srgb_backprop() -> Result<()>{
-	use graph::GraphDef;
-	use ops::numeric_check::numeric_test;
-	use ops::loss::mse::Mse;
-
-	let mut g = GraphDef::new();
-
-	let node1 = g.new_node(shape![7, 5, 16], "input", tag![])?;
-	let node2 = g.new_node(shape![7, 5, 16], "output", tag![])?;
-	let node3 = g.new_node(shape![7, 5, 16], "target", tag![])?;
-
-
-	let _o1 = g.new_op(LinearToSrgb::new(&node1, &node2), tag![])?;
-	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
-
-	let iters = 100;
-	let failures = 1;
-	let tolerance = 0.005; // why is the accuracy so much worse? cancellation at the high end?
-	let step_size = 1E-2;
-	let default_variance = 0.5;
-	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
-
-	Ok(())
+	use ops::numeric_check::check_elementwise_op_variance;
+	check_elementwise_op_variance(|i, o| LinearToSrgb::new(i, o), shape![7, 5, 16], 0.002, 0.5)
 }
 
 #[test]
@@ -311,28 +312,8 @@ fn test_srgb_to_linear_slow_backprop(){
 }
 
 fn _srgb_to_linear_slow_backprop() -> Result<()>{
-	use graph::GraphDef;
-	use ops::numeric_check::numeric_test;
-	use ops::loss::mse::Mse;
-
-	let mut g = GraphDef::new();
-
-	let node1 = g.new_node(shape![7, 5, 16], "input", tag![])?;
-	let node2 = g.new_node(shape![7, 5, 16], "output", tag![])?;
-	let node3 = g.new_node(shape![7, 5, 16], "target", tag![])?;
-
-
-	let _o1 = g.new_op(SrgbToLinearSlow::new(&node1, &node2), tag![])?;
-	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
-
-	let iters = 100;
-	let failures = 1;
-	let tolerance = 0.002;
-	let step_size = 1E-2;
-	let default_variance = 1.0;
-	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
-
-	Ok(())
+	use ops::numeric_check::check_elementwise_op;
+	check_elementwise_op(|i, o| SrgbToLinearSlow::new(i, o), shape![7, 5, 16], 0.002)
 }
 
 #[test]
@@ -341,26 +322,7 @@ fn test_linear_to_srgb_slow_backprop(){
 }
 
 fn _linear_to_srgb_slow_backprop() -> Result<()>{
-	use graph::GraphDef;
-	use ops::numeric_check::numeric_test;
-	use ops::loss::mse::Mse;
-
-	let mut g = GraphDef::new();
-
-	let node1 = g.new_node(shape![7, 5, 16], "input", tag![])?;
-	let node2 = g.new_node(shape![7, 5, 16], "output", tag![])?;
-	let node3 = g.new_node(shape![7, 5, 16], "target", tag![])?;
-
-
-	let _o1 = g.new_op(LinearToSrgbSlow::new(&node1, &node2), tag![])?;
-	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
-
-	let iters = 100;
-	let failures = 1;
-	let tolerance = 0.005; // why is the accuracy so much worse? cancellation at the high end?
-	let step_size = 1E-2;
-	let default_variance = 0.5;
-	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
-
-	Ok(())
+	use ops::numeric_check::check_elementwise_op_variance;
+	// why is the accuracy so much worse? cancellation at the high end?
+	check_elementwise_op_variance(|i, o| LinearToSrgbSlow::new(i, o), shape![7, 5, 16], 0.005, 0.5)
 }
\ No newline at end of file
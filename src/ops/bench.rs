@@ -0,0 +1,64 @@
+use graph::{GraphDef, Result, Dependencies};
+use id::NodeID;
+use ops::Op;
+use ops::loss::mse::Mse;
+use ops::numeric_check::generate_input_data;
+use shape::NodeShape;
+use indexmap::IndexMap;
+use std::time::Duration;
+
+/// Returns the mean time spent in the forward and backward passes of an op which maps an input node to an output node of the same shape.
+///
+/// The graph is built as for `check_elementwise_op`, with `op_ctor` called with the input and output nodes, both of the given `shape`, and an `Mse` loss applied between the output and a target node.
+/// Each of the `iters` runs draws new inputs and parameters from N(0, 1), and the subgraph profiler times the passes of the op, including any inner ops, but not the loss.
+///
+/// e.g. `let (forward, backward) = bench_op(|i, o| Tanh::new(i, o), shape![64, 1024], 100)?;`
+pub fn bench_op<O: Op, F: FnOnce(&NodeID, &NodeID) -> O>(op_ctor: F, shape: NodeShape, iters: usize) -> Result<(Duration, Duration)> {
+	ensure!(iters > 0, "bench_op requires at least one iteration");
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape.clone(), "input", tag![])?;
+	let output = g.new_node(shape.clone(), "output", tag![])?;
+	let target = g.new_node(shape, "target", tag![])?;
+
+	let _o1 = g.new_op(op_ctor(&input, &output), tag![])?;
+	let o2 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+	// inputs and parameters
+	let dependencies = Dependencies::new(&g);
+	let leaf_ids: Vec<NodeID> = g.get_nodes().iter().filter(|node_id| dependencies.data_inputs(&node_id.value_id()).len() == 0).cloned().collect();
+
+	let mut subgraph = g.subgraph(
+		&leaf_ids.iter().map(|node_id| node_id.value_id()).collect::<Vec<_>>(),
+		&leaf_ids.iter().map(|node_id| node_id.gradient_id()).collect::<Vec<_>>())?;
+	subgraph.enable_profiling(true);
+
+	let mut no_overrides: IndexMap<NodeID, Box<FnMut()->f64>> = IndexMap::new();
+	for _ in 0..iters {
+		let data = generate_input_data(&leaf_ids, 1.0, &mut no_overrides)?;
+		subgraph.execute(data)?;
+	}
+
+	let (forward, backward) = subgraph.profile_report().into_iter()
+		.filter(|&(ref op_id, _, _, _)| op_id != &o2)
+		.fold((Duration::new(0, 0), Duration::new(0, 0)), |(forward, backward), (_, _, op_forward, op_backward)| (forward + op_forward, backward + op_backward));
+
+	Ok((forward / iters as u32, backward / iters as u32))
+}
+
+
+#[test]
+fn test_bench_op(){
+	_bench_op().unwrap();
+}
+
+fn _bench_op() -> Result<()>{
+	use ops::activ::relu::ReLU;
+
+	let (forward, backward) = bench_op(|input, output| ReLU::new(input, output), shape![64, 256], 5)?;
+	assert!(forward > Duration::new(0, 0), "{:?}", forward);
+	assert!(backward > Duration::new(0, 0), "{:?}", backward);
+
+	Ok(())
+}
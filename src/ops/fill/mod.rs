@@ -1 +1,2 @@
-pub mod coord;
\ No newline at end of file
+pub mod coord;
+pub mod positional_encoding;
\ No newline at end of file
@@ -0,0 +1,228 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use ndarray::{Axis, Ix2};
+use std::any::Any;
+
+/// An `Op` which fills the output with sinusoidal positional encodings
+///
+/// The last two axes of the output are taken to be [seq_len, dim], and any outer axes are filled identically.
+/// For position `pos` and channel pair `i`:
+///
+/// PE(pos, 2i) = sin(pos / base^(2i/dim))
+/// PE(pos, 2i+1) = cos(pos / base^(2i/dim))
+///
+/// The encoding is constant, so it has no parameters and no gradients are produced.
+/// The output can be added to embeddings using the `Add` op.
+#[must_use]
+#[derive(Clone, Debug)] 
+pub struct PositionalEncoding {
+	output_id: NodeID,
+	base: f32,
+	name: Option<String>,
+}
+
+impl PositionalEncoding {
+	pub fn new(output_id: &NodeID) -> Self {
+		PositionalEncoding {
+			output_id: output_id.clone(),
+			base: 10000.0,
+			name: None,
+		}
+	}
+
+	/// The base of the geometric progression of wavelengths.
+	///
+	/// Default: 10000.0
+	pub fn base(mut self, base: f32) -> Self {
+		self.base = base;
+		self
+	}
+}
+
+impl Op for PositionalEncoding {
+	type InstanceType = PositionalEncodingInstance;
+
+	fn type_name(&self) -> &'static str {
+		"PositionalEncoding"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.output_id.shape().ndim() >= 2, "PositionalEncoding output must have at least two axes, [seq_len, dim]");
+		let name = standard_op_name(&self, &self.name, graph, &[], &[self.output_id.clone()]);
+
+		Ok(PositionalEncodingInstance{
+			name: name,
+			output_id: self.output_id.clone(),
+			base: self.base,
+			forward_id: graph.add_pass(PositionalEncodingForward::new(
+				self.output_id.clone(),
+				self.base)),
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)] 
+pub struct PositionalEncodingInstance {
+	name: String,
+	output_id: NodeID,
+	base: f32,
+	forward_id: PassID,
+}
+
+impl OpInstance for PositionalEncodingInstance {
+
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(vec![], vec![self.output_id.clone()])
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.forward_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {
+		vec![]
+	}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {
+		vec![]
+	}
+
+	fn propagate_shape_constraints(&self, _shapes: &mut GraphShapes) -> Result<()>{
+		Ok(())
+	}
+}
+
+/// Returns the encoding of channel `channel` at position `pos`.
+fn encoding(pos: usize, channel: usize, dim: usize, base: f32) -> f32 {
+	let pair = (channel / 2) * 2;
+	let angle = pos as f32 / base.powf(pair as f32 / dim as f32);
+	if channel % 2 == 0 {
+		angle.sin()
+	} else {
+		angle.cos()
+	}
+}
+
+#[derive(Clone, Debug)]
+struct PositionalEncodingForward {
+	output_id: NodeID,
+	base: f32,
+}
+
+impl PositionalEncodingForward {
+	pub fn new(output_id: NodeID, base: f32) -> Self {
+		PositionalEncodingForward {
+			output_id,
+			base,
+		}
+	}
+}
+
+impl Pass for PositionalEncodingForward {
+	fn type_name(&self) -> &'static str {"PositionalEncodingForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![], vec![self.output_id.value_id()])
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let mut output = data.get_mut(&self.output_id.value_id())?;
+
+		ensure!(
+			output.ndim() >= 2,
+			ErrorKind::PassError(self.name(), format!("output shape: {:?} must have at least two axes", output.shape()))
+		);
+
+		let ndim = output.ndim();
+		let seq_len = output.shape()[ndim - 2];
+		let dim = output.shape()[ndim - 1];
+		let outer: usize = output.shape()[..ndim - 2].iter().product();
+
+		let mut output = output.view_mut().into_shape(Ix2(outer * seq_len, dim)).expect("output must be contiguous");
+		for (i, mut row) in output.axis_iter_mut(Axis(0)).enumerate() {
+			let pos = i % seq_len;
+			for (channel, e) in row.iter_mut().enumerate() {
+				*e += encoding(pos, channel, dim, self.base);
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_positional_encoding(){
+	_positional_encoding().unwrap();
+}
+
+fn _positional_encoding() -> Result<()>{
+	use graph::GraphDef;
+
+	let mut g = GraphDef::new();
+
+	let output = g.new_node(shape![6, 8], "output", tag![])?;
+	let _o1 = g.new_op(PositionalEncoding::new(&output), tag![])?;
+
+	let mut subgraph = g.subgraph(&[], &[output.value_id()])?;
+	let storage = subgraph.execute(vec![])?;
+	let out = storage.get(&output.value_id())?.into_dimensionality::<Ix2>().unwrap();
+
+	assert_eq!(out[[0, 0]], 0.0);
+	assert_eq!(out[[0, 1]], 1.0);
+	assert!((out[[1, 0]] - 1.0f32.sin()).abs() < 1e-6);
+	assert!((out[[1, 1]] - 1.0f32.cos()).abs() < 1e-6);
+	assert!((out[[3, 2]] - (3.0 / 10000f32.powf(2.0/8.0)).sin()).abs() < 1e-6);
+	assert!((out[[3, 3]] - (3.0 / 10000f32.powf(2.0/8.0)).cos()).abs() < 1e-6);
+	assert!((out[[5, 7]] - (5.0 / 10000f32.powf(6.0/8.0)).cos()).abs() < 1e-6);
+
+	Ok(())
+}
+
+#[test]
+fn test_positional_encoding_add(){
+	_positional_encoding_add().unwrap();
+}
+
+fn _positional_encoding_add() -> Result<()>{
+	use graph::GraphDef;
+	use ops::math::add::Add;
+	use ops::loss::proportional::Proportional;
+	use ndarray::ArrayD;
+
+	let mut g = GraphDef::new();
+
+	let embeddings = g.new_node(shape![3, 6, 8], "embeddings", tag![])?;
+	let encoding = g.new_node(shape![6, 8], "encoding", tag![])?;
+	let output = g.new_node(shape![3, 6, 8], "output", tag![])?;
+	let _o1 = g.new_op(PositionalEncoding::new(&encoding), tag![])?;
+	let _o2 = g.new_op(Add::new(&embeddings, &output), tag![])?;
+	let _o3 = g.new_op(Add::new(&encoding, &output), tag![])?;
+	let _o4 = g.new_op(Proportional::new(&output), tag![])?;
+
+	let mut subgraph = g.subgraph(&[embeddings.value_id()], &[output.value_id(), encoding.value_id(), embeddings.gradient_id()])?;
+	let storage = subgraph.execute(vec![ArrayD::from_elem(&[3, 6, 8][..], 2.0)])?;
+
+	// each batch element receives the same encoding, and gradients pass through to the embeddings unchanged
+	let out = storage.get(&output.value_id())?;
+	let enc = storage.get(&encoding.value_id())?;
+	for batch in out.outer_iter() {
+		for (&o, &e) in batch.iter().zip(enc.iter()) {
+			assert!((o - (e + 2.0)).abs() < 1e-6);
+		}
+	}
+	let n = (3 * 6 * 8) as f32;
+	assert!(storage.get(&embeddings.gradient_id())?.iter().all(|&g| (g - 1.0/n).abs() < 1e-7));
+
+	Ok(())
+}
@@ -0,0 +1,429 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use ndarray::{ArrayViewD, Axis, Zip};
+use std::any::Any;
+use std::f32;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// The running (min, max) of each channel, shared between the op instance and its forward pass.
+#[derive(Clone)]
+struct SharedRange(Arc<Mutex<Option<Vec<(f32, f32)>>>>);
+
+impl fmt::Debug for SharedRange {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "SharedRange")
+	}
+}
+
+/// FakeQuant Op
+///
+/// Simulates per-channel asymmetric integer quantisation for quantisation-aware training.
+/// The input is rounded to the grid `(q - zero_point) * scale`, for integers `q` in `[0, 2^bits - 1]`, and values outside the representable range are clamped.
+///
+/// The `scale` and `zero_point` of each channel are calculated from a running min and max of that channel, which always includes zero.
+/// When gradients are being calculated through the op the running range is updated as `r = momentum r + (1 - momentum) r_batch`,
+/// with the first batch initialising it. When the op is only being evaluated the running range is left unchanged.
+///
+/// The backward pass is a straight through estimator: the output gradient is passed through unchanged where the input is within the representable range, and is zero outside.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct FakeQuant {
+	name: Option<String>,
+	input_id: NodeID,
+	output_id: NodeID,
+	bits: u32,
+	axis: isize,
+	momentum: f32,
+}
+
+impl FakeQuant {
+	pub fn new(input_id: &NodeID, output_id: &NodeID) -> Self{
+		FakeQuant {
+			name: None,
+			input_id: input_id.clone(),
+			output_id: output_id.clone(),
+			bits: 8,
+			axis: -1,
+			momentum: 0.99,
+		}
+	}
+
+	/// The number of bits of the simulated integer type, in the range [1, 16].
+	///
+	/// Default: 8
+	pub fn bits(mut self, bits: u32) -> Self {
+		self.bits = bits;
+		self
+	}
+
+	/// The channel axis, each channel has its own `scale` and `zero_point`.
+	///
+	/// Can be in the range [-input.ndims(), input.ndims()).
+	/// Default: -1
+	pub fn axis(mut self, axis: isize) -> Self {
+		self.axis = axis;
+		self
+	}
+
+	/// The decay of the running min and max, in the range [0, 1].
+	///
+	/// Default: 0.99
+	pub fn momentum(mut self, momentum: f32) -> Self {
+		self.momentum = momentum;
+		self
+	}
+}
+
+impl Op for FakeQuant {
+	type InstanceType = FakeQuantInstance;
+
+	fn type_name(&self) -> &'static str {
+		"FakeQuant"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.bits >= 1 && self.bits <= 16, format!("FakeQuant bits {} must be in the range [1, 16]", self.bits));
+		ensure!(self.momentum >= 0.0 && self.momentum <= 1.0, format!("FakeQuant momentum {} must be in the range [0, 1]", self.momentum));
+		let ndim = self.input_id.shape().ndim();
+		ensure!(self.axis >= -(ndim as isize) && self.axis < ndim as isize, format!("FakeQuant axis {} is out of range for input with {} axes", self.axis, ndim));
+		let axis = (self.axis + ndim as isize) as usize % ndim;
+
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]);
+
+		let range = SharedRange(Arc::new(Mutex::new(None)));
+
+		let forward_id = graph.add_pass(FakeQuantForward::new(
+			self.input_id.clone(),
+			self.output_id.clone(),
+			self.bits,
+			axis,
+			self.momentum,
+			range.clone()));
+
+		let backward_id = graph.add_pass(FakeQuantBackward::new(
+			self.input_id.clone(),
+			self.output_id.clone(),
+			self.bits,
+			axis,
+			forward_id.clone()));
+
+		Ok(FakeQuantInstance{
+			name: name,
+			input_id: self.input_id.clone(),
+			output_id: self.output_id.clone(),
+			bits: self.bits,
+			axis: axis,
+			range: range,
+			forward_id: forward_id,
+			backward_id: backward_id,
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)]
+pub struct FakeQuantInstance{
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	bits: u32,
+	axis: usize,
+	range: SharedRange,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl FakeQuantInstance {
+	/// The channel axis, in the range [0, input.ndims())
+	pub fn axis(&self) -> usize {
+		self.axis
+	}
+
+	/// The running (min, max) of each channel, or `None` if the op has not yet been executed while calculating gradients.
+	pub fn running_range(&self) -> Option<Vec<(f32, f32)>> {
+		self.range.0.lock().expect("Could not acquire lock on FakeQuant range").clone()
+	}
+
+	/// The (scale, zero_point) of each channel derived from the running range, as used when exporting to an integer format.
+	pub fn quantisation_params(&self) -> Option<Vec<(f32, f32)>> {
+		self.running_range().map(|range| range.iter().map(|&(min, max)| quantisation_params(min, max, self.bits)).collect())
+	}
+}
+
+impl OpInstance for FakeQuantInstance {
+
+	fn name(&self) -> &str{&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){(vec![self.input_id.clone()], vec![self.output_id.clone()])}
+
+	fn inner_passes(&self) -> Vec<PassID>{vec![self.forward_id.clone(), self.backward_id.clone()]}
+
+	fn inner_ops(&self) -> Vec<OpID>{vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID>{vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+		let input_shape = shapes.get_shape(&self.input_id).clone();
+		shapes.merge_with(&self.output_id, &input_shape)
+	}
+
+}
+
+/// Returns the (scale, zero_point) which maps `[0, 2^bits - 1]` over a range, widened if necessary to include zero
+fn quantisation_params(min: f32, max: f32, bits: u32) -> (f32, f32) {
+	let q_max = ((1u32 << bits) - 1) as f32;
+	let min = min.min(0.0);
+	let max = max.max(0.0);
+	let scale = if max > min {(max - min) / q_max} else {1.0};
+	let zero_point = (-min / scale).round().max(0.0).min(q_max);
+	(scale, zero_point)
+}
+
+/// Returns the (min, max) of each channel of the input
+fn channel_ranges(input: &ArrayViewD<f32>, axis: usize) -> Vec<(f32, f32)> {
+	input.axis_iter(Axis(axis)).map(|channel| {
+		channel.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &x| (min.min(x), max.max(x)))
+	}).collect()
+}
+
+fn check_shapes(pass_name: String, input_shape: &[usize], output_shape: &[usize], axis: usize) -> Result<()> {
+	ensure!(input_shape == output_shape,
+		ErrorKind::PassError(pass_name.clone(), format!("input shape: {:?} did not match output shape: {:?}", input_shape, output_shape)));
+	ensure!(axis < input_shape.len(),
+		ErrorKind::PassError(pass_name, format!("axis {} is out of range for input shape: {:?}", axis, input_shape)));
+	Ok(())
+}
+
+
+#[derive(Clone, Debug)]
+struct FakeQuantForward {
+	input_id: NodeID,
+	output_id: NodeID,
+	bits: u32,
+	axis: usize,
+	momentum: f32,
+	range: SharedRange,
+}
+
+impl FakeQuantForward {
+	fn new(input_id: NodeID, output_id: NodeID, bits: u32, axis: usize, momentum: f32, range: SharedRange) -> Self {
+		FakeQuantForward {
+			input_id,
+			output_id,
+			bits,
+			axis,
+			momentum,
+			range,
+		}
+	}
+}
+
+impl Pass for FakeQuantForward {
+	fn type_name(&self) -> &'static str {"FakeQuantForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.input_id.value_id()],
+			vec![self.output_id.value_id()]
+		)
+	}
+
+	/// Returns the (scale, zero_point) of each channel as pass data
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let input = data.get(&self.input_id.value_id())?;
+		let mut output = data.get_mut(&self.output_id.value_id())?;
+
+		check_shapes(self.name(), input.shape(), output.shape(), self.axis)?;
+
+		let batch_range = channel_ranges(&input, self.axis);
+
+		// only update the running range if training, which is when the gradient flows back through the op
+		let range = {
+			let mut running_range = self.range.0.lock().expect("Could not acquire lock on FakeQuant range");
+			let training = data.is_required(&self.output_id.gradient_id());
+			match *running_range {
+				Some(ref mut running_range) => {
+					ensure!(running_range.len() == batch_range.len(),
+						ErrorKind::PassError(self.name(), format!("input has {} channels, but the running range has {}", batch_range.len(), running_range.len())));
+					if training {
+						let momentum = self.momentum;
+						for (&mut (ref mut min, ref mut max), &(batch_min, batch_max)) in running_range.iter_mut().zip(&batch_range) {
+							*min = momentum * *min + (1.0 - momentum) * batch_min;
+							*max = momentum * *max + (1.0 - momentum) * batch_max;
+						}
+					}
+				},
+				None => if training {
+					*running_range = Some(batch_range.clone());
+				},
+			}
+			running_range.clone().unwrap_or(batch_range)
+		};
+
+		let params: Vec<(f32, f32)> = range.iter().map(|&(min, max)| quantisation_params(min, max, self.bits)).collect();
+		let q_max = ((1u32 << self.bits) - 1) as f32;
+
+		for ((input, mut output), &(scale, zero_point)) in input.axis_iter(Axis(self.axis)).zip(output.axis_iter_mut(Axis(self.axis))).zip(&params) {
+			Zip::from(&mut output).and(&input).apply(|output, &x| {
+				let q = ((x / scale).round() + zero_point).max(0.0).min(q_max);
+				*output += (q - zero_point) * scale;
+			});
+		}
+
+		Ok(Box::new(params))
+	}
+}
+
+
+#[derive(Clone, Debug)]
+struct FakeQuantBackward {
+	input_id: NodeID,
+	output_id: NodeID,
+	bits: u32,
+	axis: usize,
+	forward_id: PassID,
+}
+
+impl FakeQuantBackward {
+	fn new(input_id: NodeID, output_id: NodeID, bits: u32, axis: usize, forward_id: PassID) -> Self {
+		FakeQuantBackward {
+			input_id,
+			output_id,
+			bits,
+			axis,
+			forward_id,
+		}
+	}
+}
+
+impl Pass for FakeQuantBackward {
+	fn type_name(&self) -> &'static str {"FakeQuantBackward"}
+
+	/// The output value is listed only to ensure the forward pass, which calculates the quantisation parameters, runs first.
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.input_id.value_id(), self.output_id.value_id(), self.output_id.gradient_id()],
+			vec![self.input_id.gradient_id()]
+		)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let params = match data.get_pass_data(&self.forward_id).and_then(|pass_data| pass_data.downcast_ref::<Vec<(f32, f32)>>()) {
+			Some(params) => params,
+			None => bail!(ErrorKind::PassError(self.name(), "forward pass data was not available".to_string())),
+		};
+
+		let input = data.get(&self.input_id.value_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+		let mut input_grad = data.get_mut(&self.input_id.gradient_id())?;
+
+		check_shapes(self.name(), input.shape(), output_grad.shape(), self.axis)?;
+		ensure!(params.len() == input.shape()[self.axis],
+			ErrorKind::PassError(self.name(), format!("input has {} channels, but the forward pass used {}", input.shape()[self.axis], params.len())));
+
+		let q_max = ((1u32 << self.bits) - 1) as f32;
+
+		let iter = input.axis_iter(Axis(self.axis))
+			.zip(output_grad.axis_iter(Axis(self.axis)))
+			.zip(input_grad.axis_iter_mut(Axis(self.axis)))
+			.zip(params);
+		for (((input, output_grad), mut input_grad), &(scale, zero_point)) in iter {
+			let low = -zero_point * scale;
+			let high = (q_max - zero_point) * scale;
+			Zip::from(&mut input_grad).and(&input).and(&output_grad).apply(|input_grad, &x, &output_grad| {
+				if x >= low && x <= high {
+					*input_grad += output_grad;
+				}
+			});
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_fake_quant(){
+	_fake_quant().unwrap();
+}
+
+fn _fake_quant() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::proportional::Proportional;
+	use ndarray::{ArrayD, Dimension, IxDyn};
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![5, 3], "input", tag![])?;
+	let output = g.new_node(shape![5, 3], "output", tag![])?;
+
+	// a momentum of 1 keeps the range set by the first batch
+	let o1 = g.new_op(FakeQuant::new(&input, &output).bits(4).momentum(1.0), tag![])?;
+	let _o2 = g.new_op(Proportional::new(&output).multiplier(15.0), tag![])?;
+
+	let input_data = ArrayD::from_shape_vec(IxDyn(&[5, 3]), vec![
+		-1.0, 0.5, -3.0,
+		-0.3, 1.7, -2.2,
+		0.0, 2.9, -0.4,
+		0.61, 0.1, -1.9,
+		2.0, 3.0, -0.01,
+	]).unwrap();
+
+	let mut subgraph = g.subgraph(&[input.value_id()], &[output.value_id(), input.gradient_id()])?;
+	let storage = subgraph.execute(vec![input_data.clone()])?;
+
+	let instance = o1.instance().as_any().downcast_ref::<FakeQuantInstance>().unwrap();
+	let range = instance.running_range().unwrap();
+	assert_eq!(range, vec![(-1.0, 2.0), (0.1, 3.0), (-3.0, -0.01)]);
+	let params = instance.quantisation_params().unwrap();
+
+	// outputs lie on the grid and within the representable range, and are no more than half a step from the input
+	let out = storage.get(&output.value_id())?;
+	for ((idx, &y), &x) in out.indexed_iter().zip(input_data.iter()) {
+		let (scale, zero_point) = params[idx[1]];
+		let q = y / scale + zero_point;
+		assert!((q - q.round()).abs() < 1e-3, "{:?} {} {}", idx.slice(), y, q);
+		assert!(q.round() >= 0.0 && q.round() <= 15.0);
+		assert!((y - x).abs() <= 0.5 * scale + 1e-6, "{:?} {} {}", idx.slice(), y, x);
+	}
+
+	// the gradient passes straight through inside the range
+	let grad = storage.get(&input.gradient_id())?;
+	assert!(grad.iter().all(|&x| (x - 1.0).abs() < 1e-6), "{:?}", grad);
+
+	// and is zero outside the clip range, where the output is clamped
+	let outlier_data = ArrayD::from_shape_vec(IxDyn(&[5, 3]), vec![
+		-10.0, 0.5, -3.0,
+		0.5, 1.7, -2.2,
+		0.0, 30.0, -0.4,
+		0.5, 0.1, 5.0,
+		2.0, 3.0, -0.01,
+	]).unwrap();
+	let storage = subgraph.execute(vec![outlier_data.clone()])?;
+	let out = storage.get(&output.value_id())?;
+	let grad = storage.get(&input.gradient_id())?;
+	for ((idx, &g), &x) in grad.indexed_iter().zip(outlier_data.iter()) {
+		let (scale, zero_point) = params[idx[1]];
+		let (low, high) = (-zero_point * scale, (15.0 - zero_point) * scale);
+		if x < low || x > high {
+			assert_eq!(g, 0.0, "{:?}", idx.slice());
+			assert!(out[idx.slice()] == low || out[idx.slice()] == high);
+		} else {
+			assert!((g - 1.0).abs() < 1e-6, "{:?}", idx.slice());
+		}
+	}
+	assert_eq!(grad[[0, 0]], 0.0);
+	assert_eq!(grad[[2, 1]], 0.0);
+	assert_eq!(grad[[3, 2]], 0.0);
+	assert_eq!(instance.running_range().unwrap(), range);
+
+	Ok(())
+}
@@ -1 +1,2 @@
-pub mod stop_grad;
\ No newline at end of file
+pub mod stop_grad;
+pub mod fake_quant;
\ No newline at end of file
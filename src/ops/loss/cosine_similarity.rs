@@ -0,0 +1,237 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use ndarray::Axis;
+use std::any::Any;
+
+/// An `Op` which implements the Cosine Similarity Loss
+///
+/// The loss is `1 - cos(input, target)` averaged over the outermost axis, where each outer index is a separate sample
+/// and the cosine similarity is calculated over all remaining axes.
+///
+/// The target is treated as a constant, and only the input receives gradients.
+/// Both input and target vectors must be nonzero.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct CosineSimilarityLoss {
+	input_id: NodeID,
+	target_id: NodeID,
+	multiplier: f32,
+	name: Option<String>,
+}
+
+impl CosineSimilarityLoss {
+	pub fn new(input: &NodeID, target: &NodeID) -> Self {
+		CosineSimilarityLoss {
+			input_id: input.clone(),
+			target_id: target.clone(),
+			multiplier: 1.0,
+			name: None,
+		}
+	}
+
+	/// Applies a multiplier to the loss generated.
+	pub fn multiplier(mut self, multiplier: f32) -> Self {
+		self.multiplier = multiplier;
+		self
+	}
+}
+
+impl Op for CosineSimilarityLoss {
+	type InstanceType = CosineSimilarityLossInstance;
+
+	fn type_name(&self) -> &'static str {
+		"CosineSimilarityLoss"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone(), self.target_id.clone()], &[]);
+
+		Ok(CosineSimilarityLossInstance{
+			name: name,
+			multiplier: self.multiplier,
+			input_id: self.input_id.clone(),
+			target_id: self.target_id.clone(),
+			pass_id: graph.add_pass(CosineSimilarityLossJointPass::new(
+				self.multiplier,
+				self.input_id.clone(),
+				self.target_id.clone())),
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)] 
+pub struct CosineSimilarityLossInstance {
+	name: String,
+	multiplier: f32,
+	input_id: NodeID,
+	target_id: NodeID,
+	pass_id: PassID,
+}
+
+impl OpInstance for CosineSimilarityLossInstance {
+
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(vec![self.input_id.clone(), self.target_id.clone()], vec![])
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.pass_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {
+		vec![]
+	}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {
+		vec![]
+	}
+
+	fn propagate_shape_constraints(&self, _shapes: &mut GraphShapes) -> Result<()>{
+		Ok(())
+	}
+}
+
+
+#[derive(Clone, Debug)]
+struct CosineSimilarityLossJointPass {
+	multiplier: f32,
+	input_id: NodeID,
+	target_id: NodeID,
+}
+
+impl CosineSimilarityLossJointPass {
+	pub fn new(multiplier: f32, input_id: NodeID, target_id: NodeID) -> Self {
+		CosineSimilarityLossJointPass {
+			multiplier,
+			input_id,
+			target_id,
+		}
+	}
+}
+
+impl Pass for CosineSimilarityLossJointPass {
+	fn type_name(&self) -> &'static str {"CosineSimilarityLossJointPass"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id(), self.target_id.value_id()],
+		vec![self.input_id.gradient_id()])
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let input = data.get(&self.input_id.value_id())?;
+		let target = data.get(&self.target_id.value_id())?;
+
+		ensure!(
+			input.shape() == target.shape(),
+			ErrorKind::PassError(self.name(), format!("input shape: {:?} did not match target shape: {:?}", input.shape(), target.shape()))
+		);
+		ensure!(
+			input.ndim() > 0,
+			ErrorKind::PassError(self.name(), format!("input must have an outer axis, but had shape: {:?}", input.shape()))
+		);
+
+		let n = input.shape()[0];
+		let multiplier = self.multiplier/n as f32;
+		let grad_multiplier = multiplier * data.loss_scale();
+
+		let mut error = 0.0;
+
+		if data.is_required(&self.input_id.gradient_id()) {
+			let mut input_grad = data.get_mut(&self.input_id.gradient_id())?;
+
+			let iter = input.outer_iter().zip(target.outer_iter()).zip(input_grad.axis_iter_mut(Axis(0)));
+			for ((input, target), mut input_grad) in iter {
+				let (dot, input_sqr, target_sqr) = input.iter().zip(target.iter())
+					.fold((0.0, 0.0, 0.0), |(dot, input_sqr, target_sqr), (&x, &t)| (dot + x*t, input_sqr + x*x, target_sqr + t*t));
+				let input_norm = input_sqr.sqrt();
+				let target_norm = target_sqr.sqrt();
+				let cos = dot/(input_norm*target_norm);
+
+				error += (1.0 - cos)*multiplier;
+
+				// d(1 - cos)/dx = cos * x/|x|^2 - t/(|x||t|)
+				let a = cos/input_sqr;
+				let b = 1.0/(input_norm*target_norm);
+				for ((ig, &x), &t) in input_grad.iter_mut().zip(input.iter()).zip(target.iter()) {
+					*ig += (a*x - b*t)*grad_multiplier;
+				}
+			}
+		}
+
+		data.loss_add(error);
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_cosine_similarity_loss_backprop(){
+	_cosine_similarity_loss_backprop().unwrap();
+}
+
+fn _cosine_similarity_loss_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5, 16], "input", tag![])?;
+	let node2 = g.new_node(shape![7, 5, 16], "target", tag![])?;
+
+	let _o1 = g.new_op(CosineSimilarityLoss::new(&node1, &node2), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_cosine_similarity_loss_identical(){
+	_cosine_similarity_loss_identical().unwrap();
+}
+
+fn _cosine_similarity_loss_identical() -> Result<()>{
+	use graph::GraphDef;
+	use ndarray::ArrayD;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![3, 8], "input", tag![])?;
+	let node2 = g.new_node(shape![3, 8], "target", tag![])?;
+
+	let _o1 = g.new_op(CosineSimilarityLoss::new(&node1, &node2), tag![])?;
+
+	let values = ArrayD::from_shape_fn(&[3, 8][..], |idx| (idx[0] * 8 + idx[1]) as f32 - 10.0);
+
+	let mut subgraph = g.subgraph(&[node1.value_id(), node2.value_id()], &[node1.gradient_id()])?;
+
+	let storage = subgraph.execute(vec![values.clone(), values.clone()])?;
+	assert!(storage.loss().abs() < 1e-5);
+	assert!(storage.get(&node1.gradient_id())?.iter().all(|g| g.abs() < 1e-5));
+
+	// scaling the input does not change the loss
+	let storage = subgraph.execute(vec![values.mapv(|x| x * 3.0), values.clone()])?;
+	assert!(storage.loss().abs() < 1e-5);
+
+	// opposite vectors give the maximum loss of 2
+	let storage = subgraph.execute(vec![values.mapv(|x| -x), values.clone()])?;
+	assert!((storage.loss() - 2.0).abs() < 1e-5);
+
+	Ok(())
+}
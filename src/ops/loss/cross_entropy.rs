@@ -2,7 +2,7 @@ use graph::{GraphDef, GraphShapes, ErrorKind, Result};
 use id::{NodeID, DataID, OpID, PassID};
 use storage::Storage;
 use ops::{standard_op_name, Op, OpInstance, Pass};
-use ops::loss::LossType;
+use ops::loss::{LossType, Reduction};
 use std::any::Any;
 
 
@@ -15,6 +15,10 @@ use std::any::Any;
 /// If `output()` is set to a node of size 1, the Cross Entropy will be written to that Node, and the gradient will be backprop'd from the output node.
 ///
 /// If `separate_loss()` is set a scalar node will be added to the graph, and a `Loss` Op attached to it.
+///
+/// If `label_smoothing()` is set the labels are smoothed towards a uniform distribution over the classes of the innermost axis.
+///
+/// If `reduction()` is set to `Mean` the loss is averaged over all positions, i.e. all axes except the innermost class axis, rather than summed.
 #[must_use]
 #[derive(Clone, Debug)]
 pub struct CrossEntropy {
@@ -22,6 +26,8 @@ pub struct CrossEntropy {
 	labels_id: NodeID,
 	output: Option<NodeID>,
 	multiplier: f32,
+	label_smoothing: f32,
+	reduction: Option<Reduction>,
 	name: Option<String>,
 }
 
@@ -32,6 +38,8 @@ impl CrossEntropy {
 			labels_id: labels_id.clone(),
 			output: None,
 			multiplier: 1.0,
+			label_smoothing: 0.0,
+			reduction: None,
 			name: None,
 		}
 	}
@@ -50,6 +58,27 @@ impl CrossEntropy {
 		self.multiplier = multiplier;
 		self
 	}
+
+	/// Replaces the labels with `(1 - eps) * labels + eps / num_classes` before calculating the loss and gradients.
+	///
+	/// The classes are taken to lie along the innermost axis, e.g. the output of a `Softmax` over the last dimension.
+	/// Default: 0.0
+	pub fn label_smoothing(mut self, eps: f32) -> Self {
+		self.label_smoothing = eps;
+		self
+	}
+
+	/// Sets how the loss is reduced.
+	///
+	/// * `Mean`: the loss is summed over the class axis and averaged over all other axes.
+	/// * `Sum`: the loss is summed over all axes.
+	/// * `None`: the loss of each element is written to the output node, which must be set.
+	///
+	/// Default: not set, the loss is summed unless `output()` is set, in which case it is not reduced.
+	pub fn reduction(mut self, reduction: Reduction) -> Self {
+		self.reduction = Some(reduction);
+		self
+	}
 }
 
 impl Op for CrossEntropy {
@@ -65,6 +94,15 @@ impl Op for CrossEntropy {
 	}
 
 	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.label_smoothing >= 0.0 && self.label_smoothing <= 1.0, "CrossEntropy label_smoothing must be in the range [0, 1]");
+
+		let reduction = match (self.reduction, self.output.is_some()) {
+			(None, true) | (Some(Reduction::None), true) => Reduction::None,
+			(None, false) => Reduction::Sum,
+			(Some(Reduction::None), false) => bail!("CrossEntropy Reduction::None requires an output node for the per element loss"),
+			(Some(_), true) => bail!("CrossEntropy output is per element, and can only be used with Reduction::None"),
+			(Some(reduction), false) => reduction,
+		};
 
 		let name =  if let Some(ref output_id) = self.output {
 			standard_op_name(&self, &self.name, graph, &[self.logits_id.clone(), self.labels_id.clone()], &[output_id.clone()])
@@ -77,11 +115,13 @@ impl Op for CrossEntropy {
 				output_id: output_id.clone(),
 				forward_id: graph.add_pass(CrossEntropyForward::new(
 					self.multiplier,
+					self.label_smoothing,
 					self.logits_id.clone(),
 					self.labels_id.clone(),
 					output_id.clone())),
 				backward_id: graph.add_pass(CrossEntropyBackward::new(
 					self.multiplier,
+					self.label_smoothing,
 					self.logits_id.clone(),
 					self.labels_id.clone(),
 					output_id.clone())),
@@ -90,14 +130,18 @@ impl Op for CrossEntropy {
 			LossType::Joint{
 				pass_id: graph.add_pass(CrossEntropyJointPass::new(
 					self.multiplier,
+					self.label_smoothing,
 					self.logits_id.clone(),
-					self.labels_id.clone()))
+					self.labels_id.clone(),
+					reduction))
 			}
 		};
 
 		Ok(CrossEntropyInstance{
 			name: name,
 			multiplier: self.multiplier,
+			label_smoothing: self.label_smoothing,
+			reduction: reduction,
 			logits_id: self.logits_id.clone(),
 			labels_id: self.labels_id.clone(),
 			loss_type: loss_type,
@@ -110,6 +154,8 @@ impl Op for CrossEntropy {
 pub struct CrossEntropyInstance {
 	name: String,
 	multiplier: f32,
+	label_smoothing: f32,
+	reduction: Reduction,
 	logits_id: NodeID,
 	labels_id: NodeID,
 	loss_type: LossType,
@@ -153,22 +199,36 @@ impl OpInstance for CrossEntropyInstance {
 			Ok(())
 		}
 	}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("multiplier", format!("{:?}", self.multiplier)), ("label_smoothing", format!("{:?}", self.label_smoothing)), ("reduction", format!("{:?}", self.reduction))]
+	}
 }
 
 
+/// Returns the scale and offset which map labels to smoothed labels, with classes along the innermost axis.
+fn smoothing_coefficients(eps: f32, shape: &[usize]) -> (f32, f32) {
+	let num_classes = shape.last().cloned().unwrap_or(1);
+	(1.0 - eps, eps / num_classes as f32)
+}
+
 #[derive(Clone, Debug)]
 struct CrossEntropyJointPass {
 	multiplier: f32,
+	label_smoothing: f32,
 	logits_id: NodeID,
 	labels_id: NodeID,
+	reduction: Reduction,
 }
 
 impl CrossEntropyJointPass {
-	pub fn new(multiplier: f32, logits_id: NodeID, labels_id: NodeID) -> Self {
+	pub fn new(multiplier: f32, label_smoothing: f32, logits_id: NodeID, labels_id: NodeID, reduction: Reduction) -> Self {
 		CrossEntropyJointPass {
 			multiplier,
+			label_smoothing,
 			logits_id,
 			labels_id,
+			reduction,
 		}
 	}
 }
@@ -191,13 +251,16 @@ impl Pass for CrossEntropyJointPass {
 			);
 
 
+		let (label_scale, label_offset) = smoothing_coefficients(self.label_smoothing, logits_val.shape());
+		let num_classes = logits_val.shape().last().cloned().unwrap_or(1).max(1);
 		let logits_val = logits_val.as_slice().unwrap();
 		let labels_val = labels_val.as_slice().unwrap();
 
 		let n = logits_val.len();
 		assert!(labels_val.len() == n);
 		
-		let multiplier = self.multiplier;
+		let multiplier = self.multiplier/self.reduction.divisor(n/num_classes) as f32;
+		let grad_multiplier = multiplier * data.loss_scale();
 
 		let mut error = 0.0;
 
@@ -210,9 +273,9 @@ impl Pass for CrossEntropyJointPass {
 			assert!(labels_grad.len() == n);
 
 			for i in 0..n {
-				error += -labels_val[i] * logits_val[i].ln() * multiplier;
-				logits_grad[i] += -labels_val[i] * multiplier / logits_val[i];
-				labels_grad[i] += - logits_val[i].ln() * multiplier;
+				error += -(labels_val[i] * label_scale + label_offset) * logits_val[i].ln() * multiplier;
+				logits_grad[i] += -(labels_val[i] * label_scale + label_offset) * grad_multiplier / logits_val[i];
+				labels_grad[i] += - logits_val[i].ln() * label_scale * grad_multiplier;
 			}
 
 		} else if data.is_required(&self.logits_id.gradient_id()) {
@@ -222,8 +285,8 @@ impl Pass for CrossEntropyJointPass {
 
 
 			for i in 0..n {
-				error += -labels_val[i] * logits_val[i].ln() * multiplier;
-				logits_grad[i] += -labels_val[i] * multiplier / logits_val[i];
+				error += -(labels_val[i] * label_scale + label_offset) * logits_val[i].ln() * multiplier;
+				logits_grad[i] += -(labels_val[i] * label_scale + label_offset) * grad_multiplier / logits_val[i];
 			}
 
 		} else if data.is_required(&self.labels_id.gradient_id()) {
@@ -232,8 +295,8 @@ impl Pass for CrossEntropyJointPass {
 			assert!(labels_grad.len() == n);
 
 			for i in 0..n {
-				error += -labels_val[i] * logits_val[i].ln() * multiplier;
-				labels_grad[i] += - logits_val[i].ln() * multiplier;
+				error += -(labels_val[i] * label_scale + label_offset) * logits_val[i].ln() * multiplier;
+				labels_grad[i] += - logits_val[i].ln() * label_scale * grad_multiplier;
 			}
 		}
 
@@ -247,15 +310,17 @@ impl Pass for CrossEntropyJointPass {
 #[derive(Clone, Debug)]
 struct CrossEntropyForward {
 	multiplier: f32,
+	label_smoothing: f32,
 	logits_id: NodeID,
 	labels_id: NodeID,
 	output_id: NodeID,
 }
 
 impl CrossEntropyForward {
-	pub fn new(multiplier: f32, logits_id: NodeID, labels_id: NodeID, output_id: NodeID) -> Self {
+	pub fn new(multiplier: f32, label_smoothing: f32, logits_id: NodeID, labels_id: NodeID, output_id: NodeID) -> Self {
 		CrossEntropyForward {
 			multiplier,
+			label_smoothing,
 			logits_id,
 			labels_id,
 			output_id,
@@ -281,6 +346,7 @@ impl Pass for CrossEntropyForward {
 			ErrorKind::PassError(self.name(), format!("labels shape: {:?} did not match logits shape: {:?}", labels_val.shape(), logits_val.shape()))
 			);
 
+		let (label_scale, label_offset) = smoothing_coefficients(self.label_smoothing, logits_val.shape());
 		let logits_val = logits_val.as_slice().unwrap();
 		let labels_val = labels_val.as_slice().unwrap();
 		let output_val = output_val.as_slice_mut().unwrap();
@@ -292,7 +358,7 @@ impl Pass for CrossEntropyForward {
 		let multiplier = self.multiplier;
 
 		for i in 0..n {
-			output_val[i] += -labels_val[i] * logits_val[i].ln() * multiplier;
+			output_val[i] += -(labels_val[i] * label_scale + label_offset) * logits_val[i].ln() * multiplier;
 		}
 
 		Ok(Box::new(()))
@@ -302,15 +368,17 @@ impl Pass for CrossEntropyForward {
 #[derive(Clone, Debug)]
 struct CrossEntropyBackward {
 	multiplier: f32,
+	label_smoothing: f32,
 	logits_id: NodeID,
 	labels_id: NodeID,
 	output_id: NodeID,
 }
 
 impl CrossEntropyBackward {
-	pub fn new(multiplier: f32, logits_id: NodeID, labels_id: NodeID, output_id: NodeID) -> Self {
+	pub fn new(multiplier: f32, label_smoothing: f32, logits_id: NodeID, labels_id: NodeID, output_id: NodeID) -> Self {
 		CrossEntropyBackward {
 			multiplier,
+			label_smoothing,
 			logits_id,
 			labels_id,
 			output_id,
@@ -336,6 +404,7 @@ impl Pass for CrossEntropyBackward {
 			ErrorKind::PassError(self.name(), format!("labels shape: {:?} did not match logits shape: {:?}", labels_val.shape(), logits_val.shape()))
 			);
 
+		let (label_scale, label_offset) = smoothing_coefficients(self.label_smoothing, logits_val.shape());
 		let logits_val = logits_val.as_slice().unwrap();
 		let labels_val = labels_val.as_slice().unwrap();
 		let output_grad = output_grad.as_slice().unwrap();
@@ -355,8 +424,8 @@ impl Pass for CrossEntropyBackward {
 			assert!(output_grad.len() == n);
 
 			for i in 0..n {
-				logits_grad[i] += -labels_val[i] * multiplier / logits_val[i] * output_grad[i];
-				labels_grad[i] += - logits_val[i].ln() * multiplier * output_grad[i];
+				logits_grad[i] += -(labels_val[i] * label_scale + label_offset) * multiplier / logits_val[i] * output_grad[i];
+				labels_grad[i] += - logits_val[i].ln() * label_scale * multiplier * output_grad[i];
 			}
 
 		} else if data.is_required(&self.logits_id.gradient_id()) {
@@ -366,7 +435,7 @@ impl Pass for CrossEntropyBackward {
 			assert!(output_grad.len() == n);
 
 			for i in 0..n {
-				logits_grad[i] += -labels_val[i] * multiplier / logits_val[i] * output_grad[i];
+				logits_grad[i] += -(labels_val[i] * label_scale + label_offset) * multiplier / logits_val[i] * output_grad[i];
 			}
 
 		} else if data.is_required(&self.labels_id.gradient_id()) {
@@ -376,7 +445,7 @@ impl Pass for CrossEntropyBackward {
 			assert!(output_grad.len() == n);
 
 			for i in 0..n {
-				labels_grad[i] += - logits_val[i].ln() * multiplier * output_grad[i];
+				labels_grad[i] += - logits_val[i].ln() * label_scale * multiplier * output_grad[i];
 			}
 		}
 
@@ -445,4 +514,77 @@ fn _cross_entropy_output_backprop() -> Result<()>{
 	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
 
 	Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_cross_entropy_label_smoothing_backprop(){
+	_cross_entropy_label_smoothing_backprop().unwrap();
+}
+
+fn _cross_entropy_label_smoothing_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::activ::logistic::Logistic;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5, 16], "input1", tag![])?;
+	let node2 = g.new_node(shape![7, 5, 16], "logistic", tag![])?;
+	let node3 = g.new_node(shape![7, 5, 16], "input2", tag![])?;
+
+	let _o1 = g.new_op(Logistic::new(&node1, &node2), tag![])?;
+	let _o2 = g.new_op(CrossEntropy::new(&node2, &node3).label_smoothing(0.1), tag![])?;
+
+	let iters = 100;
+	let failures = 2;
+	let tolerance = 0.005;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_cross_entropy_label_smoothing(){
+	_cross_entropy_label_smoothing().unwrap();
+}
+
+fn _cross_entropy_label_smoothing() -> Result<()>{
+	use graph::GraphDef;
+	use ops::activ::softmax::Softmax;
+	use ndarray::{arr2, ArrayD};
+
+	let loss_and_grad = |eps: f32| -> Result<(f32, ArrayD<f32>)> {
+		let mut g = GraphDef::new();
+
+		let input = g.new_node(shape![2, 4], "input", tag![])?;
+		let probs = g.new_node(shape![2, 4], "probs", tag![])?;
+		let labels = g.new_node(shape![2, 4], "labels", tag![])?;
+
+		let _o1 = g.new_op(Softmax::new(&input, &probs).axes(&[-1]), tag![])?;
+		let _o2 = g.new_op(CrossEntropy::new(&probs, &labels).label_smoothing(eps), tag![])?;
+
+		// the predictions are confidently correct
+		let input_val = arr2(&[[30.0, 0.0, 0.0, 0.0], [0.0, 0.0, 30.0, 0.0]]).into_dyn();
+		let labels_val = arr2(&[[1.0, 0.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0]]).into_dyn();
+
+		let mut subgraph = g.subgraph(&[input.value_id(), labels.value_id()], &[input.gradient_id()])?;
+		let storage = subgraph.execute(vec![input_val, labels_val])?;
+		let input_grad = storage.get(&input.gradient_id())?.to_owned();
+		Ok((storage.loss(), input_grad))
+	};
+
+	let max_abs = |arr: &ArrayD<f32>| arr.iter().fold(0.0f32, |max, &x| max.max(x.abs()));
+
+	let (loss, grad) = loss_and_grad(0.0)?;
+	assert!(loss < 1e-6, "{}", loss);
+	assert!(max_abs(&grad) < 1e-6, "{}", grad);
+
+	// smoothing keeps a penalty and a gradient away from the confident prediction
+	let (loss, grad) = loss_and_grad(0.1)?;
+	assert!(loss > 0.1, "{}", loss);
+	assert!(max_abs(&grad) > 0.01, "{}", grad);
+
+	Ok(())
+}
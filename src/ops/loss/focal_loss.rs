@@ -0,0 +1,329 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use std::any::Any;
+
+/// An `Op` which implements the Focal Loss
+///
+/// This op expects one input tensor of probabilities in the range (0, 1), e.g. the output of a `Logistic` or `Softmax` op,
+/// and a second of 0 or 1 labels.
+///
+/// The loss is the sum of `-alpha * labels * (1 - p)^gamma * ln(p)` over all elements, which down-weights well classified examples relative to CrossEntropy.
+/// With `gamma` equal to 0 and no `alpha` this is equivalent to `CrossEntropy`.
+///
+/// This `Op` has no output and will generate loss and gradients.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct FocalLoss {
+	probs_id: NodeID,
+	labels_id: NodeID,
+	gamma: f32,
+	alpha: Option<Vec<f32>>,
+	multiplier: f32,
+	name: Option<String>,
+}
+
+impl FocalLoss {
+	pub fn new(probs_id: &NodeID, labels_id: &NodeID) -> Self {
+		FocalLoss {
+			probs_id: probs_id.clone(),
+			labels_id: labels_id.clone(),
+			gamma: 2.0,
+			alpha: None,
+			multiplier: 1.0,
+			name: None,
+		}
+	}
+
+	/// The focusing parameter, γ, which reduces the loss of examples as their probability approaches 1.
+	///
+	/// Default: 2.0
+	pub fn gamma(mut self, gamma: f32) -> Self {
+		self.gamma = gamma;
+		self
+	}
+
+	/// Per-class weights, α, applied to the loss of each class.
+	///
+	/// The classes are taken to lie along the innermost axis, which must have the same length as `alpha`.
+	/// Default: None
+	pub fn alpha<A: Into<Option<Vec<f32>>>>(mut self, alpha: A) -> Self {
+		self.alpha = alpha.into();
+		self
+	}
+
+	/// Applies a multiplier to the loss generated.
+	pub fn multiplier(mut self, multiplier: f32) -> Self {
+		self.multiplier = multiplier;
+		self
+	}
+}
+
+impl Op for FocalLoss {
+	type InstanceType = FocalLossInstance;
+
+	fn type_name(&self) -> &'static str {
+		"FocalLoss"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.gamma >= 0.0, "FocalLoss gamma must be non-negative");
+		let name = standard_op_name(&self, &self.name, graph, &[self.probs_id.clone(), self.labels_id.clone()], &[]);
+
+		Ok(FocalLossInstance{
+			name: name,
+			gamma: self.gamma,
+			alpha: self.alpha.clone(),
+			multiplier: self.multiplier,
+			probs_id: self.probs_id.clone(),
+			labels_id: self.labels_id.clone(),
+			pass_id: graph.add_pass(FocalLossJointPass::new(
+				self.multiplier,
+				self.gamma,
+				self.alpha,
+				self.probs_id.clone(),
+				self.labels_id.clone())),
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)] 
+pub struct FocalLossInstance {
+	name: String,
+	gamma: f32,
+	alpha: Option<Vec<f32>>,
+	multiplier: f32,
+	probs_id: NodeID,
+	labels_id: NodeID,
+	pass_id: PassID,
+}
+
+impl OpInstance for FocalLossInstance {
+
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(vec![self.probs_id.clone(), self.labels_id.clone()], vec![])
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.pass_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {
+		vec![]
+	}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {
+		vec![]
+	}
+
+	fn propagate_shape_constraints(&self, _shapes: &mut GraphShapes) -> Result<()>{
+		Ok(())
+	}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("gamma", format!("{:?}", self.gamma)), ("alpha", format!("{:?}", self.alpha)), ("multiplier", format!("{:?}", self.multiplier))]
+	}
+}
+
+
+#[derive(Clone, Debug)]
+struct FocalLossJointPass {
+	multiplier: f32,
+	gamma: f32,
+	alpha: Option<Vec<f32>>,
+	probs_id: NodeID,
+	labels_id: NodeID,
+}
+
+impl FocalLossJointPass {
+	pub fn new(multiplier: f32, gamma: f32, alpha: Option<Vec<f32>>, probs_id: NodeID, labels_id: NodeID) -> Self {
+		FocalLossJointPass {
+			multiplier,
+			gamma,
+			alpha,
+			probs_id,
+			labels_id,
+		}
+	}
+}
+
+impl Pass for FocalLossJointPass {
+	fn type_name(&self) -> &'static str {"FocalLossJointPass"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.probs_id.value_id(), self.labels_id.value_id()],
+		vec![self.probs_id.gradient_id(), self.labels_id.gradient_id()])
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let probs_val = data.get(&self.probs_id.value_id())?;
+		let labels_val = data.get(&self.labels_id.value_id())?;
+
+		ensure!(
+			labels_val.shape() == probs_val.shape(),
+			ErrorKind::PassError(self.name(), format!("labels shape: {:?} did not match probs shape: {:?}", labels_val.shape(), probs_val.shape()))
+		);
+
+		let num_classes = probs_val.shape().last().cloned().unwrap_or(1);
+		if let Some(ref alpha) = self.alpha {
+			ensure!(
+				alpha.len() == num_classes,
+				ErrorKind::PassError(self.name(), format!("alpha length: {} did not match the innermost axis of probs shape: {:?}", alpha.len(), probs_val.shape()))
+			);
+		}
+
+		let probs_val = probs_val.as_slice().unwrap();
+		let labels_val = labels_val.as_slice().unwrap();
+
+		let n = probs_val.len();
+		assert!(labels_val.len() == n);
+
+		let gamma = self.gamma;
+		let loss_scale = data.loss_scale();
+		let weight = |i: usize| self.multiplier * self.alpha.as_ref().map(|alpha| alpha[i % num_classes]).unwrap_or(1.0);
+
+		// returns the loss per unit label, and its derivative with respect to p
+		let focal = |p: f32| -> (f32, f32) {
+			let q = 1.0 - p;
+			let ln_p = p.ln();
+			let q_gamma = q.powf(gamma);
+			let grad = if gamma == 0.0 {
+				-1.0 / p
+			} else {
+				gamma * q.powf(gamma - 1.0) * ln_p - q_gamma / p
+			};
+			(-q_gamma * ln_p, grad)
+		};
+
+		let mut error = 0.0;
+
+		if data.is_required(&self.probs_id.gradient_id()) && data.is_required(&self.labels_id.gradient_id()) {
+			let mut probs_grad = data.get_mut(&self.probs_id.gradient_id())?;
+			let probs_grad = probs_grad.as_slice_mut().unwrap();
+			let mut labels_grad = data.get_mut(&self.labels_id.gradient_id())?;
+			let labels_grad = labels_grad.as_slice_mut().unwrap();
+			assert!(probs_grad.len() == n);
+			assert!(labels_grad.len() == n);
+
+			for i in 0..n {
+				let (loss, grad) = focal(probs_val[i]);
+				let weight = weight(i);
+				error += labels_val[i] * loss * weight;
+				probs_grad[i] += labels_val[i] * grad * weight * loss_scale;
+				labels_grad[i] += loss * weight * loss_scale;
+			}
+
+		} else if data.is_required(&self.probs_id.gradient_id()) {
+			let mut probs_grad = data.get_mut(&self.probs_id.gradient_id())?;
+			let probs_grad = probs_grad.as_slice_mut().unwrap();
+			assert!(probs_grad.len() == n);
+
+			for i in 0..n {
+				let (loss, grad) = focal(probs_val[i]);
+				let weight = weight(i);
+				error += labels_val[i] * loss * weight;
+				probs_grad[i] += labels_val[i] * grad * weight * loss_scale;
+			}
+
+		} else if data.is_required(&self.labels_id.gradient_id()) {
+			let mut labels_grad = data.get_mut(&self.labels_id.gradient_id())?;
+			let labels_grad = labels_grad.as_slice_mut().unwrap();
+			assert!(labels_grad.len() == n);
+
+			for i in 0..n {
+				let (loss, _grad) = focal(probs_val[i]);
+				let weight = weight(i);
+				error += labels_val[i] * loss * weight;
+				labels_grad[i] += loss * weight * loss_scale;
+			}
+
+		} else {
+			for i in 0..n {
+				let (loss, _grad) = focal(probs_val[i]);
+				error += labels_val[i] * loss * weight(i);
+			}
+		}
+
+		data.loss_add(error);
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_focal_loss_backprop(){
+	_focal_loss_backprop().unwrap();
+}
+
+fn _focal_loss_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::activ::logistic::Logistic;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5, 16], "input1", tag![])?;
+	let node2 = g.new_node(shape![7, 5, 16], "logistic", tag![])?;
+	let node3 = g.new_node(shape![7, 5, 16], "input2", tag![])?;
+
+	let alpha = (0..16).map(|i| 0.25 + i as f32 * 0.05).collect();
+	let _o1 = g.new_op(Logistic::new(&node1, &node2), tag![])?;
+	let _o2 = g.new_op(FocalLoss::new(&node2, &node3).gamma(2.0).alpha(alpha), tag![])?;
+
+	let iters = 100;
+	let failures = 2;
+	let tolerance = 0.005;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_focal_loss_gamma(){
+	_focal_loss_gamma().unwrap();
+}
+
+fn _focal_loss_gamma() -> Result<()>{
+	use graph::GraphDef;
+	use ndarray::ArrayD;
+
+	let loss = |p: f32, gamma: f32| -> Result<f32> {
+		let mut g = GraphDef::new();
+
+		let probs = g.new_node(shape![1], "probs", tag![])?;
+		let labels = g.new_node(shape![1], "labels", tag![])?;
+
+		let _o1 = g.new_op(FocalLoss::new(&probs, &labels).gamma(gamma), tag![])?;
+
+		// the loss is added by the backward pass, which is only run if a gradient is requested
+		let mut subgraph = g.subgraph(&[probs.value_id(), labels.value_id()], &[probs.gradient_id()])?;
+		let storage = subgraph.execute(vec![ArrayD::from_elem(&[1][..], p), ArrayD::from_elem(&[1][..], 1.0)])?;
+		Ok(storage.loss())
+	};
+
+	// gamma of zero is equivalent to cross entropy
+	assert!((loss(0.9, 0.0)? - -(0.9f32).ln()).abs() < 1e-6);
+
+	// increasing gamma shrinks the loss of the well classified example relative to the hard example
+	let easy = 0.9;
+	let hard = 0.3;
+	let ratio0 = loss(easy, 0.0)? / loss(hard, 0.0)?;
+	let ratio1 = loss(easy, 1.0)? / loss(hard, 1.0)?;
+	let ratio2 = loss(easy, 2.0)? / loss(hard, 2.0)?;
+	assert!(ratio1 < ratio0, "{} {}", ratio1, ratio0);
+	assert!(ratio2 < ratio1, "{} {}", ratio2, ratio1);
+
+	Ok(())
+}
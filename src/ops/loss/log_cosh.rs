@@ -0,0 +1,256 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use ndarray::Zip;
+use std::any::Any;
+use std::f32;
+
+/// An `Op` which implements the Log-Cosh Loss
+///
+/// The loss is the mean of ln(cosh(input1 - input2)) over all elements,
+/// which behaves like Mse/2 for small differences and like Mae for large differences.
+///
+/// This `Op` has no output and will generate loss and gradients.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct LogCosh {
+	input1_id: NodeID,
+	input2_id: NodeID,
+	multiplier: f32,
+	name: Option<String>,
+}
+
+impl LogCosh {
+	pub fn new(input1: &NodeID, input2: &NodeID) -> Self {
+		LogCosh {
+			input1_id: input1.clone(),
+			input2_id: input2.clone(),
+			multiplier: 1.0,
+			name: None,
+		}
+	}
+
+	/// Applies a multiplier to the loss generated.
+	pub fn multiplier(mut self, multiplier: f32) -> Self {
+		self.multiplier = multiplier;
+		self
+	}
+}
+
+impl Op for LogCosh {
+	type InstanceType = LogCoshInstance;
+
+	fn type_name(&self) -> &'static str {
+		"LogCosh"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let name = standard_op_name(&self, &self.name, graph, &[self.input1_id.clone(), self.input2_id.clone()], &[]);
+
+		Ok(LogCoshInstance{
+			name: name,
+			multiplier: self.multiplier,
+			input1_id: self.input1_id.clone(),
+			input2_id: self.input2_id.clone(),
+			pass_id: graph.add_pass(LogCoshJointPass::new(
+				self.multiplier,
+				self.input1_id.clone(),
+				self.input2_id.clone())),
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)] 
+pub struct LogCoshInstance {
+	name: String,
+	multiplier: f32,
+	input1_id: NodeID,
+	input2_id: NodeID,
+	pass_id: PassID,
+}
+
+impl OpInstance for LogCoshInstance {
+
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(vec![self.input1_id.clone(), self.input2_id.clone()], vec![])
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.pass_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {
+		vec![]
+	}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {
+		vec![]
+	}
+
+	fn propagate_shape_constraints(&self, _shapes: &mut GraphShapes) -> Result<()>{
+		Ok(())
+	}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("multiplier", format!("{:?}", self.multiplier))]
+	}
+}
+
+/// Numerically stable ln(cosh(x)) = |x| + ln(1 + exp(-2|x|)) - ln(2)
+fn log_cosh(x: f32) -> f32 {
+	let abs = x.abs();
+	abs + (-2.0*abs).exp().ln_1p() - f32::consts::LN_2
+}
+
+
+#[derive(Clone, Debug)]
+struct LogCoshJointPass {
+	multiplier: f32,
+	input1_id: NodeID,
+	input2_id: NodeID,
+}
+
+impl LogCoshJointPass {
+	pub fn new(multiplier: f32, input1_id: NodeID, input2_id: NodeID) -> Self {
+		LogCoshJointPass {
+			multiplier,
+			input1_id,
+			input2_id,
+		}
+	}
+}
+
+impl Pass for LogCoshJointPass {
+	fn type_name(&self) -> &'static str {"LogCoshJointPass"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input1_id.value_id(), self.input2_id.value_id()],
+		vec![self.input1_id.gradient_id(), self.input2_id.gradient_id()])
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let input1 = data.get(&self.input1_id.value_id())?;
+		let input2 = data.get(&self.input2_id.value_id())?;
+
+		ensure!(
+			input2.shape() == input1.shape(),
+			ErrorKind::PassError(self.name(), format!("input1 shape: {:?} did not match input2 shape: {:?}", input2.shape(), input1.shape()))
+		);
+
+		let multiplier = self.multiplier/input1.len() as f32;
+		let grad_multiplier = multiplier * data.loss_scale();
+
+		let mut error = 0.0;
+
+		if data.is_required(&self.input1_id.gradient_id()) && data.is_required(&self.input2_id.gradient_id()) {
+			let mut input1_grad = data.get_mut(&self.input1_id.gradient_id())?;
+			let mut input2_grad = data.get_mut(&self.input2_id.gradient_id())?;
+
+			Zip::from(&input1)
+			.and(&input2)
+			.and(&mut input1_grad)
+			.and(&mut input2_grad)
+			.apply(|input1, input2, input1_grad, input2_grad| {
+				let diff = input1-input2;
+				error += log_cosh(diff)*multiplier;
+				let grad = diff.tanh()*grad_multiplier;
+				*input1_grad += grad;
+				*input2_grad += -grad;
+			});
+		} else if data.is_required(&self.input1_id.gradient_id()) {
+			let mut input1_grad = data.get_mut(&self.input1_id.gradient_id())?;
+
+			Zip::from(&input1)
+			.and(&input2)
+			.and(&mut input1_grad)
+			.apply(|input1, input2, input1_grad| {
+				let diff = input1-input2;
+				error += log_cosh(diff)*multiplier;
+				*input1_grad += diff.tanh()*grad_multiplier;
+			});
+		} else if data.is_required(&self.input2_id.gradient_id()) {
+			let mut input2_grad = data.get_mut(&self.input2_id.gradient_id())?;
+
+			Zip::from(&input1)
+			.and(&input2)
+			.and(&mut input2_grad)
+			.apply(|input1, input2, input2_grad| {
+				let diff = input1-input2;
+				error += log_cosh(diff)*multiplier;
+				*input2_grad += -diff.tanh()*grad_multiplier;
+			});
+		}
+
+		data.loss_add(error);
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_log_cosh_backprop(){
+	_log_cosh_backprop().unwrap();
+}
+
+fn _log_cosh_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5, 16], "input1", tag![])?;
+	let node2 = g.new_node(shape![7, 5, 16], "input2", tag![])?;
+
+	let _o1 = g.new_op(LogCosh::new(&node1, &node2), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_log_cosh_saturation(){
+	_log_cosh_saturation().unwrap();
+}
+
+fn _log_cosh_saturation() -> Result<()>{
+	use graph::GraphDef;
+	use ndarray::ArrayD;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 4], "input1", tag![])?;
+	let node2 = g.new_node(shape![2, 4], "input2", tag![])?;
+
+	let _o1 = g.new_op(LogCosh::new(&node1, &node2), tag![])?;
+
+	let mut subgraph = g.subgraph(&[node1.value_id(), node2.value_id()], &[node1.gradient_id()])?;
+
+	let input1 = ArrayD::from_shape_vec(&[2, 4][..], vec![100.0, -100.0, 50.0, -50.0, 1e4, -1e4, 20.0, -20.0]).unwrap();
+	let storage = subgraph.execute(vec![input1, ArrayD::zeros(&[2, 4][..])])?;
+
+	// large residuals give gradients of ±1/n, and losses approaching |x| - ln(2)
+	let n = 8.0;
+	for (&g, &x) in storage.get(&node1.gradient_id())?.iter().zip(&[1.0, -1.0, 1.0, -1.0, 1.0, -1.0, 1.0, -1.0]) {
+		assert!((g - x/n).abs() < 1e-6);
+	}
+	let expected_loss = (100.0 + 100.0 + 50.0 + 50.0 + 1e4 + 1e4 + 20.0 + 20.0 - 8.0*f32::consts::LN_2)/n;
+	assert!((storage.loss() - expected_loss).abs()/expected_loss < 1e-5);
+
+	Ok(())
+}
@@ -2,7 +2,7 @@ use graph::{GraphDef, GraphShapes, ErrorKind, Result};
 use id::{NodeID, DataID, OpID, PassID};
 use storage::Storage;
 use ops::{standard_op_name, Op, OpInstance, Pass};
-use ops::loss::LossType;
+use ops::loss::{LossType, Reduction, resolve_reduction};
 use shape::NodeShape;
 use smallvec::SmallVec;
 use ndarray::{Dimension, Zip};
@@ -22,6 +22,7 @@ pub struct Mae {
 	output: Option<NodeID>,
 	mean_axes: SmallVec<[isize; 6]>,
 	keep_dims: bool,
+	reduction: Option<Reduction>,
 	multiplier: f32,
 	name: Option<String>,
 }
@@ -34,6 +35,7 @@ impl Mae {
 			output: None,
 			mean_axes: SmallVec::new(),
 			keep_dims: false,
+			reduction: None,
 			multiplier: 1.0,
 			name: None,
 		}
@@ -66,6 +68,19 @@ impl Mae {
 		self
 	}
 
+	/// Sets how the loss is reduced.
+	///
+	/// * `Mean`: the loss is averaged over `mean_axes()`, or over all axes if none are supplied.
+	/// * `Sum`: the loss is summed over `mean_axes()`, or over all axes if none are supplied.
+	/// * `None`: the loss of each element is written to the output node, which must be set and have the same shape as the inputs.
+	///
+	/// If a `Joint` loss is not reduced to a scalar, the remaining values are summed.
+	/// Default: not set, the loss is averaged over `mean_axes()` only, and summed over any other axes.
+	pub fn reduction(mut self, reduction: Reduction) -> Self {
+		self.reduction = Some(reduction);
+		self
+	}
+
 	/// Applies a multiplier to the output or to the loss generated.
 	pub fn multiplier(mut self, multiplier: f32) -> Self {
 		self.multiplier = multiplier;
@@ -87,6 +102,7 @@ impl Op for Mae {
 	}
 
 	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let (reduction, mean_axes) = resolve_reduction(self.reduction, &self.mean_axes, self.input1_id.shape().ndim(), self.output.is_some())?;
 
 		let name =  if let Some(ref output_id) = self.output {
 			standard_op_name(&self, &self.name, graph, &[self.input1_id.clone(), self.input2_id.clone()], &[output_id.clone()])
@@ -102,15 +118,17 @@ impl Op for Mae {
 					self.input1_id.clone(),
 					self.input2_id.clone(),
 					output_id.clone(),
-					self.mean_axes.clone(),
-					self.keep_dims)),
+					mean_axes.clone(),
+					self.keep_dims,
+					reduction)),
 				backward_id: graph.add_pass(MaeBackward::new(
 					self.multiplier,
 					self.input1_id.clone(),
 					self.input2_id.clone(),
 					output_id.clone(),
-					self.mean_axes.clone(),
-					self.keep_dims)),
+					mean_axes.clone(),
+					self.keep_dims,
+					reduction)),
 			}
 		} else {
 			LossType::Joint{
@@ -118,7 +136,8 @@ impl Op for Mae {
 					self.multiplier,
 					self.input1_id.clone(),
 					self.input2_id.clone(),
-					self.mean_axes.clone()))
+					mean_axes.clone(),
+					reduction))
 			}
 		};
 
@@ -128,8 +147,9 @@ impl Op for Mae {
 			input1_id: self.input1_id.clone(),
 			input2_id: self.input2_id.clone(),
 			loss_type: loss_type,
-			mean_axes: self.mean_axes,
+			mean_axes: mean_axes,
 			keep_dims: self.keep_dims,
+			reduction: reduction,
 		})
 	}
 }
@@ -144,6 +164,7 @@ pub struct MaeInstance {
 	loss_type: LossType,
 	mean_axes: SmallVec<[isize; 6]>,
 	keep_dims: bool,
+	reduction: Reduction,
 }
 
 impl OpInstance for MaeInstance {
@@ -184,6 +205,15 @@ impl OpInstance for MaeInstance {
 		}
 	}
 
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![
+			("multiplier", format!("{:?}", self.multiplier)),
+			("mean_axes", format!("{:?}", &self.mean_axes[..])),
+			("keep_dims", format!("{:?}", self.keep_dims)),
+			("reduction", format!("{:?}", self.reduction)),
+		]
+	}
 }
 
 fn calc_output_shape(input_shape: &[usize], axes: &[isize], keep_dims: bool) -> SmallVec<[usize; 6]> {
@@ -217,15 +247,17 @@ struct MaeJointPass {
 	input1_id: NodeID,
 	input2_id: NodeID,
 	mean_axes: SmallVec<[isize; 6]>,
+	reduction: Reduction,
 }
 
 impl MaeJointPass {
-	pub fn new(multiplier: f32, input1_id: NodeID, input2_id: NodeID, mean_axes: SmallVec<[isize; 6]>) -> Self {
+	pub fn new(multiplier: f32, input1_id: NodeID, input2_id: NodeID, mean_axes: SmallVec<[isize; 6]>, reduction: Reduction) -> Self {
 		MaeJointPass {
 			multiplier,
 			input1_id,
 			input2_id,
 			mean_axes,
+			reduction,
 		}
 	}
 }
@@ -250,7 +282,8 @@ impl Pass for MaeJointPass {
 		let input_shape: SmallVec<[usize; 6]> = input1.shape().iter().cloned().collect();
 
 		let divisor: usize = input_shape.iter().zip(reduction_mask(input_shape.len(), &self.mean_axes)).filter_map(|(dim, reduce)| if reduce{Some(dim)} else {None}).product();
-		let multiplier = self.multiplier/divisor as f32;
+		let multiplier = self.multiplier/self.reduction.divisor(divisor) as f32;
+		let grad_multiplier = multiplier * data.loss_scale();
 
 		//let output_shape_actual = calc_output_shape(&input_shape, &self.axes, self.keep_dims);
 		let output_shape_keep_dims = calc_output_shape(&input_shape, &self.mean_axes, true);
@@ -274,8 +307,8 @@ impl Pass for MaeJointPass {
 				.apply(|input1, input2, input1_grad, input2_grad| { 
 					let diff = input1-input2;
 					error += diff.abs()*multiplier;
-					*input1_grad +=  diff.signum()*multiplier;
-					*input2_grad += -diff.signum()*multiplier;
+					*input1_grad +=  diff.signum()*grad_multiplier;
+					*input2_grad += -diff.signum()*grad_multiplier;
 				});
 			}
 		} else if data.is_required(&self.input1_id.gradient_id()) {
@@ -292,7 +325,7 @@ impl Pass for MaeJointPass {
 				.apply(|input1, input2, input1_grad| { 
 					let diff = input1-input2;
 					error += diff.abs()*multiplier;
-					*input1_grad +=  diff.signum()*multiplier;
+					*input1_grad +=  diff.signum()*grad_multiplier;
 				});
 			}
 		} else if data.is_required(&self.input2_id.gradient_id()) {
@@ -309,7 +342,7 @@ impl Pass for MaeJointPass {
 				.apply(|input1, input2, input2_grad| { 
 					let diff = input1-input2;
 					error += diff.abs()*multiplier;
-					*input2_grad += -diff.signum()*multiplier;
+					*input2_grad += -diff.signum()*grad_multiplier;
 				});
 			}
 		}
@@ -329,10 +362,11 @@ struct MaeForward {
 	output_id: NodeID,
 	mean_axes: SmallVec<[isize; 6]>,
 	keep_dims: bool,
+	reduction: Reduction,
 }
 
 impl MaeForward {
-	pub fn new(multiplier: f32, input1_id: NodeID, input2_id: NodeID, output_id: NodeID, mean_axes: SmallVec<[isize; 6]>, keep_dims: bool) -> Self {
+	pub fn new(multiplier: f32, input1_id: NodeID, input2_id: NodeID, output_id: NodeID, mean_axes: SmallVec<[isize; 6]>, keep_dims: bool, reduction: Reduction) -> Self {
 		MaeForward {
 			multiplier,
 			input1_id,
@@ -340,6 +374,7 @@ impl MaeForward {
 			output_id,
 			mean_axes,
 			keep_dims,
+			reduction,
 		}
 	}
 }
@@ -366,7 +401,7 @@ impl Pass for MaeForward {
 		let output_shape: SmallVec<[usize; 6]> = output.shape().iter().cloned().collect();
 
 		let divisor: usize = input_shape.iter().zip(reduction_mask(input_shape.len(), &self.mean_axes)).filter_map(|(dim, reduce)| if reduce{Some(dim)} else {None}).product();
-		let multiplier = self.multiplier/divisor as f32;
+		let multiplier = self.multiplier/self.reduction.divisor(divisor) as f32;
 
 		let output_shape_actual = calc_output_shape(&input_shape, &self.mean_axes, self.keep_dims);
 		let output_shape_keep_dims = calc_output_shape(&input_shape, &self.mean_axes, true);
@@ -400,10 +435,11 @@ struct MaeBackward {
 	output_id: NodeID,
 	mean_axes: SmallVec<[isize; 6]>,
 	keep_dims: bool,
+	reduction: Reduction,
 }
 
 impl MaeBackward {
-	pub fn new(multiplier: f32, input1_id: NodeID, input2_id: NodeID, output_id: NodeID, mean_axes: SmallVec<[isize; 6]>, keep_dims: bool) -> Self {
+	pub fn new(multiplier: f32, input1_id: NodeID, input2_id: NodeID, output_id: NodeID, mean_axes: SmallVec<[isize; 6]>, keep_dims: bool, reduction: Reduction) -> Self {
 		MaeBackward {
 			multiplier,
 			input1_id,
@@ -411,6 +447,7 @@ impl MaeBackward {
 			output_id,
 			mean_axes,
 			keep_dims,
+			reduction,
 		}
 	}
 }
@@ -437,7 +474,7 @@ impl Pass for MaeBackward {
 		let output_shape: SmallVec<[usize; 6]> = output_grad.shape().iter().cloned().collect();
 
 		let divisor: usize = input_shape.iter().zip(reduction_mask(input_shape.len(), &self.mean_axes)).filter_map(|(dim, reduce)| if reduce{Some(dim)} else {None}).product();
-		let multiplier = self.multiplier/divisor as f32;
+		let multiplier = self.multiplier/self.reduction.divisor(divisor) as f32;
 
 		let output_shape_actual = calc_output_shape(&input_shape, &self.mean_axes, self.keep_dims);
 		let output_shape_keep_dims = calc_output_shape(&input_shape, &self.mean_axes, true);
@@ -4,9 +4,16 @@ pub mod mae;
 pub mod cross_entropy;
 pub mod prediction;
 pub mod robust;
+pub mod cosine_similarity;
+pub mod log_cosh;
+pub mod focal_loss;
+pub mod weighted_loss;
+pub mod softmax_cross_entropy;
 
 
 use id::{NodeID, PassID};
+use graph::Result;
+use smallvec::SmallVec;
 
 #[derive(Clone, Debug)] 
 pub(crate) enum LossType {
@@ -18,4 +25,48 @@ pub(crate) enum LossType {
 		forward_id: PassID,
 		backward_id: PassID
 	},
-}
\ No newline at end of file
+}
+pub mod triplet_loss;
+
+/// How loss values are combined over the reduced axes of a loss `Op`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reduction {
+	/// The sum of the values is divided by the number of values reduced.
+	Mean,
+	/// The values are summed.
+	Sum,
+	/// No reduction, the loss of each element is written to the output node.
+	None,
+}
+
+impl Reduction {
+	/// Returns the divisor applied to the sum of `count` reduced values.
+	pub(crate) fn divisor(&self, count: usize) -> usize {
+		match *self {
+			Reduction::Mean => count,
+			Reduction::Sum | Reduction::None => 1,
+		}
+	}
+}
+
+/// Resolves the reduction and axes supplied to an elementwise loss builder into those used by its passes.
+///
+/// If no reduction is set the supplied axes are averaged, and no other axes are reduced.
+/// If a reduction is set and no axes are supplied, all `ndim` axes are reduced.
+pub(crate) fn resolve_reduction(reduction: Option<Reduction>, axes: &[isize], ndim: usize, has_output: bool) -> Result<(Reduction, SmallVec<[isize; 6]>)> {
+	match reduction {
+		Option::None => Ok((Reduction::Mean, axes.iter().cloned().collect())),
+		Some(Reduction::None) => {
+			ensure!(has_output, "Reduction::None requires an output node for the per element loss");
+			ensure!(axes.is_empty(), format!("Reduction::None can not be combined with reduction axes: {:?}", axes));
+			Ok((Reduction::None, SmallVec::new()))
+		},
+		Some(reduction) => {
+			if axes.is_empty() {
+				Ok((reduction, (0..ndim as isize).collect()))
+			} else {
+				Ok((reduction, axes.iter().cloned().collect()))
+			}
+		},
+	}
+}
@@ -2,7 +2,7 @@ use graph::{GraphDef, GraphShapes, ErrorKind, Result};
 use id::{NodeID, DataID, OpID, PassID};
 use storage::Storage;
 use ops::{standard_op_name, Op, OpInstance, Pass};
-use ops::loss::LossType;
+use ops::loss::{LossType, Reduction, resolve_reduction};
 use shape::NodeShape;
 use smallvec::SmallVec;
 use ndarray::{Dimension, Zip};
@@ -22,6 +22,7 @@ pub struct Mse {
 	output: Option<NodeID>,
 	mean_axes: SmallVec<[isize; 6]>,
 	keep_dims: bool,
+	reduction: Option<Reduction>,
 	multiplier: f32,
 	name: Option<String>,
 }
@@ -34,6 +35,7 @@ impl Mse {
 			output: None,
 			mean_axes: SmallVec::new(),
 			keep_dims: false,
+			reduction: None,
 			multiplier: 1.0,
 			name: None,
 		}
@@ -66,6 +68,19 @@ impl Mse {
 		self
 	}
 
+	/// Sets how the loss is reduced.
+	///
+	/// * `Mean`: the loss is averaged over `mean_axes()`, or over all axes if none are supplied.
+	/// * `Sum`: the loss is summed over `mean_axes()`, or over all axes if none are supplied.
+	/// * `None`: the loss of each element is written to the output node, which must be set and have the same shape as the inputs.
+	///
+	/// If a `Joint` loss is not reduced to a scalar, the remaining values are summed.
+	/// Default: not set, the loss is averaged over `mean_axes()` only, and summed over any other axes.
+	pub fn reduction(mut self, reduction: Reduction) -> Self {
+		self.reduction = Some(reduction);
+		self
+	}
+
 	/// Applies a multiplier to the output or to the loss generated.
 	pub fn multiplier(mut self, multiplier: f32) -> Self {
 		self.multiplier = multiplier;
@@ -87,6 +102,7 @@ impl Op for Mse {
 	}
 
 	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let (reduction, mean_axes) = resolve_reduction(self.reduction, &self.mean_axes, self.input1_id.shape().ndim(), self.output.is_some())?;
 
 		let name =  if let Some(ref output_id) = self.output {
 			standard_op_name(&self, &self.name, graph, &[self.input1_id.clone(), self.input2_id.clone()], &[output_id.clone()])
@@ -102,15 +118,17 @@ impl Op for Mse {
 					self.input1_id.clone(),
 					self.input2_id.clone(),
 					output_id.clone(),
-					self.mean_axes.clone(),
-					self.keep_dims)),
+					mean_axes.clone(),
+					self.keep_dims,
+					reduction)),
 				backward_id: graph.add_pass(MseBackward::new(
 					self.multiplier,
 					self.input1_id.clone(),
 					self.input2_id.clone(),
 					output_id.clone(),
-					self.mean_axes.clone(),
-					self.keep_dims)),
+					mean_axes.clone(),
+					self.keep_dims,
+					reduction)),
 			}
 		} else {
 			LossType::Joint{
@@ -118,7 +136,8 @@ impl Op for Mse {
 					self.multiplier,
 					self.input1_id.clone(),
 					self.input2_id.clone(),
-					self.mean_axes.clone()))
+					mean_axes.clone(),
+					reduction))
 			}
 		};
 
@@ -128,8 +147,9 @@ impl Op for Mse {
 			input1_id: self.input1_id.clone(),
 			input2_id: self.input2_id.clone(),
 			loss_type: loss_type,
-			mean_axes: self.mean_axes,
+			mean_axes: mean_axes,
 			keep_dims: self.keep_dims,
+			reduction: reduction,
 		})
 	}
 }
@@ -144,6 +164,7 @@ pub struct MseInstance {
 	loss_type: LossType,
 	mean_axes: SmallVec<[isize; 6]>,
 	keep_dims: bool,
+	reduction: Reduction,
 }
 
 impl OpInstance for MseInstance {
@@ -184,6 +205,15 @@ impl OpInstance for MseInstance {
 		}
 	}
 
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![
+			("multiplier", format!("{:?}", self.multiplier)),
+			("mean_axes", format!("{:?}", &self.mean_axes[..])),
+			("keep_dims", format!("{:?}", self.keep_dims)),
+			("reduction", format!("{:?}", self.reduction)),
+		]
+	}
 }
 
 fn calc_output_shape(input_shape: &[usize], axes: &[isize], keep_dims: bool) -> SmallVec<[usize; 6]> {
@@ -217,15 +247,17 @@ struct MseJointPass {
 	input1_id: NodeID,
 	input2_id: NodeID,
 	mean_axes: SmallVec<[isize; 6]>,
+	reduction: Reduction,
 }
 
 impl MseJointPass {
-	pub fn new(multiplier: f32, input1_id: NodeID, input2_id: NodeID, mean_axes: SmallVec<[isize; 6]>) -> Self {
+	pub fn new(multiplier: f32, input1_id: NodeID, input2_id: NodeID, mean_axes: SmallVec<[isize; 6]>, reduction: Reduction) -> Self {
 		MseJointPass {
 			multiplier,
 			input1_id,
 			input2_id,
 			mean_axes,
+			reduction,
 		}
 	}
 }
@@ -250,7 +282,8 @@ impl Pass for MseJointPass {
 		let input_shape: SmallVec<[usize; 6]> = input1.shape().iter().cloned().collect();
 
 		let divisor: usize = input_shape.iter().zip(reduction_mask(input_shape.len(), &self.mean_axes)).filter_map(|(dim, reduce)| if reduce{Some(dim)} else {None}).product();
-		let multiplier = self.multiplier/divisor as f32;
+		let multiplier = self.multiplier/self.reduction.divisor(divisor) as f32;
+		let grad_multiplier = multiplier * data.loss_scale();
 
 		//let output_shape_actual = calc_output_shape(&input_shape, &self.axes, self.keep_dims);
 		let output_shape_keep_dims = calc_output_shape(&input_shape, &self.mean_axes, true);
@@ -274,8 +307,8 @@ impl Pass for MseJointPass {
 				.apply(|input1, input2, input1_grad, input2_grad| { 
 					let diff = input1-input2;
 					error += diff*diff*multiplier;
-					*input1_grad +=  2.0*diff*multiplier;
-					*input2_grad += -2.0*diff*multiplier;
+					*input1_grad +=  2.0*diff*grad_multiplier;
+					*input2_grad += -2.0*diff*grad_multiplier;
 				});
 			}
 
@@ -293,7 +326,7 @@ impl Pass for MseJointPass {
 				.apply(|input1, input2, input1_grad| { 
 					let diff = input1-input2;
 					error += diff*diff*multiplier;
-					*input1_grad +=  2.0*diff*multiplier;
+					*input1_grad +=  2.0*diff*grad_multiplier;
 				});
 			}
 		} else if data.is_required(&self.input2_id.gradient_id()) {
@@ -310,7 +343,7 @@ impl Pass for MseJointPass {
 				.apply(|input1, input2, input2_grad| { 
 					let diff = input1-input2;
 					error += diff*diff*multiplier;
-					*input2_grad += -2.0*diff*multiplier;
+					*input2_grad += -2.0*diff*grad_multiplier;
 				});
 			}
 		}
@@ -330,10 +363,11 @@ struct MseForward {
 	output_id: NodeID,
 	mean_axes: SmallVec<[isize; 6]>,
 	keep_dims: bool,
+	reduction: Reduction,
 }
 
 impl MseForward {
-	pub fn new(multiplier: f32, input1_id: NodeID, input2_id: NodeID, output_id: NodeID, mean_axes: SmallVec<[isize; 6]>, keep_dims: bool) -> Self {
+	pub fn new(multiplier: f32, input1_id: NodeID, input2_id: NodeID, output_id: NodeID, mean_axes: SmallVec<[isize; 6]>, keep_dims: bool, reduction: Reduction) -> Self {
 		MseForward {
 			multiplier,
 			input1_id,
@@ -341,6 +375,7 @@ impl MseForward {
 			output_id,
 			mean_axes,
 			keep_dims,
+			reduction,
 		}
 	}
 }
@@ -367,7 +402,7 @@ impl Pass for MseForward {
 		let output_shape: SmallVec<[usize; 6]> = output.shape().iter().cloned().collect();
 
 		let divisor: usize = input_shape.iter().zip(reduction_mask(input_shape.len(), &self.mean_axes)).filter_map(|(dim, reduce)| if reduce{Some(dim)} else {None}).product();
-		let multiplier = self.multiplier/divisor as f32;
+		let multiplier = self.multiplier/self.reduction.divisor(divisor) as f32;
 
 		let output_shape_actual = calc_output_shape(&input_shape, &self.mean_axes, self.keep_dims);
 		let output_shape_keep_dims = calc_output_shape(&input_shape, &self.mean_axes, true);
@@ -401,10 +436,11 @@ struct MseBackward {
 	output_id: NodeID,
 	mean_axes: SmallVec<[isize; 6]>,
 	keep_dims: bool,
+	reduction: Reduction,
 }
 
 impl MseBackward {
-	pub fn new(multiplier: f32, input1_id: NodeID, input2_id: NodeID, output_id: NodeID, mean_axes: SmallVec<[isize; 6]>, keep_dims: bool) -> Self {
+	pub fn new(multiplier: f32, input1_id: NodeID, input2_id: NodeID, output_id: NodeID, mean_axes: SmallVec<[isize; 6]>, keep_dims: bool, reduction: Reduction) -> Self {
 		MseBackward {
 			multiplier,
 			input1_id,
@@ -412,6 +448,7 @@ impl MseBackward {
 			output_id,
 			mean_axes,
 			keep_dims,
+			reduction,
 		}
 	}
 }
@@ -438,7 +475,7 @@ impl Pass for MseBackward {
 		let output_shape: SmallVec<[usize; 6]> = output_grad.shape().iter().cloned().collect();
 
 		let divisor: usize = input_shape.iter().zip(reduction_mask(input_shape.len(), &self.mean_axes)).filter_map(|(dim, reduce)| if reduce{Some(dim)} else {None}).product();
-		let multiplier = self.multiplier/divisor as f32;
+		let multiplier = self.multiplier/self.reduction.divisor(divisor) as f32;
 
 		let output_shape_actual = calc_output_shape(&input_shape, &self.mean_axes, self.keep_dims);
 		let output_shape_keep_dims = calc_output_shape(&input_shape, &self.mean_axes, true);
@@ -563,4 +600,77 @@ fn _mse_output_backprop() -> Result<()>{
 	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
 
 	Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_mse_reduction(){
+	_mse_reduction().unwrap();
+}
+
+fn _mse_reduction() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::proportional::Proportional;
+	use ndarray::{arr2, ArrayD};
+
+	let input1 = arr2(&[[1.0, 2.0, 3.0], [0.0, -1.0, 4.0]]).into_dyn();
+	let input2 = arr2(&[[0.0, 0.0, 1.0], [1.0, 1.0, 1.0]]).into_dyn();
+	let diff = &input1 - &input2;
+	let sqr_sum: f32 = diff.iter().map(|d| d * d).sum();
+
+	// joint losses, checking the loss value and the gradient scale
+	for &(reduction, divisor) in [(Reduction::Mean, 6.0), (Reduction::Sum, 1.0)].iter() {
+		let mut g = GraphDef::new();
+		let node1 = g.new_node(shape![2, 3], "input1", tag![])?;
+		let node2 = g.new_node(shape![2, 3], "input2", tag![])?;
+		let _o1 = g.new_op(Mse::new(&node1, &node2).reduction(reduction), tag![])?;
+
+		let mut subgraph = g.subgraph(&[node1.value_id(), node2.value_id()], &[node1.gradient_id()])?;
+		let storage = subgraph.execute(vec![input1.clone(), input2.clone()])?;
+
+		assert!((storage.loss() - sqr_sum / divisor).abs() < 1e-5, "{:?} {}", reduction, storage.loss());
+		let expected_grad = diff.mapv(|d| 2.0 * d / divisor);
+		assert!(storage.get(&node1.gradient_id())?.all_close(&expected_grad, 1e-5), "{:?}", reduction);
+	}
+
+	// reduced outputs
+	for &(reduction, divisor) in [(Reduction::Mean, 6.0), (Reduction::Sum, 1.0)].iter() {
+		let mut g = GraphDef::new();
+		let node1 = g.new_node(shape![2, 3], "input1", tag![])?;
+		let node2 = g.new_node(shape![2, 3], "input2", tag![])?;
+		let node3 = g.new_node(shape![1, 1], "output", tag![])?;
+		let _o1 = g.new_op(Mse::new(&node1, &node2).reduction(reduction).keep_dims(true).output(&node3), tag![])?;
+		let _o2 = g.new_op(Proportional::new(&node3), tag![])?;
+
+		let mut subgraph = g.subgraph(&[node1.value_id(), node2.value_id()], &[node3.value_id(), node1.gradient_id()])?;
+		let storage = subgraph.execute(vec![input1.clone(), input2.clone()])?;
+
+		assert!((storage.get(&node3.value_id())?[[0, 0]] - sqr_sum / divisor).abs() < 1e-5, "{:?}", reduction);
+		let expected_grad = diff.mapv(|d| 2.0 * d / divisor);
+		assert!(storage.get(&node1.gradient_id())?.all_close(&expected_grad, 1e-5), "{:?}", reduction);
+	}
+
+	// per element output
+	{
+		let mut g = GraphDef::new();
+		let node1 = g.new_node(shape![2, 3], "input1", tag![])?;
+		let node2 = g.new_node(shape![2, 3], "input2", tag![])?;
+		let node3 = g.new_node(shape![2, 3], "output", tag![])?;
+		let _o1 = g.new_op(Mse::new(&node1, &node2).reduction(Reduction::None).output(&node3), tag![])?;
+		let _o2 = g.new_op(Proportional::new(&node3), tag![])?;
+
+		let mut subgraph = g.subgraph(&[node1.value_id(), node2.value_id()], &[node3.value_id(), node1.gradient_id()])?;
+		let storage = subgraph.execute(vec![input1.clone(), input2.clone()])?;
+
+		let expected: ArrayD<f32> = diff.mapv(|d| d * d);
+		assert!(storage.get(&node3.value_id())?.all_close(&expected, 1e-5));
+		assert!(storage.get(&node1.gradient_id())?.all_close(&diff.mapv(|d| 2.0 * d), 1e-5));
+	}
+
+	// no reduction requires an output
+	let mut g = GraphDef::new();
+	let node1 = g.new_node(shape![2, 3], "input1", tag![])?;
+	let node2 = g.new_node(shape![2, 3], "input2", tag![])?;
+	assert!(g.new_op(Mse::new(&node1, &node2).reduction(Reduction::None), tag![]).is_err());
+
+	Ok(())
+}
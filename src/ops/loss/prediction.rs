@@ -3,7 +3,7 @@ use id::{NodeID, DataID, OpID, PassID};
 use storage::Storage;
 use ops::{standard_op_name, Op, OpInstance, Pass};
 use shape::{NodeShape, NodeDim};
-use ndarray::{Dimension, IxDyn};
+use ndarray::{Dimension, IxDyn, ArrayViewD, ArrayView1, Axis};
 use std::any::Any;
 use smallvec::SmallVec;
 use std::f32;
@@ -238,6 +238,36 @@ impl Pass for PredictionForward {
 }
 
 
+/// Returns the fraction of examples for which the argmax of the innermost axis of `predictions` matches the target class.
+///
+/// `targets` can either be one-hot (or any scores) with the same shape as `predictions`, in which case its argmax is the target class,
+/// or integer class indices with the innermost axis of `predictions` removed.
+/// Ties are resolved in favour of the lowest index.
+pub fn accuracy(predictions: ArrayViewD<f32>, targets: ArrayViewD<f32>) -> f32 {
+	assert!(predictions.ndim() > 0, "predictions must have at least one axis");
+	let classes_axis = Axis(predictions.ndim() - 1);
+	let one_hot = targets.shape() == predictions.shape();
+	assert!(one_hot || targets.shape() == &predictions.shape()[..predictions.ndim() - 1],
+		"targets shape: {:?} must either match predictions shape: {:?}, or omit its innermost axis", targets.shape(), predictions.shape());
+
+	let argmax = |lane: ArrayView1<f32>| lane.iter().enumerate().fold((0, f32::NEG_INFINITY), |(max_i, max), (i, &x)| if x > max {(i, x)} else {(max_i, max)}).0;
+
+	let predicted_classes = predictions.lanes(classes_axis).into_iter().map(|lane| argmax(lane));
+	let target_classes: Vec<usize> = if one_hot {
+		targets.lanes(classes_axis).into_iter().map(|lane| argmax(lane)).collect()
+	} else {
+		targets.iter().map(|&x| x.round() as usize).collect()
+	};
+
+	let count = target_classes.len();
+	if count == 0 {
+		return 0.0;
+	}
+	let correct = predicted_classes.zip(target_classes).filter(|&(predicted, target)| predicted == target).count();
+	correct as f32 / count as f32
+}
+
+
 #[test]
 fn test_prediction(){
 	_prediction().unwrap();
@@ -286,4 +316,32 @@ fn _prediction() -> Result<()>{
 	assert_eq!(out.as_slice().unwrap(), expect.as_slice());
 
 	Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_accuracy(){
+	use ndarray::{arr1, arr2};
+
+	let predictions = arr2(&[
+		[0.1, 0.7, 0.2],
+		[0.5, 0.3, 0.2],
+		[0.2, 0.2, 0.6],
+		[0.3, 0.4, 0.3],
+	]).into_dyn();
+
+	// three of four predictions are correct
+	let class_targets = arr1(&[1.0, 0.0, 1.0, 1.0]).into_dyn();
+	assert_eq!(accuracy(predictions.view(), class_targets.view()), 0.75);
+
+	let one_hot_targets = arr2(&[
+		[0.0, 1.0, 0.0],
+		[1.0, 0.0, 0.0],
+		[0.0, 1.0, 0.0],
+		[0.0, 1.0, 0.0],
+	]).into_dyn();
+	assert_eq!(accuracy(predictions.view(), one_hot_targets.view()), 0.75);
+
+	// two of four predictions are correct
+	let class_targets = arr1(&[1.0, 2.0, 2.0, 0.0]).into_dyn();
+	assert_eq!(accuracy(predictions.view(), class_targets.view()), 0.5);
+}
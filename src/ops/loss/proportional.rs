@@ -78,6 +78,9 @@ impl OpInstance for ProportionalInstance {
 
 	fn propagate_shape_constraints(&self, _shapes: &mut GraphShapes) -> Result<()>{Ok(())}
 
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("multiplier", format!("{:?}", self.multiplier))]
+	}
 }
 
 
@@ -115,6 +118,7 @@ impl Pass for ProportionalBackward {
 		assert!(input_grad.len() == n);
 
 		let multiplier = self.multiplier/n as f32;
+		let grad_multiplier = multiplier * data.loss_scale();
 		const SIMD: usize = 16;
 		let mut error = 0.0;
 		let mut errs = [0.;SIMD];
@@ -125,13 +129,13 @@ impl Pass for ProportionalBackward {
 
 			for j in 0..SIMD{
 				errs[j] += input1_val[j]*multiplier;
-				input1_grad[j] += multiplier;
+				input1_grad[j] += grad_multiplier;
 			}
 		}
 
 		for j in (n/SIMD)*SIMD..n {
 			error += input_val[j]*multiplier;
-			input_grad[j] += multiplier;
+			input_grad[j] += grad_multiplier;
 		}
 
 
@@ -2,7 +2,7 @@ use graph::{GraphDef, GraphShapes, ErrorKind, Result};
 use id::{NodeID, DataID, OpID, PassID};
 use storage::Storage;
 use ops::{standard_op_name, Op, OpInstance, Pass};
-use ops::loss::LossType;
+use ops::loss::{LossType, Reduction, resolve_reduction};
 use shape::NodeShape;
 use smallvec::SmallVec;
 use ndarray::{Dimension, Zip};
@@ -62,6 +62,7 @@ impl Robust {
 			output: None,
 			mean_axes: SmallVec::new(),
 			keep_dims: false,
+			reduction: None,
 			multiplier: 1.0,
 			scale: scale,
 			power: power,
@@ -96,6 +97,19 @@ impl Robust {
 		self
 	}
 
+	/// Sets how the loss is reduced.
+	///
+	/// * `Mean`: the loss is averaged over `mean_axes()`, or over all axes if none are supplied.
+	/// * `Sum`: the loss is summed over `mean_axes()`, or over all axes if none are supplied.
+	/// * `None`: the loss of each element is written to the output node, which must be set and have the same shape as the inputs.
+	///
+	/// If a `Joint` loss is not reduced to a scalar, the remaining values are summed.
+	/// Default: not set, the loss is averaged over `mean_axes()` only, and summed over any other axes.
+	pub fn reduction(mut self, reduction: Reduction) -> Self {
+		self.reduction = Some(reduction);
+		self
+	}
+
 	/// Applies a multiplier to the output or to the loss generated.
 	pub fn multiplier(mut self, multiplier: f32) -> Self {
 		self.multiplier = multiplier;
@@ -117,6 +131,7 @@ impl Op for Robust {
 	}
 
 	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let (reduction, mean_axes) = resolve_reduction(self.reduction, &self.mean_axes, self.input1_id.shape().ndim(), self.output.is_some())?;
 
 		let name =  if let Some(ref output_id) = self.output {
 			standard_op_name(&self, &self.name, graph, &[self.input1_id.clone(), self.input2_id.clone()], &[output_id.clone()])
@@ -134,8 +149,9 @@ impl Op for Robust {
 					self.input1_id.clone(),
 					self.input2_id.clone(),
 					output_id.clone(),
-					self.mean_axes.clone(),
-					self.keep_dims)),
+					mean_axes.clone(),
+					self.keep_dims,
+					reduction)),
 				backward_id: graph.add_pass(RobustBackward::new(
 					self.multiplier,
 					self.scale,
@@ -143,8 +159,9 @@ impl Op for Robust {
 					self.input1_id.clone(),
 					self.input2_id.clone(),
 					output_id.clone(),
-					self.mean_axes.clone(),
-					self.keep_dims)),
+					mean_axes.clone(),
+					self.keep_dims,
+					reduction)),
 			}
 		} else {
 			LossType::Joint{
@@ -154,7 +171,8 @@ impl Op for Robust {
 					self.power,
 					self.input1_id.clone(),
 					self.input2_id.clone(),
-					self.mean_axes.clone()))
+					mean_axes.clone(),
+					reduction))
 			}
 		};
 
@@ -166,8 +184,9 @@ impl Op for Robust {
 			input1_id: self.input1_id.clone(),
 			input2_id: self.input2_id.clone(),
 			loss_type: loss_type,
-			mean_axes: self.mean_axes,
+			mean_axes: mean_axes,
 			keep_dims: self.keep_dims,
+			reduction: reduction,
 		})
 	}
 }
@@ -184,6 +203,7 @@ pub struct RobustInstance {
 	loss_type: LossType,
 	mean_axes: SmallVec<[isize; 6]>,
 	keep_dims: bool,
+	reduction: Reduction,
 }
 
 impl OpInstance for RobustInstance {
@@ -259,10 +279,11 @@ struct RobustJointPass {
 	input1_id: NodeID,
 	input2_id: NodeID,
 	mean_axes: SmallVec<[isize; 6]>,
+	reduction: Reduction,
 }
 
 impl RobustJointPass {
-	pub fn new(multiplier: f32, scale: f32, power: f32, input1_id: NodeID, input2_id: NodeID, mean_axes: SmallVec<[isize; 6]>) -> Self {
+	pub fn new(multiplier: f32, scale: f32, power: f32, input1_id: NodeID, input2_id: NodeID, mean_axes: SmallVec<[isize; 6]>, reduction: Reduction) -> Self {
 		RobustJointPass {
 			multiplier,
 			scale,
@@ -270,6 +291,7 @@ impl RobustJointPass {
 			input1_id,
 			input2_id,
 			mean_axes,
+			reduction,
 		}
 	}
 }
@@ -294,7 +316,8 @@ impl Pass for RobustJointPass {
 		let input_shape: SmallVec<[usize; 6]> = input1.shape().iter().cloned().collect();
 
 		let divisor: usize = input_shape.iter().zip(reduction_mask(input_shape.len(), &self.mean_axes)).filter_map(|(dim, reduce)| if reduce{Some(dim)} else {None}).product();
-		let multiplier = self.multiplier/divisor as f32;
+		let multiplier = self.multiplier/self.reduction.divisor(divisor) as f32;
+		let grad_multiplier = multiplier * data.loss_scale();
 
 		//let output_shape_actual = calc_output_shape(&input_shape, &self.axes, self.keep_dims);
 		//let output_shape_keep_dims = calc_output_shape(&input_shape, &self.mean_axes, true);
@@ -318,8 +341,8 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input1_grad, input2_grad| { 
 						let x = input1-input2;
 						error += multiplier * (0.5*(x/c)*(x/c)).ln_1p();
-						*input1_grad +=  multiplier * 2.0 * x / (x*x + 2.0*c*c);
-						*input2_grad += -multiplier * 2.0 * x / (x*x + 2.0*c*c);
+						*input1_grad +=  grad_multiplier * 2.0 * x / (x*x + 2.0*c*c);
+						*input2_grad += -grad_multiplier * 2.0 * x / (x*x + 2.0*c*c);
 					});
 			} else if a == f32::NEG_INFINITY {
 				Zip::from(input1) 
@@ -329,8 +352,8 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input1_grad, input2_grad| { 
 						let x = input1-input2;
 						error += -multiplier * (-0.5*(x/c)*(x/c)).exp_m1();
-						*input1_grad +=  multiplier * x/(c*c) * (-0.5*(x/c)*(x/c)).exp();
-						*input2_grad += -multiplier * x/(c*c) * (-0.5*(x/c)*(x/c)).exp();
+						*input1_grad +=  grad_multiplier * x/(c*c) * (-0.5*(x/c)*(x/c)).exp();
+						*input2_grad += -grad_multiplier * x/(c*c) * (-0.5*(x/c)*(x/c)).exp();
 					});
 			} else if a == 1.0 {
 				Zip::from(&input1) 
@@ -340,8 +363,8 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input1_grad, input2_grad| { 
 						let x = input1-input2;
 						error += multiplier * (((x/c)*(x/c) + 1.0).sqrt() - 1.0); //TODO change to numerically stable version https://stackoverflow.com/questions/32444817/numerically-stable-evaluation-of-sqrtxa-sqrtx
-						*input1_grad +=  multiplier * x/((c*c) * ((x/c)*(x/c) + 1.0).sqrt());
-						*input2_grad += -multiplier * x/((c*c) * ((x/c)*(x/c) + 1.0).sqrt());
+						*input1_grad +=  grad_multiplier * x/((c*c) * ((x/c)*(x/c) + 1.0).sqrt());
+						*input2_grad += -grad_multiplier * x/((c*c) * ((x/c)*(x/c) + 1.0).sqrt());
 					});
 			} else if a == 2.0 {
 				Zip::from(&input1) 
@@ -351,8 +374,8 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input1_grad, input2_grad| { 
 						let x = input1-input2;
 						error += multiplier * ((x/c)*(x/c))/a;;
-						*input1_grad +=  multiplier * x/(c*c);
-						*input2_grad += -multiplier * x/(c*c);
+						*input1_grad +=  grad_multiplier * x/(c*c);
+						*input2_grad += -grad_multiplier * x/(c*c);
 					});
 			} else {
 				let za = 1.0f32.max(2.0-a);
@@ -363,8 +386,8 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input1_grad, input2_grad| { 
 						let x = input1-input2;
 						error += multiplier * za / a *(((x/c)*(x/c)/za + 1.0).powf(0.5 * a) - 1.0);
-						*input1_grad +=  multiplier * x/(c*c) * ((x/c)*(x/c)/za + 1.0).powf(0.5*a - 1.0);
-						*input2_grad += -multiplier * x/(c*c) * ((x/c)*(x/c)/za + 1.0).powf(0.5*a - 1.0);
+						*input1_grad +=  grad_multiplier * x/(c*c) * ((x/c)*(x/c)/za + 1.0).powf(0.5*a - 1.0);
+						*input2_grad += -grad_multiplier * x/(c*c) * ((x/c)*(x/c)/za + 1.0).powf(0.5*a - 1.0);
 					});
 			}
 			
@@ -384,7 +407,7 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input1_grad| { 
 						let x = input1-input2;
 						error += multiplier * (0.5*(x/c)*(x/c)).ln_1p();
-						*input1_grad +=  multiplier * 2.0 * x / (x*x + 2.0*c*c);
+						*input1_grad +=  grad_multiplier * 2.0 * x / (x*x + 2.0*c*c);
 					});
 			} else if a == f32::NEG_INFINITY {
 				Zip::from(&input1) 
@@ -393,7 +416,7 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input1_grad| { 
 						let x = input1-input2;
 						error += multiplier * (-0.5*(x/c)*(x/c)).exp_m1();
-						*input1_grad +=  multiplier * x/(c*c) * (-0.5*(x/c)*(x/c)).exp();
+						*input1_grad +=  grad_multiplier * x/(c*c) * (-0.5*(x/c)*(x/c)).exp();
 					});
 			} else if a == 1.0 {
 				Zip::from(&input1) 
@@ -402,7 +425,7 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input1_grad| { 
 						let x = input1-input2;
 						error += multiplier * (((x/c)*(x/c) + 1.0).sqrt() - 1.0); //TODO change to numerically stable version https://stackoverflow.com/questions/32444817/numerically-stable-evaluation-of-sqrtxa-sqrtx
-						*input1_grad +=  multiplier * x/((c*c) * ((x/c)*(x/c) + 1.0).sqrt());
+						*input1_grad +=  grad_multiplier * x/((c*c) * ((x/c)*(x/c) + 1.0).sqrt());
 					});
 			} else if a == 2.0 {
 				Zip::from(&input1) 
@@ -411,7 +434,7 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input1_grad| { 
 						let x = input1-input2;
 						error += multiplier * ((x/c)*(x/c))/a;;
-						*input1_grad +=  multiplier * x/(c*c);
+						*input1_grad +=  grad_multiplier * x/(c*c);
 					});
 			} else {
 				let za = 1.0f32.max(2.0-a);
@@ -421,7 +444,7 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input1_grad| { 
 						let x = input1-input2;
 						error += multiplier * za / a *(((x/c)*(x/c)/za + 1.0).powf(0.5 * a) - 1.0);
-						*input1_grad +=  multiplier * x/(c*c) * ((x/c)*(x/c)/za + 1.0).powf(0.5*a - 1.0);
+						*input1_grad +=  grad_multiplier * x/(c*c) * ((x/c)*(x/c)/za + 1.0).powf(0.5*a - 1.0);
 					});
 			}
 
@@ -442,7 +465,7 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input2_grad| { 
 						let x = input1-input2;
 						error += multiplier * (0.5*(x/c)*(x/c)).ln_1p();
-						*input2_grad += -multiplier * 2.0 * x / (x*x + 2.0*c*c);
+						*input2_grad += -grad_multiplier * 2.0 * x / (x*x + 2.0*c*c);
 					});
 			} else if a == f32::NEG_INFINITY {
 				Zip::from(&input1) 
@@ -451,7 +474,7 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input2_grad| { 
 						let x = input1-input2;
 						error += multiplier * (-0.5*(x/c)*(x/c)).exp_m1();
-						*input2_grad += -multiplier * x/(c*c) * (-0.5*(x/c)*(x/c)).exp();
+						*input2_grad += -grad_multiplier * x/(c*c) * (-0.5*(x/c)*(x/c)).exp();
 					});
 			} else if a == 1.0 {
 				Zip::from(&input1) 
@@ -460,7 +483,7 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input2_grad| { 
 						let x = input1-input2;
 						error += multiplier * (((x/c)*(x/c) + 1.0).sqrt() - 1.0); //TODO change to numerically stable version https://stackoverflow.com/questions/32444817/numerically-stable-evaluation-of-sqrtxa-sqrtx
-						*input2_grad += -multiplier * x/((c*c) * ((x/c)*(x/c) + 1.0).sqrt());
+						*input2_grad += -grad_multiplier * x/((c*c) * ((x/c)*(x/c) + 1.0).sqrt());
 					});
 			} else if a == 2.0 {
 				Zip::from(&input1) 
@@ -469,7 +492,7 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input2_grad| { 
 						let x = input1-input2;
 						error += multiplier * ((x/c)*(x/c))/a;;
-						*input2_grad += -multiplier * x/(c*c);
+						*input2_grad += -grad_multiplier * x/(c*c);
 					});
 			} else {
 				let za = 1.0f32.max(2.0-a);
@@ -479,7 +502,7 @@ impl Pass for RobustJointPass {
 					.apply(|input1, input2, input2_grad| { 
 						let x = input1-input2;
 						error += multiplier * za / a *(((x/c)*(x/c)/za + 1.0).powf(0.5 * a) - 1.0);
-						*input2_grad += -multiplier * x/(c*c) * ((x/c)*(x/c)/za + 1.0).powf(0.5*a - 1.0);
+						*input2_grad += -grad_multiplier * x/(c*c) * ((x/c)*(x/c)/za + 1.0).powf(0.5*a - 1.0);
 					});
 			}
 			
@@ -502,10 +525,11 @@ struct RobustForward {
 	output_id: NodeID,
 	mean_axes: SmallVec<[isize; 6]>,
 	keep_dims: bool,
+	reduction: Reduction,
 }
 
 impl RobustForward {
-	pub fn new(multiplier: f32, scale: f32, power: f32, input1_id: NodeID, input2_id: NodeID, output_id: NodeID, mean_axes: SmallVec<[isize; 6]>, keep_dims: bool) -> Self {
+	pub fn new(multiplier: f32, scale: f32, power: f32, input1_id: NodeID, input2_id: NodeID, output_id: NodeID, mean_axes: SmallVec<[isize; 6]>, keep_dims: bool, reduction: Reduction) -> Self {
 		RobustForward {
 			multiplier,
 			scale,
@@ -515,6 +539,7 @@ impl RobustForward {
 			output_id,
 			mean_axes,
 			keep_dims,
+			reduction,
 		}
 	}
 }
@@ -541,7 +566,7 @@ impl Pass for RobustForward {
 		let output_shape: SmallVec<[usize; 6]> = output.shape().iter().cloned().collect();
 
 		let divisor: usize = input_shape.iter().zip(reduction_mask(input_shape.len(), &self.mean_axes)).filter_map(|(dim, reduce)| if reduce{Some(dim)} else {None}).product();
-		let multiplier = self.multiplier/divisor as f32;
+		let multiplier = self.multiplier/self.reduction.divisor(divisor) as f32;
 
 		let output_shape_actual = calc_output_shape(&input_shape, &self.mean_axes, self.keep_dims);
 		let output_shape_keep_dims = calc_output_shape(&input_shape, &self.mean_axes, true);
@@ -614,10 +639,11 @@ struct RobustBackward {
 	output_id: NodeID,
 	mean_axes: SmallVec<[isize; 6]>,
 	keep_dims: bool,
+	reduction: Reduction,
 }
 
 impl RobustBackward {
-	pub fn new(multiplier: f32, scale: f32, power: f32, input1_id: NodeID, input2_id: NodeID, output_id: NodeID, mean_axes: SmallVec<[isize; 6]>, keep_dims: bool) -> Self {
+	pub fn new(multiplier: f32, scale: f32, power: f32, input1_id: NodeID, input2_id: NodeID, output_id: NodeID, mean_axes: SmallVec<[isize; 6]>, keep_dims: bool, reduction: Reduction) -> Self {
 		RobustBackward {
 			multiplier,
 			scale,
@@ -627,6 +653,7 @@ impl RobustBackward {
 			output_id,
 			mean_axes,
 			keep_dims,
+			reduction,
 		}
 	}
 }
@@ -653,7 +680,7 @@ impl Pass for RobustBackward {
 		let output_shape: SmallVec<[usize; 6]> = output_grad.shape().iter().cloned().collect();
 
 		let divisor: usize = input_shape.iter().zip(reduction_mask(input_shape.len(), &self.mean_axes)).filter_map(|(dim, reduce)| if reduce{Some(dim)} else {None}).product();
-		let multiplier = self.multiplier/divisor as f32;
+		let multiplier = self.multiplier/self.reduction.divisor(divisor) as f32;
 
 		let output_shape_actual = calc_output_shape(&input_shape, &self.mean_axes, self.keep_dims);
 		let output_shape_keep_dims = calc_output_shape(&input_shape, &self.mean_axes, true);
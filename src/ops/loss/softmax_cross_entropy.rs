@@ -0,0 +1,351 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use ops::loss::Reduction;
+use std::any::Any;
+
+/// An `Op` which implements a Softmax followed by a Cross entropy Loss, for integer class targets
+///
+/// This op expects one input tensor of unnormalised logits, with the classes along the innermost axis,
+/// and a second of integer class indices with the innermost axis of the logits removed, e.g. logits of shape `[batch, seq, classes]` and targets of shape `[batch, seq]`.
+///
+/// The loss is `-ln(softmax(logits)[target])` averaged over all positions.
+/// Calculating the softmax within the loss avoids the loss of precision from taking the log of small probabilities.
+///
+/// If `ignore_index()` is set, positions with that target, such as padding in a batch of sequences, contribute nothing to the loss or gradient,
+/// and the average is taken over the remaining positions only.
+/// If `reduction()` is set to `Sum` the loss is summed over positions rather than averaged.
+///
+/// This `Op` has no output and will generate loss and gradients. No gradient is generated for the targets.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct SoftmaxCrossEntropy {
+	logits_id: NodeID,
+	targets_id: NodeID,
+	ignore_index: Option<usize>,
+	reduction: Reduction,
+	multiplier: f32,
+	name: Option<String>,
+}
+
+impl SoftmaxCrossEntropy {
+	pub fn new(logits_id: &NodeID, targets_id: &NodeID) -> Self {
+		SoftmaxCrossEntropy {
+			logits_id: logits_id.clone(),
+			targets_id: targets_id.clone(),
+			ignore_index: None,
+			reduction: Reduction::Mean,
+			multiplier: 1.0,
+			name: None,
+		}
+	}
+
+	/// Positions whose target equals this value are excluded from the loss and receive no gradient.
+	///
+	/// The value does not need to be a valid class index.
+	/// Default: None
+	pub fn ignore_index<I: Into<Option<usize>>>(mut self, ignore_index: I) -> Self {
+		self.ignore_index = ignore_index.into();
+		self
+	}
+
+	/// Sets how the loss is reduced over positions, either `Mean` or `Sum`.
+	///
+	/// `None` is not supported as this `Op` has no output.
+	/// Default: `Mean`
+	pub fn reduction(mut self, reduction: Reduction) -> Self {
+		self.reduction = reduction;
+		self
+	}
+
+	/// Applies a multiplier to the loss generated.
+	pub fn multiplier(mut self, multiplier: f32) -> Self {
+		self.multiplier = multiplier;
+		self
+	}
+}
+
+impl Op for SoftmaxCrossEntropy {
+	type InstanceType = SoftmaxCrossEntropyInstance;
+
+	fn type_name(&self) -> &'static str {
+		"SoftmaxCrossEntropy"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.logits_id.shape().ndim() > 0, "SoftmaxCrossEntropy logits must have at least one axis");
+		ensure!(self.targets_id.shape().ndim() + 1 == self.logits_id.shape().ndim(), format!("SoftmaxCrossEntropy targets '{}' with shape {:?} must have one fewer axis than logits '{}' with shape {:?}",
+			self.targets_id.name(), self.targets_id.shape(), self.logits_id.name(), self.logits_id.shape()));
+
+		ensure!(self.reduction != Reduction::None, "SoftmaxCrossEntropy does not support Reduction::None, as it has no output");
+
+		let name = standard_op_name(&self, &self.name, graph, &[self.logits_id.clone(), self.targets_id.clone()], &[]);
+
+		Ok(SoftmaxCrossEntropyInstance{
+			name: name,
+			ignore_index: self.ignore_index,
+			multiplier: self.multiplier,
+			reduction: self.reduction,
+			logits_id: self.logits_id.clone(),
+			targets_id: self.targets_id.clone(),
+			pass_id: graph.add_pass(SoftmaxCrossEntropyJointPass::new(
+				self.multiplier,
+				self.ignore_index,
+				self.logits_id.clone(),
+				self.targets_id.clone(),
+				self.reduction)),
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)] 
+pub struct SoftmaxCrossEntropyInstance {
+	name: String,
+	ignore_index: Option<usize>,
+	multiplier: f32,
+	reduction: Reduction,
+	logits_id: NodeID,
+	targets_id: NodeID,
+	pass_id: PassID,
+}
+
+impl SoftmaxCrossEntropyInstance {
+	/// The target value which is excluded from the loss, if any
+	pub fn ignore_index(&self) -> Option<usize> {
+		self.ignore_index
+	}
+}
+
+impl OpInstance for SoftmaxCrossEntropyInstance {
+
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(vec![self.logits_id.clone(), self.targets_id.clone()], vec![])
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.pass_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {
+		vec![]
+	}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {
+		vec![]
+	}
+
+	fn propagate_shape_constraints(&self, _shapes: &mut GraphShapes) -> Result<()>{
+		Ok(())
+	}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("ignore_index", format!("{:?}", self.ignore_index)), ("multiplier", format!("{:?}", self.multiplier)), ("reduction", format!("{:?}", self.reduction))]
+	}
+}
+
+
+#[derive(Clone, Debug)]
+struct SoftmaxCrossEntropyJointPass {
+	multiplier: f32,
+	ignore_index: Option<usize>,
+	logits_id: NodeID,
+	targets_id: NodeID,
+	reduction: Reduction,
+}
+
+impl SoftmaxCrossEntropyJointPass {
+	pub fn new(multiplier: f32, ignore_index: Option<usize>, logits_id: NodeID, targets_id: NodeID, reduction: Reduction) -> Self {
+		SoftmaxCrossEntropyJointPass {
+			multiplier,
+			ignore_index,
+			logits_id,
+			targets_id,
+			reduction,
+		}
+	}
+}
+
+impl Pass for SoftmaxCrossEntropyJointPass {
+	fn type_name(&self) -> &'static str {"SoftmaxCrossEntropyJointPass"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.logits_id.value_id(), self.targets_id.value_id()],
+		vec![self.logits_id.gradient_id()])
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let logits_val = data.get(&self.logits_id.value_id())?;
+		let targets_val = data.get(&self.targets_id.value_id())?;
+
+		ensure!(
+			logits_val.ndim() > 0 && targets_val.shape() == &logits_val.shape()[..logits_val.ndim() - 1],
+			ErrorKind::PassError(self.name(), format!("targets shape: {:?} did not match logits shape: {:?} with the innermost axis removed", targets_val.shape(), logits_val.shape()))
+		);
+
+		let num_classes = logits_val.shape()[logits_val.ndim() - 1];
+		ensure!(num_classes > 0, ErrorKind::PassError(self.name(), format!("logits shape: {:?} has no classes", logits_val.shape())));
+
+		// None for ignored positions
+		let mut targets = Vec::with_capacity(targets_val.len());
+		for &target in targets_val.iter() {
+			let index = target.round() as usize;
+			if target >= -0.5 && Some(index) == self.ignore_index {
+				targets.push(None);
+			} else {
+				ensure!(
+					target >= -0.5 && index < num_classes,
+					ErrorKind::PassError(self.name(), format!("target: {} is not a class index for logits shape: {:?}", target, logits_val.shape()))
+				);
+				targets.push(Some(index));
+			}
+		}
+
+		let count = targets.iter().filter(|target| target.is_some()).count();
+		if count == 0 {
+			return Ok(Box::new(()));
+		}
+		let scale = self.multiplier / self.reduction.divisor(count) as f32;
+		let grad_scale = scale * data.loss_scale();
+
+		let logits_val = logits_val.as_slice().unwrap();
+		let mut error = 0.0;
+
+		if data.is_required(&self.logits_id.gradient_id()) {
+			let mut logits_grad = data.get_mut(&self.logits_id.gradient_id())?;
+			let logits_grad = logits_grad.as_slice_mut().unwrap();
+			assert!(logits_grad.len() == logits_val.len());
+
+			for ((logits, logits_grad), target) in logits_val.chunks(num_classes).zip(logits_grad.chunks_mut(num_classes)).zip(&targets) {
+				if let &Some(target) = target {
+					let max = logits.iter().fold(::std::f32::NEG_INFINITY, |max, &x| max.max(x));
+					let sum = logits.iter().fold(0.0, |acc, &x| acc + (x - max).exp());
+					error += (sum.ln() + max - logits[target]) * scale;
+
+					// grad = softmax - one_hot(target)
+					for (i, (&x, grad)) in logits.iter().zip(logits_grad.iter_mut()).enumerate() {
+						let label = if i == target {1.0} else {0.0};
+						*grad += ((x - max).exp() / sum - label) * grad_scale;
+					}
+				}
+			}
+
+		} else {
+			for (logits, target) in logits_val.chunks(num_classes).zip(&targets) {
+				if let &Some(target) = target {
+					let max = logits.iter().fold(::std::f32::NEG_INFINITY, |max, &x| max.max(x));
+					let sum = logits.iter().fold(0.0, |acc, &x| acc + (x - max).exp());
+					error += (sum.ln() + max - logits[target]) * scale;
+				}
+			}
+		}
+
+		data.loss_add(error);
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_softmax_cross_entropy_backprop(){
+	_softmax_cross_entropy_backprop().unwrap();
+}
+
+fn _softmax_cross_entropy_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use rand::thread_rng;
+	use rand::distributions::{Distribution, Range};
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5, 16], "logits", tag![])?;
+	let node2 = g.new_node(shape![7, 5], "targets", tag![])?;
+
+	let _o1 = g.new_op(SoftmaxCrossEntropy::new(&node1, &node2).ignore_index(16), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+
+	// class indices, including the ignored value
+	let sample: Box<::std::ops::FnMut() -> f64 + 'static> = Box::new(|| {
+		let rng = &mut thread_rng();
+		let range = Range::new(0, 17);
+		range.sample(rng) as f64
+	});
+	let mut override_dist = indexmap![];
+	override_dist.insert(node2.clone(), sample);
+
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut override_dist)?;
+
+	Ok(())
+}
+
+#[test]
+fn test_softmax_cross_entropy_ignore_index(){
+	_softmax_cross_entropy_ignore_index().unwrap();
+}
+
+fn _softmax_cross_entropy_ignore_index() -> Result<()>{
+	use graph::GraphDef;
+	use ndarray::{arr2, arr3, ArrayD, Axis};
+
+	let mut g = GraphDef::new();
+
+	let logits = g.new_node(shape![2, 3, 4], "logits", tag![])?;
+	let targets = g.new_node(shape![2, 3], "targets", tag![])?;
+
+	let _o1 = g.new_op(SoftmaxCrossEntropy::new(&logits, &targets).ignore_index(0), tag![])?;
+
+	// class 0 is the padding token, so only 4 of the 6 positions count
+	let logits_val = arr3(&[
+		[[0.0, 1.0, 2.0, 3.0], [1.0, -1.0, 0.5, 0.0], [5.0, 0.0, 0.0, 0.0]],
+		[[0.0, 0.0, 0.0, 0.0], [9.0, 2.0, -3.0, 1.0], [-2.0, 0.0, 3.0, 1.0]],
+	]).into_dyn();
+	let targets_val = arr2(&[[3.0, 1.0, 0.0], [2.0, 0.0, 1.0]]).into_dyn();
+
+	let mut subgraph = g.subgraph(&[logits.value_id(), targets.value_id()], &[logits.gradient_id()])?;
+	let storage = subgraph.execute(vec![logits_val.clone(), targets_val.clone()])?;
+	let logits_grad = storage.get(&logits.gradient_id())?.to_owned();
+
+	let cross_entropy = |logits: &[f32], target: usize| {
+		let sum = logits.iter().fold(0.0f32, |acc, &x| acc + x.exp());
+		sum.ln() - logits[target]
+	};
+	let mut expected = 0.0;
+	for (logits, &target) in logits_val.lanes(Axis(2)).into_iter().zip(targets_val.iter()) {
+		if target != 0.0 {
+			expected += cross_entropy(&logits.to_vec(), target as usize);
+		}
+	}
+	expected /= 4.0;
+	assert!((storage.loss() - expected).abs() < 1e-5, "{} {}", storage.loss(), expected);
+
+	// ignored positions receive no gradient, and the others sum to zero
+	for (grad, &target) in logits_grad.lanes(Axis(2)).into_iter().zip(targets_val.iter()) {
+		if target == 0.0 {
+			assert!(grad.iter().all(|&x| x == 0.0), "{}", grad);
+		} else {
+			assert!(grad.iter().any(|&x| x != 0.0), "{}", grad);
+			assert!(grad.iter().fold(0.0, |acc, &x| acc + x).abs() < 1e-6, "{}", grad);
+		}
+	}
+
+	// a batch of only padding produces no loss
+	let storage = subgraph.execute(vec![logits_val, ArrayD::zeros(&[2, 3][..])])?;
+	assert_eq!(storage.loss(), 0.0);
+
+	Ok(())
+}
@@ -0,0 +1,282 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use ndarray::{ArrayD, Axis};
+use std::any::Any;
+
+/// An `Op` which implements the Triplet Margin Loss
+///
+/// The loss is `max(0, d(a, p) - d(a, n) + margin)` averaged over the outermost axis, where `d` is the squared L2 distance,
+/// and each outer index is a separate (anchor, positive, negative) triplet with the distance calculated over all remaining axes.
+///
+/// All three inputs receive gradients, but only from triplets which violate the margin.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct TripletLoss {
+	anchor_id: NodeID,
+	positive_id: NodeID,
+	negative_id: NodeID,
+	margin: f32,
+	multiplier: f32,
+	name: Option<String>,
+}
+
+impl TripletLoss {
+	pub fn new(anchor: &NodeID, positive: &NodeID, negative: &NodeID) -> Self {
+		TripletLoss {
+			anchor_id: anchor.clone(),
+			positive_id: positive.clone(),
+			negative_id: negative.clone(),
+			margin: 1.0,
+			multiplier: 1.0,
+			name: None,
+		}
+	}
+
+	/// The required gap between the squared distance to the negative and the squared distance to the positive.
+	///
+	/// Default: 1.0
+	pub fn margin(mut self, margin: f32) -> Self {
+		self.margin = margin;
+		self
+	}
+
+	/// Applies a multiplier to the loss generated.
+	pub fn multiplier(mut self, multiplier: f32) -> Self {
+		self.multiplier = multiplier;
+		self
+	}
+}
+
+impl Op for TripletLoss {
+	type InstanceType = TripletLossInstance;
+
+	fn type_name(&self) -> &'static str {
+		"TripletLoss"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.margin >= 0.0, format!("TripletLoss margin must not be negative, found: {}", self.margin));
+
+		let name = standard_op_name(&self, &self.name, graph, &[self.anchor_id.clone(), self.positive_id.clone(), self.negative_id.clone()], &[]);
+
+		Ok(TripletLossInstance{
+			name: name,
+			margin: self.margin,
+			multiplier: self.multiplier,
+			anchor_id: self.anchor_id.clone(),
+			positive_id: self.positive_id.clone(),
+			negative_id: self.negative_id.clone(),
+			pass_id: graph.add_pass(TripletLossJointPass::new(
+				self.margin,
+				self.multiplier,
+				self.anchor_id.clone(),
+				self.positive_id.clone(),
+				self.negative_id.clone())),
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)]
+pub struct TripletLossInstance {
+	name: String,
+	margin: f32,
+	multiplier: f32,
+	anchor_id: NodeID,
+	positive_id: NodeID,
+	negative_id: NodeID,
+	pass_id: PassID,
+}
+
+impl TripletLossInstance {
+	pub fn margin(&self) -> f32 {
+		self.margin
+	}
+}
+
+impl OpInstance for TripletLossInstance {
+
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(vec![self.anchor_id.clone(), self.positive_id.clone(), self.negative_id.clone()], vec![])
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.pass_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {
+		vec![]
+	}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {
+		vec![]
+	}
+
+	fn propagate_shape_constraints(&self, _shapes: &mut GraphShapes) -> Result<()>{
+		Ok(())
+	}
+}
+
+
+#[derive(Clone, Debug)]
+struct TripletLossJointPass {
+	margin: f32,
+	multiplier: f32,
+	anchor_id: NodeID,
+	positive_id: NodeID,
+	negative_id: NodeID,
+}
+
+impl TripletLossJointPass {
+	pub fn new(margin: f32, multiplier: f32, anchor_id: NodeID, positive_id: NodeID, negative_id: NodeID) -> Self {
+		TripletLossJointPass {
+			margin,
+			multiplier,
+			anchor_id,
+			positive_id,
+			negative_id,
+		}
+	}
+}
+
+impl Pass for TripletLossJointPass {
+	fn type_name(&self) -> &'static str {"TripletLossJointPass"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.anchor_id.value_id(), self.positive_id.value_id(), self.negative_id.value_id()],
+		vec![self.anchor_id.gradient_id(), self.positive_id.gradient_id(), self.negative_id.gradient_id()])
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let anchor = data.get(&self.anchor_id.value_id())?;
+		let positive = data.get(&self.positive_id.value_id())?;
+		let negative = data.get(&self.negative_id.value_id())?;
+
+		ensure!(
+			anchor.shape() == positive.shape() && anchor.shape() == negative.shape(),
+			ErrorKind::PassError(self.name(), format!("anchor shape: {:?} did not match positive shape: {:?} and negative shape: {:?}", anchor.shape(), positive.shape(), negative.shape()))
+		);
+		ensure!(
+			anchor.ndim() > 0,
+			ErrorKind::PassError(self.name(), format!("anchor must have an outer axis, but had shape: {:?}", anchor.shape()))
+		);
+
+		let n = anchor.shape()[0];
+		let multiplier = self.multiplier/n as f32;
+		let grad_multiplier = multiplier * data.loss_scale();
+
+		let mut anchor_grad = ArrayD::zeros(anchor.shape());
+		let mut positive_grad = ArrayD::zeros(anchor.shape());
+		let mut negative_grad = ArrayD::zeros(anchor.shape());
+
+		let mut error = 0.0;
+
+		let iter = anchor.outer_iter().zip(positive.outer_iter()).zip(negative.outer_iter())
+			.zip(anchor_grad.axis_iter_mut(Axis(0))).zip(positive_grad.axis_iter_mut(Axis(0))).zip(negative_grad.axis_iter_mut(Axis(0)));
+		for (((((a, p), neg), mut ag), mut pg), mut ng) in iter {
+			let (pos_dist, neg_dist) = a.iter().zip(p.iter()).zip(neg.iter())
+				.fold((0.0, 0.0), |(pos_dist, neg_dist), ((&a, &p), &n)| (pos_dist + (a - p)*(a - p), neg_dist + (a - n)*(a - n)));
+
+			let violation = pos_dist - neg_dist + self.margin;
+			if violation <= 0.0 {
+				continue;
+			}
+
+			error += violation*multiplier;
+
+			// d/da = 2(n - p), d/dp = -2(a - p), d/dn = 2(a - n)
+			let iter = ag.iter_mut().zip(pg.iter_mut()).zip(ng.iter_mut()).zip(a.iter()).zip(p.iter()).zip(neg.iter());
+			for (((((ag, pg), ng), &a), &p), &n) in iter {
+				*ag += 2.0*(n - p)*grad_multiplier;
+				*pg += -2.0*(a - p)*grad_multiplier;
+				*ng += 2.0*(a - n)*grad_multiplier;
+			}
+		}
+
+		for (id, grad) in [&self.anchor_id, &self.positive_id, &self.negative_id].iter().zip(&[anchor_grad, positive_grad, negative_grad]) {
+			if data.is_required(&id.gradient_id()) {
+				let mut input_grad = data.get_mut(&id.gradient_id())?;
+				input_grad += grad;
+			}
+		}
+
+		data.loss_add(error);
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_triplet_loss_backprop(){
+	_triplet_loss_backprop().unwrap();
+}
+
+fn _triplet_loss_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 8], "anchor", tag![])?;
+	let node2 = g.new_node(shape![7, 8], "positive", tag![])?;
+	let node3 = g.new_node(shape![7, 8], "negative", tag![])?;
+
+	// with unit variance inputs the squared distances are around 16, so a large margin keeps every triplet in the active region
+	let _o1 = g.new_op(TripletLoss::new(&node1, &node2, &node3).margin(200.0), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_triplet_loss_satisfied(){
+	_triplet_loss_satisfied().unwrap();
+}
+
+fn _triplet_loss_satisfied() -> Result<()>{
+	use graph::GraphDef;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![3, 4], "anchor", tag![])?;
+	let node2 = g.new_node(shape![3, 4], "positive", tag![])?;
+	let node3 = g.new_node(shape![3, 4], "negative", tag![])?;
+
+	let _o1 = g.new_op(TripletLoss::new(&node1, &node2, &node3).margin(2.0), tag![])?;
+
+	let anchor = ArrayD::from_shape_fn(&[3, 4][..], |idx| (idx[0] * 4 + idx[1]) as f32 * 0.1);
+	let positive = anchor.mapv(|x| x + 0.1);
+	let far_negative = anchor.mapv(|x| x + 10.0);
+
+	let mut subgraph = g.subgraph(&[node1.value_id(), node2.value_id(), node3.value_id()], &[node1.gradient_id(), node2.gradient_id(), node3.gradient_id()])?;
+
+	// negatives far beyond the margin produce no loss and no gradient
+	let storage = subgraph.execute(vec![anchor.clone(), positive.clone(), far_negative])?;
+	assert_eq!(storage.loss(), 0.0);
+	for id in &[&node1, &node2, &node3] {
+		assert!(storage.get(&id.gradient_id())?.iter().all(|&g| g == 0.0));
+	}
+
+	// a negative at the same distance as the positive violates the margin by exactly the margin
+	let near_negative = anchor.mapv(|x| x - 0.1);
+	let storage = subgraph.execute(vec![anchor.clone(), positive, near_negative])?;
+	assert!((storage.loss() - 2.0).abs() < 1e-4, "{}", storage.loss());
+
+	Ok(())
+}
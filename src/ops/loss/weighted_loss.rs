@@ -0,0 +1,201 @@
+use graph::{GraphDef, GraphShapes, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use std::any::Any;
+
+/// This `Op` combines several loss nodes into the total loss, scaling each by a weight.
+///
+/// Each loss node is typically the `output()` of another loss `Op`, such as `Mse`.
+/// All elements of each loss node are summed, so that a loss node contributes `weight` times the loss the generating `Op` would produce without an output.
+/// The gradient of each loss node is set to its weight.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct WeightedLoss {
+	losses: Vec<(NodeID, f32)>,
+	name: Option<String>,
+}
+
+impl WeightedLoss {
+	pub fn new(losses: &[(NodeID, f32)]) -> Self {
+		WeightedLoss {
+			losses: losses.to_vec(),
+			name: None,
+		}
+	}
+}
+
+impl Op for WeightedLoss {
+	type InstanceType = WeightedLossInstance;
+
+	fn type_name(&self) -> &'static str {
+		"WeightedLoss"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.losses.len() > 0, "WeightedLoss requires at least one loss node");
+		let input_ids: Vec<NodeID> = self.losses.iter().map(|&(ref id, _)| id.clone()).collect();
+		let name = standard_op_name(&self, &self.name, graph, &input_ids, &[]);
+
+		Ok(WeightedLossInstance{
+			name: name,
+			losses: self.losses.clone(),
+			pass_id: graph.add_pass(WeightedLossBackward::new(self.losses)),
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)] 
+pub struct WeightedLossInstance{
+	name: String,
+	losses: Vec<(NodeID, f32)>,
+	pass_id: PassID,
+}
+
+impl WeightedLossInstance {
+	/// Returns the loss nodes and their weights
+	pub fn losses(&self) -> &[(NodeID, f32)] {
+		&self.losses
+	}
+}
+
+impl OpInstance for WeightedLossInstance {
+
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){(self.losses.iter().map(|&(ref id, _)| id.clone()).collect(), vec![])}
+
+	fn inner_passes(&self) -> Vec<PassID> {vec![self.pass_id.clone()]}
+
+	fn inner_ops(&self) -> Vec<OpID> {vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {vec![]}
+
+	fn propagate_shape_constraints(&self, _shapes: &mut GraphShapes) -> Result<()>{Ok(())}
+}
+
+
+#[derive(Clone, Debug)]
+struct WeightedLossBackward {
+	losses: Vec<(NodeID, f32)>,
+}
+
+impl WeightedLossBackward {
+	pub fn new(losses: Vec<(NodeID, f32)>) -> Self {
+		WeightedLossBackward {
+			losses,
+		}
+	}
+}
+
+impl Pass for WeightedLossBackward {
+	fn type_name(&self) -> &'static str {"WeightedLossBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(self.losses.iter().map(|&(ref id, _)| id.value_id()).collect(),
+		self.losses.iter().map(|&(ref id, _)| id.gradient_id()).collect())
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let mut error = 0.0;
+		let loss_scale = data.loss_scale();
+
+		for &(ref loss_id, weight) in &self.losses {
+			error += data.get(&loss_id.value_id())?.iter().fold(0.0, |acc, &x| acc + x) * weight;
+
+			if data.is_required(&loss_id.gradient_id()) {
+				let mut loss_grad = data.get_mut(&loss_id.gradient_id())?;
+				loss_grad.map_inplace(|x| *x += weight * loss_scale);
+			}
+		}
+
+		data.loss_add(error);
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_weighted_loss_backprop(){
+	_weighted_loss_backprop().unwrap();
+}
+
+fn _weighted_loss_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5, 16], "input1", tag![])?;
+	let node2 = g.new_node(shape![7, 5, 16], "input2", tag![])?;
+
+	let _o1 = g.new_op(WeightedLoss::new(&[(node1.clone(), 0.25), (node2.clone(), -3.0)]), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.001;
+	let step_size = 1E-3;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_weighted_loss_mse(){
+	_weighted_loss_mse().unwrap();
+}
+
+fn _weighted_loss_mse() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::mse::Mse;
+	use ops::numeric_check::generate_input_data;
+
+	// two tasks sharing an input, combined through output nodes and WeightedLoss
+	let mut g1 = GraphDef::new();
+	let input1 = g1.new_node(shape![4, 3], "input", tag![])?;
+	let target1a = g1.new_node(shape![4, 3], "target_a", tag![])?;
+	let target1b = g1.new_node(shape![4, 3], "target_b", tag![])?;
+	let loss_a = g1.new_node(shape![4, 3], "loss_a", tag![])?;
+	let loss_b = g1.new_node(shape![4, 3], "loss_b", tag![])?;
+	let _o1 = g1.new_op(Mse::new(&input1, &target1a).output(&loss_a), tag![])?;
+	let _o2 = g1.new_op(Mse::new(&input1, &target1b).output(&loss_b), tag![])?;
+	let _o3 = g1.new_op(WeightedLoss::new(&[(loss_a, 0.25), (loss_b, 0.75)]), tag![])?;
+
+	// the same tasks applied individually
+	let mut g2 = GraphDef::new();
+	let input2 = g2.new_node(shape![4, 3], "input", tag![])?;
+	let target2a = g2.new_node(shape![4, 3], "target_a", tag![])?;
+	let target2b = g2.new_node(shape![4, 3], "target_b", tag![])?;
+	let _o4 = g2.new_op(Mse::new(&input2, &target2a), tag![])?;
+	let _o5 = g2.new_op(Mse::new(&input2, &target2b), tag![])?;
+
+	let input_data = generate_input_data(&[input1.clone(), target1a.clone(), target1b.clone()], 1.0, &mut indexmap![])?;
+
+	let mut sg1 = g1.subgraph(&[input1.value_id(), target1a.value_id(), target1b.value_id()], &[input1.gradient_id()])?;
+	let storage1 = sg1.execute(input_data.clone())?;
+
+	// the individual gradients, found by masking one target with the input so its loss and gradient are zero
+	let mut sg2 = g2.subgraph(&[input2.value_id(), target2a.value_id(), target2b.value_id()], &[input2.gradient_id()])?;
+	let storage_a = sg2.execute(vec![input_data[0].clone(), input_data[1].clone(), input_data[0].clone()])?;
+	let storage_b = sg2.execute(vec![input_data[0].clone(), input_data[0].clone(), input_data[2].clone()])?;
+
+	let expected_loss = 0.25 * storage_a.loss() + 0.75 * storage_b.loss();
+	assert!((storage1.loss() - expected_loss).abs() < 1e-4 * expected_loss.abs().max(1.0));
+
+	let grad = storage1.get(&input1.gradient_id())?;
+	let grad_a = storage_a.get(&input2.gradient_id())?;
+	let grad_b = storage_b.get(&input2.gradient_id())?;
+	for ((&g, &a), &b) in grad.iter().zip(grad_a.iter()).zip(grad_b.iter()) {
+		assert!((g - (0.25 * a + 0.75 * b)).abs() < 1e-5, "{} {} {}", g, a, b);
+	}
+
+	Ok(())
+}
@@ -2,11 +2,16 @@ use graph::{GraphDef, GraphShapes, ErrorKind, Result};
 use id::{NodeID, DataID, OpID, PassID};
 use storage::Storage;
 use ops::{standard_op_name, Op, OpInstance, Pass};
+use ops::math::broadcast::{broadcast_to, accumulate_broadcast_grad};
 use shape::{NodeShape, NodeDim};
 use ndarray::{ArrayViewMutD, ArrayViewD, Dimension};
 use smallvec::SmallVec;
 use std::any::Any;
 
+/// Add Op
+///
+/// The value of the input is broadcast to the shape of the output following NumPy rules, then added to the output.
+/// The gradient is summed back over the broadcast axes using `accumulate_broadcast_grad()`.
 #[must_use]
 #[derive(Clone, Debug)]
 pub struct Add {
@@ -144,6 +149,10 @@ impl OpInstance for AddInstance {
 		}).into();
 		shapes.merge_with(&self.output_id, &output_shape)
 	}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("extra_axes", format!("{:?}", &self.extra_axes[..]))]
+	}
 }
 
 #[derive(Clone, Debug)]
@@ -164,7 +173,7 @@ impl AddForward{
 }
 
 impl Pass for AddForward {
-	fn type_name(&self) -> &'static str {"AddBackward"}
+	fn type_name(&self) -> &'static str {"AddForward"}
 
 	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
 		(
@@ -180,10 +189,9 @@ impl Pass for AddForward {
 		let effective_shape = effective_shape(input.shape(), &self.extra_axes, output.ndim())?;
 		let input_effective = input.into_shape(effective_shape.as_slice()).expect("must be a bug in effective_shape()");
 
-		let input_broadcast = if let Some(view) = input_effective.broadcast(output.shape()) {
-			view
-		} else {
-			bail!(ErrorKind::PassError(self.name(), format!("Could not broadcast input shape: {:?} to output shape: {:?}", effective_shape.as_slice(), output.shape())));
+		let input_broadcast = match broadcast_to(&input_effective, output.shape()) {
+			Ok(view) => view,
+			Err(err) => bail!(ErrorKind::PassError(self.name(), err.to_string())),
 		};
 
 		output += &input_broadcast;
@@ -222,18 +230,12 @@ impl Pass for AddBackward {
 	fn run (&self, data: &Storage) -> Result<Box<Any>>{
 		let input_grad = data.get_mut(&self.input_id.gradient_id())?;
 		let output_grad = data.get(&self.output_id.gradient_id())?;
-		
 
 		let effective_shape = effective_shape(input_grad.shape(), &self.extra_axes, output_grad.ndim())?;
 		let mut input_grad_effective = input_grad.into_shape(effective_shape.as_slice()).expect("must be a bug in effective_shape()");
 
-		ensure!(
-			input_grad_effective.broadcast(output_grad.shape()).is_some(), 
-			ErrorKind::PassError(self.name(), format!("Could not broadcast input shape: {:?} to output shape: {:?}", input_grad_effective.shape(), output_grad.shape()))
-		);
-
-		for chunk in output_grad.exact_chunks(input_grad_effective.shape()){
-			input_grad_effective += &chunk;
+		if let Err(err) = accumulate_broadcast_grad(&mut input_grad_effective, &output_grad) {
+			bail!(ErrorKind::PassError(self.name(), err.to_string()));
 		}
 
 		Ok(Box::new(()))
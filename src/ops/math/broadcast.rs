@@ -0,0 +1,396 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use shape::NodeShape;
+use ndarray::{ArrayD, ArrayViewD, ArrayViewMutD, Dimension, Zip};
+use ndarray_parallel::prelude::*;
+use smallvec::SmallVec;
+use std::any::Any;
+use std::fmt::Debug;
+use std::iter;
+
+/// Returns the shape which two shapes broadcast to, following NumPy rules.
+///
+/// The shapes are aligned at their innermost axes, with any missing outer axes treated as size 1.
+/// Each pair of aligned axes must either be equal, or one of them must be 1, in which case it is broadcast to the size of the other.
+/// e.g. `[3, 1]` and `[4]` broadcast to `[3, 4]`, while `[3]` and `[4]` are incompatible.
+pub fn broadcast_shape(shape1: &[usize], shape2: &[usize]) -> Result<SmallVec<[usize; 6]>> {
+	let ndim = shape1.len().max(shape2.len());
+	let padded = |shape: &[usize]| -> SmallVec<[usize; 6]> {
+		iter::repeat(1).take(ndim - shape.len()).chain(shape.iter().cloned()).collect()
+	};
+
+	let mut shape = SmallVec::new();
+	for (&dim1, &dim2) in padded(shape1).iter().zip(padded(shape2).iter()) {
+		if dim1 == dim2 || dim2 == 1 {
+			shape.push(dim1);
+		} else if dim1 == 1 {
+			shape.push(dim2);
+		} else {
+			bail!("Shapes {:?} and {:?} can not be broadcast together", shape1, shape2);
+		}
+	}
+	Ok(shape)
+}
+
+/// Returns a view of `input` broadcast to `shape`, or an error if `shape` is not a broadcast of the input shape.
+pub fn broadcast_to<'a>(input: &'a ArrayViewD<f32>, shape: &[usize]) -> Result<ArrayViewD<'a, f32>> {
+	match input.broadcast(shape) {
+		Some(view) => Ok(view),
+		None => bail!("Could not broadcast shape {:?} to shape {:?}", input.shape(), shape),
+	}
+}
+
+/// Adds `grad` into `input_grad`, summing over any axes along which the input was broadcast to produce the shape of `grad`.
+///
+/// This is the gradient of a broadcast, and is used by ops which broadcast their inputs.
+/// `input_grad` must be in standard layout, as is the case for arrays borrowed from `Storage`.
+pub fn accumulate_broadcast_grad(input_grad: &mut ArrayViewMutD<f32>, grad: &ArrayViewD<f32>) -> Result<()> {
+	ensure!(input_grad.broadcast(grad.shape()).is_some(), "Could not broadcast input gradient shape {:?} to gradient shape {:?}", input_grad.shape(), grad.shape());
+
+	let padded_shape: SmallVec<[usize; 6]> = iter::repeat(1).take(grad.ndim() - input_grad.ndim()).chain(input_grad.shape().iter().cloned()).collect();
+	let mut input_grad = input_grad.view_mut().into_shape(padded_shape.as_slice()).expect("Input gradient must be in standard layout");
+
+	for chunk in grad.exact_chunks(padded_shape.as_slice()) {
+		input_grad += &chunk;
+	}
+	Ok(())
+}
+
+
+/// A differentiable function of two values, applied elementwise by `BinaryElementwise`.
+pub trait BinaryFunction: Clone + Debug + Send + Sync + 'static {
+	/// The type name of ops built using this function, e.g. "Mul"
+	fn type_name(&self) -> &'static str;
+
+	/// Returns f(x, y)
+	fn value(&self, x: f32, y: f32) -> f32;
+
+	/// Returns the partial derivatives (∂f/∂x, ∂f/∂y)
+	fn gradient(&self, x: f32, y: f32) -> (f32, f32);
+}
+
+/// BinaryElementwise Op
+///
+/// Both inputs are broadcast to a common shape following NumPy rules (see `broadcast_shape()`), the function is applied elementwise, and the result is added to the output.
+/// The output must have the broadcast shape.
+/// The gradient of each input is summed over the axes along which it was broadcast.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct BinaryElementwise<F: BinaryFunction> {
+	input1: NodeID,
+	input2: NodeID,
+	output: NodeID,
+	func: F,
+	name: Option<String>,
+}
+
+impl<F: BinaryFunction> BinaryElementwise<F> {
+	pub fn new(input1: &NodeID, input2: &NodeID, output: &NodeID, func: F) -> Self {
+		BinaryElementwise {
+			input1: input1.clone(),
+			input2: input2.clone(),
+			output: output.clone(),
+			func: func,
+			name: None,
+		}
+	}
+}
+
+impl<F: BinaryFunction> Op for BinaryElementwise<F> {
+	type InstanceType = BinaryElementwiseInstance;
+
+	fn type_name(&self) -> &'static str {
+		self.func.type_name()
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let name = standard_op_name(&self, &self.name, graph, &[self.input1.clone(), self.input2.clone()], &[self.output.clone()]);
+
+		Ok(BinaryElementwiseInstance{
+			name: name,
+			input1_id: self.input1.clone(),
+			input2_id: self.input2.clone(),
+			output_id: self.output.clone(),
+			forward_id: graph.add_pass(BinaryElementwiseForward::new(
+				self.input1.clone(),
+				self.input2.clone(),
+				self.output.clone(),
+				self.func.clone())),
+			backward_id: graph.add_pass(BinaryElementwiseBackward::new(
+				self.input1.clone(),
+				self.input2.clone(),
+				self.output.clone(),
+				self.func.clone())),
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)]
+pub struct BinaryElementwiseInstance{
+	name: String,
+	input1_id: NodeID,
+	input2_id: NodeID,
+	output_id: NodeID,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl OpInstance for BinaryElementwiseInstance {
+
+	fn name(&self) -> &str{&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){(vec![self.input1_id.clone(), self.input2_id.clone()], vec![self.output_id.clone()])}
+
+	fn inner_passes(&self) -> Vec<PassID>{vec![self.forward_id.clone(), self.backward_id.clone()]}
+
+	fn inner_ops(&self) -> Vec<OpID>{vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID>{vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+		let input1_shape = shapes.get_shape(&self.input1_id).to_data_shape()?;
+		let input2_shape = shapes.get_shape(&self.input2_id).to_data_shape()?;
+		let output_shape: NodeShape = broadcast_shape(input1_shape.slice(), input2_shape.slice())?.into();
+		shapes.merge_with(&self.output_id, &output_shape)
+	}
+
+}
+
+
+#[derive(Clone, Debug)]
+struct BinaryElementwiseForward<F: BinaryFunction> {
+	input1_id: NodeID,
+	input2_id: NodeID,
+	output_id: NodeID,
+	func: F,
+}
+
+impl<F: BinaryFunction> BinaryElementwiseForward<F> {
+	pub fn new(input1_id: NodeID, input2_id: NodeID, output_id: NodeID, func: F) -> Self {
+		BinaryElementwiseForward {
+			input1_id,
+			input2_id,
+			output_id,
+			func,
+		}
+	}
+}
+
+impl<F: BinaryFunction> Pass for BinaryElementwiseForward<F> {
+	fn type_name(&self) -> &'static str {"BinaryElementwiseForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.input1_id.value_id(), self.input2_id.value_id()],
+			vec![self.output_id.value_id()]
+		)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let input1: ArrayViewD<f32> = data.get(&self.input1_id.value_id())?;
+		let input2: ArrayViewD<f32> = data.get(&self.input2_id.value_id())?;
+		let mut output: ArrayViewMutD<f32> = data.get_mut(&self.output_id.value_id())?;
+
+		check_shapes(self.name(), input1.shape(), input2.shape(), output.shape())?;
+		let input1 = broadcast_to(&input1, output.shape())?;
+		let input2 = broadcast_to(&input2, output.shape())?;
+
+		let func = &self.func;
+		Zip::from(&mut output)
+			.and(&input1)
+			.and(&input2)
+			.par_apply(|output, &x, &y| {
+				*output += func.value(x, y);
+			});
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Clone, Debug)]
+struct BinaryElementwiseBackward<F: BinaryFunction> {
+	input1_id: NodeID,
+	input2_id: NodeID,
+	output_id: NodeID,
+	func: F,
+}
+
+impl<F: BinaryFunction> BinaryElementwiseBackward<F> {
+	pub fn new(input1_id: NodeID, input2_id: NodeID, output_id: NodeID, func: F) -> Self {
+		BinaryElementwiseBackward {
+			input1_id,
+			input2_id,
+			output_id,
+			func,
+		}
+	}
+}
+
+impl<F: BinaryFunction> Pass for BinaryElementwiseBackward<F> {
+	fn type_name(&self) -> &'static str {"BinaryElementwiseBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.input1_id.value_id(), self.input2_id.value_id(), self.output_id.gradient_id()],
+			vec![self.input1_id.gradient_id(), self.input2_id.gradient_id()]
+		)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let input1: ArrayViewD<f32> = data.get(&self.input1_id.value_id())?;
+		let input2: ArrayViewD<f32> = data.get(&self.input2_id.value_id())?;
+		let output_grad: ArrayViewD<f32> = data.get(&self.output_id.gradient_id())?;
+
+		check_shapes(self.name(), input1.shape(), input2.shape(), output_grad.shape())?;
+		let input1 = broadcast_to(&input1, output_grad.shape())?;
+		let input2 = broadcast_to(&input2, output_grad.shape())?;
+
+		let func = &self.func;
+		if data.is_required(&self.input1_id.gradient_id()) {
+			let mut input1_grad = data.get_mut(&self.input1_id.gradient_id())?;
+			accumulate_partial(&mut input1_grad, &output_grad, &input1, &input2, |x, y| func.gradient(x, y).0)?;
+		}
+
+		if data.is_required(&self.input2_id.gradient_id()) {
+			let mut input2_grad = data.get_mut(&self.input2_id.gradient_id())?;
+			accumulate_partial(&mut input2_grad, &output_grad, &input1, &input2, |x, y| func.gradient(x, y).1)?;
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+fn check_shapes(pass_name: String, input1_shape: &[usize], input2_shape: &[usize], output_shape: &[usize]) -> Result<()> {
+	let shape = match broadcast_shape(input1_shape, input2_shape) {
+		Ok(shape) => shape,
+		Err(err) => bail!(ErrorKind::PassError(pass_name, err.to_string())),
+	};
+	ensure!(shape.as_slice() == output_shape,
+		ErrorKind::PassError(pass_name, format!("input1 shape: {:?} and input2 shape: {:?} broadcast to shape: {:?} which did not match output shape: {:?}", input1_shape, input2_shape, shape.as_slice(), output_shape)));
+	Ok(())
+}
+
+/// Adds `partial(x, y) * output_grad` to an input gradient, summing over any broadcast axes of the input.
+fn accumulate_partial<P: Fn(f32, f32) -> f32 + Sync>(input_grad: &mut ArrayViewMutD<f32>, output_grad: &ArrayViewD<f32>, input1: &ArrayViewD<f32>, input2: &ArrayViewD<f32>, partial: P) -> Result<()> {
+	if input_grad.shape() == output_grad.shape() {
+		Zip::from(input_grad)
+			.and(output_grad)
+			.and(input1)
+			.and(input2)
+			.par_apply(|input_grad, &output_grad, &x, &y| {
+				*input_grad += partial(x, y) * output_grad;
+			});
+		Ok(())
+	} else {
+		let mut grad = ArrayD::zeros(output_grad.shape());
+		Zip::from(&mut grad)
+			.and(output_grad)
+			.and(input1)
+			.and(input2)
+			.par_apply(|grad, &output_grad, &x, &y| {
+				*grad = partial(x, y) * output_grad;
+			});
+		accumulate_broadcast_grad(input_grad, &grad.view())
+	}
+}
+
+
+#[test]
+fn test_broadcast_shape(){
+	// scalar to tensor
+	assert_eq!(broadcast_shape(&[], &[3, 4]).unwrap().as_slice(), &[3, 4]);
+	assert_eq!(broadcast_shape(&[1], &[3, 4]).unwrap().as_slice(), &[3, 4]);
+	// trailing dimensions
+	assert_eq!(broadcast_shape(&[3, 4], &[4]).unwrap().as_slice(), &[3, 4]);
+	assert_eq!(broadcast_shape(&[2, 1, 4], &[3, 1]).unwrap().as_slice(), &[2, 3, 4]);
+	// incompatible
+	assert!(broadcast_shape(&[3], &[4]).is_err());
+	assert!(broadcast_shape(&[2, 3, 4], &[2, 4]).is_err());
+}
+
+#[test]
+fn test_broadcast_scalar_backprop(){
+	_broadcast_backprop(shape![1], shape![3, 4], shape![3, 4]).unwrap();
+}
+
+#[test]
+fn test_broadcast_trailing_backprop(){
+	_broadcast_backprop(shape![5, 3, 4], shape![4], shape![5, 3, 4]).unwrap();
+}
+
+#[test]
+fn test_broadcast_both_backprop(){
+	_broadcast_backprop(shape![5, 1, 4], shape![3, 1], shape![5, 3, 4]).unwrap();
+}
+
+#[cfg(test)]
+#[derive(Clone, Debug)]
+struct TestFunc;
+
+#[cfg(test)]
+impl BinaryFunction for TestFunc {
+	fn type_name(&self) -> &'static str {"TestFunc"}
+
+	fn value(&self, x: f32, y: f32) -> f32 {x * y + x.sin()}
+
+	fn gradient(&self, x: f32, y: f32) -> (f32, f32) {(y + x.cos(), x)}
+}
+
+#[cfg(test)]
+fn _broadcast_backprop(input1_shape: NodeShape, input2_shape: NodeShape, output_shape: NodeShape) -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(input1_shape, "input1", tag![])?;
+	let node2 = g.new_node(input2_shape, "input2", tag![])?;
+	let node3 = g.new_node(output_shape.clone(), "output", tag![])?;
+	let node4 = g.new_node(output_shape, "target", tag![])?;
+
+	let _o1 = g.new_op(BinaryElementwise::new(&node1, &node2, &node3, TestFunc), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node3, &node4), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_broadcast_incompatible(){
+	_broadcast_incompatible().unwrap();
+}
+
+#[cfg(test)]
+fn _broadcast_incompatible() -> Result<()>{
+	use graph::GraphDef;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![3], "input1", tag![])?;
+	let node2 = g.new_node(shape![4], "input2", tag![])?;
+	let node3 = g.new_node(shape![Unknown], "output", tag![])?;
+
+	let _o1 = g.new_op(BinaryElementwise::new(&node1, &node2, &node3, TestFunc), tag![])?;
+
+	let mut subgraph = g.subgraph(&[node1.value_id(), node2.value_id()], &[node3.value_id()])?;
+	let result = subgraph.execute(vec![ArrayD::zeros(&[3][..]), ArrayD::zeros(&[4][..])]);
+	assert!(result.is_err());
+
+	Ok(())
+}
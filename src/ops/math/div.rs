@@ -123,6 +123,10 @@ impl OpInstance for DivInstance {
 			shapes.merge_with(&self.output_id, &output_shape)
 		}
 	}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("broadcast_numerator", format!("{:?}", self.broadcast_numerator))]
+	}
 }
 
 
@@ -18,6 +18,7 @@ impl ActivationFunc for ExpFunc {
 	fn backprop_requires_input_value() -> bool {true}
 }
 
+/// Exp Op, the exponential function of the input.
 #[must_use]
 #[derive(Clone, Debug)]
 pub struct Exp {
@@ -0,0 +1,302 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use ndarray::Axis;
+use std::any::Any;
+
+/// L2Normalize Op
+///
+/// Divides the input by its L2 norm along an axis, projecting each vector onto the unit sphere, and adds the result to the output.
+/// The norm is calculated as `sqrt(sum(x^2) + epsilon)`, so that zero vectors produce a zero output rather than NaN.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct L2Normalize {
+	input: NodeID,
+	output: NodeID,
+	axis: isize,
+	epsilon: f32,
+	name: Option<String>,
+}
+
+impl L2Normalize {
+	pub fn new(input: &NodeID, output: &NodeID) -> Self {
+		L2Normalize {
+			input: input.clone(),
+			output: output.clone(),
+			axis: -1,
+			epsilon: 1e-12,
+			name: None,
+		}
+	}
+
+	/// The axis along which the norm is calculated.
+	///
+	/// Can be in the range [-input.ndims(), input.ndims()).
+	/// Default: -1
+	pub fn axis(mut self, axis: isize) -> Self {
+		self.axis = axis;
+		self
+	}
+
+	/// Added to the sum of squares before the square root is taken.
+	///
+	/// Default: 1e-12
+	pub fn epsilon(mut self, epsilon: f32) -> Self {
+		self.epsilon = epsilon;
+		self
+	}
+}
+
+impl Op for L2Normalize {
+	type InstanceType = L2NormalizeInstance;
+
+	fn type_name(&self) -> &'static str {
+		"L2Normalize"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let ndim = self.input.shape().ndim();
+		ensure!(self.axis >= -(ndim as isize) && self.axis < ndim as isize, format!("L2Normalize axis {} is out of range for input with {} axes", self.axis, ndim));
+		let axis = (self.axis + ndim as isize) as usize % ndim;
+
+		let name = standard_op_name(&self, &self.name, graph, &[self.input.clone()], &[self.output.clone()]);
+
+		Ok(L2NormalizeInstance{
+			name: name,
+			input_id: self.input.clone(),
+			output_id: self.output.clone(),
+			axis: axis,
+			forward_id: graph.add_pass(L2NormalizeForward::new(
+				self.input.clone(),
+				self.output.clone(),
+				axis,
+				self.epsilon)),
+			backward_id: graph.add_pass(L2NormalizeBackward::new(
+				self.input.clone(),
+				self.output.clone(),
+				axis,
+				self.epsilon)),
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)]
+pub struct L2NormalizeInstance{
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	axis: usize,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl L2NormalizeInstance {
+	/// The axis along which the norm is calculated, in the range [0, input.ndims())
+	pub fn axis(&self) -> usize {
+		self.axis
+	}
+}
+
+impl OpInstance for L2NormalizeInstance {
+
+	fn name(&self) -> &str{&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){(vec![self.input_id.clone()], vec![self.output_id.clone()])}
+
+	fn inner_passes(&self) -> Vec<PassID>{vec![self.forward_id.clone(), self.backward_id.clone()]}
+
+	fn inner_ops(&self) -> Vec<OpID>{vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID>{vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+		let input_shape = shapes.get_shape(&self.input_id).clone();
+		shapes.merge_with(&self.output_id, &input_shape)
+	}
+
+}
+
+
+#[derive(Clone, Debug)]
+struct L2NormalizeForward {
+	input_id: NodeID,
+	output_id: NodeID,
+	axis: usize,
+	epsilon: f32,
+}
+
+impl L2NormalizeForward {
+	pub fn new(input_id: NodeID, output_id: NodeID, axis: usize, epsilon: f32) -> Self {
+		L2NormalizeForward {
+			input_id,
+			output_id,
+			axis,
+			epsilon,
+		}
+	}
+}
+
+impl Pass for L2NormalizeForward {
+	fn type_name(&self) -> &'static str {"L2NormalizeForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.input_id.value_id()],
+			vec![self.output_id.value_id()]
+		)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let input = data.get(&self.input_id.value_id())?;
+		let mut output = data.get_mut(&self.output_id.value_id())?;
+
+		ensure!(
+			input.shape() == output.shape(),
+			ErrorKind::PassError(self.name(), format!("input shape: {:?} did not match output shape: {:?}", input.shape(), output.shape()))
+		);
+		ensure!(
+			self.axis < input.ndim(),
+			ErrorKind::PassError(self.name(), format!("axis {} is out of range for input shape: {:?}", self.axis, input.shape()))
+		);
+
+		for (input, mut output) in input.lanes(Axis(self.axis)).into_iter().zip(output.lanes_mut(Axis(self.axis))) {
+			let norm = (input.iter().fold(0.0, |acc, &x| acc + x * x) + self.epsilon).sqrt();
+			for (o, &x) in output.iter_mut().zip(input.iter()) {
+				*o += x / norm;
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Clone, Debug)]
+struct L2NormalizeBackward {
+	input_id: NodeID,
+	output_id: NodeID,
+	axis: usize,
+	epsilon: f32,
+}
+
+impl L2NormalizeBackward {
+	pub fn new(input_id: NodeID, output_id: NodeID, axis: usize, epsilon: f32) -> Self {
+		L2NormalizeBackward {
+			input_id,
+			output_id,
+			axis,
+			epsilon,
+		}
+	}
+}
+
+impl Pass for L2NormalizeBackward {
+	fn type_name(&self) -> &'static str {"L2NormalizeBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.input_id.value_id(), self.output_id.gradient_id()],
+			vec![self.input_id.gradient_id()]
+		)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let input = data.get(&self.input_id.value_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+		let mut input_grad = data.get_mut(&self.input_id.gradient_id())?;
+
+		ensure!(
+			input.shape() == output_grad.shape(),
+			ErrorKind::PassError(self.name(), format!("input shape: {:?} did not match output shape: {:?}", input.shape(), output_grad.shape()))
+		);
+		ensure!(
+			self.axis < input.ndim(),
+			ErrorKind::PassError(self.name(), format!("axis {} is out of range for input shape: {:?}", self.axis, input.shape()))
+		);
+
+		let iter = input.lanes(Axis(self.axis)).into_iter()
+			.zip(output_grad.lanes(Axis(self.axis)))
+			.zip(input_grad.lanes_mut(Axis(self.axis)));
+		for ((input, output_grad), mut input_grad) in iter {
+			let (norm_sqr, dot) = input.iter().zip(output_grad.iter())
+				.fold((self.epsilon, 0.0), |(norm_sqr, dot), (&x, &g)| (norm_sqr + x * x, dot + x * g));
+			let norm = norm_sqr.sqrt();
+
+			// grad_in = (grad_out - x (x·grad_out)/norm^2)/norm
+			let a = dot / norm_sqr;
+			for ((ig, &x), &g) in input_grad.iter_mut().zip(input.iter()).zip(output_grad.iter()) {
+				*ig += (g - x * a) / norm;
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_l2_normalize_backprop(){
+	_l2_normalize_backprop().unwrap();
+}
+
+fn _l2_normalize_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![4, 8], "input", tag![])?;
+	let node2 = g.new_node(shape![4, 8], "output", tag![])?;
+	let node3 = g.new_node(shape![4, 8], "target", tag![])?;
+
+	let _o1 = g.new_op(L2Normalize::new(&node1, &node2).axis(-1), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_l2_normalize_value(){
+	_l2_normalize_value().unwrap();
+}
+
+fn _l2_normalize_value() -> Result<()>{
+	use graph::GraphDef;
+	use ndarray::{arr2, Ix2};
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 3], "input", tag![])?;
+	let node2 = g.new_node(shape![2, 3], "output", tag![])?;
+
+	let _o1 = g.new_op(L2Normalize::new(&node1, &node2).axis(0), tag![])?;
+
+	let mut subgraph = g.subgraph(&[node1.value_id()], &[node2.value_id()])?;
+	let storage = subgraph.execute(vec![arr2(&[[3.0, 0.0, -1.0], [4.0, 0.0, 0.0]]).into_dyn()])?;
+	let output = storage.get(&node2.value_id())?.into_dimensionality::<Ix2>().unwrap();
+
+	// each column is normalised, and the zero column stays zero
+	assert!((output[[0, 0]] - 0.6).abs() < 1e-6);
+	assert!((output[[1, 0]] - 0.8).abs() < 1e-6);
+	assert_eq!(output[[0, 1]], 0.0);
+	assert_eq!(output[[1, 1]], 0.0);
+	assert!((output[[0, 2]] + 1.0).abs() < 1e-6);
+	assert_eq!(output[[1, 2]], 0.0);
+
+	Ok(())
+}
@@ -18,6 +18,9 @@ impl ActivationFunc for LogFunc {
 	fn backprop_requires_input_value() -> bool {true}
 }
 
+/// Log Op, the natural logarithm of the input.
+///
+/// As with `f32::ln()`, zero inputs produce -inf and negative inputs produce NaN, with the gradient becoming inf or NaN respectively.
 #[must_use]
 #[derive(Clone, Debug)]
 pub struct Log {
@@ -85,4 +88,53 @@ fn _log_backprop() -> Result<()>{
 	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
 
 	Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_log_positive_backprop(){
+	_log_positive_backprop().unwrap();
+}
+
+fn _log_positive_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+	use rand::thread_rng;
+	use rand::distributions::{Distribution, Range};
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5, 16], "input", tag![])?;
+	let node2 = g.new_node(shape![7, 5, 16], "output", tag![])?;
+	let node3 = g.new_node(shape![7, 5, 16], "target", tag![])?;
+
+
+	let _o1 = g.new_op(Log::new(&node1, &node2), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+
+	let sample: Box<::std::ops::FnMut() -> f64 + 'static> = Box::new(|| {
+		let rng = &mut thread_rng();
+		let range = Range::new(0.5, 10.0);
+		range.sample(rng)
+	});
+	let mut override_dist = indexmap![];
+	override_dist.insert(node1.clone(), sample);
+
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut override_dist)?;
+
+	Ok(())
+}
+
+#[test]
+fn test_log_non_positive(){
+	let func = LogFunc{};
+	assert_eq!(func.value(0.0), ::std::f32::NEG_INFINITY);
+	assert!(func.value(-1.0).is_nan());
+	assert_eq!(func.gradient(0.0, 1.0), ::std::f32::INFINITY);
+}
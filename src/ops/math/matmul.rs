@@ -107,6 +107,7 @@ impl Op for MatMul {
 			M: self.M,
 			N: self.N,
 			K: self.K,
+			alpha: self.alpha,
 			forward_id: graph.add_pass(MatMulPass::new( // C += A B
 				self.A_id.value_id(),
 				self.B_id.value_id(),
@@ -160,6 +161,7 @@ pub struct MatMulInstance {
 	pub M: Option<usize>,
 	pub N: Option<usize>,
 	pub K: Option<usize>,
+	alpha: f32,
 	forward_id: PassID,
 	backward1_id: PassID,
 	backward2_id: PassID,
@@ -332,6 +334,18 @@ impl OpInstance for MatMulInstance {
 
 		Ok(())
 	}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![
+			("A_trans", format!("{:?}", self.A_trans)),
+			("B_trans", format!("{:?}", self.B_trans)),
+			("C_trans", format!("{:?}", self.C_trans)),
+			("M", format!("{:?}", self.M)),
+			("N", format!("{:?}", self.N)),
+			("K", format!("{:?}", self.K)),
+			("alpha", format!("{:?}", self.alpha)),
+		]
+	}
 }
 
 
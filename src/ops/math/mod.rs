@@ -10,4 +10,9 @@ pub mod sin;
 pub mod cos;
 pub mod abs;
 pub mod reciprocal;
-pub mod scale;
\ No newline at end of file
+pub mod scale;
+pub mod pow;
+pub mod weighted_sum;
+pub mod l2_normalize;
+pub mod broadcast;
+pub mod pairwise_distance;
\ No newline at end of file
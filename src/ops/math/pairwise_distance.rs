@@ -0,0 +1,272 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use shape::NodeShape;
+use ndarray::{Array2, ArrayView2, Axis, Ix2};
+use std::any::Any;
+
+/// PairwiseDistance Op
+///
+/// Takes a `[n, d]` input of `n` embeddings and adds the `[n, n]` matrix of squared L2 distances between every pair of rows to the output.
+/// The distances are calculated as `||a||^2 + ||b||^2 - 2a·b`, using a single matrix multiplication, and negative results due to rounding are clamped to zero.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct PairwiseDistance {
+	input: NodeID,
+	output: NodeID,
+	name: Option<String>,
+}
+
+impl PairwiseDistance {
+	pub fn new(input: &NodeID, output: &NodeID) -> Self {
+		PairwiseDistance {
+			input: input.clone(),
+			output: output.clone(),
+			name: None,
+		}
+	}
+}
+
+impl Op for PairwiseDistance {
+	type InstanceType = PairwiseDistanceInstance;
+
+	fn type_name(&self) -> &'static str {
+		"PairwiseDistance"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.input.shape().ndim() == 2, format!("PairwiseDistance input must have 2 axes, found shape: {:?}", self.input.shape()));
+		ensure!(self.output.shape().ndim() == 2, format!("PairwiseDistance output must have 2 axes, found shape: {:?}", self.output.shape()));
+
+		let name = standard_op_name(&self, &self.name, graph, &[self.input.clone()], &[self.output.clone()]);
+
+		Ok(PairwiseDistanceInstance{
+			name: name,
+			input_id: self.input.clone(),
+			output_id: self.output.clone(),
+			forward_id: graph.add_pass(PairwiseDistanceForward::new(
+				self.input.clone(),
+				self.output.clone())),
+			backward_id: graph.add_pass(PairwiseDistanceBackward::new(
+				self.input.clone(),
+				self.output.clone())),
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)]
+pub struct PairwiseDistanceInstance{
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl OpInstance for PairwiseDistanceInstance {
+
+	fn name(&self) -> &str{&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){(vec![self.input_id.clone()], vec![self.output_id.clone()])}
+
+	fn inner_passes(&self) -> Vec<PassID>{vec![self.forward_id.clone(), self.backward_id.clone()]}
+
+	fn inner_ops(&self) -> Vec<OpID>{vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID>{vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+		let input_shape = shapes.get_shape(&self.input_id).to_data_shape()?;
+		ensure!(input_shape.ndim() == 2, ErrorKind::ShapePropagationError(self.name.clone(), format!("input must have 2 axes, found shape: {:?}", input_shape)));
+
+		let n = input_shape[0];
+		let output_shape: NodeShape = (&[n, n]).into();
+		shapes.merge_with(&self.output_id, &output_shape)
+	}
+
+}
+
+/// Returns the unclamped squared distance matrix, `||a||^2 + ||b||^2 - 2a·b`
+fn raw_distances(input: &ArrayView2<f32>) -> Array2<f32> {
+	let mut distances = input.dot(&input.t());
+	let sqr_norms: Vec<f32> = distances.diag().to_vec();
+	distances.indexed_iter_mut().for_each(|((i, j), d)| {
+		*d = sqr_norms[i] + sqr_norms[j] - 2.0 * *d;
+	});
+	distances
+}
+
+
+#[derive(Clone, Debug)]
+struct PairwiseDistanceForward {
+	input_id: NodeID,
+	output_id: NodeID,
+}
+
+impl PairwiseDistanceForward {
+	pub fn new(input_id: NodeID, output_id: NodeID) -> Self {
+		PairwiseDistanceForward {
+			input_id,
+			output_id,
+		}
+	}
+}
+
+impl Pass for PairwiseDistanceForward {
+	fn type_name(&self) -> &'static str {"PairwiseDistanceForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.input_id.value_id()],
+			vec![self.output_id.value_id()]
+		)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let input = data.get(&self.input_id.value_id())?;
+		let output = data.get_mut(&self.output_id.value_id())?;
+
+		let input = input.into_dimensionality::<Ix2>().map_err(|_| ErrorKind::PassError(self.name(), format!("input must have 2 axes, found shape: {:?}", self.input_id.shape())))?;
+		let mut output = output.into_dimensionality::<Ix2>().map_err(|_| ErrorKind::PassError(self.name(), format!("output must have 2 axes, found shape: {:?}", self.output_id.shape())))?;
+
+		let n = input.shape()[0];
+		ensure!(
+			output.shape() == [n, n],
+			ErrorKind::PassError(self.name(), format!("output shape: {:?} did not match [n, n] for input shape: {:?}", output.shape(), input.shape()))
+		);
+
+		let distances = raw_distances(&input);
+		output.zip_mut_with(&distances, |o, &d| *o += d.max(0.0));
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Clone, Debug)]
+struct PairwiseDistanceBackward {
+	input_id: NodeID,
+	output_id: NodeID,
+}
+
+impl PairwiseDistanceBackward {
+	pub fn new(input_id: NodeID, output_id: NodeID) -> Self {
+		PairwiseDistanceBackward {
+			input_id,
+			output_id,
+		}
+	}
+}
+
+impl Pass for PairwiseDistanceBackward {
+	fn type_name(&self) -> &'static str {"PairwiseDistanceBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.input_id.value_id(), self.output_id.gradient_id()],
+			vec![self.input_id.gradient_id()]
+		)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let input = data.get(&self.input_id.value_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+		let input_grad = data.get_mut(&self.input_id.gradient_id())?;
+
+		let input = input.into_dimensionality::<Ix2>().map_err(|_| ErrorKind::PassError(self.name(), format!("input must have 2 axes, found shape: {:?}", self.input_id.shape())))?;
+		let output_grad = output_grad.into_dimensionality::<Ix2>().map_err(|_| ErrorKind::PassError(self.name(), format!("output must have 2 axes, found shape: {:?}", self.output_id.shape())))?;
+		let mut input_grad = input_grad.into_dimensionality::<Ix2>().map_err(|_| ErrorKind::PassError(self.name(), format!("input must have 2 axes, found shape: {:?}", self.input_id.shape())))?;
+
+		let n = input.shape()[0];
+		ensure!(
+			output_grad.shape() == [n, n],
+			ErrorKind::PassError(self.name(), format!("output shape: {:?} did not match [n, n] for input shape: {:?}", output_grad.shape(), input.shape()))
+		);
+
+		// d(D_ij)/d(x_i) = 2(x_i - x_j), where the distance was not clamped.
+		// Collecting both D_ij and D_ji into a symmetric weight matrix W, grad_x = 2(diag(rowsum(W)) x - W x)
+		let distances = raw_distances(&input);
+		let mut weights = Array2::from_shape_fn([n, n], |(i, j)| {
+			let g_ij = if distances[[i, j]] > 0.0 {output_grad[[i, j]]} else {0.0};
+			let g_ji = if distances[[j, i]] > 0.0 {output_grad[[j, i]]} else {0.0};
+			2.0 * (g_ij + g_ji)
+		});
+		let row_sums = weights.sum_axis(Axis(1));
+		weights.mapv_inplace(|w| -w);
+
+		input_grad.scaled_add(1.0, &weights.dot(&input));
+		for ((mut input_grad, input), &row_sum) in input_grad.outer_iter_mut().zip(input.outer_iter()).zip(row_sums.iter()) {
+			input_grad.scaled_add(row_sum, &input);
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_pairwise_distance_backprop(){
+	_pairwise_distance_backprop().unwrap();
+}
+
+fn _pairwise_distance_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![4, 8], "input", tag![])?;
+	let node2 = g.new_node(shape![4, 4], "output", tag![])?;
+	let node3 = g.new_node(shape![4, 4], "target", tag![])?;
+
+	let _o1 = g.new_op(PairwiseDistance::new(&node1, &node2), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.005;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_pairwise_distance_value(){
+	_pairwise_distance_value().unwrap();
+}
+
+fn _pairwise_distance_value() -> Result<()>{
+	use graph::GraphDef;
+	use ndarray::arr2;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![3, 2], "input", tag![])?;
+	let node2 = g.new_node(shape![Unknown, Unknown], "output", tag![])?;
+
+	let _o1 = g.new_op(PairwiseDistance::new(&node1, &node2), tag![])?;
+
+	let mut subgraph = g.subgraph(&[node1.value_id()], &[node2.value_id()])?;
+	let storage = subgraph.execute(vec![arr2(&[[0.0, 0.0], [3.0, 4.0], [3.0, 4.0]]).into_dyn()])?;
+	let output = storage.get(&node2.value_id())?.into_dimensionality::<Ix2>().unwrap();
+
+	let expected = arr2(&[
+		[0.0, 25.0, 25.0],
+		[25.0, 0.0, 0.0],
+		[25.0, 0.0, 0.0]]);
+	assert_eq!(output.shape(), &[3, 3]);
+	assert!(output.all_close(&expected, 1e-5), "{:?}", output);
+	assert!(output.iter().all(|&d| d >= 0.0));
+
+	Ok(())
+}
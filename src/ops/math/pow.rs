@@ -0,0 +1,119 @@
+use graph::{GraphDef, Result};
+use id::NodeID;
+use ops::Op;
+use ops::activ::elementwise::{ActivationFunc, ElementwiseInstance, elementwise_build};
+
+#[derive(Clone, Debug)] 
+pub struct PowFunc{
+	exponent: f32,
+}
+
+impl ActivationFunc for PowFunc {
+	fn value(&self, input: f32) -> f32{
+		input.powf(self.exponent)
+	}
+
+	fn gradient(&self, input: f32, output_grad: f32) -> f32{
+		output_grad * self.exponent * input.powf(self.exponent - 1.0)
+	}
+
+	fn backprop_requires_input_value() -> bool {true}
+}
+
+/// Pow Op, the input is raised to a constant power.
+///
+/// As with `f32::powf()`, negative inputs raised to a non-integer exponent produce NaN.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct Pow {
+	output: NodeID,
+	input: NodeID,
+	exponent: f32,
+	name: Option<String>,
+}
+
+impl Pow {
+	pub fn new(input: &NodeID, output: &NodeID, exponent: f32) -> Self {
+		Pow {
+			input: input.clone(),
+			output: output.clone(),
+			exponent: exponent,
+			name: None,
+		}
+	}
+}
+
+impl Op for Pow {
+	type InstanceType = ElementwiseInstance<PowFunc>;
+
+	fn type_name(&self) -> &'static str {
+		"Pow"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		elementwise_build(graph, &self, &self.name, &self.input, &self.output, PowFunc{exponent: self.exponent})
+	}
+}
+
+
+#[test]
+fn test_pow_square_backprop(){
+	_pow_backprop(2.0).unwrap();
+}
+
+#[test]
+fn test_pow_sqrt_backprop(){
+	_pow_backprop(0.5).unwrap();
+}
+
+fn _pow_backprop(exponent: f32) -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+	use rand::thread_rng;
+	use rand::distributions::{Distribution, Range};
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5, 16], "input", tag![])?;
+	let node2 = g.new_node(shape![7, 5, 16], "output", tag![])?;
+	let node3 = g.new_node(shape![7, 5, 16], "target", tag![])?;
+
+
+	let _o1 = g.new_op(Pow::new(&node1, &node2, exponent), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+
+	let sample: Box<::std::ops::FnMut() -> f64 + 'static> = Box::new(|| {
+		let rng = &mut thread_rng();
+		let range = Range::new(0.5, 2.0);
+		range.sample(rng)
+	});
+	let mut override_dist = indexmap![];
+	override_dist.insert(node1.clone(), sample);
+
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut override_dist)?;
+
+	Ok(())
+}
+
+#[test]
+fn test_pow_negative_base(){
+	let func = PowFunc{exponent: 0.5};
+	assert!(func.value(-4.0).is_nan());
+	assert_eq!(func.value(4.0), 2.0);
+
+	let func = PowFunc{exponent: 3.0};
+	assert_eq!(func.value(-2.0), -8.0);
+	assert_eq!(func.gradient(-2.0, 1.0), 12.0);
+}
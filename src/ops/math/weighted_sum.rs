@@ -0,0 +1,280 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use ndarray::Zip;
+use ndarray_parallel::prelude::*;
+use std::any::Any;
+
+/// WeightedSum Op
+///
+/// Each input is multiplied by its fixed weight, and the sum added to the output.
+/// All inputs must have the same shape as each other and the output, e.g. when averaging an ensemble of model outputs.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct WeightedSum {
+	inputs: Vec<(NodeID, f32)>,
+	output: NodeID,
+	name: Option<String>,
+}
+
+impl WeightedSum {
+	pub fn new(inputs: &[(NodeID, f32)], output: &NodeID) -> Self {
+		WeightedSum {
+			inputs: inputs.to_vec(),
+			output: output.clone(),
+			name: None,
+		}
+	}
+}
+
+impl Op for WeightedSum {
+	type InstanceType = WeightedSumInstance;
+
+	fn type_name(&self) -> &'static str {
+		"WeightedSum"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.inputs.len() > 0, "WeightedSum requires at least one input");
+		for &(ref input, _) in &self.inputs {
+			ensure!(input.shape() == self.inputs[0].0.shape(), format!("WeightedSum inputs must have equal shapes, but '{}' has shape {:?} and '{}' has shape {:?}",
+				input.name(), input.shape(), self.inputs[0].0.name(), self.inputs[0].0.shape()));
+			ensure!(input.shape().merge(self.output.shape()).is_ok(), format!("WeightedSum input '{}' with shape {:?} is incompatible with output '{}' with shape {:?}",
+				input.name(), input.shape(), self.output.name(), self.output.shape()));
+		}
+
+		let input_ids: Vec<NodeID> = self.inputs.iter().map(|&(ref input, _)| input.clone()).collect();
+		let name = standard_op_name(&self, &self.name, graph, &input_ids, &[self.output.clone()]);
+
+		Ok(WeightedSumInstance{
+			name: name,
+			inputs: self.inputs.clone(),
+			output_id: self.output.clone(),
+			forward_id: graph.add_pass(WeightedSumForward::new(
+				self.inputs.clone(),
+				self.output.clone())),
+			backward_id: graph.add_pass(WeightedSumBackward::new(
+				self.inputs.clone(),
+				self.output.clone())),
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)]
+pub struct WeightedSumInstance{
+	name: String,
+	inputs: Vec<(NodeID, f32)>,
+	output_id: NodeID,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl WeightedSumInstance {
+	/// The inputs and their weights
+	pub fn inputs(&self) -> &[(NodeID, f32)] {
+		&self.inputs
+	}
+}
+
+impl OpInstance for WeightedSumInstance {
+
+	fn name(&self) -> &str{&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(self.inputs.iter().map(|&(ref input, _)| input.clone()).collect(), vec![self.output_id.clone()])
+	}
+
+	fn inner_passes(&self) -> Vec<PassID>{vec![self.forward_id.clone(), self.backward_id.clone()]}
+
+	fn inner_ops(&self) -> Vec<OpID>{vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID>{vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+		for &(ref input, _) in &self.inputs {
+			let input_shape = shapes.get_shape(input).clone();
+			shapes.merge_with(&self.output_id, &input_shape)?;
+		}
+		Ok(())
+	}
+
+}
+
+
+#[derive(Clone, Debug)]
+struct WeightedSumForward {
+	inputs: Vec<(NodeID, f32)>,
+	output_id: NodeID,
+}
+
+impl WeightedSumForward {
+	pub fn new(inputs: Vec<(NodeID, f32)>, output_id: NodeID) -> Self {
+		WeightedSumForward {
+			inputs,
+			output_id,
+		}
+	}
+}
+
+impl Pass for WeightedSumForward {
+	fn type_name(&self) -> &'static str {"WeightedSumForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			self.inputs.iter().map(|&(ref input, _)| input.value_id()).collect(),
+			vec![self.output_id.value_id()]
+		)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let mut output = data.get_mut(&self.output_id.value_id())?;
+
+		for &(ref input_id, weight) in &self.inputs {
+			let input = data.get(&input_id.value_id())?;
+
+			ensure!(
+				input.shape() == output.shape(),
+				ErrorKind::PassError(self.name(), format!("input '{}' shape: {:?} did not match output shape: {:?}", input_id.name(), input.shape(), output.shape()))
+			);
+
+			Zip::from(&mut output)
+				.and(&input)
+				.par_apply(|output, input| {
+					*output += weight * input;
+				});
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Clone, Debug)]
+struct WeightedSumBackward {
+	inputs: Vec<(NodeID, f32)>,
+	output_id: NodeID,
+}
+
+impl WeightedSumBackward {
+	pub fn new(inputs: Vec<(NodeID, f32)>, output_id: NodeID) -> Self {
+		WeightedSumBackward {
+			inputs,
+			output_id,
+		}
+	}
+}
+
+impl Pass for WeightedSumBackward {
+	fn type_name(&self) -> &'static str {"WeightedSumBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.output_id.gradient_id()],
+			self.inputs.iter().map(|&(ref input, _)| input.gradient_id()).collect()
+		)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+
+		for &(ref input_id, weight) in &self.inputs {
+			let mut input_grad = data.get_mut(&input_id.gradient_id())?;
+
+			ensure!(
+				input_grad.shape() == output_grad.shape(),
+				ErrorKind::PassError(self.name(), format!("input '{}' shape: {:?} did not match output shape: {:?}", input_id.name(), input_grad.shape(), output_grad.shape()))
+			);
+
+			Zip::from(&mut input_grad)
+				.and(&output_grad)
+				.par_apply(|input_grad, output_grad| {
+					*input_grad += weight * output_grad;
+				});
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_weighted_sum_backprop(){
+	_weighted_sum_backprop().unwrap();
+}
+
+fn _weighted_sum_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5, 16], "input1", tag![])?;
+	let node2 = g.new_node(shape![7, 5, 16], "input2", tag![])?;
+	let node3 = g.new_node(shape![7, 5, 16], "input3", tag![])?;
+	let node4 = g.new_node(shape![7, 5, 16], "output", tag![])?;
+	let node5 = g.new_node(shape![7, 5, 16], "target", tag![])?;
+
+	let _o1 = g.new_op(WeightedSum::new(&[(node1.clone(), 0.5), (node2.clone(), -1.5), (node3.clone(), 2.0)], &node4), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node4, &node5), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_weighted_sum_value(){
+	_weighted_sum_value().unwrap();
+}
+
+fn _weighted_sum_value() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::proportional::Proportional;
+	use ndarray::ArrayD;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![3, 4], "input1", tag![])?;
+	let node2 = g.new_node(shape![3, 4], "input2", tag![])?;
+	let node3 = g.new_node(shape![3, 4], "input3", tag![])?;
+	let node4 = g.new_node(shape![3, 4], "output", tag![])?;
+
+	let _o1 = g.new_op(WeightedSum::new(&[(node1.clone(), 0.5), (node2.clone(), -1.5), (node3.clone(), 2.0)], &node4), tag![])?;
+	let _o2 = g.new_op(Proportional::new(&node4), tag![])?;
+
+	let mut subgraph = g.subgraph(
+		&[node1.value_id(), node2.value_id(), node3.value_id()],
+		&[node4.value_id(), node1.gradient_id(), node2.gradient_id(), node3.gradient_id()])?;
+	let storage = subgraph.execute(vec![
+		ArrayD::from_elem(&[3, 4][..], 1.0),
+		ArrayD::from_elem(&[3, 4][..], 2.0),
+		ArrayD::from_elem(&[3, 4][..], 3.0),
+	])?;
+
+	// 0.5*1 - 1.5*2 + 2*3
+	assert!(storage.get(&node4.value_id())?.iter().all(|&x| (x - 3.5).abs() < 1e-6));
+
+	// Proportional passes a gradient of 1/12 to each output element
+	for &(ref node, weight) in &[(node1, 0.5), (node2, -1.5), (node3.clone(), 2.0)] {
+		assert!(storage.get(&node.gradient_id())?.iter().all(|&x| (x - weight / 12.0).abs() < 1e-6));
+	}
+
+	// inputs with unequal shapes are rejected when the op is built
+	let node6 = g.new_node(shape![4, 3], "input4", tag![])?;
+	assert!(g.new_op(WeightedSum::new(&[(node6, 1.0), (node3, 1.0)], &node4), tag![]).is_err());
+
+	Ok(())
+}
@@ -1,5 +1,6 @@
 pub mod dummy;
 pub mod numeric_check;
+pub mod bench;
 pub mod loss;
 pub mod nn;
 pub mod math;
@@ -9,12 +10,18 @@ pub mod reduce;
 pub mod regularisation;
 pub mod grad;
 pub mod fill;
+pub mod registry;
 
 use graph::{GraphDef, GraphShapes, Result};
 use storage::Storage;
 use id::{NodeID, DataID, OpID, PassID, OpTag};
 use std::any::Any;
+use std::fmt;
 use std::fmt::Debug;
+use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use indexmap::IndexMap;
+use ops::activ::elementwise::ElementwiseFunc;
 
 
 /// Generated default unique names for `Op`s
@@ -152,6 +159,133 @@ pub trait OpInstance: Any + OpClone + OpAny + Debug + Send + Sync{
 
 	/// TODO
 	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>;
+
+	/// Returns the scalar function applied by this Op if it is a single input, single output elementwise Op,
+	/// which allows it to be fused with neighbouring elementwise Ops by `GraphDef::fuse_elementwise()`.
+	///
+	/// Default: None
+	fn elementwise_func(&self) -> Option<Arc<ElementwiseFunc>> {
+		None
+	}
+
+	/// Returns the names of the scalar hyperparameters which can be changed after this Op is built, see `GraphDef::set_op_param()`.
+	///
+	/// Default: empty
+	fn op_params(&self) -> Vec<&'static str> {
+		vec![]
+	}
+
+	/// Returns the named scalar hyperparameter, if it is one of `op_params()`.
+	///
+	/// Default: None
+	fn op_param(&self, _key: &str) -> Option<OpParam> {
+		None
+	}
+
+	/// Returns the settings this Op was built with, other than its `op_params()` and initialisers, as (name, value) pairs.
+	///
+	/// `GraphDef::to_json()` can only record ops with their default settings, and compares these against an Op built with the defaults,
+	/// so ops with settings must report all of them to be serialised correctly.
+	/// Default: empty
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![]
+	}
+}
+
+/// A scalar Op hyperparameter which can be read and changed after the graph is built.
+///
+/// The handle only holds the value the Op was built with. Changed values, set by `GraphDef::set_op_param()`, are held by each `GraphDef`
+/// and shared with the `Subgraph`s built from it, so passes must read the current value from the `Storage` they are run with using `get()`.
+#[derive(Clone)]
+pub struct OpParam {
+	id: usize,
+	initial: f32,
+	min: f32,
+	max: f32,
+}
+
+static OP_PARAM_COUNT: AtomicUsize = ATOMIC_USIZE_INIT;
+
+impl OpParam {
+	pub fn new(value: f32) -> Self {
+		OpParam {
+			id: OP_PARAM_COUNT.fetch_add(1, Ordering::SeqCst),
+			initial: value,
+			min: ::std::f32::NEG_INFINITY,
+			max: ::std::f32::INFINITY,
+		}
+	}
+
+	/// Restricts the values accepted by `GraphDef::set_op_param()` to the range [min, max].
+	///
+	/// Default: [-inf, inf]
+	pub fn with_range(mut self, min: f32, max: f32) -> Self {
+		self.min = min;
+		self.max = max;
+		self
+	}
+
+	/// The range of accepted values, (min, max).
+	pub fn range(&self) -> (f32, f32) {
+		(self.min, self.max)
+	}
+
+	/// Returns whether `value` lies within `range()`, which is never the case for NaN.
+	pub fn in_range(&self, value: f32) -> bool {
+		value >= self.min && value <= self.max
+	}
+
+	pub(crate) fn id(&self) -> usize {
+		self.id
+	}
+
+	/// The value the Op was built with.
+	pub fn initial(&self) -> f32 {
+		self.initial
+	}
+
+	/// The current value for the graph being executed.
+	pub fn get(&self, data: &Storage) -> f32 {
+		data.op_param(self)
+	}
+}
+
+/// Only the initial value is shown, so that ops built with the same settings have the same `Debug` output.
+impl Debug for OpParam {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "OpParam({:?})", self.initial)
+	}
+}
+
+/// The values of `OpParam`s which have been changed from their initial values.
+///
+/// Clones hold independent values, while `share()` returns a handle which sees later changes.
+#[derive(Debug, Default)]
+pub(crate) struct OpParamValues {
+	values: Arc<RwLock<IndexMap<usize, f32>>>,
+}
+
+impl OpParamValues {
+	pub fn share(&self) -> Arc<RwLock<IndexMap<usize, f32>>> {
+		self.values.clone()
+	}
+
+	pub fn get(&self, param: &OpParam) -> f32 {
+		self.values.read().expect("Could not acquire lock on OpParam values").get(&param.id).cloned().unwrap_or(param.initial)
+	}
+
+	pub fn set(&self, param: &OpParam, value: f32) {
+		self.values.write().expect("Could not acquire lock on OpParam values").insert(param.id, value);
+	}
+}
+
+impl Clone for OpParamValues {
+	fn clone(&self) -> Self {
+		let values = self.values.read().expect("Could not acquire lock on OpParam values").clone();
+		OpParamValues {
+			values: Arc::new(RwLock::new(values)),
+		}
+	}
 }
 
 
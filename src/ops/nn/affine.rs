@@ -0,0 +1,177 @@
+use graph::{GraphDef, GraphShapes, Result};
+use id::{NodeID, OpID, PassID};
+use init::Initialiser;
+use ops::math::add::Add;
+use ops::math::mul::Mul;
+use ops::{standard_op_name, standard_inner_parameter_name, Op, OpInstance};
+use shape::NodeDim;
+
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct Affine {
+	input_id: NodeID,
+	output_id: NodeID,
+	axis: isize,
+	name: Option<String>,
+}
+
+impl Affine {
+	/// Creates an Op which implements a learnable per-channel scale and shift, `output = gamma * input + beta`
+	///
+	/// `gamma` and `beta` are `Parameter` nodes with one element per channel, broadcast over all other axes.
+	/// They are initialised to 1 and 0 respectively, so that the op is initially the identity.
+	pub fn new(input: &NodeID, output: &NodeID) -> Self {
+		Affine {
+			input_id: input.clone(),
+			output_id: output.clone(),
+			axis: -1,
+			name: None,
+		}
+	}
+
+	/// The channel axis, which must have a Known size.
+	///
+	/// Can be in the range [-input.ndims(), input.ndims()).
+	/// Default: -1
+	pub fn axis(mut self, axis: isize) -> Self {
+		self.axis = axis;
+		self
+	}
+}
+
+impl Op for Affine {
+	type InstanceType = AffineInstance;
+
+	fn type_name(&self) -> &'static str {
+		"Affine"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]);
+
+		let params_shape = {
+			let input_shape = self.input_id.shape();
+			let ndim = input_shape.ndim();
+			ensure!(self.axis >= -(ndim as isize) && self.axis < ndim as isize, "Affine axis {} is out of range for input with {} axes", self.axis, ndim);
+			let axis = (self.axis + ndim as isize) as usize % ndim;
+			let channels = match input_shape.dimensions()[axis] {
+				NodeDim::Known(dim) => dim,
+				_ => bail!("Affine channel axis must have a Known size"),
+			};
+			let mut params_shape = vec![1; ndim];
+			params_shape[axis] = channels;
+			params_shape
+		};
+
+		let gamma_name = standard_inner_parameter_name(&name, graph);
+		let gamma_id = graph.new_node(params_shape.clone().into(), gamma_name, tag![Parameter])?;
+		graph.set_initialiser(&gamma_id, Initialiser::fill(1.0));
+
+		let beta_name = standard_inner_parameter_name(&name, graph);
+		let beta_id = graph.new_node(params_shape.into(), beta_name, tag![Parameter])?;
+		graph.set_initialiser(&beta_id, Initialiser::fill(0.0));
+
+		let mul_id = graph.new_op(Mul::new(&self.input_id, &gamma_id, &self.output_id), tag![])?;
+		let add_id = graph.new_op(Add::new(&beta_id, &self.output_id), tag![])?;
+
+		Ok(AffineInstance{
+			name: name,
+			input_id: self.input_id,
+			output_id: self.output_id,
+			gamma_id: gamma_id,
+			beta_id: beta_id,
+			axis: self.axis,
+			mul_id: mul_id,
+			add_id: add_id,
+		})
+	}
+}
+
+
+/// Affine Op, the input is scaled by `gamma` then shifted by `beta` per channel
+#[derive(Clone, Debug)] 
+pub struct AffineInstance{
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	gamma_id: NodeID,
+	beta_id: NodeID,
+	axis: isize,
+	mul_id: OpID,
+	add_id: OpID,
+}
+
+impl AffineInstance {
+	/// Returns the scale parameter node
+	pub fn gamma(&self) -> &NodeID {
+		&self.gamma_id
+	}
+
+	/// Returns the shift parameter node
+	pub fn beta(&self) -> &NodeID {
+		&self.beta_id
+	}
+}
+
+impl OpInstance for AffineInstance {
+
+	fn name(&self) -> &str{&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(vec![self.input_id.clone()], vec![self.output_id.clone()])
+	}
+
+	fn inner_passes(&self) -> Vec<PassID>{vec![]}
+
+	fn inner_ops(&self) -> Vec<OpID>{vec![self.mul_id.clone(), self.add_id.clone()]}
+
+	fn inner_nodes(&self) -> Vec<NodeID>{vec![self.gamma_id.clone(), self.beta_id.clone()]}
+
+	fn propagate_shape_constraints(&self, _shapes: &mut GraphShapes) -> Result<()>{Ok(())}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("axis", format!("{:?}", self.axis))]
+	}
+}
+
+
+#[test]
+fn test_affine_backprop(){
+	_affine_backprop().unwrap();
+}
+
+fn _affine_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 6], "input", tag![])?;
+	let output = g.new_node(shape![4, 6], "output", tag![])?;
+	let target = g.new_node(shape![4, 6], "target", tag![])?;
+
+	let o1 = g.new_op(Affine::new(&input, &output), tag![])?;
+	let _o2 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+	// gamma and beta are initialised to the identity
+	let init_values = g.initialise_nodes(&o1.instance().inner_nodes())?;
+	assert_eq!(init_values[0].shape(), &[1, 6]);
+	assert!(init_values[0].iter().all(|&x| x == 1.0));
+	assert!(init_values[1].iter().all(|&x| x == 0.0));
+
+	// numeric_test draws random values for the input, gamma and beta
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
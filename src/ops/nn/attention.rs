@@ -0,0 +1,487 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use ndarray::{ArrayD, Array2, Array3, ArrayView2, Axis, Zip};
+use std::any::Any;
+use std::f32;
+
+/// ScaledDotProductAttention Op
+///
+/// Computes `output += softmax(query·keyᵀ/sqrt(d) + mask)·value` for each matrix in the batch.
+/// The last two axes of each input are the matrix axes, `query` is `[.., Sq, d]`, `key` is `[.., Sk, d]`, and `value` is `[.., Sk, dv]`,
+/// producing an output of shape `[.., Sq, dv]`. All leading axes are batch axes and must match between inputs.
+///
+/// The optional additive mask is `[Sq, Sk]`, applied to every matrix in the batch, or `[.., Sq, Sk]`.
+/// Positions with a mask value of `-inf` receive zero attention. Query positions where every key is masked produce zeros.
+/// The same node may be used for `query`, `key`, and `value`, as in self attention.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct ScaledDotProductAttention {
+	query_id: NodeID,
+	key_id: NodeID,
+	value_id: NodeID,
+	output_id: NodeID,
+	mask_id: Option<NodeID>,
+	name: Option<String>,
+}
+
+impl ScaledDotProductAttention {
+	pub fn new(query: &NodeID, key: &NodeID, value: &NodeID, output: &NodeID) -> Self {
+		ScaledDotProductAttention {
+			query_id: query.clone(),
+			key_id: key.clone(),
+			value_id: value.clone(),
+			output_id: output.clone(),
+			mask_id: None,
+			name: None,
+		}
+	}
+
+	/// Provide a node which is added to the attention logits before the softmax.
+	///
+	/// Default: None
+	pub fn mask(mut self, mask: &NodeID) -> Self {
+		self.mask_id = Some(mask.clone());
+		self
+	}
+
+	fn input_ids(&self) -> Vec<NodeID> {
+		let mut inputs = vec![self.query_id.clone(), self.key_id.clone(), self.value_id.clone()];
+		inputs.extend(self.mask_id.iter().cloned());
+		inputs
+	}
+}
+
+impl Op for ScaledDotProductAttention {
+	type InstanceType = ScaledDotProductAttentionInstance;
+
+	fn type_name(&self) -> &'static str {
+		"ScaledDotProductAttention"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		for node_id in [&self.query_id, &self.key_id, &self.value_id, &self.output_id].iter() {
+			ensure!(node_id.shape().ndim() >= 2, format!("ScaledDotProductAttention requires inputs and output to have at least 2 axes, node '{}' has shape {:?}", node_id.name(), node_id.shape()));
+		}
+
+		let inputs = self.input_ids();
+		let name = standard_op_name(&self, &self.name, graph, &inputs, &[self.output_id.clone()]);
+
+		Ok(ScaledDotProductAttentionInstance{
+			name: name,
+			query_id: self.query_id.clone(),
+			key_id: self.key_id.clone(),
+			value_id: self.value_id.clone(),
+			output_id: self.output_id.clone(),
+			mask_id: self.mask_id.clone(),
+			forward_id: graph.add_pass(AttentionForward::new(
+				self.query_id.clone(),
+				self.key_id.clone(),
+				self.value_id.clone(),
+				self.mask_id.clone(),
+				self.output_id.clone())),
+			backward_id: graph.add_pass(AttentionBackward::new(
+				self.query_id.clone(),
+				self.key_id.clone(),
+				self.value_id.clone(),
+				self.mask_id.clone(),
+				self.output_id.clone())),
+		})
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct ScaledDotProductAttentionInstance{
+	name: String,
+	query_id: NodeID,
+	key_id: NodeID,
+	value_id: NodeID,
+	output_id: NodeID,
+	mask_id: Option<NodeID>,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl OpInstance for ScaledDotProductAttentionInstance {
+
+	fn name(&self) -> &str{&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		let mut inputs = vec![self.query_id.clone(), self.key_id.clone(), self.value_id.clone()];
+		inputs.extend(self.mask_id.iter().cloned());
+		(inputs, vec![self.output_id.clone()])
+	}
+
+	fn inner_passes(&self) -> Vec<PassID>{vec![self.forward_id.clone(), self.backward_id.clone()]}
+
+	fn inner_ops(&self) -> Vec<OpID>{vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID>{vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+		// the output matches the query, except for the last axis which matches the value
+		let mut output_shape = shapes.get_shape(&self.query_id).clone();
+		let value_dim = {
+			let value_shape = shapes.get_shape(&self.value_id);
+			value_shape.dimensions()[value_shape.ndim() - 1].clone()
+		};
+		{
+			let n = output_shape.ndim();
+			output_shape.dimensions_mut()[n - 1] = value_dim;
+		}
+		shapes.merge_with(&self.output_id, &output_shape)
+	}
+}
+
+/// Splits a shape into the product of the batch axes, and the two matrix axes.
+fn matrix_dims(shape: &[usize]) -> (usize, usize, usize) {
+	let n = shape.len();
+	(shape[..n - 2].iter().product(), shape[n - 2], shape[n - 1])
+}
+
+/// Returns the attention weights, `softmax(query·keyᵀ*scale + mask)`, for a single matrix.
+fn attention_weights(query: ArrayView2<f32>, key: ArrayView2<f32>, mask: Option<ArrayView2<f32>>, scale: f32) -> Array2<f32> {
+	let mut weights = query.dot(&key.t());
+	weights *= scale;
+	if let Some(mask) = mask {
+		weights += &mask;
+	}
+
+	for mut row in weights.outer_iter_mut() {
+		let max = row.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+		if max == f32::NEG_INFINITY {
+			row.fill(0.0);
+			continue;
+		}
+		row.mapv_inplace(|x| (x - max).exp());
+		let sum = row.scalar_sum();
+		row /= sum;
+	}
+
+	weights
+}
+
+/// Checks the input shapes agree and returns (batch, Sq, Sk, d, dv, mask batch)
+fn check_shapes(pass_name: String, query: &[usize], key: &[usize], value: &[usize], mask: Option<&[usize]>, output: &[usize]) -> Result<(usize, usize, usize, usize, usize, usize)> {
+	ensure!(
+		query.len() >= 2 && query.len() == key.len() && query.len() == value.len() && query.len() == output.len(),
+		ErrorKind::PassError(pass_name.clone(), format!("query shape: {:?}, key shape: {:?}, value shape: {:?}, and output shape: {:?} must have the same number of axes, at least 2", query, key, value, output))
+	);
+	let n = query.len();
+	ensure!(
+		query[..n-2] == key[..n-2] && query[..n-2] == value[..n-2] && query[..n-2] == output[..n-2],
+		ErrorKind::PassError(pass_name.clone(), format!("batch axes of query shape: {:?}, key shape: {:?}, value shape: {:?}, and output shape: {:?} did not match", query, key, value, output))
+	);
+
+	let (batch, sq, d) = matrix_dims(query);
+	let (_, sk, dv) = matrix_dims(value);
+	ensure!(
+		key[n-2] == sk && key[n-1] == d,
+		ErrorKind::PassError(pass_name.clone(), format!("key shape: {:?} must be [.., {}, {}] to match query shape: {:?} and value shape: {:?}", key, sk, d, query, value))
+	);
+	ensure!(
+		output[n-2] == sq && output[n-1] == dv,
+		ErrorKind::PassError(pass_name.clone(), format!("output shape: {:?} must be [.., {}, {}] to match query shape: {:?} and value shape: {:?}", output, sq, dv, query, value))
+	);
+
+	let mask_batch = match mask {
+		Some(mask) if mask == &[sq, sk][..] => 1,
+		Some(mask) if mask.len() == n && mask[..n-2] == query[..n-2] && mask[n-2] == sq && mask[n-1] == sk => batch,
+		Some(mask) => bail!(ErrorKind::PassError(pass_name, format!("mask shape: {:?} must be [{}, {}] or match the batch axes of query shape: {:?}", mask, sq, sk, query))),
+		None => 0,
+	};
+
+	Ok((batch, sq, sk, d, dv, mask_batch))
+}
+
+
+#[derive(Clone, Debug)]
+struct AttentionForward {
+	query_id: NodeID,
+	key_id: NodeID,
+	value_id: NodeID,
+	mask_id: Option<NodeID>,
+	output_id: NodeID,
+}
+
+impl AttentionForward {
+	pub fn new(query_id: NodeID, key_id: NodeID, value_id: NodeID, mask_id: Option<NodeID>, output_id: NodeID) -> Self {
+		AttentionForward {
+			query_id,
+			key_id,
+			value_id,
+			mask_id,
+			output_id,
+		}
+	}
+}
+
+impl Pass for AttentionForward {
+	fn type_name(&self) -> &'static str {"AttentionForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		let mut inputs = vec![self.query_id.value_id(), self.key_id.value_id(), self.value_id.value_id()];
+		inputs.extend(self.mask_id.iter().map(|mask_id| mask_id.value_id()));
+		(inputs, vec![self.output_id.value_id()])
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let query = data.get(&self.query_id.value_id())?;
+		let key = data.get(&self.key_id.value_id())?;
+		let value = data.get(&self.value_id.value_id())?;
+		let mask = match self.mask_id {
+			Some(ref mask_id) => Some(data.get(&mask_id.value_id())?),
+			None => None,
+		};
+		let output = data.get_mut(&self.output_id.value_id())?;
+
+		let (batch, sq, sk, d, dv, mask_batch) = check_shapes(self.name(), query.shape(), key.shape(), value.shape(), mask.as_ref().map(|mask| mask.shape()), output.shape())?;
+		let scale = 1.0/(d as f32).sqrt();
+
+		let query = query.into_shape((batch, sq, d)).expect("query must be contiguous");
+		let key = key.into_shape((batch, sk, d)).expect("key must be contiguous");
+		let value = value.into_shape((batch, sk, dv)).expect("value must be contiguous");
+		let mask = mask.map(|mask| mask.into_shape((mask_batch, sq, sk)).expect("mask must be contiguous"));
+		let mut output = output.into_shape((batch, sq, dv)).expect("output must be contiguous");
+
+		for (b, mut output) in output.outer_iter_mut().enumerate() {
+			let mask = mask.as_ref().map(|mask| mask.subview(Axis(0), if mask_batch == 1 {0} else {b}));
+			let weights = attention_weights(query.subview(Axis(0), b), key.subview(Axis(0), b), mask, scale);
+			output += &weights.dot(&value.subview(Axis(0), b));
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Clone, Debug)]
+struct AttentionBackward {
+	query_id: NodeID,
+	key_id: NodeID,
+	value_id: NodeID,
+	mask_id: Option<NodeID>,
+	output_id: NodeID,
+}
+
+impl AttentionBackward {
+	pub fn new(query_id: NodeID, key_id: NodeID, value_id: NodeID, mask_id: Option<NodeID>, output_id: NodeID) -> Self {
+		AttentionBackward {
+			query_id,
+			key_id,
+			value_id,
+			mask_id,
+			output_id,
+		}
+	}
+}
+
+impl Pass for AttentionBackward {
+	fn type_name(&self) -> &'static str {"AttentionBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		let mut inputs = vec![self.query_id.value_id(), self.key_id.value_id(), self.value_id.value_id()];
+		inputs.extend(self.mask_id.iter().map(|mask_id| mask_id.value_id()));
+		inputs.push(self.output_id.gradient_id());
+
+		let mut outputs = vec![self.query_id.gradient_id(), self.key_id.gradient_id(), self.value_id.gradient_id()];
+		outputs.extend(self.mask_id.iter().map(|mask_id| mask_id.gradient_id()));
+		(inputs, outputs)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let query = data.get(&self.query_id.value_id())?;
+		let key = data.get(&self.key_id.value_id())?;
+		let value = data.get(&self.value_id.value_id())?;
+		let mask = match self.mask_id {
+			Some(ref mask_id) => Some(data.get(&mask_id.value_id())?),
+			None => None,
+		};
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+
+		let (batch, sq, sk, d, dv, mask_batch) = check_shapes(self.name(), query.shape(), key.shape(), value.shape(), mask.as_ref().map(|mask| mask.shape()), output_grad.shape())?;
+		let scale = 1.0/(d as f32).sqrt();
+
+		let query = query.into_shape((batch, sq, d)).expect("query must be contiguous");
+		let key = key.into_shape((batch, sk, d)).expect("key must be contiguous");
+		let value = value.into_shape((batch, sk, dv)).expect("value must be contiguous");
+		let mask = mask.map(|mask| mask.into_shape((mask_batch, sq, sk)).expect("mask must be contiguous"));
+		let output_grad = output_grad.into_shape((batch, sq, dv)).expect("output gradient must be contiguous");
+
+		// gradients are accumulated locally, as query, key, and value may be the same node
+		let mut query_grad: Array3<f32> = Array3::zeros((batch, sq, d));
+		let mut key_grad: Array3<f32> = Array3::zeros((batch, sk, d));
+		let mut value_grad: Array3<f32> = Array3::zeros((batch, sk, dv));
+		let mut mask_grad: Array3<f32> = Array3::zeros((mask_batch, sq, sk));
+
+		for b in 0..batch {
+			let mask_b = if mask_batch == 1 {0} else {b};
+			let (query, key, value, output_grad) = (query.subview(Axis(0), b), key.subview(Axis(0), b), value.subview(Axis(0), b), output_grad.subview(Axis(0), b));
+			let weights = attention_weights(query, key, mask.as_ref().map(|mask| mask.subview(Axis(0), mask_b)), scale);
+
+			value_grad.subview_mut(Axis(0), b).scaled_add(1.0, &weights.t().dot(&output_grad));
+
+			// back through the softmax, dS = P * (dP - sum(dP * P))
+			let mut logits_grad = output_grad.dot(&value.t());
+			for (mut logits_grad, weights) in logits_grad.outer_iter_mut().zip(weights.outer_iter()) {
+				let dot = logits_grad.iter().zip(weights.iter()).fold(0.0, |acc, (&g, &p)| acc + g*p);
+				Zip::from(&mut logits_grad).and(&weights).apply(|g, &p| *g = p*(*g - dot));
+			}
+
+			if mask_batch > 0 {
+				mask_grad.subview_mut(Axis(0), mask_b).scaled_add(1.0, &logits_grad);
+			}
+			query_grad.subview_mut(Axis(0), b).scaled_add(scale, &logits_grad.dot(&key));
+			key_grad.subview_mut(Axis(0), b).scaled_add(scale, &logits_grad.t().dot(&query));
+		}
+
+		let mut grads: Vec<(DataID, ArrayD<f32>)> = vec![];
+		let mut pending = vec![
+			(self.query_id.gradient_id(), query_grad.into_dyn()),
+			(self.key_id.gradient_id(), key_grad.into_dyn()),
+			(self.value_id.gradient_id(), value_grad.into_dyn()),
+		];
+		if let Some(ref mask_id) = self.mask_id {
+			pending.push((mask_id.gradient_id(), mask_grad.into_dyn()));
+		}
+		for (grad_id, grad) in pending {
+			if !data.is_required(&grad_id) {
+				continue;
+			}
+			match grads.iter().position(|&(ref id, _)| id == &grad_id) {
+				Some(i) => grads[i].1 += &grad,
+				None => grads.push((grad_id, grad)),
+			}
+		}
+
+		for (grad_id, grad) in grads {
+			let mut grad_view = data.get_mut(&grad_id)?;
+			let grad = grad.into_shape(grad_view.shape()).expect("gradient must match the shape of its node");
+			grad_view += &grad;
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_attention_backprop(){
+	_attention_backprop().unwrap();
+}
+
+fn _attention_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let query = g.new_node(shape![2, 4, 8], "query", tag![])?;
+	let key = g.new_node(shape![2, 4, 8], "key", tag![])?;
+	let value = g.new_node(shape![2, 4, 8], "value", tag![])?;
+	let mask = g.new_node(shape![4, 4], "mask", tag![])?;
+	let output = g.new_node(shape![2, 4, 8], "output", tag![])?;
+	let target = g.new_node(shape![2, 4, 8], "target", tag![])?;
+
+	let _o1 = g.new_op(ScaledDotProductAttention::new(&query, &key, &value, &output).mask(&mask), tag![])?;
+	let _o2 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.005;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_attention_self_backprop(){
+	_attention_self_backprop().unwrap();
+}
+
+fn _attention_self_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![2, 4, 8], "input", tag![])?;
+	let output = g.new_node(shape![2, 4, 8], "output", tag![])?;
+	let target = g.new_node(shape![2, 4, 8], "target", tag![])?;
+
+	let _o1 = g.new_op(ScaledDotProductAttention::new(&input, &input, &input, &output), tag![])?;
+	let _o2 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.005;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_attention_mask(){
+	_attention_mask().unwrap();
+}
+
+fn _attention_mask() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::generate_input_data;
+	use ndarray::Ix3;
+
+	let mut g = GraphDef::new();
+
+	let query = g.new_node(shape![2, 4, 8], "query", tag![])?;
+	let key = g.new_node(shape![2, 4, 8], "key", tag![])?;
+	let value = g.new_node(shape![2, 4, 8], "value", tag![])?;
+	let mask = g.new_node(shape![4, 4], "mask", tag![])?;
+	let output = g.new_node(shape![2, 4, 8], "output", tag![])?;
+
+	let _o1 = g.new_op(ScaledDotProductAttention::new(&query, &key, &value, &output).mask(&mask), tag![])?;
+
+	// causal mask, each query position may only attend to keys at the same or earlier positions
+	let mask_val = ArrayD::from_shape_fn(&[4, 4][..], |i| if i[1] > i[0] {f32::NEG_INFINITY} else {0.0});
+	let inputs = generate_input_data(&[query.clone(), key.clone(), value.clone()], 1.0, &mut indexmap![])?;
+
+	let mut subgraph = g.forward_subgraph(&[query.clone(), key.clone(), value.clone(), mask.clone()], &[output.clone()])?;
+
+	let storage = subgraph.execute(inputs.iter().cloned().chain(Some(mask_val.clone())).collect())?;
+	let output_1 = storage.get(&output.value_id())?.into_dimensionality::<Ix3>().unwrap().to_owned();
+	let value_1 = inputs[2].view().into_dimensionality::<Ix3>().unwrap();
+
+	// the first position can only attend to itself
+	for b in 0..2 {
+		assert!(output_1.subview(Axis(0), b).subview(Axis(0), 0).all_close(&value_1.subview(Axis(0), b).subview(Axis(0), 0), 1e-6));
+	}
+
+	// changing the values at the last position only changes the output at the last position
+	let mut value_2 = inputs[2].clone();
+	for b in 0..2 {
+		for x in value_2.subview_mut(Axis(0), b).subview_mut(Axis(0), 3).iter_mut() {
+			*x += 10.0;
+		}
+	}
+	let storage = subgraph.execute(vec![inputs[0].clone(), inputs[1].clone(), value_2, mask_val])?;
+	let output_2 = storage.get(&output.value_id())?.into_dimensionality::<Ix3>().unwrap().to_owned();
+	for b in 0..2 {
+		for i in 0..4 {
+			let unchanged = output_1.subview(Axis(0), b).subview(Axis(0), i).all_close(&output_2.subview(Axis(0), b).subview(Axis(0), i), 1e-5);
+			assert_eq!(unchanged, i < 3, "batch {} position {}", b, i);
+		}
+	}
+
+	Ok(())
+}
@@ -113,6 +113,7 @@ impl Op for Bias {
 			weights_id: weights_id,
 			output_id: self.output_id,
 			weights_are_inner: weights_are_inner,
+			shared_axes: self.shared_axes,
 			add_id: add_id,
 		})
 	}
@@ -127,6 +128,7 @@ pub struct BiasInstance{
 	output_id: NodeID,
 	weights_id: NodeID,
 	weights_are_inner: bool,
+	shared_axes: SmallVec<[isize; 6]>,
 	add_id: OpID,
 }
 
@@ -158,6 +160,10 @@ impl OpInstance for BiasInstance {
 	}
 
 	fn propagate_shape_constraints(&self, _shapes: &mut GraphShapes) -> Result<()>{Ok(())}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("shared_axes", format!("{:?}", &self.shared_axes[..]))]
+	}
 }
 
 
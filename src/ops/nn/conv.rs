@@ -3,7 +3,7 @@ use id::{NodeID, DataID, OpID, PassID};
 use storage::Storage;
 use ops::{standard_op_name, standard_inner_parameter_name, Op, OpInstance, Pass};
 use shape::{NodeDim, NodeShape};
-use ndarray::{ArrayViewMutD, ArrayD, Dimension, Axis, IxDyn};
+use ndarray::{ArrayViewMutD, ArrayViewD, ArrayBase, ArrayD, Data, Dimension, Axis, IxDyn, Slice as AxisSlice};
 use std::any::Any;
 use std::iter;
 use std::sync::atomic::{ATOMIC_USIZE_INIT, Ordering};
@@ -16,7 +16,7 @@ use unchecked_index as ui;
 use num_cpus;
 use matrixmultiply;
 use init::Initialiser;
-use rand::{thread_rng, Isaac64Rng, SeedableRng};
+use rng::new_rng;
 use rand::distributions::{Distribution, Normal};
 use smallvec::SmallVec;
 use typenum::{UInt, UTerm, U1, U2, U3};
@@ -64,6 +64,7 @@ pub struct Conv {
 	input_id: NodeID,
 	output_id: NodeID,
 	filter_id: Option<NodeID>,
+	dilation: Vec<usize>,
 	initialiser: Option<Initialiser>,
 	lowering_memory: usize,
 }
@@ -77,6 +78,7 @@ impl Conv {
 			input_id: input_id.clone(),
 			output_id: output_id.clone(),
 			filter_id: None,
+			dilation: vec![1; kernel_shape.len()],
 			initialiser: None,
 			lowering_memory: 1024*384,
 		}
@@ -101,6 +103,17 @@ impl Conv {
 		self
 	}
 
+	/// The spacing between kernel taps along each spatial dimension, for dilated (atrous) convolutions
+	///
+	/// A dilation of `d` spreads a kernel of width `k` over `d*(k-1)+1` input spaxels, leaving gaps of `d-1` between taps.
+	/// Must have one entry per spatial dimension, each greater than zero.
+	///
+	/// Default value: all ones, a standard convolution
+	pub fn dilation(mut self, dilation: &[usize]) -> Self {
+		self.dilation = dilation.to_vec();
+		self
+	}
+
 	pub fn init (mut self, initialiser: Initialiser) -> Self {
 		self.initialiser = Some(initialiser);
 		self
@@ -117,7 +130,7 @@ impl Conv {
 		Initialiser::new("MSRA Initialiser for Linear Op".to_string(), move |mut arr: ArrayViewMutD<f32>, _instance: Option<&OpInstance>|{
 			let k = arr.len()/arr.shape()[0];
 
-			let mut rng = Isaac64Rng::from_rng(thread_rng()).unwrap();
+			let mut rng = new_rng();
 			let norm = Normal::new(0.0, (multiplier as f64 / k as f64).sqrt());
 			for e in arr.iter_mut() {
 				*e = norm.sample(&mut rng) as f32;
@@ -147,6 +160,9 @@ impl Op for Conv {
 	}
 
 	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.dilation.len() == self.kernel_shape.len(), format!("Conv dilation {:?} must have one entry per spatial dimension of kernel shape {:?}", self.dilation, self.kernel_shape));
+		ensure!(self.dilation.iter().all(|&d| d > 0), format!("Conv dilation {:?} must be greater than zero", self.dilation));
+
 		let (name, filter_is_inner) = if let Some(ref filter) = self.filter_id {
 			(standard_op_name(&self, &self.name, graph, &[self.input_id.clone(), filter.clone()], &[self.output_id.clone()]), false)
 		} else {
@@ -188,6 +204,7 @@ impl Op for Conv {
 		Ok(ConvInstance{
 			name: name,
 			padding: self.padding,
+			dilation: self.dilation.clone(),
 			//kernel_shape: self.kernel_shape.clone(),
 			input_id: self.input_id.clone(),
 			output_id: self.output_id.clone(),
@@ -197,6 +214,7 @@ impl Op for Conv {
 				self.input_id.clone(),
 				self.output_id.clone(),
 				filter.clone(),
+				self.dilation.clone(),
 				self.lowering_memory,
 				//self.kernel_shape.clone(),
 			)),
@@ -204,6 +222,7 @@ impl Op for Conv {
 				self.input_id.clone(),
 				self.output_id.clone(),
 				filter.clone(),
+				self.dilation.clone(),
 				self.lowering_memory,
 				//self.kernel_shape.clone(),
 			)),
@@ -216,6 +235,7 @@ impl Op for Conv {
 pub struct ConvInstance {
 	name: String,
 	padding: Padding,
+	dilation: Vec<usize>,
 	input_id: NodeID,
 	output_id: NodeID,
 	filter_id: NodeID,
@@ -224,6 +244,13 @@ pub struct ConvInstance {
 	backward_id: PassID,
 }
 
+impl ConvInstance {
+	/// Returns the node holding the `Cout.H.W.Cin` filter, which can be supplied to another `Conv` to share it
+	pub fn filter(&self) -> &NodeID {
+		&self.filter_id
+	}
+}
+
 impl OpInstance for ConvInstance {
 	fn name(&self) -> &str {&self.name}
 
@@ -264,8 +291,11 @@ impl OpInstance for ConvInstance {
 		let in_channels = input_shape[input_shape.len()-1];
 		ensure!(in_channels == filter_shape[filter_shape.len()-1], format!("input channels dimension {} does not match final filter dimension {}", in_channels, filter_shape[filter_shape.len()-1]));
 
+		ensure!(filter_shape.len() == self.dilation.len() + 2, format!("filter shape {:?} does not have one spatial dimension per dilation {:?}", filter_shape, self.dilation));
+		let dilated_filter_shape = dilated_shape(filter_shape, &self.dilation);
+
 		let input_spatial = input_shape[1..input_shape.len()-1].iter();
-		let filter_spatial = filter_shape[1..filter_shape.len()-1].iter();
+		let filter_spatial = dilated_filter_shape[1..dilated_filter_shape.len()-1].iter();
 		ensure!(input_spatial.len() == filter_spatial.len(), "input shape and filter shape do not hav ethe same number of spatial dimensions");
 		
 
@@ -315,15 +345,17 @@ pub struct ConvForward {
 	input_id: NodeID,
 	output_id: NodeID,
 	filter_id: NodeID,
+	dilation: Vec<usize>,
 	lowering_memory: usize,
 }
 
 impl ConvForward {
-	pub fn new(input_id: NodeID, output_id: NodeID, filter_id: NodeID, lowering_memory: usize) -> Self{
+	pub fn new(input_id: NodeID, output_id: NodeID, filter_id: NodeID, dilation: Vec<usize>, lowering_memory: usize) -> Self{
 		ConvForward {
 			input_id,
 			output_id,
 			filter_id,
+			dilation,
 			lowering_memory,
 		}
 	}
@@ -342,6 +374,16 @@ impl Pass for ConvForward {
 		let filter = data.get(&self.filter_id.value_id())?;
 		let output = data.get_mut(&self.output_id.value_id())?;
 
+		// dilated filters are expanded with zeros between taps, and then convolved as normal
+		ensure!(filter.ndim() == self.dilation.len() + 2, "Filter ndims does not match dilation length");
+		let dilated_filter;
+		let filter = if is_dilated(&self.dilation) {
+			dilated_filter = dilate_filter(filter, &self.dilation);
+			dilated_filter.view()
+		} else {
+			filter
+		};
+
 		let n = input.shape()[0]; //TODO use ensure to guard against zero length shapes
 		let in_size: usize = input.shape()[1..].iter().product();
 		let _out_size: usize = output.shape()[1..].iter().product();
@@ -451,15 +493,17 @@ struct ConvBackward {
 	input_id: NodeID,
 	output_id: NodeID,
 	filter_id: NodeID,
+	dilation: Vec<usize>,
 	lowering_memory: usize,
 }
 
 impl ConvBackward {
-	pub fn new(input_id: NodeID, output_id: NodeID, filter_id: NodeID, lowering_memory: usize) -> Self {
+	pub fn new(input_id: NodeID, output_id: NodeID, filter_id: NodeID, dilation: Vec<usize>, lowering_memory: usize) -> Self {
 		ConvBackward {
 			input_id,
 			output_id,
 			filter_id,
+			dilation,
 			lowering_memory,
 		}
 	}
@@ -479,6 +523,17 @@ impl Pass for ConvBackward {
 		let filter = data.get(&self.filter_id.value_id())?;
 		let output_grad = data.get(&self.output_id.gradient_id())?;
 
+		// dilated filters are expanded with zeros between taps, and the gradient is gathered back from the taps at the end
+		ensure!(filter.ndim() == self.dilation.len() + 2, "Filter ndims does not match dilation length");
+		let dilated = is_dilated(&self.dilation);
+		let dilated_filter;
+		let filter = if dilated {
+			dilated_filter = dilate_filter(filter, &self.dilation);
+			dilated_filter.view()
+		} else {
+			filter
+		};
+
 		let n = input.shape()[0]; //TODO use ensure to guard against zero length shapes
 		let _in_size: usize = input.shape()[1..].iter().product();
 		let out_size: usize = output_grad.shape()[1..].iter().product();
@@ -652,15 +707,22 @@ impl Pass for ConvBackward {
 		// Write accumulated gradients back to the original (non-ROT180) format
 		if require_filter_gradients {
 			let mut filter_grad = data.get_mut(&self.filter_id.gradient_id())?;
+			let mut dilated_filter_grad: ArrayD<f32> = if dilated {ArrayD::zeros(filter.shape())} else {ArrayD::zeros(IxDyn(&[]))};
 
-			let mut inverted_filter_grad_actual = filter_grad.view_mut();
-			inverted_filter_grad_actual.swap_axes(0, filter.ndim()-1);
-			for axis in (1..filter.ndim()-1).map(Axis) {
-				inverted_filter_grad_actual.invert_axis(axis);
+			{
+				let mut inverted_filter_grad_actual = if dilated {dilated_filter_grad.view_mut()} else {filter_grad.view_mut()};
+				inverted_filter_grad_actual.swap_axes(0, filter.ndim()-1);
+				for axis in (1..filter.ndim()-1).map(Axis) {
+					inverted_filter_grad_actual.invert_axis(axis);
+				}
+				for _ in 0..n_threads{
+					let inverted_filter_grad = rx.recv().unwrap();
+					inverted_filter_grad_actual += &inverted_filter_grad;
+				}
 			}
-			for _ in 0..n_threads{
-				let inverted_filter_grad = rx.recv().unwrap();
-				inverted_filter_grad_actual += &inverted_filter_grad;
+
+			if dilated {
+				filter_grad += &dilated_taps(dilated_filter_grad.view(), &self.dilation);
 			}
 		}
 
@@ -924,6 +986,36 @@ unsafe fn _unsafe_pack_impl(patch: &mut [f32], input: &[f32], channels: usize, a
 	}
 }
 
+/// returns true if any spatial dimension has a dilation greater than one
+fn is_dilated(dilation: &[usize]) -> bool {
+	dilation.iter().any(|&d| d != 1)
+}
+
+/// returns the shape of a Cout.H.W.Cin filter once its spatial dimensions are dilated, each growing from k to d*(k-1)+1
+fn dilated_shape(filter_shape: &[usize], dilation: &[usize]) -> SmallVec<[usize;6]> {
+	debug_assert_eq!(filter_shape.len(), dilation.len() + 2);
+	let n = filter_shape.len();
+	iter::once(filter_shape[0])
+		.chain(filter_shape[1..n-1].iter().zip(dilation).map(|(&k, &d)| if k == 0 {0} else {d*(k-1)+1}))
+		.chain(iter::once(filter_shape[n-1]))
+		.collect()
+}
+
+/// Restricts a dilated Cout.H.W.Cin filter to the locations of the original kernel taps, i.e. every d-th spaxel of each spatial dimension
+fn dilated_taps<S: Data>(mut arr: ArrayBase<S, IxDyn>, dilation: &[usize]) -> ArrayBase<S, IxDyn> {
+	for (i, &d) in dilation.iter().enumerate() {
+		arr.slice_axis_inplace(Axis(i + 1), AxisSlice::new(0, None, d as isize));
+	}
+	arr
+}
+
+/// returns a copy of a Cout.H.W.Cin filter with zeros inserted between the kernel taps of each spatial dimension
+fn dilate_filter(filter: ArrayViewD<f32>, dilation: &[usize]) -> ArrayD<f32> {
+	let mut dilated = ArrayD::zeros(IxDyn(&dilated_shape(filter.shape(), dilation)));
+	dilated_taps(dilated.view_mut(), dilation).assign(&filter);
+	dilated
+}
+
 /// returns the [start, end) range indicies of the kernel which overlap with the 'image'
 /// returns [start, end) of the kernel range which overlaps with the image, given the width of the image and the position of the center of the kernel
 /// only odd kernels are valid.
@@ -1103,6 +1195,51 @@ fn _conv_backprop() -> Result<()>{
 	Ok(())
 }
 
+#[test]
+fn conv_dilation_backprop(){
+	_conv_dilation_backprop().unwrap();
+}
+
+fn _conv_dilation_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![1, 7, 7, 2], "input", tag![])?;
+	let node2 = g.new_node(shape![Unknown, Unknown, Unknown, 3], "conv", tag![])?;
+	let node3 = g.new_node(shape![1, 3, 3, 3], "target", tag![])?;
+
+	// a dilated 3x3 kernel covers 5x5 input spaxels
+	let _o1 = g.new_op(Conv::new(&node1, &node2, &[3, 3]).dilation(&[2, 2]).padding(Padding::Valid), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.01;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_dilate_filter(){
+	use ndarray::ArrayD;
+
+	let filter = ArrayD::from_shape_fn(IxDyn(&[1, 2, 3, 1]), |i| (i[1] * 3 + i[2] + 1) as f32);
+	let dilated = dilate_filter(filter.view(), &[3, 2]);
+	assert_eq!(dilated.shape(), &[1, 4, 5, 1]);
+	assert_eq!(dilated.iter().cloned().collect::<Vec<f32>>(), vec![
+		1.0, 0.0, 2.0, 0.0, 3.0,
+		0.0, 0.0, 0.0, 0.0, 0.0,
+		0.0, 0.0, 0.0, 0.0, 0.0,
+		4.0, 0.0, 5.0, 0.0, 6.0,
+	]);
+	assert_eq!(dilated_taps(dilated.view(), &[3, 2]), filter.view());
+}
 
 #[test]
 fn test_kernel_shuffles(){
@@ -0,0 +1,607 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, standard_inner_parameter_name, Op, OpInstance, Pass};
+use ops::nn::conv::Padding;
+use shape::{NodeDim, NodeShape};
+use ndarray::{ArrayViewMutD, Dimension};
+use std::any::Any;
+use std::iter;
+use init::Initialiser;
+use rng::new_rng;
+use rand::distributions::{Distribution, Normal};
+use smallvec::SmallVec;
+
+/// The transposed convolution, or deconvolution, used to upsample in generative and segmentation nets
+///
+/// Each input spaxel scatters a copy of the kernel, scaled by its values, into the output.
+/// Neighbouring input spaxels are placed `stride` spaxels apart in the output, so the output is upsampled by the stride.
+/// This is the gradient of a strided `Conv` with respect to its input, and its backward pass is the corresponding convolution.
+///
+/// The input and output of the Op are both rank (N+2) Tensors of shape:
+///
+/// `[num_batches, spatial_shape[0], ..., spatial_shape[N-1], num_channels]`
+///
+/// and the filters are a rank (N+2) Tensor of shape
+///
+/// `[num_input_channels, spatial_filter_shape[0], ..., spatial_filter_shape[N-1], num_output_channels]`
+///
+/// This matches the layout of a `Conv` filter mapping from the output channels to the input channels,
+/// so the filter of a `Conv` can be supplied to tie the weights of a decoder to an encoder.
+///
+/// For each spatial dimension, with input size `i`, kernel size `k` and stride `s`, the output size is:
+///
+/// * `Padding::Valid`: `(i - 1) * s + k`, every spaxel touched by the kernel.
+/// * `Padding::Same`: `i * s`, cropping `k - s` spaxels, with the extra spaxel taken from the end when odd. Requires `k >= s`.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct ConvTranspose {
+	name: Option<String>,
+	kernel_shape: Vec<usize>,
+	stride: Vec<usize>,
+	padding: Padding,
+	input_id: NodeID,
+	output_id: NodeID,
+	filter_id: Option<NodeID>,
+	initialiser: Option<Initialiser>,
+}
+
+impl ConvTranspose {
+	pub fn new(input_id: &NodeID, output_id: &NodeID, kernel_shape: &[usize]) -> Self{
+		ConvTranspose {
+			name: None,
+			kernel_shape: kernel_shape.to_vec(),
+			stride: vec![1; kernel_shape.len()],
+			padding: Padding::Valid,
+			input_id: input_id.clone(),
+			output_id: output_id.clone(),
+			filter_id: None,
+			initialiser: None,
+		}
+	}
+
+	/// The upsampling factor along each spatial dimension
+	///
+	/// Must have one entry per spatial dimension, each greater than zero.
+	///
+	/// Default value: all ones
+	pub fn stride(mut self, stride: &[usize]) -> Self {
+		self.stride = stride.to_vec();
+		self
+	}
+
+	/// Padding determines the shape of the output with respect to the input
+	///
+	/// Only `Padding::Valid` and `Padding::Same` are supported.
+	///
+	/// Default: `Padding::Valid`
+	pub fn padding(mut self, padding: Padding) -> Self {
+		self.padding = padding;
+		self
+	}
+
+	/// Provide a node to replace the filter tensor
+	///
+	/// The expected shape is `Cin.H.W.Cout`
+	/// If left as `None` a suitable `Parameter` node will be automatically created.
+	///
+	/// Default value: `None`
+	pub fn filter(mut self, node_id: Option<&NodeID>) -> Self {
+		self.filter_id = node_id.cloned();
+		self
+	}
+
+	pub fn init (mut self, initialiser: Initialiser) -> Self {
+		self.initialiser = Some(initialiser);
+		self
+	}
+
+	/// MSRA/He initialisation
+	///
+	/// This initialises the parameter filter with gaussian values drawn from N(0, multiplier/K).
+	/// Where K is the number of filter elements per output channel, which overestimates the incoming neurons to each outgoing neuron by a factor of the stride volume.
+	/// For typical use, the variance multiplier should cancel out the variance modifying
+	/// effect of the nonlinearity, e.g. use 2.0 with ReLU, and 1.0 with Tanh.
+	pub fn msra(multiplier: f32) -> Initialiser {
+		Initialiser::new("MSRA Initialiser for ConvTranspose Op".to_string(), move |mut arr: ArrayViewMutD<f32>, _instance: Option<&OpInstance>|{
+			let k = arr.len()/arr.shape()[arr.ndim() - 1];
+
+			let mut rng = new_rng();
+			let norm = Normal::new(0.0, (multiplier as f64 / k as f64).sqrt());
+			for e in arr.iter_mut() {
+				*e = norm.sample(&mut rng) as f32;
+			}
+		})
+	}
+
+	/// Xavier initialisation
+	///
+	/// This is just msra with a multiplier of 1.0
+	pub fn xavier() -> Initialiser {
+		ConvTranspose::msra(1.0)
+	}
+}
+
+impl Op for ConvTranspose {
+	type InstanceType = ConvTransposeInstance;
+
+	fn type_name(&self) -> &'static str {
+		"ConvTranspose"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.stride.len() == self.kernel_shape.len(), format!("ConvTranspose stride {:?} must have one entry per spatial dimension of kernel shape {:?}", self.stride, self.kernel_shape));
+		ensure!(self.stride.iter().all(|&s| s > 0), format!("ConvTranspose stride {:?} must be greater than zero", self.stride));
+		match self.padding {
+			Padding::Valid => {},
+			Padding::Same => {
+				ensure!(self.kernel_shape.iter().zip(&self.stride).all(|(&k, &s)| k >= s), format!("ConvTranspose with Padding::Same requires kernel shape {:?} to be at least the stride {:?}", self.kernel_shape, self.stride));
+			},
+			ref padding => bail!(format!("ConvTranspose does not support {:?}, only Padding::Valid and Padding::Same", padding)),
+		}
+
+		let (name, filter_is_inner) = if let Some(ref filter) = self.filter_id {
+			(standard_op_name(&self, &self.name, graph, &[self.input_id.clone(), filter.clone()], &[self.output_id.clone()]), false)
+		} else {
+			(standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]), true)
+		};
+
+		let filter = if let Some(filter) = self.filter_id {
+			let filter_shape = filter.shape().to_data_shape()?;
+			let filter_shape = filter_shape.slice();
+			ensure!(filter_shape.len() == self.kernel_shape.len() + 2 && &self.kernel_shape[..] == &filter_shape[1..filter_shape.len()-1], "If a filter node is supplied, it must have a fixed shape matching the kernel shape");
+			filter
+		} else {
+			let filter_name = standard_inner_parameter_name(&name, graph);
+			let shape: NodeShape = {
+				let in_shape = self.input_id.shape();
+				let out_shape = self.output_id.shape();
+
+				let c_in = &in_shape.dimensions()[in_shape.ndims()-1];
+				let c_out = &out_shape.dimensions()[out_shape.ndims()-1];
+				if let (&NodeDim::Known(c_in), &NodeDim::Known(c_out)) = (c_in, c_out) {
+					iter::once(c_in).chain(self.kernel_shape.iter().cloned()).chain(iter::once(c_out)).into()
+				} else {
+					bail!(format!("The channel dimensions (innermost dimensions) of both the input and output must be known so that a fixed sized parameter node can be inferred."));
+				}
+			};
+			graph.new_node(shape, filter_name, tag![Parameter])?
+		};
+
+		if let Some(initialiser) = self.initialiser {
+			graph.set_initialiser(&filter, initialiser);
+		};
+
+		Ok(ConvTransposeInstance{
+			name: name,
+			stride: self.stride.clone(),
+			padding: self.padding.clone(),
+			input_id: self.input_id.clone(),
+			output_id: self.output_id.clone(),
+			filter_id: filter.clone(),
+			filter_is_inner: filter_is_inner,
+			forward_id: graph.add_pass(ConvTransposeForward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				filter.clone(),
+				self.stride.clone(),
+				self.padding.clone(),
+			)),
+			backward_id: graph.add_pass(ConvTransposeBackward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				filter.clone(),
+				self.stride.clone(),
+				self.padding,
+			)),
+		})
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct ConvTransposeInstance {
+	name: String,
+	stride: Vec<usize>,
+	padding: Padding,
+	input_id: NodeID,
+	output_id: NodeID,
+	filter_id: NodeID,
+	filter_is_inner: bool,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl ConvTransposeInstance {
+	/// Returns the node holding the `Cin.H.W.Cout` filter
+	pub fn filter(&self) -> &NodeID {
+		&self.filter_id
+	}
+}
+
+impl OpInstance for ConvTransposeInstance {
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(
+			if self.filter_is_inner {
+				vec![self.input_id.clone()]
+			} else {
+				vec![self.input_id.clone(), self.filter_id.clone()]
+			},
+			vec![self.output_id.clone()]
+		)
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.forward_id.clone(), self.backward_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {
+		if self.filter_is_inner {
+			vec![self.filter_id.clone()]
+		} else {
+			vec![]
+		}
+	}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+		let input_shape = shapes.get_shape(&self.input_id).to_data_shape()?;
+		let filter_shape = shapes.get_shape(&self.filter_id).to_data_shape()?;
+		let input_shape = input_shape.slice();
+		let filter_shape = filter_shape.slice();
+
+		ensure!(input_shape.len() == filter_shape.len(), format!("input shape {:?} and filter shape {:?} do not have the same number of dimensions", input_shape, filter_shape));
+		ensure!(filter_shape.len() == self.stride.len() + 2, format!("filter shape {:?} does not have one spatial dimension per stride {:?}", filter_shape, self.stride));
+
+		let in_channels = input_shape[input_shape.len()-1];
+		ensure!(in_channels == filter_shape[0], format!("input channels dimension {} does not match first filter dimension {}", in_channels, filter_shape[0]));
+
+		let output_shape: NodeShape = iter::once(input_shape[0])
+			.chain(output_spatial_shape(&input_shape[1..input_shape.len()-1], &filter_shape[1..filter_shape.len()-1], &self.stride, &self.padding))
+			.chain(iter::once(filter_shape[filter_shape.len()-1]))
+			.into();
+
+		shapes.merge_with(&self.output_id, &output_shape)
+	}
+}
+
+/// Returns the spatial shape of the output, see `ConvTranspose` for the formula
+fn output_spatial_shape(input_spatial: &[usize], kernel_spatial: &[usize], stride: &[usize], padding: &Padding) -> SmallVec<[usize; 6]> {
+	input_spatial.iter().zip(kernel_spatial).zip(stride).map(|((&i, &k), &s)| {
+		match padding {
+			&Padding::Same => i * s,
+			_ => if i == 0 {0} else {(i - 1) * s + k},
+		}
+	}).collect()
+}
+
+/// Returns the number of spaxels cropped from the start of each spatial dimension of the full output
+fn crop_offsets(kernel_spatial: &[usize], stride: &[usize], padding: &Padding) -> SmallVec<[usize; 6]> {
+	kernel_spatial.iter().zip(stride).map(|(&k, &s)| {
+		match padding {
+			&Padding::Same => (k - s)/2,
+			_ => 0,
+		}
+	}).collect()
+}
+
+/// Calls `f(input_spaxel, kernel_spaxel, output_spaxel)` for every kernel tap of every input spaxel which lands within the output.
+///
+/// Spaxel indices are flat indices into the spatial dimensions, in row major order.
+fn for_each_tap<F: FnMut(usize, usize, usize)>(input_spatial: &[usize], kernel_spatial: &[usize], output_spatial: &[usize], stride: &[usize], offsets: &[usize], mut f: F) {
+	let ndim = input_spatial.len();
+	let in_spaxels: usize = input_spatial.iter().product();
+	let kernel_spaxels: usize = kernel_spatial.iter().product();
+
+	let mut in_coord: SmallVec<[usize; 6]> = iter::repeat(0).take(ndim).collect();
+	let mut kernel_coord: SmallVec<[usize; 6]> = iter::repeat(0).take(ndim).collect();
+
+	for i in 0..in_spaxels {
+		unravel(i, input_spatial, &mut in_coord);
+		'kernel: for k in 0..kernel_spaxels {
+			unravel(k, kernel_spatial, &mut kernel_coord);
+			let mut o = 0;
+			for axis in 0..ndim {
+				let full = in_coord[axis] * stride[axis] + kernel_coord[axis];
+				if full < offsets[axis] || full - offsets[axis] >= output_spatial[axis] {
+					continue 'kernel;
+				}
+				o = o * output_spatial[axis] + full - offsets[axis];
+			}
+			f(i, k, o);
+		}
+	}
+}
+
+/// Writes the row major coordinates of flat index `ind` into `coord`
+fn unravel(mut ind: usize, shape: &[usize], coord: &mut [usize]) {
+	for (c, &dim) in coord.iter_mut().zip(shape).rev() {
+		*c = ind % dim;
+		ind /= dim;
+	}
+}
+
+
+#[derive(Debug, Clone)]
+struct ConvTransposeForward {
+	input_id: NodeID,
+	output_id: NodeID,
+	filter_id: NodeID,
+	stride: Vec<usize>,
+	padding: Padding,
+}
+
+impl ConvTransposeForward {
+	pub fn new(input_id: NodeID, output_id: NodeID, filter_id: NodeID, stride: Vec<usize>, padding: Padding) -> Self {
+		ConvTransposeForward {
+			input_id,
+			output_id,
+			filter_id,
+			stride,
+			padding,
+		}
+	}
+}
+
+impl Pass for ConvTransposeForward {
+	fn type_name(&self) -> &'static str {"ConvTransposeForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id(), self.filter_id.value_id()],
+		vec![self.output_id.value_id()])
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let filter = data.get(&self.filter_id.value_id())?;
+		let mut output = data.get_mut(&self.output_id.value_id())?;
+
+		check_shapes(self.name(), input.shape(), filter.shape(), output.shape(), &self.stride, &self.padding)?;
+
+		let n = input.shape()[0];
+		let in_channels = input.shape()[input.ndim()-1];
+		let out_channels = output.shape()[output.ndim()-1];
+		let input_spatial = &input.shape()[1..input.ndim()-1];
+		let kernel_spatial = &filter.shape()[1..filter.ndim()-1];
+		let output_spatial = output.shape()[1..output.ndim()-1].to_vec();
+		let offsets = crop_offsets(kernel_spatial, &self.stride, &self.padding);
+
+		let in_size = input.len()/n;
+		let out_size = output.len()/n;
+		let kernel_spaxels: usize = kernel_spatial.iter().product();
+
+		let input = input.as_slice().unwrap();
+		let filter = filter.as_slice().unwrap();
+		let output = output.as_slice_mut().unwrap();
+
+		for (input, output) in input.chunks(in_size).zip(output.chunks_mut(out_size)) {
+			for_each_tap(input_spatial, kernel_spatial, &output_spatial, &self.stride, &offsets, |i, k, o| {
+				let input = &input[i*in_channels..][..in_channels];
+				let output = &mut output[o*out_channels..][..out_channels];
+				for (ci, &x) in input.iter().enumerate() {
+					let filter = &filter[(ci*kernel_spaxels + k)*out_channels..][..out_channels];
+					for (y, &w) in output.iter_mut().zip(filter) {
+						*y += x * w;
+					}
+				}
+			});
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Debug, Clone)]
+struct ConvTransposeBackward {
+	input_id: NodeID,
+	output_id: NodeID,
+	filter_id: NodeID,
+	stride: Vec<usize>,
+	padding: Padding,
+}
+
+impl ConvTransposeBackward {
+	pub fn new(input_id: NodeID, output_id: NodeID, filter_id: NodeID, stride: Vec<usize>, padding: Padding) -> Self {
+		ConvTransposeBackward {
+			input_id,
+			output_id,
+			filter_id,
+			stride,
+			padding,
+		}
+	}
+}
+
+impl Pass for ConvTransposeBackward {
+	fn type_name(&self) -> &'static str {"ConvTransposeBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id(), self.filter_id.value_id(), self.output_id.gradient_id()],
+		vec![self.input_id.gradient_id(), self.filter_id.gradient_id()])
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let filter = data.get(&self.filter_id.value_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+
+		check_shapes(self.name(), input.shape(), filter.shape(), output_grad.shape(), &self.stride, &self.padding)?;
+
+		let n = input.shape()[0];
+		let in_channels = input.shape()[input.ndim()-1];
+		let out_channels = output_grad.shape()[output_grad.ndim()-1];
+		let input_spatial = input.shape()[1..input.ndim()-1].to_vec();
+		let kernel_spatial = filter.shape()[1..filter.ndim()-1].to_vec();
+		let output_spatial = output_grad.shape()[1..output_grad.ndim()-1].to_vec();
+		let offsets = crop_offsets(&kernel_spatial, &self.stride, &self.padding);
+
+		let in_size = input.len()/n;
+		let out_size = output_grad.len()/n;
+		let kernel_spaxels: usize = kernel_spatial.iter().product();
+
+		let input = input.as_slice().unwrap();
+		let filter = filter.as_slice().unwrap();
+		let output_grad = output_grad.as_slice().unwrap();
+
+		// the input gradient is a strided convolution of the output gradient
+		if data.is_required(&self.input_id.gradient_id()) {
+			let mut input_grad = data.get_mut(&self.input_id.gradient_id())?;
+			let input_grad = input_grad.as_slice_mut().unwrap();
+
+			for (input_grad, output_grad) in input_grad.chunks_mut(in_size).zip(output_grad.chunks(out_size)) {
+				for_each_tap(&input_spatial, &kernel_spatial, &output_spatial, &self.stride, &offsets, |i, k, o| {
+					let input_grad = &mut input_grad[i*in_channels..][..in_channels];
+					let output_grad = &output_grad[o*out_channels..][..out_channels];
+					for (ci, ig) in input_grad.iter_mut().enumerate() {
+						let filter = &filter[(ci*kernel_spaxels + k)*out_channels..][..out_channels];
+						*ig += output_grad.iter().zip(filter).fold(0.0, |acc, (&g, &w)| acc + g * w);
+					}
+				});
+			}
+		}
+
+		if data.is_required(&self.filter_id.gradient_id()) {
+			let mut filter_grad = data.get_mut(&self.filter_id.gradient_id())?;
+			let filter_grad = filter_grad.as_slice_mut().unwrap();
+
+			for (input, output_grad) in input.chunks(in_size).zip(output_grad.chunks(out_size)) {
+				for_each_tap(&input_spatial, &kernel_spatial, &output_spatial, &self.stride, &offsets, |i, k, o| {
+					let input = &input[i*in_channels..][..in_channels];
+					let output_grad = &output_grad[o*out_channels..][..out_channels];
+					for (ci, &x) in input.iter().enumerate() {
+						let filter_grad = &mut filter_grad[(ci*kernel_spaxels + k)*out_channels..][..out_channels];
+						for (fg, &g) in filter_grad.iter_mut().zip(output_grad) {
+							*fg += x * g;
+						}
+					}
+				});
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+fn check_shapes(pass_name: String, input_shape: &[usize], filter_shape: &[usize], output_shape: &[usize], stride: &[usize], padding: &Padding) -> Result<()> {
+	ensure!(input_shape.len() == stride.len() + 2 && filter_shape.len() == input_shape.len() && output_shape.len() == input_shape.len(),
+		ErrorKind::PassError(pass_name, format!("input shape {:?}, filter shape {:?} and output shape {:?} must have {} dimensions", input_shape, filter_shape, output_shape, stride.len() + 2)));
+
+	let n = input_shape.len();
+	let expected_spatial = output_spatial_shape(&input_shape[1..n-1], &filter_shape[1..n-1], stride, padding);
+	ensure!(input_shape[0] == output_shape[0]
+		&& input_shape[n-1] == filter_shape[0]
+		&& output_shape[n-1] == filter_shape[n-1]
+		&& &expected_spatial[..] == &output_shape[1..n-1],
+		ErrorKind::PassError(pass_name, format!("input shape {:?} and filter shape {:?} are incompatible with output shape {:?}", input_shape, filter_shape, output_shape)));
+
+	Ok(())
+}
+
+
+#[test]
+fn conv_transpose_backprop(){
+	_conv_transpose_backprop().unwrap();
+}
+
+fn _conv_transpose_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 3, 4, 5], "input", tag![])?;
+	let node2 = g.new_node(shape![Unknown, Unknown, Unknown, 3], "conv_transpose", tag![])?;
+	let node3 = g.new_node(shape![2, 7, 13, 3], "target", tag![])?;
+
+	let _o1 = g.new_op(ConvTranspose::new(&node1, &node2, &[3, 4]).stride(&[2, 3]), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.01;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn conv_transpose_same_backprop(){
+	_conv_transpose_same_backprop().unwrap();
+}
+
+fn _conv_transpose_same_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 3, 4, 5], "input", tag![])?;
+	let node2 = g.new_node(shape![Unknown, Unknown, Unknown, 3], "conv_transpose", tag![])?;
+	let node3 = g.new_node(shape![2, 6, 12, 3], "target", tag![])?;
+
+	let _o1 = g.new_op(ConvTranspose::new(&node1, &node2, &[3, 4]).stride(&[2, 3]).padding(Padding::Same), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.01;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_conv_transpose_shape(){
+	_conv_transpose_shape().unwrap();
+}
+
+fn _conv_transpose_shape() -> Result<()>{
+	use graph::GraphDef;
+	use ndarray::{ArrayD, IxDyn, Ix4};
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![1, 3, 4, 1], "input", tag![])?;
+	let valid = g.new_node(shape![Unknown, Unknown, Unknown, 1], "valid", tag![])?;
+	let same = g.new_node(shape![Unknown, Unknown, Unknown, 1], "same", tag![])?;
+	let filter = g.new_node(shape![1, 3, 3, 1], "filter", tag![])?;
+
+	let _o1 = g.new_op(ConvTranspose::new(&input, &valid, &[3, 3]).stride(&[2, 3]).filter(Some(&filter)), tag![])?;
+	let _o2 = g.new_op(ConvTranspose::new(&input, &same, &[3, 3]).stride(&[2, 3]).filter(Some(&filter)).padding(Padding::Same), tag![])?;
+
+	let mut subgraph = g.subgraph(&[input.value_id(), filter.value_id()], &[valid.value_id(), same.value_id()])?;
+	let storage = subgraph.execute(vec![ArrayD::ones(IxDyn(&[1, 3, 4, 1])), ArrayD::ones(IxDyn(&[1, 3, 3, 1]))])?;
+
+	// (i - 1) * s + k for Valid, and i * s for Same
+	let valid_val = storage.get(&valid.value_id())?.into_dimensionality::<Ix4>().unwrap();
+	let same_val = storage.get(&same.value_id())?.into_dimensionality::<Ix4>().unwrap();
+	assert_eq!(valid_val.shape(), &[1, (3 - 1) * 2 + 3, (4 - 1) * 3 + 3, 1]);
+	assert_eq!(same_val.shape(), &[1, 3 * 2, 4 * 3, 1]);
+
+	// kernels overlap by one row where the stride is less than the kernel, and tile exactly where they are equal
+	assert_eq!(valid_val[[0, 0, 0, 0]], 1.0);
+	assert_eq!(valid_val[[0, 2, 0, 0]], 2.0);
+	assert_eq!(valid_val.iter().fold(0.0, |acc, &x| acc + x), 3.0 * 4.0 * 9.0);
+
+	// Same crops (k - s)/2 from the start of each spatial dimension, rounding the remainder onto the end
+	assert_eq!(same_val[[0, 1, 0, 0]], 1.0);
+	assert_eq!(same_val[[0, 2, 0, 0]], 2.0);
+	assert_eq!(same_val[[0, 5, 0, 0]], 1.0);
+
+	Ok(())
+}
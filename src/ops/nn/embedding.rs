@@ -0,0 +1,345 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use init::Initialiser;
+use storage::Storage;
+use ops::{standard_op_name, standard_inner_parameter_name, Op, OpInstance, Pass};
+use shape::{NodeShape, NodeDim};
+use ndarray::{Axis, Ix2};
+use std::any::Any;
+
+/// Embedding lookup, as used for categorical and token inputs
+///
+/// Creates an Op which gathers rows of a `[vocab, dim]` weights table, selected by the integer values of the input node.
+/// An input of shape `[n, ...]` produces an output of shape `[n, ..., dim]`.
+/// Input values are rounded to the nearest integer, and must lie in `[0, vocab)`.
+/// No gradient is propagated to the input. Repeated indices accumulate gradient into the same row of the table.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct Embedding {
+	input_id: NodeID,
+	output_id: NodeID,
+	weights_id: Option<NodeID>,
+	vocab: usize,
+	dim: Option<usize>,
+	name: Option<String>,
+	initialiser: Option<Initialiser>,
+}
+
+impl Embedding {
+	/// Constructs a new `Embedding` Op with a table of `vocab` rows.
+	pub fn new(input: &NodeID, output: &NodeID, vocab: usize) -> Self {
+		Embedding {
+			input_id: input.clone(),
+			output_id: output.clone(),
+			weights_id: None,
+			vocab: vocab,
+			dim: None,
+			name: None,
+			initialiser: None,
+		}
+	}
+
+	/// The number of columns in the weights table
+	///
+	/// If not set, this will be inferred from the innermost dimension of the output, which must then be Known.
+	pub fn dim(mut self, dim: usize) -> Self {
+		self.dim = Some(dim);
+		self
+	}
+
+	/// Provide a node to replace the weights table
+	///
+	/// The node must have shape `[vocab, dim]`.
+	/// If left as `None` a suitable `Parameter` node will be automatically created.
+	///
+	/// Default value: `None`
+	pub fn weights(mut self, node_id: Option<&NodeID>) -> Self {
+		self.weights_id = node_id.cloned();
+		self
+	}
+
+	/// Provide an Initialiser for the weights node
+	///
+	/// If not set, an automatically created weights table is initialised with `Initialiser::gaussian(0.0, 1.0)`.
+	pub fn init(mut self, initialiser: Initialiser) -> Self {
+		self.initialiser = Some(initialiser);
+		self
+	}
+}
+
+impl Op for Embedding {
+	type InstanceType = EmbeddingInstance;
+
+	fn type_name(&self) -> &'static str {
+		"Embedding"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+
+		let (name, weights_are_inner) = if let Some(ref weights) = self.weights_id {
+			(standard_op_name(&self, &self.name, graph, &[self.input_id.clone(), weights.clone()], &[self.output_id.clone()]), false)
+		} else {
+			(standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]), true)
+		};
+
+		let dim = match (self.dim, self.output_id.shape().dimensions().last()) {
+			(Some(dim), _) => dim,
+			(None, Some(&NodeDim::Known(dim))) => dim,
+			_ => bail!(format!("Embedding op ({}) could not infer dim, as the innermost output dimension is not Known. Use .dim()", name)),
+		};
+
+		let weights_id = if let Some(weights) = self.weights_id {
+			weights
+		} else {
+			let weights_name = standard_inner_parameter_name(&name, graph);
+			let weights = graph.new_node(shape![self.vocab, dim], weights_name, tag![Parameter])?;
+			if self.initialiser.is_none() {
+				graph.set_initialiser(&weights, Initialiser::gaussian(0.0, 1.0));
+			}
+			weights
+		};
+
+		if let Some(initialiser) = self.initialiser {
+			graph.set_initialiser(&weights_id, initialiser);
+		}
+
+		Ok(EmbeddingInstance{
+			name: name,
+			input_id: self.input_id.clone(),
+			output_id: self.output_id.clone(),
+			weights_id: weights_id.clone(),
+			weights_are_inner: weights_are_inner,
+			vocab: self.vocab,
+			dim: dim,
+			forward_id: graph.add_pass(EmbeddingForward::new(
+				self.input_id.clone(),
+				weights_id.clone(),
+				self.output_id.clone(),
+			)),
+			backward_id: graph.add_pass(EmbeddingBackward::new(
+				self.input_id.clone(),
+				weights_id.clone(),
+				self.output_id.clone(),
+			)),
+		})
+	}
+}
+
+
+/// Embedding Op
+#[derive(Clone, Debug)]
+pub struct EmbeddingInstance{
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	weights_id: NodeID,
+	weights_are_inner: bool,
+	vocab: usize,
+	dim: usize,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl EmbeddingInstance {
+	/// Returns the node holding the `[vocab, dim]` weights table
+	pub fn weights(&self) -> &NodeID {
+		&self.weights_id
+	}
+}
+
+impl OpInstance for EmbeddingInstance {
+
+	fn name(&self) -> &str{&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(
+			if self.weights_are_inner {
+				vec![self.input_id.clone()]
+			} else {
+				vec![self.input_id.clone(), self.weights_id.clone()]
+			},
+			vec![self.output_id.clone()]
+		)
+	}
+
+	fn inner_passes(&self) -> Vec<PassID>{vec![self.forward_id.clone(), self.backward_id.clone()]}
+
+	fn inner_ops(&self) -> Vec<OpID>{vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID>{
+		if self.weights_are_inner {
+			vec![self.weights_id.clone()]
+		} else {
+			vec![]
+		}
+	}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+		shapes.merge_with(&self.weights_id, &shape![self.vocab, self.dim])?;
+
+		let input_shape = shapes.get_shape(&self.input_id).clone();
+		let output_shape: NodeShape = input_shape.dimensions().iter().cloned().chain(Some(NodeDim::Known(self.dim))).into();
+		shapes.merge_with(&self.output_id, &output_shape)?;
+		Ok(())
+	}
+}
+
+/// Converts an index value to a row number, checking that it is in range
+fn row_index(pass_name: String, value: f32, vocab: usize) -> Result<usize> {
+	let row = value.round();
+	if !(row >= 0.0 && (row as usize) < vocab) {
+		bail!(ErrorKind::PassError(pass_name, format!("index value {} is outside the range [0, {})", value, vocab)));
+	}
+	Ok(row as usize)
+}
+
+
+#[derive(Clone, Debug)]
+pub struct EmbeddingForward {
+	input_id: NodeID,
+	weights_id: NodeID,
+	output_id: NodeID,
+}
+
+impl EmbeddingForward {
+	pub fn new(input_id: NodeID, weights_id: NodeID, output_id: NodeID) -> Self {
+		EmbeddingForward {
+			input_id,
+			weights_id,
+			output_id,
+		}
+	}
+}
+
+impl Pass for EmbeddingForward {
+	fn type_name(&self) -> &'static str {"EmbeddingForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id(), self.weights_id.value_id()],
+		vec![self.output_id.value_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let weights = data.get(&self.weights_id.value_id())?;
+		let output = data.get_mut(&self.output_id.value_id())?;
+
+		ensure!(weights.ndim() == 2, ErrorKind::PassError(self.name(), format!("weights must have 2 dimensions, found shape: {:?}", weights.shape())));
+		let vocab = weights.shape()[0];
+		let dim = weights.shape()[1];
+		ensure!(output.len() == input.len() * dim, ErrorKind::PassError(self.name(), format!("input shape: {:?} and weights shape: {:?} incompatible with output shape: {:?}", input.shape(), weights.shape(), output.shape())));
+
+		let weights = weights.into_shape(Ix2(vocab, dim)).expect("weights must be contiguous");
+		let mut output = output.into_shape(Ix2(input.len(), dim)).expect("output must be contiguous");
+
+		for (value, mut output_row) in input.iter().zip(output.outer_iter_mut()) {
+			let row = row_index(self.name(), *value, vocab)?;
+			output_row += &weights.subview(Axis(0), row);
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Clone, Debug)]
+pub struct EmbeddingBackward {
+	input_id: NodeID,
+	weights_id: NodeID,
+	output_id: NodeID,
+}
+
+impl EmbeddingBackward {
+	pub fn new(input_id: NodeID, weights_id: NodeID, output_id: NodeID) -> Self {
+		EmbeddingBackward {
+			input_id,
+			weights_id,
+			output_id,
+		}
+	}
+}
+
+impl Pass for EmbeddingBackward {
+	fn type_name(&self) -> &'static str {"EmbeddingBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id(), self.output_id.gradient_id()],
+		vec![self.weights_id.gradient_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+		let weights_grad = data.get_mut(&self.weights_id.gradient_id())?;
+
+		ensure!(weights_grad.ndim() == 2, ErrorKind::PassError(self.name(), format!("weights must have 2 dimensions, found shape: {:?}", weights_grad.shape())));
+		let vocab = weights_grad.shape()[0];
+		let dim = weights_grad.shape()[1];
+		ensure!(output_grad.len() == input.len() * dim, ErrorKind::PassError(self.name(), format!("input shape: {:?} and weights shape: {:?} incompatible with output shape: {:?}", input.shape(), weights_grad.shape(), output_grad.shape())));
+
+		let mut weights_grad = weights_grad.into_shape(Ix2(vocab, dim)).expect("weights gradient must be contiguous");
+		let output_grad = output_grad.into_shape(Ix2(input.len(), dim)).expect("output gradient must be contiguous");
+
+		for (value, output_grad_row) in input.iter().zip(output_grad.outer_iter()) {
+			let row = row_index(self.name(), *value, vocab)?;
+			let mut weights_grad_row = weights_grad.subview_mut(Axis(0), row);
+			weights_grad_row += &output_grad_row;
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_embedding_backprop(){
+	_embedding_backprop().unwrap();
+}
+
+fn _embedding_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::proportional::Proportional;
+	use ndarray::{ArrayD, IxDyn};
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![4], "input", tag![])?;
+	let node2 = g.new_node(shape![4, 2], "output", tag![])?;
+
+	let o1 = g.new_op(Embedding::new(&node1, &node2, 5), tag![])?;
+	let _o2 = g.new_op(Proportional::new(&node2), tag![])?;
+
+	let weights = o1.instance().weights().clone();
+
+	let indices = ArrayD::from_shape_vec(IxDyn(&[4]), vec![1.0, 3.0, 1.0, 0.0]).unwrap();
+	let table = ArrayD::from_shape_fn(IxDyn(&[5, 2]), |idx| (idx[0] * 10 + idx[1]) as f32);
+
+	let mut subgraph = g.subgraph(&[node1.value_id(), weights.value_id()], &[node2.value_id(), weights.gradient_id()])?;
+	let storage = subgraph.execute(vec![indices, table.clone()])?;
+
+	// gathered rows
+	let output = storage.get(&node2.value_id())?;
+	for (i, &row) in [1, 3, 1, 0].iter().enumerate() {
+		for j in 0..2 {
+			assert_eq!(output[[i, j]], table[[row, j]]);
+		}
+	}
+
+	// Proportional applies a gradient of 1/8 to each output element,
+	// which should accumulate into the gathered rows only
+	let weights_grad = storage.get(&weights.gradient_id())?;
+	let counts = [1.0, 2.0, 0.0, 1.0, 0.0];
+	for row in 0..5 {
+		for j in 0..2 {
+			let expected = counts[row]/8.0;
+			assert!((weights_grad[[row, j]] - expected).abs() < 1e-6, "row {} col {} grad {} expected {}", row, j, weights_grad[[row, j]], expected);
+		}
+	}
+
+	Ok(())
+}
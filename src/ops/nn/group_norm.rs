@@ -0,0 +1,450 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use init::Initialiser;
+use storage::Storage;
+use ops::{standard_op_name, standard_inner_parameter_name, Op, OpInstance, Pass};
+use shape::NodeDim;
+use ndarray::{ArrayViewD, Axis, Zip, Slice as AxisSlice};
+use std::any::Any;
+use std::ops::Range;
+
+/// GroupNorm Op
+///
+/// Splits the channels of each example into `groups` groups of consecutive channels, and normalises each group to zero mean and unit variance
+/// using the statistics over the group's channels and all spatial positions of that example alone.
+/// A learnable per-channel scale and shift is then applied, `output += gamma * (x - mean)/sqrt(var + epsilon) + beta`.
+/// As the statistics don't depend on the rest of the batch, this remains stable for small batch sizes.
+///
+/// The outermost axis is the batch axis. The channel axis defaults to the innermost axis, matching the `[batch, spatial..., channels]` layout used by `Conv`.
+/// `gamma` and `beta` are `Parameter` nodes of shape `[channels]`, initialised to 1 and 0 respectively unless other initialisers are supplied.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct GroupNorm {
+	input_id: NodeID,
+	output_id: NodeID,
+	groups: usize,
+	axis: isize,
+	epsilon: f32,
+	gamma_initialiser: Option<Initialiser>,
+	beta_initialiser: Option<Initialiser>,
+	name: Option<String>,
+}
+
+impl GroupNorm {
+	pub fn new(input: &NodeID, output: &NodeID) -> Self {
+		GroupNorm {
+			input_id: input.clone(),
+			output_id: output.clone(),
+			groups: 32,
+			axis: -1,
+			epsilon: 1e-5,
+			gamma_initialiser: None,
+			beta_initialiser: None,
+			name: None,
+		}
+	}
+
+	/// The number of groups the channels are split into, which must divide the number of channels.
+	///
+	/// A single group normalises over all channels, as in layer normalisation, while one group per channel gives instance normalisation.
+	/// Default: 32
+	pub fn groups(mut self, groups: usize) -> Self {
+		self.groups = groups;
+		self
+	}
+
+	/// The channel axis, which must have a Known size and can't be the outermost (batch) axis.
+	///
+	/// Can be in the range [-input.ndims(), input.ndims()).
+	/// Default: -1
+	pub fn axis(mut self, axis: isize) -> Self {
+		self.axis = axis;
+		self
+	}
+
+	/// Added to the variance before the square root is taken.
+	///
+	/// Default: 1e-5
+	pub fn epsilon(mut self, epsilon: f32) -> Self {
+		self.epsilon = epsilon;
+		self
+	}
+
+	/// Provide an Initialiser for the scale parameter, `gamma`
+	///
+	/// Default: `Initialiser::fill(1.0)`
+	pub fn gamma_init(mut self, initialiser: Initialiser) -> Self {
+		self.gamma_initialiser = Some(initialiser);
+		self
+	}
+
+	/// Provide an Initialiser for the shift parameter, `beta`
+	///
+	/// Default: `Initialiser::fill(0.0)`
+	pub fn beta_init(mut self, initialiser: Initialiser) -> Self {
+		self.beta_initialiser = Some(initialiser);
+		self
+	}
+}
+
+impl Op for GroupNorm {
+	type InstanceType = GroupNormInstance;
+
+	fn type_name(&self) -> &'static str {
+		"GroupNorm"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.epsilon >= 0.0, format!("GroupNorm epsilon must not be negative, found: {}", self.epsilon));
+
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]);
+
+		let (axis, channels) = {
+			let input_shape = self.input_id.shape();
+			let ndim = input_shape.ndim();
+			ensure!(ndim > 1, "GroupNorm input must have a batch axis and a channel axis");
+			ensure!(self.axis >= -(ndim as isize) && self.axis < ndim as isize, format!("GroupNorm axis {} is out of range for input with {} axes", self.axis, ndim));
+			let axis = (self.axis + ndim as isize) as usize % ndim;
+			ensure!(axis > 0, "GroupNorm channel axis can't be the outermost (batch) axis");
+			let channels = match input_shape.dimensions()[axis] {
+				NodeDim::Known(dim) => dim,
+				_ => bail!("GroupNorm channel axis must have a Known size"),
+			};
+			(axis, channels)
+		};
+		ensure!(self.groups > 0 && channels % self.groups == 0, format!("GroupNorm groups: {} must divide the number of channels: {}", self.groups, channels));
+
+		let gamma_name = standard_inner_parameter_name(&name, graph);
+		let gamma_id = graph.new_node(shape![channels], gamma_name, tag![Parameter])?;
+		graph.set_initialiser(&gamma_id, self.gamma_initialiser.unwrap_or_else(|| Initialiser::fill(1.0)));
+
+		let beta_name = standard_inner_parameter_name(&name, graph);
+		let beta_id = graph.new_node(shape![channels], beta_name, tag![Parameter])?;
+		graph.set_initialiser(&beta_id, self.beta_initialiser.unwrap_or_else(|| Initialiser::fill(0.0)));
+
+		Ok(GroupNormInstance{
+			name: name,
+			input_id: self.input_id.clone(),
+			output_id: self.output_id.clone(),
+			gamma_id: gamma_id.clone(),
+			beta_id: beta_id.clone(),
+			axis: axis,
+			groups: self.groups,
+			epsilon: self.epsilon,
+			forward_id: graph.add_pass(GroupNormForward::new(
+				self.input_id.clone(),
+				gamma_id.clone(),
+				beta_id.clone(),
+				self.output_id.clone(),
+				axis,
+				self.groups,
+				self.epsilon)),
+			backward_id: graph.add_pass(GroupNormBackward::new(
+				self.input_id.clone(),
+				gamma_id,
+				beta_id,
+				self.output_id.clone(),
+				axis,
+				self.groups,
+				self.epsilon)),
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)]
+pub struct GroupNormInstance{
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	gamma_id: NodeID,
+	beta_id: NodeID,
+	axis: usize,
+	groups: usize,
+	epsilon: f32,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl GroupNormInstance {
+	/// Returns the scale parameter node
+	pub fn gamma(&self) -> &NodeID {
+		&self.gamma_id
+	}
+
+	/// Returns the shift parameter node
+	pub fn beta(&self) -> &NodeID {
+		&self.beta_id
+	}
+
+	/// The channel axis, in the range [1, input.ndims())
+	pub fn axis(&self) -> usize {
+		self.axis
+	}
+
+	/// The number of groups the channels are split into
+	pub fn groups(&self) -> usize {
+		self.groups
+	}
+}
+
+impl OpInstance for GroupNormInstance {
+
+	fn name(&self) -> &str{&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){(vec![self.input_id.clone()], vec![self.output_id.clone()])}
+
+	fn inner_passes(&self) -> Vec<PassID>{vec![self.forward_id.clone(), self.backward_id.clone()]}
+
+	fn inner_ops(&self) -> Vec<OpID>{vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID>{vec![self.gamma_id.clone(), self.beta_id.clone()]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+		let input_shape = shapes.get_shape(&self.input_id).clone();
+		shapes.merge_with(&self.output_id, &input_shape)
+	}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("axis", format!("{:?}", self.axis)), ("groups", format!("{:?}", self.groups)), ("epsilon", format!("{:?}", self.epsilon))]
+	}
+}
+
+/// Returns the mean and 1/sqrt(var + epsilon) over a range of channels of one example
+fn group_moments(x: &ArrayViewD<f32>, axis: Axis, channels: Range<usize>, epsilon: f32) -> (f32, f32) {
+	let group = x.slice_axis(axis, AxisSlice::from(channels));
+	let n = group.len() as f32;
+	let mean = group.scalar_sum()/n;
+	let var = group.iter().fold(0.0, |acc, &x| acc + (x - mean)*(x - mean))/n;
+	(mean, 1.0/(var + epsilon).sqrt())
+}
+
+/// Checks the shapes of the input, output and parameters, returning the number of channels in each group
+fn check_shapes(pass_name: String, input_shape: &[usize], output_shape: &[usize], param_len: usize, axis: usize, groups: usize) -> Result<usize> {
+	ensure!(
+		input_shape == output_shape,
+		ErrorKind::PassError(pass_name, format!("input shape: {:?} did not match output shape: {:?}", input_shape, output_shape))
+	);
+	ensure!(
+		axis > 0 && axis < input_shape.len(),
+		ErrorKind::PassError(pass_name, format!("channel axis {} is out of range for input shape: {:?}", axis, input_shape))
+	);
+	ensure!(
+		param_len == input_shape[axis] && input_shape[axis] % groups == 0,
+		ErrorKind::PassError(pass_name, format!("input shape: {:?} does not have {} channels in {} groups", input_shape, param_len, groups))
+	);
+	Ok(input_shape[axis]/groups)
+}
+
+
+#[derive(Clone, Debug)]
+struct GroupNormForward {
+	input_id: NodeID,
+	gamma_id: NodeID,
+	beta_id: NodeID,
+	output_id: NodeID,
+	axis: usize,
+	groups: usize,
+	epsilon: f32,
+}
+
+impl GroupNormForward {
+	pub fn new(input_id: NodeID, gamma_id: NodeID, beta_id: NodeID, output_id: NodeID, axis: usize, groups: usize, epsilon: f32) -> Self {
+		GroupNormForward {
+			input_id,
+			gamma_id,
+			beta_id,
+			output_id,
+			axis,
+			groups,
+			epsilon,
+		}
+	}
+}
+
+impl Pass for GroupNormForward {
+	fn type_name(&self) -> &'static str {"GroupNormForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.input_id.value_id(), self.gamma_id.value_id(), self.beta_id.value_id()],
+			vec![self.output_id.value_id()]
+		)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let input = data.get(&self.input_id.value_id())?;
+		let gamma = data.get(&self.gamma_id.value_id())?;
+		let beta = data.get(&self.beta_id.value_id())?;
+		let mut output = data.get_mut(&self.output_id.value_id())?;
+
+		let group_size = check_shapes(self.name(), input.shape(), output.shape(), gamma.len(), self.axis, self.groups)?;
+		ensure!(beta.len() == gamma.len(), ErrorKind::PassError(self.name(), format!("beta shape: {:?} did not match gamma shape: {:?}", beta.shape(), gamma.shape())));
+		let gamma: Vec<f32> = gamma.iter().cloned().collect();
+		let beta: Vec<f32> = beta.iter().cloned().collect();
+
+		// the batch axis is removed when iterating over examples
+		let axis = Axis(self.axis - 1);
+		for (x, mut output) in input.outer_iter().zip(output.outer_iter_mut()) {
+			for group in 0..self.groups {
+				let channels = group*group_size..(group + 1)*group_size;
+				let (mean, inv_std) = group_moments(&x, axis, channels.clone(), self.epsilon);
+				for channel in channels {
+					let (gamma, beta) = (gamma[channel], beta[channel]);
+					Zip::from(&mut output.subview_mut(axis, channel)).and(&x.subview(axis, channel)).apply(|output, &x| {
+						*output += gamma*(x - mean)*inv_std + beta;
+					});
+				}
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Clone, Debug)]
+struct GroupNormBackward {
+	input_id: NodeID,
+	gamma_id: NodeID,
+	beta_id: NodeID,
+	output_id: NodeID,
+	axis: usize,
+	groups: usize,
+	epsilon: f32,
+}
+
+impl GroupNormBackward {
+	pub fn new(input_id: NodeID, gamma_id: NodeID, beta_id: NodeID, output_id: NodeID, axis: usize, groups: usize, epsilon: f32) -> Self {
+		GroupNormBackward {
+			input_id,
+			gamma_id,
+			beta_id,
+			output_id,
+			axis,
+			groups,
+			epsilon,
+		}
+	}
+}
+
+impl Pass for GroupNormBackward {
+	fn type_name(&self) -> &'static str {"GroupNormBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.input_id.value_id(), self.gamma_id.value_id(), self.output_id.gradient_id()],
+			vec![self.input_id.gradient_id(), self.gamma_id.gradient_id(), self.beta_id.gradient_id()]
+		)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let input = data.get(&self.input_id.value_id())?;
+		let gamma = data.get(&self.gamma_id.value_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+
+		let group_size = check_shapes(self.name(), input.shape(), output_grad.shape(), gamma.len(), self.axis, self.groups)?;
+		let gamma: Vec<f32> = gamma.iter().cloned().collect();
+
+		let mut input_grad = if data.is_required(&self.input_id.gradient_id()) {Some(data.get_mut(&self.input_id.gradient_id())?)} else {None};
+		let mut gamma_grad_sum = vec![0.0; gamma.len()];
+		let mut beta_grad_sum = vec![0.0; gamma.len()];
+
+		// the batch axis is removed when iterating over examples
+		let axis = Axis(self.axis - 1);
+		let mut input_grad_iter = input_grad.as_mut().map(|input_grad| input_grad.outer_iter_mut());
+		for (x, output_grad) in input.outer_iter().zip(output_grad.outer_iter()) {
+			let mut input_grad = input_grad_iter.as_mut().map(|iter| iter.next().unwrap());
+
+			for group in 0..self.groups {
+				let channels = group*group_size..(group + 1)*group_size;
+				let (mean, inv_std) = group_moments(&x, axis, channels.clone(), self.epsilon);
+				let n = x.slice_axis(axis, AxisSlice::from(channels.clone())).len() as f32;
+
+				// sums over the group of dx_hat and dx_hat*x_hat, where dx_hat = output_grad*gamma
+				let mut sum_grad = 0.0;
+				let mut sum_grad_x_hat = 0.0;
+				for channel in channels.clone() {
+					let mut channel_grad = 0.0;
+					let mut channel_grad_x_hat = 0.0;
+					Zip::from(&x.subview(axis, channel)).and(&output_grad.subview(axis, channel)).apply(|&x, &output_grad| {
+						channel_grad += output_grad;
+						channel_grad_x_hat += output_grad*(x - mean)*inv_std;
+					});
+					beta_grad_sum[channel] += channel_grad;
+					gamma_grad_sum[channel] += channel_grad_x_hat;
+					sum_grad += channel_grad*gamma[channel];
+					sum_grad_x_hat += channel_grad_x_hat*gamma[channel];
+				}
+
+				// dx = inv_std/n * (n*dx_hat - sum(dx_hat) - x_hat*sum(dx_hat*x_hat))
+				if let Some(ref mut input_grad) = input_grad {
+					let scale = inv_std/n;
+					for channel in channels {
+						let gamma = gamma[channel];
+						Zip::from(&mut input_grad.subview_mut(axis, channel)).and(&x.subview(axis, channel)).and(&output_grad.subview(axis, channel)).apply(|input_grad, &x, &output_grad| {
+							let x_hat = (x - mean)*inv_std;
+							*input_grad += scale*(n*output_grad*gamma - sum_grad - x_hat*sum_grad_x_hat);
+						});
+					}
+				}
+			}
+		}
+
+		if data.is_required(&self.gamma_id.gradient_id()) {
+			let mut gamma_grad = data.get_mut(&self.gamma_id.gradient_id())?;
+			for (gamma_grad, &sum) in gamma_grad.iter_mut().zip(&gamma_grad_sum) {
+				*gamma_grad += sum;
+			}
+		}
+
+		if data.is_required(&self.beta_id.gradient_id()) {
+			let mut beta_grad = data.get_mut(&self.beta_id.gradient_id())?;
+			for (beta_grad, &sum) in beta_grad.iter_mut().zip(&beta_grad_sum) {
+				*beta_grad += sum;
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_group_norm_backprop(){
+	_group_norm_backprop().unwrap();
+}
+
+fn _group_norm_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 8, 4, 4], "input", tag![])?;
+	let node2 = g.new_node(shape![2, 8, 4, 4], "output", tag![])?;
+	let node3 = g.new_node(shape![2, 8, 4, 4], "target", tag![])?;
+
+	// NCHW layout, with 2 channels per group
+	let _o1 = g.new_op(GroupNorm::new(&node1, &node2).axis(1).groups(4), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	assert!(g.new_op(GroupNorm::new(&node1, &node2).axis(1).groups(3), tag![]).is_err());
+	assert!(g.new_op(GroupNorm::new(&node1, &node2).axis(0).groups(2), tag![]).is_err());
+
+	// numeric_test draws random values for the input, gamma and beta, and checks the gradients of each
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.005;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
@@ -0,0 +1,394 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use init::Initialiser;
+use storage::Storage;
+use ops::{standard_op_name, standard_inner_parameter_name, Op, OpInstance, Pass};
+use shape::NodeDim;
+use ndarray::{ArrayView1, Axis};
+use std::any::Any;
+
+/// LayerNorm Op
+///
+/// Normalises each vector along the last axis of the input to zero mean and unit variance, using the statistics of that vector alone,
+/// then applies a learnable per-feature scale and shift, `output += gamma * (x - mean)/sqrt(var + epsilon) + beta`.
+/// Unlike batch normalisation the result for each example does not depend on the rest of the batch, so training and inference behave identically.
+///
+/// `gamma` and `beta` are `Parameter` nodes of shape `[features]`, initialised to 1 and 0 respectively unless other initialisers are supplied.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct LayerNorm {
+	input_id: NodeID,
+	output_id: NodeID,
+	epsilon: f32,
+	gamma_initialiser: Option<Initialiser>,
+	beta_initialiser: Option<Initialiser>,
+	name: Option<String>,
+}
+
+impl LayerNorm {
+	pub fn new(input: &NodeID, output: &NodeID) -> Self {
+		LayerNorm {
+			input_id: input.clone(),
+			output_id: output.clone(),
+			epsilon: 1e-5,
+			gamma_initialiser: None,
+			beta_initialiser: None,
+			name: None,
+		}
+	}
+
+	/// Added to the variance before the square root is taken.
+	///
+	/// Default: 1e-5
+	pub fn epsilon(mut self, epsilon: f32) -> Self {
+		self.epsilon = epsilon;
+		self
+	}
+
+	/// Provide an Initialiser for the scale parameter, `gamma`
+	///
+	/// Default: `Initialiser::fill(1.0)`
+	pub fn gamma_init(mut self, initialiser: Initialiser) -> Self {
+		self.gamma_initialiser = Some(initialiser);
+		self
+	}
+
+	/// Provide an Initialiser for the shift parameter, `beta`
+	///
+	/// Default: `Initialiser::fill(0.0)`
+	pub fn beta_init(mut self, initialiser: Initialiser) -> Self {
+		self.beta_initialiser = Some(initialiser);
+		self
+	}
+}
+
+impl Op for LayerNorm {
+	type InstanceType = LayerNormInstance;
+
+	fn type_name(&self) -> &'static str {
+		"LayerNorm"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.epsilon >= 0.0, format!("LayerNorm epsilon must not be negative, found: {}", self.epsilon));
+
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]);
+
+		let features = {
+			let input_shape = self.input_id.shape();
+			ensure!(input_shape.ndim() > 0, "LayerNorm input must have at least 1 axis");
+			match input_shape.dimensions()[input_shape.ndim() - 1] {
+				NodeDim::Known(dim) => dim,
+				_ => bail!("LayerNorm feature axis, the last axis of the input, must have a Known size"),
+			}
+		};
+
+		let gamma_name = standard_inner_parameter_name(&name, graph);
+		let gamma_id = graph.new_node(shape![features], gamma_name, tag![Parameter])?;
+		graph.set_initialiser(&gamma_id, self.gamma_initialiser.unwrap_or_else(|| Initialiser::fill(1.0)));
+
+		let beta_name = standard_inner_parameter_name(&name, graph);
+		let beta_id = graph.new_node(shape![features], beta_name, tag![Parameter])?;
+		graph.set_initialiser(&beta_id, self.beta_initialiser.unwrap_or_else(|| Initialiser::fill(0.0)));
+
+		Ok(LayerNormInstance{
+			name: name,
+			input_id: self.input_id.clone(),
+			output_id: self.output_id.clone(),
+			gamma_id: gamma_id.clone(),
+			beta_id: beta_id.clone(),
+			epsilon: self.epsilon,
+			forward_id: graph.add_pass(LayerNormForward::new(
+				self.input_id.clone(),
+				gamma_id.clone(),
+				beta_id.clone(),
+				self.output_id.clone(),
+				self.epsilon)),
+			backward_id: graph.add_pass(LayerNormBackward::new(
+				self.input_id.clone(),
+				gamma_id,
+				beta_id,
+				self.output_id.clone(),
+				self.epsilon)),
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)]
+pub struct LayerNormInstance{
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	gamma_id: NodeID,
+	beta_id: NodeID,
+	epsilon: f32,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl LayerNormInstance {
+	/// Returns the scale parameter node
+	pub fn gamma(&self) -> &NodeID {
+		&self.gamma_id
+	}
+
+	/// Returns the shift parameter node
+	pub fn beta(&self) -> &NodeID {
+		&self.beta_id
+	}
+}
+
+impl OpInstance for LayerNormInstance {
+
+	fn name(&self) -> &str{&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){(vec![self.input_id.clone()], vec![self.output_id.clone()])}
+
+	fn inner_passes(&self) -> Vec<PassID>{vec![self.forward_id.clone(), self.backward_id.clone()]}
+
+	fn inner_ops(&self) -> Vec<OpID>{vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID>{vec![self.gamma_id.clone(), self.beta_id.clone()]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+		let input_shape = shapes.get_shape(&self.input_id).clone();
+		shapes.merge_with(&self.output_id, &input_shape)
+	}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("epsilon", format!("{:?}", self.epsilon))]
+	}
+}
+
+/// Returns the mean and 1/sqrt(var + epsilon) of a vector
+fn moments(x: &ArrayView1<f32>, epsilon: f32) -> (f32, f32) {
+	let n = x.len() as f32;
+	let mean = x.scalar_sum()/n;
+	let var = x.iter().fold(0.0, |acc, &x| acc + (x - mean)*(x - mean))/n;
+	(mean, 1.0/(var + epsilon).sqrt())
+}
+
+
+#[derive(Clone, Debug)]
+struct LayerNormForward {
+	input_id: NodeID,
+	gamma_id: NodeID,
+	beta_id: NodeID,
+	output_id: NodeID,
+	epsilon: f32,
+}
+
+impl LayerNormForward {
+	pub fn new(input_id: NodeID, gamma_id: NodeID, beta_id: NodeID, output_id: NodeID, epsilon: f32) -> Self {
+		LayerNormForward {
+			input_id,
+			gamma_id,
+			beta_id,
+			output_id,
+			epsilon,
+		}
+	}
+}
+
+impl Pass for LayerNormForward {
+	fn type_name(&self) -> &'static str {"LayerNormForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.input_id.value_id(), self.gamma_id.value_id(), self.beta_id.value_id()],
+			vec![self.output_id.value_id()]
+		)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let input = data.get(&self.input_id.value_id())?;
+		let gamma = data.get(&self.gamma_id.value_id())?;
+		let beta = data.get(&self.beta_id.value_id())?;
+		let mut output = data.get_mut(&self.output_id.value_id())?;
+
+		ensure!(
+			input.shape() == output.shape(),
+			ErrorKind::PassError(self.name(), format!("input shape: {:?} did not match output shape: {:?}", input.shape(), output.shape()))
+		);
+		let axis = Axis(input.ndim() - 1);
+		ensure!(
+			gamma.len() == input.len_of(axis) && beta.len() == input.len_of(axis),
+			ErrorKind::PassError(self.name(), format!("gamma shape: {:?} and beta shape: {:?} did not match the last axis of input shape: {:?}", gamma.shape(), beta.shape(), input.shape()))
+		);
+
+		for (x, mut output) in input.lanes(axis).into_iter().zip(output.lanes_mut(axis)) {
+			let (mean, inv_std) = moments(&x, self.epsilon);
+			for (((output, &x), &gamma), &beta) in output.iter_mut().zip(x.iter()).zip(gamma.iter()).zip(beta.iter()) {
+				*output += gamma*(x - mean)*inv_std + beta;
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Clone, Debug)]
+struct LayerNormBackward {
+	input_id: NodeID,
+	gamma_id: NodeID,
+	beta_id: NodeID,
+	output_id: NodeID,
+	epsilon: f32,
+}
+
+impl LayerNormBackward {
+	pub fn new(input_id: NodeID, gamma_id: NodeID, beta_id: NodeID, output_id: NodeID, epsilon: f32) -> Self {
+		LayerNormBackward {
+			input_id,
+			gamma_id,
+			beta_id,
+			output_id,
+			epsilon,
+		}
+	}
+}
+
+impl Pass for LayerNormBackward {
+	fn type_name(&self) -> &'static str {"LayerNormBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.input_id.value_id(), self.gamma_id.value_id(), self.output_id.gradient_id()],
+			vec![self.input_id.gradient_id(), self.gamma_id.gradient_id(), self.beta_id.gradient_id()]
+		)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let input = data.get(&self.input_id.value_id())?;
+		let gamma = data.get(&self.gamma_id.value_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+
+		ensure!(
+			input.shape() == output_grad.shape(),
+			ErrorKind::PassError(self.name(), format!("input shape: {:?} did not match output shape: {:?}", input.shape(), output_grad.shape()))
+		);
+		let axis = Axis(input.ndim() - 1);
+		let n = input.len_of(axis);
+		ensure!(
+			gamma.len() == n,
+			ErrorKind::PassError(self.name(), format!("gamma shape: {:?} did not match the last axis of input shape: {:?}", gamma.shape(), input.shape()))
+		);
+
+		let gamma: Vec<f32> = gamma.iter().cloned().collect();
+		let mut input_grad = if data.is_required(&self.input_id.gradient_id()) {Some(data.get_mut(&self.input_id.gradient_id())?)} else {None};
+		let mut gamma_grad = if data.is_required(&self.gamma_id.gradient_id()) {Some(data.get_mut(&self.gamma_id.gradient_id())?)} else {None};
+		let mut beta_grad = if data.is_required(&self.beta_id.gradient_id()) {Some(data.get_mut(&self.beta_id.gradient_id())?)} else {None};
+
+		let mut input_grad_lanes = input_grad.as_mut().map(|input_grad| input_grad.lanes_mut(axis).into_iter());
+		let mut x_hat = vec![0.0; n];
+		let mut x_hat_grad = vec![0.0; n];
+		for (x, output_grad) in input.lanes(axis).into_iter().zip(output_grad.lanes(axis)) {
+			let (mean, inv_std) = moments(&x, self.epsilon);
+			for (j, (&x, &output_grad)) in x.iter().zip(output_grad.iter()).enumerate() {
+				x_hat[j] = (x - mean)*inv_std;
+				x_hat_grad[j] = output_grad*gamma[j];
+			}
+
+			if let Some(ref mut gamma_grad) = gamma_grad {
+				for ((gamma_grad, &x_hat), &output_grad) in gamma_grad.iter_mut().zip(&x_hat).zip(output_grad.iter()) {
+					*gamma_grad += output_grad*x_hat;
+				}
+			}
+
+			if let Some(ref mut beta_grad) = beta_grad {
+				for (beta_grad, &output_grad) in beta_grad.iter_mut().zip(output_grad.iter()) {
+					*beta_grad += output_grad;
+				}
+			}
+
+			// dx = inv_std/n * (n*dx_hat - sum(dx_hat) - x_hat*sum(dx_hat*x_hat))
+			if let Some(ref mut input_grad_lanes) = input_grad_lanes {
+				let sum_grad: f32 = x_hat_grad.iter().sum();
+				let sum_grad_x_hat: f32 = x_hat_grad.iter().zip(&x_hat).map(|(g, x)| g*x).sum();
+				let scale = inv_std/n as f32;
+
+				let mut input_grad = input_grad_lanes.next().unwrap();
+				for ((input_grad, &g), &x_hat) in input_grad.iter_mut().zip(&x_hat_grad).zip(&x_hat) {
+					*input_grad += scale*(n as f32*g - sum_grad - x_hat*sum_grad_x_hat);
+				}
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_layer_norm_backprop(){
+	_layer_norm_backprop().unwrap();
+}
+
+fn _layer_norm_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![4, 32], "input", tag![])?;
+	let node2 = g.new_node(shape![4, 32], "output", tag![])?;
+	let node3 = g.new_node(shape![4, 32], "target", tag![])?;
+
+	let _o1 = g.new_op(LayerNorm::new(&node1, &node2), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	// numeric_test draws random values for the input, gamma and beta, and checks the gradients of each
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.005;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_layer_norm_value(){
+	_layer_norm_value().unwrap();
+}
+
+fn _layer_norm_value() -> Result<()>{
+	use graph::GraphDef;
+	use ndarray::{ArrayD, Ix2};
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![Unknown, 32], "input", tag![])?;
+	let output = g.new_node(shape![Unknown, 32], "output", tag![])?;
+
+	let o1 = g.new_op(LayerNorm::new(&input, &output), tag![])?;
+	let params = g.initialise_nodes(&o1.instance().inner_nodes())?;
+	assert_eq!(params[0].shape(), &[32]);
+	assert!(params[0].iter().all(|&x| x == 1.0));
+	assert!(params[1].iter().all(|&x| x == 0.0));
+
+	// rows with very different scales and offsets are each normalised independently
+	let input_val = ArrayD::from_shape_fn(&[3, 32][..], |i| (i[0] as f32 + 1.0) * 10.0 * ((i[1] as f32).sin() + i[0] as f32));
+
+	let mut subgraph = g.subgraph(&[input.value_id()].iter().cloned().chain(o1.instance().inner_nodes().iter().map(|node_id| node_id.value_id())).collect::<Vec<_>>(), &[output.value_id()])?;
+	let storage = subgraph.execute(vec![input_val, params[0].clone(), params[1].clone()])?;
+	let output_val = storage.get(&output.value_id())?.into_dimensionality::<Ix2>().unwrap();
+
+	for row in output_val.outer_iter() {
+		let mean = row.scalar_sum()/32.0;
+		let var = row.iter().fold(0.0, |acc, &x| acc + (x - mean)*(x - mean))/32.0;
+		assert!(mean.abs() < 1e-4, "{:?}", row);
+		assert!((var - 1.0).abs() < 1e-3, "{:?}", row);
+	}
+
+	Ok(())
+}
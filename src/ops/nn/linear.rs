@@ -1,28 +1,33 @@
 use graph::{GraphDef, GraphShapes, Result};
 use id::{NodeID, OpID, PassID};
 use init::Initialiser;
-use ops::{standard_op_name, standard_inner_parameter_name, Op, OpInstance};
+use ops::{standard_op_name, standard_inner_parameter_name, standard_inner_node_name, Op, OpInstance};
 use shape::{NodeShape, NodeDim};
 use ops::math::matmul::{MatMul, MatMulInstance};
-use rand::{thread_rng, Isaac64Rng, SeedableRng};
+use ops::activ::elementwise::{ActivationFunc, ElementwiseFunc};
+use ops::activ::fused::FusedElementwise;
+use rng::new_rng;
 use rand::distributions::{Distribution, Normal};
 use ndarray::ArrayViewMutD;
+use std::sync::Arc;
 
 /// The Linear portion of a fully connected layer
 ///
 /// Creates an Op which implements the differentiable matrix multiplication component of typical neural nets.
 /// Calculates C += A B, where B is a weights matrix, A is the input node, and C is the output node.
-/// Does not include bias.
+/// Does not include bias, but can apply an activation function to the product, see `activation()`.
 #[must_use]
 #[derive(Clone, Debug)]
 pub struct Linear {
 	input_id: NodeID,
 	output_id: NodeID,
 	weights_id: Option<NodeID>,
+	transpose_weights: bool,
 	k: Option<usize>,
 	n: Option<usize>,
 	name: Option<String>,
 	initialiser: Option<Initialiser>,
+	activation: Option<Arc<ElementwiseFunc>>,
 }
 
 impl Linear {
@@ -32,10 +37,12 @@ impl Linear {
 			input_id: input.clone(),
 			output_id: output.clone(),
 			weights_id: None,
+			transpose_weights: false,
 			k: None,
 			n: None,
 			name: None,
 			initialiser: None,
+			activation: None,
 		}
 	}
 
@@ -68,6 +75,32 @@ impl Linear {
 		self
 	}
 
+	/// Store the weights matrix as `[n, k]`, and calculate C += A B^T
+	///
+	/// Combined with `weights()` this ties the weights of two ops, e.g. a decoder can reuse the weights of an encoder:
+	/// `Linear::new(&hidden, &output).weights(Some(encoder.weights())).transpose_weights(true)`.
+	/// Both ops then read the same `Parameter` node, so it appears once in the optimiser's parameters and its gradient accumulates contributions from each op.
+	///
+	/// Default value: `false`
+	pub fn transpose_weights(mut self, transpose: bool) -> Self {
+		self.transpose_weights = transpose;
+		self
+	}
+
+	/// Apply an activation function to the product, calculating C += f(A B)
+	///
+	/// The product is stored in an inner node and the activation is applied by an inner op,
+	/// so the hidden node and activation op otherwise needed are not added by the user,
+	/// e.g. `Linear::new(&input, &output).activation(ReLUFunc{})`.
+	/// This is a convenience only: the inner node holds the full product, so no memory or computation is saved compared to separate ops.
+	///
+	/// Default value: `None`, the identity
+	pub fn activation<F: ActivationFunc>(mut self, func: F) -> Self {
+		let func: Arc<ElementwiseFunc> = Arc::new(func);
+		self.activation = Some(func);
+		self
+	}
+
 	/// Provide an Initialiser for the weights node
 	pub fn init(mut self, initialiser: Initialiser) -> Self {
 		self.initialiser = Some(initialiser);
@@ -88,7 +121,7 @@ impl Linear {
 				.and_then(|matmul_instance| matmul_instance.K)
 				.unwrap_or(arr.shape()[0]); //TODO use ensure to guard against zero length shapes
 
-			let mut rng = Isaac64Rng::from_rng(thread_rng()).unwrap();
+			let mut rng = new_rng();
 			let norm = Normal::new(0.0, (multiplier as f64 / k as f64).sqrt());
 			for e in arr.iter_mut() {
 				*e = norm.sample(&mut rng) as f32;
@@ -148,18 +181,35 @@ impl Op for Linear {
 			if k.is_none() {k = Some(get_inner(self.input_id.shape()));}
 
 			let weights_name = standard_inner_parameter_name(&name, graph);
-			graph.new_node(shape![k.unwrap(), n.unwrap()], weights_name, tag![Parameter])?
+			if self.transpose_weights {
+				graph.new_node(shape![n.unwrap(), k.unwrap()], weights_name, tag![Parameter])?
+			} else {
+				graph.new_node(shape![k.unwrap(), n.unwrap()], weights_name, tag![Parameter])?
+			}
 		};
 
 		if let Some(initialiser) = self.initialiser {
 			graph.set_initialiser(&weights, initialiser);
 		}
 
-		let mut mat_mul = MatMul::new(&self.input_id.clone(), &weights, &self.output_id.clone());
+		// with an activation, the matmul writes to an inner node which the activation reads
+		let product_id = if self.activation.is_some() {
+			let product_name = standard_inner_node_name(&name, graph);
+			Some(graph.new_node(self.output_id.shape().clone(), product_name, tag![])?)
+		} else {
+			None
+		};
+
+		let mut mat_mul = MatMul::new(&self.input_id.clone(), &weights, product_id.as_ref().unwrap_or(&self.output_id)).b_trans(self.transpose_weights);
 		if let Some(n) = self.n {mat_mul = mat_mul.n(n)}
 		if let Some(k) = self.k {mat_mul = mat_mul.k(k)}
 		let matmul_id = graph.new_op(mat_mul, tag![])?;
 
+		let activation_id = match (&product_id, self.activation.clone()) {
+			(&Some(ref product_id), Some(activation)) => Some(graph.new_op(FusedElementwise::new(product_id, &self.output_id, vec![activation]), tag![])?),
+			_ => None,
+		};
+
 
 		Ok(LinearInstance{
 			name: name,
@@ -167,7 +217,13 @@ impl Op for Linear {
 			output_id: self.output_id,
 			weights_id: weights,
 			weights_are_inner: weights_are_inner,
+			transpose_weights: self.transpose_weights,
+			k: self.k,
+			n: self.n,
+			activation: self.activation,
 			matmul_id: matmul_id,
+			product_id: product_id,
+			activation_id: activation_id,
 		})
 	}
 }
@@ -181,7 +237,20 @@ pub struct LinearInstance{
 	output_id: NodeID,
 	weights_id: NodeID,
 	weights_are_inner: bool,
+	transpose_weights: bool,
+	k: Option<usize>,
+	n: Option<usize>,
+	activation: Option<Arc<ElementwiseFunc>>,
 	matmul_id: OpID,
+	product_id: Option<NodeID>,
+	activation_id: Option<OpID>,
+}
+
+impl LinearInstance {
+	/// Returns the node holding the weights matrix, which can be supplied to another op to share it
+	pub fn weights(&self) -> &NodeID {
+		&self.weights_id
+	}
 }
 
 impl OpInstance for LinearInstance {
@@ -201,17 +270,32 @@ impl OpInstance for LinearInstance {
 
 	fn inner_passes(&self) -> Vec<PassID>{vec![]}
 
-	fn inner_ops(&self) -> Vec<OpID>{vec![self.matmul_id.clone()]}
+	fn inner_ops(&self) -> Vec<OpID>{
+		let mut ops = vec![self.matmul_id.clone()];
+		ops.extend(self.activation_id.iter().cloned());
+		ops
+	}
 
 	fn inner_nodes(&self) -> Vec<NodeID>{
-		if self.weights_are_inner {
+		let mut nodes = if self.weights_are_inner {
 			vec![self.weights_id.clone()]
 		} else {
 			vec![]
-		}
+		};
+		nodes.extend(self.product_id.iter().cloned());
+		nodes
 	}
 
 	fn propagate_shape_constraints(&self, _shapes: &mut GraphShapes) -> Result<()>{Ok(())}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![
+			("transpose_weights", format!("{:?}", self.transpose_weights)),
+			("k", format!("{:?}", self.k)),
+			("n", format!("{:?}", self.n)),
+			("activation", format!("{:?}", self.activation)),
+		]
+	}
 }
 
 
@@ -249,4 +333,174 @@ fn _linear_init_backprop() -> Result<()>{
 	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
 
 	Ok(())
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_linear_tied_backprop(){
+	_linear_tied_backprop().unwrap();
+}
+
+fn _linear_tied_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5], "input", tag![])?;
+	let node2 = g.new_node(shape![7, 3], "hidden", tag![])?;
+	let node3 = g.new_node(shape![7, 5], "output", tag![])?;
+	let node4 = g.new_node(shape![7, 3], "hidden_target", tag![])?;
+
+	let o1 = g.new_op(Linear::new(&node1, &node2).init(Linear::msra(1.0)), tag![])?;
+	let weights = o1.instance().as_any().downcast_ref::<LinearInstance>().unwrap().weights().clone();
+	let _o2 = g.new_op(Linear::new(&node2, &node3).weights(Some(&weights)).transpose_weights(true), tag![])?;
+
+	// gradients reach the shared weights through both ops
+	let _o3 = g.new_op(Mse::new(&node3, &node1), tag![])?;
+	let _o4 = g.new_op(Mse::new(&node2, &node4), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.001;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_linear_tied_weights(){
+	_linear_tied_weights().unwrap();
+}
+
+fn _linear_tied_weights() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::mse::Mse;
+	use opt::Opt;
+	use opt::sgd::Sgd;
+	use ndarray::ArrayD;
+
+	let build = |tied: bool| -> Result<(GraphDef, NodeID, NodeID)> {
+		let mut g = GraphDef::new();
+
+		let input = g.new_node(shape![7, 5], "input", tag![])?;
+		let hidden = g.new_node(shape![7, 3], "hidden", tag![])?;
+		let output = g.new_node(shape![7, 5], "output", tag![])?;
+
+		let o1 = g.new_op(Linear::new(&input, &hidden).init(Linear::msra(1.0)), tag![])?;
+		if tied {
+			let weights = o1.instance().as_any().downcast_ref::<LinearInstance>().unwrap().weights().clone();
+			let _o2 = g.new_op(Linear::new(&hidden, &output).weights(Some(&weights)).transpose_weights(true), tag![])?;
+		} else {
+			let _o2 = g.new_op(Linear::new(&hidden, &output).init(Linear::msra(1.0)), tag![])?;
+		}
+		let _o3 = g.new_op(Mse::new(&output, &input), tag![])?;
+		Ok((g, input, hidden))
+	};
+
+	let (untied, _, _) = build(false)?;
+	let (g, input, hidden) = build(true)?;
+	assert_eq!(untied.num_params()?, 30);
+	assert_eq!(g.num_params()?, 15);
+
+	let mut opt = Sgd::new(&g)?.rate(0.01);
+	assert_eq!(opt.parameters().len(), 1);
+	let params = g.initialise_nodes(opt.parameters())?;
+	let input_val = ArrayD::from_shape_fn(&[7, 5][..], |i| (i[0] * 5 + i[1]) as f32 * 0.1 - 1.0);
+
+	let mut encoder = g.subgraph(&[input.value_id(), opt.parameters()[0].value_id()], &[hidden.value_id()])?;
+	let storage = encoder.execute(vec![input_val.clone(), params[0].clone()])?;
+	let hidden_before = storage.get(&hidden.value_id())?.to_owned();
+
+	// the loss is only on the decoder output, but the update changes what the encoder computes
+	let (_err, _step, _change_norm, new_params) = opt.step(vec![input_val.clone()], params)?;
+	let storage = encoder.execute(vec![input_val, new_params[0].clone()])?;
+	let hidden_after = storage.get(&hidden.value_id())?.to_owned();
+	assert_ne!(hidden_before, hidden_after);
+
+	Ok(())
+}
+
+#[test]
+fn test_linear_activation_backprop(){
+	_linear_activation_backprop().unwrap();
+}
+
+fn _linear_activation_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+	use ops::activ::relu::ReLUFunc;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 5], "input", tag![])?;
+	let node2 = g.new_node(shape![7, 16], "output", tag![])?;
+	let node3 = g.new_node(shape![7, 16], "target", tag![])?;
+
+	let _o1 = g.new_op(Linear::new(&node1, &node2).init(Linear::msra(2.0)).activation(ReLUFunc{}), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_linear_activation_matches_separate_ops(){
+	_linear_activation_matches_separate_ops().unwrap();
+}
+
+fn _linear_activation_matches_separate_ops() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::generate_input_data;
+	use ops::loss::mse::Mse;
+	use ops::activ::relu::{ReLU, ReLUFunc};
+
+	let build = |fused: bool| -> Result<(GraphDef, NodeID, NodeID, NodeID, NodeID)> {
+		let mut g = GraphDef::new();
+
+		let input = g.new_node(shape![7, 5], "input", tag![])?;
+		let output = g.new_node(shape![7, 16], "output", tag![])?;
+		let target = g.new_node(shape![7, 16], "target", tag![])?;
+
+		let o1 = if fused {
+			g.new_op(Linear::new(&input, &output).activation(ReLUFunc{}), tag![])?
+		} else {
+			let hidden = g.new_node(shape![7, 16], "hidden", tag![])?;
+			let o1 = g.new_op(Linear::new(&input, &hidden), tag![])?;
+			let _o2 = g.new_op(ReLU::new(&hidden, &output), tag![])?;
+			o1
+		};
+		let _o3 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+		let weights = o1.instance().as_any().downcast_ref::<LinearInstance>().unwrap().weights().clone();
+		Ok((g, input, output, target, weights))
+	};
+
+	let (g1, input1, output1, target1, weights1) = build(true)?;
+	let (g2, input2, output2, target2, weights2) = build(false)?;
+	assert_eq!(g1.input_nodes(), vec![input1.clone(), target1.clone()]);
+
+	let data = generate_input_data(&[input1.clone(), target1.clone(), weights1.clone()], 1.0, &mut indexmap![])?;
+
+	let mut sg1 = g1.subgraph(&[input1.value_id(), target1.value_id(), weights1.value_id()], &[output1.value_id(), input1.gradient_id(), weights1.gradient_id()])?;
+	let mut sg2 = g2.subgraph(&[input2.value_id(), target2.value_id(), weights2.value_id()], &[output2.value_id(), input2.gradient_id(), weights2.gradient_id()])?;
+
+	let storage1 = sg1.execute(data.clone())?;
+	let storage2 = sg2.execute(data)?;
+
+	assert!((storage1.loss() - storage2.loss()).abs() <= 1e-5 * storage2.loss().abs());
+	assert!(storage1.get(&output1.value_id())?.all_close(&storage2.get(&output2.value_id())?, 1e-5));
+	assert!(storage1.get(&input1.gradient_id())?.all_close(&storage2.get(&input2.gradient_id())?, 1e-5));
+	assert!(storage1.get(&weights1.gradient_id())?.all_close(&storage2.get(&weights2.gradient_id())?, 1e-5));
+
+	Ok(())
+}
@@ -1,3 +1,9 @@
 pub mod bias;
 pub mod linear;
-pub mod conv;
\ No newline at end of file
+pub mod conv;
+pub mod embedding;
+pub mod affine;
+pub mod conv_transpose;
+pub mod layer_norm;
+pub mod group_norm;
+pub mod attention;
\ No newline at end of file
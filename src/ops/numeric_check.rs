@@ -1,6 +1,10 @@
-use graph::{GraphDef, Result, Dependencies};
+use graph::{GraphDef, Subgraph, Result, Dependencies};
 use id::{NodeID, DataID, NodeTag};
-use ndarray::ArrayD;
+use ops::Op;
+use ops::activ::elementwise::{ActivationFunc, ActivationFuncF64, Real};
+use ops::loss::mse::Mse;
+use shape::NodeShape;
+use ndarray::{ArrayD, Dimension};
 use rand::thread_rng;
 use rand::distributions::{Normal, Distribution};
 use indexmap::IndexMap;
@@ -85,6 +89,71 @@ pub fn numeric_test(iters: usize, failures: usize, tolerance: f32, graph: &Graph
 }
 
 
+/// Checks the gradients of an op which maps an input node to an output node of the same shape, such as an activation function.
+///
+/// `op_ctor` is called with the input and output nodes, both of the given `shape`, and an `Mse` loss is applied between the output and a target node.
+/// `numeric_test` is then run using 100 iterations allowing 1 failure, a step size of 1e-2, and inputs drawn from N(0, 1).
+///
+/// e.g. `check_elementwise_op(|i, o| Tanh::new(i, o), shape![7, 5, 16], 0.002)?;`
+pub fn check_elementwise_op<O: Op, F: FnOnce(&NodeID, &NodeID) -> O>(op_ctor: F, shape: NodeShape, tolerance: f32) -> Result<()> {
+	check_elementwise_op_variance(op_ctor, shape, tolerance, 1.0)
+}
+
+/// As for `check_elementwise_op`, but with inputs drawn from N(0, `default_variance`).
+pub fn check_elementwise_op_variance<O: Op, F: FnOnce(&NodeID, &NodeID) -> O>(op_ctor: F, shape: NodeShape, tolerance: f32, default_variance: f32) -> Result<()> {
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape.clone(), "input", tag![])?;
+	let output = g.new_node(shape.clone(), "output", tag![])?;
+	let target = g.new_node(shape, "target", tag![])?;
+
+	let _o1 = g.new_op(op_ctor(&input, &output), tag![])?;
+	let _o2 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let step_size = 1E-2;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])
+}
+
+/// Returns the largest relative error between the `gradient()` of an `ActivationFunc` and a central difference of its `value()`, evaluated in f32.
+///
+/// Rounding limits the accuracy of f32 differences to around 1e-4 at best, see `activation_func_error_f64()`.
+pub fn activation_func_error<F: ActivationFunc>(func: &F, inputs: &[f64], step_size: f64) -> f64 {
+	max_relative_error(&|x: f32| func.value(x), &|x: f32, output_grad: f32| func.gradient(x, output_grad), inputs, step_size)
+}
+
+/// Returns the largest relative error between the `gradient_f64()` of an `ActivationFuncF64` and a central difference of its `value_f64()`.
+pub fn activation_func_error_f64<F: ActivationFuncF64>(func: &F, inputs: &[f64], step_size: f64) -> f64 {
+	max_relative_error(&|x: f64| func.value_f64(x), &|x: f64, output_grad: f64| func.gradient_f64(x, output_grad), inputs, step_size)
+}
+
+/// Checks the gradient of an `ActivationFuncF64` in double precision, at 1000 inputs drawn from N(0, `default_variance`), using a step size of 1e-6.
+///
+/// Unlike `check_elementwise_op()` no graph is built, so the achievable tolerance is limited by f64 rather than f32 rounding.
+/// A discontinuity in the gradient within a step of an input will still cause a failure.
+pub fn check_activation_func_f64<F: ActivationFuncF64>(func: &F, tolerance: f64, default_variance: f32) -> Result<()> {
+	let mut inputs = vec![0.0; 1000];
+	normal_fill(&mut inputs, 0.0, default_variance);
+	let inputs: Vec<f64> = inputs.into_iter().map(|x| x as f64).collect();
+
+	let err = activation_func_error_f64(func, &inputs, 1e-6);
+	assert!(err <= tolerance, "f64 gradient error: {} exceeded tolerance: {}", err, tolerance);
+
+	Ok(())
+}
+
+fn max_relative_error<T: Real>(value: &Fn(T) -> T, gradient: &Fn(T, T) -> T, inputs: &[f64], step_size: f64) -> f64 {
+	let step = T::from_f64(step_size);
+	inputs.iter().map(|&x| {
+		let x = T::from_f64(x);
+		let numeric = ((value(x + step) - value(x - step))/(step + step)).to_f64();
+		let analytic = gradient(x, T::from_f64(1.0)).to_f64();
+		let scale = numeric.abs().max(analytic.abs());
+		if scale == 0.0 {0.0} else {(numeric - analytic).abs()/scale}
+	}).fold(0.0, f64::max)
+}
+
 /// Returns the relative error of the derivatives with respect to parameters and inputs
 ///
 /// (param_err, input_err)
@@ -142,4 +211,158 @@ pub fn numeric_error(graph: &GraphDef, step_size: f32, default_variance: f32, ov
 	
 	Ok((param_err, input_err))
 
-}
\ No newline at end of file
+}
+
+
+/// Estimates the Hessian-vector product of the loss, H·v, using a central difference of the gradients along v
+///
+/// Hv ≈ (∇L(x + εv) - ∇L(x - εv))/2ε
+///
+/// The subgraph inputs must be the values of `node_ids`, in order, and the subgraph outputs must include their gradients.
+/// `point` and `vector` supply one array per node, and one array per node is returned.
+pub fn numeric_hvp(subgraph: &mut Subgraph, node_ids: &[NodeID], point: &[ArrayD<f32>], vector: &[ArrayD<f32>], step_size: f32) -> Result<Vec<ArrayD<f32>>> {
+	ensure!(point.len() == node_ids.len() && vector.len() == node_ids.len(), "numeric_hvp requires one point array and one vector array per node");
+
+	let perturb = |sign: f32| -> Vec<ArrayD<f32>> {
+		point.iter().zip(vector).map(|(x, v)| {
+			let mut x = x.clone();
+			x.scaled_add(sign*step_size, v);
+			x
+		}).collect()
+	};
+
+	let output_1 = subgraph.execute(perturb(1.0))?.into_map();
+	let output_2 = subgraph.execute(perturb(-1.0))?.into_map();
+
+	node_ids.iter().map(|node_id| {
+		match (output_1.get(&node_id.gradient_id()), output_2.get(&node_id.gradient_id())) {
+			(Some(grad_1), Some(grad_2)) => Ok((grad_1 - grad_2)/(2.0*step_size)),
+			_ => bail!(format!("numeric_hvp requires the subgraph to output the gradient of node: {}", node_id)),
+		}
+	}).collect()
+}
+
+/// Checks the second order behaviour of the loss along `vector`, which supplies directions for some leaf nodes (inputs or parameters) of the graph.
+/// Leaf nodes missing from `vector` are held fixed, and all leaf nodes are drawn from N(0, 1).
+///
+/// No ops provide analytic second derivatives, so the Hessian-vector product is estimated by `numeric_hvp()` and checked for consistency:
+/// * the curvature along v, v·Hv, must match the second difference of the loss along v
+/// * the Hessian must be symmetric, u·Hv = v·Hu, for a random direction u
+///
+/// Both relative errors must not exceed `tolerance`.
+pub fn numeric_hvp_test(graph: &GraphDef, vector: &IndexMap<NodeID, ArrayD<f32>>, tolerance: f32) -> Result<()> {
+	let dependencies = Dependencies::new(&graph);
+	let node_ids: Vec<NodeID> = graph.get_nodes().iter().filter(|node_id| dependencies.data_inputs(&node_id.value_id()).len() == 0).cloned().collect();
+
+	for node_id in vector.keys() {
+		ensure!(node_ids.contains(node_id), format!("numeric_hvp_test vector contains node: {}, which is not a leaf node of the graph", node_id));
+	}
+
+	let mut v = vec![];
+	for node_id in &node_ids {
+		let shape = node_id.shape().to_data_shape()?;
+		match vector.get(node_id) {
+			Some(arr) => {
+				ensure!(arr.shape() == shape.slice(), format!("numeric_hvp_test vector shape: {:?} did not match shape: {:?} of node: {}", arr.shape(), shape.slice(), node_id));
+				v.push(arr.clone());
+			},
+			None => v.push(ArrayD::zeros(shape)),
+		}
+	}
+	let v_norm = dot(&v, &v).sqrt();
+	ensure!(v_norm > 0.0, "numeric_hvp_test vector must be non-zero");
+
+	let point = generate_input_data(&node_ids, 1.0, &mut indexmap![])?;
+	let u = generate_input_data(&node_ids, 1.0, &mut indexmap![])?;
+	let u_norm = dot(&u, &u).sqrt();
+
+	let mut subgraph = graph.subgraph(
+		&node_ids.iter().map(|node_id| node_id.value_id()).collect::<Vec<_>>(),
+		&node_ids.iter().map(|node_id| node_id.gradient_id()).collect::<Vec<_>>())?;
+
+	// gradients can be differenced over a short step, but the loss second difference needs a longer one to stay above rounding error
+	let gradient_step = 1E-2;
+	let loss_step = 1E-1;
+
+	let hv = numeric_hvp(&mut subgraph, &node_ids, &point, &v, gradient_step/v_norm)?;
+	let hu = numeric_hvp(&mut subgraph, &node_ids, &point, &u, gradient_step/u_norm)?;
+
+	let epsilon = loss_step/v_norm;
+	let mut losses = vec![];
+	for &sign in &[1.0, 0.0, -1.0] {
+		let data = point.iter().zip(&v).map(|(x, v)| {
+			let mut x = x.clone();
+			x.scaled_add(sign*epsilon, v);
+			x
+		}).collect();
+		losses.push(subgraph.execute(data)?.loss());
+	}
+	let second_difference = (losses[0] - 2.0*losses[1] + losses[2])/(epsilon*epsilon);
+
+	let curvature_err = relative_error(dot(&v, &hv), second_difference);
+	let symmetry_err = relative_error(dot(&u, &hv), dot(&v, &hu));
+
+	assert!(curvature_err <= tolerance, "curvature error: {} v·Hv: {} second difference: {}", curvature_err, dot(&v, &hv), second_difference);
+	assert!(symmetry_err <= tolerance, "symmetry error: {} u·Hv: {} v·Hu: {}", symmetry_err, dot(&u, &hv), dot(&v, &hu));
+
+	Ok(())
+}
+
+fn dot(a: &[ArrayD<f32>], b: &[ArrayD<f32>]) -> f32 {
+	a.iter().zip(b).map(|(a, b)| a.iter().zip(b.iter()).fold(0.0, |acc, (a, b)| acc + a*b)).sum()
+}
+
+fn relative_error(a: f32, b: f32) -> f32 {
+	let scale = a.abs().max(b.abs());
+	if scale == 0.0 {0.0} else {(a - b).abs()/scale}
+}
+
+
+#[test]
+fn test_numeric_hvp_quadratic(){
+	_numeric_hvp_quadratic().unwrap();
+}
+
+fn _numeric_hvp_quadratic() -> Result<()>{
+	let mut g = GraphDef::new();
+
+	let param = g.new_node(shape![2, 3], "param", tag![Parameter])?;
+	let target = g.new_node(shape![2, 3], "target", tag![])?;
+
+	// L = 1.5*sum((param - target)^2), so H = 3[I, -I; -I, I]
+	let _o1 = g.new_op(Mse::new(&param, &target).multiplier(1.5), tag![])?;
+
+	let v = generate_input_data(&[param.clone()], 1.0, &mut indexmap![])?.remove(0);
+	numeric_hvp_test(&g, &indexmap![param.clone() => v.clone()], 1e-3)?;
+
+	let mut subgraph = g.subgraph(&[param.value_id(), target.value_id()], &[param.gradient_id(), target.gradient_id()])?;
+	let point = generate_input_data(&[param.clone(), target.clone()], 1.0, &mut indexmap![])?;
+	let hv = numeric_hvp(&mut subgraph, &[param.clone(), target.clone()], &point, &[v.clone(), ArrayD::zeros(v.shape())], 1e-2)?;
+
+	assert!(hv[0].all_close(&(&v*3.0), 1e-3), "{:?}", hv[0]);
+	assert!(hv[1].all_close(&(&v*-3.0), 1e-3), "{:?}", hv[1]);
+
+	Ok(())
+}
+
+#[test]
+fn test_f64_activation_check(){
+	_f64_activation_check().unwrap();
+}
+
+fn _f64_activation_check() -> Result<()>{
+	use ops::activ::srgb::{SrgbToLinearFunc, LinearToSrgbFunc};
+
+	// inputs in the unit interval, away from the kinks where the linear segments join the curves
+	let inputs: Vec<f64> = (0..1000).map(|i| (i as f64 + 0.5)/1000.0).filter(|&x| (x - 0.0404482362771082).abs() > 1e-3 && (x - 0.00313066844250063).abs() > 1e-3).collect();
+
+	for &(err_f32, err_f64) in &[
+		(activation_func_error(&SrgbToLinearFunc{}, &inputs, 1e-3), activation_func_error_f64(&SrgbToLinearFunc{}, &inputs, 1e-6)),
+		(activation_func_error(&LinearToSrgbFunc{}, &inputs, 1e-3), activation_func_error_f64(&LinearToSrgbFunc{}, &inputs, 1e-6)),
+	] {
+		assert!(err_f64 < 1e-7, "f32 error: {} f64 error: {}", err_f32, err_f64);
+		assert!(err_f64 * 100.0 < err_f32, "f32 error: {} f64 error: {}", err_f32, err_f64);
+	}
+
+	Ok(())
+}
@@ -0,0 +1,324 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use shape::NodeShape;
+use ndarray::{ArrayView1, Axis, Dimension};
+use std::any::Any;
+use std::f32;
+use smallvec::SmallVec;
+
+
+/// LogSumExp
+///
+/// Calculates `ln(sum(exp(x)))` along an axis, subtracting the maximum of each lane before exponentiating so that large inputs don't overflow.
+/// The gradient is the softmax of the lane multiplied by the output gradient.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct LogSumExp {
+	name: Option<String>,
+	input_id: NodeID,
+	output_id: NodeID,
+	axis: isize,
+	keep_dims: bool,
+}
+
+impl LogSumExp {
+
+	pub fn new(input_id: &NodeID, output_id: &NodeID) -> Self{
+		LogSumExp {
+			name: None,
+			input_id: input_id.clone(),
+			output_id: output_id.clone(),
+			axis: -1,
+			keep_dims: false,
+		}
+	}
+
+	/// The axis which is reduced across.
+	///
+	/// Can be in the range [-input.ndims(), input.ndims()).
+	/// Default: -1
+	pub fn axis(mut self, axis: isize) -> Self {
+		self.axis = axis;
+		self
+	}
+
+	/// If `true` the reduced axis still appears in the output with size 1, otherwise it is removed.
+	///
+	/// Default: `false`
+	pub fn keep_dims(mut self, keep_dims: bool) -> Self {
+		self.keep_dims = keep_dims;
+		self
+	}
+}
+
+impl Op for LogSumExp {
+	type InstanceType = LogSumExpInstance;
+
+	fn type_name(&self) -> &'static str {
+		"LogSumExp"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let ndim = self.input_id.shape().ndim();
+		ensure!(self.axis >= -(ndim as isize) && self.axis < ndim as isize, format!("LogSumExp axis {} is out of range for input with {} axes", self.axis, ndim));
+		let axis = (self.axis + ndim as isize) as usize % ndim;
+
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]);
+
+		Ok(LogSumExpInstance{
+			name: name,
+			input_id: self.input_id.clone(),
+			output_id: self.output_id.clone(),
+			axis: axis,
+			keep_dims: self.keep_dims,
+			forward_id:graph.add_pass(LogSumExpForward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				axis,
+				self.keep_dims,
+			)),
+			backward_id:graph.add_pass(LogSumExpBackward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				axis,
+				self.keep_dims,
+			)),
+		})
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct LogSumExpInstance {
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	axis: usize,
+	keep_dims: bool,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl LogSumExpInstance {
+	/// The axis which is reduced across, in the range [0, input.ndims())
+	pub fn axis(&self) -> usize {
+		self.axis
+	}
+}
+
+impl OpInstance for LogSumExpInstance {
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(
+			vec![self.input_id.clone()],
+			vec![self.output_id.clone()]
+		)
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.forward_id.clone(), self.backward_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+
+		let input_shape = shapes.get_shape(&self.input_id).to_data_shape()?;
+
+		let output_shape: NodeShape = calc_output_shape(input_shape.slice(), self.axis, self.keep_dims).into();
+
+		shapes.merge_with(&self.output_id, &output_shape)?;
+		Ok(())
+	}
+}
+
+fn calc_output_shape(input_shape: &[usize], axis: usize, keep_dims: bool) -> SmallVec<[usize; 6]> {
+	if keep_dims {
+		input_shape.iter().enumerate().map(|(i, &dim)| if i == axis {1} else {dim}).collect()
+	} else {
+		input_shape.iter().enumerate().filter_map(|(i, &dim)| if i == axis {None} else {Some(dim)}).collect()
+	}
+}
+
+fn check_shapes(pass_name: String, input_shape: &[usize], output_shape: &[usize], axis: usize, keep_dims: bool) -> Result<()> {
+	ensure!(axis < input_shape.len(), ErrorKind::PassError(pass_name.clone(), format!("axis {} is out of range for input shape: {:?}", axis, input_shape)));
+	let output_shape_actual = calc_output_shape(input_shape, axis, keep_dims);
+	ensure!(output_shape_actual.as_slice() == output_shape,
+		ErrorKind::PassError(pass_name, format!("Output shape {:?} does not match reduced input shape {:?}", output_shape, output_shape_actual.as_slice())));
+	Ok(())
+}
+
+/// Returns `ln(sum(exp(x)))` of a lane, using the maximum for stability
+fn log_sum_exp(lane: &ArrayView1<f32>) -> f32 {
+	let max = lane.iter().fold(f32::NEG_INFINITY, |max, &x| max.max(x));
+	if max == f32::NEG_INFINITY {
+		return max;
+	}
+	max + lane.iter().fold(0.0, |sum, &x| sum + (x - max).exp()).ln()
+}
+
+
+#[derive(Debug, Clone)]
+pub struct LogSumExpForward {
+	input_id: NodeID,
+	output_id: NodeID,
+	axis: usize,
+	keep_dims: bool,
+}
+
+impl LogSumExpForward {
+	pub fn new(input_id: NodeID, output_id: NodeID, axis: usize, keep_dims: bool) -> Self{
+		LogSumExpForward {
+			input_id,
+			output_id,
+			axis,
+			keep_dims,
+		}
+	}
+}
+
+impl Pass for LogSumExpForward {
+	fn type_name(&self) -> &'static str {"LogSumExpForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id()],
+		vec![self.output_id.value_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let mut output = data.get_mut(&self.output_id.value_id())?;
+
+		check_shapes(self.name(), input.shape(), output.shape(), self.axis, self.keep_dims)?;
+
+		for (lane, output) in input.lanes(Axis(self.axis)).into_iter().zip(output.iter_mut()) {
+			*output += log_sum_exp(&lane);
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct LogSumExpBackward {
+	input_id: NodeID,
+	output_id: NodeID,
+	axis: usize,
+	keep_dims: bool,
+}
+
+impl LogSumExpBackward {
+	pub fn new(input_id: NodeID, output_id: NodeID, axis: usize, keep_dims: bool) -> Self{
+		LogSumExpBackward {
+			input_id,
+			output_id,
+			axis,
+			keep_dims,
+		}
+	}
+}
+
+impl Pass for LogSumExpBackward {
+	fn type_name(&self) -> &'static str {"LogSumExpBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id(), self.output_id.gradient_id()],
+		vec![self.input_id.gradient_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let mut input_grad = data.get_mut(&self.input_id.gradient_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+
+		check_shapes(self.name(), input.shape(), output_grad.shape(), self.axis, self.keep_dims)?;
+
+		let iter = input.lanes(Axis(self.axis)).into_iter()
+			.zip(input_grad.lanes_mut(Axis(self.axis)))
+			.zip(output_grad.iter());
+		for ((lane, mut lane_grad), &output_grad) in iter {
+			let lse = log_sum_exp(&lane);
+			if lse == f32::NEG_INFINITY {
+				continue;
+			}
+			// grad_in = softmax(x) * grad_out
+			for (ig, &x) in lane_grad.iter_mut().zip(lane.iter()) {
+				*ig += (x - lse).exp() * output_grad;
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_log_sum_exp_backprop(){
+	_log_sum_exp_backprop().unwrap();
+}
+
+fn _log_sum_exp_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![3, 10], "input", tag![])?;
+	let node2 = g.new_node(shape![3], "output", tag![])?;
+	let node3 = g.new_node(shape![3], "target", tag![])?;
+
+	let _o1 = g.new_op(LogSumExp::new(&node1, &node2).axis(-1), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_log_sum_exp_value(){
+	_log_sum_exp_value().unwrap();
+}
+
+fn _log_sum_exp_value() -> Result<()>{
+	use graph::GraphDef;
+	use ndarray::arr2;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 4], "input", tag![])?;
+	let node2 = g.new_node(shape![2, 1], "output", tag![])?;
+
+	let _o1 = g.new_op(LogSumExp::new(&node1, &node2).axis(1).keep_dims(true), tag![])?;
+
+	let input = [[0.5f32, -1.25, 2.0, 0.0], [100.0, 101.0, 99.5, -50.0]];
+
+	let mut subgraph = g.subgraph(&[node1.value_id()], &[node2.value_id()])?;
+	let storage = subgraph.execute(vec![arr2(&input).into_dyn()])?;
+	let output = storage.get(&node2.value_id())?;
+
+	// the second row would overflow f32 without subtracting the maximum
+	for (row, &out) in input.iter().zip(output.iter()) {
+		let expected = row.iter().fold(0.0f64, |sum, &x| sum + (x as f64).exp()).ln();
+		assert!(out.is_finite());
+		assert!((out as f64 - expected).abs() < 1e-5 * expected.abs().max(1.0), "{} {}", out, expected);
+	}
+
+	Ok(())
+}
@@ -1,2 +1,6 @@
 pub mod reduce_sum;
-pub mod reduce_mean;
\ No newline at end of file
+pub mod reduce_mean;
+pub mod reduce_max;
+pub mod reduce;
+pub mod variance;
+pub mod log_sum_exp;
\ No newline at end of file
@@ -0,0 +1,188 @@
+use graph::{GraphDef, GraphShapes, Result};
+use id::{NodeID, OpID, PassID};
+use ops::{standard_op_name, Op, OpInstance};
+use ops::reduce::reduce_sum::ReduceSum;
+use ops::reduce::reduce_mean::ReduceMean;
+use ops::reduce::reduce_max::ReduceMax;
+use smallvec::SmallVec;
+
+/// The reduction performed by a `Reduce` op
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReduceKind {
+	Sum,
+	Mean,
+	Max,
+}
+
+/// Reduce
+///
+/// Reduces over the chosen axes using a `ReduceKind`, by building the matching `ReduceSum`, `ReduceMean`, or `ReduceMax` op.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct Reduce {
+	name: Option<String>,
+	input_id: NodeID,
+	output_id: NodeID,
+	kind: ReduceKind,
+	axes: SmallVec<[isize; 6]>,
+	keep_dims: bool,
+}
+
+impl Reduce {
+
+	pub fn new(input_id: &NodeID, output_id: &NodeID, kind: ReduceKind) -> Self{
+		Reduce {
+			name: None,
+			input_id: input_id.clone(),
+			output_id: output_id.clone(),
+			kind: kind,
+			axes: SmallVec::new(),
+			keep_dims: false
+		}
+	}
+
+	/// Supply which axes are to be reduced across.
+	///
+	/// If axes is empty, all axes are reduced.
+	/// Each element of `axes` can be in the range [-input.ndims(), input.ndims()).
+	///
+	/// Default: empty
+	pub fn axes(mut self, axes: &[isize]) -> Self {
+		self.axes = axes.iter().cloned().collect();
+		self
+	}
+
+	/// If `true` the reduced axes still appear in the output with size 1, otherwise they are removed.
+	///
+	/// Default: `false`
+	pub fn keep_dims(mut self, keep_dims: bool) -> Self {
+		self.keep_dims = keep_dims;
+		self
+	}
+}
+
+impl Op for Reduce {
+	type InstanceType = ReduceInstance;
+
+	fn type_name(&self) -> &'static str {
+		"Reduce"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]);
+
+		let inner_id = match self.kind {
+			ReduceKind::Sum => graph.new_op(ReduceSum::new(&self.input_id, &self.output_id).axes(&self.axes).keep_dims(self.keep_dims), tag![])?,
+			ReduceKind::Mean => graph.new_op(ReduceMean::new(&self.input_id, &self.output_id).axes(&self.axes).keep_dims(self.keep_dims), tag![])?,
+			ReduceKind::Max => graph.new_op(ReduceMax::new(&self.input_id, &self.output_id).axes(&self.axes).keep_dims(self.keep_dims), tag![])?,
+		};
+
+		Ok(ReduceInstance{
+			name: name,
+			input_id: self.input_id,
+			output_id: self.output_id,
+			kind: self.kind,
+			inner_id: inner_id,
+		})
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct ReduceInstance {
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	kind: ReduceKind,
+	inner_id: OpID,
+}
+
+impl ReduceInstance {
+	pub fn kind(&self) -> ReduceKind {
+		self.kind
+	}
+}
+
+impl OpInstance for ReduceInstance {
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(
+			vec![self.input_id.clone()],
+			vec![self.output_id.clone()]
+		)
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {vec![]}
+
+	fn inner_ops(&self) -> Vec<OpID> {vec![self.inner_id.clone()]}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {vec![]}
+
+	fn propagate_shape_constraints(&self, _shapes: &mut GraphShapes) -> Result<()>{Ok(())}
+}
+
+
+#[test]
+fn test_reduce_backprop(){
+	_reduce_backprop().unwrap();
+}
+
+fn _reduce_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	for &kind in [ReduceKind::Sum, ReduceKind::Mean].iter() {
+		let mut g = GraphDef::new();
+
+		let node1 = g.new_node(shape![7, 2, 11, 3], "input", tag![])?;
+		let node2 = g.new_node(shape![7, 11, 3], "output", tag![])?;
+		let node3 = g.new_node(shape![7, 11, 3], "target", tag![])?;
+
+		let _o1 = g.new_op(Reduce::new(&node1, &node2, kind).axes(&[1]), tag![])?;
+		let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+		let iters = 100;
+		let failures = 1;
+		let tolerance = 0.002;
+		let step_size = 1E-2;
+		let default_variance = 1.0;
+		numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+	}
+
+	Ok(())
+}
+
+#[test]
+fn test_reduce_kinds(){
+	_reduce_kinds().unwrap();
+}
+
+fn _reduce_kinds() -> Result<()>{
+	use graph::GraphDef;
+	use ndarray::{ArrayD, IxDyn};
+
+	let input = ArrayD::from_shape_vec(IxDyn(&[2, 3]), vec![1.0, 5.0, 3.0, -2.0, -4.0, 0.0]).unwrap();
+
+	for &(kind, ref expected) in [(ReduceKind::Sum, [9.0, -6.0]), (ReduceKind::Mean, [3.0, -2.0]), (ReduceKind::Max, [5.0, 0.0])].iter() {
+		let mut g = GraphDef::new();
+
+		let node1 = g.new_node(shape![2, 3], "input", tag![])?;
+		let node2 = g.new_node(shape![2, 1], "output", tag![])?;
+
+		let _o1 = g.new_op(Reduce::new(&node1, &node2, kind).axes(&[1]).keep_dims(true), tag![])?;
+
+		let mut subgraph = g.subgraph(&[node1.value_id()], &[node2.value_id()])?;
+		let storage = subgraph.execute(vec![input.clone()])?;
+		let output = storage.get(&node2.value_id())?;
+
+		assert_eq!(output.iter().cloned().collect::<Vec<_>>(), expected.to_vec(), "{:?}", kind);
+	}
+
+	Ok(())
+}
@@ -0,0 +1,367 @@
+use graph::{GraphDef, GraphShapes, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use shape::NodeShape;
+use ndarray::{ArrayD, ArrayViewD, Dimension, Zip};
+use std::any::Any;
+use std::f32;
+use smallvec::SmallVec;
+
+
+/// ReduceMax
+///
+/// Takes the maximum over the reduced axes.
+/// In backward the gradient is routed to the input which is the maximum, or if there are ties, the one with the lowest flat index.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct ReduceMax {
+	name: Option<String>,
+	input_id: NodeID,
+	output_id: NodeID,
+	axes: SmallVec<[isize; 6]>,
+	keep_dims: bool,
+}
+
+impl ReduceMax {
+
+	pub fn new(input_id: &NodeID, output_id: &NodeID) -> Self{
+		ReduceMax {
+			name: None,
+			input_id: input_id.clone(),
+			output_id: output_id.clone(),
+			axes: SmallVec::new(),
+			keep_dims: false
+		}
+	}
+
+	/// Supply which axes are to be reduced across.
+	///
+	/// If axes is empty, all axes are reduced.
+	/// Each element of `axes` can be in the range [-input.ndims(), input.ndims()).
+	///
+	/// Default: empty
+	pub fn axes(mut self, axes: &[isize]) -> Self {
+		self.axes = axes.iter().cloned().collect();
+		self
+	}
+
+	/// If `true` the reduced axes still appear in the output with size 1, otherwise they are removed.
+	///
+	/// Default: `false`
+	pub fn keep_dims(mut self, keep_dims: bool) -> Self {
+		self.keep_dims = keep_dims;
+		self
+	}
+}
+
+impl Op for ReduceMax {
+	type InstanceType = ReduceMaxInstance;
+
+	fn type_name(&self) -> &'static str {
+		"ReduceMax"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]);
+
+		Ok(ReduceMaxInstance{
+			name: name,
+			input_id: self.input_id.clone(),
+			output_id: self.output_id.clone(),
+			axes: self.axes.clone(),
+			keep_dims: self.keep_dims,
+			forward_id:graph.add_pass(ReduceMaxForward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				self.axes.clone(),
+				self.keep_dims,
+			)),
+			backward_id:graph.add_pass(ReduceMaxBackward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				self.axes.clone(),
+				self.keep_dims,
+			)),
+		})
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct ReduceMaxInstance {
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	axes: SmallVec<[isize; 6]>,
+	keep_dims: bool,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl OpInstance for ReduceMaxInstance {
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(
+			vec![self.input_id.clone()],
+			vec![self.output_id.clone()]
+		)
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.forward_id.clone(), self.backward_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+
+		let input_shape = shapes.get_shape(&self.input_id).to_data_shape()?;
+
+		let output_shape: NodeShape = calc_output_shape(input_shape.slice(), &self.axes, self.keep_dims).into();
+
+		shapes.merge_with(&self.output_id, &output_shape)?;
+		Ok(())
+	}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("axes", format!("{:?}", &self.axes[..])), ("keep_dims", format!("{:?}", self.keep_dims))]
+	}
+}
+
+fn calc_output_shape(input_shape: &[usize], axes: &[isize], keep_dims: bool) -> SmallVec<[usize; 6]> {
+	let reduce_mask = reduction_mask(input_shape.len(), &axes);
+	if keep_dims {
+		input_shape.iter().zip(&reduce_mask).map(|(&dim, &reduce)| {
+				if reduce {1} else {dim}
+			}).collect()
+	} else {
+		input_shape.iter().zip(&reduce_mask).filter_map(|(&dim, &reduce)| {
+				if reduce {None} else {Some(dim)}
+			}).collect()
+	}
+}
+
+/// Returns a mask indicating whether an axis should be reduced based on the axes list
+/// If axes is empty this returns all true,
+/// else only the axis provided are marked true.
+fn reduction_mask(len: usize, axes: &[isize]) -> SmallVec<[bool; 6]> {
+	let mut reduce = SmallVec::with_capacity(len);
+	if axes.len() == 0 {
+		for _ in 0..len {
+			reduce.push(true);
+		}
+	} else {
+		for _ in 0..len {
+			reduce.push(false);
+		}
+		for axis in axes {
+			reduce[(axis + len as isize) as usize % len] = true;
+		}
+	}
+	reduce
+}
+
+/// Returns the maximum of each reduction window, in the keep_dims output shape
+fn calc_maxima(input: &ArrayViewD<f32>, output_shape_keep_dims: &[usize]) -> ArrayD<f32> {
+	let mut maxima = ArrayD::from_elem(output_shape_keep_dims, f32::NEG_INFINITY);
+	for in_chunk in input.exact_chunks(output_shape_keep_dims) {
+		Zip::from(&mut maxima)
+			.and(&in_chunk)
+			.apply(|max, &x| {
+				if x > *max {
+					*max = x;
+				}
+			});
+	}
+	maxima
+}
+
+
+#[derive(Debug, Clone)]
+pub struct ReduceMaxForward {
+	input_id: NodeID,
+	output_id: NodeID,
+	axes: SmallVec<[isize; 6]>,
+	keep_dims: bool,
+}
+
+impl ReduceMaxForward {
+	pub fn new(input_id: NodeID, output_id: NodeID, axes: SmallVec<[isize; 6]>, keep_dims: bool) -> Self{
+		ReduceMaxForward {
+			input_id,
+			output_id,
+			axes,
+			keep_dims,
+		}
+	}
+}
+
+impl Pass for ReduceMaxForward {
+	fn type_name(&self) -> &'static str {"ReduceMaxForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id()],
+		vec![self.output_id.value_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let output = data.get_mut(&self.output_id.value_id())?;
+
+		let input_shape: SmallVec<[usize; 6]> = input.shape().iter().cloned().collect();
+		let output_shape: SmallVec<[usize; 6]> = output.shape().iter().cloned().collect();
+
+		let output_shape_actual = calc_output_shape(&input_shape, &self.axes, self.keep_dims);
+		let output_shape_keep_dims = calc_output_shape(&input_shape, &self.axes, true);
+
+		ensure!(output_shape_actual.as_slice() == output_shape.as_slice(), "Output shape {:?} does not match reduced input shape {:?}", output_shape.as_slice(), output_shape_actual.as_slice());
+
+		let mut output = output.into_shape(output_shape_keep_dims.as_slice()).expect("This should have been caught on the line above");
+		output += &calc_maxima(&input, &output_shape_keep_dims);
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct ReduceMaxBackward {
+	input_id: NodeID,
+	output_id: NodeID,
+	axes: SmallVec<[isize; 6]>,
+	keep_dims: bool,
+}
+
+impl ReduceMaxBackward {
+	pub fn new(input_id: NodeID, output_id: NodeID, axes: SmallVec<[isize; 6]>, keep_dims: bool) -> Self{
+		ReduceMaxBackward {
+			input_id,
+			output_id,
+			axes,
+			keep_dims,
+		}
+	}
+}
+
+impl Pass for ReduceMaxBackward {
+	fn type_name(&self) -> &'static str {"ReduceMaxBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id(), self.output_id.gradient_id()],
+		vec![self.input_id.gradient_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let mut input_grad = data.get_mut(&self.input_id.gradient_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+
+		let input_shape: SmallVec<[usize; 6]> = input_grad.shape().iter().cloned().collect();
+		let output_shape: SmallVec<[usize; 6]> = output_grad.shape().iter().cloned().collect();
+
+		let output_shape_actual: SmallVec<[usize; 6]> = calc_output_shape(&input_shape, &self.axes[..], self.keep_dims);
+		let output_shape_keep_dims: SmallVec<[usize; 6]> = calc_output_shape(&input_shape, &self.axes[..], true);
+
+		ensure!(output_shape_actual.as_slice() == output_shape.as_slice(), "Output shape {:?} does not match reduced input shape {:?}", output_shape.as_slice(), output_shape_actual.as_slice());
+
+		let output_grad = output_grad.into_shape(output_shape_keep_dims.as_slice()).expect("This should have been caught on the line above");
+		let maxima = calc_maxima(&input, &output_shape_keep_dims);
+
+		// chunks are visited in row major order, so the first match has the lowest flat index
+		let mut routed = ArrayD::from_elem(output_shape_keep_dims.as_slice(), false);
+		for (in_chunk, mut in_grad_chunk) in input.exact_chunks(output_shape_keep_dims.as_slice()).into_iter().zip(input_grad.exact_chunks_mut(output_shape_keep_dims.as_slice())) {
+			Zip::from(&in_chunk)
+				.and(&mut in_grad_chunk)
+				.and(&maxima)
+				.and(&output_grad)
+				.and(&mut routed)
+				.apply(|&x, in_grad, &max, &out_grad, routed| {
+					if !*routed && x == max {
+						*in_grad += out_grad;
+						*routed = true;
+					}
+				});
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_reduce_max_backprop(){
+	_reduce_max_backprop().unwrap();
+}
+
+fn _reduce_max_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+	use rand::{thread_rng, Rng};
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![3, 4, 5], "input", tag![])?;
+	let node2 = g.new_node(shape![3, 5], "output", tag![])?;
+	let node3 = g.new_node(shape![3, 5], "target", tag![])?;
+
+	let _o1 = g.new_op(ReduceMax::new(&node1, &node2).axes(&[1]), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	// well separated distinct values, so that small steps never change which input is the maximum
+	let mut values: Vec<f64> = (0..60).map(|i| i as f64 * 0.1 - 3.0).collect();
+	thread_rng().shuffle(&mut values);
+	let mut i = 0;
+	let sample: Box<::std::ops::FnMut() -> f64 + 'static> = Box::new(move || {
+		i += 1;
+		values[i % values.len()]
+	});
+	let mut override_dist = indexmap![];
+	override_dist.insert(node1.clone(), sample);
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-3;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut override_dist)?;
+
+	Ok(())
+}
+
+#[test]
+fn test_reduce_max(){
+	_reduce_max().unwrap();
+}
+
+fn _reduce_max() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::proportional::Proportional;
+	use ndarray::IxDyn;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 3], "input", tag![])?;
+	let node2 = g.new_node(shape![2, 1], "output", tag![])?;
+
+	let _o1 = g.new_op(ReduceMax::new(&node1, &node2).axes(&[-1]).keep_dims(true), tag![])?;
+	let _o2 = g.new_op(Proportional::new(&node2).multiplier(2.0), tag![])?;
+
+	let input = ArrayD::from_shape_vec(IxDyn(&[2, 3]), vec![0.5, 3.0, -1.0, -2.0, -4.0, -3.0]).unwrap();
+
+	let mut subgraph = g.subgraph(&[node1.value_id()], &[node2.value_id(), node1.gradient_id()])?;
+	let storage = subgraph.execute(vec![input])?;
+
+	assert_eq!(storage.get(&node2.value_id())?, ArrayD::from_shape_vec(IxDyn(&[2, 1]), vec![3.0, -2.0]).unwrap());
+	assert_eq!(storage.get(&node1.gradient_id())?, ArrayD::from_shape_vec(IxDyn(&[2, 3]), vec![0.0, 1.0, 0.0, 1.0, 0.0, 0.0]).unwrap());
+
+	Ok(())
+}
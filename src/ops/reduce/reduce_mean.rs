@@ -126,6 +126,10 @@ impl OpInstance for ReduceMeanInstance {
 		shapes.merge_with(&self.output_id, &output_shape)?;
 		Ok(())
 	}
+
+	fn settings(&self) -> Vec<(&'static str, String)> {
+		vec![("axes", format!("{:?}", &self.axes[..])), ("keep_dims", format!("{:?}", self.keep_dims))]
+	}
 }
 
 fn calc_output_shape(input_shape: &[usize], axes: &[isize], keep_dims: bool) -> SmallVec<[usize; 6]> {
@@ -0,0 +1,413 @@
+use graph::{GraphDef, GraphShapes, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use shape::NodeShape;
+use ndarray::{ArrayViewD, ArrayD, Dimension, Zip};
+use std::any::Any;
+use smallvec::SmallVec;
+
+
+/// Variance
+///
+/// Calculates the variance of the input over the chosen axes.
+///
+/// By default the sum of squared deviations is divided by the number of elements reduced, `N`, giving the population variance.
+/// This is the maximum likelihood estimate, and is what normalisation layers use.
+/// If `unbiased(true)` is set it is instead divided by `N - 1`, giving the sample variance, which is undefined if only one element is reduced.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct Variance {
+	name: Option<String>,
+	input_id: NodeID,
+	output_id: NodeID,
+	axes: SmallVec<[isize; 6]>,
+	keep_dims: bool,
+	unbiased: bool,
+}
+
+impl Variance {
+
+	pub fn new(input_id: &NodeID, output_id: &NodeID) -> Self{
+		Variance {
+			name: None,
+			input_id: input_id.clone(),
+			output_id: output_id.clone(),
+			axes: SmallVec::new(),
+			keep_dims: false,
+			unbiased: false,
+		}
+	}
+
+	/// Supply which axes are to be reduced across.
+	///
+	/// If axes is empty, all axes are reduced.
+	/// Each element of `axes` can be in the range [-input.ndims(), input.ndims()).
+	///
+	/// Default: empty
+	pub fn axes(mut self, axes: &[isize]) -> Self {
+		self.axes = axes.iter().cloned().collect();
+		self
+	}
+
+	/// If `true` the reduced axes still appear in the output with size 1, otherwise they are removed.
+	///
+	/// Default: `false`
+	pub fn keep_dims(mut self, keep_dims: bool) -> Self {
+		self.keep_dims = keep_dims;
+		self
+	}
+
+	/// If `true` the sum of squared deviations is divided by `N - 1` rather than `N`.
+	///
+	/// Default: `false`
+	pub fn unbiased(mut self, unbiased: bool) -> Self {
+		self.unbiased = unbiased;
+		self
+	}
+}
+
+impl Op for Variance {
+	type InstanceType = VarianceInstance;
+
+	fn type_name(&self) -> &'static str {
+		"Variance"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]);
+
+		Ok(VarianceInstance{
+			name: name,
+			input_id: self.input_id.clone(),
+			output_id: self.output_id.clone(),
+			axes: self.axes.clone(),
+			keep_dims: self.keep_dims,
+			forward_id:graph.add_pass(VarianceForward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				self.axes.clone(),
+				self.keep_dims,
+				self.unbiased,
+			)),
+			backward_id:graph.add_pass(VarianceBackward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				self.axes.clone(),
+				self.keep_dims,
+				self.unbiased,
+			)),
+		})
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct VarianceInstance {
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	axes: SmallVec<[isize; 6]>,
+	keep_dims: bool,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl OpInstance for VarianceInstance {
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(
+			vec![self.input_id.clone()],
+			vec![self.output_id.clone()]
+		)
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.forward_id.clone(), self.backward_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+
+		let input_shape = shapes.get_shape(&self.input_id).to_data_shape()?;
+
+		let output_shape: NodeShape = calc_output_shape(input_shape.slice(), &self.axes, self.keep_dims).into();
+
+		shapes.merge_with(&self.output_id, &output_shape)?;
+		Ok(())
+	}
+}
+
+fn calc_output_shape(input_shape: &[usize], axes: &[isize], keep_dims: bool) -> SmallVec<[usize; 6]> {
+	let reduce_mask = reduction_mask(input_shape.len(), &axes);
+	if keep_dims {
+		input_shape.iter().zip(&reduce_mask).map(|(&dim, &reduce)| {
+				if reduce {1} else {dim}
+			}).collect()
+	} else {
+		input_shape.iter().zip(&reduce_mask).filter_map(|(&dim, &reduce)| {
+				if reduce {None} else {Some(dim)}
+			}).collect()
+	}
+}
+
+/// Returns a mask indicating whether an axis should be reduced based on the axes list
+/// If axes is empty this returns all true,
+/// else only the axis provided are marked true.
+fn reduction_mask(len: usize, axes: &[isize]) -> SmallVec<[bool; 6]> {
+	let mut reduce = SmallVec::with_capacity(len);
+	if axes.len() == 0 {
+		for _ in 0..len {
+			reduce.push(true);
+		}
+	} else {
+		for _ in 0..len {
+			reduce.push(false);
+		}
+		for axis in axes {
+			reduce[(axis + len as isize) as usize % len] = true;
+		}
+	}
+	reduce
+}
+
+/// Returns the number of elements in each reduction, and the divisor of the sum of squared deviations
+fn reduction_size(input_shape: &[usize], axes: &[isize], unbiased: bool) -> Result<(usize, f32)> {
+	let n: usize = input_shape.iter().zip(reduction_mask(input_shape.len(), axes)).filter_map(|(&dim, reduce)| if reduce{Some(dim)} else {None}).product();
+	if unbiased {
+		ensure!(n > 1, "Unbiased variance requires more than one element in each reduction, input shape {:?} has {}", input_shape, n);
+		Ok((n, (n - 1) as f32))
+	} else {
+		Ok((n, n as f32))
+	}
+}
+
+/// Returns the mean of each reduction window, in the keep_dims output shape
+fn calc_means(input: &ArrayViewD<f32>, output_shape_keep_dims: &[usize], n: usize) -> ArrayD<f32> {
+	let mut means = ArrayD::zeros(output_shape_keep_dims);
+	for in_chunk in input.exact_chunks(output_shape_keep_dims) {
+		means.scaled_add(1.0/n as f32, &in_chunk);
+	}
+	means
+}
+
+
+#[derive(Debug, Clone)]
+pub struct VarianceForward {
+	input_id: NodeID,
+	output_id: NodeID,
+	axes: SmallVec<[isize; 6]>,
+	keep_dims: bool,
+	unbiased: bool,
+}
+
+impl VarianceForward {
+	pub fn new(input_id: NodeID, output_id: NodeID, axes: SmallVec<[isize; 6]>, keep_dims: bool, unbiased: bool) -> Self{
+		VarianceForward {
+			input_id,
+			output_id,
+			axes,
+			keep_dims,
+			unbiased,
+		}
+	}
+}
+
+impl Pass for VarianceForward {
+	fn type_name(&self) -> &'static str {"VarianceForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id()],
+		vec![self.output_id.value_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let output = data.get_mut(&self.output_id.value_id())?;
+
+		let input_shape: SmallVec<[usize; 6]> = input.shape().iter().cloned().collect();
+		let output_shape: SmallVec<[usize; 6]> = output.shape().iter().cloned().collect();
+
+		let (n, divisor) = reduction_size(&input_shape, &self.axes, self.unbiased)?;
+
+		let output_shape_actual = calc_output_shape(&input_shape, &self.axes, self.keep_dims);
+		let output_shape_keep_dims = calc_output_shape(&input_shape, &self.axes, true);
+
+		ensure!(output_shape_actual.as_slice() == output_shape.as_slice(), "Output shape {:?} does not match reduced input shape {:?}", output_shape.as_slice(), output_shape_actual.as_slice());
+
+		let mut output = output.into_shape(output_shape_keep_dims.as_slice()).expect("This should have been caught on the line above");
+		let means = calc_means(&input, &output_shape_keep_dims, n);
+		for in_chunk in input.exact_chunks(output_shape_keep_dims.as_slice()) {
+			Zip::from(&mut output)
+				.and(&in_chunk)
+				.and(&means)
+				.apply(|output, &x, &mean| {
+					*output += (x - mean) * (x - mean) / divisor;
+				});
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct VarianceBackward {
+	input_id: NodeID,
+	output_id: NodeID,
+	axes: SmallVec<[isize; 6]>,
+	keep_dims: bool,
+	unbiased: bool,
+}
+
+impl VarianceBackward {
+	pub fn new(input_id: NodeID, output_id: NodeID, axes: SmallVec<[isize; 6]>, keep_dims: bool, unbiased: bool) -> Self{
+		VarianceBackward {
+			input_id,
+			output_id,
+			axes,
+			keep_dims,
+			unbiased,
+		}
+	}
+}
+
+impl Pass for VarianceBackward {
+	fn type_name(&self) -> &'static str {"VarianceBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id(), self.output_id.gradient_id()],
+		vec![self.input_id.gradient_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let mut input_grad = data.get_mut(&self.input_id.gradient_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+
+		let input_shape: SmallVec<[usize; 6]> = input.shape().iter().cloned().collect();
+		let output_shape: SmallVec<[usize; 6]> = output_grad.shape().iter().cloned().collect();
+
+		let (n, divisor) = reduction_size(&input_shape, &self.axes, self.unbiased)?;
+
+		let output_shape_actual: SmallVec<[usize; 6]> = calc_output_shape(&input_shape, &self.axes[..], self.keep_dims);
+		let output_shape_keep_dims: SmallVec<[usize; 6]> = calc_output_shape(&input_shape, &self.axes[..], true);
+
+		ensure!(output_shape_actual.as_slice() == output_shape.as_slice(), "Output shape {:?} does not match reduced input shape {:?}", output_shape.as_slice(), output_shape_actual.as_slice());
+
+		let output_grad = output_grad.into_shape(output_shape_keep_dims.as_slice()).expect("This should have been caught on the line above");
+		let means = calc_means(&input, &output_shape_keep_dims, n);
+
+		// grad_in = 2(x - mean)/divisor * grad_out, the contribution through the mean sums to zero
+		for (mut in_grad_chunk, in_chunk) in input_grad.exact_chunks_mut(output_shape_keep_dims.as_slice()).into_iter().zip(input.exact_chunks(output_shape_keep_dims.as_slice())) {
+			Zip::from(&mut in_grad_chunk)
+				.and(&in_chunk)
+				.and(&means)
+				.and(&output_grad)
+				.apply(|in_grad, &x, &mean, &out_grad| {
+					*in_grad += 2.0 * (x - mean) / divisor * out_grad;
+				});
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_variance_backprop(){
+	_variance_backprop().unwrap();
+}
+
+fn _variance_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![4, 16], "input", tag![])?;
+	let node2 = g.new_node(shape![4], "output", tag![])?;
+	let node3 = g.new_node(shape![4], "target", tag![])?;
+
+	let _o1 = g.new_op(Variance::new(&node1, &node2).axes(&[-1]), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_variance_keep_dims_backprop(){
+	_variance_keep_dims_backprop().unwrap();
+}
+
+fn _variance_keep_dims_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![7, 2, 11, 3, 5], "input", tag![])?;
+	let node2 = g.new_node(shape![7, 1, 11, 1, 5], "output", tag![])?;
+	let node3 = g.new_node(shape![7, 1, 11, 1, 5], "target", tag![])?;
+
+	let _o1 = g.new_op(Variance::new(&node1, &node2).axes(&[-2, 1]).keep_dims(true).unbiased(true), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_variance(){
+	_variance().unwrap();
+}
+
+fn _variance() -> Result<()>{
+	use graph::GraphDef;
+	use ndarray::arr2;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 4], "input", tag![])?;
+	let node2 = g.new_node(shape![2], "population", tag![])?;
+	let node3 = g.new_node(shape![2], "sample", tag![])?;
+
+	let _o1 = g.new_op(Variance::new(&node1, &node2).axes(&[1]), tag![])?;
+	let _o2 = g.new_op(Variance::new(&node1, &node3).axes(&[1]).unbiased(true), tag![])?;
+
+	let mut subgraph = g.subgraph(&[node1.value_id()], &[node2.value_id(), node3.value_id()])?;
+	let storage = subgraph.execute(vec![arr2(&[[1.0, 2.0, 3.0, 6.0], [5.0, 5.0, 5.0, 5.0]]).into_dyn()])?;
+
+	// squared deviations of the first row sum to 14
+	let population = storage.get(&node2.value_id())?;
+	let sample = storage.get(&node3.value_id())?;
+	assert!((population[0] - 3.5).abs() < 1e-6);
+	assert!((sample[0] - 14.0/3.0).abs() < 1e-6);
+	assert_eq!(population[1], 0.0);
+	assert_eq!(sample[1], 0.0);
+
+	Ok(())
+}
@@ -0,0 +1,238 @@
+use graph::{GraphDef, Result};
+use id::{NodeID, OpID};
+use ops::activ::clip::Clip;
+use ops::activ::elu::ELU;
+use ops::activ::hard_sigmoid::HardSigmoid;
+use ops::activ::hard_swish::HardSwish;
+use ops::activ::leaky_relu::LeakyReLU;
+use ops::activ::logistic::Logistic;
+use ops::activ::mish::Mish;
+use ops::activ::relu::ReLU;
+use ops::activ::softmax::Softmax;
+use ops::activ::spline::Spline;
+use ops::activ::srgb::{SrgbToLinear, LinearToSrgb, SrgbToLinearSlow, LinearToSrgbSlow};
+use ops::activ::tanh::Tanh;
+use ops::math::abs::Abs;
+use ops::math::add::Add;
+use ops::math::cos::Cos;
+use ops::math::div::Div;
+use ops::math::exp::Exp;
+use ops::math::log::Log;
+use ops::math::matmul::MatMul;
+use ops::math::mul::Mul;
+use ops::math::reciprocal::Reciprocal;
+use ops::math::sin::Sin;
+use ops::math::sqrt::Sqrt;
+use ops::math::square::Square;
+use ops::grad::stop_grad::StopGrad;
+use ops::reduce::reduce_max::ReduceMax;
+use ops::reduce::reduce_mean::ReduceMean;
+use ops::reduce::reduce_sum::ReduceSum;
+use ops::shape::global_avg_pool::GlobalAvgPool;
+use ops::nn::bias::Bias;
+use ops::nn::linear::Linear;
+use ops::nn::affine::Affine;
+use ops::nn::layer_norm::LayerNorm;
+use ops::nn::group_norm::GroupNorm;
+use ops::loss::mse::Mse;
+use ops::loss::mae::Mae;
+use ops::loss::cross_entropy::CrossEntropy;
+use ops::loss::log_cosh::LogCosh;
+use ops::loss::focal_loss::FocalLoss;
+use ops::loss::softmax_cross_entropy::SoftmaxCrossEntropy;
+use ops::loss::proportional::Proportional;
+use indexmap::IndexMap;
+use std::sync::Mutex;
+
+/// A function which constructs an `Op` with default settings from the given inputs and outputs, and adds it to the graph.
+pub type OpConstructor = fn(&mut GraphDef, &[NodeID], &[NodeID]) -> Result<OpID>;
+
+lazy_static! {
+	static ref REGISTRY: Mutex<IndexMap<String, OpConstructor>> = Mutex::new(builtin_ops());
+}
+
+/// Constructors for ops with default settings, checking the number of inputs and outputs supplied
+macro_rules! op_constructor {
+	($op:ident, 1 => 1) => {{
+		fn ctor(graph: &mut GraphDef, inputs: &[NodeID], outputs: &[NodeID]) -> Result<OpID> {
+			check_arity(stringify!($op), inputs, outputs, 1, 1)?;
+			graph.new_op($op::new(&inputs[0], &outputs[0]), tag![])
+		}
+		ctor as OpConstructor
+	}};
+	($op:ident, 2 => 1) => {{
+		fn ctor(graph: &mut GraphDef, inputs: &[NodeID], outputs: &[NodeID]) -> Result<OpID> {
+			check_arity(stringify!($op), inputs, outputs, 2, 1)?;
+			graph.new_op($op::new(&inputs[0], &inputs[1], &outputs[0]), tag![])
+		}
+		ctor as OpConstructor
+	}};
+	($op:ident, 0 => 1) => {{
+		fn ctor(graph: &mut GraphDef, inputs: &[NodeID], outputs: &[NodeID]) -> Result<OpID> {
+			check_arity(stringify!($op), inputs, outputs, 0, 1)?;
+			graph.new_op($op::new(&outputs[0]), tag![])
+		}
+		ctor as OpConstructor
+	}};
+	($op:ident, 2 => 0) => {{
+		fn ctor(graph: &mut GraphDef, inputs: &[NodeID], outputs: &[NodeID]) -> Result<OpID> {
+			check_arity(stringify!($op), inputs, outputs, 2, 0)?;
+			graph.new_op($op::new(&inputs[0], &inputs[1]), tag![])
+		}
+		ctor as OpConstructor
+	}};
+	($op:ident, 1 => 0) => {{
+		fn ctor(graph: &mut GraphDef, inputs: &[NodeID], outputs: &[NodeID]) -> Result<OpID> {
+			check_arity(stringify!($op), inputs, outputs, 1, 0)?;
+			graph.new_op($op::new(&inputs[0]), tag![])
+		}
+		ctor as OpConstructor
+	}};
+}
+
+fn check_arity(op_name: &str, inputs: &[NodeID], outputs: &[NodeID], num_inputs: usize, num_outputs: usize) -> Result<()> {
+	ensure!(inputs.len() == num_inputs, format!("Op '{}' requires {} input(s) but {} were supplied", op_name, num_inputs, inputs.len()));
+	ensure!(outputs.len() == num_outputs, format!("Op '{}' requires {} output(s) but {} were supplied", op_name, num_outputs, outputs.len()));
+	Ok(())
+}
+
+/// The built in ops which can be constructed from only their inputs and outputs, keyed by `type_name()`.
+fn builtin_ops() -> IndexMap<String, OpConstructor> {
+	let ops: Vec<(&str, OpConstructor)> = vec![
+		("Clip", op_constructor!(Clip, 1 => 1)),
+		("ELU", op_constructor!(ELU, 1 => 1)),
+		("HardSigmoid", op_constructor!(HardSigmoid, 1 => 1)),
+		("HardSwish", op_constructor!(HardSwish, 1 => 1)),
+		("LeakyReLU", op_constructor!(LeakyReLU, 1 => 1)),
+		("Logistic", op_constructor!(Logistic, 1 => 1)),
+		("Mish", op_constructor!(Mish, 1 => 1)),
+		("ReLU", op_constructor!(ReLU, 1 => 1)),
+		("Softmax", op_constructor!(Softmax, 1 => 1)),
+		("Spline", op_constructor!(Spline, 1 => 1)),
+		("SrgbToLinear", op_constructor!(SrgbToLinear, 1 => 1)),
+		("LinearToSrgb", op_constructor!(LinearToSrgb, 1 => 1)),
+		("SrgbToLinearSlow", op_constructor!(SrgbToLinearSlow, 1 => 1)),
+		("LinearToSrgbSlow", op_constructor!(LinearToSrgbSlow, 1 => 1)),
+		("Tanh", op_constructor!(Tanh, 1 => 1)),
+
+		("Abs", op_constructor!(Abs, 1 => 1)),
+		("Add", op_constructor!(Add, 1 => 1)),
+		("Cos", op_constructor!(Cos, 1 => 1)),
+		("Div", op_constructor!(Div, 2 => 1)),
+		("Exp", op_constructor!(Exp, 1 => 1)),
+		("Log", op_constructor!(Log, 1 => 1)),
+		("MatMul", op_constructor!(MatMul, 2 => 1)),
+		("Mul", op_constructor!(Mul, 2 => 1)),
+		("Reciprocal", op_constructor!(Reciprocal, 1 => 1)),
+		("Sin", op_constructor!(Sin, 1 => 1)),
+		("Sqrt", op_constructor!(Sqrt, 1 => 1)),
+		("Square", op_constructor!(Square, 1 => 1)),
+
+		("StopGrad", op_constructor!(StopGrad, 1 => 1)),
+		("ReduceMax", op_constructor!(ReduceMax, 1 => 1)),
+		("ReduceMean", op_constructor!(ReduceMean, 1 => 1)),
+		("ReduceSum", op_constructor!(ReduceSum, 1 => 1)),
+		("GlobalAvgPool", op_constructor!(GlobalAvgPool, 1 => 1)),
+		("Bias", op_constructor!(Bias, 0 => 1)),
+		("Linear", op_constructor!(Linear, 1 => 1)),
+		("Affine", op_constructor!(Affine, 1 => 1)),
+		("LayerNorm", op_constructor!(LayerNorm, 1 => 1)),
+		("GroupNorm", op_constructor!(GroupNorm, 1 => 1)),
+
+		("Mse", op_constructor!(Mse, 2 => 0)),
+		("Mae", op_constructor!(Mae, 2 => 0)),
+		("CrossEntropy", op_constructor!(CrossEntropy, 2 => 0)),
+		("LogCosh", op_constructor!(LogCosh, 2 => 0)),
+		("FocalLoss", op_constructor!(FocalLoss, 2 => 0)),
+		("SoftmaxCrossEntropy", op_constructor!(SoftmaxCrossEntropy, 2 => 0)),
+		("Proportional", op_constructor!(Proportional, 1 => 0)),
+	];
+
+	ops.into_iter().map(|(name, ctor)| (name.to_string(), ctor)).collect()
+}
+
+/// Registers a constructor under `name`, replacing any constructor previously registered under that name.
+///
+/// By convention `name` should match the `type_name()` of the op being constructed.
+pub fn register_op<S: Into<String>>(name: S, ctor: OpConstructor) {
+	REGISTRY.lock().expect("Could not acquire lock on op registry").insert(name.into(), ctor);
+}
+
+/// Returns the names of all registered ops, built in ops first, in order of registration.
+pub fn registered_ops() -> Vec<String> {
+	REGISTRY.lock().expect("Could not acquire lock on op registry").keys().cloned().collect()
+}
+
+/// Constructs the op registered under `name` using its default settings, and adds it to the graph.
+///
+/// Because `Op` has an associated instance type it can't be boxed, so the op is built directly into `graph` and the resulting `OpID` returned.
+/// Returns an error if no op is registered under `name`, or if the number of inputs or outputs doesn't suit the op.
+pub fn build_op_by_name(graph: &mut GraphDef, name: &str, inputs: &[NodeID], outputs: &[NodeID]) -> Result<OpID> {
+	let ctor = match REGISTRY.lock().expect("Could not acquire lock on op registry").get(name) {
+		Some(ctor) => *ctor,
+		None => bail!(format!("No op is registered under the name '{}'", name)),
+	};
+	ctor(graph, inputs, outputs)
+}
+
+
+#[test]
+fn test_registry_relu(){
+	_registry_relu().unwrap();
+}
+
+fn _registry_relu() -> Result<()>{
+	use ndarray::ArrayD;
+	use rand::thread_rng;
+	use rand::distributions::{Distribution, Normal};
+
+	let mut g1 = GraphDef::new();
+	let input1 = g1.new_node(shape![5, 16], "input", tag![])?;
+	let output1 = g1.new_node(shape![5, 16], "output", tag![])?;
+	let op1 = build_op_by_name(&mut g1, "ReLU", &[input1.clone()], &[output1.clone()])?;
+
+	let mut g2 = GraphDef::new();
+	let input2 = g2.new_node(shape![5, 16], "input", tag![])?;
+	let output2 = g2.new_node(shape![5, 16], "output", tag![])?;
+	let op2 = g2.new_op(ReLU::new(&input2, &output2), tag![])?;
+
+	assert_eq!(op1.name(), op2.name());
+	assert_eq!(op1.type_name(), op2.type_name());
+	assert_eq!(g1.get_passes().len(), g2.get_passes().len());
+
+	let normal = Normal::new(0.0, 1.0);
+	let mut rng = thread_rng();
+	let input = ArrayD::from_shape_fn(&[5, 16][..], |_| normal.sample(&mut rng) as f32);
+
+	let out1 = g1.subgraph(&[input1.value_id()], &[output1.value_id()])?.execute(vec![input.clone()])?.get(&output1.value_id())?.to_owned();
+	let out2 = g2.subgraph(&[input2.value_id()], &[output2.value_id()])?.execute(vec![input])?.get(&output2.value_id())?.to_owned();
+	assert_eq!(out1, out2);
+
+	assert!(build_op_by_name(&mut g1, "ReLU", &[input1.clone(), output1.clone()], &[]).is_err());
+	assert!(build_op_by_name(&mut g1, "NotAnOp", &[input1], &[output1]).is_err());
+
+	Ok(())
+}
+
+#[test]
+fn test_registry_register(){
+	_registry_register().unwrap();
+}
+
+fn _registry_register() -> Result<()>{
+	fn negative_leaky_relu(graph: &mut GraphDef, inputs: &[NodeID], outputs: &[NodeID]) -> Result<OpID> {
+		check_arity("NegativeLeakyReLU", inputs, outputs, 1, 1)?;
+		graph.new_op(LeakyReLU::new(&inputs[0], &outputs[0]).alpha(-0.5), tag![])
+	}
+
+	register_op("NegativeLeakyReLU", negative_leaky_relu);
+	assert!(registered_ops().iter().any(|name| name == "NegativeLeakyReLU"));
+
+	let mut g = GraphDef::new();
+	let input = g.new_node(shape![5, 16], "input", tag![])?;
+	let output = g.new_node(shape![5, 16], "output", tag![])?;
+	let op = build_op_by_name(&mut g, "NegativeLeakyReLU", &[input], &[output])?;
+	assert_eq!(op.type_name(), "LeakyReLU");
+
+	Ok(())
+}
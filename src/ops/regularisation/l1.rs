@@ -234,6 +234,7 @@ impl Pass for L1JointPass {
 
 		let divisor: usize = input_shape.iter().zip(reduction_mask(input_shape.len(), &self.mean_axes)).filter_map(|(dim, reduce)| if reduce{Some(dim)} else {None}).product();
 		let multiplier = self.multiplier/divisor as f32;
+		let grad_multiplier = multiplier * data.loss_scale();
 
 		//let output_shape_actual = calc_output_shape(&input_shape, &self.axes, self.keep_dims);
 		let output_shape_keep_dims = calc_output_shape(&input_shape, &self.mean_axes, true);
@@ -250,7 +251,7 @@ impl Pass for L1JointPass {
 			.and(&mut input_grad_chunk)
 			.apply(|input, input1_grad| {
 				error += input.abs()*multiplier;
-				*input1_grad += input.signum()*multiplier;
+				*input1_grad += input.signum()*grad_multiplier;
 			});
 		}
 
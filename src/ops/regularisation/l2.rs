@@ -234,6 +234,7 @@ impl Pass for L2JointPass {
 
 		let divisor: usize = input_shape.iter().zip(reduction_mask(input_shape.len(), &self.mean_axes)).filter_map(|(dim, reduce)| if reduce{Some(dim)} else {None}).product();
 		let multiplier = self.multiplier/divisor as f32;
+		let grad_multiplier = multiplier * data.loss_scale();
 
 		let output_shape_keep_dims = calc_output_shape(&input_shape, &self.mean_axes, true);
 
@@ -249,7 +250,7 @@ impl Pass for L2JointPass {
 			.and(&mut input_grad_chunk) 
 			.apply(|input, input1_grad| { 
 				error += input*input*multiplier;
-				*input1_grad +=  2.0*input*multiplier;
+				*input1_grad +=  2.0*input*grad_multiplier;
 			});
 		}
 
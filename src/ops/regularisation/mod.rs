@@ -1,2 +1,3 @@
 pub mod l1;
-pub mod l2;
\ No newline at end of file
+pub mod l2;
+pub mod stochastic_depth;
\ No newline at end of file
@@ -0,0 +1,426 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, OpParam, Pass};
+use rng::new_rng;
+use rand::{Rng, RngCore};
+use ndarray::Zip;
+use std::any::Any;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// A shareable rng, allowing the draws made by a pass to be reproduced by seeding.
+#[derive(Clone)]
+struct SharedRng(Arc<Mutex<Box<RngCore + Send>>>);
+
+impl fmt::Debug for SharedRng {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "SharedRng")
+	}
+}
+
+/// StochasticDepth Op
+///
+/// Adds a residual branch to its skip connection, `output = skip + branch`, while randomly dropping the whole branch during training.
+///
+/// When gradients are being calculated through the op, the branch is dropped with probability `drop_rate`, leaving only the skip input,
+/// and otherwise is scaled by `1/(1 - drop_rate)` so that the expected output is the full residual.
+/// One draw is made per execution, so the branch is dropped for the whole batch.
+/// `drop_rate` must be in the range [0, 1], and can be changed within it after the graph is built using `GraphDef::set_op_param()`.
+/// When the op is only being evaluated, e.g. a subgraph which doesn't request any gradients, the branch is always added unscaled.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct StochasticDepth {
+	skip_id: NodeID,
+	branch_id: NodeID,
+	output_id: NodeID,
+	drop_rate: f32,
+	rng: SharedRng,
+	name: Option<String>,
+}
+
+impl StochasticDepth {
+	pub fn new(skip: &NodeID, branch: &NodeID, output: &NodeID, drop_rate: f32) -> Self {
+		StochasticDepth {
+			skip_id: skip.clone(),
+			branch_id: branch.clone(),
+			output_id: output.clone(),
+			drop_rate: drop_rate,
+			rng: SharedRng(Arc::new(Mutex::new(Box::new(new_rng())))),
+			name: None,
+		}
+	}
+
+	/// Supply the rng used to decide when the branch is dropped, e.g. a seeded rng for reproducibility.
+	///
+	/// Default: `rng::new_rng()`
+	pub fn rng<R: RngCore + 'static + Send>(mut self, rng: R) -> Self {
+		self.rng = SharedRng(Arc::new(Mutex::new(Box::new(rng))));
+		self
+	}
+}
+
+impl Op for StochasticDepth {
+	type InstanceType = StochasticDepthInstance;
+
+	fn type_name(&self) -> &'static str {
+		"StochasticDepth"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let drop_rate = OpParam::new(self.drop_rate).with_range(0.0, 1.0);
+		ensure!(drop_rate.in_range(self.drop_rate), format!("StochasticDepth drop_rate {} must be in the range [0, 1]", self.drop_rate));
+
+		let name = standard_op_name(&self, &self.name, graph, &[self.skip_id.clone(), self.branch_id.clone()], &[self.output_id.clone()]);
+
+		let forward_id = graph.add_pass(StochasticDepthForward::new(
+			self.skip_id.clone(),
+			self.branch_id.clone(),
+			self.output_id.clone(),
+			drop_rate.clone(),
+			self.rng.clone()));
+
+		let backward_id = graph.add_pass(StochasticDepthBackward::new(
+			self.skip_id.clone(),
+			self.branch_id.clone(),
+			self.output_id.clone(),
+			forward_id.clone()));
+
+		Ok(StochasticDepthInstance{
+			name: name,
+			skip_id: self.skip_id.clone(),
+			branch_id: self.branch_id.clone(),
+			output_id: self.output_id.clone(),
+			drop_rate: drop_rate,
+			forward_id: forward_id,
+			backward_id: backward_id,
+		})
+	}
+}
+
+
+#[derive(Clone, Debug)]
+pub struct StochasticDepthInstance{
+	name: String,
+	skip_id: NodeID,
+	branch_id: NodeID,
+	output_id: NodeID,
+	drop_rate: OpParam,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl StochasticDepthInstance {
+	/// The drop rate the op was built with, see `GraphDef::op_param()` for the current value.
+	pub fn drop_rate(&self) -> f32 {
+		self.drop_rate.initial()
+	}
+}
+
+impl OpInstance for StochasticDepthInstance {
+
+	fn name(&self) -> &str{&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){(vec![self.skip_id.clone(), self.branch_id.clone()], vec![self.output_id.clone()])}
+
+	fn inner_passes(&self) -> Vec<PassID>{vec![self.forward_id.clone(), self.backward_id.clone()]}
+
+	fn inner_ops(&self) -> Vec<OpID>{vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID>{vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+		let skip_shape = shapes.get_shape(&self.skip_id).clone();
+		let branch_shape = shapes.get_shape(&self.branch_id).clone();
+		shapes.merge_with(&self.output_id, &skip_shape)?;
+		shapes.merge_with(&self.output_id, &branch_shape)
+	}
+
+	fn op_params(&self) -> Vec<&'static str> {
+		vec!["drop_rate"]
+	}
+
+	fn op_param(&self, key: &str) -> Option<OpParam> {
+		match key {
+			"drop_rate" => Some(self.drop_rate.clone()),
+			_ => None,
+		}
+	}
+}
+
+
+#[derive(Clone, Debug)]
+struct StochasticDepthForward {
+	skip_id: NodeID,
+	branch_id: NodeID,
+	output_id: NodeID,
+	drop_rate: OpParam,
+	rng: SharedRng,
+}
+
+impl StochasticDepthForward {
+	fn new(skip_id: NodeID, branch_id: NodeID, output_id: NodeID, drop_rate: OpParam, rng: SharedRng) -> Self {
+		StochasticDepthForward {
+			skip_id,
+			branch_id,
+			output_id,
+			drop_rate,
+			rng,
+		}
+	}
+}
+
+impl Pass for StochasticDepthForward {
+	fn type_name(&self) -> &'static str {"StochasticDepthForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.skip_id.value_id(), self.branch_id.value_id()],
+			vec![self.output_id.value_id()]
+		)
+	}
+
+	/// Returns the scale applied to the branch as pass data, which is zero if the branch was dropped
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let skip = data.get(&self.skip_id.value_id())?;
+		let branch = data.get(&self.branch_id.value_id())?;
+		let mut output = data.get_mut(&self.output_id.value_id())?;
+
+		ensure!(
+			skip.shape() == output.shape() && branch.shape() == output.shape(),
+			ErrorKind::PassError(self.name(), format!("skip shape: {:?} and branch shape: {:?} did not match output shape: {:?}", skip.shape(), branch.shape(), output.shape()))
+		);
+
+		let drop_rate = self.drop_rate.get(data);
+		ensure!(
+			self.drop_rate.in_range(drop_rate),
+			ErrorKind::PassError(self.name(), format!("drop_rate {} must be in the range [0, 1]", drop_rate))
+		);
+
+		// only drop the branch if training, which is when the gradient flows back through the op
+		let scale = if data.is_required(&self.output_id.gradient_id()) {
+			let mut rng = self.rng.0.lock().expect("Could not acquire lock on StochasticDepth rng");
+			if rng.gen::<f32>() < drop_rate {
+				0.0
+			} else {
+				1.0/(1.0 - drop_rate)
+			}
+		} else {
+			1.0
+		};
+
+		Zip::from(&mut output).and(&skip).and(&branch).apply(|output, &skip, &branch| {
+			*output += skip + scale * branch;
+		});
+
+		Ok(Box::new(scale))
+	}
+}
+
+
+#[derive(Clone, Debug)]
+struct StochasticDepthBackward {
+	skip_id: NodeID,
+	branch_id: NodeID,
+	output_id: NodeID,
+	forward_id: PassID,
+}
+
+impl StochasticDepthBackward {
+	fn new(skip_id: NodeID, branch_id: NodeID, output_id: NodeID, forward_id: PassID) -> Self {
+		StochasticDepthBackward {
+			skip_id,
+			branch_id,
+			output_id,
+			forward_id,
+		}
+	}
+}
+
+impl Pass for StochasticDepthBackward {
+	fn type_name(&self) -> &'static str {"StochasticDepthBackward"}
+
+	/// The output value is listed only to ensure the forward pass, which decides whether the branch is dropped, runs first.
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(
+			vec![self.output_id.value_id(), self.output_id.gradient_id()],
+			vec![self.skip_id.gradient_id(), self.branch_id.gradient_id()]
+		)
+	}
+
+	fn run (&self, data: &Storage) -> Result<Box<Any>>{
+		let scale = match data.get_pass_data(&self.forward_id).and_then(|pass_data| pass_data.downcast_ref::<f32>()) {
+			Some(&scale) => scale,
+			None => bail!(ErrorKind::PassError(self.name(), "forward pass data was not available".to_string())),
+		};
+
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+
+		if data.is_required(&self.skip_id.gradient_id()) {
+			let mut skip_grad = data.get_mut(&self.skip_id.gradient_id())?;
+			ensure!(
+				skip_grad.shape() == output_grad.shape(),
+				ErrorKind::PassError(self.name(), format!("skip shape: {:?} did not match output shape: {:?}", skip_grad.shape(), output_grad.shape()))
+			);
+			skip_grad += &output_grad;
+		}
+
+		if scale != 0.0 && data.is_required(&self.branch_id.gradient_id()) {
+			let mut branch_grad = data.get_mut(&self.branch_id.gradient_id())?;
+			ensure!(
+				branch_grad.shape() == output_grad.shape(),
+				ErrorKind::PassError(self.name(), format!("branch shape: {:?} did not match output shape: {:?}", branch_grad.shape(), output_grad.shape()))
+			);
+			branch_grad.scaled_add(scale, &output_grad);
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_stochastic_depth_backprop(){
+	_stochastic_depth_backprop().unwrap();
+}
+
+fn _stochastic_depth_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![4, 8], "skip", tag![])?;
+	let node2 = g.new_node(shape![4, 8], "branch", tag![])?;
+	let node3 = g.new_node(shape![4, 8], "output", tag![])?;
+	let node4 = g.new_node(shape![4, 8], "target", tag![])?;
+
+	// the numeric check requires a deterministic op, so nothing is dropped
+	let _o1 = g.new_op(StochasticDepth::new(&node1, &node2, &node3, 0.0), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node3, &node4), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_stochastic_depth(){
+	_stochastic_depth().unwrap();
+}
+
+fn _stochastic_depth() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::proportional::Proportional;
+	use ndarray::ArrayD;
+	use rand::{Isaac64Rng, SeedableRng};
+
+	let mut g = GraphDef::new();
+
+	let skip = g.new_node(shape![2, 3], "skip", tag![])?;
+	let branch = g.new_node(shape![2, 3], "branch", tag![])?;
+	let output = g.new_node(shape![2, 3], "output", tag![])?;
+
+	let _o1 = g.new_op(StochasticDepth::new(&skip, &branch, &output, 0.25).rng(Isaac64Rng::from_seed([5u8; 32])), tag![])?;
+	let _o2 = g.new_op(Proportional::new(&output), tag![])?;
+
+	let skip_data = ArrayD::from_elem(&[2, 3][..], 1.0);
+	let branch_data = ArrayD::from_elem(&[2, 3][..], 3.0);
+
+	// training: the branch is either dropped or scaled by 4/3, and matches the full residual in expectation
+	let mut train = g.subgraph(&[skip.value_id(), branch.value_id()], &[output.value_id(), branch.gradient_id()])?;
+	let runs = 2000;
+	let mut dropped = 0;
+	let mut sum = 0.0;
+	for _ in 0..runs {
+		let storage = train.execute(vec![skip_data.clone(), branch_data.clone()])?;
+		let out = storage.get(&output.value_id())?;
+		let branch_grad = storage.get(&branch.gradient_id())?;
+		let first = out.iter().next().cloned().unwrap();
+		assert!(out.iter().all(|&x| x == first), "the branch must be dropped for the whole batch");
+		if (first - 1.0).abs() < 1e-6 {
+			dropped += 1;
+			assert!(branch_grad.iter().all(|&x| x == 0.0));
+		} else {
+			assert!((first - 5.0).abs() < 1e-5, "{}", first);
+			assert!(branch_grad.iter().all(|&x| (x - 4.0/3.0/6.0).abs() < 1e-6));
+		}
+		sum += first;
+	}
+	let drop_fraction = dropped as f32 / runs as f32;
+	assert!((drop_fraction - 0.25).abs() < 0.05, "{}", drop_fraction);
+	assert!((sum / runs as f32 - 4.0).abs() < 0.2, "{}", sum / runs as f32);
+
+	// inference: the branch always contributes, unscaled
+	let mut infer = g.subgraph(&[skip.value_id(), branch.value_id()], &[output.value_id()])?;
+	for _ in 0..100 {
+		let storage = infer.execute(vec![skip_data.clone(), branch_data.clone()])?;
+		assert!(storage.get(&output.value_id())?.iter().all(|&x| (x - 4.0).abs() < 1e-6));
+	}
+
+	Ok(())
+}
+
+#[test]
+fn test_stochastic_depth_set_drop_rate(){
+	_stochastic_depth_set_drop_rate().unwrap();
+}
+
+fn _stochastic_depth_set_drop_rate() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::proportional::Proportional;
+	use ndarray::ArrayD;
+
+	let mut g = GraphDef::new();
+
+	let skip = g.new_node(shape![2, 3], "skip", tag![])?;
+	let branch = g.new_node(shape![2, 3], "branch", tag![])?;
+	let output = g.new_node(shape![2, 3], "output", tag![])?;
+
+	let o1 = g.new_op(StochasticDepth::new(&skip, &branch, &output, 0.5), tag![])?;
+	let _o2 = g.new_op(Proportional::new(&output), tag![])?;
+	assert_eq!(o1.instance().op_params(), vec!["drop_rate"]);
+	assert_eq!(g.op_param(&o1, "drop_rate"), Some(0.5));
+
+	let skip_data = ArrayD::from_elem(&[2, 3][..], 1.0);
+	let branch_data = ArrayD::from_elem(&[2, 3][..], 3.0);
+	let mut train = g.subgraph(&[skip.value_id(), branch.value_id()], &[output.value_id(), branch.gradient_id()])?;
+
+	// the existing subgraph always adds the branch unscaled once the rate is zero
+	g.set_op_param(&o1, "drop_rate", 0.0)?;
+	for _ in 0..100 {
+		let storage = train.execute(vec![skip_data.clone(), branch_data.clone()])?;
+		assert!(storage.get(&output.value_id())?.iter().all(|&x| (x - 4.0).abs() < 1e-6));
+	}
+
+	// and always drops it once the rate is one
+	g.set_op_param(&o1, "drop_rate", 1.0)?;
+	for _ in 0..100 {
+		let storage = train.execute(vec![skip_data.clone(), branch_data.clone()])?;
+		assert!(storage.get(&output.value_id())?.iter().all(|&x| (x - 1.0).abs() < 1e-6));
+	}
+
+	// values outside the range are rejected when set, rather than when executed
+	assert!(g.set_op_param(&o1, "drop_rate", 1.5).is_err());
+	assert!(g.set_op_param(&o1, "drop_rate", -0.1).is_err());
+	assert_eq!(g.op_param(&o1, "drop_rate"), Some(1.0));
+	assert!(train.execute(vec![skip_data.clone(), branch_data.clone()]).is_ok());
+
+	// the same range applies when building
+	let output2 = g.new_node(shape![2, 3], "output2", tag![])?;
+	assert!(g.new_op(StochasticDepth::new(&skip, &branch, &output2, 1.5), tag![]).is_err());
+	let _o3 = g.new_op(StochasticDepth::new(&skip, &branch, &output2, 1.0), tag![])?;
+
+	Ok(())
+}
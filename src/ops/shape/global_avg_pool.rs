@@ -0,0 +1,279 @@
+use graph::{GraphDef, GraphShapes, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use shape::NodeShape;
+use ndarray::{Axis, Dimension, Ix3, Ix2};
+use std::any::Any;
+
+
+/// Global Average Pooling operation
+///
+/// Averages over all spatial (inner) dimensions, leaving only the outermost (batch) and innermost (channel) dimensions.
+/// An input of shape `[n, h, w, c]` produces an output of shape `[n, c]`.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct GlobalAvgPool {
+	name: Option<String>,
+	input_id: NodeID,
+	output_id: NodeID,
+}
+
+impl GlobalAvgPool {
+	pub fn new(input_id: &NodeID, output_id: &NodeID) -> Self{
+		GlobalAvgPool {
+			name: None,
+			input_id: input_id.clone(),
+			output_id: output_id.clone(),
+		}
+	}
+}
+
+impl Op for GlobalAvgPool {
+	type InstanceType = GlobalAvgPoolInstance;
+
+	fn type_name(&self) -> &'static str {
+		"GlobalAvgPool"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]);
+
+		Ok(GlobalAvgPoolInstance{
+			name: name,
+			input_id: self.input_id.clone(),
+			output_id: self.output_id.clone(),
+			forward_id: graph.add_pass(GlobalAvgPoolForward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+			)),
+			backward_id: graph.add_pass(GlobalAvgPoolBackward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+			)),
+		})
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct GlobalAvgPoolInstance {
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl OpInstance for GlobalAvgPoolInstance {
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(
+			vec![self.input_id.clone()],
+			vec![self.output_id.clone()]
+		)
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.forward_id.clone(), self.backward_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+
+		let input_shape = shapes.get_shape(&self.input_id).to_data_shape()?;
+
+		ensure!(input_shape.ndim() >= 2, "GlobalAvgPool input must have at least 2 dimensions");
+
+		let input_shape = input_shape.slice();
+		let output_shape: NodeShape = vec![input_shape[0], input_shape[input_shape.len() - 1]].into();
+
+		shapes.merge_with(&self.output_id, &output_shape)?;
+		Ok(())
+	}
+
+}
+
+/// Returns the (outer, spatial, channel) sizes used to view the input as a 3 dimensional array
+fn pool_dims(input_shape: &[usize], output_shape: &[usize]) -> Result<(usize, usize, usize)> {
+	ensure!(input_shape.len() >= 2, "GlobalAvgPool input must have at least 2 dimensions");
+	ensure!(output_shape.len() == 2, "GlobalAvgPool output must have 2 dimensions");
+
+	let n = input_shape[0];
+	let c = input_shape[input_shape.len() - 1];
+	ensure!(output_shape[0] == n && output_shape[1] == c, "input shape incompatible with output shape");
+
+	let spatial = input_shape[1..input_shape.len() - 1].iter().product();
+	Ok((n, spatial, c))
+}
+
+
+#[derive(Debug, Clone)]
+pub struct GlobalAvgPoolForward {
+	input_id: NodeID,
+	output_id: NodeID,
+}
+
+impl GlobalAvgPoolForward {
+	pub fn new(input_id: NodeID, output_id: NodeID) -> Self{
+		GlobalAvgPoolForward {
+			input_id,
+			output_id,
+		}
+	}
+}
+
+impl Pass for GlobalAvgPoolForward {
+	fn type_name(&self) -> &'static str {"GlobalAvgPoolForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id()],
+		vec![self.output_id.value_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let output = data.get_mut(&self.output_id.value_id())?;
+
+		let (n, spatial, c) = pool_dims(input.shape(), output.shape())?;
+		if spatial == 0 {
+			return Ok(Box::new(()));
+		}
+
+		let input = input.into_shape(Ix3(n, spatial, c)).expect("input must be contiguous");
+		let mut output = output.into_shape(Ix2(n, c)).expect("output must be contiguous");
+
+		let scale = 1.0/spatial as f32;
+		for (input, mut output) in input.outer_iter().zip(output.outer_iter_mut()) {
+			for row in input.axis_iter(Axis(0)) {
+				output.scaled_add(scale, &row);
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct GlobalAvgPoolBackward {
+	input_id: NodeID,
+	output_id: NodeID,
+}
+
+impl GlobalAvgPoolBackward {
+	pub fn new(input_id: NodeID, output_id: NodeID) -> Self{
+		GlobalAvgPoolBackward {
+			input_id,
+			output_id,
+		}
+	}
+}
+
+impl Pass for GlobalAvgPoolBackward {
+	fn type_name(&self) -> &'static str {"GlobalAvgPoolBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.output_id.gradient_id()],
+		vec![self.input_id.gradient_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input_grad = data.get_mut(&self.input_id.gradient_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+
+		let (n, spatial, c) = pool_dims(input_grad.shape(), output_grad.shape())?;
+		if spatial == 0 {
+			return Ok(Box::new(()));
+		}
+
+		let mut input_grad = input_grad.into_shape(Ix3(n, spatial, c)).expect("input gradient must be contiguous");
+		let output_grad = output_grad.into_shape(Ix2(n, c)).expect("output gradient must be contiguous");
+
+		let scale = 1.0/spatial as f32;
+		for (mut input_grad, output_grad) in input_grad.outer_iter_mut().zip(output_grad.outer_iter()) {
+			for mut row in input_grad.axis_iter_mut(Axis(0)) {
+				row.scaled_add(scale, &output_grad);
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_global_avg_pool_backprop(){
+	_global_avg_pool_backprop().unwrap();
+}
+
+fn _global_avg_pool_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 4, 4, 3], "input", tag![])?;
+	let node2 = g.new_node(shape![Unknown, Unknown], "output", tag![])?;
+	let node3 = g.new_node(shape![2, 3], "target", tag![])?;
+
+	let _o1 = g.new_op(GlobalAvgPool::new(&node1, &node2), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_global_avg_pool(){
+	_global_avg_pool().unwrap();
+}
+
+fn _global_avg_pool() -> Result<()>{
+	use graph::GraphDef;
+	use ndarray::{ArrayD, IxDyn};
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 4, 4, 3], "input", tag![])?;
+	let node2 = g.new_node(shape![Unknown, Unknown], "output", tag![])?;
+
+	let _o1 = g.new_op(GlobalAvgPool::new(&node1, &node2), tag![])?;
+
+	let input = ArrayD::from_shape_fn(IxDyn(&[2, 4, 4, 3]), |idx| (idx[0] * 100 + idx[1] * 10 + idx[2] + idx[3] * 1000) as f32);
+
+	let mut subgraph = g.subgraph(&[node1.value_id()], &[node2.value_id()])?;
+	let storage = subgraph.execute(vec![input.clone()])?;
+	let output = storage.get(&node2.value_id())?;
+
+	assert_eq!(output.shape(), &[2, 3]);
+	for n in 0..2 {
+		for c in 0..3 {
+			let mut sum = 0.0;
+			for h in 0..4 {
+				for w in 0..4 {
+					sum += input[[n, h, w, c]];
+				}
+			}
+			let expected = sum/16.0;
+			assert!((output[[n, c]] - expected).abs() < 1e-4, "{} {} {} {}", n, c, output[[n, c]], expected);
+		}
+	}
+
+	Ok(())
+}
@@ -14,6 +14,8 @@ use matrixmultiply;
 /// Linterp implements linear interpolation upscaling
 ///
 /// Increase size of each spatial dimension by given a factor by linear interpolating between spaxels in the input
+///
+/// For blocky upscaling which repeats each spaxel use `UpsampleNearest`.
 #[must_use]
 #[derive(Clone, Debug)]
 pub struct Linterp {
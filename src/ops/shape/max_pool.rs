@@ -0,0 +1,340 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use shape::NodeShape;
+use ndarray::{Dimension, IxDyn, SliceInfo, SliceOrIndex};
+use std::cmp;
+use smallvec::SmallVec;
+use std::any::Any;
+use std::f32;
+
+
+/// Max Pooling operation
+///
+/// Decrease size of dimensions by given factors.
+/// Output values are the maximum of windows of the input with the size of factors.
+///
+/// When several inputs in a window are tied for the maximum, the gradient is routed according to `stable_ties()`.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct MaxPool {
+	name: Option<String>,
+	input_id: NodeID,
+	output_id: NodeID,
+	factors: Vec<usize>,
+	stable_ties: bool,
+}
+
+impl MaxPool {
+	pub fn new(input_id: &NodeID, output_id: &NodeID, factors: &[usize]) -> Self{
+		MaxPool {
+			name: None,
+			input_id: input_id.clone(),
+			output_id: output_id.clone(),
+			factors: factors.to_vec(),
+			stable_ties: true,
+		}
+	}
+
+	/// How gradients are routed when several inputs in a window are tied for the maximum.
+	///
+	/// If true, the tied input with the lowest flat index receives the whole gradient.
+	/// If false, the gradient is divided equally between all tied inputs.
+	/// Both are deterministic.
+	///
+	/// Default: true
+	pub fn stable_ties(mut self, stable_ties: bool) -> Self {
+		self.stable_ties = stable_ties;
+		self
+	}
+}
+
+impl Op for MaxPool {
+	type InstanceType = MaxPoolInstance;
+
+	fn type_name(&self) -> &'static str {
+		"MaxPool"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]);
+
+		Ok(MaxPoolInstance{
+			name: name,
+			input_id: self.input_id.clone(),
+			output_id: self.output_id.clone(),
+			factors: self.factors.clone(),
+			forward_id: graph.add_pass(MaxPoolForward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				self.factors.clone(),
+			)),
+			backward_id: graph.add_pass(MaxPoolBackward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				self.factors.clone(),
+				self.stable_ties,
+			)),
+		})
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct MaxPoolInstance {
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	factors: Vec<usize>,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl OpInstance for MaxPoolInstance {
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(
+			vec![self.input_id.clone()],
+			vec![self.output_id.clone()]
+		)
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.forward_id.clone(), self.backward_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+
+		let input_shape = shapes.get_shape(&self.input_id).to_data_shape()?;
+
+		ensure!(input_shape.ndim() == self.factors.len(), "pooling factors must be the same length as input shape");
+
+		let output_shape: NodeShape = input_shape.slice().iter().zip(&self.factors).map(|(i, f)| (i + f - 1)/f).into();
+
+		shapes.merge_with(&self.output_id, &output_shape)?;
+		Ok(())
+	}
+
+}
+
+fn check_shapes(pass_name: String, input_shape: &[usize], output_shape: &[usize], factors: &[usize]) -> Result<()> {
+	ensure!(input_shape.len() == output_shape.len(), ErrorKind::PassError(pass_name, format!("Input ndims does not match output ndims")));
+	ensure!(input_shape.len() == factors.len(), ErrorKind::PassError(pass_name, format!("pooling factors must be the same length as input shape")));
+	ensure!(input_shape.iter().zip(factors).map(|(i, f)| (i + f - 1)/f).eq(output_shape.iter().cloned()),
+		ErrorKind::PassError(pass_name, format!("input shape {:?} and factors {:?} incompatible with output shape {:?}", input_shape, factors, output_shape)));
+	Ok(())
+}
+
+/// Returns the slice of the input covered by the window (output element) at index `window`
+fn window_slice(window: &[usize], input_shape: &[usize], factors: &[usize]) -> SliceInfo<SmallVec<[SliceOrIndex; 6]>, IxDyn> {
+	let slices: SmallVec<[SliceOrIndex; 6]> = window.iter().zip(input_shape).zip(factors).map(|((&w, &i), &f)| {
+		SliceOrIndex::Slice{start: (w*f) as isize, end: Some(cmp::min((w + 1)*f, i) as isize), step: 1}
+	}).collect();
+	SliceInfo::new(slices).unwrap()
+}
+
+fn window_max<'a, I: Iterator<Item=&'a f32>>(window: I) -> f32 {
+	window.fold(f32::NEG_INFINITY, |max, &x| if x > max {x} else {max})
+}
+
+
+#[derive(Debug, Clone)]
+pub struct MaxPoolForward {
+	input_id: NodeID,
+	output_id: NodeID,
+	factors: Vec<usize>,
+}
+
+impl MaxPoolForward {
+	pub fn new(input_id: NodeID, output_id: NodeID, factors: Vec<usize>) -> Self{
+		MaxPoolForward {
+			input_id,
+			output_id,
+			factors,
+		}
+	}
+}
+
+impl Pass for MaxPoolForward {
+	fn type_name(&self) -> &'static str {"MaxPoolForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id()],
+		vec![self.output_id.value_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let mut output = data.get_mut(&self.output_id.value_id())?;
+
+		check_shapes(self.name(), input.shape(), output.shape(), &self.factors)?;
+
+		for (window, output_element) in output.indexed_iter_mut() {
+			let si = window_slice(window.slice(), input.shape(), &self.factors);
+			let si: &SliceInfo<[SliceOrIndex], IxDyn> = si.as_ref();
+			*output_element += window_max(input.slice(si).iter());
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct MaxPoolBackward {
+	input_id: NodeID,
+	output_id: NodeID,
+	factors: Vec<usize>,
+	stable_ties: bool,
+}
+
+impl MaxPoolBackward {
+	pub fn new(input_id: NodeID, output_id: NodeID, factors: Vec<usize>, stable_ties: bool) -> Self{
+		MaxPoolBackward {
+			input_id,
+			output_id,
+			factors,
+			stable_ties,
+		}
+	}
+}
+
+impl Pass for MaxPoolBackward {
+	fn type_name(&self) -> &'static str {"MaxPoolBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id(), self.output_id.gradient_id()],
+		vec![self.input_id.gradient_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let mut input_grad = data.get_mut(&self.input_id.gradient_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+
+		check_shapes(self.name(), input.shape(), output_grad.shape(), &self.factors)?;
+
+		for (window, &grad) in output_grad.indexed_iter() {
+			let si = window_slice(window.slice(), input.shape(), &self.factors);
+			let si: &SliceInfo<[SliceOrIndex], IxDyn> = si.as_ref();
+			let input_window = input.slice(si);
+			let mut grad_window = input_grad.slice_mut(si);
+
+			let max = window_max(input_window.iter());
+			if self.stable_ties {
+				// row major iteration visits the lowest flat index of the window first
+				for (&x, x_grad) in input_window.iter().zip(grad_window.iter_mut()) {
+					if x == max {
+						*x_grad += grad;
+						break;
+					}
+				}
+			} else {
+				let count = input_window.iter().filter(|&&x| x == max).count();
+				for (&x, x_grad) in input_window.iter().zip(grad_window.iter_mut()) {
+					if x == max {
+						*x_grad += grad/count as f32;
+					}
+				}
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_max_pool_backprop(){
+	_max_pool_backprop().unwrap();
+}
+
+fn _max_pool_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 4, 6, 3], "input", tag![])?;
+	let node2 = g.new_node(shape![Unknown, Unknown, Unknown, 3], "output", tag![])?;
+	let node3 = g.new_node(shape![2, 2, 2, 3], "target", tag![])?;
+
+	let _o1 = g.new_op(MaxPool::new(&node1, &node2, &[1, 2, 3, 1]), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	// a step can occasionally change which input is the maximum of a window, so allow extra failures
+	let iters = 100;
+	let failures = 2;
+	let tolerance = 0.01;
+	let step_size = 1E-3;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_max_pool_ties(){
+	_max_pool_ties().unwrap();
+}
+
+fn _max_pool_ties() -> Result<()>{
+	use graph::GraphDef;
+	use ndarray::ArrayD;
+	use ops::loss::proportional::Proportional;
+
+	let run = |stable_ties: bool| -> Result<(ArrayD<f32>, ArrayD<f32>)> {
+		let mut g = GraphDef::new();
+
+		let node1 = g.new_node(shape![1, 4, 4, 1], "input", tag![])?;
+		let node2 = g.new_node(shape![1, 2, 2, 1], "output", tag![])?;
+
+		let _o1 = g.new_op(MaxPool::new(&node1, &node2, &[1, 2, 2, 1]).stable_ties(stable_ties), tag![])?;
+		let _o2 = g.new_op(Proportional::new(&node2).multiplier(4.0), tag![])?;
+
+		// every window is entirely tied, except the last which has a unique maximum in its final position
+		let mut input = ArrayD::from_elem(IxDyn(&[1, 4, 4, 1]), 1.0);
+		input[[0, 3, 3, 0]] = 2.0;
+
+		let mut subgraph = g.subgraph(&[node1.value_id()], &[node2.value_id(), node1.gradient_id()])?;
+		let storage = subgraph.execute(vec![input])?;
+		Ok((storage.get(&node2.value_id())?.to_owned(), storage.get(&node1.gradient_id())?.to_owned()))
+	};
+
+	// Proportional applies a gradient of 4.0/4 to each output
+	let (output, grad) = run(true)?;
+	assert_eq!(output, ArrayD::from_shape_vec(IxDyn(&[1, 2, 2, 1]), vec![1.0, 1.0, 1.0, 2.0]).unwrap());
+	let expected = ArrayD::from_shape_vec(IxDyn(&[1, 4, 4, 1]), vec![
+		1.0, 0.0, 1.0, 0.0,
+		0.0, 0.0, 0.0, 0.0,
+		1.0, 0.0, 0.0, 0.0,
+		0.0, 0.0, 0.0, 1.0,
+	]).unwrap();
+	assert_eq!(grad, expected);
+
+	for _ in 0..5 {
+		assert_eq!(run(true)?.1, expected);
+	}
+
+	let (_output, grad) = run(false)?;
+	let expected = ArrayD::from_shape_vec(IxDyn(&[1, 4, 4, 1]), vec![
+		0.25, 0.25, 0.25, 0.25,
+		0.25, 0.25, 0.25, 0.25,
+		0.25, 0.25, 0.0, 0.0,
+		0.25, 0.25, 0.0, 1.0,
+	]).unwrap();
+	assert_eq!(grad, expected);
+
+	Ok(())
+}
@@ -1,4 +1,10 @@
 pub mod avg_pool;
 pub mod shape_constraint;
 pub mod linterp;
-pub mod pixel_shuffle;
\ No newline at end of file
+pub mod pixel_shuffle;
+pub mod global_avg_pool;
+pub mod max_pool;
+pub mod pad;
+pub mod slice;
+pub mod upsample_nearest;
+pub mod split;
\ No newline at end of file
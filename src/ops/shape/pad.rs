@@ -0,0 +1,362 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use shape::NodeShape;
+use ndarray::{Axis, Dimension, Ix4};
+use std::any::Any;
+
+/// How the padded border of `Pad2D` is filled
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PadMode {
+	/// Fill with zeros. Gradients reaching the border are discarded.
+	Zero,
+	/// Mirror the input about its edge, excluding the edge itself, e.g. `[a, b, c]` padded by 2 gives `[c, b, a, b, c, b, a]`.
+	/// Each padding amount must be less than the size of the axis it pads.
+	Reflect,
+}
+
+/// Spatial padding operation
+///
+/// Pads the two spatial axes of an `[n, h, w, c]` input, producing an `[n, h + top + bottom, w + left + right, c]` output.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct Pad2D {
+	name: Option<String>,
+	input_id: NodeID,
+	output_id: NodeID,
+	padding: [usize; 4],
+	mode: PadMode,
+}
+
+impl Pad2D {
+	/// Creates a new `Pad2D` Op with `padding` given as `[top, bottom, left, right]`.
+	pub fn new(input_id: &NodeID, output_id: &NodeID, padding: [usize; 4]) -> Self{
+		Pad2D {
+			name: None,
+			input_id: input_id.clone(),
+			output_id: output_id.clone(),
+			padding: padding,
+			mode: PadMode::Zero,
+		}
+	}
+
+	/// How the padded border should be filled.
+	///
+	/// Default: `PadMode::Zero`
+	pub fn mode(mut self, mode: PadMode) -> Self {
+		self.mode = mode;
+		self
+	}
+}
+
+impl Op for Pad2D {
+	type InstanceType = Pad2DInstance;
+
+	fn type_name(&self) -> &'static str {
+		"Pad2D"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]);
+
+		Ok(Pad2DInstance{
+			name: name,
+			input_id: self.input_id.clone(),
+			output_id: self.output_id.clone(),
+			padding: self.padding,
+			forward_id: graph.add_pass(Pad2DForward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				self.padding,
+				self.mode,
+			)),
+			backward_id: graph.add_pass(Pad2DBackward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				self.padding,
+				self.mode,
+			)),
+		})
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Pad2DInstance {
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	padding: [usize; 4],
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl OpInstance for Pad2DInstance {
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(
+			vec![self.input_id.clone()],
+			vec![self.output_id.clone()]
+		)
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.forward_id.clone(), self.backward_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+
+		let input_shape = shapes.get_shape(&self.input_id).to_data_shape()?;
+
+		ensure!(input_shape.ndim() == 4, "Pad2D input must have 4 dimensions [n, h, w, c]");
+
+		let [top, bottom, left, right] = self.padding;
+		let input_shape = input_shape.slice();
+		let output_shape: NodeShape = vec![input_shape[0], input_shape[1] + top + bottom, input_shape[2] + left + right, input_shape[3]].into();
+
+		shapes.merge_with(&self.output_id, &output_shape)?;
+		Ok(())
+	}
+
+}
+
+/// For each output index along one axis, returns the input index it is copied from, if any
+fn source_indices(input_len: usize, pad_before: usize, pad_after: usize, mode: PadMode) -> Vec<Option<usize>> {
+	(0..input_len + pad_before + pad_after).map(|o| {
+		let i = o as isize - pad_before as isize;
+		let len = input_len as isize;
+		match mode {
+			PadMode::Zero => if i >= 0 && i < len {Some(i as usize)} else {None},
+			PadMode::Reflect => {
+				let i = if i < 0 {-i} else if i >= len {2 * (len - 1) - i} else {i};
+				Some(i as usize)
+			},
+		}
+	}).collect()
+}
+
+/// Returns the source indices of the output rows and columns
+fn source_maps(pass_name: String, input_shape: &[usize], output_shape: &[usize], padding: [usize; 4], mode: PadMode) -> Result<(Vec<Option<usize>>, Vec<Option<usize>>)> {
+	let [top, bottom, left, right] = padding;
+	ensure!(input_shape.len() == 4, ErrorKind::PassError(pass_name, format!("input must have 4 dimensions, found shape: {:?}", input_shape)));
+	ensure!(output_shape == &[input_shape[0], input_shape[1] + top + bottom, input_shape[2] + left + right, input_shape[3]][..],
+		ErrorKind::PassError(pass_name, format!("input shape: {:?} and padding: {:?} incompatible with output shape: {:?}", input_shape, padding, output_shape)));
+	if mode == PadMode::Reflect {
+		ensure!(top.max(bottom) < input_shape[1] && left.max(right) < input_shape[2],
+			ErrorKind::PassError(pass_name, format!("Reflect padding: {:?} must be less than the spatial dimensions of input shape: {:?}", padding, input_shape)));
+	}
+
+	Ok((source_indices(input_shape[1], top, bottom, mode), source_indices(input_shape[2], left, right, mode)))
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Pad2DForward {
+	input_id: NodeID,
+	output_id: NodeID,
+	padding: [usize; 4],
+	mode: PadMode,
+}
+
+impl Pad2DForward {
+	pub fn new(input_id: NodeID, output_id: NodeID, padding: [usize; 4], mode: PadMode) -> Self{
+		Pad2DForward {
+			input_id,
+			output_id,
+			padding,
+			mode,
+		}
+	}
+}
+
+impl Pass for Pad2DForward {
+	fn type_name(&self) -> &'static str {"Pad2DForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id()],
+		vec![self.output_id.value_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let output = data.get_mut(&self.output_id.value_id())?;
+
+		let (rows, cols) = source_maps(self.name(), input.shape(), output.shape(), self.padding, self.mode)?;
+
+		let input = input.into_dimensionality::<Ix4>().expect("input must have 4 dimensions");
+		let mut output = output.into_dimensionality::<Ix4>().expect("output must have 4 dimensions");
+
+		for (input, mut output) in input.outer_iter().zip(output.outer_iter_mut()) {
+			for (mut output_row, row) in output.outer_iter_mut().zip(&rows) {
+				let row = match *row {Some(row) => row, None => continue};
+				let input_row = input.subview(Axis(0), row);
+				for (mut output_pixel, col) in output_row.outer_iter_mut().zip(&cols) {
+					if let Some(col) = *col {
+						output_pixel += &input_row.subview(Axis(0), col);
+					}
+				}
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Pad2DBackward {
+	input_id: NodeID,
+	output_id: NodeID,
+	padding: [usize; 4],
+	mode: PadMode,
+}
+
+impl Pad2DBackward {
+	pub fn new(input_id: NodeID, output_id: NodeID, padding: [usize; 4], mode: PadMode) -> Self{
+		Pad2DBackward {
+			input_id,
+			output_id,
+			padding,
+			mode,
+		}
+	}
+}
+
+impl Pass for Pad2DBackward {
+	fn type_name(&self) -> &'static str {"Pad2DBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.output_id.gradient_id()],
+		vec![self.input_id.gradient_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input_grad = data.get_mut(&self.input_id.gradient_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+
+		let (rows, cols) = source_maps(self.name(), input_grad.shape(), output_grad.shape(), self.padding, self.mode)?;
+
+		let mut input_grad = input_grad.into_dimensionality::<Ix4>().expect("input gradient must have 4 dimensions");
+		let output_grad = output_grad.into_dimensionality::<Ix4>().expect("output gradient must have 4 dimensions");
+
+		for (mut input_grad, output_grad) in input_grad.outer_iter_mut().zip(output_grad.outer_iter()) {
+			for (output_grad_row, row) in output_grad.outer_iter().zip(&rows) {
+				let row = match *row {Some(row) => row, None => continue};
+				let mut input_grad_row = input_grad.subview_mut(Axis(0), row);
+				for (output_grad_pixel, col) in output_grad_row.outer_iter().zip(&cols) {
+					if let Some(col) = *col {
+						let mut input_grad_pixel = input_grad_row.subview_mut(Axis(0), col);
+						input_grad_pixel += &output_grad_pixel;
+					}
+				}
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_pad_backprop(){
+	_pad_backprop().unwrap();
+}
+
+fn _pad_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	for &mode in [PadMode::Zero, PadMode::Reflect].iter() {
+		let mut g = GraphDef::new();
+
+		let node1 = g.new_node(shape![1, 3, 3, 2], "input", tag![])?;
+		let node2 = g.new_node(shape![Unknown, Unknown, Unknown, 2], "output", tag![])?;
+		let node3 = g.new_node(shape![1, 5, 5, 2], "target", tag![])?;
+
+		let _o1 = g.new_op(Pad2D::new(&node1, &node2, [1, 1, 1, 1]).mode(mode), tag![])?;
+		let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+		let iters = 100;
+		let failures = 1;
+		let tolerance = 0.002;
+		let step_size = 1E-2;
+		let default_variance = 1.0;
+		numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+	}
+
+	Ok(())
+}
+
+#[test]
+fn test_pad_values(){
+	_pad_values().unwrap();
+}
+
+fn _pad_values() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::proportional::Proportional;
+	use ndarray::{ArrayD, IxDyn};
+
+	let run = |mode: PadMode| -> Result<(ArrayD<f32>, ArrayD<f32>)> {
+		let mut g = GraphDef::new();
+
+		let node1 = g.new_node(shape![1, 3, 3, 2], "input", tag![])?;
+		let node2 = g.new_node(shape![Unknown, Unknown, Unknown, Unknown], "output", tag![])?;
+
+		let _o1 = g.new_op(Pad2D::new(&node1, &node2, [1, 1, 1, 1]).mode(mode), tag![])?;
+		let _o2 = g.new_op(Proportional::new(&node2).multiplier(50.0), tag![])?;
+
+		let input = ArrayD::from_shape_fn(IxDyn(&[1, 3, 3, 2]), |idx| (idx[1] * 3 + idx[2] + 1) as f32 * if idx[3] == 0 {1.0} else {-1.0});
+
+		let mut subgraph = g.subgraph(&[node1.value_id()], &[node2.value_id(), node1.gradient_id()])?;
+		let storage = subgraph.execute(vec![input])?;
+		Ok((storage.get(&node2.value_id())?.to_owned(), storage.get(&node1.gradient_id())?.to_owned()))
+	};
+
+	// Proportional applies a gradient of 50/50 to each of the output elements
+	let (output, grad) = run(PadMode::Zero)?;
+	assert_eq!(output.shape(), &[1, 5, 5, 2]);
+	let expected: Vec<f32> = vec![
+		0.0, 0.0, 0.0, 0.0, 0.0,
+		0.0, 1.0, 2.0, 3.0, 0.0,
+		0.0, 4.0, 5.0, 6.0, 0.0,
+		0.0, 7.0, 8.0, 9.0, 0.0,
+		0.0, 0.0, 0.0, 0.0, 0.0,
+	];
+	assert_eq!(output.subview(Axis(3), 0).iter().cloned().collect::<Vec<_>>(), expected);
+	assert_eq!(output.subview(Axis(3), 1).iter().map(|x| -x).collect::<Vec<_>>(), expected);
+	// gradients reaching the border are discarded
+	assert!(grad.iter().all(|&x| x == 1.0), "{:?}", grad);
+
+	let (output, grad) = run(PadMode::Reflect)?;
+	assert_eq!(output.shape(), &[1, 5, 5, 2]);
+	let expected: Vec<f32> = vec![
+		5.0, 4.0, 5.0, 6.0, 5.0,
+		2.0, 1.0, 2.0, 3.0, 2.0,
+		5.0, 4.0, 5.0, 6.0, 5.0,
+		8.0, 7.0, 8.0, 9.0, 8.0,
+		5.0, 4.0, 5.0, 6.0, 5.0,
+	];
+	assert_eq!(output.subview(Axis(3), 0).iter().cloned().collect::<Vec<_>>(), expected);
+	// gradients reaching the border accumulate on the reflected inputs
+	let expected_grad: Vec<f32> = vec![
+		1.0, 2.0, 1.0,
+		2.0, 4.0, 2.0,
+		1.0, 2.0, 1.0,
+	];
+	assert_eq!(grad.subview(Axis(3), 0).iter().cloned().collect::<Vec<_>>(), expected_grad);
+	assert_eq!(grad.subview(Axis(3), 1).iter().cloned().collect::<Vec<_>>(), expected_grad);
+
+	Ok(())
+}
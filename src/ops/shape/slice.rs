@@ -0,0 +1,293 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use shape::{NodeShape, NodeDim};
+use ndarray::{ArrayBase, Axis, Data, Dimension, IxDyn, Slice as AxisSlice};
+use std::any::Any;
+
+/// Slice operation
+///
+/// Extracts a sub-array of the input, selecting the elements `start..start + len` along each axis.
+/// The output has shape `[len0, len1, ...]`.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct Slice {
+	name: Option<String>,
+	input_id: NodeID,
+	output_id: NodeID,
+	ranges: Vec<(usize, usize)>,
+}
+
+impl Slice {
+	/// Creates a new `Slice` Op with a `(start, len)` range for every axis of the input.
+	pub fn new(input_id: &NodeID, output_id: &NodeID, ranges: &[(usize, usize)]) -> Self{
+		Slice {
+			name: None,
+			input_id: input_id.clone(),
+			output_id: output_id.clone(),
+			ranges: ranges.to_vec(),
+		}
+	}
+}
+
+impl Op for Slice {
+	type InstanceType = SliceInstance;
+
+	fn type_name(&self) -> &'static str {
+		"Slice"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]);
+
+		let input_shape = self.input_id.shape();
+		ensure!(self.ranges.len() == input_shape.ndim(), "Slice op ({}) was given {} ranges, but the input has {} dimensions", name, self.ranges.len(), input_shape.ndim());
+		for (axis, (dim, &(start, len))) in input_shape.dimensions().iter().zip(&self.ranges).enumerate() {
+			if let NodeDim::Known(dim) = *dim {
+				ensure!(start + len <= dim, "Slice op ({}) range {}..{} is out of bounds for axis {} of size {}", name, start, start + len, axis, dim);
+			}
+		}
+
+		Ok(SliceInstance{
+			name: name,
+			input_id: self.input_id.clone(),
+			output_id: self.output_id.clone(),
+			ranges: self.ranges.clone(),
+			forward_id: graph.add_pass(SliceForward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				self.ranges.clone(),
+			)),
+			backward_id: graph.add_pass(SliceBackward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				self.ranges.clone(),
+			)),
+		})
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct SliceInstance {
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	ranges: Vec<(usize, usize)>,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl OpInstance for SliceInstance {
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(
+			vec![self.input_id.clone()],
+			vec![self.output_id.clone()]
+		)
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.forward_id.clone(), self.backward_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+
+		let input_shape = shapes.get_shape(&self.input_id).to_data_shape()?;
+
+		ensure!(input_shape.ndim() == self.ranges.len(), "Slice ranges must be the same length as input shape");
+		ensure!(input_shape.slice().iter().zip(&self.ranges).all(|(&dim, &(start, len))| start + len <= dim), "Slice ranges {:?} out of bounds for input shape {:?}", self.ranges, input_shape.slice());
+
+		let output_shape: NodeShape = self.ranges.iter().map(|&(_start, len)| len).into();
+
+		shapes.merge_with(&self.output_id, &output_shape)?;
+		Ok(())
+	}
+
+}
+
+fn check_shapes(pass_name: String, input_shape: &[usize], output_shape: &[usize], ranges: &[(usize, usize)]) -> Result<()> {
+	ensure!(input_shape.len() == ranges.len() && output_shape.len() == ranges.len(),
+		ErrorKind::PassError(pass_name, format!("ranges {:?} must have the same length as input shape {:?} and output shape {:?}", ranges, input_shape, output_shape)));
+	ensure!(input_shape.iter().zip(output_shape).zip(ranges).all(|((&i, &o), &(start, len))| o == len && start + len <= i),
+		ErrorKind::PassError(pass_name, format!("input shape {:?} and ranges {:?} incompatible with output shape {:?}", input_shape, ranges, output_shape)));
+	Ok(())
+}
+
+/// Restricts each axis of `arr` to its range
+fn slice_ranges<S: Data>(arr: &mut ArrayBase<S, IxDyn>, ranges: &[(usize, usize)]) {
+	for (axis, &(start, len)) in ranges.iter().enumerate() {
+		arr.slice_axis_inplace(Axis(axis), AxisSlice::from(start..start + len));
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct SliceForward {
+	input_id: NodeID,
+	output_id: NodeID,
+	ranges: Vec<(usize, usize)>,
+}
+
+impl SliceForward {
+	pub fn new(input_id: NodeID, output_id: NodeID, ranges: Vec<(usize, usize)>) -> Self{
+		SliceForward {
+			input_id,
+			output_id,
+			ranges,
+		}
+	}
+}
+
+impl Pass for SliceForward {
+	fn type_name(&self) -> &'static str {"SliceForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id()],
+		vec![self.output_id.value_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let mut input = data.get(&self.input_id.value_id())?;
+		let mut output = data.get_mut(&self.output_id.value_id())?;
+
+		check_shapes(self.name(), input.shape(), output.shape(), &self.ranges)?;
+
+		slice_ranges(&mut input, &self.ranges);
+		output += &input;
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct SliceBackward {
+	input_id: NodeID,
+	output_id: NodeID,
+	ranges: Vec<(usize, usize)>,
+}
+
+impl SliceBackward {
+	pub fn new(input_id: NodeID, output_id: NodeID, ranges: Vec<(usize, usize)>) -> Self{
+		SliceBackward {
+			input_id,
+			output_id,
+			ranges,
+		}
+	}
+}
+
+impl Pass for SliceBackward {
+	fn type_name(&self) -> &'static str {"SliceBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.output_id.gradient_id()],
+		vec![self.input_id.gradient_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let mut input_grad = data.get_mut(&self.input_id.gradient_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+
+		check_shapes(self.name(), input_grad.shape(), output_grad.shape(), &self.ranges)?;
+
+		slice_ranges(&mut input_grad, &self.ranges);
+		input_grad += &output_grad;
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_slice_backprop(){
+	_slice_backprop().unwrap();
+}
+
+fn _slice_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 5, 5, 3], "input", tag![])?;
+	let node2 = g.new_node(shape![Unknown, Unknown, Unknown, Unknown], "output", tag![])?;
+	let node3 = g.new_node(shape![2, 3, 3, 3], "target", tag![])?;
+
+	let _o1 = g.new_op(Slice::new(&node1, &node2, &[(0, 2), (1, 3), (1, 3), (0, 3)]), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_slice_values(){
+	_slice_values().unwrap();
+}
+
+fn _slice_values() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::proportional::Proportional;
+	use ndarray::ArrayD;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 5, 5, 3], "input", tag![])?;
+	let node2 = g.new_node(shape![Unknown, Unknown, Unknown, Unknown], "output", tag![])?;
+
+	let _o1 = g.new_op(Slice::new(&node1, &node2, &[(0, 2), (1, 3), (1, 3), (0, 3)]), tag![])?;
+	let _o2 = g.new_op(Proportional::new(&node2).multiplier(54.0), tag![])?;
+
+	let input = ArrayD::from_shape_fn(IxDyn(&[2, 5, 5, 3]), |idx| (idx[0] * 1000 + idx[1] * 100 + idx[2] * 10 + idx[3]) as f32);
+
+	let mut subgraph = g.subgraph(&[node1.value_id()], &[node2.value_id(), node1.gradient_id()])?;
+	let storage = subgraph.execute(vec![input.clone()])?;
+	let output = storage.get(&node2.value_id())?;
+	let grad = storage.get(&node1.gradient_id())?;
+
+	assert_eq!(output.shape(), &[2, 3, 3, 3]);
+	for (idx, &x) in output.indexed_iter() {
+		assert_eq!(x, input[[idx[0], idx[1] + 1, idx[2] + 1, idx[3]]]);
+	}
+
+	// Proportional applies a gradient of 54/54 to each output element, which should only reach the cropped region
+	for (idx, &x) in grad.indexed_iter() {
+		let inside = idx[1] >= 1 && idx[1] < 4 && idx[2] >= 1 && idx[2] < 4;
+		assert_eq!(x, if inside {1.0} else {0.0}, "{:?}", idx.slice());
+	}
+
+	Ok(())
+}
+
+#[test]
+fn test_slice_out_of_bounds(){
+	use graph::GraphDef;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 5], "input", tag![]).unwrap();
+	let node2 = g.new_node(shape![2, 3], "output", tag![]).unwrap();
+
+	assert!(g.new_op(Slice::new(&node1, &node2, &[(0, 2), (3, 3)]), tag![]).is_err());
+	assert!(g.new_op(Slice::new(&node1, &node2, &[(0, 2)]), tag![]).is_err());
+	assert!(g.new_op(Slice::new(&node1, &node2, &[(0, 2), (2, 3)]), tag![]).is_ok());
+}
@@ -0,0 +1,382 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use shape::{NodeShape, NodeDim};
+use ndarray::{Axis, Dimension, Slice as AxisSlice};
+use std::any::Any;
+
+/// Split operation
+///
+/// Divides the input along an axis into consecutive parts, one for each output, e.g. to separate the heads of a multi-head layer.
+/// By default the parts are of equal size, alternatively the size of each part can be set using `sizes()`.
+/// In the backward pass the gradient of each output is added to the corresponding part of the input gradient.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct Split {
+	name: Option<String>,
+	input_id: NodeID,
+	output_ids: Vec<NodeID>,
+	axis: isize,
+	sizes: Option<Vec<usize>>,
+}
+
+impl Split {
+	/// Creates a new `Split` Op, which divides the input along `axis` into one part for each output.
+	///
+	/// `axis` can be in the range [-input.ndims(), input.ndims()).
+	pub fn new(input_id: &NodeID, output_ids: &[NodeID], axis: isize) -> Self{
+		Split {
+			name: None,
+			input_id: input_id.clone(),
+			output_ids: output_ids.to_vec(),
+			axis: axis,
+			sizes: None,
+		}
+	}
+
+	/// The size of each part along the split axis, which must sum to the size of the input axis.
+	///
+	/// Default: `None`, the input axis is divided equally between the outputs.
+	pub fn sizes(mut self, sizes: &[usize]) -> Self {
+		self.sizes = Some(sizes.to_vec());
+		self
+	}
+}
+
+impl Op for Split {
+	type InstanceType = SplitInstance;
+
+	fn type_name(&self) -> &'static str {
+		"Split"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &self.output_ids);
+
+		ensure!(self.output_ids.len() > 0, "Split op ({}) requires at least one output", name);
+		if let Some(ref sizes) = self.sizes {
+			ensure!(sizes.len() == self.output_ids.len(), "Split op ({}) was given {} sizes, but has {} outputs", name, sizes.len(), self.output_ids.len());
+		}
+
+		let input_shape = self.input_id.shape();
+		let ndim = input_shape.ndim();
+		ensure!(self.axis >= -(ndim as isize) && self.axis < ndim as isize, "Split op ({}) axis {} is out of range for input with {} axes", name, self.axis, ndim);
+		let axis = (self.axis + ndim as isize) as usize % ndim;
+
+		if let NodeDim::Known(dim) = input_shape.dimensions()[axis] {
+			split_sizes(dim, self.output_ids.len(), &self.sizes).map_err(|err| format!("Split op ({}): {}", name, err))?;
+		}
+
+		Ok(SplitInstance{
+			name: name,
+			input_id: self.input_id.clone(),
+			output_ids: self.output_ids.clone(),
+			axis: axis,
+			sizes: self.sizes.clone(),
+			forward_id: graph.add_pass(SplitForward::new(
+				self.input_id.clone(),
+				self.output_ids.clone(),
+				axis,
+				self.sizes.clone(),
+			)),
+			backward_id: graph.add_pass(SplitBackward::new(
+				self.input_id.clone(),
+				self.output_ids.clone(),
+				axis,
+				self.sizes.clone(),
+			)),
+		})
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct SplitInstance {
+	name: String,
+	input_id: NodeID,
+	output_ids: Vec<NodeID>,
+	axis: usize,
+	sizes: Option<Vec<usize>>,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl SplitInstance {
+	/// The axis which is split, in the range [0, input.ndims())
+	pub fn axis(&self) -> usize {
+		self.axis
+	}
+}
+
+impl OpInstance for SplitInstance {
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(
+			vec![self.input_id.clone()],
+			self.output_ids.clone()
+		)
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.forward_id.clone(), self.backward_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+
+		let input_shape = shapes.get_shape(&self.input_id).to_data_shape()?;
+		let sizes = split_sizes(input_shape[self.axis], self.output_ids.len(), &self.sizes)?;
+
+		for (output_id, size) in self.output_ids.iter().zip(sizes) {
+			let output_shape: NodeShape = input_shape.slice().iter().enumerate().map(|(i, &dim)| if i == self.axis {size} else {dim}).into();
+			shapes.merge_with(output_id, &output_shape)?;
+		}
+		Ok(())
+	}
+
+}
+
+/// Returns the size of each part, checking that the parts exactly cover an axis of size `dim`
+fn split_sizes(dim: usize, outputs: usize, sizes: &Option<Vec<usize>>) -> Result<Vec<usize>> {
+	match *sizes {
+		Some(ref sizes) => {
+			ensure!(sizes.len() == outputs, "{} sizes were given for {} outputs", sizes.len(), outputs);
+			ensure!(sizes.iter().sum::<usize>() == dim, "sizes {:?} do not sum to the axis size {}", sizes, dim);
+			Ok(sizes.clone())
+		},
+		None => {
+			ensure!(dim % outputs == 0, "axis size {} is not divisible into {} equal parts", dim, outputs);
+			Ok(vec![dim / outputs; outputs])
+		}
+	}
+}
+
+/// Returns the `(start, len)` range of each part along the axis, checking the shapes of the input and outputs
+fn check_shapes(pass_name: String, input_shape: &[usize], output_shapes: &[Option<Vec<usize>>], axis: usize, sizes: &Option<Vec<usize>>) -> Result<Vec<(usize, usize)>> {
+	ensure!(axis < input_shape.len(), ErrorKind::PassError(pass_name.clone(), format!("axis {} is out of range for input shape: {:?}", axis, input_shape)));
+	let sizes = match split_sizes(input_shape[axis], output_shapes.len(), sizes) {
+		Ok(sizes) => sizes,
+		Err(err) => bail!(ErrorKind::PassError(pass_name, err.to_string())),
+	};
+
+	let mut ranges = vec![];
+	let mut start = 0;
+	for (output_shape, size) in output_shapes.iter().zip(sizes) {
+		if let Some(ref output_shape) = *output_shape {
+			let expected: Vec<usize> = input_shape.iter().enumerate().map(|(i, &dim)| if i == axis {size} else {dim}).collect();
+			ensure!(output_shape == &expected,
+				ErrorKind::PassError(pass_name.clone(), format!("output shape {:?} did not match expected shape {:?} for input shape {:?}", output_shape, expected, input_shape)));
+		}
+		ranges.push((start, size));
+		start += size;
+	}
+	Ok(ranges)
+}
+
+
+#[derive(Debug, Clone)]
+pub struct SplitForward {
+	input_id: NodeID,
+	output_ids: Vec<NodeID>,
+	axis: usize,
+	sizes: Option<Vec<usize>>,
+}
+
+impl SplitForward {
+	pub fn new(input_id: NodeID, output_ids: Vec<NodeID>, axis: usize, sizes: Option<Vec<usize>>) -> Self{
+		SplitForward {
+			input_id,
+			output_ids,
+			axis,
+			sizes,
+		}
+	}
+}
+
+impl Pass for SplitForward {
+	fn type_name(&self) -> &'static str {"SplitForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id()],
+		self.output_ids.iter().map(|id| id.value_id()).collect())
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+
+		let mut outputs = vec![];
+		for output_id in &self.output_ids {
+			if data.is_required(&output_id.value_id()) {
+				outputs.push(Some(data.get_mut(&output_id.value_id())?));
+			} else {
+				outputs.push(None);
+			}
+		}
+
+		let output_shapes: Vec<_> = outputs.iter().map(|output| output.as_ref().map(|output| output.shape().to_vec())).collect();
+		let ranges = check_shapes(self.name(), input.shape(), &output_shapes, self.axis, &self.sizes)?;
+
+		for (output, (start, len)) in outputs.iter_mut().zip(ranges) {
+			if let Some(ref mut output) = *output {
+				*output += &input.slice_axis(Axis(self.axis), AxisSlice::from(start..start + len));
+			}
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct SplitBackward {
+	input_id: NodeID,
+	output_ids: Vec<NodeID>,
+	axis: usize,
+	sizes: Option<Vec<usize>>,
+}
+
+impl SplitBackward {
+	pub fn new(input_id: NodeID, output_ids: Vec<NodeID>, axis: usize, sizes: Option<Vec<usize>>) -> Self{
+		SplitBackward {
+			input_id,
+			output_ids,
+			axis,
+			sizes,
+		}
+	}
+}
+
+impl Pass for SplitBackward {
+	fn type_name(&self) -> &'static str {"SplitBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(self.output_ids.iter().map(|id| id.gradient_id()).collect(),
+		vec![self.input_id.gradient_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let mut input_grad = data.get_mut(&self.input_id.gradient_id())?;
+
+		let mut output_grads = vec![];
+		for output_id in &self.output_ids {
+			output_grads.push(data.get(&output_id.gradient_id())?);
+		}
+
+		let output_shapes: Vec<_> = output_grads.iter().map(|output_grad| Some(output_grad.shape().to_vec())).collect();
+		let ranges = check_shapes(self.name(), input_grad.shape(), &output_shapes, self.axis, &self.sizes)?;
+
+		for (output_grad, (start, len)) in output_grads.iter().zip(ranges) {
+			let mut input_grad_part = input_grad.slice_axis_mut(Axis(self.axis), AxisSlice::from(start..start + len));
+			input_grad_part += output_grad;
+		}
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[test]
+fn test_split_backprop(){
+	_split_backprop().unwrap();
+}
+
+fn _split_backprop() -> Result<()>{
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 3, 8], "input", tag![])?;
+	let node2 = g.new_node(shape![Unknown, Unknown, Unknown], "output1", tag![])?;
+	let node3 = g.new_node(shape![Unknown, Unknown, Unknown], "output2", tag![])?;
+	let node4 = g.new_node(shape![2, 3, 4], "target1", tag![])?;
+	let node5 = g.new_node(shape![2, 3, 4], "target2", tag![])?;
+
+	let _o1 = g.new_op(Split::new(&node1, &[node2.clone(), node3.clone()], 2), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node4), tag![])?;
+	let _o3 = g.new_op(Mse::new(&node3, &node5), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.002;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_split_values(){
+	_split_values().unwrap();
+}
+
+fn _split_values() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::proportional::Proportional;
+	use ndarray::{ArrayD, IxDyn};
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 3, 8], "input", tag![])?;
+	let node2 = g.new_node(shape![Unknown, Unknown, Unknown], "output1", tag![])?;
+	let node3 = g.new_node(shape![Unknown, Unknown, Unknown], "output2", tag![])?;
+
+	let _o1 = g.new_op(Split::new(&node1, &[node2.clone(), node3.clone()], -1).sizes(&[3, 5]), tag![])?;
+	let _o2 = g.new_op(Proportional::new(&node2).multiplier(18.0), tag![])?;
+	let _o3 = g.new_op(Proportional::new(&node3).multiplier(-60.0), tag![])?;
+
+	let input = ArrayD::from_shape_fn(IxDyn(&[2, 3, 8]), |idx| (idx[0] * 100 + idx[1] * 10 + idx[2]) as f32);
+
+	let mut subgraph = g.subgraph(&[node1.value_id()], &[node2.value_id(), node3.value_id(), node1.gradient_id()])?;
+	let storage = subgraph.execute(vec![input.clone()])?;
+	let output1 = storage.get(&node2.value_id())?;
+	let output2 = storage.get(&node3.value_id())?;
+	let grad = storage.get(&node1.gradient_id())?;
+
+	assert_eq!(output1.shape(), &[2, 3, 3]);
+	assert_eq!(output2.shape(), &[2, 3, 5]);
+	for (idx, &x) in output1.indexed_iter() {
+		assert_eq!(x, input[[idx[0], idx[1], idx[2]]]);
+	}
+	for (idx, &x) in output2.indexed_iter() {
+		assert_eq!(x, input[[idx[0], idx[1], idx[2] + 3]]);
+	}
+
+	// Proportional applies a gradient of multiplier/size to each element, routed back to the part of the input each output came from
+	for (idx, &x) in grad.indexed_iter() {
+		assert_eq!(x, if idx[2] < 3 {1.0} else {-2.0}, "{:?}", idx.slice());
+	}
+
+	Ok(())
+}
+
+#[test]
+fn test_split_build_checks(){
+	use graph::GraphDef;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 3, 8], "input", tag![]).unwrap();
+	let node2 = g.new_node(shape![Unknown, Unknown, Unknown], "output1", tag![]).unwrap();
+	let node3 = g.new_node(shape![Unknown, Unknown, Unknown], "output2", tag![]).unwrap();
+	let node4 = g.new_node(shape![Unknown, Unknown, Unknown], "output3", tag![]).unwrap();
+	let outputs = [node2.clone(), node3.clone(), node4.clone()];
+
+	assert!(g.new_op(Split::new(&node1, &outputs, 2), tag![]).is_err());
+	assert!(g.new_op(Split::new(&node1, &outputs, 3), tag![]).is_err());
+	assert!(g.new_op(Split::new(&node1, &outputs, 2).sizes(&[2, 2, 2]), tag![]).is_err());
+	assert!(g.new_op(Split::new(&node1, &outputs[..2], 2).sizes(&[2, 3, 3]), tag![]).is_err());
+	assert!(g.new_op(Split::new(&node1, &outputs, 2).sizes(&[2, 3, 3]), tag![]).is_ok());
+	assert!(g.new_op(Split::new(&node1, &outputs, 1), tag![]).is_ok());
+}
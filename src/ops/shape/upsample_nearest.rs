@@ -0,0 +1,308 @@
+use graph::{GraphDef, GraphShapes, ErrorKind, Result};
+use id::{NodeID, DataID, OpID, PassID};
+use storage::Storage;
+use ops::{standard_op_name, Op, OpInstance, Pass};
+use shape::NodeShape;
+use ndarray::Dimension;
+use std::any::Any;
+use smallvec::SmallVec;
+
+/// UpsampleNearest implements nearest neighbour upscaling
+///
+/// Increase size of each dimension by the given factor by repeating each spaxel of the input.
+/// Gradients from each copy are accumulated back to the source spaxel.
+/// Factors of 1 leave a dimension unchanged, e.g. `&[1, 2, 2, 1]` doubles the height and width of a `[batch, H, W, channels]` input.
+///
+/// For smooth upscaling use `Linterp`, which interpolates linearly between spaxels.
+#[must_use]
+#[derive(Clone, Debug)]
+pub struct UpsampleNearest {
+	name: Option<String>,
+	factors: Vec<usize>,
+	input_id: NodeID,
+	output_id: NodeID,
+}
+
+impl UpsampleNearest {
+	pub fn new(input_id: &NodeID, output_id: &NodeID, factors: &[usize]) -> Self{
+		UpsampleNearest {
+			name: None,
+			input_id: input_id.clone(),
+			output_id: output_id.clone(),
+			factors: factors.to_vec(),
+		}
+	}
+}
+
+impl Op for UpsampleNearest {
+	type InstanceType = UpsampleNearestInstance;
+
+	fn type_name(&self) -> &'static str {
+		"UpsampleNearest"
+	}
+
+	fn name<T: Into<String>>(mut self, name: T) -> Self{
+		self.name = Some(name.into());
+		self
+	}
+
+	fn build(self, graph: &mut GraphDef) -> Result<Self::InstanceType> {
+		ensure!(self.factors.iter().all(|&f| f > 0), format!("UpsampleNearest factors {:?} must be greater than zero", self.factors));
+		let name = standard_op_name(&self, &self.name, graph, &[self.input_id.clone()], &[self.output_id.clone()]);
+
+		Ok(UpsampleNearestInstance{
+			name: name,
+			input_id: self.input_id.clone(),
+			output_id: self.output_id.clone(),
+			factors: self.factors.clone(),
+			forward_id: graph.add_pass(UpsampleNearestForward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				self.factors.clone(),
+			)),
+			backward_id: graph.add_pass(UpsampleNearestBackward::new(
+				self.input_id.clone(),
+				self.output_id.clone(),
+				self.factors.clone(),
+			)),
+		})
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct UpsampleNearestInstance {
+	name: String,
+	input_id: NodeID,
+	output_id: NodeID,
+	factors: Vec<usize>,
+	forward_id: PassID,
+	backward_id: PassID,
+}
+
+impl OpInstance for UpsampleNearestInstance {
+	fn name(&self) -> &str {&self.name}
+
+	fn dependencies(&self) -> (Vec<NodeID>, Vec<NodeID>){
+		(
+			vec![self.input_id.clone()],
+			vec![self.output_id.clone()]
+		)
+	}
+
+	fn inner_passes(&self) -> Vec<PassID> {
+		vec![self.forward_id.clone(), self.backward_id.clone()]
+	}
+
+	fn inner_ops(&self) -> Vec<OpID> {vec![]}
+
+	fn inner_nodes(&self) -> Vec<NodeID> {vec![]}
+
+	fn propagate_shape_constraints(&self, shapes: &mut GraphShapes) -> Result<()>{
+		let input_shape = shapes.get_shape(&self.input_id).to_data_shape()?;
+
+		ensure!(input_shape.ndim() == self.factors.len(), "expansion factors must be the same length as input shape");
+
+		let output_shape: NodeShape = input_shape.slice().iter().zip(&self.factors).map(|(dim, f)| dim * f).into();
+
+		shapes.merge_with(&self.output_id, &output_shape)
+	}
+}
+
+
+#[derive(Debug, Clone)]
+struct UpsampleNearestForward {
+	input_id: NodeID,
+	output_id: NodeID,
+	factors: Vec<usize>,
+}
+
+impl UpsampleNearestForward {
+	pub fn new(input_id: NodeID, output_id: NodeID, factors: Vec<usize>) -> Self{
+		UpsampleNearestForward {
+			input_id,
+			output_id,
+			factors,
+		}
+	}
+}
+
+impl Pass for UpsampleNearestForward {
+	fn type_name(&self) -> &'static str {"UpsampleNearestForward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.input_id.value_id()],
+		vec![self.output_id.value_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let input = data.get(&self.input_id.value_id())?;
+		let mut output = data.get_mut(&self.output_id.value_id())?;
+
+		check_shapes(self.name(), input.shape(), output.shape(), &self.factors)?;
+
+		let input_shape = input.shape().to_vec();
+		let input = input.as_slice().unwrap();
+		let output = output.as_slice_mut().unwrap();
+
+		for_each_source(&input_shape, &self.factors, |i, o| {
+			output[o] += input[i];
+		});
+
+		Ok(Box::new(()))
+	}
+}
+
+
+#[derive(Debug, Clone)]
+struct UpsampleNearestBackward {
+	input_id: NodeID,
+	output_id: NodeID,
+	factors: Vec<usize>,
+}
+
+impl UpsampleNearestBackward {
+	pub fn new(input_id: NodeID, output_id: NodeID, factors: Vec<usize>) -> Self{
+		UpsampleNearestBackward {
+			input_id,
+			output_id,
+			factors,
+		}
+	}
+}
+
+impl Pass for UpsampleNearestBackward {
+	fn type_name(&self) -> &'static str {"UpsampleNearestBackward"}
+
+	fn dependencies(&self) -> (Vec<DataID>, Vec<DataID>){
+		(vec![self.output_id.gradient_id()],
+		vec![self.input_id.gradient_id()])
+	}
+
+	fn run(&self, data: &Storage) -> Result<Box<Any>> {
+		let mut input_grad = data.get_mut(&self.input_id.gradient_id())?;
+		let output_grad = data.get(&self.output_id.gradient_id())?;
+
+		check_shapes(self.name(), input_grad.shape(), output_grad.shape(), &self.factors)?;
+
+		let input_shape = input_grad.shape().to_vec();
+		let input_grad = input_grad.as_slice_mut().unwrap();
+		let output_grad = output_grad.as_slice().unwrap();
+
+		for_each_source(&input_shape, &self.factors, |i, o| {
+			input_grad[i] += output_grad[o];
+		});
+
+		Ok(Box::new(()))
+	}
+}
+
+fn check_shapes(pass_name: String, input_shape: &[usize], output_shape: &[usize], factors: &[usize]) -> Result<()> {
+	ensure!(input_shape.len() == factors.len() && output_shape.len() == factors.len()
+		&& input_shape.iter().zip(factors).map(|(i, f)| i * f).eq(output_shape.iter().cloned()),
+		ErrorKind::PassError(pass_name, format!("input shape {:?} and factors {:?} incompatible with output shape {:?}", input_shape, factors, output_shape)));
+	Ok(())
+}
+
+/// Calls `f(input_index, output_index)` for every element of the output, with the index of the input element it is copied from
+fn for_each_source<F: FnMut(usize, usize)>(input_shape: &[usize], factors: &[usize], mut f: F) {
+	if input_shape.len() == 0 {
+		f(0, 0);
+		return;
+	}
+	let input_strides = strides(input_shape.iter().cloned());
+	let output_strides = strides(input_shape.iter().zip(factors).map(|(i, f)| i * f));
+	for_each_source_recurse(input_shape, factors, &input_strides, &output_strides, 0, 0, 0, &mut f);
+}
+
+fn for_each_source_recurse<F: FnMut(usize, usize)>(input_shape: &[usize], factors: &[usize], input_strides: &[usize], output_strides: &[usize],
+	axis: usize, input_offset: usize, output_offset: usize, f: &mut F) {
+	for o in 0..input_shape[axis] * factors[axis] {
+		let input_ind = input_offset + o / factors[axis] * input_strides[axis];
+		let output_ind = output_offset + o * output_strides[axis];
+		if axis + 1 == input_shape.len() {
+			f(input_ind, output_ind);
+		} else {
+			for_each_source_recurse(input_shape, factors, input_strides, output_strides, axis + 1, input_ind, output_ind, f);
+		}
+	}
+}
+
+/// Returns the row major strides of a shape
+fn strides<I: DoubleEndedIterator<Item=usize>>(shape: I) -> SmallVec<[usize; 6]> {
+	let mut strides = shape.rev().scan(1, |state, i| {
+		let res = Some(*state);
+		*state *= i;
+		res
+	}).collect::<SmallVec<[usize; 6]>>();
+	strides.reverse();
+	strides
+}
+
+
+#[test]
+fn upsample_nearest_backprop(){
+	_upsample_nearest_backprop().unwrap();
+}
+
+fn _upsample_nearest_backprop() -> Result<()> {
+	use graph::GraphDef;
+	use ops::numeric_check::numeric_test;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let node1 = g.new_node(shape![2, 4, 3, 5], "input", tag![])?;
+	let node2 = g.new_node(shape![2, 8, 9, 5], "upsample", tag![])?;
+	let node3 = g.new_node(shape![2, 8, 9, 5], "target", tag![])?;
+
+	let _o1 = g.new_op(UpsampleNearest::new(&node1, &node2, &[1, 2, 3, 1]), tag![])?;
+	let _o2 = g.new_op(Mse::new(&node2, &node3), tag![])?;
+
+	let iters = 100;
+	let failures = 1;
+	let tolerance = 0.01;
+	let step_size = 1E-2;
+	let default_variance = 1.0;
+	numeric_test(iters, failures, tolerance, &g, step_size, default_variance, &mut indexmap![])?;
+
+	Ok(())
+}
+
+#[test]
+fn test_upsample_nearest(){
+	_test_upsample_nearest().unwrap();
+}
+
+fn _test_upsample_nearest() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::proportional::Proportional;
+	use ndarray::{ArrayD, IxDyn};
+
+	let mut g = GraphDef::new();
+	let input = g.new_node(shape![1, 2, 2, 1], "input", tag![])?;
+	let output = g.new_node(shape![1, Unknown, Unknown, 1], "output", tag![])?;
+	let _o1 = g.new_op(UpsampleNearest::new(&input, &output, &[1, 2, 3, 1]), tag![])?;
+	let _o2 = g.new_op(Proportional::new(&output), tag![])?;
+
+	let data_in = ArrayD::from_shape_vec(IxDyn(&[1, 2, 2, 1]), vec![1.0, 2.0, 3.0, 4.0]).unwrap();
+
+	let mut subgraph = g.subgraph(&[input.value_id()], &[output.value_id(), input.gradient_id()])?;
+	let storage = subgraph.execute(vec![data_in])?;
+
+	let out = storage.get(&output.value_id())?;
+	assert_eq!(out.shape(), &[1, 4, 6, 1]);
+
+	let expected = vec![
+		1.0, 1.0, 1.0, 2.0, 2.0, 2.0,
+		1.0, 1.0, 1.0, 2.0, 2.0, 2.0,
+		3.0, 3.0, 3.0, 4.0, 4.0, 4.0,
+		3.0, 3.0, 3.0, 4.0, 4.0, 4.0,
+	];
+	assert_eq!(out.as_slice().unwrap(), &expected[..]);
+
+	// each input receives the gradient from all 6 of its copies, with Proportional passing 1/24 to each output
+	let input_grad = storage.get(&input.gradient_id())?;
+	assert!(input_grad.iter().all(|&x| (x - 6.0/24.0).abs() < 1e-6), "{:?}", input_grad);
+
+	Ok(())
+}
@@ -1,9 +1,13 @@
 use graph::{GraphDef, Subgraph, Result};
 use id::{NodeTag, NodeID, DataID};
 use opt::{Opt, CallbackData, CallbackSignal};
+use opt::pipeline::GradPipeline;
+use opt::moments::Moments;
+use opt::state::{OptState, save_state};
 use ndarray::{ArrayD, Zip};
 use std::num::FpCategory;
-use rayon::prelude::*;
+use std::path::Path;
+use std::io;
 
 /// Adam Optimiser
 ///
@@ -20,13 +24,11 @@ pub struct Adam {
 	parameters: Vec<NodeID>,
 	callbacks: Vec<Box<FnMut(&CallbackData)->CallbackSignal>>,
 	rate: f32,
-	beta1: f32,
-	beta2: f32,
-	epsilon: f32,
+	moments: Moments,
 	bias_correct: bool,
-	momentum_vec: Vec<ArrayD<f32>>,
-	curvature_vec: Vec<ArrayD<f32>>,
 	step_count: usize,
+	pipeline: GradPipeline,
+	reset_each_epoch: bool,
 }
 
 
@@ -37,20 +39,9 @@ impl Adam {
 
 		let subgraph = graph.default_subgraph()?;
 
-		Ok(Adam {
-			inputs: subgraph.inputs().iter().filter(|data_id| !data_id.tags().contains(&NodeTag::Parameter)).cloned().collect(),
-			parameters: subgraph.inputs().iter().filter_map(|data_id| if data_id.tags().contains(&NodeTag::Parameter) {Some(data_id.node_id())} else {None}).collect(),
-			subgraph: subgraph,
-			callbacks: vec![],
-			rate: 1e-3,
-			beta1: 0.9,
-			beta2: 0.995,
-			epsilon: 1e-8,
-			bias_correct: true,
-			momentum_vec: vec![],
-			curvature_vec: vec![],
-			step_count: 0,
-		})
+		let inputs = subgraph.inputs().iter().filter(|data_id| !data_id.tags().contains(&NodeTag::Parameter)).cloned().collect();
+		let parameters = subgraph.inputs().iter().filter_map(|data_id| if data_id.tags().contains(&NodeTag::Parameter) {Some(data_id.node_id())} else {None}).collect();
+		Ok(Adam::from_parts(subgraph, inputs, parameters))
 	}
 
 	/// Define a custom optimisation problem by supplying a subgraph and a list of parameters to optimise.
@@ -70,19 +61,21 @@ impl Adam {
 		assert!(parameter_ids.iter().all(|id| subgraph.outputs().contains(&id.value_id())), "Subgraph outputs must contain all parameter values");
 		assert!(parameter_ids.iter().all(|id| subgraph.outputs().contains(&id.gradient_id())), "Subgraph outputs must contain all parameter gradients");
 
+		Adam::from_parts(subgraph, maybe_inputs, parameter_ids)
+	}
+
+	fn from_parts(subgraph: Subgraph, inputs: Vec<DataID>, parameters: Vec<NodeID>) -> Self {
 		Adam {
-			inputs: maybe_inputs,
-			parameters: parameter_ids,
+			pipeline: GradPipeline::new(parameters.clone()),
+			inputs: inputs,
+			parameters: parameters,
 			subgraph: subgraph,
 			callbacks: vec![],
 			rate: 1e-3,
-			beta1: 0.9,
-			beta2: 0.995,
-			epsilon: 1e-7,
+			moments: Moments::new(0.995),
 			bias_correct: true,
-			momentum_vec: vec![],
-			curvature_vec: vec![],
 			step_count: 0,
+			reset_each_epoch: false,
 		}
 	}
 
@@ -96,7 +89,7 @@ impl Adam {
 	///
 	/// Default: 0.9
 	pub fn beta1(mut self, beta1: f32) -> Self{
-		self.beta1 = beta1;
+		self.moments.beta1 = beta1;
 		self
 	}
 
@@ -104,7 +97,7 @@ impl Adam {
 	///
 	/// Default: 0.995
 	pub fn beta2(mut self, beta2: f32) -> Self{
-		self.beta2 = beta2;
+		self.moments.beta2 = beta2;
 		self
 	}
 
@@ -113,7 +106,7 @@ impl Adam {
 	/// Sometimes worth increasing, according to google.
 	/// Default: 1e-8
 	pub fn epsilon(mut self, epsilon: f32) -> Self{
-		self.epsilon = epsilon;
+		self.moments.epsilon = epsilon;
 		self
 	}
 
@@ -125,6 +118,59 @@ impl Adam {
 		self.bias_correct = bias_correct;
 		self
 	}
+
+	/// Zero the momentum and curvature estimates at the end of each epoch of the training stream, see `Opt::reset_state()`
+	///
+	/// Epochs are taken from `DataStream::epoch_size()`, if the training stream does not report one this has no effect.
+	/// Bias correction restarts after each reset. The parameters, and the step count used by any rate schedule, are unaffected.
+	/// Default: false
+	pub fn reset_state_each_epoch(mut self, enable: bool) -> Self {
+		self.reset_each_epoch = enable;
+		self
+	}
+
+	/// Returns the current learning rate, α
+	pub fn learning_rate(&self) -> f32 {
+		self.rate
+	}
+
+	/// Returns the number of steps taken so far
+	pub fn step_count(&self) -> usize {
+		self.step_count
+	}
+
+	/// Returns the gradient processing applied around each update, e.g. loss scaling, clipping, and parameter averaging.
+	pub fn pipeline(&self) -> &GradPipeline {
+		&self.pipeline
+	}
+
+	/// Returns the gradient processing applied around each update, for configuration.
+	pub fn pipeline_mut(&mut self) -> &mut GradPipeline {
+		&mut self.pipeline
+	}
+
+	/// Writes the learning rate, step count, momentum and curvature vectors, and the pipeline's loss scale, warmup progress, and parameter moving average to a file,
+	/// so that optimisation can be resumed with `load_state()`.
+	///
+	/// The pipeline configuration, gradient activity counts, histograms, and gradient noise rng are not saved.
+	pub fn save_state<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+		save_state(path, self.step_count, self.moments.state_start, self.rate, &self.pipeline.state(), &[&self.moments.momentum_vec[..], &self.moments.curvature_vec[..], self.pipeline.ema_vec()])
+	}
+
+	/// Restores the state written by `save_state()`.
+	///
+	/// The pipeline should be configured before loading, as enabling loss scaling resets the scale.
+	/// Returns an error if the number or shapes of the saved arrays do not match the parameters of this optimiser.
+	pub fn load_state<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+		let OptState{step_count, state_start, rate, pipeline, mut vecs} = OptState::load(path, &self.parameters, 3)?;
+		self.step_count = step_count;
+		self.moments.state_start = state_start;
+		self.rate = rate;
+		self.pipeline.set_state(pipeline, vecs.pop().unwrap());
+		self.moments.curvature_vec = vecs.pop().unwrap();
+		self.moments.momentum_vec = vecs.pop().unwrap();
+		Ok(())
+	}
 }
 
 impl Opt for Adam {
@@ -141,52 +187,24 @@ impl Opt for Adam {
 		&self.parameters
 	}
 
-	fn step(&mut self, mut inputs: Vec<ArrayD<f32>>, mut parameters: Vec<ArrayD<f32>>) -> Result<(f32, usize, f32, Vec<ArrayD<f32>>)> {
+	fn step(&mut self, inputs: Vec<ArrayD<f32>>, parameters: Vec<ArrayD<f32>>) -> Result<(f32, usize, f32, Vec<ArrayD<f32>>)> {
 		assert_eq!(inputs.len(), self.inputs().len(), "Incorrect number of inputs supplied to optimiser.step()");
-		assert_eq!(parameters.len(), self.parameters().len(), "Incorrect number of prameters supplied to optimiser.step()");
-
-		inputs.append(&mut parameters);
-		
-		assert_eq!(self.subgraph.inputs().len(), inputs.len());
-
-		let storage = self.subgraph.execute(inputs)?;
-		let loss = storage.loss();
-		let mut map = storage.into_map();
 
-		let mut params: Vec<_> = self.parameters.iter().map(|p| map.remove(&p.value_id()).expect("Subgraph must have parameter values as outputs.")).collect();
+		let base_rate = self.rate;
+		let epsilon = self.moments.epsilon;
+		let t = self.moments.t(self.step_count);
+		let (momentum_correction, curv_correction) = self.moments.corrections(t);
+		let momentum_correction = if self.bias_correct {momentum_correction} else {1.0};
+		let moments = &mut self.moments;
 
-		if self.momentum_vec.len() != self.parameters.len() {
-			self.momentum_vec = params.iter().map(|param| ArrayD::zeros(param.shape())).collect();
-		}
-		if self.curvature_vec.len() != self.parameters.len() {
-			self.curvature_vec = params.iter().map(|param| ArrayD::zeros(param.shape())).collect();
-		}
-
-		let rate = self.rate;
-		let beta1 = self.beta1;
-		let beta2 = self.beta2;
-		let epsilon = self.epsilon;
-		let momentum_correction = if self.step_count < 1_000_000{1.0/(1.0 - self.beta1.powi(self.step_count as i32 + 1))} else {1.0}; 
-		let curv_correction = if self.step_count < 1_000_000{1.0/(1.0 - self.beta2.powi(self.step_count as i32 + 1))} else {1.0}; 
-		
-		
-		//let momentum_vec = &mut self.momentum_vec;
-		//let curvature_vec = &mut self.curvature_vec;
-		let bias_correct = self.bias_correct;
-
-		
-		//for (i, param_grad) in self.parameters.iter().map(|p| map.remove(&p.gradient_id()).expect("Subgraph must have parameter gradients as outputs.")).enumerate() {
-		let param_grads: Vec<_> = self.parameters.iter().map(|p| map.remove(&p.gradient_id()).expect("Subgraph must have parameter gradients as outputs.")).collect();
-		let change_sqr: f32 = param_grads.par_iter().zip(self.momentum_vec.par_iter_mut()).zip(self.curvature_vec.par_iter_mut()).zip(params.par_iter_mut()).with_max_len(1).map(|(((param_grad_outer, momentum_outer), curvature_outer), params_outer)| {
-			let mut change_sqr = 0.0;
-			if bias_correct {
-				Zip::from(params_outer)
-					.and(momentum_outer)
-					.and(curvature_outer)
-					.and(param_grad_outer)
-					.apply(|param, momentum, curv, param_grad| {
-						*momentum = *momentum * beta1 + (1.0-beta1)*param_grad;
-						*curv = *curv * beta2 + (1.0-beta1)*param_grad*param_grad;
+		let (loss, change_norm, params) = self.pipeline.step(&mut self.subgraph, inputs, parameters, self.step_count, |rate_multiplier, params, param_grads| {
+			let rate = base_rate * rate_multiplier;
+			moments.update(params, param_grads, |params, momentum, curvature| {
+				let mut change_sqr = 0.0;
+				Zip::from(params)
+					.and(momentum)
+					.and(curvature)
+					.apply(|param, momentum, curv| {
 						let change = -rate * (*momentum) * momentum_correction/((*curv*curv_correction).sqrt() + epsilon);
 						change_sqr += change*change;
 						*param += change;
@@ -194,28 +212,17 @@ impl Opt for Adam {
 							*param = 0.0;
 						}
 					});
-			} else {
-				Zip::from(params_outer)
-					.and(momentum_outer)
-					.and(curvature_outer)
-					.and(param_grad_outer)
-					.apply(|param, momentum, curv, param_grad| {
-						*momentum = *momentum * beta1 + (1.0-beta1)*param_grad;
-						*curv = *curv * beta2 + (1.0-beta1)*param_grad*param_grad;
-						let change = -rate * (*momentum) /((*curv*curv_correction).sqrt() + epsilon);
-						change_sqr += change*change;
-						*param += change;
-						if let FpCategory::Subnormal = param.classify(){
-							*param = 0.0;
-						}
-					});
-			}
-			change_sqr
-		}).sum();
-
-		self.step_count += 1;
+				change_sqr
+			})
+		})?;
 
-		Ok((loss, self.step_count, change_sqr.sqrt(), params))
+		match change_norm {
+			Some(change_norm) => {
+				self.step_count += 1;
+				Ok((loss, self.step_count, change_norm, params))
+			},
+			None => Ok((loss, self.step_count, 0.0, params)),
+		}
 	}
 
 	fn callbacks(&mut self) -> &mut [Box<FnMut(&CallbackData)->CallbackSignal>]{
@@ -225,4 +232,220 @@ impl Opt for Adam {
 	fn add_boxed_callback(&mut self, func: Box<FnMut(&CallbackData)->CallbackSignal>){
 		self.callbacks.push(func)
 	}
-}
\ No newline at end of file
+
+	fn reset_state(&mut self) {
+		self.moments.reset(self.step_count);
+	}
+
+	fn resets_state_each_epoch(&self) -> bool {
+		self.reset_each_epoch
+	}
+}
+
+
+#[test]
+fn test_adam_constructor_defaults(){
+	_test_adam_constructor_defaults().unwrap();
+}
+
+fn _test_adam_constructor_defaults() -> Result<()>{
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Mse::new(&param, &input), tag![])?;
+
+	let opt1 = Adam::new(&g)?;
+	let opt2 = Adam::with_subgraph(g.default_subgraph()?, vec![param.clone()]);
+
+	assert_eq!(opt1.rate, opt2.rate);
+	assert_eq!(opt1.moments.beta1, opt2.moments.beta1);
+	assert_eq!(opt1.moments.beta2, opt2.moments.beta2);
+	assert_eq!(opt1.moments.epsilon, opt2.moments.epsilon);
+	assert_eq!(opt1.bias_correct, opt2.bias_correct);
+
+	Ok(())
+}
+
+#[test]
+fn test_adam_update(){
+	_test_adam_update().unwrap();
+}
+
+fn _test_adam_update() -> Result<()>{
+	use ops::loss::proportional::Proportional;
+
+	let mut g = GraphDef::new();
+
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	// each element has a constant gradient of 0.5
+	let _o1 = g.new_op(Proportional::new(&param).multiplier(6.0), tag![])?;
+
+	let mut opt = Adam::new(&g)?.rate(0.1).beta2(0.9).bias_correct(false);
+
+	// m = 0.1*0.5, v = (1 - 0.9)*0.5*0.5, v_c = v/(1 - 0.9)
+	let (_, _, _, params) = opt.step(vec![], vec![ArrayD::zeros(&[4, 3][..])])?;
+	assert!(opt.moments.momentum_vec[0].iter().all(|&m| (m - 0.05).abs() < 1e-7));
+	assert!(opt.moments.curvature_vec[0].iter().all(|&v| (v - 0.025).abs() < 1e-7));
+	assert!(params[0].iter().all(|&p| (p + 0.1*0.05/0.5).abs() < 1e-6), "{:?}", params[0]);
+
+	// m = 0.9*0.05 + 0.05, v = 0.9*0.025 + 0.025, v_c = v/(1 - 0.9^2)
+	let (_, _, _, params) = opt.step(vec![], params)?;
+	assert!(opt.moments.momentum_vec[0].iter().all(|&m| (m - 0.095).abs() < 1e-7));
+	assert!(opt.moments.curvature_vec[0].iter().all(|&v| (v - 0.0475).abs() < 1e-7));
+	assert!(params[0].iter().all(|&p| (p + 0.01 + 0.1*0.095/0.5).abs() < 1e-6), "{:?}", params[0]);
+
+	Ok(())
+}
+
+#[test]
+fn test_adam_state_round_trip(){
+	_test_adam_state_round_trip().unwrap();
+}
+
+fn _test_adam_state_round_trip() -> Result<()>{
+	use ops::loss::mse::Mse;
+	use opt::temp_path;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Mse::new(&param, &input), tag![])?;
+
+	let input_data = ArrayD::from_shape_fn(&[4, 3][..], |idx| (idx[0] * 3 + idx[1]) as f32);
+
+	let mut opt1 = Adam::new(&g)?.rate(0.1);
+	let params = g.initialise_nodes(opt1.parameters())?;
+	let (_, _, _, params) = opt1.step(vec![input_data.clone()], params)?;
+	let (_, _, _, params) = opt1.step(vec![input_data.clone()], params)?;
+
+	let path = temp_path("adam_state_round_trip.bin");
+	opt1.save_state(&path).unwrap();
+
+	let mut opt2 = Adam::new(&g)?;
+	opt2.load_state(&path).unwrap();
+	assert_eq!(opt2.step_count(), 2);
+	assert_eq!(opt2.learning_rate(), 0.1);
+
+	let (err1, step1, change1, params1) = opt1.step(vec![input_data.clone()], params.clone())?;
+	let (err2, step2, change2, params2) = opt2.step(vec![input_data.clone()], params.clone())?;
+	assert_eq!(err1, err2);
+	assert_eq!(step1, step2);
+	assert_eq!(change1, change2);
+	assert_eq!(params1, params2);
+
+	// loading into an optimiser with different parameters fails
+	let mut g2 = GraphDef::new();
+	let input2 = g2.new_node(shape![5, 3], "input", tag![])?;
+	let param2 = g2.new_node(shape![5, 3], "param", tag![Parameter])?;
+	let _o2 = g2.new_op(Mse::new(&param2, &input2), tag![])?;
+	let mut opt3 = Adam::new(&g2)?;
+	assert!(opt3.load_state(&path).is_err());
+
+	::std::fs::remove_file(&path).ok();
+
+	Ok(())
+}
+
+#[test]
+fn test_adam_epsilon(){
+	_test_adam_epsilon().unwrap();
+}
+
+fn _test_adam_epsilon() -> Result<()>{
+	use ops::loss::proportional::Proportional;
+
+	let mut g = GraphDef::new();
+
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	// each element has a gradient of 1e-6, so the curvature estimate is near zero
+	let _o1 = g.new_op(Proportional::new(&param).multiplier(12e-6), tag![])?;
+
+	let change_norm = |epsilon: f32| -> Result<f32> {
+		let mut opt = Adam::new(&g)?.rate(0.1).epsilon(epsilon);
+		let (_err, _step, change_norm, _params) = opt.step(vec![], vec![ArrayD::zeros(&[4, 3][..])])?;
+		Ok(change_norm)
+	};
+
+	// with the default epsilon the update is a full step of the rate, a larger epsilon damps it
+	let small = change_norm(1e-8)?;
+	let large = change_norm(1e-3)?;
+	assert!((small - 0.1 * 12f32.sqrt()).abs() < 0.01, "{}", small);
+	assert!(large < small * 0.01, "{} {}", large, small);
+
+	Ok(())
+}
+
+#[test]
+fn test_adam_reset_state_each_epoch(){
+	_test_adam_reset_state_each_epoch().unwrap();
+}
+
+fn _test_adam_reset_state_each_epoch() -> Result<()>{
+	use ops::loss::mse::Mse;
+	use data::DataStream;
+	use opt::UnboxedCallbacks;
+	use opt::temp_path;
+
+	struct EpochStream;
+	impl DataStream for EpochStream {
+		fn next(&mut self) -> Vec<ArrayD<f32>>{
+			vec![ArrayD::ones(&[4, 3][..])]
+		}
+
+		fn epoch_size(&self) -> Option<usize> {
+			Some(3)
+		}
+	}
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Mse::new(&param, &input), tag![])?;
+
+	for steps in 1..8 {
+		let mut opt = Adam::new(&g)?.rate(0.01).reset_state_each_epoch(true);
+		opt.add_callback(move |data| if data.step >= steps {CallbackSignal::Stop} else {CallbackSignal::Continue});
+		let params = opt.optimise(&mut EpochStream, &g)?;
+
+		let mut reference = Adam::new(&g)?.rate(0.01);
+		reference.add_callback(move |data| if data.step >= steps {CallbackSignal::Stop} else {CallbackSignal::Continue});
+		let reference_params = reference.optimise(&mut EpochStream, &g)?;
+
+		// state is zeroed only at the end of each epoch of 3 steps
+		let zeroed = opt.moments.momentum_vec.iter().chain(&opt.moments.curvature_vec).all(|arr| arr.iter().all(|&x| x == 0.0));
+		assert_eq!(zeroed, steps % 3 == 0, "after {} steps", steps);
+		assert!(opt.moments.momentum_vec.iter().chain(&opt.moments.curvature_vec).all(|arr| arr.shape() == &[4, 3]));
+
+		// the first epoch is unaffected, and the reset at its end does not change the parameters
+		if steps < 3 {
+			assert_eq!(opt.moments.momentum_vec, reference.moments.momentum_vec);
+		}
+		if steps <= 3 {
+			assert_eq!(params, reference_params);
+		}
+	}
+
+	// epochs are counted in optimiser steps, so training resumed mid-epoch resets at the same steps
+	let mut opt1 = Adam::new(&g)?.rate(0.01).reset_state_each_epoch(true);
+	opt1.add_callback(|data| if data.step >= 4 {CallbackSignal::Stop} else {CallbackSignal::Continue});
+	let params = opt1.optimise(&mut EpochStream, &g)?;
+
+	let path = temp_path("adam_state_reset_each_epoch.bin");
+	opt1.save_state(&path).unwrap();
+	let mut opt2 = Adam::new(&g)?.reset_state_each_epoch(true);
+	opt2.load_state(&path).unwrap();
+	assert_eq!(opt2.moments.state_start, 3);
+
+	opt2.add_callback(|data| if data.step >= 6 {CallbackSignal::Stop} else {CallbackSignal::Continue});
+	let _params = opt2.optimise_from(&mut EpochStream, params)?;
+	assert_eq!(opt2.step_count(), 6);
+	assert_eq!(opt2.moments.state_start, 6);
+	assert!(opt2.moments.momentum_vec.iter().chain(&opt2.moments.curvature_vec).all(|arr| arr.iter().all(|&x| x == 0.0)));
+
+	Ok(())
+}
@@ -0,0 +1,37 @@
+use ndarray::ArrayD;
+
+/// Adaptive gradient clipping, which limits the norm of each parameter array's gradient relative to the norm of the parameter array.
+///
+/// Each gradient is rescaled if required so that `||g|| <= λ max(||θ||, ε)`.
+/// Parameters with small norms, which are easily disrupted by large updates, are clipped more aggressively than global norm clipping would.
+pub(crate) struct GradClip {
+	pub lambda: Option<f32>,
+	pub epsilon: f32,
+}
+
+impl GradClip {
+	/// Disabled by default, with ε = 1e-3.
+	pub fn new() -> Self {
+		GradClip {
+			lambda: None,
+			epsilon: 1e-3,
+		}
+	}
+
+	/// Rescales each gradient in place. Does nothing if disabled.
+	pub fn apply(&self, params: &[ArrayD<f32>], grads: &mut [ArrayD<f32>]) {
+		let lambda = match self.lambda {
+			Some(lambda) => lambda,
+			None => return,
+		};
+
+		for (param, grad) in params.iter().zip(grads.iter_mut()) {
+			let param_norm = param.iter().fold(0.0f32, |acc, &x| acc + x * x).sqrt();
+			let grad_norm = grad.iter().fold(0.0f32, |acc, &x| acc + x * x).sqrt();
+			let max_norm = lambda * param_norm.max(self.epsilon);
+			if grad_norm > max_norm {
+				*grad *= max_norm / grad_norm;
+			}
+		}
+	}
+}
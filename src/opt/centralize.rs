@@ -0,0 +1,29 @@
+use ndarray::ArrayD;
+
+/// Gradient centralisation, which subtracts the mean of each weight array's gradient from that gradient.
+///
+/// Only parameter arrays with more than one axis are treated as weights, so biases and other vectors are left unchanged.
+pub(crate) struct GradCentralization {
+	pub enabled: bool,
+}
+
+impl GradCentralization {
+	/// Disabled by default.
+	pub fn new() -> Self {
+		GradCentralization {
+			enabled: false,
+		}
+	}
+
+	/// Centralises each weight gradient in place. Does nothing if disabled.
+	pub fn apply(&self, grads: &mut [ArrayD<f32>]) {
+		if !self.enabled {
+			return;
+		}
+
+		for grad in grads.iter_mut().filter(|grad| grad.ndim() > 1 && grad.len() > 0) {
+			let mean = grad.iter().fold(0.0f32, |acc, &x| acc + x) / grad.len() as f32;
+			grad.mapv_inplace(|x| x - mean);
+		}
+	}
+}
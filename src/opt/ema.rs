@@ -0,0 +1,48 @@
+use ndarray::ArrayD;
+
+/// An exponential moving average of the parameters, updated after each optimiser step.
+///
+/// The average is a shadow copy, and does not feed back into the optimisation.
+pub(crate) struct ParamEma {
+	pub decay: Option<f32>,
+	pub params: Vec<ArrayD<f32>>,
+}
+
+impl ParamEma {
+	/// Disabled by default, with no decay.
+	pub fn new() -> Self {
+		ParamEma {
+			decay: None,
+			params: vec![],
+		}
+	}
+
+	/// Updates the average as `ema = decay * ema + (1 - decay) * params`. Does nothing if disabled.
+	///
+	/// The average is initialised to a copy of `params` on the first update.
+	pub fn update(&mut self, params: &[ArrayD<f32>]) {
+		let decay = match self.decay {
+			Some(decay) => decay,
+			None => return,
+		};
+
+		if self.params.len() != params.len() {
+			self.params = params.to_vec();
+			return;
+		}
+
+		for (ema, param) in self.params.iter_mut().zip(params) {
+			*ema *= decay;
+			ema.scaled_add(1.0 - decay, param);
+		}
+	}
+
+	/// Returns the averaged parameters, or `None` if disabled or no updates have occurred.
+	pub fn params(&self) -> Option<&[ArrayD<f32>]> {
+		if self.decay.is_some() && self.params.len() > 0 {
+			Some(&self.params)
+		} else {
+			None
+		}
+	}
+}
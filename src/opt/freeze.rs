@@ -0,0 +1,64 @@
+use id::{NodeID, OpID};
+use ndarray::ArrayD;
+use indexmap::IndexSet;
+
+/// Parameters which are excluded from optimiser updates.
+///
+/// Gradients are still computed through frozen parameters, so the ops around them continue to train.
+pub(crate) struct FrozenParams {
+	pub params: IndexSet<NodeID>,
+}
+
+impl FrozenParams {
+	/// Nothing frozen by default.
+	pub fn new() -> Self {
+		FrozenParams {
+			params: IndexSet::new(),
+		}
+	}
+
+	/// Freezes the members of `parameters` used by the ops, including parameters created by or supplied to their inner ops.
+	pub fn freeze(&mut self, parameters: &[NodeID], op_ids: &[OpID]) {
+		self.params.extend(op_parameters(parameters, op_ids));
+	}
+
+	/// Unfreezes the members of `parameters` used by the ops.
+	pub fn unfreeze(&mut self, parameters: &[NodeID], op_ids: &[OpID]) {
+		for param in op_parameters(parameters, op_ids) {
+			self.params.remove(&param);
+		}
+	}
+
+	/// Zeros the gradients of frozen parameters so they don't accumulate into optimiser state,
+	/// and returns copies of their values so that they can be restored after the update.
+	pub fn hold(&self, parameters: &[NodeID], params: &[ArrayD<f32>], grads: &mut [ArrayD<f32>]) -> Vec<(usize, ArrayD<f32>)> {
+		let mut held = vec![];
+		for (i, param_id) in parameters.iter().enumerate() {
+			if self.params.contains(param_id) {
+				grads[i].fill(0.0);
+				held.push((i, params[i].clone()));
+			}
+		}
+		held
+	}
+
+	/// Restores the values returned by `hold()`, undoing any change from momentum, decay or constraints.
+	pub fn restore(held: Vec<(usize, ArrayD<f32>)>, params: &mut [ArrayD<f32>]) {
+		for (i, param) in held {
+			params[i] = param;
+		}
+	}
+}
+
+/// Returns the members of `parameters` which are inputs or inner nodes of the ops, searching inner ops recursively.
+pub(crate) fn op_parameters(parameters: &[NodeID], op_ids: &[OpID]) -> Vec<NodeID> {
+	let mut nodes = IndexSet::new();
+	let mut stack = op_ids.to_vec();
+	while let Some(op_id) = stack.pop() {
+		let instance = op_id.instance();
+		nodes.extend(instance.dependencies().0);
+		nodes.extend(instance.inner_nodes());
+		stack.extend(instance.inner_ops());
+	}
+	parameters.iter().filter(|param| nodes.contains(*param)).cloned().collect()
+}
@@ -0,0 +1,47 @@
+use ndarray::{ArrayD, Zip};
+
+/// Counts, for each parameter element, the number of steps in which it received a nonzero gradient.
+///
+/// Elements whose count stays at zero, such as the weights of dead ReLU units, never contribute to the loss.
+pub(crate) struct GradActivity {
+	pub enabled: bool,
+	pub counts: Vec<ArrayD<u32>>,
+}
+
+impl GradActivity {
+	/// Disabled by default.
+	pub fn new() -> Self {
+		GradActivity {
+			enabled: false,
+			counts: vec![],
+		}
+	}
+
+	/// Increments the count of each element with a nonzero gradient. Does nothing if disabled.
+	pub fn update(&mut self, grads: &[ArrayD<f32>]) {
+		if !self.enabled {
+			return;
+		}
+
+		if self.counts.len() != grads.len() {
+			self.counts = grads.iter().map(|grad| ArrayD::zeros(grad.shape())).collect();
+		}
+
+		for (count, grad) in self.counts.iter_mut().zip(grads) {
+			Zip::from(count).and(grad).apply(|count, &grad| {
+				if grad != 0.0 {
+					*count += 1;
+				}
+			});
+		}
+	}
+
+	/// Returns the counts, or `None` if disabled or no steps have been taken.
+	pub fn counts(&self) -> Option<&[ArrayD<u32>]> {
+		if self.enabled && self.counts.len() > 0 {
+			Some(&self.counts)
+		} else {
+			None
+		}
+	}
+}
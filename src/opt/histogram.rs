@@ -0,0 +1,149 @@
+use ndarray::ArrayD;
+
+/// The number of bins used when no bin edges are supplied.
+pub const DEFAULT_BINS: usize = 30;
+
+/// Counts of values falling between consecutive bin edges.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+	/// Bin edges in increasing order. Bin `i` covers `[edges[i], edges[i+1])`, except the last bin, which also includes its upper edge.
+	pub edges: Vec<f32>,
+	/// The number of values in each bin, one fewer than the number of edges.
+	pub counts: Vec<u64>,
+	/// The number of values below the first edge.
+	pub underflow: u64,
+	/// The number of values above the last edge, or which are NaN.
+	pub overflow: u64,
+}
+
+impl Histogram {
+	/// Bins the values using the supplied edges, or if `None`, `DEFAULT_BINS` equal width bins spanning the finite values.
+	pub fn new(values: &ArrayD<f32>, edges: Option<&[f32]>) -> Histogram {
+		let edges = match edges {
+			Some(edges) => edges.to_vec(),
+			None => default_edges(values),
+		};
+		assert!(edges.len() >= 2, "Histograms require at least two bin edges");
+		assert!(edges.windows(2).all(|w| w[0] < w[1]), "Histogram bin edges must be strictly increasing");
+
+		let mut hist = Histogram {
+			counts: vec![0; edges.len() - 1],
+			edges,
+			underflow: 0,
+			overflow: 0,
+		};
+
+		let last = hist.edges.len() - 1;
+		for &x in values.iter() {
+			if x < hist.edges[0] {
+				hist.underflow += 1;
+			} else if x == hist.edges[last] {
+				hist.counts[last - 1] += 1;
+			} else if !(x < hist.edges[last]) {
+				hist.overflow += 1;
+			} else {
+				// index of the last edge not greater than x
+				let bin = match hist.edges.binary_search_by(|edge| edge.partial_cmp(&x).unwrap()) {
+					Ok(i) => i,
+					Err(i) => i - 1,
+				};
+				hist.counts[bin] += 1;
+			}
+		}
+
+		hist
+	}
+
+	/// The total number of values, including those outside the edges.
+	pub fn total(&self) -> u64 {
+		self.counts.iter().sum::<u64>() + self.underflow + self.overflow
+	}
+}
+
+/// Histograms of the weights and gradients of each parameter, in the same order as the optimiser parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histograms {
+	/// The step at which the histograms were collected.
+	pub step: usize,
+	pub weights: Vec<Histogram>,
+	pub gradients: Vec<Histogram>,
+}
+
+/// Collects histograms of the parameters and their gradients every `every` steps.
+pub(crate) struct HistogramCollector {
+	pub every: Option<usize>,
+	pub edges: Option<Vec<f32>>,
+	pub last: Option<Histograms>,
+}
+
+impl HistogramCollector {
+	/// Disabled by default.
+	pub fn new() -> Self {
+		HistogramCollector {
+			every: None,
+			edges: None,
+			last: None,
+		}
+	}
+
+	/// Replaces the last histograms if `step` is a multiple of `every`. Does nothing if disabled.
+	pub fn update(&mut self, step: usize, params: &[ArrayD<f32>], grads: &[ArrayD<f32>]) {
+		match self.every {
+			Some(every) if every > 0 && step % every == 0 => {},
+			_ => return,
+		}
+
+		let edges = self.edges.as_ref().map(|edges| &edges[..]);
+		self.last = Some(Histograms {
+			step,
+			weights: params.iter().map(|param| Histogram::new(param, edges)).collect(),
+			gradients: grads.iter().map(|grad| Histogram::new(grad, edges)).collect(),
+		});
+	}
+
+	/// Returns the last histograms collected, or `None` if disabled or none have been collected.
+	pub fn last(&self) -> Option<&Histograms> {
+		if self.every.is_some() {
+			self.last.as_ref()
+		} else {
+			None
+		}
+	}
+}
+
+/// Equal width edges spanning the finite values, widened slightly if all values are equal.
+fn default_edges(values: &ArrayD<f32>) -> Vec<f32> {
+	let (min, max) = values.iter().filter(|x| x.is_finite()).fold((::std::f32::INFINITY, ::std::f32::NEG_INFINITY), |(min, max), &x| (min.min(x), max.max(x)));
+	let (min, max) = if min > max {
+		(-0.5, 0.5)
+	} else if min == max {
+		(min - 0.5, max + 0.5)
+	} else {
+		(min, max)
+	};
+
+	let mut edges: Vec<f32> = (0..DEFAULT_BINS + 1).map(|i| min + (max - min) * i as f32 / DEFAULT_BINS as f32).collect();
+	edges[DEFAULT_BINS] = max;
+	edges
+}
+
+
+#[test]
+fn test_histogram(){
+	use ndarray::IxDyn;
+
+	let values = ArrayD::from_shape_vec(IxDyn(&[8]), vec![-2.0, -1.0, -0.5, 0.0, 0.5, 1.0, 3.0, ::std::f32::NAN]).unwrap();
+	let hist = Histogram::new(&values, Some(&[-1.0, 0.0, 1.0]));
+	assert_eq!(hist.counts, vec![2, 3]);
+	assert_eq!(hist.underflow, 1);
+	assert_eq!(hist.overflow, 2);
+	assert_eq!(hist.total(), 8);
+
+	let hist = Histogram::new(&values, None);
+	assert_eq!(hist.edges.len(), DEFAULT_BINS + 1);
+	assert_eq!(hist.edges[0], -2.0);
+	assert_eq!(hist.edges[DEFAULT_BINS], 3.0);
+	assert_eq!(hist.underflow, 0);
+	assert_eq!(hist.overflow, 1);
+	assert_eq!(hist.total(), 8);
+}
@@ -0,0 +1,306 @@
+use graph::{GraphDef, Subgraph, Result};
+use id::{NodeTag, NodeID, DataID};
+use opt::{Opt, CallbackData, CallbackSignal};
+use opt::pipeline::GradPipeline;
+use opt::moments::Moments;
+use opt::state::{OptState, save_state};
+use ndarray::{ArrayD, Zip};
+use std::num::FpCategory;
+use std::path::Path;
+use std::io;
+
+/// LAMB (Layer-wise Adaptive Moments for Batch training) Optimiser
+///
+/// Adam, with the update for each parameter array rescaled by a trust ratio so that its size is proportional to the norm of the parameters.
+/// This keeps the relative change of every layer similar, which allows the large learning rates used with large batch sizes.
+///
+/// t = t + 1
+/// m = β1 m + (1 - β1) ∇f(θ)
+/// v = β2 v + (1 - β2) ∇f(θ) ∇f(θ)
+/// m_c = m / (1 - β1^t)
+/// v_c = v / (1 - β2^t)
+/// u = m_c / (sqrt(v_c) + eps)
+/// θ = θ - α min(||θ|| / ||u||, max_trust_ratio) u
+///
+/// The norms are taken over each parameter array. If either norm is zero the trust ratio is 1.
+///
+pub struct Lamb {
+	subgraph: Subgraph,
+	inputs: Vec<DataID>,
+	parameters: Vec<NodeID>,
+	callbacks: Vec<Box<FnMut(&CallbackData)->CallbackSignal>>,
+	rate: f32,
+	moments: Moments,
+	max_trust_ratio: f32,
+	step_count: usize,
+	pipeline: GradPipeline,
+	reset_each_epoch: bool,
+}
+
+
+impl Lamb {
+
+	/// Create an optimisation problem assuming that all nodes marked `Parameter` should be optimised, and all other leaf nodes are batch inputs.
+	pub fn new(graph: &GraphDef) -> Result<Self> {
+
+		let subgraph = graph.default_subgraph()?;
+
+		let inputs = subgraph.inputs().iter().filter(|data_id| !data_id.tags().contains(&NodeTag::Parameter)).cloned().collect();
+		let parameters = subgraph.inputs().iter().filter_map(|data_id| if data_id.tags().contains(&NodeTag::Parameter) {Some(data_id.node_id())} else {None}).collect();
+		Ok(Lamb::from_parts(subgraph, inputs, parameters))
+	}
+
+	/// Define a custom optimisation problem by supplying a subgraph and a list of parameters to optimise.
+	///
+	/// The subgraph must meet the following:
+	/// - subgraph inputs are ordered with general inputs (values or gradients) followed by parameter values.
+	/// - subgraph outputs must include all parameters values and gradients.
+	///
+	/// Note: All leaf nodes not listed as parameters are assumed to be batch inputs.
+	pub fn with_subgraph(subgraph: Subgraph, parameter_ids: Vec<NodeID>) -> Self {
+
+		let n_inputs = subgraph.inputs().len() - parameter_ids.len();
+		let maybe_inputs = subgraph.inputs()[0..n_inputs].to_vec();
+		
+		assert!(subgraph.inputs()[n_inputs..].iter().cloned().eq(parameter_ids.iter().map(|id| id.value_id())), "The final inputs to the subgraph must be the values of the optimiser parameter nodes");
+
+		assert!(parameter_ids.iter().all(|id| subgraph.outputs().contains(&id.value_id())), "Subgraph outputs must contain all parameter values");
+		assert!(parameter_ids.iter().all(|id| subgraph.outputs().contains(&id.gradient_id())), "Subgraph outputs must contain all parameter gradients");
+
+		Lamb::from_parts(subgraph, maybe_inputs, parameter_ids)
+	}
+
+	fn from_parts(subgraph: Subgraph, inputs: Vec<DataID>, parameters: Vec<NodeID>) -> Self {
+		Lamb {
+			pipeline: GradPipeline::new(parameters.clone()),
+			inputs: inputs,
+			parameters: parameters,
+			subgraph: subgraph,
+			callbacks: vec![],
+			rate: 1e-3,
+			moments: Moments::new(0.999),
+			max_trust_ratio: 10.0,
+			step_count: 0,
+			reset_each_epoch: false,
+		}
+	}
+
+	/// Learning rate, α
+	pub fn rate(mut self, rate: f32) -> Self{
+		self.rate = rate;
+		self
+	}
+
+	/// Momentum coefficient, β1
+	///
+	/// Default: 0.9
+	pub fn beta1(mut self, beta1: f32) -> Self{
+		self.moments.beta1 = beta1;
+		self
+	}
+
+	/// Momentum coefficient, β2
+	///
+	/// Default: 0.999
+	pub fn beta2(mut self, beta2: f32) -> Self{
+		self.moments.beta2 = beta2;
+		self
+	}
+
+	/// Fuzz Factor, eps
+	///
+	/// Sometimes worth increasing, according to google.
+	/// Default: 1e-8
+	pub fn epsilon(mut self, epsilon: f32) -> Self{
+		self.moments.epsilon = epsilon;
+		self
+	}
+
+	/// The upper limit of the trust ratio, ||θ|| / ||u||, which scales the update of each parameter array.
+	///
+	/// Default: 10.0
+	pub fn max_trust_ratio(mut self, max_trust_ratio: f32) -> Self {
+		assert!(max_trust_ratio > 0.0, "Lamb max_trust_ratio must be greater than 0.0");
+		self.max_trust_ratio = max_trust_ratio;
+		self
+	}
+
+	/// Zero the momentum and curvature estimates at the end of each epoch of the training stream, see `Opt::reset_state()`
+	///
+	/// Epochs are taken from `DataStream::epoch_size()`, if the training stream does not report one this has no effect.
+	/// Bias correction restarts after each reset. The parameters, and the step count used by any rate schedule, are unaffected.
+	/// Default: false
+	pub fn reset_state_each_epoch(mut self, enable: bool) -> Self {
+		self.reset_each_epoch = enable;
+		self
+	}
+
+	/// Returns the current learning rate, α
+	pub fn learning_rate(&self) -> f32 {
+		self.rate
+	}
+
+	/// Returns the number of steps taken so far
+	pub fn step_count(&self) -> usize {
+		self.step_count
+	}
+
+	/// Returns the gradient processing applied around each update, e.g. loss scaling, clipping, and parameter averaging.
+	pub fn pipeline(&self) -> &GradPipeline {
+		&self.pipeline
+	}
+
+	/// Returns the gradient processing applied around each update, for configuration.
+	pub fn pipeline_mut(&mut self) -> &mut GradPipeline {
+		&mut self.pipeline
+	}
+
+	/// Writes the learning rate, step count, momentum and curvature vectors, and the pipeline's loss scale, warmup progress, and parameter moving average to a file,
+	/// so that optimisation can be resumed with `load_state()`.
+	///
+	/// The pipeline configuration, gradient activity counts, histograms, and gradient noise rng are not saved.
+	pub fn save_state<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+		save_state(path, self.step_count, self.moments.state_start, self.rate, &self.pipeline.state(), &[&self.moments.momentum_vec[..], &self.moments.curvature_vec[..], self.pipeline.ema_vec()])
+	}
+
+	/// Restores the state written by `save_state()`.
+	///
+	/// The pipeline should be configured before loading, as enabling loss scaling resets the scale.
+	/// Returns an error if the number or shapes of the saved arrays do not match the parameters of this optimiser.
+	pub fn load_state<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+		let OptState{step_count, state_start, rate, pipeline, mut vecs} = OptState::load(path, &self.parameters, 3)?;
+		self.step_count = step_count;
+		self.moments.state_start = state_start;
+		self.rate = rate;
+		self.pipeline.set_state(pipeline, vecs.pop().unwrap());
+		self.moments.curvature_vec = vecs.pop().unwrap();
+		self.moments.momentum_vec = vecs.pop().unwrap();
+		Ok(())
+	}
+}
+
+impl Opt for Lamb {
+
+	fn subgraph(&self) -> &Subgraph {
+		&self.subgraph
+	}
+
+	fn inputs(&self) -> &[DataID]{
+		&self.inputs
+	}
+
+	fn parameters(&self) -> &[NodeID]{
+		&self.parameters
+	}
+
+	fn step(&mut self, inputs: Vec<ArrayD<f32>>, parameters: Vec<ArrayD<f32>>) -> Result<(f32, usize, f32, Vec<ArrayD<f32>>)> {
+		assert_eq!(inputs.len(), self.inputs().len(), "Incorrect number of inputs supplied to optimiser.step()");
+
+		let base_rate = self.rate;
+		let epsilon = self.moments.epsilon;
+		let t = self.moments.t(self.step_count);
+		let (momentum_correction, curv_correction) = self.moments.corrections(t);
+		let max_trust_ratio = self.max_trust_ratio;
+		let moments = &mut self.moments;
+
+		let (loss, change_norm, params) = self.pipeline.step(&mut self.subgraph, inputs, parameters, self.step_count, |rate_multiplier, params, param_grads| {
+			let rate = base_rate * rate_multiplier;
+			moments.update(params, param_grads, |params, momentum, curvature| {
+				let mut update = ArrayD::zeros(params.shape());
+				Zip::from(&mut update)
+					.and(momentum)
+					.and(curvature)
+					.apply(|update, momentum, curv| {
+						*update = (*momentum) * momentum_correction/((*curv*curv_correction).sqrt() + epsilon);
+					});
+
+				let param_norm = params.iter().fold(0.0f32, |acc, &x| acc + x * x).sqrt();
+				let update_norm = update.iter().fold(0.0f32, |acc, &x| acc + x * x).sqrt();
+				let trust_ratio = if param_norm > 0.0 && update_norm > 0.0 {
+					(param_norm/update_norm).min(max_trust_ratio)
+				} else {
+					1.0
+				};
+
+				let mut change_sqr = 0.0;
+				Zip::from(params)
+					.and(&update)
+					.apply(|param, update| {
+						let change = -rate * trust_ratio * update;
+						change_sqr += change*change;
+						*param += change;
+						if let FpCategory::Subnormal = param.classify(){
+							*param = 0.0;
+						}
+					});
+				change_sqr
+			})
+		})?;
+
+		match change_norm {
+			Some(change_norm) => {
+				self.step_count += 1;
+				Ok((loss, self.step_count, change_norm, params))
+			},
+			None => Ok((loss, self.step_count, 0.0, params)),
+		}
+	}
+
+	fn callbacks(&mut self) -> &mut [Box<FnMut(&CallbackData)->CallbackSignal>]{
+		&mut self.callbacks
+	}
+
+	fn add_boxed_callback(&mut self, func: Box<FnMut(&CallbackData)->CallbackSignal>){
+		self.callbacks.push(func)
+	}
+
+	fn reset_state(&mut self) {
+		self.moments.reset(self.step_count);
+	}
+
+	fn resets_state_each_epoch(&self) -> bool {
+		self.reset_each_epoch
+	}
+}
+
+
+#[test]
+fn test_lamb_trust_ratio(){
+	_test_lamb_trust_ratio().unwrap();
+}
+
+fn _test_lamb_trust_ratio() -> Result<()>{
+	use ops::loss::proportional::Proportional;
+	use init::Initialiser;
+
+	let mut g = GraphDef::new();
+
+	let large = g.new_node(shape![4, 3], "large", tag![Parameter])?;
+	let small = g.new_node(shape![4, 3], "small", tag![Parameter])?;
+
+	// large weights with small gradients, and small weights with large gradients
+	let _o1 = g.new_op(Proportional::new(&large).multiplier(0.01), tag![])?;
+	let _o2 = g.new_op(Proportional::new(&small).multiplier(100.0), tag![])?;
+	g.set_initialiser(&large, Initialiser::fill(5.0));
+	g.set_initialiser(&small, Initialiser::fill(0.01));
+
+	let norm = |x: &ArrayD<f32>| x.iter().fold(0.0f32, |acc, &x| acc + x * x).sqrt();
+
+	// on the first step every element of the Adam update u is 1, so the change of each array is α ||θ||, limited to α max_trust_ratio ||u||
+	for &(max_trust_ratio, expected_large) in &[(10.0, 5.0), (2.0, 2.0)] {
+		let mut opt = Lamb::new(&g)?.rate(0.1).max_trust_ratio(max_trust_ratio);
+		assert_eq!(opt.parameters(), &[large.clone(), small.clone()]);
+		let params = g.initialise_nodes(opt.parameters())?;
+		let (_err, _step, _change_norm, new_params) = opt.step(vec![], params.clone())?;
+
+		let large_change = norm(&(&new_params[0] - &params[0]));
+		let small_change = norm(&(&new_params[1] - &params[1]));
+		let n_sqrt = 12.0f32.sqrt();
+
+		assert!((large_change - 0.1 * expected_large * n_sqrt).abs() < 1e-4, "{}", large_change);
+		assert!((small_change - 0.1 * norm(&params[1])).abs() < 1e-6, "{}", small_change);
+		assert!(large_change > small_change * 100.0);
+	}
+
+	Ok(())
+}
@@ -0,0 +1,174 @@
+use graph::{Subgraph, Result};
+use id::{NodeID, DataID};
+use opt::{Opt, CallbackData, CallbackSignal};
+use ndarray::ArrayD;
+
+/// Lookahead Optimiser
+///
+/// Wraps an inner optimiser, which updates a set of fast weights.
+/// Every k steps the slow weights are moved toward the fast weights, and the fast weights are reset to the slow weights:
+///
+/// θ_slow = θ_slow + α (θ_fast - θ_slow)
+/// θ_fast = θ_slow
+///
+/// If the parameters supplied to a step differ from those returned by the previous step, the slow weights are reset to the supplied parameters.
+///
+/// Callbacks added to the inner optimiser are not called, add callbacks to the `Lookahead` instead.
+pub struct Lookahead<O: Opt> {
+	inner: O,
+	callbacks: Vec<Box<FnMut(&CallbackData)->CallbackSignal>>,
+	k: usize,
+	alpha: f32,
+	slow_params: Vec<ArrayD<f32>>,
+	fast_params: Vec<ArrayD<f32>>,
+	step_count: usize,
+}
+
+impl<O: Opt> Lookahead<O> {
+
+	/// Wrap an inner optimiser, which determines the subgraph, inputs and parameters.
+	pub fn new(inner: O) -> Self {
+		Lookahead {
+			inner: inner,
+			callbacks: vec![],
+			k: 5,
+			alpha: 0.5,
+			slow_params: vec![],
+			fast_params: vec![],
+			step_count: 0,
+		}
+	}
+
+	/// Number of inner steps between each update of the slow weights, k
+	///
+	/// Default: 5
+	pub fn k(mut self, k: usize) -> Self {
+		assert!(k > 0, "Lookahead k must be greater than zero");
+		self.k = k;
+		self
+	}
+
+	/// Slow weights step size, α
+	///
+	/// Default: 0.5
+	pub fn alpha(mut self, alpha: f32) -> Self {
+		self.alpha = alpha;
+		self
+	}
+
+	/// Borrows the inner optimiser
+	pub fn inner(&self) -> &O {
+		&self.inner
+	}
+
+	/// Mutably borrows the inner optimiser
+	pub fn inner_mut(&mut self) -> &mut O {
+		&mut self.inner
+	}
+
+	/// Returns the slow weights, which are empty until the first step
+	pub fn slow_params(&self) -> &[ArrayD<f32>] {
+		&self.slow_params
+	}
+
+	/// Returns the number of steps taken so far
+	pub fn step_count(&self) -> usize {
+		self.step_count
+	}
+}
+
+impl<O: Opt> Opt for Lookahead<O> {
+
+	fn subgraph(&self) -> &Subgraph {
+		self.inner.subgraph()
+	}
+
+	fn inputs(&self) -> &[DataID]{
+		self.inner.inputs()
+	}
+
+	fn parameters(&self) -> &[NodeID]{
+		self.inner.parameters()
+	}
+
+	fn step(&mut self, inputs: Vec<ArrayD<f32>>, parameters: Vec<ArrayD<f32>>) -> Result<(f32, usize, f32, Vec<ArrayD<f32>>)>{
+		if parameters != self.fast_params {
+			self.slow_params = parameters.clone();
+		}
+
+		let (loss, _inner_step, change_norm, mut params) = self.inner.step(inputs, parameters)?;
+		self.step_count += 1;
+
+		if self.step_count % self.k == 0 {
+			for (slow, fast) in self.slow_params.iter_mut().zip(params.iter_mut()) {
+				*fast -= &*slow;
+				slow.scaled_add(self.alpha, fast);
+				fast.assign(slow);
+			}
+		}
+
+		self.fast_params = params.clone();
+		Ok((loss, self.step_count, change_norm, params))
+	}
+
+	fn callbacks(&mut self) -> &mut [Box<FnMut(&CallbackData)->CallbackSignal>]{
+		&mut self.callbacks
+	}
+
+	fn add_boxed_callback(&mut self, func: Box<FnMut(&CallbackData)->CallbackSignal>){
+		self.callbacks.push(func)
+	}
+
+	/// Resets the state of the inner optimiser, the slow weights are kept.
+	fn reset_state(&mut self) {
+		self.inner.reset_state();
+	}
+
+	fn resets_state_each_epoch(&self) -> bool {
+		self.inner.resets_state_each_epoch()
+	}
+}
+
+
+#[test]
+fn test_lookahead_sync(){
+	_test_lookahead_sync().unwrap();
+}
+
+fn _test_lookahead_sync() -> Result<()>{
+	use graph::GraphDef;
+	use ops::loss::proportional::Proportional;
+	use opt::sgd::Sgd;
+
+	let mut g = GraphDef::new();
+
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Proportional::new(&param).multiplier(12.0), tag![])?;
+
+	// each element has a gradient of 1.0, so each inner step reduces the fast weights by 0.1
+	let mut opt = Lookahead::new(Sgd::new(&g)?.rate(0.1)).k(3).alpha(0.5);
+
+	let mut params = vec![ArrayD::zeros(&[4, 3][..])];
+	let mut slow = 0.0;
+	let mut fast = 0.0;
+	for i in 1..10 {
+		let (_err, step, _change_norm, new_params) = opt.step(vec![], params)?;
+		params = new_params;
+		assert_eq!(step, i);
+
+		fast -= 0.1;
+		if i % 3 == 0 {
+			slow += 0.5 * (fast - slow);
+			fast = slow;
+		}
+		assert!(params[0].iter().all(|&x| (x - fast).abs() < 1e-5), "step {}: {} != {}", i, params[0], fast);
+		assert!(opt.slow_params()[0].iter().all(|&x| (x - slow).abs() < 1e-5), "step {}: {} != {}", i, opt.slow_params()[0], slow);
+	}
+
+	// supplying different parameters resets the slow weights
+	let (_err, _step, _change_norm, params) = opt.step(vec![], vec![ArrayD::from_elem(&[4, 3][..], 1.0)])?;
+	assert!(opt.slow_params()[0].iter().all(|&x| x == 1.0), "{}", opt.slow_params()[0]);
+	assert!(params[0].iter().all(|&x| (x - 0.9).abs() < 1e-5), "{}", params[0]);
+
+	Ok(())
+}
@@ -0,0 +1,79 @@
+use ndarray::ArrayD;
+use opt::LossScale;
+
+/// Tracks the current loss scale, which the optimiser applies to the subgraph before each step, and removes it from the resulting parameter gradients.
+pub(crate) struct LossScaler {
+	pub mode: Option<LossScale>,
+	scale: f32,
+	stable_steps: usize,
+}
+
+impl LossScaler {
+	/// Disabled by default.
+	pub fn new() -> Self {
+		LossScaler {
+			mode: None,
+			scale: 1.0,
+			stable_steps: 0,
+		}
+	}
+
+	pub fn set_mode(&mut self, mode: Option<LossScale>) {
+		self.scale = match mode {
+			Some(LossScale::Static(scale)) | Some(LossScale::Dynamic{init: scale, ..}) => scale,
+			None => 1.0,
+		};
+		self.mode = mode;
+		self.stable_steps = 0;
+	}
+
+	/// Returns the current scale, or `None` if disabled.
+	pub fn scale(&self) -> Option<f32> {
+		self.mode.map(|_| self.scale)
+	}
+
+	/// Returns the current scale and the number of consecutive finite steps towards growing it, whether or not enabled.
+	pub fn state(&self) -> (f32, usize) {
+		(self.scale, self.stable_steps)
+	}
+
+	/// Restores a state returned by `state()`.
+	pub fn set_state(&mut self, scale: f32, stable_steps: usize) {
+		self.scale = scale;
+		self.stable_steps = stable_steps;
+	}
+
+	/// Checks that the scaled gradients are finite, then divides them by the scale in place.
+	///
+	/// Returns `false` if the update should be skipped due to non-finite gradients, in which case a dynamic scale is backed off.
+	/// After `interval` consecutive finite steps a dynamic scale is grown.
+	pub fn unscale(&mut self, grads: &mut [ArrayD<f32>]) -> bool {
+		let mode = match self.mode {
+			Some(mode) => mode,
+			None => return true,
+		};
+
+		let scale = self.scale;
+		let finite = grads.iter().all(|grad| grad.iter().all(|e| e.is_finite()));
+		if finite {
+			for grad in grads.iter_mut() {
+				grad.mapv_inplace(|e| e / scale);
+			}
+		}
+
+		if let LossScale::Dynamic{growth, backoff, interval, ..} = mode {
+			if finite {
+				self.stable_steps += 1;
+				if self.stable_steps >= interval {
+					self.scale *= growth;
+					self.stable_steps = 0;
+				}
+			} else {
+				self.scale *= backoff;
+				self.stable_steps = 0;
+			}
+		}
+
+		finite
+	}
+}
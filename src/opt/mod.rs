@@ -1,19 +1,91 @@
 pub mod sgd;
 pub mod adam;
+pub mod radam;
+pub mod lamb;
+pub mod lookahead;
+pub mod spsa;
+pub mod schedules;
+pub mod vec_math;
+mod state;
+mod noise;
+mod ema;
+mod loss_scale;
+mod freeze;
+mod grad_activity;
+mod agc;
+mod centralize;
+mod warmup;
+mod histogram;
+mod pipeline;
+mod moments;
+
+pub use opt::histogram::{Histogram, Histograms};
+pub use opt::pipeline::GradPipeline;
+pub(crate) use opt::freeze::op_parameters;
 
 use graph::{GraphDef, Subgraph, Result};
 use id::{NodeID, DataID};
 use data::DataStream;
 use ndarray::ArrayD;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
 
 pub enum CallbackSignal{
 	Stop,
 	Continue,
 }
 
+/// A projection applied to each parameter array after every optimiser update.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WeightConstraint {
+	/// Rescale any parameter array whose L2 norm exceeds the given value, so that its norm equals the value.
+	MaxNorm(f32),
+	/// Rescale every parameter array to have an L2 norm of 1. Arrays which are entirely zero are left unchanged.
+	UnitNorm,
+}
+
+impl WeightConstraint {
+	/// Projects `param` in place to satisfy the constraint.
+	pub fn apply(&self, param: &mut ArrayD<f32>) {
+		let norm = param.iter().fold(0.0f32, |acc, &x| acc + x * x).sqrt();
+		let target = match *self {
+			WeightConstraint::MaxNorm(max) => if norm > max {max} else {return},
+			WeightConstraint::UnitNorm => 1.0,
+		};
+		if norm > 0.0 {
+			*param *= target / norm;
+		}
+	}
+}
+
+/// Scaling applied to the loss, and therefore the gradients, to avoid underflow in reduced precision gradients.
+///
+/// The gradients produced by loss ops are multiplied by the scale during backpropagation (see `Subgraph::set_loss_scale()`),
+/// and the parameter gradients are divided by it again before the update. The reported loss is not scaled.
+/// Updates are skipped for any step where the scaled gradients are not finite.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LossScale {
+	/// A fixed scale.
+	Static(f32),
+	/// Starts at `init`, is multiplied by `backoff` after any step with non-finite gradients,
+	/// and is multiplied by `growth` after `interval` consecutive steps with finite gradients.
+	Dynamic{init: f32, growth: f32, backoff: f32, interval: usize},
+}
+
+impl LossScale {
+	/// A dynamic scale starting at 2^16, which halves on overflow and doubles after 2000 stable steps.
+	pub fn dynamic() -> Self {
+		LossScale::Dynamic{init: 65536.0, growth: 2.0, backoff: 0.5, interval: 2000}
+	}
+}
+
 pub struct CallbackData<'a>{
 	pub err: f32,
 	pub step: usize,
+	/// The total number of examples evaluated during this call to `optimise_from()`.
+	///
+	/// Taken as the sum of the outermost dimension of the first input of each step, or one per step if there are no inputs.
+	pub eval_count: usize,
 	pub change_norm: f32,
 	pub params: &'a [ArrayD<f32>],
 	pub stream: &'a DataStream,
@@ -38,24 +110,70 @@ pub trait Opt {
 
 	fn add_boxed_callback(&mut self, func: Box<FnMut(&CallbackData)->CallbackSignal>);
 
+	/// Clears accumulated optimiser state, such as momentum, leaving the parameters and the step count unchanged.
+	///
+	/// Default: does nothing, for optimisers without such state.
+	fn reset_state(&mut self) {}
+
+	/// Whether `optimise_from()` should call `reset_state()` at the end of each epoch of the training stream.
+	///
+	/// An epoch ends when the step count is a multiple of `DataStream::epoch_size()`, so after `load_state()` the epochs remain aligned,
+	/// provided the training stream is resumed at the matching position.
+	/// Default: false
+	fn resets_state_each_epoch(&self) -> bool {
+		false
+	}
+
 	fn optimise(&mut self, training_stream: &mut DataStream, graph: &GraphDef) -> Result<Vec<ArrayD<f32>>>{
 		let params = graph.initialise_nodes(self.parameters())?;
 		self.optimise_from(training_stream, params)
 	}
 
-	fn optimise_from(&mut self, training_stream: &mut DataStream, mut params: Vec<ArrayD<f32>>) -> Result<Vec<ArrayD<f32>>>{
-		let mut stop = false;
-		while !stop {
-			let (err, step, change_norm, new_params) = self.step(training_stream.next(), params)?;
-			params = new_params;
+	fn optimise_from(&mut self, training_stream: &mut DataStream, params: Vec<ArrayD<f32>>) -> Result<Vec<ArrayD<f32>>>{
+		optimise_calls(self, training_stream, params, None)
+	}
+
+	/// Optimises for `epochs` passes over the training stream, stopping early only if a callback returns `Stop`.
+	///
+	/// The length of an epoch is taken from `DataStream::epoch_size()`, and an error is returned if the stream does not report one.
+	fn optimise_epochs(&mut self, training_stream: &mut DataStream, params: Vec<ArrayD<f32>>, epochs: usize) -> Result<Vec<ArrayD<f32>>>{
+		let epoch_size = match training_stream.epoch_size() {
+			Some(epoch_size) => epoch_size,
+			None => bail!("optimise_epochs() requires a training stream which reports an epoch_size()"),
+		};
+		optimise_calls(self, training_stream, params, Some(epochs * epoch_size))
+	}
+}
+
+/// Steps the optimiser until a callback returns `Stop`, or `max_calls` calls to `training_stream.next()` have been made.
+fn optimise_calls<O: Opt + ?Sized>(opt: &mut O, training_stream: &mut DataStream, mut params: Vec<ArrayD<f32>>, max_calls: Option<usize>) -> Result<Vec<ArrayD<f32>>>{
+	let mut stop = false;
+	let mut eval_count = 0;
+	// epochs are counted in optimiser steps rather than calls made here, so that they stay aligned when resuming from a saved state
+	let epoch_size = if opt.resets_state_each_epoch() {training_stream.epoch_size()} else {None};
+	let mut last_reset = None;
+	let mut calls = 0;
+	while !stop && max_calls.map_or(true, |max_calls| calls < max_calls) {
+		let inputs = training_stream.next();
+		eval_count += inputs.get(0).and_then(|arr| arr.shape().get(0).cloned()).unwrap_or(1);
+		let (err, step, change_norm, new_params) = opt.step(inputs, params)?;
+		params = new_params;
 
-			let data = CallbackData{err: err, step: step, change_norm: change_norm, params: &params, stream: training_stream};
-			for func in self.callbacks().iter_mut(){
-				stop = stop | matches!(func(&data), CallbackSignal::Stop);
+		calls += 1;
+		if let Some(epoch_size) = epoch_size {
+			// skipped updates repeat the step count, so only reset once per boundary
+			if epoch_size > 0 && step > 0 && step % epoch_size == 0 && last_reset != Some(step) {
+				opt.reset_state();
+				last_reset = Some(step);
 			}
 		}
-		Ok(params)
+
+		let data = CallbackData{err: err, step: step, eval_count: eval_count, change_norm: change_norm, params: &params, stream: training_stream};
+		for func in opt.callbacks().iter_mut(){
+			stop = stop | matches!(func(&data), CallbackSignal::Stop);
+		}
 	}
+	Ok(params)
 }
 
 pub trait UnboxedCallbacks: Opt {
@@ -66,6 +184,10 @@ pub trait UnboxedCallbacks: Opt {
 
 impl<O: Opt> UnboxedCallbacks for O {}
 
+/// Prints the number of calls to the callback and the error after each step.
+///
+/// Optimisers do not print progress themselves, output is only produced by callbacks such as this one.
+/// For custom formatting or logging, add a callback which reads the `CallbackData` fields directly.
 pub fn print_step_data() -> Box<FnMut(&CallbackData)->CallbackSignal>{
 	let mut step = 0;
 	Box::new(move |data|{
@@ -75,6 +197,7 @@ pub fn print_step_data() -> Box<FnMut(&CallbackData)->CallbackSignal>{
 	})
 }
 
+/// Stops optimisation once more than `max` steps have been taken, so the first `max` steps complete and the step after them is the last.
 pub fn max_steps(max: usize) -> Box<FnMut(&CallbackData)->CallbackSignal>{
 	let mut step = 0;
 	Box::new(move |_data|{
@@ -87,6 +210,20 @@ pub fn max_steps(max: usize) -> Box<FnMut(&CallbackData)->CallbackSignal>{
 	})
 }
 
+/// Stops optimisation once more than `max` examples have been evaluated, as counted by `CallbackData::eval_count`.
+///
+/// Like `max_steps`, the limit is only checked after each step, so the step which takes the count past `max` still completes,
+/// while a step which reaches exactly `max` does not stop optimisation.
+pub fn max_evals(max: usize) -> Box<FnMut(&CallbackData)->CallbackSignal>{
+	Box::new(move |data|{
+		if data.eval_count <= max {
+			CallbackSignal::Continue
+		} else {
+			CallbackSignal::Stop
+		}
+	})
+}
+
 pub fn min_err(min: f32) -> Box<FnMut(&CallbackData)->CallbackSignal>{
 	Box::new(move |data|{
 		if data.err > min {
@@ -108,4 +245,428 @@ pub fn every_n_steps(n: usize, mut func: Box<FnMut(&CallbackData)->CallbackSigna
 			CallbackSignal::Continue
 		}
 	})
-}
\ No newline at end of file
+}
+
+
+/// Appends the step number, number of examples evaluated, and error to a CSV file after each step.
+///
+/// The file is created, and a `step,evals,err` header written, on the first call.
+/// Rows are buffered, and flushed every 100 steps and when the callback is dropped.
+/// If the file can't be created or written to, the error is printed once and no further rows are logged. Optimisation is never stopped.
+pub fn csv_logger(path: &str) -> Box<FnMut(&CallbackData)->CallbackSignal>{
+	let path = path.to_string();
+	let flush_interval = 100;
+	let mut writer = None;
+	let mut failed = false;
+	let mut rows = 0;
+	Box::new(move |data|{
+		if !failed {
+			if let Err(err) = write_csv_row(&mut writer, &path, data, rows % flush_interval == 0) {
+				eprintln!("csv_logger could not write to '{}', no further rows will be logged: {}", path, err);
+				writer = None;
+				failed = true;
+			}
+			rows += 1;
+		}
+		CallbackSignal::Continue
+	})
+}
+
+fn write_csv_row(writer: &mut Option<BufWriter<File>>, path: &str, data: &CallbackData, flush: bool) -> io::Result<()> {
+	if writer.is_none() {
+		let mut new_writer = BufWriter::new(File::create(path)?);
+		writeln!(new_writer, "step,evals,err")?;
+		*writer = Some(new_writer);
+	}
+	let writer = writer.as_mut().unwrap();
+	writeln!(writer, "{},{},{}", data.step, data.eval_count, data.err)?;
+	if flush {
+		writer.flush()?;
+	}
+	Ok(())
+}
+
+
+/// Prints the classification accuracy of `prediction` against `target` after each step, evaluated using the current parameters.
+///
+/// Each evaluation draws a batch from `stream`, which must supply values for the input nodes of the graph, as ordered by `GraphDef::input_nodes()`.
+/// The parameters are expected in the same order as used by optimisers constructed with `new(&graph)`.
+/// See `ops::loss::prediction::accuracy()` for the accepted target formats.
+pub fn log_accuracy<S: DataStream + 'static>(graph: &GraphDef, prediction: &NodeID, target: &NodeID, stream: S) -> Result<Box<FnMut(&CallbackData)->CallbackSignal>>{
+	accuracy_callback(graph, prediction, target, stream, |step, acc| println!("step:{}\taccuracy:{}", step, acc))
+}
+
+/// Evaluates accuracy as for `log_accuracy()`, passing the step and accuracy to `report`.
+fn accuracy_callback<S: DataStream + 'static, F: FnMut(usize, f32) + 'static>(graph: &GraphDef, prediction: &NodeID, target: &NodeID, mut stream: S, mut report: F) -> Result<Box<FnMut(&CallbackData)->CallbackSignal>>{
+	use ops::loss::prediction::accuracy;
+	use id::NodeTag;
+
+	let input_ids: Vec<DataID> = graph.input_nodes().iter().map(|node_id| node_id.value_id()).collect();
+	let parameter_ids: Vec<DataID> = graph.default_subgraph()?.inputs().iter().filter(|data_id| data_id.tags().contains(&NodeTag::Parameter)).cloned().collect();
+	let mut subgraph = graph.subgraph(&input_ids.iter().chain(&parameter_ids).cloned().collect::<Vec<_>>(), &[prediction.value_id(), target.value_id()])?;
+	let prediction = prediction.clone();
+	let target = target.clone();
+
+	Ok(Box::new(move |data|{
+		let mut inputs = stream.next();
+		inputs.extend(data.params.iter().cloned());
+		let storage = subgraph.execute(inputs).expect("Could not execute accuracy evaluation");
+		let acc = accuracy(storage.get(&prediction.value_id()).unwrap(), storage.get(&target.value_id()).unwrap());
+		report(data.step, acc);
+		CallbackSignal::Continue
+	}))
+}
+
+/// Learning rate range test.
+///
+/// Runs Sgd on the parameters of `graph` for up to `num_iters` steps, increasing the learning rate exponentially from `start_lr` to `end_lr`,
+/// and returns the (learning rate, loss) pair for each step. The loss is that of the mini-batch evaluated before the update at that rate.
+/// The test stops early if the loss becomes non-finite or exceeds four times the minimum loss seen so far.
+///
+/// A good maximum learning rate is typically somewhat below the rate at the loss minimum.
+pub fn lr_range_test(graph: &GraphDef, training_stream: &mut DataStream, start_lr: f32, end_lr: f32, num_iters: usize) -> Result<Vec<(f32, f32)>> {
+	use opt::sgd::Sgd;
+
+	ensure!(start_lr > 0.0 && end_lr > start_lr, "lr_range_test requires 0 < start_lr < end_lr");
+	ensure!(num_iters > 1, "lr_range_test requires num_iters > 1");
+	let divergence_factor = 4.0;
+
+	let lr = move |step: usize| start_lr * (end_lr / start_lr).powf(step as f32 / (num_iters - 1) as f32);
+
+	let mut opt = Sgd::new(graph)?.rate(1.0);
+	opt.pipeline_mut().rate_schedule(Box::new(lr));
+	let mut params = graph.initialise_nodes(opt.parameters())?;
+
+	let mut curve = Vec::with_capacity(num_iters);
+	let mut min_loss = ::std::f32::INFINITY;
+	for step in 0..num_iters {
+		let (loss, _step, _change_norm, new_params) = opt.step(training_stream.next(), params)?;
+		params = new_params;
+		curve.push((lr(step), loss));
+
+		min_loss = min_loss.min(loss);
+		if !loss.is_finite() || loss > min_loss * divergence_factor {
+			break;
+		}
+	}
+
+	Ok(curve)
+}
+
+/// Runs `lr_range_test()` and writes the resulting curve to a CSV file at `path`, for plotting.
+///
+/// Each row is `lr,loss,smoothed`, below a header row, where `smoothed` is a bias corrected exponential moving average of the loss with a decay of 0.98.
+/// The mini-batch losses are noisy, so the smoothed loss is usually the better guide for choosing a learning rate.
+/// Returns the number of rows written, which is less than `num_iters` if the test stopped early.
+pub fn lr_finder_to_csv(graph: &GraphDef, training_stream: &mut DataStream, path: &str, start_lr: f32, end_lr: f32, num_iters: usize) -> Result<usize> {
+	let curve = lr_range_test(graph, training_stream, start_lr, end_lr, num_iters)?;
+	write_lr_curve(&curve, path).map_err(|err| format!("lr_finder_to_csv could not write to '{}': {}", path, err))?;
+	Ok(curve.len())
+}
+
+fn write_lr_curve(curve: &[(f32, f32)], path: &str) -> io::Result<()> {
+	let decay = 0.98f32;
+	let mut average = 0.0;
+
+	let mut writer = BufWriter::new(File::create(path)?);
+	writeln!(writer, "lr,loss,smoothed")?;
+	for (i, &(lr, loss)) in curve.iter().enumerate() {
+		average = decay * average + (1.0 - decay) * loss;
+		let smoothed = average / (1.0 - decay.powi(i as i32 + 1));
+		writeln!(writer, "{},{},{}", lr, loss, smoothed)?;
+	}
+	writer.flush()
+}
+
+#[cfg(test)]
+struct ConstStream {
+	shape: Vec<usize>,
+}
+
+#[cfg(test)]
+impl DataStream for ConstStream {
+	fn next(&mut self) -> Vec<ArrayD<f32>>{
+		vec![ArrayD::zeros(self.shape.as_slice())]
+	}
+}
+
+/// Returns a path in the temporary directory which is unique to this process and call, so that tests running concurrently don't share files.
+#[cfg(test)]
+pub(crate) fn temp_path(name: &str) -> ::std::path::PathBuf {
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	static COUNTER: AtomicUsize = AtomicUsize::new(0);
+	::std::env::temp_dir().join(format!("alumina_{}_{}_{}", ::std::process::id(), COUNTER.fetch_add(1, Ordering::SeqCst), name))
+}
+
+#[test]
+fn test_max_steps(){
+	_test_max_steps().unwrap();
+}
+
+fn _test_max_steps() -> Result<()>{
+	use ops::loss::mse::Mse;
+	use opt::sgd::Sgd;
+	use std::sync::{Arc, Mutex};
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Mse::new(&param, &input), tag![])?;
+
+	let steps = Arc::new(Mutex::new(vec![]));
+	let steps_clone = steps.clone();
+
+	let mut opt = Sgd::new(&g)?;
+	opt.add_boxed_callback(max_steps(5));
+	opt.add_callback(move |data| {steps_clone.lock().unwrap().push(data.step); CallbackSignal::Continue});
+	opt.optimise(&mut ConstStream{shape: vec![4, 3]}, &g)?;
+
+	// max_steps allows `max` steps to complete before stopping on the following step.
+	assert_eq!(*steps.lock().unwrap(), vec![1, 2, 3, 4, 5, 6]);
+
+	Ok(())
+}
+
+#[test]
+fn test_optimise_epochs(){
+	_test_optimise_epochs().unwrap();
+}
+
+fn _test_optimise_epochs() -> Result<()>{
+	use ops::loss::mse::Mse;
+	use opt::sgd::Sgd;
+	use data::DataSet;
+	use data::array_set::ArraySet;
+	use std::sync::{Arc, Mutex};
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Mse::new(&param, &input), tag![])?;
+
+	// an epoch of 5 calls to next()
+	let mut stream = ArraySet::new(vec![ArrayD::zeros(&[5, 4, 3][..])]).sequential();
+	assert_eq!(stream.epoch_size(), Some(5));
+
+	let steps = Arc::new(Mutex::new(vec![]));
+	let steps_clone = steps.clone();
+
+	let mut opt = Sgd::new(&g)?;
+	opt.add_callback(move |data| {steps_clone.lock().unwrap().push(data.step); CallbackSignal::Continue});
+	let params = g.initialise_nodes(opt.parameters())?;
+	let params = opt.optimise_epochs(&mut stream, params, 3)?;
+	assert_eq!(*steps.lock().unwrap(), (1..16).collect::<Vec<_>>());
+
+	// callbacks can still stop optimisation early
+	let mut opt = Sgd::new(&g)?;
+	opt.add_boxed_callback(max_steps(2));
+	let params = opt.optimise_epochs(&mut stream, params, 3)?;
+	assert_eq!(opt.step_count(), 3);
+
+	let _params = opt.optimise_epochs(&mut stream, params.clone(), 0)?;
+	assert_eq!(opt.step_count(), 3);
+
+	assert!(opt.optimise_epochs(&mut ConstStream{shape: vec![4, 3]}, params, 1).is_err());
+
+	Ok(())
+}
+
+#[test]
+fn test_max_evals(){
+	_test_max_evals().unwrap();
+}
+
+fn _test_max_evals() -> Result<()>{
+	use ops::loss::mse::Mse;
+	use opt::sgd::Sgd;
+	use std::sync::{Arc, Mutex};
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Mse::new(&param, &input), tag![])?;
+
+	let evals = Arc::new(Mutex::new(vec![]));
+	let evals_clone = evals.clone();
+
+	let mut opt = Sgd::new(&g)?;
+	opt.add_boxed_callback(max_evals(10));
+	opt.add_callback(move |data| {evals_clone.lock().unwrap().push(data.eval_count); CallbackSignal::Continue});
+	opt.optimise(&mut ConstStream{shape: vec![4, 3]}, &g)?;
+
+	assert_eq!(*evals.lock().unwrap(), vec![4, 8, 12]);
+
+	// reaching the limit exactly does not stop optimisation, exceeding it does
+	evals.lock().unwrap().clear();
+	let evals_clone = evals.clone();
+	let mut opt = Sgd::new(&g)?;
+	opt.add_boxed_callback(max_evals(8));
+	opt.add_callback(move |data| {evals_clone.lock().unwrap().push(data.eval_count); CallbackSignal::Continue});
+	opt.optimise(&mut ConstStream{shape: vec![4, 3]}, &g)?;
+
+	assert_eq!(*evals.lock().unwrap(), vec![4, 8, 12]);
+
+	Ok(())
+}
+
+#[test]
+fn test_csv_logger(){
+	_test_csv_logger().unwrap();
+}
+
+fn _test_csv_logger() -> Result<()>{
+	use ops::loss::mse::Mse;
+	use opt::sgd::Sgd;
+	use std::fs;
+	use std::io::Read;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Mse::new(&param, &input), tag![])?;
+
+	let path = temp_path("csv_logger.csv");
+	let mut opt = Sgd::new(&g)?;
+	opt.add_boxed_callback(max_steps(5));
+	opt.add_boxed_callback(csv_logger(path.to_str().unwrap()));
+	opt.optimise(&mut ConstStream{shape: vec![4, 3]}, &g)?;
+	// dropping the callback flushes any buffered rows
+	drop(opt);
+
+	let mut contents = String::new();
+	File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+	fs::remove_file(&path).unwrap();
+
+	let lines: Vec<&str> = contents.lines().collect();
+	assert_eq!(lines.len(), 7, "{}", contents);
+	assert_eq!(lines[0], "step,evals,err");
+	for (i, line) in lines[1..].iter().enumerate() {
+		let fields: Vec<&str> = line.split(',').collect();
+		assert_eq!(fields.len(), 3, "{}", line);
+		assert_eq!(fields[0].parse::<usize>().unwrap(), i + 1);
+		assert_eq!(fields[1].parse::<usize>().unwrap(), (i + 1) * 4);
+		assert!(fields[2].parse::<f32>().unwrap().is_finite());
+	}
+
+	Ok(())
+}
+
+#[test]
+fn test_lr_range_test(){
+	_test_lr_range_test().unwrap();
+}
+
+fn _test_lr_range_test() -> Result<()>{
+	use ops::loss::mse::Mse;
+	use init::Initialiser;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![1], "input", tag![])?;
+	let param = g.new_node(shape![1], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Mse::new(&param, &input), tag![])?;
+	g.set_initialiser(&param, Initialiser::fill(1.0));
+
+	// each step scales the param by (1 - 2 lr), so the loss falls fastest near lr = 0.5 and diverges above lr = 1.0
+	let curve = lr_range_test(&g, &mut ConstStream{shape: vec![1]}, 1e-3, 10.0, 50)?;
+
+	assert!(curve.len() > 2 && curve.len() < 50, "{:?}", curve);
+	assert!((curve[0].0 - 1e-3).abs() < 1e-9);
+	assert!(curve.windows(2).all(|w| w[0].0 < w[1].0));
+
+	let min_index = (0..curve.len()).fold(0, |min, i| if curve[i].1 < curve[min].1 {i} else {min});
+	assert!(min_index > 0 && min_index < curve.len() - 1, "{:?}", curve);
+
+	Ok(())
+}
+
+#[test]
+fn test_lr_finder_to_csv(){
+	_test_lr_finder_to_csv().unwrap();
+}
+
+fn _test_lr_finder_to_csv() -> Result<()>{
+	use ops::loss::mse::Mse;
+	use init::Initialiser;
+	use std::fs;
+	use std::io::Read;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![1], "input", tag![])?;
+	let param = g.new_node(shape![1], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Mse::new(&param, &input), tag![])?;
+	g.set_initialiser(&param, Initialiser::fill(1.0));
+
+	// the rate stays below 0.5, so the loss falls at every step and the test runs to completion
+	let iters = 20;
+	let path = temp_path("lr_finder.csv");
+	let rows = lr_finder_to_csv(&g, &mut ConstStream{shape: vec![1]}, path.to_str().unwrap(), 1e-3, 0.1, iters)?;
+	assert_eq!(rows, iters);
+
+	let mut contents = String::new();
+	File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+	fs::remove_file(&path).unwrap();
+
+	let lines: Vec<&str> = contents.lines().collect();
+	assert_eq!(lines.len(), iters + 1, "{}", contents);
+	assert_eq!(lines[0], "lr,loss,smoothed");
+
+	let rows: Vec<Vec<f32>> = lines[1..].iter().map(|line| line.split(',').map(|field| field.parse::<f32>().unwrap()).collect()).collect();
+	assert!(rows.iter().all(|row| row.len() == 3 && row.iter().all(|x| x.is_finite())), "{}", contents);
+	assert!(rows.windows(2).all(|w| w[0][0] < w[1][0]), "{}", contents);
+
+	// bias correction makes the first smoothed value equal to the first loss
+	assert!((rows[0][1] - rows[0][2]).abs() <= 1e-5 * rows[0][1].abs(), "{}", contents);
+
+	Ok(())
+}
+
+#[test]
+fn test_log_accuracy(){
+	_test_log_accuracy().unwrap();
+}
+
+fn _test_log_accuracy() -> Result<()>{
+	use ops::loss::mse::Mse;
+	use std::rc::Rc;
+	use std::cell::RefCell;
+	use ops::math::add::Add;
+	use opt::sgd::Sgd;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let output = g.new_node(shape![4, 3], "output", tag![])?;
+	let _o1 = g.new_op(Add::new(&param, &output), tag![])?;
+	let _o2 = g.new_op(Add::new(&input, &output), tag![])?;
+	let _o3 = g.new_op(Mse::new(&output, &input), tag![])?;
+
+	// the target is the input node, so evaluation only needs the input supplied
+	let mut opt = Sgd::new(&g)?.rate(0.1);
+	opt.add_boxed_callback(log_accuracy(&g, &output, &input, ConstStream{shape: vec![4, 3]})?);
+	opt.add_boxed_callback(max_steps(3));
+	opt.optimise(&mut ConstStream{shape: vec![4, 3]}, &g)?;
+
+	// with a zero input the target class is always 0, and the prediction is the argmax of each row of the parameter
+	let reports = Rc::new(RefCell::new(vec![]));
+	let reports_clone = reports.clone();
+	let mut callback = accuracy_callback(&g, &output, &input, ConstStream{shape: vec![4, 3]}, move |step, acc| reports_clone.borrow_mut().push((step, acc)))?;
+
+	let params = [ArrayD::from_shape_vec(&[4, 3][..], vec![
+		1.0, 0.0, 0.0,
+		2.0, 1.0, 0.0,
+		0.0, 1.0, 0.0,
+		3.0, -1.0, 2.0,
+	]).unwrap()];
+	let stream = ConstStream{shape: vec![4, 3]};
+	callback(&CallbackData{err: 0.0, step: 7, eval_count: 0, change_norm: 0.0, params: &params, stream: &stream});
+	assert_eq!(*reports.borrow(), vec![(7, 0.75)]);
+
+	Ok(())
+}
@@ -0,0 +1,78 @@
+use ndarray::{ArrayD, Zip};
+use rayon::prelude::*;
+
+/// The first and second moment estimates of the gradients, shared by the Adam family of optimisers.
+///
+/// m = β1 m + (1 - β1) ∇f(θ)
+/// v = β2 v + (1 - β2) ∇f(θ) ∇f(θ)
+pub(crate) struct Moments {
+	pub beta1: f32,
+	pub beta2: f32,
+	pub epsilon: f32,
+	pub momentum_vec: Vec<ArrayD<f32>>,
+	pub curvature_vec: Vec<ArrayD<f32>>,
+	/// The step count at which the estimates were last reset
+	pub state_start: usize,
+}
+
+impl Moments {
+	/// β1 = 0.9 and eps = 1e-8, with no estimates until the first update.
+	pub fn new(beta2: f32) -> Self {
+		Moments {
+			beta1: 0.9,
+			beta2: beta2,
+			epsilon: 1e-8,
+			momentum_vec: vec![],
+			curvature_vec: vec![],
+			state_start: 0,
+		}
+	}
+
+	/// Returns t, the number of updates to the estimates including the one about to be made at `step_count`.
+	pub fn t(&self, step_count: usize) -> usize {
+		step_count - self.state_start + 1
+	}
+
+	/// Returns the bias corrections for the momentum and curvature, 1/(1 - β1^t) and 1/(1 - β2^t).
+	pub fn corrections(&self, t: usize) -> (f32, f32) {
+		if t <= 1_000_000 {
+			(1.0/(1.0 - self.beta1.powi(t as i32)), 1.0/(1.0 - self.beta2.powi(t as i32)))
+		} else {
+			(1.0, 1.0)
+		}
+	}
+
+	/// Zeros the estimates, restarting bias correction from `step_count`.
+	pub fn reset(&mut self, step_count: usize) {
+		for arr in self.momentum_vec.iter_mut().chain(self.curvature_vec.iter_mut()) {
+			arr.fill(0.0);
+		}
+		self.state_start = step_count;
+	}
+
+	/// Updates the estimates with `grads`, then calls `update` with each parameter array and its momentum and curvature.
+	///
+	/// `update` should change the parameters in place and return the sum of squared changes, which are summed over all arrays.
+	pub fn update<F>(&mut self, params: &mut [ArrayD<f32>], grads: &[ArrayD<f32>], update: F) -> f32
+		where F: Fn(&mut ArrayD<f32>, &ArrayD<f32>, &ArrayD<f32>) -> f32 + Sync {
+		if self.momentum_vec.len() != params.len() {
+			self.momentum_vec = params.iter().map(|param| ArrayD::zeros(param.shape())).collect();
+		}
+		if self.curvature_vec.len() != params.len() {
+			self.curvature_vec = params.iter().map(|param| ArrayD::zeros(param.shape())).collect();
+		}
+
+		let beta1 = self.beta1;
+		let beta2 = self.beta2;
+		grads.par_iter().zip(self.momentum_vec.par_iter_mut()).zip(self.curvature_vec.par_iter_mut()).zip(params.par_iter_mut()).with_max_len(1).map(|(((param_grad_outer, momentum_outer), curvature_outer), params_outer)| {
+			Zip::from(&mut *momentum_outer)
+				.and(&mut *curvature_outer)
+				.and(param_grad_outer)
+				.apply(|momentum, curv, param_grad| {
+					*momentum = *momentum * beta1 + (1.0-beta1)*param_grad;
+					*curv = *curv * beta2 + (1.0-beta2)*param_grad*param_grad;
+				});
+			update(params_outer, momentum_outer, curvature_outer)
+		}).sum()
+	}
+}
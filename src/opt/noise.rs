@@ -0,0 +1,39 @@
+use ndarray::ArrayD;
+use rand::RngCore;
+use rand::distributions::{Distribution, Normal};
+use rng::new_rng;
+
+/// Annealed gaussian noise added to parameter gradients before each update.
+///
+/// At step t (starting from 0) the noise is drawn from N(0, η/(1 + t)^γ).
+pub(crate) struct GradNoise {
+	pub eta: f32,
+	pub gamma: f32,
+	pub rng: Box<RngCore + Send>,
+}
+
+impl GradNoise {
+	/// Disabled by default, with η = 0.
+	pub fn new() -> Self {
+		GradNoise {
+			eta: 0.0,
+			gamma: 0.55,
+			rng: Box::new(new_rng()),
+		}
+	}
+
+	/// Adds noise to each gradient in place. Does nothing if η is not positive.
+	pub fn apply(&mut self, step_count: usize, grads: &mut [ArrayD<f32>]) {
+		if !(self.eta > 0.0) {
+			return;
+		}
+
+		let variance = self.eta as f64 / (1.0 + step_count as f64).powf(self.gamma as f64);
+		let norm = Normal::new(0.0, variance.sqrt());
+		for grad in grads.iter_mut() {
+			for e in grad.iter_mut() {
+				*e += norm.sample(&mut self.rng) as f32;
+			}
+		}
+	}
+}
@@ -0,0 +1,278 @@
+use graph::{Subgraph, Result};
+use id::{NodeID, OpID};
+use opt::{WeightConstraint, LossScale};
+use opt::noise::GradNoise;
+use opt::ema::ParamEma;
+use opt::loss_scale::LossScaler;
+use opt::freeze::FrozenParams;
+use opt::grad_activity::GradActivity;
+use opt::histogram::{HistogramCollector, Histograms};
+use opt::agc::GradClip;
+use opt::centralize::GradCentralization;
+use opt::warmup::AutoWarmup;
+use ndarray::ArrayD;
+use rand::RngCore;
+
+/// The state of a `GradPipeline` which changes from step to step, saved along with the optimiser state.
+///
+/// The parameter moving average is saved separately, as a parameter array list.
+pub(crate) struct PipelineState {
+	pub loss_scale: f32,
+	pub stable_steps: usize,
+	pub prev_norm: Option<f32>,
+	pub steps_since_release: Option<usize>,
+}
+
+/// The processing shared by all optimisers, applied around the update rule of each optimiser.
+///
+/// Each optimiser step:
+/// 1. executes the subgraph with the loss scale applied, then removes the scale from the parameter gradients, skipping the update if they are not finite
+/// 2. records gradient activity and histograms, and measures the gradient norm for automatic warmup
+/// 3. centralises, clips, and adds noise to the gradients, in that order
+/// 4. zeros the gradients of frozen parameters
+/// 5. updates the parameters using the optimiser's rule, with its rate multiplied by the rate schedule and warmup
+/// 6. applies the weight constraint, restores frozen parameters, and updates the parameter moving average
+///
+/// Everything is disabled by default. Access through the `pipeline()` and `pipeline_mut()` methods of each optimiser.
+pub struct GradPipeline {
+	parameters: Vec<NodeID>,
+	loss_scale: LossScaler,
+	grad_activity: GradActivity,
+	histograms: HistogramCollector,
+	auto_warmup: AutoWarmup,
+	grad_centralization: GradCentralization,
+	grad_clip: GradClip,
+	grad_noise: GradNoise,
+	frozen: FrozenParams,
+	rate_schedule: Option<Box<FnMut(usize) -> f32>>,
+	weight_constraint: Option<WeightConstraint>,
+	param_ema: ParamEma,
+}
+
+impl GradPipeline {
+	pub(crate) fn new(parameters: Vec<NodeID>) -> Self {
+		GradPipeline {
+			parameters: parameters,
+			loss_scale: LossScaler::new(),
+			grad_activity: GradActivity::new(),
+			histograms: HistogramCollector::new(),
+			auto_warmup: AutoWarmup::new(),
+			grad_centralization: GradCentralization::new(),
+			grad_clip: GradClip::new(),
+			grad_noise: GradNoise::new(),
+			frozen: FrozenParams::new(),
+			rate_schedule: None,
+			weight_constraint: None,
+			param_ema: ParamEma::new(),
+		}
+	}
+
+	/// Scale the loss and gradients to avoid underflow, skipping any update where the scaled gradients are not finite
+	///
+	/// Default: None
+	pub fn loss_scale<L: Into<Option<LossScale>>>(&mut self, loss_scale: L) -> &mut Self {
+		self.loss_scale.set_mode(loss_scale.into());
+		self
+	}
+
+	/// Count, for each parameter element, the number of steps in which it received a nonzero gradient
+	///
+	/// See `grad_activity()`.
+	/// Default: false
+	pub fn track_grad_activity(&mut self, enable: bool) -> &mut Self {
+		self.grad_activity.enabled = enable;
+		self
+	}
+
+	/// Every `every` steps, collect histograms of the values and gradients of each parameter
+	///
+	/// Parameter values are binned before the update. See `last_histograms()`.
+	/// Default: None
+	pub fn collect_histograms<N: Into<Option<usize>>>(&mut self, every: N) -> &mut Self {
+		self.histograms.every = every.into();
+		self
+	}
+
+	/// Set the bin edges used by `collect_histograms()`, which must be strictly increasing
+	///
+	/// If `None`, each histogram uses equal width bins spanning its values.
+	/// Default: None
+	pub fn histogram_edges<E: Into<Option<Vec<f32>>>>(&mut self, edges: E) -> &mut Self {
+		self.histograms.edges = edges.into();
+		self
+	}
+
+	/// Automatic warmup, which holds the learning rate at 0.01 α until the global gradient norm stabilises,
+	/// i.e. until the ratio between consecutive gradient norms, max(n_t, n_t-1)/min(n_t, n_t-1), is at most `target_norm_ratio`.
+	/// The rate then ramps linearly up to α over the next 100 steps, and is not held again.
+	///
+	/// Combines with `rate_schedule()` by multiplication.
+	/// Default: None
+	pub fn auto_warmup<R: Into<Option<f32>>>(&mut self, target_norm_ratio: R) -> &mut Self {
+		self.auto_warmup.target_norm_ratio = target_norm_ratio.into();
+		self
+	}
+
+	/// Gradient centralisation, which subtracts the mean of each weight array's gradient from that gradient.
+	///
+	/// Only parameters with more than one axis are centralised, so biases are left unchanged.
+	/// Default: false
+	pub fn grad_centralization(&mut self, enabled: bool) -> &mut Self {
+		self.grad_centralization.enabled = enabled;
+		self
+	}
+
+	/// Adaptive gradient clipping, which rescales the gradient of each parameter array so that ||∇f(θ)|| <= λ max(||θ||, 1e-3)
+	///
+	/// Default: None
+	pub fn adaptive_grad_clip<L: Into<Option<f32>>>(&mut self, lambda: L) -> &mut Self {
+		self.grad_clip.lambda = lambda.into();
+		self
+	}
+
+	/// Annealed gradient noise, N(0, η/(1 + t)^γ), added to the gradients
+	///
+	/// Setting η to 0.0 disables the noise.
+	/// Default: η = 0.0, γ = 0.55
+	pub fn grad_noise(&mut self, eta: f32, gamma: f32) -> &mut Self {
+		self.grad_noise.eta = eta;
+		self.grad_noise.gamma = gamma;
+		self
+	}
+
+	/// Supply the rng used to generate gradient noise, e.g. a seeded rng for reproducibility.
+	///
+	/// Default: `rng::new_rng()`
+	pub fn grad_noise_rng<R: RngCore + 'static + Send>(&mut self, rng: R) -> &mut Self {
+		self.grad_noise.rng = Box::new(rng);
+		self
+	}
+
+	/// A schedule which multiplies the learning rate, α, based on the number of steps taken so far (starting from 0)
+	///
+	/// See the `schedules` module.
+	/// Default: None
+	pub fn rate_schedule(&mut self, schedule: Box<FnMut(usize) -> f32>) -> &mut Self {
+		self.rate_schedule = Some(schedule);
+		self
+	}
+
+	/// A constraint applied to each parameter array after every update
+	///
+	/// Default: None
+	pub fn weight_constraint<C: Into<Option<WeightConstraint>>>(&mut self, constraint: C) -> &mut Self {
+		self.weight_constraint = constraint.into();
+		self
+	}
+
+	/// Maintain an exponential moving average of the parameters, updated after every step as:
+	/// ema = decay ema + (1 - decay) θ
+	///
+	/// The average does not affect the optimisation, and can be retrieved with `ema_params()`, e.g. for evaluation.
+	/// Default: None
+	pub fn param_ema<D: Into<Option<f32>>>(&mut self, decay: D) -> &mut Self {
+		self.param_ema.decay = decay.into();
+		self
+	}
+
+	/// Excludes the parameters used by the ops from updates, e.g. to keep a pretrained backbone fixed while training new layers.
+	///
+	/// Gradients are still computed through frozen parameters, so the ops which depend on them continue to train.
+	pub fn freeze(&mut self, op_ids: &[OpID]) -> &mut Self {
+		self.frozen.freeze(&self.parameters, op_ids);
+		self
+	}
+
+	/// Allows the parameters used by the ops to be updated again after `freeze()`.
+	pub fn unfreeze(&mut self, op_ids: &[OpID]) -> &mut Self {
+		self.frozen.unfreeze(&self.parameters, op_ids);
+		self
+	}
+
+	/// Returns the current loss scale, if enabled by `loss_scale()`
+	pub fn current_loss_scale(&self) -> Option<f32> {
+		self.loss_scale.scale()
+	}
+
+	/// Returns the number of steps in which each parameter element received a nonzero gradient, if enabled by `track_grad_activity()` and at least one step has been taken.
+	///
+	/// Elements with a count of zero, such as the weights of dead ReLU units, have not been trained.
+	pub fn grad_activity(&self) -> Option<&[ArrayD<u32>]> {
+		self.grad_activity.counts()
+	}
+
+	/// Returns the most recently collected histograms, if enabled by `collect_histograms()` and at least one has been collected.
+	pub fn last_histograms(&self) -> Option<&Histograms> {
+		self.histograms.last()
+	}
+
+	/// Returns the exponential moving average of the parameters, if enabled by `param_ema()` and at least one step has been taken.
+	pub fn ema_params(&self) -> Option<&[ArrayD<f32>]> {
+		self.param_ema.params()
+	}
+
+	/// Returns the state which changes from step to step, other than the parameter moving average.
+	pub(crate) fn state(&self) -> PipelineState {
+		let (loss_scale, stable_steps) = self.loss_scale.state();
+		let (prev_norm, steps_since_release) = self.auto_warmup.state();
+		PipelineState{loss_scale, stable_steps, prev_norm, steps_since_release}
+	}
+
+	/// Returns the parameter moving average, which is empty if disabled or no steps have been taken.
+	pub(crate) fn ema_vec(&self) -> &[ArrayD<f32>] {
+		&self.param_ema.params
+	}
+
+	/// Restores the state returned by `state()` and `ema_vec()`.
+	pub(crate) fn set_state(&mut self, state: PipelineState, ema_params: Vec<ArrayD<f32>>) {
+		self.loss_scale.set_state(state.loss_scale, state.stable_steps);
+		self.auto_warmup.set_state(state.prev_norm, state.steps_since_release);
+		self.param_ema.params = ema_params;
+	}
+
+	/// Executes the subgraph and performs one update using `update`, which is supplied the rate multiplier,
+	/// the parameters to update in place, and the processed gradients, and returns the sum of squared changes.
+	///
+	/// Returns the loss, the l2 norm of the parameter change, and the new parameters,
+	/// or a change norm of `None` if the update was skipped due to non-finite gradients.
+	pub(crate) fn step<F>(&mut self, subgraph: &mut Subgraph, mut inputs: Vec<ArrayD<f32>>, mut parameters: Vec<ArrayD<f32>>, step_count: usize, update: F) -> Result<(f32, Option<f32>, Vec<ArrayD<f32>>)>
+		where F: FnOnce(f32, &mut [ArrayD<f32>], &[ArrayD<f32>]) -> f32 {
+		assert_eq!(parameters.len(), self.parameters.len(), "Incorrect number of prameters supplied to optimiser.step()");
+		inputs.append(&mut parameters);
+		assert_eq!(subgraph.inputs().len(), inputs.len(), "Incorrect number of inputs supplied to optimiser.step()");
+
+		subgraph.set_loss_scale(self.loss_scale.scale().unwrap_or(1.0));
+		let storage = subgraph.execute(inputs)?;
+		let loss = storage.loss();
+		let mut map = storage.into_map();
+
+		let mut params: Vec<_> = self.parameters.iter().map(|p| map.remove(&p.value_id()).expect("Subgraph must have parameter values as outputs.")).collect();
+		let mut param_grads: Vec<_> = self.parameters.iter().map(|p| map.remove(&p.gradient_id()).expect("Subgraph must have parameter gradients as outputs.")).collect();
+		if !self.loss_scale.unscale(&mut param_grads) {
+			return Ok((loss, None, params));
+		}
+
+		self.grad_activity.update(&param_grads);
+		self.histograms.update(step_count, &params, &param_grads);
+		let warmup = self.auto_warmup.update(&param_grads);
+		self.grad_centralization.apply(&mut param_grads);
+		self.grad_clip.apply(&params, &mut param_grads);
+		self.grad_noise.apply(step_count, &mut param_grads);
+		let held = self.frozen.hold(&self.parameters, &params, &mut param_grads);
+
+		let rate_multiplier = self.rate_schedule.as_mut().map(|schedule| schedule(step_count)).unwrap_or(1.0) * warmup;
+		let change_sqr = update(rate_multiplier, &mut params[..], &param_grads[..]);
+
+		if let Some(constraint) = self.weight_constraint {
+			for param in params.iter_mut() {
+				constraint.apply(param);
+			}
+		}
+
+		FrozenParams::restore(held, &mut params);
+
+		self.param_ema.update(&params);
+
+		Ok((loss, Some(change_sqr.sqrt()), params))
+	}
+}
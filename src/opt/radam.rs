@@ -0,0 +1,315 @@
+use graph::{GraphDef, Subgraph, Result};
+use id::{NodeTag, NodeID, DataID};
+use opt::{Opt, CallbackData, CallbackSignal};
+use opt::pipeline::GradPipeline;
+use opt::moments::Moments;
+use opt::state::{OptState, save_state};
+use ndarray::{ArrayD, Zip};
+use std::num::FpCategory;
+use std::path::Path;
+use std::io;
+
+/// RAdam (Rectified Adam) Optimiser
+///
+/// Adam, with the adaptive learning rate disabled in early steps while the variance estimate is unreliable, removing the need for warmup.
+///
+/// t = t + 1
+/// m = β1 m + (1 - β1) ∇f(θ)
+/// v = β2 v + (1 - β2) ∇f(θ) ∇f(θ)
+/// m_c = m / (1 - β1^t)
+/// ρ_inf = 2 / (1 - β2) - 1
+/// ρ_t = ρ_inf - 2 t β2^t / (1 - β2^t)
+///
+/// If ρ_t > threshold, the rectified adaptive update is used:
+/// v_c = v / (1 - β2^t)
+/// r = sqrt(((ρ_t - 4)(ρ_t - 2) ρ_inf) / ((ρ_inf - 4)(ρ_inf - 2) ρ_t))
+/// θ = θ - α r m_c / (sqrt(v_c) + eps)
+///
+/// Otherwise, the un-adapted momentum update is used:
+/// θ = θ - α m_c
+///
+pub struct RAdam {
+	subgraph: Subgraph,
+	inputs: Vec<DataID>,
+	parameters: Vec<NodeID>,
+	callbacks: Vec<Box<FnMut(&CallbackData)->CallbackSignal>>,
+	rate: f32,
+	moments: Moments,
+	threshold: f32,
+	step_count: usize,
+	pipeline: GradPipeline,
+	reset_each_epoch: bool,
+}
+
+
+impl RAdam {
+
+	/// Create an optimisation problem assuming that all nodes marked `Parameter` should be optimised, and all other leaf nodes are batch inputs.
+	pub fn new(graph: &GraphDef) -> Result<Self> {
+
+		let subgraph = graph.default_subgraph()?;
+
+		let inputs = subgraph.inputs().iter().filter(|data_id| !data_id.tags().contains(&NodeTag::Parameter)).cloned().collect();
+		let parameters = subgraph.inputs().iter().filter_map(|data_id| if data_id.tags().contains(&NodeTag::Parameter) {Some(data_id.node_id())} else {None}).collect();
+		Ok(RAdam::from_parts(subgraph, inputs, parameters))
+	}
+
+	/// Define a custom optimisation problem by supplying a subgraph and a list of parameters to optimise.
+	///
+	/// The subgraph must meet the following:
+	/// - subgraph inputs are ordered with general inputs (values or gradients) followed by parameter values.
+	/// - subgraph outputs must include all parameters values and gradients.
+	///
+	/// Note: All leaf nodes not listed as parameters are assumed to be batch inputs.
+	pub fn with_subgraph(subgraph: Subgraph, parameter_ids: Vec<NodeID>) -> Self {
+
+		let n_inputs = subgraph.inputs().len() - parameter_ids.len();
+		let maybe_inputs = subgraph.inputs()[0..n_inputs].to_vec();
+		
+		assert!(subgraph.inputs()[n_inputs..].iter().cloned().eq(parameter_ids.iter().map(|id| id.value_id())), "The final inputs to the subgraph must be the values of the optimiser parameter nodes");
+
+		assert!(parameter_ids.iter().all(|id| subgraph.outputs().contains(&id.value_id())), "Subgraph outputs must contain all parameter values");
+		assert!(parameter_ids.iter().all(|id| subgraph.outputs().contains(&id.gradient_id())), "Subgraph outputs must contain all parameter gradients");
+
+		RAdam::from_parts(subgraph, maybe_inputs, parameter_ids)
+	}
+
+	fn from_parts(subgraph: Subgraph, inputs: Vec<DataID>, parameters: Vec<NodeID>) -> Self {
+		RAdam {
+			pipeline: GradPipeline::new(parameters.clone()),
+			inputs: inputs,
+			parameters: parameters,
+			subgraph: subgraph,
+			callbacks: vec![],
+			rate: 1e-3,
+			moments: Moments::new(0.999),
+			threshold: 4.0,
+			step_count: 0,
+			reset_each_epoch: false,
+		}
+	}
+
+	/// Learning rate, α
+	pub fn rate(mut self, rate: f32) -> Self{
+		self.rate = rate;
+		self
+	}
+
+	/// Momentum coefficient, β1
+	///
+	/// Default: 0.9
+	pub fn beta1(mut self, beta1: f32) -> Self{
+		self.moments.beta1 = beta1;
+		self
+	}
+
+	/// Momentum coefficient, β2
+	///
+	/// Default: 0.999
+	pub fn beta2(mut self, beta2: f32) -> Self{
+		self.moments.beta2 = beta2;
+		self
+	}
+
+	/// Fuzz Factor, eps
+	///
+	/// Sometimes worth increasing, according to google.
+	/// Default: 1e-8
+	pub fn epsilon(mut self, epsilon: f32) -> Self{
+		self.moments.epsilon = epsilon;
+		self
+	}
+
+	/// The value the approximated simple moving average length, ρ_t, must exceed before the rectified adaptive update is used.
+	///
+	/// Must be at least 4.0 for the rectification term to be defined.
+	/// Default: 4.0
+	pub fn threshold(mut self, threshold: f32) -> Self {
+		assert!(threshold >= 4.0, "RAdam threshold must be at least 4.0");
+		self.threshold = threshold;
+		self
+	}
+
+	/// Zero the momentum and curvature estimates at the end of each epoch of the training stream, see `Opt::reset_state()`
+	///
+	/// Epochs are taken from `DataStream::epoch_size()`, if the training stream does not report one this has no effect.
+	/// Bias correction restarts after each reset. The parameters, and the step count used by any rate schedule, are unaffected.
+	/// Default: false
+	pub fn reset_state_each_epoch(mut self, enable: bool) -> Self {
+		self.reset_each_epoch = enable;
+		self
+	}
+
+	/// Returns the current learning rate, α
+	pub fn learning_rate(&self) -> f32 {
+		self.rate
+	}
+
+	/// Returns the number of steps taken so far
+	pub fn step_count(&self) -> usize {
+		self.step_count
+	}
+
+	/// Returns the gradient processing applied around each update, e.g. loss scaling, clipping, and parameter averaging.
+	pub fn pipeline(&self) -> &GradPipeline {
+		&self.pipeline
+	}
+
+	/// Returns the gradient processing applied around each update, for configuration.
+	pub fn pipeline_mut(&mut self) -> &mut GradPipeline {
+		&mut self.pipeline
+	}
+
+	/// Returns the rectification term, r, for step `t` (starting from 1), or `None` if ρ_t does not exceed the threshold and the un-adapted update is used.
+	pub fn rectification(&self, t: usize) -> Option<f32> {
+		let beta2 = self.moments.beta2 as f64;
+		let beta2_t = beta2.powi(t as i32);
+		let rho_inf = 2.0/(1.0 - beta2) - 1.0;
+		let rho_t = rho_inf - 2.0 * t as f64 * beta2_t/(1.0 - beta2_t);
+		if rho_t > self.threshold as f64 {
+			Some((((rho_t - 4.0)*(rho_t - 2.0)*rho_inf)/((rho_inf - 4.0)*(rho_inf - 2.0)*rho_t)).sqrt() as f32)
+		} else {
+			None
+		}
+	}
+
+	/// Writes the learning rate, step count, momentum and curvature vectors, and the pipeline's loss scale, warmup progress, and parameter moving average to a file,
+	/// so that optimisation can be resumed with `load_state()`.
+	///
+	/// The pipeline configuration, gradient activity counts, histograms, and gradient noise rng are not saved.
+	pub fn save_state<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+		save_state(path, self.step_count, self.moments.state_start, self.rate, &self.pipeline.state(), &[&self.moments.momentum_vec[..], &self.moments.curvature_vec[..], self.pipeline.ema_vec()])
+	}
+
+	/// Restores the state written by `save_state()`.
+	///
+	/// The pipeline should be configured before loading, as enabling loss scaling resets the scale.
+	/// Returns an error if the number or shapes of the saved arrays do not match the parameters of this optimiser.
+	pub fn load_state<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+		let OptState{step_count, state_start, rate, pipeline, mut vecs} = OptState::load(path, &self.parameters, 3)?;
+		self.step_count = step_count;
+		self.moments.state_start = state_start;
+		self.rate = rate;
+		self.pipeline.set_state(pipeline, vecs.pop().unwrap());
+		self.moments.curvature_vec = vecs.pop().unwrap();
+		self.moments.momentum_vec = vecs.pop().unwrap();
+		Ok(())
+	}
+}
+
+impl Opt for RAdam {
+
+	fn subgraph(&self) -> &Subgraph {
+		&self.subgraph
+	}
+
+	fn inputs(&self) -> &[DataID]{
+		&self.inputs
+	}
+
+	fn parameters(&self) -> &[NodeID]{
+		&self.parameters
+	}
+
+	fn step(&mut self, inputs: Vec<ArrayD<f32>>, parameters: Vec<ArrayD<f32>>) -> Result<(f32, usize, f32, Vec<ArrayD<f32>>)> {
+		assert_eq!(inputs.len(), self.inputs().len(), "Incorrect number of inputs supplied to optimiser.step()");
+
+		let base_rate = self.rate;
+		let epsilon = self.moments.epsilon;
+		let t = self.moments.t(self.step_count);
+		let (momentum_correction, curv_correction) = self.moments.corrections(t);
+		let rectification = self.rectification(t);
+		let moments = &mut self.moments;
+
+		let (loss, change_norm, params) = self.pipeline.step(&mut self.subgraph, inputs, parameters, self.step_count, |rate_multiplier, params, param_grads| {
+			let rate = base_rate * rate_multiplier;
+			moments.update(params, param_grads, |params, momentum, curvature| {
+				let mut change_sqr = 0.0;
+				Zip::from(params)
+					.and(momentum)
+					.and(curvature)
+					.apply(|param, momentum, curv| {
+						let change = if let Some(r) = rectification {
+							-rate * r * (*momentum) * momentum_correction/((*curv*curv_correction).sqrt() + epsilon)
+						} else {
+							-rate * (*momentum) * momentum_correction
+						};
+						change_sqr += change*change;
+						*param += change;
+						if let FpCategory::Subnormal = param.classify(){
+							*param = 0.0;
+						}
+					});
+				change_sqr
+			})
+		})?;
+
+		match change_norm {
+			Some(change_norm) => {
+				self.step_count += 1;
+				Ok((loss, self.step_count, change_norm, params))
+			},
+			None => Ok((loss, self.step_count, 0.0, params)),
+		}
+	}
+
+	fn callbacks(&mut self) -> &mut [Box<FnMut(&CallbackData)->CallbackSignal>]{
+		&mut self.callbacks
+	}
+
+	fn add_boxed_callback(&mut self, func: Box<FnMut(&CallbackData)->CallbackSignal>){
+		self.callbacks.push(func)
+	}
+
+	fn reset_state(&mut self) {
+		self.moments.reset(self.step_count);
+	}
+
+	fn resets_state_each_epoch(&self) -> bool {
+		self.reset_each_epoch
+	}
+}
+
+
+#[test]
+fn test_radam_rectification(){
+	_test_radam_rectification().unwrap();
+}
+
+fn _test_radam_rectification() -> Result<()>{
+	use ops::loss::proportional::Proportional;
+
+	let mut g = GraphDef::new();
+
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Proportional::new(&param).multiplier(24.0), tag![])?;
+
+	// each element has a constant gradient of 2.0, so m_c = 2.0 and sqrt(v_c) = 2.0 at every step
+	let mut opt = RAdam::new(&g)?.rate(0.1);
+	let mut params = g.initialise_nodes(opt.parameters())?;
+	let n_sqrt = 12.0f32.sqrt();
+
+	for t in 1..21 {
+		let rectification = opt.rectification(t);
+		let (_err, step, change_norm, new_params) = opt.step(vec![], params)?;
+		params = new_params;
+		assert_eq!(step, t);
+
+		if t <= 3 {
+			// early steps use the un-adapted update, α m_c
+			assert!(rectification.is_none());
+			assert!((change_norm - 0.1 * 2.0 * n_sqrt).abs() < 1e-4, "step: {} change_norm: {}", t, change_norm);
+		} else if t >= 6 {
+			// later steps use the rectified adaptive update, α r m_c / sqrt(v_c)
+			let r = rectification.expect("rectified update should be in use");
+			assert!(r > 0.0 && r < 1.0);
+			assert!((change_norm - 0.1 * r * n_sqrt).abs() < 1e-4, "step: {} change_norm: {} r: {}", t, change_norm, r);
+		}
+	}
+
+	// the rectification term grows towards 1 over time
+	assert!(opt.rectification(10).unwrap() < opt.rectification(1000).unwrap());
+	assert!(opt.rectification(100_000).unwrap() > 0.99);
+
+	Ok(())
+}
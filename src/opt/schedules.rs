@@ -0,0 +1,149 @@
+//! Learning rate schedules.
+//!
+//! A schedule maps the optimiser step count (starting from 0) to a multiplier of the optimiser's base rate,
+//! and is supplied to an optimiser using `GradPipeline::rate_schedule()`.
+//!
+//! `triangular()` takes absolute rates, and returns them relative to its `base_lr`, which should also be used as the optimiser's base rate.
+//! The rates supplied to the other schedules are themselves multipliers of the optimiser's base rate, and are returned unchanged.
+
+use std::f32::consts::PI;
+
+/// Triangular cyclic learning rate policy.
+///
+/// The rate ramps linearly from `base_lr` up to `max_lr` over `step_size` steps, then back down to `base_lr` over the next `step_size` steps,
+/// repeating indefinitely. The returned multiplier is relative to `base_lr`, which should be the base rate of the optimiser.
+pub fn triangular(base_lr: f32, max_lr: f32, step_size: usize) -> Box<FnMut(usize) -> f32> {
+	assert!(step_size > 0, "triangular schedule step_size must be greater than zero");
+	assert!(base_lr > 0.0, "triangular schedule base_lr must be greater than zero");
+	Box::new(move |step| {
+		let cycle_pos = (step % (2 * step_size)) as f32 / step_size as f32; // in [0, 2)
+		let x = (cycle_pos - 1.0).abs();
+		let lr = base_lr + (max_lr - base_lr) * (1.0 - x);
+		lr / base_lr
+	})
+}
+
+/// Linear warmup followed by inverse square root decay, as commonly used for transformers.
+///
+/// Returns `peak_lr * min(step/warmup_steps, sqrt(warmup_steps/step))`, which rises linearly from 0 at step 0,
+/// reaches `peak_lr` at `warmup_steps`, then decays proportionally to `1/sqrt(step)`.
+/// As the value multiplies the optimiser's base rate, either set the base rate to 1.0 and supply the absolute `peak_lr`,
+/// or supply a `peak_lr` of 1.0 to peak at the base rate.
+pub fn warmup_inverse_sqrt(warmup_steps: usize, peak_lr: f32) -> Box<FnMut(usize) -> f32> {
+	assert!(warmup_steps > 0, "warmup_inverse_sqrt schedule warmup_steps must be greater than zero");
+	Box::new(move |step| {
+		let ratio = step as f32 / warmup_steps as f32;
+		if ratio <= 1.0 {
+			peak_lr * ratio
+		} else {
+			peak_lr / ratio.sqrt()
+		}
+	})
+}
+
+
+/// Cosine annealing with warm restarts (SGDR).
+///
+/// Within each cycle the rate follows `min_lr + (initial_lr - min_lr)*(1 + cos(π*t/T))/2`, decaying from `initial_lr` towards `min_lr`,
+/// where `t` is the number of steps since the last restart and `T` is the length of the current cycle.
+/// The first cycle is `t0` steps long, and each following cycle is `t_mult` times longer than the one before.
+/// As with `warmup_inverse_sqrt()`, the value multiplies the optimiser's base rate.
+pub fn sgdr(initial_lr: f32, min_lr: f32, t0: usize, t_mult: usize) -> Box<FnMut(usize) -> f32> {
+	assert!(t0 > 0, "sgdr schedule t0 must be greater than zero");
+	assert!(t_mult > 0, "sgdr schedule t_mult must be greater than zero");
+	Box::new(move |step| {
+		let (t, cycle_len) = if t_mult == 1 {
+			(step % t0, t0)
+		} else {
+			// cycle n starts at t0*(t_mult^n - 1)/(t_mult - 1), so n is found with a log, then corrected for any rounding error
+			let cycle_start = |n: u32| t0 * (t_mult.pow(n) - 1) / (t_mult - 1);
+			let mut n = ((1.0 + step as f64 * (t_mult - 1) as f64 / t0 as f64).ln() / (t_mult as f64).ln()).floor() as u32;
+			while n > 0 && cycle_start(n) > step {
+				n -= 1;
+			}
+			while cycle_start(n + 1) <= step {
+				n += 1;
+			}
+			(step - cycle_start(n), t0 * t_mult.pow(n))
+		};
+		let cos = (PI * t as f32 / cycle_len as f32).cos();
+		min_lr + (initial_lr - min_lr) * 0.5 * (1.0 + cos)
+	})
+}
+
+
+#[test]
+fn test_triangular(){
+	let mut schedule = triangular(0.1, 0.5, 4);
+
+	let expected = [
+		(0, 1.0), (1, 2.0), (2, 3.0), (3, 4.0), (4, 5.0),
+		(5, 4.0), (6, 3.0), (7, 2.0), (8, 1.0),
+		(10, 3.0), (12, 5.0), (16, 1.0), (100, 1.0), (102, 3.0),
+	];
+
+	for &(step, multiplier) in expected.iter() {
+		let actual = schedule(step);
+		assert!((actual - multiplier).abs() < 1e-5, "step: {} expected: {} actual: {}", step, multiplier, actual);
+	}
+}
+
+#[test]
+fn test_warmup_inverse_sqrt(){
+	let mut schedule = warmup_inverse_sqrt(100, 0.5);
+
+	// linear warmup
+	assert_eq!(schedule(0), 0.0);
+	for &step in [10, 25, 50, 99].iter() {
+		let expected = 0.5 * step as f32 / 100.0;
+		assert!((schedule(step) - expected).abs() < 1e-6, "step: {} expected: {} actual: {}", step, expected, schedule(step));
+	}
+
+	// peak
+	assert!((schedule(100) - 0.5).abs() < 1e-6);
+	assert!(schedule(99) < schedule(100) && schedule(101) < schedule(100));
+
+	// inverse sqrt decay, quadrupling the step halves the rate
+	for &step in [100, 150, 400, 1000].iter() {
+		let ratio = schedule(step * 4) / schedule(step);
+		assert!((ratio - 0.5).abs() < 1e-5, "step: {} ratio: {}", step, ratio);
+	}
+	assert!((schedule(400) - 0.25).abs() < 1e-6);
+}
+
+#[test]
+fn test_sgdr(){
+	let mut schedule = sgdr(1.0, 0.1, 10, 2);
+
+	// restarts at 0, 10, 30, 70
+	for &step in [0, 10, 30, 70].iter() {
+		assert!((schedule(step) - 1.0).abs() < 1e-6, "step: {} actual: {}", step, schedule(step));
+	}
+	for &step in [9, 29, 69].iter() {
+		assert!(schedule(step) < 0.15, "step: {} actual: {}", step, schedule(step));
+	}
+
+	// the midpoint of each cycle is halfway between initial_lr and min_lr, so the cycle lengths double
+	for &step in [5, 20, 50].iter() {
+		assert!((schedule(step) - 0.55).abs() < 1e-5, "step: {} actual: {}", step, schedule(step));
+	}
+
+	// monotonically decreasing within a cycle
+	for step in 10..29 {
+		assert!(schedule(step + 1) < schedule(step));
+	}
+
+	// late cycles are found directly, the 20th restart is at 10*(2^20 - 1)
+	let restart = 10 * ((1 << 20) - 1);
+	assert!((schedule(restart) - 1.0).abs() < 1e-6, "actual: {}", schedule(restart));
+	assert!(schedule(restart - 1) < 0.15, "actual: {}", schedule(restart - 1));
+
+	// t_mult of 1 gives a fixed cycle length, starting from initial_lr
+	let mut schedule = sgdr(0.5, 0.0, 4, 1);
+	assert_eq!(schedule(0), 0.5);
+	assert!((schedule(2) - 0.25).abs() < 1e-6);
+	for step in 0..4 {
+		assert_eq!(schedule(step), schedule(step + 4));
+		assert_eq!(schedule(step), schedule(step + 40));
+	}
+}
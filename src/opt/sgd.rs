@@ -1,9 +1,13 @@
 use graph::{GraphDef, Subgraph, Result};
 use id::{NodeTag, NodeID, DataID};
 use opt::{Opt, CallbackData, CallbackSignal};
+use opt::pipeline::GradPipeline;
+use opt::state::{OptState, save_state};
 use ndarray::{ArrayD, Zip};
 use std::num::FpCategory;
 use rayon::prelude::*;
+use std::path::Path;
+use std::io;
 
 pub struct Sgd {
 	subgraph: Subgraph,
@@ -14,6 +18,8 @@ pub struct Sgd {
 	momentum: Option<f32>,
 	momentum_vec: Vec<ArrayD<f32>>,
 	step_count: usize,
+	pipeline: GradPipeline,
+	reset_each_epoch: bool,
 }
 
 
@@ -24,16 +30,9 @@ impl Sgd {
 
 		let subgraph = graph.default_subgraph()?;
 
-		Ok(Sgd {
-			inputs: subgraph.inputs().iter().filter(|data_id| !data_id.tags().contains(&NodeTag::Parameter)).cloned().collect(),
-			parameters: subgraph.inputs().iter().filter_map(|data_id| if data_id.tags().contains(&NodeTag::Parameter) {Some(data_id.node_id())} else {None}).collect(),
-			subgraph: subgraph,
-			callbacks: vec![],
-			rate: 1e-3,
-			momentum: None,
-			momentum_vec: vec![],
-			step_count: 0,
-		})
+		let inputs = subgraph.inputs().iter().filter(|data_id| !data_id.tags().contains(&NodeTag::Parameter)).cloned().collect();
+		let parameters = subgraph.inputs().iter().filter_map(|data_id| if data_id.tags().contains(&NodeTag::Parameter) {Some(data_id.node_id())} else {None}).collect();
+		Ok(Sgd::from_parts(subgraph, inputs, parameters))
 	}
 
 	/// Define a custom optimisation problem by supplying a subgraph and a list of parameters to optimise.
@@ -53,15 +52,21 @@ impl Sgd {
 		assert!(parameter_ids.iter().all(|id| subgraph.outputs().contains(&id.value_id())), "Subgraph outputs must contain all parameter values");
 		assert!(parameter_ids.iter().all(|id| subgraph.outputs().contains(&id.gradient_id())), "Subgraph outputs must contain all parameter gradients");
 
+		Sgd::from_parts(subgraph, maybe_inputs, parameter_ids)
+	}
+
+	fn from_parts(subgraph: Subgraph, inputs: Vec<DataID>, parameters: Vec<NodeID>) -> Self {
 		Sgd {
-			inputs: maybe_inputs,
-			parameters: parameter_ids,
+			pipeline: GradPipeline::new(parameters.clone()),
+			inputs: inputs,
+			parameters: parameters,
 			subgraph: subgraph,
 			callbacks: vec![],
 			rate: 1e-3,
 			momentum: None,
 			momentum_vec: vec![],
 			step_count: 0,
+			reset_each_epoch: false,
 		}
 	}
 
@@ -87,6 +92,57 @@ impl Sgd {
 		self.momentum = momentum.into();
 		self
 	}
+
+	/// Zero the momentum at the end of each epoch of the training stream, see `Opt::reset_state()`
+	///
+	/// Epochs are taken from `DataStream::epoch_size()`, if the training stream does not report one this has no effect.
+	/// The parameters, and the step count used by any rate schedule, are unaffected.
+	/// Default: false
+	pub fn reset_state_each_epoch(mut self, enable: bool) -> Self {
+		self.reset_each_epoch = enable;
+		self
+	}
+
+	/// Returns the current learning rate, α
+	pub fn learning_rate(&self) -> f32 {
+		self.rate
+	}
+
+	/// Returns the number of steps taken so far
+	pub fn step_count(&self) -> usize {
+		self.step_count
+	}
+
+	/// Returns the gradient processing applied around each update, e.g. loss scaling, clipping, and parameter averaging.
+	pub fn pipeline(&self) -> &GradPipeline {
+		&self.pipeline
+	}
+
+	/// Returns the gradient processing applied around each update, for configuration.
+	pub fn pipeline_mut(&mut self) -> &mut GradPipeline {
+		&mut self.pipeline
+	}
+
+	/// Writes the learning rate, step count, momentum vectors, and the pipeline's loss scale, warmup progress, and parameter moving average to a file,
+	/// so that optimisation can be resumed with `load_state()`.
+	///
+	/// The pipeline configuration, gradient activity counts, histograms, and gradient noise rng are not saved.
+	pub fn save_state<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+		save_state(path, self.step_count, 0, self.rate, &self.pipeline.state(), &[&self.momentum_vec[..], self.pipeline.ema_vec()])
+	}
+
+	/// Restores the state written by `save_state()`.
+	///
+	/// The pipeline should be configured before loading, as enabling loss scaling resets the scale.
+	/// Returns an error if the number or shapes of the saved arrays do not match the parameters of this optimiser.
+	pub fn load_state<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+		let OptState{step_count, rate, pipeline, mut vecs, ..} = OptState::load(path, &self.parameters, 2)?;
+		self.step_count = step_count;
+		self.rate = rate;
+		self.pipeline.set_state(pipeline, vecs.pop().unwrap());
+		self.momentum_vec = vecs.pop().unwrap();
+		Ok(())
+	}
 }
 
 impl Opt for Sgd {
@@ -103,70 +159,61 @@ impl Opt for Sgd {
 		&self.parameters
 	}
 
-	fn step(&mut self, mut inputs: Vec<ArrayD<f32>>, mut parameters: Vec<ArrayD<f32>>) -> Result<(f32, usize, f32, Vec<ArrayD<f32>>)>{
+	fn step(&mut self, inputs: Vec<ArrayD<f32>>, parameters: Vec<ArrayD<f32>>) -> Result<(f32, usize, f32, Vec<ArrayD<f32>>)>{
 		assert_eq!(inputs.len(), self.inputs().len(), "Incorrect number of inputs supplied to optimiser.step()");
-		assert_eq!(parameters.len(), self.parameters().len(), "Incorrect number of prameters supplied to optimiser.step()");
 
-		inputs.append(&mut parameters);
-		
-		assert_eq!(self.subgraph.inputs().len(), inputs.len());
+		let base_rate = self.rate;
+		let momentum = self.momentum;
+		let momentum_vec = &mut self.momentum_vec;
+		let (loss, change_norm, params) = self.pipeline.step(&mut self.subgraph, inputs, parameters, self.step_count, |rate_multiplier, params, param_grads| {
+			let rate = base_rate * rate_multiplier;
+			if let Some(momentum) = momentum {
+				if momentum_vec.len() != params.len() {
+					*momentum_vec = params.iter().map(|param| ArrayD::zeros(param.shape())).collect();
+				}
 
-		let storage = self.subgraph.execute(inputs)?;
-		let loss = storage.loss();
-		let mut map = storage.into_map();
+				param_grads.par_iter().zip(params.par_iter_mut()).zip(momentum_vec.par_iter_mut()).with_max_len(1).map(|((param_grad_outer, params_outer), momentum_outer)| {
+					let mut change_sqr = 0.0;
+					Zip::from(param_grad_outer)
+						.and(momentum_outer)
+						.and(params_outer)
+						.apply(|grad, grad_momentum, param| {
+							*grad_momentum = (*grad_momentum) * momentum + grad;
+							let change = -rate * (*grad_momentum);
+							change_sqr += change * change;
+							*param += change;
+							if let FpCategory::Subnormal = param.classify(){
+								*param = 0.0;
+							}
+						});
+					change_sqr
+				}).sum()
 
-		let mut params: Vec<_> = self.parameters.iter().map(|p| map.remove(&p.value_id()).expect("Subgraph must have parameter values as outputs.")).collect();
-		let param_grads: Vec<_> = self.parameters.iter().map(|p| map.remove(&p.gradient_id()).expect("Subgraph must have parameter gradients as outputs.")).collect();
-		
-		let rate = self.rate;
-		let change_sqr: f32;
-		if let Some(momentum) = self.momentum {
-			if self.momentum_vec.len() != self.parameters.len() {
-				self.momentum_vec = params.iter().map(|param| ArrayD::zeros(param.shape())).collect();
+			} else {
+				param_grads.par_iter().zip(params.par_iter_mut()).with_max_len(1).map(|(param_grad_outer, params_outer)| {
+					let mut change_sqr = 0.0;
+					Zip::from(param_grad_outer)
+						.and(params_outer)
+						.apply(|grad, param| {
+							let change = -rate * (*grad);
+							change_sqr += change * change;
+							*param += change;
+							if let FpCategory::Subnormal = param.classify(){
+								*param = 0.0;
+							}
+						});
+					change_sqr
+				}).sum()
 			}
+		})?;
 
-			//for (i, grad) in self.parameters.iter().map(|p| map.remove(&p.gradient_id()).expect("Subgraph must have parameter gradients as outputs.")).enumerate() {
-				// self.momentum_vec[i] *= momentum;
-				// self.momentum_vec[i] += &grad;
-				// params[i].scaled_add(-self.rate, &self.momentum_vec[i]);
-			change_sqr = param_grads.par_iter().zip(params.par_iter_mut()).zip(self.momentum_vec.par_iter_mut()).with_max_len(1).map(|((param_grad_outer, params_outer), momentum_outer)| {
-				let mut change_sqr = 0.0;
-				Zip::from(param_grad_outer)
-					.and(momentum_outer)
-					.and(params_outer)
-					.apply(|grad, grad_momentum, param| {
-						*grad_momentum = (*grad_momentum) * momentum + grad;
-						let change = -rate * (*grad_momentum);
-						change_sqr += change * change;
-						*param += change;
-						if let FpCategory::Subnormal = param.classify(){
-							*param = 0.0;
-						}
-					});
-				change_sqr
-			}).sum();
-
-		} else {
-			//for (i, grad) in self.parameters.iter().map(|p| map.remove(&p.gradient_id()).expect("Subgraph must have parameter gradients as outputs.")).enumerate() {
-			change_sqr = param_grads.par_iter().zip(params.par_iter_mut()).with_max_len(1).map(|(param_grad_outer, params_outer)| {
-				let mut change_sqr = 0.0;
-				Zip::from(param_grad_outer)
-					.and(params_outer)
-					.apply(|grad, param| {
-						let change = -rate * (*grad);
-						change_sqr += change * change;
-						*param += change;
-						if let FpCategory::Subnormal = param.classify(){
-							*param = 0.0;
-						}
-					});
-				change_sqr
-			}).sum();
-		};
-
-		self.step_count += 1;
-
-		Ok((loss, self.step_count, change_sqr.sqrt(), params))
+		match change_norm {
+			Some(change_norm) => {
+				self.step_count += 1;
+				Ok((loss, self.step_count, change_norm, params))
+			},
+			None => Ok((loss, self.step_count, 0.0, params)),
+		}
 	}
 
 	fn callbacks(&mut self) -> &mut [Box<FnMut(&CallbackData)->CallbackSignal>]{
@@ -176,4 +223,562 @@ impl Opt for Sgd {
 	fn add_boxed_callback(&mut self, func: Box<FnMut(&CallbackData)->CallbackSignal>){
 		self.callbacks.push(func)
 	}
-}
\ No newline at end of file
+
+	fn reset_state(&mut self) {
+		for arr in self.momentum_vec.iter_mut() {
+			arr.fill(0.0);
+		}
+	}
+
+	fn resets_state_each_epoch(&self) -> bool {
+		self.reset_each_epoch
+	}
+}
+
+
+#[test]
+fn test_sgd_state_getters(){
+	_test_sgd_state_getters().unwrap();
+}
+
+fn _test_sgd_state_getters() -> Result<()>{
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Mse::new(&param, &input), tag![])?;
+
+	let mut opt = Sgd::new(&g)?.rate(0.1);
+	assert_eq!(opt.learning_rate(), 0.1);
+	assert_eq!(opt.step_count(), 0);
+
+	let params = g.initialise_nodes(opt.parameters())?;
+	let (_err, step, _change_norm, params) = opt.step(vec![ArrayD::ones(&[4, 3][..])], params)?;
+	assert_eq!(step, 1);
+	assert_eq!(opt.step_count(), 1);
+
+	opt.step(vec![ArrayD::ones(&[4, 3][..])], params)?;
+	assert_eq!(opt.step_count(), 2);
+
+	Ok(())
+}
+
+#[test]
+fn test_sgd_grad_noise(){
+	_test_sgd_grad_noise().unwrap();
+}
+
+fn _test_sgd_grad_noise() -> Result<()>{
+	use ops::loss::mse::Mse;
+	use rand::{Isaac64Rng, SeedableRng};
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Mse::new(&param, &input), tag![])?;
+
+	let params = g.initialise_nodes(&[param.clone()])?;
+	let step = |opt: &mut Sgd| -> Result<Vec<ArrayD<f32>>> {
+		let (_err, _step, _change_norm, new_params) = opt.step(vec![ArrayD::ones(&[4, 3][..])], params.clone())?;
+		Ok(new_params)
+	};
+
+	// without noise, steps from identical state are identical
+	let mut opt = Sgd::new(&g)?.rate(0.1);
+	assert_eq!(step(&mut opt)?, step(&mut opt)?);
+
+	// with noise, two steps from identical state diverge
+	let mut opt = Sgd::new(&g)?.rate(0.1);
+	opt.pipeline_mut().grad_noise(0.1, 0.55);
+	assert_ne!(step(&mut opt)?, step(&mut opt)?);
+
+	// with a seeded rng, the noise is reproducible
+	let mut opt1 = Sgd::new(&g)?.rate(0.1);
+	opt1.pipeline_mut().grad_noise(0.1, 0.55).grad_noise_rng(Isaac64Rng::from_seed([7u8; 32]));
+	let mut opt2 = Sgd::new(&g)?.rate(0.1);
+	opt2.pipeline_mut().grad_noise(0.1, 0.55).grad_noise_rng(Isaac64Rng::from_seed([7u8; 32]));
+	assert_eq!(step(&mut opt1)?, step(&mut opt2)?);
+	assert_eq!(step(&mut opt1)?, step(&mut opt2)?);
+
+	Ok(())
+}
+
+#[test]
+fn test_sgd_weight_constraint(){
+	_test_sgd_weight_constraint().unwrap();
+}
+
+fn _test_sgd_weight_constraint() -> Result<()>{
+	use ops::loss::proportional::Proportional;
+	use opt::WeightConstraint;
+
+	let mut g = GraphDef::new();
+
+	let param1 = g.new_node(shape![4, 3], "param1", tag![Parameter])?;
+	let param2 = g.new_node(shape![4, 3], "param2", tag![Parameter])?;
+	let _o1 = g.new_op(Proportional::new(&param1).multiplier(0.0), tag![])?;
+	let _o2 = g.new_op(Proportional::new(&param2).multiplier(0.0), tag![])?;
+
+	let norm = |arr: &ArrayD<f32>| arr.iter().fold(0.0f32, |acc, &x| acc + x * x).sqrt();
+
+	let mut opt = Sgd::new(&g)?.rate(0.1);
+	opt.pipeline_mut().weight_constraint(WeightConstraint::MaxNorm(2.0));
+	assert_eq!(opt.parameters(), &[param1.clone(), param2.clone()]);
+
+	// param1 has norm 6.0, above the cap, param2 has norm ~0.35, below the cap
+	let params = vec![ArrayD::from_elem(&[4, 3][..], 3.0f32.sqrt()), ArrayD::from_elem(&[4, 3][..], 0.1)];
+	let (_err, _step, _change_norm, params) = opt.step(vec![], params)?;
+
+	assert!((norm(&params[0]) - 2.0).abs() < 1e-5, "{}", norm(&params[0]));
+	assert!(params[0].iter().all(|&x| (x - 2.0/12.0f32.sqrt()).abs() < 1e-5));
+	assert!(params[1].iter().all(|&x| x == 0.1));
+
+	let mut opt = Sgd::new(&g)?.rate(0.1);
+	opt.pipeline_mut().weight_constraint(WeightConstraint::UnitNorm);
+	let (_err, _step, _change_norm, params) = opt.step(vec![], params)?;
+	assert!((norm(&params[0]) - 1.0).abs() < 1e-5);
+	assert!((norm(&params[1]) - 1.0).abs() < 1e-5);
+
+	Ok(())
+}
+
+#[test]
+fn test_sgd_rate_schedule(){
+	_test_sgd_rate_schedule().unwrap();
+}
+
+fn _test_sgd_rate_schedule() -> Result<()>{
+	use ops::loss::proportional::Proportional;
+	use opt::schedules::triangular;
+
+	let mut g = GraphDef::new();
+
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Proportional::new(&param).multiplier(12.0), tag![])?;
+
+	// each element has a gradient of 1.0, so the change norm is sqrt(12) times the rate
+	let mut opt = Sgd::new(&g)?.rate(0.1);
+	opt.pipeline_mut().rate_schedule(triangular(0.1, 0.5, 4));
+	let mut params = g.initialise_nodes(opt.parameters())?;
+	for &multiplier in [1.0, 2.0, 3.0, 4.0, 5.0, 4.0].iter() {
+		let (_err, _step, change_norm, new_params) = opt.step(vec![], params)?;
+		params = new_params;
+		assert!((change_norm - 0.1 * multiplier * 12.0f32.sqrt()).abs() < 1e-4, "{} {}", change_norm, multiplier);
+	}
+
+	Ok(())
+}
+
+#[test]
+fn test_sgd_param_ema(){
+	_test_sgd_param_ema().unwrap();
+}
+
+fn _test_sgd_param_ema() -> Result<()>{
+	use ops::loss::proportional::Proportional;
+
+	let mut g = GraphDef::new();
+
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Proportional::new(&param).multiplier(12.0), tag![])?;
+
+	let run = |opt: &mut Sgd| -> Result<Vec<ArrayD<f32>>> {
+		let mut params = vec![ArrayD::zeros(&[4, 3][..])];
+		for _ in 0..10 {
+			let (_err, _step, _change_norm, new_params) = opt.step(vec![], params)?;
+			params = new_params;
+		}
+		Ok(params)
+	};
+
+	let mut opt = Sgd::new(&g)?.rate(0.1);
+	let plain_params = run(&mut opt)?;
+	assert!(opt.pipeline().ema_params().is_none());
+
+	// with zero decay the average is the latest parameters
+	let mut opt = Sgd::new(&g)?.rate(0.1);
+	opt.pipeline_mut().param_ema(0.0);
+	let params = run(&mut opt)?;
+	assert_eq!(params, plain_params);
+	assert_eq!(opt.pipeline().ema_params().unwrap(), &params[..]);
+
+	// with decay near one the average lags behind the parameters, which fall by 0.1 each step
+	let mut opt = Sgd::new(&g)?.rate(0.1);
+	opt.pipeline_mut().param_ema(0.99);
+	let params = run(&mut opt)?;
+	assert_eq!(params, plain_params);
+	let ema = &opt.pipeline().ema_params().unwrap()[0];
+	assert!(params[0].iter().zip(ema.iter()).all(|(&p, &e)| e > p + 0.5), "{} {}", params[0], ema);
+
+	Ok(())
+}
+
+#[test]
+fn test_sgd_loss_scale(){
+	_test_sgd_loss_scale().unwrap();
+}
+
+fn _test_sgd_loss_scale() -> Result<()>{
+	use ops::loss::mse::Mse;
+	use opt::LossScale;
+	use std::f32;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Mse::new(&param, &input), tag![])?;
+
+	let mut opt = Sgd::new(&g)?.rate(0.1);
+	opt.pipeline_mut().loss_scale(LossScale::Dynamic{init: 1024.0, growth: 2.0, backoff: 0.5, interval: 2});
+	assert_eq!(opt.pipeline().current_loss_scale(), Some(1024.0));
+
+	let params = vec![ArrayD::zeros(&[4, 3][..])];
+
+	// a finite step updates the parameters
+	let (_err, step, _change_norm, params) = opt.step(vec![ArrayD::ones(&[4, 3][..])], params)?;
+	assert_eq!(step, 1);
+	assert!(params[0].iter().all(|&x| (x - 0.2).abs() < 1e-6));
+	assert_eq!(opt.pipeline().current_loss_scale(), Some(1024.0));
+
+	// a NaN gradient backs off the scale, and skips the update
+	let (_err, step, change_norm, params) = opt.step(vec![ArrayD::from_elem(&[4, 3][..], f32::NAN)], params)?;
+	assert_eq!(step, 1);
+	assert_eq!(change_norm, 0.0);
+	assert!(params[0].iter().all(|&x| (x - 0.2).abs() < 1e-6));
+	assert_eq!(opt.pipeline().current_loss_scale(), Some(512.0));
+
+	// after `interval` finite steps the scale grows again
+	let (_err, _step, _change_norm, params) = opt.step(vec![ArrayD::ones(&[4, 3][..])], params)?;
+	assert_eq!(opt.pipeline().current_loss_scale(), Some(512.0));
+	let (_err, step, _change_norm, params) = opt.step(vec![ArrayD::ones(&[4, 3][..])], params)?;
+	assert_eq!(step, 3);
+	assert_eq!(opt.pipeline().current_loss_scale(), Some(1024.0));
+	assert!(params[0].iter().all(|&x| x.is_finite()));
+
+	Ok(())
+}
+
+#[test]
+fn test_sgd_loss_scale_underflow(){
+	_test_sgd_loss_scale_underflow().unwrap();
+}
+
+fn _test_sgd_loss_scale_underflow() -> Result<()>{
+	use ops::nn::linear::Linear;
+	use ops::loss::mse::Mse;
+	use opt::LossScale;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![1, 1], "input", tag![])?;
+	let hidden = g.new_node(shape![1, 1], "hidden", tag![])?;
+	let target = g.new_node(shape![1, 1], "target", tag![])?;
+	let _o1 = g.new_op(Linear::new(&input, &hidden), tag![])?;
+	let _o2 = g.new_op(Mse::new(&hidden, &target).multiplier(1e-23), tag![])?;
+
+	// with a zero weight the gradient at hidden is -2e-46, which underflows, although the weight gradient is -2e-26
+	let run = |opt: &mut Sgd| -> Result<f32> {
+		let inputs = opt.inputs().iter().map(|data_id| {
+			let value = if data_id == &input.value_id() {1e20} else {1e-23};
+			ArrayD::from_elem(&[1, 1][..], value)
+		}).collect();
+		let (_err, _step, _change_norm, params) = opt.step(inputs, vec![ArrayD::zeros(&[1, 1][..])])?;
+		Ok(params[0][[0, 0]])
+	};
+
+	let mut opt = Sgd::new(&g)?.rate(1.0);
+	assert_eq!(run(&mut opt)?, 0.0);
+
+	let mut opt = Sgd::new(&g)?.rate(1.0);
+	opt.pipeline_mut().loss_scale(LossScale::Static(2f32.powi(40)));
+	let weight = run(&mut opt)?;
+	assert!((weight - 2e-26).abs() < 1e-30, "{}", weight);
+
+	Ok(())
+}
+
+#[test]
+fn test_sgd_freeze(){
+	_test_sgd_freeze().unwrap();
+}
+
+fn _test_sgd_freeze() -> Result<()>{
+	use ops::nn::linear::Linear;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![2, 3], "input", tag![])?;
+	let hidden = g.new_node(shape![2, 4], "hidden", tag![])?;
+	let output = g.new_node(shape![2, 5], "output", tag![])?;
+	let target = g.new_node(shape![2, 5], "target", tag![])?;
+	let o1 = g.new_op(Linear::new(&input, &hidden), tag![])?;
+	let o2 = g.new_op(Linear::new(&hidden, &output), tag![])?;
+	let _o3 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+	let mut opt = Sgd::new(&g)?.rate(0.1).momentum(0.9);
+	let frozen_index = opt.parameters().iter().position(|p| o1.instance().inner_nodes().contains(p)).unwrap();
+	let trained_index = opt.parameters().iter().position(|p| o2.instance().inner_nodes().contains(p)).unwrap();
+
+	let initial_params = g.initialise_nodes(opt.parameters())?;
+	let inputs = || vec![ArrayD::ones(&[2, 3][..]), ArrayD::zeros(&[2, 5][..])];
+
+	// the frozen layer is unchanged, but gradients still flow through it to train the unfrozen layer
+	opt.pipeline_mut().freeze(&[o1.clone()]);
+	let mut params = initial_params.clone();
+	for _ in 0..3 {
+		let (_err, _step, _change_norm, new_params) = opt.step(inputs(), params)?;
+		params = new_params;
+	}
+	assert_eq!(params[frozen_index], initial_params[frozen_index]);
+	assert_ne!(params[trained_index], initial_params[trained_index]);
+
+	opt.pipeline_mut().unfreeze(&[o1]);
+	let (_err, _step, _change_norm, params) = opt.step(inputs(), params)?;
+	assert_ne!(params[frozen_index], initial_params[frozen_index]);
+
+	Ok(())
+}
+
+#[test]
+fn test_sgd_grad_activity(){
+	_test_sgd_grad_activity().unwrap();
+}
+
+fn _test_sgd_grad_activity() -> Result<()>{
+	use ops::activ::relu::ReLU;
+	use ops::math::add::Add;
+	use ops::loss::mse::Mse;
+	use init::Initialiser;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let target = g.new_node(shape![4, 3], "target", tag![])?;
+	let dead = g.new_node(shape![4, 3], "dead", tag![Parameter])?;
+	let alive = g.new_node(shape![4, 3], "alive", tag![Parameter])?;
+	let dead_hidden = g.new_node(shape![4, 3], "dead_hidden", tag![])?;
+	let alive_hidden = g.new_node(shape![4, 3], "alive_hidden", tag![])?;
+	let dead_output = g.new_node(shape![4, 3], "dead_output", tag![])?;
+	let alive_output = g.new_node(shape![4, 3], "alive_output", tag![])?;
+
+	// the dead ReLU is saturated far below zero, and so never passes back a gradient
+	let _o1 = g.new_op(Add::new(&input, &dead_hidden), tag![])?;
+	let _o2 = g.new_op(Add::new(&dead, &dead_hidden), tag![])?;
+	let _o3 = g.new_op(ReLU::new(&dead_hidden, &dead_output), tag![])?;
+	let _o4 = g.new_op(Mse::new(&dead_output, &target), tag![])?;
+	let _o5 = g.new_op(Add::new(&input, &alive_hidden), tag![])?;
+	let _o6 = g.new_op(Add::new(&alive, &alive_hidden), tag![])?;
+	let _o7 = g.new_op(ReLU::new(&alive_hidden, &alive_output), tag![])?;
+	let _o8 = g.new_op(Mse::new(&alive_output, &target), tag![])?;
+	g.set_initialiser(&dead, Initialiser::fill(-100.0));
+	g.set_initialiser(&alive, Initialiser::fill(1.0));
+
+	let mut opt = Sgd::new(&g)?.rate(0.01);
+	let mut params = g.initialise_nodes(opt.parameters())?;
+	let (_err, _step, _change_norm, new_params) = opt.step(vec![ArrayD::ones(&[4, 3][..]), ArrayD::zeros(&[4, 3][..])], params)?;
+	params = new_params;
+	assert!(opt.pipeline().grad_activity().is_none());
+
+	let mut opt = Sgd::new(&g)?.rate(0.01);
+	opt.pipeline_mut().track_grad_activity(true);
+	assert_eq!(opt.parameters(), &[dead.clone(), alive.clone()]);
+	for _ in 0..3 {
+		let (_err, _step, _change_norm, new_params) = opt.step(vec![ArrayD::ones(&[4, 3][..]), ArrayD::zeros(&[4, 3][..])], params)?;
+		params = new_params;
+	}
+
+	let activity = opt.pipeline().grad_activity().unwrap();
+	assert!(activity[0].iter().all(|&count| count == 0));
+	assert!(activity[1].iter().all(|&count| count == 3));
+
+	Ok(())
+}
+
+#[test]
+fn test_sgd_adaptive_grad_clip(){
+	_test_sgd_adaptive_grad_clip().unwrap();
+}
+
+fn _test_sgd_adaptive_grad_clip() -> Result<()>{
+	use ops::loss::proportional::Proportional;
+	use init::Initialiser;
+
+	let mut g = GraphDef::new();
+
+	let small = g.new_node(shape![4, 3], "small", tag![Parameter])?;
+	let large = g.new_node(shape![4, 3], "large", tag![Parameter])?;
+
+	// both parameters receive the same large gradient of 1000/12 per element
+	let _o1 = g.new_op(Proportional::new(&small).multiplier(1000.0), tag![])?;
+	let _o2 = g.new_op(Proportional::new(&large).multiplier(1000.0), tag![])?;
+	g.set_initialiser(&small, Initialiser::fill(0.01));
+	g.set_initialiser(&large, Initialiser::fill(10.0));
+
+	let mut opt = Sgd::new(&g)?.rate(1.0);
+	opt.pipeline_mut().adaptive_grad_clip(0.01);
+	assert_eq!(opt.parameters(), &[small.clone(), large.clone()]);
+	let params = g.initialise_nodes(opt.parameters())?;
+	let (_err, _step, _change_norm, new_params) = opt.step(vec![], params.clone())?;
+
+	let norm = |x: &ArrayD<f32>| x.iter().fold(0.0f32, |acc, &x| acc + x * x).sqrt();
+	let small_change = norm(&(&new_params[0] - &params[0]));
+	let large_change = norm(&(&new_params[1] - &params[1]));
+
+	// each update is limited to 0.01 times the norm of its parameter
+	assert!((small_change - 0.01 * norm(&params[0])).abs() < 1e-6);
+	assert!((large_change - 0.01 * norm(&params[1])).abs() < 1e-3);
+	assert!(small_change * 100.0 < large_change);
+
+	Ok(())
+}
+
+#[test]
+fn test_sgd_grad_centralization(){
+	_test_sgd_grad_centralization().unwrap();
+}
+
+fn _test_sgd_grad_centralization() -> Result<()>{
+	use ops::loss::mse::Mse;
+	use ops::loss::proportional::Proportional;
+	use init::Initialiser;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let weights = g.new_node(shape![4, 3], "weights", tag![Parameter])?;
+	let bias = g.new_node(shape![3], "bias", tag![Parameter])?;
+
+	let _o1 = g.new_op(Mse::new(&weights, &input), tag![])?;
+	let _o2 = g.new_op(Proportional::new(&bias), tag![])?;
+	g.set_initialiser(&weights, Initialiser::fill(0.0));
+	g.set_initialiser(&bias, Initialiser::fill(0.0));
+
+	let input_data = ArrayD::from_shape_fn(&[4, 3][..], |idx| (idx[0] * 3 + idx[1]) as f32 + 1.0);
+
+	let mut opt = Sgd::new(&g)?.rate(1.0);
+	opt.pipeline_mut().grad_centralization(true);
+	assert_eq!(opt.parameters(), &[weights.clone(), bias.clone()]);
+	let params = g.initialise_nodes(opt.parameters())?;
+	let (_err, _step, _change_norm, new_params) = opt.step(vec![input_data], params.clone())?;
+
+	// with a rate of 1 the change is the negative of the centralised gradient
+	let weights_change = &new_params[0] - &params[0];
+	let bias_change = &new_params[1] - &params[1];
+
+	assert!(weights_change.iter().any(|&x| x.abs() > 1e-3), "{:?}", weights_change);
+	assert!(weights_change.iter().fold(0.0f32, |acc, &x| acc + x).abs() < 1e-5, "{:?}", weights_change);
+	assert!(bias_change.iter().all(|&x| (x + 1.0/3.0).abs() < 1e-6), "{:?}", bias_change);
+
+	Ok(())
+}
+
+#[test]
+fn test_sgd_histograms(){
+	_test_sgd_histograms().unwrap();
+}
+
+fn _test_sgd_histograms() -> Result<()>{
+	use ops::nn::linear::Linear;
+	use ops::nn::bias::Bias;
+	use ops::loss::mse::Mse;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 5], "input", tag![])?;
+	let output = g.new_node(shape![4, 3], "output", tag![])?;
+	let target = g.new_node(shape![4, 3], "target", tag![])?;
+
+	let _o1 = g.new_op(Linear::new(&input, &output), tag![])?;
+	let _o2 = g.new_op(Bias::new(&output), tag![])?;
+	let _o3 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+	let mut opt = Sgd::new(&g)?.rate(0.01);
+	opt.pipeline_mut().collect_histograms(2);
+	assert!(opt.pipeline().last_histograms().is_none());
+
+	let mut params = g.initialise_nodes(opt.parameters())?;
+	for _ in 0..2 {
+		let (_err, _step, _change_norm, new_params) = opt.step(vec![ArrayD::ones(&[4, 5][..]), ArrayD::zeros(&[4, 3][..])], params)?;
+		params = new_params;
+	}
+
+	{
+		// collected at step 0, but not step 1
+		let histograms = opt.pipeline().last_histograms().unwrap();
+		assert_eq!(histograms.step, 0);
+		assert_eq!(histograms.weights.len(), 2);
+		assert_eq!(histograms.gradients.len(), 2);
+		assert_eq!(histograms.weights.iter().map(|hist| hist.total()).sum::<u64>(), g.num_params()? as u64);
+		assert_eq!(histograms.gradients.iter().map(|hist| hist.total()).sum::<u64>(), g.num_params()? as u64);
+	}
+
+	let mut opt = Sgd::new(&g)?.rate(0.01);
+	opt.pipeline_mut().collect_histograms(1).histogram_edges(vec![-1.0, 0.0, 1.0]);
+	let (_err, _step, _change_norm, _new_params) = opt.step(vec![ArrayD::ones(&[4, 5][..]), ArrayD::zeros(&[4, 3][..])], params)?;
+	let histograms = opt.pipeline().last_histograms().unwrap();
+	assert!(histograms.weights.iter().chain(&histograms.gradients).all(|hist| hist.edges == vec![-1.0, 0.0, 1.0]));
+	assert_eq!(histograms.weights.iter().map(|hist| hist.total()).sum::<u64>(), g.num_params()? as u64);
+
+	Ok(())
+}
+
+#[test]
+fn test_sgd_pipeline_state_round_trip(){
+	_test_sgd_pipeline_state_round_trip().unwrap();
+}
+
+fn _test_sgd_pipeline_state_round_trip() -> Result<()>{
+	use ops::loss::mse::Mse;
+	use opt::{LossScale, temp_path};
+	use std::f32;
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![4, 3], "input", tag![])?;
+	let param = g.new_node(shape![4, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Mse::new(&param, &input), tag![])?;
+
+	let configure = |opt: &mut Sgd| {
+		opt.pipeline_mut().loss_scale(LossScale::Dynamic{init: 1024.0, growth: 2.0, backoff: 0.5, interval: 3}).auto_warmup(1.5).param_ema(0.9);
+	};
+	let inputs = |x: f32| vec![ArrayD::from_elem(&[4, 3][..], x)];
+
+	// a NaN gradient backs off the scale, after which two of the three finite steps needed to grow it are taken
+	let mut opt1 = Sgd::new(&g)?.rate(0.1).momentum(0.9);
+	configure(&mut opt1);
+	let mut params = vec![ArrayD::zeros(&[4, 3][..])];
+	for &x in [1.0, f32::NAN, 2.0, 1.0].iter() {
+		let (_err, _step, _change_norm, new_params) = opt1.step(inputs(x), params)?;
+		params = new_params;
+	}
+	assert_eq!(opt1.pipeline().current_loss_scale(), Some(512.0));
+
+	let path = temp_path("sgd_pipeline_state_round_trip.bin");
+	opt1.save_state(&path).unwrap();
+
+	let mut opt2 = Sgd::new(&g)?;
+	configure(&mut opt2);
+	opt2.load_state(&path).unwrap();
+	::std::fs::remove_file(&path).ok();
+	assert_eq!(opt2.pipeline().current_loss_scale(), Some(512.0));
+	assert_eq!(opt2.pipeline().ema_params(), opt1.pipeline().ema_params());
+
+	// the loss scale, warmup, and moving average carry on from the saved state
+	for &x in [1.5, 1.0].iter() {
+		let (err1, step1, change1, params1) = opt1.step(inputs(x), params.clone())?;
+		let (err2, step2, change2, params2) = opt2.step(inputs(x), params.clone())?;
+		assert_eq!((err1, step1, change1), (err2, step2, change2));
+		assert_eq!(params1, params2);
+		params = params1;
+	}
+	assert_eq!(opt1.pipeline().current_loss_scale(), Some(1024.0));
+	assert_eq!(opt2.pipeline().current_loss_scale(), Some(1024.0));
+	assert_eq!(opt2.pipeline().ema_params(), opt1.pipeline().ema_params());
+
+	Ok(())
+}
@@ -0,0 +1,251 @@
+use graph::{GraphDef, Subgraph, Result};
+use id::{NodeTag, NodeID, DataID};
+use opt::{Opt, CallbackData, CallbackSignal};
+use rng::new_rng;
+use ndarray::{ArrayD, Zip};
+use rand::{Rng, RngCore};
+
+/// Simultaneous Perturbation Stochastic Approximation (SPSA) Optimiser
+///
+/// A gradient free optimiser, which estimates the gradient from the loss at two perturbed copies of the parameters.
+/// At step k, every parameter element is perturbed by ±c_k, with the sign Δ drawn independently and uniformly:
+///
+/// ĝ = Δ (f(θ + c_k Δ) - f(θ - c_k Δ)) / 2c_k
+/// θ = θ - a_k ĝ
+///
+/// where a_k = a/(k + 1 + A)^α and c_k = c/(k + 1)^γ.
+///
+/// The parameter gradients calculated by the subgraph are not used, so ops which are not differentiable, or have incorrect gradients, can be optimised through.
+/// In this library the loss is accumulated by the same passes that begin backpropagation, so the subgraph still executes the backward passes.
+/// The error reported to callbacks is the mean of the two perturbed losses.
+pub struct Spsa {
+	subgraph: Subgraph,
+	inputs: Vec<DataID>,
+	parameters: Vec<NodeID>,
+	callbacks: Vec<Box<FnMut(&CallbackData)->CallbackSignal>>,
+	rate: f32,
+	perturbation: f32,
+	stability: f32,
+	rate_decay: f32,
+	perturbation_decay: f32,
+	rng: Box<RngCore + Send>,
+	step_count: usize,
+}
+
+impl Spsa {
+
+	/// Create an optimisation problem assuming that all nodes marked `Parameter` should be optimised, and all other leaf nodes are batch inputs.
+	pub fn new(graph: &GraphDef) -> Result<Self> {
+
+		let subgraph = graph.default_subgraph()?;
+
+		Ok(Spsa {
+			inputs: subgraph.inputs().iter().filter(|data_id| !data_id.tags().contains(&NodeTag::Parameter)).cloned().collect(),
+			parameters: subgraph.inputs().iter().filter_map(|data_id| if data_id.tags().contains(&NodeTag::Parameter) {Some(data_id.node_id())} else {None}).collect(),
+			subgraph: subgraph,
+			callbacks: vec![],
+			rate: 0.1,
+			perturbation: 0.01,
+			stability: 10.0,
+			rate_decay: 0.602,
+			perturbation_decay: 0.101,
+			rng: Box::new(new_rng()),
+			step_count: 0,
+		})
+	}
+
+	/// Define a custom optimisation problem by supplying a subgraph and a list of parameters to optimise.
+	///
+	/// The subgraph must meet the following:
+	/// - subgraph inputs are ordered with general inputs (values or gradients) followed by parameter values.
+	/// - subgraph outputs must include all parameters values.
+	///
+	/// Note: All leaf nodes not listed as parameters are assumed to be batch inputs.
+	pub fn with_subgraph(subgraph: Subgraph, parameter_ids: Vec<NodeID>) -> Self {
+
+		let n_inputs = subgraph.inputs().len() - parameter_ids.len();
+		let maybe_inputs = subgraph.inputs()[0..n_inputs].to_vec();
+
+		assert!(subgraph.inputs()[n_inputs..].iter().cloned().eq(parameter_ids.iter().map(|id| id.value_id())), "The final inputs to the subgraph must be the values of the optimiser parameter nodes");
+
+		Spsa {
+			inputs: maybe_inputs,
+			parameters: parameter_ids,
+			subgraph: subgraph,
+			callbacks: vec![],
+			rate: 0.1,
+			perturbation: 0.01,
+			stability: 10.0,
+			rate_decay: 0.602,
+			perturbation_decay: 0.101,
+			rng: Box::new(new_rng()),
+			step_count: 0,
+		}
+	}
+
+	/// Learning rate numerator, a
+	///
+	/// Default: 0.1
+	pub fn rate(mut self, rate: f32) -> Self {
+		self.rate = rate;
+		self
+	}
+
+	/// Perturbation size numerator, c
+	///
+	/// This should be around the standard deviation of the noise in the loss, or small if the loss is deterministic.
+	/// Default: 0.01
+	pub fn perturbation(mut self, perturbation: f32) -> Self {
+		self.perturbation = perturbation;
+		self
+	}
+
+	/// Stability constant, A, which reduces the learning rate of the early steps
+	///
+	/// Default: 10.0
+	pub fn stability(mut self, stability: f32) -> Self {
+		self.stability = stability;
+		self
+	}
+
+	/// Learning rate decay exponent, α
+	///
+	/// Default: 0.602
+	pub fn rate_decay(mut self, rate_decay: f32) -> Self {
+		self.rate_decay = rate_decay;
+		self
+	}
+
+	/// Perturbation decay exponent, γ
+	///
+	/// Default: 0.101
+	pub fn perturbation_decay(mut self, perturbation_decay: f32) -> Self {
+		self.perturbation_decay = perturbation_decay;
+		self
+	}
+
+	/// Supply the rng used to draw the perturbation signs, e.g. a seeded rng for reproducibility.
+	///
+	/// Default: `rng::new_rng()`
+	pub fn rng<R: RngCore + 'static + Send>(mut self, rng: R) -> Self {
+		self.rng = Box::new(rng);
+		self
+	}
+
+	/// Returns the learning rate, a_k, for the next step
+	pub fn learning_rate(&self) -> f32 {
+		self.rate / (self.step_count as f32 + 1.0 + self.stability).powf(self.rate_decay)
+	}
+
+	/// Returns the perturbation size, c_k, for the next step
+	pub fn perturbation_size(&self) -> f32 {
+		self.perturbation / (self.step_count as f32 + 1.0).powf(self.perturbation_decay)
+	}
+
+	/// Returns the number of steps taken so far
+	pub fn step_count(&self) -> usize {
+		self.step_count
+	}
+
+	/// Returns the loss at the supplied parameters
+	fn evaluate(&mut self, inputs: &[ArrayD<f32>], params: &[ArrayD<f32>]) -> Result<f32> {
+		let subgraph_inputs: Vec<_> = inputs.iter().chain(params).cloned().collect();
+		assert_eq!(self.subgraph.inputs().len(), subgraph_inputs.len());
+
+		let storage = self.subgraph.execute(subgraph_inputs)?;
+		Ok(storage.loss())
+	}
+}
+
+impl Opt for Spsa {
+
+	fn subgraph(&self) -> &Subgraph {
+		&self.subgraph
+	}
+
+	fn inputs(&self) -> &[DataID]{
+		&self.inputs
+	}
+
+	fn parameters(&self) -> &[NodeID]{
+		&self.parameters
+	}
+
+	fn step(&mut self, inputs: Vec<ArrayD<f32>>, mut params: Vec<ArrayD<f32>>) -> Result<(f32, usize, f32, Vec<ArrayD<f32>>)>{
+		assert_eq!(inputs.len(), self.inputs().len(), "Incorrect number of inputs supplied to optimiser.step()");
+		assert_eq!(params.len(), self.parameters().len(), "Incorrect number of prameters supplied to optimiser.step()");
+
+		let rate = self.learning_rate();
+		let c = self.perturbation_size();
+
+		let deltas: Vec<ArrayD<f32>> = {
+			let rng = &mut self.rng;
+			params.iter().map(|param| param.map(|_| if rng.gen::<bool>() {1.0} else {-1.0})).collect()
+		};
+
+		let params_plus: Vec<_> = params.iter().zip(&deltas).map(|(param, delta)| param + &(delta * c)).collect();
+		let params_minus: Vec<_> = params.iter().zip(&deltas).map(|(param, delta)| param - &(delta * c)).collect();
+
+		let loss_plus = self.evaluate(&inputs, &params_plus)?;
+		let loss_minus = self.evaluate(&inputs, &params_minus)?;
+		let scale = -rate * (loss_plus - loss_minus) / (2.0 * c);
+
+		let mut change_sqr = 0.0;
+		for (param, delta) in params.iter_mut().zip(&deltas) {
+			Zip::from(param).and(delta).apply(|param, &delta| {
+				let change = scale * delta;
+				change_sqr += change * change;
+				*param += change;
+			});
+		}
+
+		self.step_count += 1;
+
+		Ok((0.5 * (loss_plus + loss_minus), self.step_count, change_sqr.sqrt(), params))
+	}
+
+	fn callbacks(&mut self) -> &mut [Box<FnMut(&CallbackData)->CallbackSignal>]{
+		&mut self.callbacks
+	}
+
+	fn add_boxed_callback(&mut self, func: Box<FnMut(&CallbackData)->CallbackSignal>){
+		self.callbacks.push(func)
+	}
+}
+
+
+#[test]
+fn test_spsa_quadratic(){
+	_test_spsa_quadratic().unwrap();
+}
+
+fn _test_spsa_quadratic() -> Result<()>{
+	use ops::loss::mse::Mse;
+	use rand::{Isaac64Rng, SeedableRng};
+
+	let mut g = GraphDef::new();
+
+	let input = g.new_node(shape![2, 3], "input", tag![])?;
+	let param = g.new_node(shape![2, 3], "param", tag![Parameter])?;
+	let _o1 = g.new_op(Mse::new(&param, &input), tag![])?;
+
+	let mut opt = Spsa::new(&g)?.rate(0.1).perturbation(0.1).rng(Isaac64Rng::from_seed([19u8; 32]));
+
+	let target = ArrayD::from_shape_vec(&[2, 3][..], vec![1.0, -2.0, 0.5, 3.0, 0.0, -1.0]).unwrap();
+	let mut params = g.initialise_nodes(opt.parameters())?;
+	let (initial_err, _, _, _) = opt.step(vec![target.clone()], params.clone())?;
+
+	let mut err = initial_err;
+	for _ in 0..2000 {
+		let (new_err, _step, _change_norm, new_params) = opt.step(vec![target.clone()], params)?;
+		err = new_err;
+		params = new_params;
+	}
+	assert_eq!(opt.step_count(), 2001);
+
+	// the reported error is measured at the perturbed parameters, so includes n*c_k^2 even at the optimum
+	assert!(err < 1e-2 * initial_err, "initial: {} final: {}", initial_err, err);
+	assert!(params[0].all_close(&target, 0.05), "{:?}", params[0]);
+
+	Ok(())
+}
@@ -0,0 +1,126 @@
+use ndarray::{ArrayD, Dimension, IxDyn};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use id::NodeID;
+use opt::pipeline::PipelineState;
+use std::fs::File;
+use std::path::Path;
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+
+const MAGIC: &[u8; 8] = b"ALUMOPT1";
+
+/// Writes the internal state of an optimiser to a file, sufficient to resume optimisation exactly.
+///
+/// `state_start` is the step count at which the arrays were last reset, see `Opt::reset_state()`.
+/// Each entry in `vecs` is a per-parameter array list, such as momentum, which is either empty or has one array per parameter.
+pub(crate) fn save_state<P: AsRef<Path>>(path: P, step_count: usize, state_start: usize, rate: f32, pipeline: &PipelineState, vecs: &[&[ArrayD<f32>]]) -> Result<()> {
+	let mut writer = BufWriter::new(File::create(path)?);
+
+	writer.write_all(MAGIC)?;
+	writer.write_u64::<LittleEndian>(step_count as u64)?;
+	writer.write_u64::<LittleEndian>(state_start as u64)?;
+	writer.write_f32::<LittleEndian>(rate)?;
+	writer.write_f32::<LittleEndian>(pipeline.loss_scale)?;
+	writer.write_u64::<LittleEndian>(pipeline.stable_steps as u64)?;
+	writer.write_u8(pipeline.prev_norm.is_some() as u8)?;
+	writer.write_f32::<LittleEndian>(pipeline.prev_norm.unwrap_or(0.0))?;
+	writer.write_u8(pipeline.steps_since_release.is_some() as u8)?;
+	writer.write_u64::<LittleEndian>(pipeline.steps_since_release.unwrap_or(0) as u64)?;
+	writer.write_u64::<LittleEndian>(vecs.len() as u64)?;
+	for vec in vecs {
+		writer.write_u64::<LittleEndian>(vec.len() as u64)?;
+		for arr in vec.iter() {
+			writer.write_u64::<LittleEndian>(arr.ndim() as u64)?;
+			for &dim in arr.shape() {
+				writer.write_u64::<LittleEndian>(dim as u64)?;
+			}
+			for &x in arr.iter() {
+				writer.write_f32::<LittleEndian>(x)?;
+			}
+		}
+	}
+	writer.flush()
+}
+
+/// The internal state of an optimiser, as read by `OptState::load()`.
+pub(crate) struct OptState {
+	pub step_count: usize,
+	pub state_start: usize,
+	pub rate: f32,
+	pub pipeline: PipelineState,
+	pub vecs: Vec<Vec<ArrayD<f32>>>,
+}
+
+impl OptState {
+	/// Loads a state, checking that each array list is empty or matches the shapes of `parameters`, and that there are `num_vecs` lists.
+	pub fn load<P: AsRef<Path>>(path: P, parameters: &[NodeID], num_vecs: usize) -> Result<OptState> {
+		let mut reader = BufReader::new(File::open(path)?);
+
+		let mut magic = [0u8; 8];
+		reader.read_exact(&mut magic)?;
+		if &magic != MAGIC {
+			return Err(invalid("File is not an optimiser state"));
+		}
+
+		let step_count = reader.read_u64::<LittleEndian>()? as usize;
+		let state_start = reader.read_u64::<LittleEndian>()? as usize;
+		if state_start > step_count {
+			return Err(invalid(&format!("State start step {} is after the step count {}", state_start, step_count)));
+		}
+		let rate = reader.read_f32::<LittleEndian>()?;
+
+		let loss_scale = reader.read_f32::<LittleEndian>()?;
+		let stable_steps = reader.read_u64::<LittleEndian>()? as usize;
+		let has_prev_norm = reader.read_u8()? != 0;
+		let prev_norm = reader.read_f32::<LittleEndian>()?;
+		let has_steps_since_release = reader.read_u8()? != 0;
+		let steps_since_release = reader.read_u64::<LittleEndian>()? as usize;
+		let pipeline = PipelineState{
+			loss_scale,
+			stable_steps,
+			prev_norm: if has_prev_norm {Some(prev_norm)} else {None},
+			steps_since_release: if has_steps_since_release {Some(steps_since_release)} else {None},
+		};
+
+		let n_vecs = reader.read_u64::<LittleEndian>()? as usize;
+		if n_vecs != num_vecs {
+			return Err(invalid(&format!("Expected {} parameter array lists, found {}", num_vecs, n_vecs)));
+		}
+
+		let mut vecs = Vec::with_capacity(n_vecs);
+		for _ in 0..n_vecs {
+			let n_arrs = reader.read_u64::<LittleEndian>()? as usize;
+			if n_arrs != 0 && n_arrs != parameters.len() {
+				return Err(invalid(&format!("Optimiser has {} parameters, but the saved state has {}", parameters.len(), n_arrs)));
+			}
+
+			let mut vec = Vec::with_capacity(n_arrs);
+			for param in parameters.iter().take(n_arrs) {
+				let ndim = reader.read_u64::<LittleEndian>()? as usize;
+				let mut shape = Vec::with_capacity(ndim);
+				for _ in 0..ndim {
+					shape.push(reader.read_u64::<LittleEndian>()? as usize);
+				}
+
+				if let Ok(param_shape) = param.shape().to_data_shape() {
+					if param_shape.slice() != shape.as_slice() {
+						return Err(invalid(&format!("Parameter '{}' has shape {:?}, but the saved state has shape {:?}", param.name(), param_shape.slice(), shape)));
+					}
+				}
+
+				let len: usize = shape.iter().product();
+				let mut data = Vec::with_capacity(len);
+				for _ in 0..len {
+					data.push(reader.read_f32::<LittleEndian>()?);
+				}
+				vec.push(ArrayD::from_shape_vec(IxDyn(&shape), data).expect("data length must match shape"));
+			}
+			vecs.push(vec);
+		}
+
+		Ok(OptState{step_count, state_start, rate, pipeline, vecs})
+	}
+}
+
+fn invalid(message: &str) -> Error {
+	Error::new(ErrorKind::InvalidData, message.to_string())
+}
@@ -0,0 +1,50 @@
+/// Vector operations over flattened parameters, gradients and optimiser state.
+pub trait VecMath {
+	/// The sum of elementwise products.
+	fn dot(&self, other: &Self) -> f32;
+
+	/// The L2 norm.
+	fn norm2(&self) -> f32;
+
+	/// The cosine of the angle between the two vectors, `dot(a, b)/(norm2(a) norm2(b))`.
+	///
+	/// Returns 0.0 rather than NaN if either vector has a norm of zero, so that the result can't poison optimiser state.
+	fn cos_similarity(&self, other: &Self) -> f32;
+}
+
+impl VecMath for [f32] {
+	fn dot(&self, other: &[f32]) -> f32 {
+		assert_eq!(self.len(), other.len(), "Vectors must have equal lengths");
+		self.iter().zip(other).fold(0.0, |acc, (a, b)| acc + a * b)
+	}
+
+	fn norm2(&self) -> f32 {
+		self.dot(self).sqrt()
+	}
+
+	fn cos_similarity(&self, other: &[f32]) -> f32 {
+		let norms = self.norm2() * other.norm2();
+		if norms == 0.0 {
+			0.0
+		} else {
+			self.dot(other) / norms
+		}
+	}
+}
+
+
+#[test]
+fn test_cos_similarity(){
+	let a = [3.0, 0.0, 0.0];
+	let b = [1.0, 3.0f32.sqrt(), 0.0];
+	let zero = [0.0, 0.0, 0.0];
+
+	// 60 degrees apart
+	assert!((a[..].cos_similarity(&b[..]) - 0.5).abs() < 1e-6);
+	assert!((b[..].cos_similarity(&a[..]) - 0.5).abs() < 1e-6);
+	assert!((a[..].cos_similarity(&a[..]) - 1.0).abs() < 1e-6);
+
+	assert_eq!(a[..].cos_similarity(&zero[..]), 0.0);
+	assert_eq!(zero[..].cos_similarity(&b[..]), 0.0);
+	assert_eq!(zero[..].cos_similarity(&zero[..]), 0.0);
+}
@@ -0,0 +1,99 @@
+use ndarray::ArrayD;
+
+/// Automatic learning rate warmup, gated by the stability of the global gradient norm.
+///
+/// While the ratio between consecutive gradient norms, `max(n_t, n_t-1)/min(n_t, n_t-1)`, exceeds the target ratio the rate multiplier is held at `cap`.
+/// The first time the ratio falls to the target or below, the warmup is released and the multiplier ramps linearly from `cap` to 1 over `ramp_steps` steps.
+/// Once released the multiplier is never capped again.
+pub(crate) struct AutoWarmup {
+	pub target_norm_ratio: Option<f32>,
+	pub cap: f32,
+	pub ramp_steps: usize,
+	prev_norm: Option<f32>,
+	steps_since_release: Option<usize>,
+}
+
+impl AutoWarmup {
+	/// Disabled by default, with a cap of 0.01 and a ramp of 100 steps.
+	pub fn new() -> Self {
+		AutoWarmup {
+			target_norm_ratio: None,
+			cap: 0.01,
+			ramp_steps: 100,
+			prev_norm: None,
+			steps_since_release: None,
+		}
+	}
+
+	/// Returns the multiplier for the learning rate of this step, given the gradients. Returns 1.0 if disabled.
+	pub fn update(&mut self, grads: &[ArrayD<f32>]) -> f32 {
+		if self.target_norm_ratio.is_none() {
+			return 1.0;
+		}
+		let norm = grads.iter().fold(0.0f32, |acc, grad| grad.iter().fold(acc, |acc, &x| acc + x * x)).sqrt();
+		self.update_norm(norm)
+	}
+
+	fn update_norm(&mut self, norm: f32) -> f32 {
+		let target_norm_ratio = match self.target_norm_ratio {
+			Some(target_norm_ratio) => target_norm_ratio,
+			None => return 1.0,
+		};
+
+		if self.steps_since_release.is_none() {
+			if let Some(prev_norm) = self.prev_norm {
+				let ratio = norm.max(prev_norm) / norm.min(prev_norm);
+				if ratio <= target_norm_ratio {
+					self.steps_since_release = Some(0);
+				}
+			}
+			self.prev_norm = Some(norm);
+		}
+
+		match self.steps_since_release {
+			Some(steps) => {
+				self.steps_since_release = Some(steps + 1);
+				let progress = (steps + 1) as f32 / self.ramp_steps.max(1) as f32;
+				self.cap + (1.0 - self.cap) * progress.min(1.0)
+			},
+			None => self.cap,
+		}
+	}
+
+	/// Returns the previous gradient norm, and the number of steps since the warmup was released, if it has been.
+	pub fn state(&self) -> (Option<f32>, Option<usize>) {
+		(self.prev_norm, self.steps_since_release)
+	}
+
+	/// Restores a state returned by `state()`.
+	pub fn set_state(&mut self, prev_norm: Option<f32>, steps_since_release: Option<usize>) {
+		self.prev_norm = prev_norm;
+		self.steps_since_release = steps_since_release;
+	}
+}
+
+
+#[test]
+fn test_auto_warmup(){
+	let mut warmup = AutoWarmup::new();
+	warmup.ramp_steps = 4;
+	assert_eq!(warmup.update_norm(100.0), 1.0);
+
+	warmup.target_norm_ratio = Some(1.1);
+
+	// unstable phase, consecutive norms differ by more than 10%
+	for &norm in [100.0, 40.0, 80.0, 20.0, 15.0, 30.0, 27.0].iter() {
+		assert_eq!(warmup.update_norm(norm), 0.01, "norm: {}", norm);
+	}
+
+	// stabilised, ramp up to the full rate
+	let expected = [0.2575, 0.505, 0.7525, 1.0, 1.0];
+	for &multiplier in expected.iter() {
+		let actual = warmup.update_norm(26.0);
+		assert!((actual - multiplier).abs() < 1e-5, "expected: {} actual: {}", multiplier, actual);
+	}
+
+	// later instability does not cap the rate again
+	assert_eq!(warmup.update_norm(260.0), 1.0);
+	assert_eq!(warmup.update_norm(2.6), 1.0);
+}
@@ -0,0 +1,118 @@
+use rand::{thread_rng, Isaac64Rng, SeedableRng};
+use byteorder::{LittleEndian, ByteOrder};
+use std::sync::Mutex;
+
+lazy_static! {
+	static ref GLOBAL_RNG: Mutex<Option<Isaac64Rng>> = Mutex::new(None);
+}
+
+// Held by tests which set the global seed, so that they don't interleave their draws
+#[cfg(test)]
+lazy_static! {
+	static ref GLOBAL_SEED_TEST_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Seeds the source of randomness used by initialisers and data streams that are not supplied an explicit rng.
+///
+/// After calling this, rngs returned by `new_rng()` are derived deterministically from `seed`, in the order they are requested.
+/// This means that a training run which initialises parameters and constructs data streams in the same order will be reproducible.
+///
+/// This is process wide, and so other threads creating rngs concurrently will interfere with the sequence.
+pub fn set_global_seed(seed: u64) {
+	*GLOBAL_RNG.lock().expect("Could not acquire lock on global rng") = Some(seeded_rng(seed));
+}
+
+/// Returns to seeding new rngs from `thread_rng()`.
+pub fn clear_global_seed() {
+	*GLOBAL_RNG.lock().expect("Could not acquire lock on global rng") = None;
+}
+
+/// Returns a new rng, seeded from the global seed if one has been set, otherwise seeded from `thread_rng()`.
+pub fn new_rng() -> Isaac64Rng {
+	derive_rng(&mut GLOBAL_RNG.lock().expect("Could not acquire lock on global rng"))
+}
+
+fn seeded_rng(seed: u64) -> Isaac64Rng {
+	let mut seed_bytes = [0u8; 32];
+	LittleEndian::write_u64(&mut seed_bytes[0..8], seed);
+	Isaac64Rng::from_seed(seed_bytes)
+}
+
+fn derive_rng(source: &mut Option<Isaac64Rng>) -> Isaac64Rng {
+	match *source {
+		Some(ref mut source_rng) => Isaac64Rng::from_rng(source_rng).unwrap(),
+		None => Isaac64Rng::from_rng(thread_rng()).unwrap(),
+	}
+}
+
+
+#[test]
+fn test_seeded_rngs(){
+	use rand::Rng;
+
+	let draw = |source: &mut Option<Isaac64Rng>| -> Vec<u64> {
+		(0..3).map(|_| derive_rng(source).gen::<u64>()).collect()
+	};
+
+	let draws1 = draw(&mut Some(seeded_rng(1234)));
+	let draws2 = draw(&mut Some(seeded_rng(1234)));
+	let draws3 = draw(&mut Some(seeded_rng(4321)));
+	let draws4 = draw(&mut None);
+
+	assert_eq!(draws1, draws2);
+	assert!(draws1[0] != draws1[1] && draws1[1] != draws1[2]);
+	assert!(draws1 != draws3);
+	assert!(draws1 != draws4);
+}
+
+#[test]
+fn test_global_seed(){
+	_test_global_seed().unwrap();
+}
+
+fn _test_global_seed() -> ::graph::Result<()>{
+	use graph::GraphDef;
+	use ops::loss::mse::Mse;
+	use ops::nn::linear::Linear;
+	use opt::{Opt, UnboxedCallbacks, CallbackSignal};
+	use opt::sgd::Sgd;
+	use data::{DataSet, DataStream};
+	use data::array_set::ArraySet;
+	use ndarray::ArrayD;
+
+	let train = || -> ::graph::Result<Vec<ArrayD<f32>>> {
+		let mut g = GraphDef::new();
+
+		let input = g.new_node(shape![Unknown, 4], "input", tag![])?;
+		let output = g.new_node(shape![Unknown, 3], "output", tag![])?;
+		let target = g.new_node(shape![Unknown, 3], "target", tag![])?;
+
+		let _o1 = g.new_op(Linear::new(&input, &output).init(Linear::xavier()), tag![])?;
+		let _o2 = g.new_op(Mse::new(&output, &target), tag![])?;
+
+		let inputs = ArrayD::from_shape_fn(&[10, 4][..], |idx| (idx[0] * 4 + idx[1]) as f32 * 0.1);
+		let targets = ArrayD::from_shape_fn(&[10, 3][..], |idx| (idx[0] + idx[1]) as f32 * 0.1);
+		let mut stream = ArraySet::new(vec![inputs, targets]).shuffle_random().batch(3);
+
+		let mut step = 0;
+		let mut opt = Sgd::new(&g)?.rate(1e-2);
+		opt.add_callback(move |_data| {step += 1; if step < 5 {CallbackSignal::Continue} else {CallbackSignal::Stop}});
+		opt.optimise(&mut stream, &g)
+	};
+
+	let _lock = GLOBAL_SEED_TEST_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+	set_global_seed(1234);
+	let params1 = train();
+	set_global_seed(1234);
+	let params2 = train();
+	set_global_seed(4321);
+	let params3 = train();
+	clear_global_seed();
+
+	let (params1, params2, params3) = (params1?, params2?, params3?);
+	assert_eq!(params1, params2);
+	assert!(params1 != params3);
+
+	Ok(())
+}
@@ -7,6 +7,7 @@ use std::any::Any;
 
 use id::*;
 use graph::{Dependencies, DataStatus, ErrorKind, Result};
+use ops::OpParam;
 
 enum DataState<T>{
 	Unallocated,
@@ -26,6 +27,8 @@ pub struct Storage<'a> {
 	dependencies: &'a Dependencies,
 
 	loss: Cell<f32>,
+	loss_scale: f32,
+	op_params: IndexMap<usize, f32>,
 	data: IndexMap<DataID, DataState<ArrayD<f32>>>,
 	borrow_flags: IndexMap<DataID, Cell<usize>>,
 	current_pass: Option<PassID>,
@@ -64,6 +67,8 @@ impl<'a> Storage<'a> {
 			dependencies,
 
 			loss: Cell::new(0.0),
+			loss_scale: 1.0,
+			op_params: indexmap![],
 			data: data,
 			borrow_flags: borrow_flags,
 			current_pass: None,
@@ -94,6 +99,13 @@ impl<'a> Storage<'a> {
 		mem::replace(self.data.get_mut(data_id).unwrap(), DataState::Deallocated);
 	}
 
+	/// Returns the data specified by DataID to an unallocated state, so that it is reallocated with zeros when next accessed.
+	///
+	/// Used to discard data which will be recomputed.
+	pub (crate) fn discard(&mut self, data_id: &DataID){
+		mem::replace(self.data.get_mut(data_id).unwrap(), DataState::Unallocated);
+	}
+
 	/// This resets runtime borrow checks, allowing for a new round of borrowing patterns.
 	/// By taking `self` this forces return of all prior borrows.
 	pub fn clear_borrow_flags(mut self) -> Self{
@@ -154,6 +166,26 @@ impl<'a> Storage<'a> {
 		unsafe{*self.loss.as_ptr() += additional_loss;}
 	}
 
+	pub (crate) fn set_loss_scale(&mut self, loss_scale: f32){
+		self.loss_scale = loss_scale;
+	}
+
+	/// The factor by which passes which add to the loss should multiply the gradients they produce, but not the loss added.
+	///
+	/// This is 1.0 unless loss scaling has been enabled using `Subgraph::set_loss_scale()`.
+	pub fn loss_scale(&self) -> f32 {
+		self.loss_scale
+	}
+
+	pub (crate) fn set_op_params(&mut self, op_params: IndexMap<usize, f32>){
+		self.op_params = op_params;
+	}
+
+	/// The current value of an `OpParam`, which is its initial value unless changed by `GraphDef::set_op_param()`.
+	pub fn op_param(&self, param: &OpParam) -> f32 {
+		self.op_params.get(&param.id()).cloned().unwrap_or(param.initial())
+	}
+
 	/// Immutably borrows data element associated with the given ID.
 	/// 
 	/// A Pass may only borrow data which is listed as a input or output dependency.